@@ -102,6 +102,34 @@ struct Cli {
     #[arg(long, value_name = "FORMAT", default_value = "readable")]
     format: String,
 
+    /// Spaces per indentation level in readable output
+    #[arg(long, value_name = "N", default_value_t = 4)]
+    indent_width: usize,
+
+    /// Indent readable output with tabs
+    #[arg(long)]
+    use_tabs: bool,
+
+    /// Wrap long argument lists and table constructors past this many columns
+    #[arg(long, value_name = "COLUMNS")]
+    max_line_width: Option<usize>,
+
+    /// Quote style for string literals (double, single, auto)
+    #[arg(long, value_name = "STYLE", default_value = "double")]
+    quote_style: String,
+
+    /// Carry comments from the source into the generated Lua
+    #[arg(long)]
+    preserve_comments: bool,
+
+    /// Annotate generated Lua with LuaLS/EmmyLua type comments
+    #[arg(long)]
+    annotations: bool,
+
+    /// Write a .d.tl declaration file next to each output
+    #[arg(long)]
+    declaration: bool,
+
     /// Enable aggressive optimizations with whole-program analysis
     #[arg(long)]
     optimize: bool,
@@ -110,10 +138,20 @@ struct Cli {
     #[arg(long)]
     no_optimize: bool,
 
-    /// Enable optimizer profiling (logs pass timings)
+    /// Enable optimizer profiling (prints pass timings per module)
     #[arg(long)]
     profile_optimizer: bool,
 
+    /// Print optimization remarks per module (text, json)
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "text"
+    )]
+    opt_report: Option<String>,
+
     /// Disable parallel optimization (for benchmarking)
     #[arg(long)]
     no_parallel_optimization: bool,
@@ -133,6 +171,33 @@ struct Cli {
     /// Reflection metadata mode (selective, full, none)
     #[arg(long, value_name = "MODE", default_value = "selective")]
     reflection: String,
+
+    /// Runtime parameter validation (off, auto, explicit)
+    #[arg(long, value_name = "MODE")]
+    validation: Option<String>,
+
+    /// How validation failures are reported (fail_fast, collect)
+    #[arg(long, value_name = "MODE")]
+    validation_errors: Option<String>,
+
+    /// Runtime private/protected member checks (debug, off); debug only
+    /// applies to unoptimized builds
+    #[arg(long, value_name = "MODE")]
+    access_checks: Option<String>,
+
+    /// Builds that keep @trace decorators (debug, always, off); debug keeps
+    /// them only in unoptimized builds
+    #[arg(long, value_name = "MODE")]
+    traces: Option<String>,
+
+    /// Instrument generated code to record an execution profile (pgo);
+    /// disables optimizations
+    #[arg(long, value_name = "MODE")]
+    instrument: Option<String>,
+
+    /// Optimize with a profile recorded by an --instrument pgo build
+    #[arg(long, value_name = "FILE")]
+    profile_use: Option<PathBuf>,
 }
 
 fn main() -> anyhow::Result<()> {
@@ -190,11 +255,13 @@ fn main() -> anyhow::Result<()> {
     resolved_cli.no_emit = config.compiler_options.no_emit;
     resolved_cli.pretty = config.compiler_options.pretty;
     resolved_cli.copy_lua_to_output = config.compiler_options.copy_lua_to_output;
+    let project_config = load_project_config(&cli)?;
+    resolved_cli.declaration |= project_config.compiler_options.declaration;
 
     if cli.watch {
-        watch_mode(resolved_cli)?;
+        watch_mode(resolved_cli, &project_config)?;
     } else {
-        compile(resolved_cli, target)?;
+        compile(resolved_cli, target, &project_config)?;
     }
 
     Ok(())
@@ -298,6 +365,56 @@ fn parse_optimization_level(
     }
 }
 
+/// How `--opt-report` prints optimization remarks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OptReportFormat {
+    Text,
+    Json,
+}
+
+/// Parse the `--opt-report` format; `None` when the flag is absent
+fn parse_opt_report(format: Option<&str>) -> anyhow::Result<Option<OptReportFormat>> {
+    match format.map(str::to_lowercase).as_deref() {
+        None => Ok(None),
+        Some("text") => Ok(Some(OptReportFormat::Text)),
+        Some("json") => Ok(Some(OptReportFormat::Json)),
+        Some(other) => Err(anyhow::anyhow!(
+            "Invalid --opt-report format '{}'. Supported formats: text, json",
+            other
+        )),
+    }
+}
+
+/// Parse the `--instrument` mode; off when the flag is absent
+fn parse_instrumentation(
+    mode: Option<&str>,
+) -> anyhow::Result<typedlua_core::codegen::Instrumentation> {
+    use typedlua_core::codegen::Instrumentation;
+
+    match mode.map(str::to_lowercase).as_deref() {
+        None => Ok(Instrumentation::Off),
+        Some("pgo") => Ok(Instrumentation::Pgo),
+        Some(other) => Err(anyhow::anyhow!(
+            "Invalid --instrument mode '{}'. Supported modes: pgo",
+            other
+        )),
+    }
+}
+
+/// Id of `file_path` for keys that must not depend on how the file was named:
+/// the path relative to the project root, after resolving symlinks. Profile
+/// counters and reflection type IDs use it, so `src/a.tl`, `./src/a.tl` and
+/// an absolute path agree, and so do checkouts in different directories.
+fn project_module_id(file_path: &Path, project_root: &Path) -> String {
+    let canonical = |path: &Path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let file = canonical(file_path);
+    let root = canonical(project_root);
+    file.strip_prefix(&root)
+        .unwrap_or(&file)
+        .to_string_lossy()
+        .to_string()
+}
+
 /// Parse the output format string
 fn parse_output_format(format: &str) -> typedlua_core::config::OutputFormat {
     use typedlua_core::config::OutputFormat;
@@ -309,6 +426,128 @@ fn parse_output_format(format: &str) -> typedlua_core::config::OutputFormat {
     }
 }
 
+/// Resolve the validation settings; CLI flags override the config file
+fn parse_validation(
+    cli: &Cli,
+    config: &typedlua_core::project_config::ValidationOptions,
+) -> anyhow::Result<(
+    typedlua_core::codegen::ValidationMode,
+    typedlua_core::codegen::ValidationErrors,
+)> {
+    use typedlua_core::codegen::{ValidationErrors, ValidationMode};
+
+    let mode = match cli.validation.as_deref().map(str::to_lowercase).as_deref() {
+        Some("off") => ValidationMode::Off,
+        Some("auto") => ValidationMode::Auto,
+        Some("explicit") => ValidationMode::Explicit,
+        Some(other) => {
+            return Err(anyhow::anyhow!(
+                "Invalid --validation mode '{}'. Supported modes: off, auto, explicit",
+                other
+            ))
+        }
+        None => config.mode,
+    };
+    let errors = match cli
+        .validation_errors
+        .as_deref()
+        .map(str::to_lowercase)
+        .as_deref()
+    {
+        Some("fail_fast") => ValidationErrors::FailFast,
+        Some("collect") => ValidationErrors::Collect,
+        Some(other) => {
+            return Err(anyhow::anyhow!(
+                "Invalid --validation-errors mode '{}'. Supported modes: fail_fast, collect",
+                other
+            ))
+        }
+        None => config.errors,
+    };
+    Ok((mode, errors))
+}
+
+/// Resolve the access check mode; the CLI flag overrides the config file
+fn parse_access_checks(
+    cli: &Cli,
+    config: &typedlua_core::project_config::EmitOptions,
+) -> anyhow::Result<typedlua_core::codegen::AccessChecks> {
+    use typedlua_core::codegen::AccessChecks;
+
+    match cli
+        .access_checks
+        .as_deref()
+        .map(str::to_lowercase)
+        .as_deref()
+    {
+        Some("debug") => Ok(AccessChecks::Debug),
+        Some("off") => Ok(AccessChecks::Off),
+        Some(other) => Err(anyhow::anyhow!(
+            "Invalid --access-checks mode '{}'. Supported modes: debug, off",
+            other
+        )),
+        None => Ok(config.access_checks),
+    }
+}
+
+/// Resolve which builds keep `@trace`; the CLI flag overrides the config file
+fn parse_traces(
+    cli: &Cli,
+    config: &typedlua_core::project_config::EmitOptions,
+) -> anyhow::Result<typedlua_core::codegen::Traces> {
+    use typedlua_core::codegen::Traces;
+
+    match cli.traces.as_deref().map(str::to_lowercase).as_deref() {
+        Some("debug") => Ok(Traces::Debug),
+        Some("always") => Ok(Traces::Always),
+        Some("off") => Ok(Traces::Off),
+        Some(other) => Err(anyhow::anyhow!(
+            "Invalid --traces mode '{}'. Supported modes: debug, always, off",
+            other
+        )),
+        None => Ok(config.traces),
+    }
+}
+
+/// Build the pretty-printer options from CLI flags
+fn parse_printer_options(cli: &Cli) -> anyhow::Result<typedlua_core::codegen::PrinterOptions> {
+    use typedlua_core::codegen::{PrinterOptions, QuoteStyle};
+
+    let quote_style = match cli.quote_style.to_lowercase().as_str() {
+        "double" => QuoteStyle::Double,
+        "single" => QuoteStyle::Single,
+        "auto" => QuoteStyle::Auto,
+        other => {
+            return Err(anyhow::anyhow!(
+                "Invalid --quote-style '{}'. Supported styles: double, single, auto",
+                other
+            ))
+        }
+    };
+    Ok(PrinterOptions {
+        indent_width: cli.indent_width,
+        use_tabs: cli.use_tabs,
+        max_line_width: cli.max_line_width,
+        quote_style,
+        preserve_comments: cli.preserve_comments,
+    })
+}
+
+/// Load the emit options that `CompilerConfig` does not cover from the same config file
+fn load_project_config(cli: &Cli) -> anyhow::Result<typedlua_core::project_config::ProjectConfig> {
+    use typedlua_core::project_config::ProjectConfig;
+
+    let path = match cli.project {
+        Some(ref project_path) => project_path.clone(),
+        None => PathBuf::from("tlconfig.yaml"),
+    };
+    if !path.exists() {
+        return Ok(ProjectConfig::default());
+    }
+    ProjectConfig::from_file(&path)
+        .map_err(|e| anyhow::anyhow!("Failed to load config file: {}", e))
+}
+
 /// Load configuration from file (if specified) and resolve input files
 fn load_config_and_files(
     cli: &Cli,
@@ -998,6 +1237,10 @@ struct CompilationOutput {
     output_path: PathBuf,
     /// Module to save to cache after compilation (stale files only)
     cache_entry: Option<CacheEntryData>,
+    /// `.d.tl` contents when declaration output is enabled
+    declaration: Option<String>,
+    /// Optimization remarks and pass timings for this module
+    opt_report: typedlua_core::optimizer::OptimizationReport,
 }
 
 struct CompilationError {
@@ -1013,6 +1256,11 @@ struct CheckedModule<'arena> {
     output_path: PathBuf,
     enable_source_map: bool,
     cache_entry: Option<CacheEntryData>,
+    /// Source text, kept only when comments are carried into the output
+    source: Option<String>,
+    /// Exports reported by the type checker with their checked types, kept
+    /// only for declaration output
+    declared_exports: Vec<(String, typedlua_core::codegen::CheckedType)>,
 }
 
 // SAFETY: All fields are Send after StringInterner migration to ThreadedRodeo.
@@ -1062,7 +1310,11 @@ fn parse_single_file<'arena>(
 }
 
 /// Compile the input files
-fn compile(cli: Cli, target: typedlua_core::codegen::LuaTarget) -> anyhow::Result<()> {
+fn compile(
+    cli: Cli,
+    target: typedlua_core::codegen::LuaTarget,
+    project_config: &typedlua_core::project_config::ProjectConfig,
+) -> anyhow::Result<()> {
     use rustc_hash::FxHashSet;
     use std::collections::HashMap;
     use std::sync::Arc;
@@ -1085,6 +1337,20 @@ fn compile(cli: Cli, target: typedlua_core::codegen::LuaTarget) -> anyhow::Resul
     let mut container = typedlua_core::di::DiContainer::production(compiler_config);
     let use_cache = !cli.no_cache;
     let use_incremental_check = !cli.force_full_check;
+    // Flags that change the emitted code are part of the cache key, so
+    // turning instrumentation or a profile on or off, or changing the
+    // profile, recompiles every module
+    let cache_key = format!(
+        "optimize={:?};instrument={:?};profile={}",
+        parse_optimization_level(cli.optimize, cli.no_optimize)?,
+        parse_instrumentation(cli.instrument.as_deref())?,
+        cli.profile_use
+            .as_ref()
+            .map(|path| {
+                typedlua_core::cache::hash_file(path).unwrap_or_else(|_| String::from("unknown"))
+            })
+            .unwrap_or_default()
+    );
 
     // --- Incremental type checking setup ---
     let mut incremental_checker = IncrementalChecker::new();
@@ -1093,8 +1359,9 @@ fn compile(cli: Cli, target: typedlua_core::codegen::LuaTarget) -> anyhow::Resul
 
     if use_cache && use_incremental_check {
         let config = CompilerOptions::default();
-        if let Ok(mut cache_manager) = CacheManager::new(&project_root, &config) {
-            if cache_manager.load_manifest().is_ok() {
+        if let Ok(cache_manager) = CacheManager::new(&project_root, &config) {
+            let mut cache_manager = cache_manager.with_build_key(&cache_key);
+            if cache_manager.load_manifest().is_ok() && cache_manager.is_valid() {
                 if let Some(ref manifest) = cache_manager.manifest {
                     // Load old declaration hashes from cache
                     for (module_path, hashes) in &manifest.declaration_hashes {
@@ -1124,16 +1391,25 @@ fn compile(cli: Cli, target: typedlua_core::codegen::LuaTarget) -> anyhow::Resul
         }
     }
 
+    // Whole-program analysis and cross-module export summaries need the AST
+    // of every module, unchanged or not, so optimized builds type check
+    // everything instead of skipping what the cache marks unchanged
+    let whole_program = parse_optimization_level(cli.optimize, cli.no_optimize)?
+        >= typedlua_core::config::OptimizationLevel::O2
+        && parse_instrumentation(cli.instrument.as_deref())?
+            == typedlua_core::codegen::Instrumentation::Off;
+
     // Determine which files need recompilation
     let stale_files: FxHashSet<PathBuf>;
     let cached_modules: HashMap<PathBuf, CachedModule>;
 
-    if use_cache {
+    if use_cache && !whole_program {
         let config = CompilerOptions::default();
         let mut cache_manager = CacheManager::new(&project_root, &config)
-            .unwrap_or_else(|_| CacheManager::new(Path::new("."), &config).unwrap());
+            .unwrap_or_else(|_| CacheManager::new(Path::new("."), &config).unwrap())
+            .with_build_key(&cache_key);
 
-        if cache_manager.load_manifest().is_err() {
+        if cache_manager.load_manifest().is_err() || !cache_manager.is_valid() {
             let _ = cache_manager.clear();
             let _ = cache_manager.load_manifest();
         }
@@ -1167,7 +1443,7 @@ fn compile(cli: Cli, target: typedlua_core::codegen::LuaTarget) -> anyhow::Resul
             );
         }
     } else {
-        // No cache: everything is stale
+        // No cache, or a whole-program build: everything is stale
         stale_files = cli
             .files
             .iter()
@@ -1411,6 +1687,27 @@ fn compile(cli: Cli, target: typedlua_core::codegen::LuaTarget) -> anyhow::Resul
                 output_path,
                 enable_source_map: cli.source_map || cli.inline_source_map,
                 cache_entry,
+                source: cli
+                    .preserve_comments
+                    .then(|| std::fs::read_to_string(file_path).ok())
+                    .flatten(),
+                declared_exports: if cli.declaration {
+                    exports
+                        .named
+                        .iter()
+                        .map(|(name, exported)| {
+                            (
+                                name.clone(),
+                                typedlua_core::codegen::CheckedType::new(
+                                    &exported.symbol.typ,
+                                    &parsed.interner,
+                                ),
+                            )
+                        })
+                        .collect()
+                } else {
+                    Vec::new()
+                },
             })
             }) // End of with_pooled_arena
         })
@@ -1431,20 +1728,78 @@ fn compile(cli: Cli, target: typedlua_core::codegen::LuaTarget) -> anyhow::Resul
         );
     }
 
-    // --- Phase 1.5: Whole-program analysis (for O3+ optimizations) ---
+    // --- Phase 1.5: Whole-program analysis (for O2+ optimizations) ---
     // Build cross-module analysis before parallel codegen
-    let optimization_level = parse_optimization_level(cli.optimize, cli.no_optimize)?;
+    let mut optimization_level = parse_optimization_level(cli.optimize, cli.no_optimize)?;
+    let opt_report = parse_opt_report(cli.opt_report.as_deref())?;
+    let instrumentation = parse_instrumentation(cli.instrument.as_deref())?;
+    if instrumentation != typedlua_core::codegen::Instrumentation::Off {
+        if cli.profile_use.is_some() {
+            return Err(anyhow::anyhow!(
+                "Cannot use both --instrument and --profile-use flags"
+            ));
+        }
+        // Counters are keyed by source position, so instrumented code keeps
+        // the shape of the source
+        info!("Instrumented build: optimizations disabled");
+        optimization_level = typedlua_core::config::OptimizationLevel::O0;
+    }
+    let profile = match &cli.profile_use {
+        Some(path) => Some(
+            typedlua_core::optimizer::Profile::from_file(path).map_err(anyhow::Error::msg)?,
+        ),
+        None => None,
+    };
     info!("Optimization level: {:?}", optimization_level);
+    // Default builds emit the type-checked AST as-is; the optimizer runs for
+    // --optimize and for the flags that report on or steer it
+    let run_optimizer = optimization_level >= typedlua_core::config::OptimizationLevel::O3
+        || opt_report.is_some()
+        || cli.profile_optimizer
+        || profile.is_some();
     let wpa_start = Instant::now();
+    // Import sources of each module resolved to the module ids its imports
+    // were summarized under, for cross-module inlining
+    let mut module_imports: HashMap<PathBuf, FxHashMap<String, String>> = HashMap::new();
     let whole_program_analysis =
-        if optimization_level >= typedlua_core::config::OptimizationLevel::O3 {
-            info!("Building whole-program analysis for O3 optimizations...");
+        if optimization_level >= typedlua_core::config::OptimizationLevel::O2 {
+            info!("Building whole-program analysis for O2+ optimizations...");
             let ast_refs: Vec<&typedlua_parser::ast::Program> =
                 checked_modules.iter().map(|m| &m.ast).collect();
-            Some(typedlua_core::optimizer::WholeProgramAnalysis::build(
+            let mut analysis = typedlua_core::optimizer::WholeProgramAnalysis::build(
                 &ast_refs,
                 optimization_level,
-            ))
+            );
+            if optimization_level >= typedlua_core::config::OptimizationLevel::O3 {
+                for module in &checked_modules {
+                    let canonical = module
+                        .file_path
+                        .canonicalize()
+                        .unwrap_or_else(|_| module.file_path.clone());
+                    analysis.summarize_exports(
+                        canonical.to_string_lossy().to_string(),
+                        &module.ast,
+                        &module.interner,
+                    );
+                    let imports = module
+                        .ast
+                        .statements
+                        .iter()
+                        .filter_map(|stmt| match stmt {
+                            typedlua_parser::ast::statement::Statement::Import(import) => resolver
+                                .resolve(&import.source, &module.file_path)
+                                .ok()
+                                .map(|dep| {
+                                    let id = dep.path().to_string_lossy().to_string();
+                                    (import.source.clone(), id)
+                                }),
+                            _ => None,
+                        })
+                        .collect();
+                    module_imports.insert(module.file_path.clone(), imports);
+                }
+            }
+            Some(analysis)
         } else {
             None
         };
@@ -1521,6 +1876,32 @@ fn compile(cli: Cli, target: typedlua_core::codegen::LuaTarget) -> anyhow::Resul
         checked_modules
     };
 
+    // Reflection type IDs are stable hashes, so modules restored from the cache
+    // keep theirs; registering the modules generated now settles collisions
+    // between them before parallel codegen
+    let type_ids = {
+        let mut registry = typedlua_core::codegen::TypeIdRegistry::new();
+        let mut ordered: Vec<&CheckedModule> = checked_modules_filtered.iter().collect();
+        ordered.sort_by(|a, b| a.file_path.cmp(&b.file_path));
+        for module in ordered {
+            registry.register_module(
+                &project_module_id(&module.file_path, &project_root),
+                module.ast.statements,
+                &module.interner,
+            );
+        }
+        Arc::new(registry)
+    };
+
+    let validation = parse_validation(&cli, &project_config.validation)?;
+    let access_checks = parse_access_checks(&cli, &project_config.compiler_options)?;
+    let traces = parse_traces(&cli, &project_config.compiler_options)?;
+    let printer_options = parse_printer_options(&cli)?;
+    let optimizer_options = &project_config.optimizer;
+    for name in optimizer_options.unknown_passes() {
+        warn!("Unknown optimizer pass '{}' in tlconfig.yaml", name);
+    }
+
     // --- Phase 2: Parallel code generation ---
     // Each module's codegen is independent - can run in parallel
     let codegen_start = Instant::now();
@@ -1532,8 +1913,25 @@ fn compile(cli: Cli, target: typedlua_core::codegen::LuaTarget) -> anyhow::Resul
             let mut builder = CodeGeneratorBuilder::new(module.interner.clone())
                 .target(target)
                 .output_format(output_format)
+                .printer_options(printer_options.clone())
+                .annotations(cli.annotations)
+                .validation(validation.0, validation.1)
+                .access_checks(access_checks)
+                .traces(traces)
+                .instrument(
+                    instrumentation,
+                    project_module_id(&module.file_path, &project_root),
+                )
+                .type_ids(
+                    type_ids.clone(),
+                    project_module_id(&module.file_path, &project_root),
+                )
                 .optimization_level(optimization_level);
 
+            if let Some(source) = module.source {
+                builder = builder.source_text(source);
+            }
+
             if module.enable_source_map {
                 builder = builder.source_map(module.file_path.to_string_lossy().to_string());
             }
@@ -1553,11 +1951,74 @@ fn compile(cli: Cli, target: typedlua_core::codegen::LuaTarget) -> anyhow::Resul
                 }
             }
 
+            let declaration = cli.declaration.then(|| {
+                typedlua_core::codegen::DeclarationGenerator::new(&module.interner)
+                    .with_checked_exports(module.declared_exports)
+                    .generate(module.ast.statements)
+            });
+
             let mut generator = builder.build();
-            // Convert arena-allocated Program to mutable AST for codegen
+            // Convert arena-allocated Program to mutable AST for optimization and codegen
+            let arena = bumpalo::Bump::new();
             let mut mutable_ast = typedlua_core::MutableProgram::from_program(&module.ast);
+
+            let report = if run_optimizer {
+                let mut optimizer = typedlua_core::optimizer::Optimizer::new(
+                    optimization_level,
+                    Arc::new(CollectingDiagnosticHandler::new()),
+                    module.interner.clone(),
+                )
+                .with_options(optimizer_options.clone())
+                .with_target(target);
+                if opt_report.is_some() {
+                    optimizer.enable_remarks();
+                }
+                if let Some(ref analysis) = whole_program_analysis {
+                    optimizer.set_whole_program_analysis(analysis.clone());
+                }
+                if let Some(imports) = module_imports.get(&module.file_path) {
+                    optimizer.set_module_imports(imports.clone());
+                }
+                if let Some(module_profile) = profile
+                    .as_ref()
+                    .and_then(|profile| {
+                        profile.module(&project_module_id(&module.file_path, &project_root))
+                    })
+                {
+                    optimizer.set_profile(module_profile);
+                }
+                // Modules that failed type checking were skipped above
+                optimizer.set_type_facts(typedlua_core::optimizer::TypeFacts::from_checked_program(
+                    &mutable_ast,
+                    &module.interner,
+                    &arena,
+                ));
+                if let Err(err) = optimizer.optimize(&mut mutable_ast, &arena) {
+                    return CompilationResult {
+                        file_path: module.file_path,
+                        result: Err(CompilationError {
+                            diagnostics: Vec::new(),
+                            source: format!("Optimization failed: {}", err),
+                        }),
+                    };
+                }
+                optimizer.take_report()
+            } else {
+                typedlua_core::optimizer::OptimizationReport::default()
+            };
+
             let lua_code = generator.generate(&mut mutable_ast);
             let source_map = generator.take_source_map();
+            let codegen_errors = generator.take_errors();
+            if !codegen_errors.is_empty() {
+                return CompilationResult {
+                    file_path: module.file_path,
+                    result: Err(CompilationError {
+                        diagnostics: Vec::new(),
+                        source: codegen_errors.join("; "),
+                    }),
+                };
+            }
 
             CompilationResult {
                 file_path: module.file_path,
@@ -1566,6 +2027,8 @@ fn compile(cli: Cli, target: typedlua_core::codegen::LuaTarget) -> anyhow::Resul
                     source_map,
                     output_path: module.output_path,
                     cache_entry: module.cache_entry,
+                    declaration,
+                    opt_report: report,
                 }),
             }
         })
@@ -1585,8 +2048,9 @@ fn compile(cli: Cli, target: typedlua_core::codegen::LuaTarget) -> anyhow::Resul
     // --- Phase 3: Save cache entries (sequential — CacheManager needs &mut self) ---
     if use_cache {
         let config = CompilerOptions::default();
-        if let Ok(mut cache_manager) = CacheManager::new(&project_root, &config) {
-            if cache_manager.load_manifest().is_err() {
+        if let Ok(cache_manager) = CacheManager::new(&project_root, &config) {
+            let mut cache_manager = cache_manager.with_build_key(&cache_key);
+            if cache_manager.load_manifest().is_err() || !cache_manager.is_valid() {
                 let _ = cache_manager.clear();
                 let _ = cache_manager.load_manifest();
             }
@@ -1622,10 +2086,27 @@ fn compile(cli: Cli, target: typedlua_core::codegen::LuaTarget) -> anyhow::Resul
 
     // Collect bundled output if --out-file is specified
     let mut bundled_code = String::new();
+    let mut json_reports = Vec::new();
 
     for result in &results {
         match &result.result {
             Ok(output) => {
+                let module_name = result.file_path.to_string_lossy();
+                match opt_report {
+                    Some(OptReportFormat::Json) => json_reports.push(
+                        output
+                            .opt_report
+                            .to_json(&module_name, cli.profile_optimizer),
+                    ),
+                    Some(OptReportFormat::Text) => {
+                        eprint!("{}", output.opt_report.remarks_to_text(&module_name))
+                    }
+                    None => {}
+                }
+                if cli.profile_optimizer && opt_report != Some(OptReportFormat::Json) {
+                    eprint!("{}", output.opt_report.timings_to_text(&module_name));
+                }
+
                 if !cli.no_emit {
                     if cli.out_file.is_some() {
                         // Bundling mode: accumulate code
@@ -1662,6 +2143,21 @@ fn compile(cli: Cli, target: typedlua_core::codegen::LuaTarget) -> anyhow::Resul
                             }
                         }
                     }
+
+                    if let Some(ref declaration) = output.declaration {
+                        // A bundle has one output file, so declarations go beside it per module
+                        let declaration_path = match cli.out_file {
+                            Some(ref out_file) => out_file
+                                .with_file_name(result.file_path.file_name().unwrap_or_default())
+                                .with_extension("d.tl"),
+                            None => output.output_path.with_extension("d.tl"),
+                        };
+                        if let Some(parent) = declaration_path.parent() {
+                            std::fs::create_dir_all(parent)?;
+                        }
+                        std::fs::write(&declaration_path, declaration)?;
+                        info!("Generated declaration: {:?}", declaration_path);
+                    }
                 }
             }
             Err(error) => {
@@ -1683,6 +2179,10 @@ fn compile(cli: Cli, target: typedlua_core::codegen::LuaTarget) -> anyhow::Resul
         }
     }
 
+    if opt_report == Some(OptReportFormat::Json) {
+        eprintln!("{}", serde_json::Value::Array(json_reports));
+    }
+
     // Write bundled output if specified
     if !cli.no_emit && cli.out_file.is_some() {
         let out_file = cli.out_file.as_ref().unwrap();
@@ -1862,7 +2362,10 @@ fn print_diagnostics_from_vec(
 }
 
 /// Watch mode - recompile on file changes
-fn watch_mode(cli: Cli) -> anyhow::Result<()> {
+fn watch_mode(
+    cli: Cli,
+    project_config: &typedlua_core::project_config::ProjectConfig,
+) -> anyhow::Result<()> {
     use notify::{
         event::{EventKind, ModifyKind},
         Event, RecursiveMode, Watcher,
@@ -1876,7 +2379,7 @@ fn watch_mode(cli: Cli) -> anyhow::Result<()> {
 
     // Initial compilation
     println!("\nInitial compilation:");
-    let _ = compile(cli.clone(), target);
+    let _ = compile(cli.clone(), target, project_config);
 
     // Create a channel to receive file system events
     let (tx, rx) = channel();
@@ -1923,7 +2426,7 @@ fn watch_mode(cli: Cli) -> anyhow::Result<()> {
                         let now = std::time::Instant::now();
                         if now.duration_since(last_compile) >= debounce_duration {
                             println!("\n\nFile changed, recompiling...");
-                            let _ = compile(cli.clone(), target);
+                            let _ = compile(cli.clone(), target, project_config);
                             last_compile = now;
                         }
                     }
@@ -115,6 +115,73 @@ fn test_invalid_lua_target_falls_back() {
         .success();
 }
 
+/// Test an unknown --quote-style is rejected
+#[test]
+fn test_quote_style_invalid() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_file = temp_dir.path().join("main.tl");
+    fs::write(&input_file, "const x: string = \"hi\"").unwrap();
+
+    typedlua_cmd()
+        .arg(&input_file)
+        .arg("--quote-style")
+        .arg("backtick")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Invalid --quote-style 'backtick'. Supported styles: double, single, auto",
+        ));
+}
+
+/// Test --traces always keeps @trace in the default optimized build
+#[test]
+fn test_traces_always_keeps_trace_when_optimized() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_file = temp_dir.path().join("service.tl");
+    let output_file = temp_dir.path().join("service.lua");
+    fs::write(
+        &input_file,
+        "class Service {\n    @trace\n    public run(): void\n    end\n}\n",
+    )
+    .unwrap();
+
+    typedlua_cmd()
+        .arg(&input_file)
+        .arg("--no-cache")
+        .assert()
+        .success();
+    let output = fs::read_to_string(&output_file).unwrap();
+    assert!(
+        !output.contains("__decorate(Service, Service.run"),
+        "default build drops traces: {}",
+        output
+    );
+
+    typedlua_cmd()
+        .arg(&input_file)
+        .arg("--no-cache")
+        .arg("--traces")
+        .arg("always")
+        .assert()
+        .success();
+    let output = fs::read_to_string(&output_file).unwrap();
+    assert!(
+        output.contains("Service.run = __decorate(Service, Service.run, { trace }"),
+        "--traces always keeps them: {}",
+        output
+    );
+
+    typedlua_cmd()
+        .arg(&input_file)
+        .arg("--traces")
+        .arg("verbose")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Invalid --traces mode 'verbose'. Supported modes: debug, always, off",
+        ));
+}
+
 /// Test lexer errors are reported properly
 #[test]
 fn test_lexer_error_reporting() {
@@ -249,6 +316,43 @@ fn test_inline_source_map() {
     );
 }
 
+/// Test type IDs and registry keys do not depend on how the input path is spelled
+#[test]
+fn test_type_ids_match_across_path_spellings() {
+    let temp_dir = TempDir::new().unwrap();
+    let src_dir = temp_dir.path().join("src");
+    fs::create_dir(&src_dir).unwrap();
+    let input_file = src_dir.join("zoo.tl");
+    let output_file = src_dir.join("zoo.lua");
+    fs::write(
+        &input_file,
+        "class Animal {}\nclass Dog extends Animal {}\n",
+    )
+    .unwrap();
+
+    let type_lines = |path: &std::path::Path| {
+        typedlua_cmd()
+            .current_dir(&temp_dir)
+            .arg(path)
+            .arg("--reflection")
+            .arg("full")
+            .arg("--no-cache")
+            .assert()
+            .success();
+        fs::read_to_string(&output_file)
+            .unwrap()
+            .lines()
+            .filter(|line| line.contains("__typeId =") || line.contains("__TypeRegistry["))
+            .map(str::to_string)
+            .collect::<Vec<_>>()
+    };
+
+    let relative = type_lines(std::path::Path::new("src/zoo.tl"));
+    assert!(!relative.is_empty(), "no type IDs emitted");
+    assert_eq!(relative, type_lines(std::path::Path::new("./src/zoo.tl")));
+    assert_eq!(relative, type_lines(&input_file));
+}
+
 // ============================================================================
 // PARALLEL COMPILATION TESTS
 // ============================================================================
@@ -441,3 +545,153 @@ fn test_file_with_spaces() {
 
     typedlua_cmd().arg(&input_file).assert().success();
 }
+
+// ============================================================================
+// OPTIMIZATION REPORT TESTS
+// ============================================================================
+
+const DEVIRTUALIZABLE: &str = r#"
+class Dog {
+    speak(): string {
+        return "woof"
+    }
+}
+
+const d = new Dog()
+const s = d:speak()
+"#;
+
+/// Test --opt-report prints text remarks naming the module and pass
+#[test]
+fn test_opt_report_text() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_file = temp_dir.path().join("dog.tl");
+    fs::write(&input_file, DEVIRTUALIZABLE).unwrap();
+
+    typedlua_cmd()
+        .arg(&input_file)
+        .arg("--optimize")
+        .arg("--opt-report")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "applied: devirtualized `:speak()` to `Dog.speak` [devirtualization]",
+        ));
+}
+
+/// Test --opt-report=json with --profile-optimizer includes pass timings
+#[test]
+fn test_opt_report_json_with_timings() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_file = temp_dir.path().join("dog.tl");
+    fs::write(&input_file, DEVIRTUALIZABLE).unwrap();
+
+    typedlua_cmd()
+        .arg(&input_file)
+        .arg("--optimize")
+        .arg("--opt-report=json")
+        .arg("--profile-optimizer")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("\"pass\":\"devirtualization\""))
+        .stderr(predicate::str::contains("\"timings\""));
+}
+
+/// Test an unknown --opt-report format is rejected
+#[test]
+fn test_opt_report_invalid_format() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_file = temp_dir.path().join("dog.tl");
+    fs::write(&input_file, DEVIRTUALIZABLE).unwrap();
+
+    typedlua_cmd()
+        .arg(&input_file)
+        .arg("--opt-report=yaml")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid --opt-report format"));
+}
+
+// ============================================================================
+// PROFILE-GUIDED OPTIMIZATION TESTS
+// ============================================================================
+
+/// Test --instrument pgo embeds the profile runtime and counters
+#[test]
+fn test_instrument_pgo_emits_counters() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_file = temp_dir.path().join("main.tl");
+    let output_file = temp_dir.path().join("main.lua");
+    fs::write(
+        &input_file,
+        "function twice(x: number): number\n    return x * 2\nend\n",
+    )
+    .unwrap();
+
+    typedlua_cmd()
+        .arg(&input_file)
+        .arg("--instrument")
+        .arg("pgo")
+        .assert()
+        .success();
+
+    let output = fs::read_to_string(&output_file).unwrap();
+    assert!(output.contains("__typedlua_pgo"), "{}", output);
+    assert!(output.contains(":function:twice\"]"), "{}", output);
+}
+
+/// Test an unknown --instrument mode is rejected
+#[test]
+fn test_instrument_invalid_mode() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_file = temp_dir.path().join("main.tl");
+    fs::write(&input_file, "const x: number = 42").unwrap();
+
+    typedlua_cmd()
+        .arg(&input_file)
+        .arg("--instrument")
+        .arg("coverage")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid --instrument mode"));
+}
+
+/// Test --instrument and --profile-use cannot be combined
+#[test]
+fn test_instrument_with_profile_use_rejected() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_file = temp_dir.path().join("main.tl");
+    let profile_file = temp_dir.path().join("typedlua.profile");
+    fs::write(&input_file, "const x: number = 42").unwrap();
+    fs::write(&profile_file, "").unwrap();
+
+    typedlua_cmd()
+        .arg(&input_file)
+        .arg("--instrument")
+        .arg("pgo")
+        .arg("--profile-use")
+        .arg(&profile_file)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Cannot use both --instrument and --profile-use",
+        ));
+}
+
+/// Test a malformed --profile-use file is reported
+#[test]
+fn test_profile_use_malformed_profile() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_file = temp_dir.path().join("main.tl");
+    let profile_file = temp_dir.path().join("typedlua.profile");
+    fs::write(&input_file, "const x: number = 42").unwrap();
+    fs::write(&profile_file, "not a profile\n").unwrap();
+
+    typedlua_cmd()
+        .arg(&input_file)
+        .arg("--profile-use")
+        .arg(&profile_file)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid profile entry"));
+}
@@ -48,6 +48,15 @@ impl CacheManager {
         })
     }
 
+    /// Fold build settings that change the emitted code but are not part of
+    /// `CompilerOptions` (command-line flags, a profile's contents) into the
+    /// config hash, so a cache written under other settings is not valid
+    pub fn with_build_key(mut self, key: &str) -> Self {
+        let combined = format!("{}\n{}", self.config_hash, key);
+        self.config_hash = blake3::hash(combined.as_bytes()).to_hex().to_string();
+        self
+    }
+
     /// Initialize cache directories
     fn ensure_cache_dirs(&self) -> Result<()> {
         std::fs::create_dir_all(&self.cache_dir)?;
@@ -325,4 +334,28 @@ mod tests {
         assert!(manager.manifest.is_some());
         assert!(manager.cache_dir.exists());
     }
+
+    #[test]
+    fn test_build_key_invalidates_other_builds() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = CompilerOptions::default();
+
+        let mut plain = CacheManager::new(temp_dir.path(), &config)
+            .unwrap()
+            .with_build_key("instrument=Off");
+        plain.load_manifest().unwrap();
+        plain.save_manifest().unwrap();
+
+        let mut instrumented = CacheManager::new(temp_dir.path(), &config)
+            .unwrap()
+            .with_build_key("instrument=Pgo");
+        instrumented.load_manifest().unwrap();
+        assert!(!instrumented.is_valid());
+
+        let mut again = CacheManager::new(temp_dir.path(), &config)
+            .unwrap()
+            .with_build_key("instrument=Off");
+        again.load_manifest().unwrap();
+        assert!(again.is_valid());
+    }
 }
@@ -0,0 +1,202 @@
+//! Runtime enforcement of `private` and `protected` instance members.
+//!
+//! With [`AccessChecks::Debug`], debug (O0) builds embed
+//! `typedlua_runtime::class::ACCESS_CHECKS`, give every class with
+//! non-public instance members a `__access` table of their reflection flags,
+//! and wrap instances in `new` with `__index`/`__newindex` guards that look
+//! up the calling class through `debug.getinfo`. Optimized builds emit none
+//! of this, so release code pays nothing for it.
+
+use super::{CodeGenerator, OptimizationLevel};
+use serde::{Deserialize, Serialize};
+use typedlua_parser::ast::statement::*;
+
+/// Whether member access modifiers are checked at runtime
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccessChecks {
+    /// Guard instances in debug (O0) builds
+    Debug,
+    /// No runtime checks (default)
+    #[default]
+    Off,
+}
+
+impl CodeGenerator {
+    /// Access checks are requested and this is a debug build
+    pub(crate) fn access_checks_enabled(&self) -> bool {
+        self.enforce_access_modifiers
+            && self.optimization_level.effective() == OptimizationLevel::O0
+    }
+
+    pub(crate) fn embed_access_checks(&mut self) {
+        self.writeln(typedlua_runtime::class::ACCESS_CHECKS);
+    }
+
+    /// Whether any class in `statements` has guarded members
+    pub(crate) fn needs_access_checks(&self, statements: &[Statement]) -> bool {
+        statements.iter().any(|statement| match statement {
+            Statement::Class(class_decl) => self.access_flags(class_decl).is_some(),
+            Statement::Export(export) => match &export.kind {
+                ExportKind::Declaration(decl) => {
+                    self.needs_access_checks(std::slice::from_ref(&**decl))
+                }
+                _ => false,
+            },
+            _ => false,
+        })
+    }
+
+    /// Reflection flags of the class's non-public instance members
+    fn access_flags(&self, class_decl: &ClassDeclaration) -> Option<Vec<(String, u8)>> {
+        let members: Vec<(String, u8)> = class_decl
+            .members
+            .iter()
+            .filter_map(|member| match member {
+                ClassMember::Property(prop) if !prop.is_static => {
+                    Some((self.resolve(prop.name.node), Self::encode_field_flags(prop)))
+                }
+                ClassMember::Method(method) if !method.is_static => Some((
+                    self.resolve(method.name.node),
+                    Self::encode_access_flags(method.access.as_ref(), false, false),
+                )),
+                _ => None,
+            })
+            .filter(|(_, flags)| flags & 1 == 0)
+            .collect();
+        (!members.is_empty()).then_some(members)
+    }
+
+    /// `Class.__access = { name = flags, ... }` for the class's guarded members
+    pub(crate) fn write_access_table(&mut self, class_name: &str, class_decl: &ClassDeclaration) {
+        if !self.uses_access_checks {
+            return;
+        }
+        let Some(members) = self.access_flags(class_decl) else {
+            return;
+        };
+        let entries: Vec<String> = members
+            .iter()
+            .map(|(name, flags)| format!("{} = {}", name, flags))
+            .collect();
+        self.write_indent();
+        self.writeln(&format!(
+            "{}.__access = {{ {} }}",
+            class_name,
+            entries.join(", ")
+        ));
+    }
+
+    /// Register the class's functions as its own code, once all members exist
+    pub(crate) fn write_access_registration(&mut self, class_name: &str) {
+        if self.uses_access_checks {
+            self.write_indent();
+            self.writeln(&format!("__accessRegister({})", class_name));
+        }
+    }
+
+    /// Register a method before a decorator replaces it, so the original
+    /// body still counts as code of the class
+    pub(crate) fn write_access_owner(&mut self, class_name: &str, target: &str) {
+        if self.uses_access_checks {
+            self.write_indent();
+            self.writeln(&format!("__accessOwners[{}] = {}", target, class_name));
+        }
+    }
+
+    /// `self = __guardInstance(Class, self)` right after the instance is created
+    pub(crate) fn write_access_guard(&mut self, class_name: &str) {
+        if self.uses_access_checks {
+            self.write_indent();
+            self.writeln(&format!("self = __guardInstance({}, self)", class_name));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MutableProgram;
+    use bumpalo::Bump;
+    use std::sync::Arc;
+    use typedlua_parser::diagnostics::CollectingDiagnosticHandler;
+    use typedlua_parser::lexer::Lexer;
+    use typedlua_parser::parser::Parser;
+    use typedlua_parser::string_interner::StringInterner;
+
+    fn generate(source: &str, enforce: bool, level: OptimizationLevel) -> String {
+        let handler = Arc::new(CollectingDiagnosticHandler::new());
+        let (interner, common) = StringInterner::new_with_common_identifiers();
+        let interner = Arc::new(interner);
+        let arena = Bump::new();
+        let mut lexer = Lexer::new(source, handler.clone(), &interner);
+        let tokens = lexer.tokenize().expect("Lexing failed");
+        let mut parser = Parser::new(tokens, handler, &interner, &common, &arena);
+        let program = parser.parse().expect("Parsing failed");
+        let mutable = MutableProgram::from_program(&program);
+
+        let mut generator = CodeGenerator::new(interner.clone())
+            .with_optimization_level(level)
+            .with_enforce_access_modifiers(enforce);
+        generator.generate(&mutable)
+    }
+
+    const ACCOUNT: &str = r#"
+        class Account {
+            private balance: number
+            protected owner: string
+            public id: number
+
+            constructor(owner: string) {
+                self.owner = owner
+                self.balance = 0
+            }
+
+            private audit(): void {
+            }
+        }
+    "#;
+
+    #[test]
+    fn test_debug_build_guards_instances() {
+        let output = generate(ACCOUNT, true, OptimizationLevel::O0);
+        assert!(
+            output.contains("Account.__access = { balance = 2, owner = 4, audit = 2 }"),
+            "{}",
+            output
+        );
+        assert!(
+            output.contains("self = __guardInstance(Account, self)"),
+            "{}",
+            output
+        );
+        assert!(output.contains("__accessRegister(Account)"), "{}", output);
+        assert!(
+            output.contains("local function __guardInstance"),
+            "{}",
+            output
+        );
+    }
+
+    #[test]
+    fn test_release_build_has_no_checks() {
+        let output = generate(ACCOUNT, true, OptimizationLevel::O2);
+        assert!(!output.contains("__access"), "{}", output);
+        assert!(!output.contains("__guardInstance"), "{}", output);
+    }
+
+    #[test]
+    fn test_off_and_public_only_classes_have_no_checks() {
+        let output = generate(ACCOUNT, false, OptimizationLevel::O0);
+        assert!(!output.contains("__guardInstance"), "{}", output);
+
+        let public_only = r#"
+            class Point {
+                x: number
+                public y: number
+            }
+        "#;
+        let output = generate(public_only, true, OptimizationLevel::O0);
+        assert!(!output.contains("__guardInstance"), "{}", output);
+    }
+}
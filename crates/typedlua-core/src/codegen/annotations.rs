@@ -0,0 +1,414 @@
+//! LuaLS / EmmyLua annotations (`---@class`, `---@param`, ...) derived from
+//! TypedLua declarations, so plain-Lua consumers keep type information.
+
+use super::CodeGenerator;
+use typedlua_parser::ast::expression::Literal;
+use typedlua_parser::ast::pattern::Pattern;
+use typedlua_parser::ast::statement::*;
+use typedlua_parser::ast::types::{
+    IndexKeyType, ObjectTypeMember, PrimitiveType, Type, TypeKind, TypeParameter,
+};
+
+impl CodeGenerator {
+    pub fn with_annotations(mut self, enabled: bool) -> Self {
+        self.emit_annotations = enabled;
+        self
+    }
+
+    /// Annotations are pointless in minified output, which drops comments
+    fn annotations_enabled(&self) -> bool {
+        self.emit_annotations && !self.emitter.is_minified()
+    }
+
+    fn write_annotation(&mut self, annotation: &str) {
+        self.write_indent();
+        self.write("---@");
+        self.writeln(annotation);
+    }
+
+    /// Render a TypedLua type in LuaLS type syntax
+    pub(crate) fn lua_type(&self, ty: &Type) -> String {
+        match &ty.kind {
+            TypeKind::Primitive(p) => match p {
+                PrimitiveType::Nil | PrimitiveType::Void => "nil",
+                PrimitiveType::Boolean => "boolean",
+                PrimitiveType::Number => "number",
+                PrimitiveType::Integer => "integer",
+                PrimitiveType::String => "string",
+                PrimitiveType::Table => "table",
+                PrimitiveType::Coroutine => "thread",
+                _ => "any",
+            }
+            .to_string(),
+            TypeKind::Reference(type_ref) => {
+                let name = self.resolve(type_ref.name.node);
+                let args: Vec<String> = type_ref
+                    .type_arguments
+                    .iter()
+                    .flat_map(|args| args.iter())
+                    .map(|arg| self.lua_type(arg))
+                    .collect();
+                match (name.as_str(), args.as_slice()) {
+                    ("Array", [element]) => Self::array_of(element),
+                    ("Record" | "Map", [key, value]) => format!("table<{}, {}>", key, value),
+                    (_, []) => name,
+                    _ => format!("{}<{}>", name, args.join(", ")),
+                }
+            }
+            TypeKind::Union(types) => types
+                .iter()
+                .map(|t| self.lua_type(t))
+                .collect::<Vec<_>>()
+                .join("|"),
+            // LuaLS has no intersections; the first member is the closest fit
+            TypeKind::Intersection(types) => types
+                .first()
+                .map_or_else(|| "table".to_string(), |t| self.lua_type(t)),
+            TypeKind::Object(object) => {
+                let fields: Vec<String> = object
+                    .members
+                    .iter()
+                    .map(|member| match member {
+                        ObjectTypeMember::Property(prop) => format!(
+                            "{}{}: {}",
+                            self.resolve(prop.name.node),
+                            if prop.is_optional { "?" } else { "" },
+                            self.lua_type(&prop.type_annotation)
+                        ),
+                        ObjectTypeMember::Method(method) => format!(
+                            "{}: {}",
+                            self.resolve(method.name.node),
+                            self.lua_function_type(&method.parameters, Some(&method.return_type))
+                        ),
+                        ObjectTypeMember::Index(index) => format!(
+                            "[{}]: {}",
+                            Self::index_key_type(&index.key_type),
+                            self.lua_type(&index.value_type)
+                        ),
+                    })
+                    .collect();
+                format!("{{ {} }}", fields.join(", "))
+            }
+            TypeKind::Array(inner) => Self::array_of(&self.lua_type(inner)),
+            TypeKind::Tuple(types) => format!(
+                "[{}]",
+                types
+                    .iter()
+                    .map(|t| self.lua_type(t))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            TypeKind::Function(func) => {
+                self.lua_function_type(&func.parameters, Some(&func.return_type))
+            }
+            TypeKind::Literal(literal) => match literal {
+                Literal::Nil => "nil".to_string(),
+                Literal::Boolean(b) => b.to_string(),
+                Literal::Number(n) => n.to_string(),
+                Literal::Integer(i) => i.to_string(),
+                Literal::String(s) => format!("\"{}\"", s),
+            },
+            TypeKind::Nullable(inner) => format!("{}|nil", self.lua_type(inner)),
+            TypeKind::Parenthesized(inner) => self.lua_type(inner),
+            _ => "any".to_string(),
+        }
+    }
+
+    fn array_of(element: &str) -> String {
+        if element.contains('|') || element.starts_with("fun(") {
+            format!("({})[]", element)
+        } else {
+            format!("{}[]", element)
+        }
+    }
+
+    fn index_key_type(key_type: &IndexKeyType) -> &'static str {
+        match key_type {
+            IndexKeyType::String => "string",
+            IndexKeyType::Number => "number",
+        }
+    }
+
+    fn lua_function_type(&self, parameters: &[Parameter], return_type: Option<&Type>) -> String {
+        let params: Vec<String> = parameters
+            .iter()
+            .enumerate()
+            .map(|(i, param)| {
+                let (name, ty) = self.param_annotation(i, param);
+                format!("{}: {}", name, ty)
+            })
+            .collect();
+        match return_type.map(|ty| self.lua_type(ty)) {
+            Some(ret) if ret != "nil" => format!("fun({}): {}", params.join(", "), ret),
+            _ => format!("fun({})", params.join(", ")),
+        }
+    }
+
+    /// Name and type of a parameter as LuaLS spells them (`name?`, `...`)
+    fn param_annotation(&self, index: usize, param: &Parameter) -> (String, String) {
+        let ty = param.type_annotation.as_ref();
+        if param.is_rest {
+            let element = match ty.map(|t| &t.kind) {
+                Some(TypeKind::Array(inner)) => self.lua_type(inner),
+                _ => ty.map_or_else(|| "any".to_string(), |t| self.lua_type(t)),
+            };
+            return ("...".to_string(), element);
+        }
+        let name = match &param.pattern {
+            Pattern::Identifier(ident) => self.resolve(ident.node),
+            _ => format!("p{}", index + 1),
+        };
+        let optional = if param.is_optional || param.default.is_some() {
+            "?"
+        } else {
+            ""
+        };
+        let ty = ty.map_or_else(|| "any".to_string(), |t| self.lua_type(t));
+        (format!("{}{}", name, optional), ty)
+    }
+
+    fn annotate_generics(&mut self, type_parameters: Option<&[TypeParameter]>) {
+        for param in type_parameters.into_iter().flatten() {
+            let name = self.resolve(param.name.node);
+            match &param.constraint {
+                Some(constraint) => {
+                    let constraint = self.lua_type(constraint);
+                    self.write_annotation(&format!("generic {} : {}", name, constraint));
+                }
+                None => self.write_annotation(&format!("generic {}", name)),
+            }
+        }
+    }
+
+    /// `---@param` / `---@return` lines for a function-like declaration.
+    /// Destructured parameters are skipped, since the generated Lua does not
+    /// bind them under a single name.
+    fn annotate_signature(&mut self, parameters: &[Parameter], return_type: Option<&Type>) {
+        for (i, param) in parameters.iter().enumerate() {
+            if !param.is_rest && !matches!(param.pattern, Pattern::Identifier(_)) {
+                continue;
+            }
+            let (name, ty) = self.param_annotation(i, param);
+            self.write_annotation(&format!("param {} {}", name, ty));
+        }
+        if let Some(ret) = return_type.map(|ty| self.lua_type(ty)) {
+            if ret != "nil" {
+                self.write_annotation(&format!("return {}", ret));
+            }
+        }
+    }
+
+    pub(crate) fn annotate_function(&mut self, decl: &FunctionDeclaration) {
+        if !self.annotations_enabled() {
+            return;
+        }
+        self.annotate_generics(decl.type_parameters.as_deref());
+        self.annotate_signature(&decl.parameters, decl.return_type.as_ref());
+    }
+
+    pub(crate) fn annotate_method(&mut self, method: &MethodDeclaration) {
+        if !self.annotations_enabled() {
+            return;
+        }
+        self.annotate_generics(method.type_parameters.as_deref());
+        self.annotate_signature(&method.parameters, method.return_type.as_ref());
+    }
+
+    /// `---@param` lines for the constructor's parameters and `---@return` the
+    /// class, above `Class.new`
+    pub(crate) fn annotate_constructor(&mut self, class_name: &str, parameters: &[Parameter]) {
+        if !self.annotations_enabled() {
+            return;
+        }
+        self.annotate_signature(parameters, None);
+        self.write_annotation(&format!("return {}", class_name));
+    }
+
+    pub(crate) fn annotate_primary_constructor(
+        &mut self,
+        class_decl: &ClassDeclaration,
+        class_name: &str,
+    ) {
+        if !self.annotations_enabled() {
+            return;
+        }
+        for param in class_decl.primary_constructor.into_iter().flatten() {
+            let name = self.resolve(param.name.node);
+            let ty = self.lua_type(&param.type_annotation);
+            self.write_annotation(&format!("param {} {}", name, ty));
+        }
+        self.write_annotation(&format!("return {}", class_name));
+    }
+
+    pub(crate) fn annotate_getter(&mut self, getter: &GetterDeclaration) {
+        if !self.annotations_enabled() {
+            return;
+        }
+        let ret = self.lua_type(&getter.return_type);
+        self.write_annotation(&format!("return {}", ret));
+    }
+
+    pub(crate) fn annotate_setter(&mut self, setter: &SetterDeclaration) {
+        if !self.annotations_enabled() {
+            return;
+        }
+        self.annotate_signature(std::slice::from_ref(&setter.parameter), None);
+    }
+
+    fn access_prefix(access: Option<&AccessModifier>) -> &'static str {
+        match access {
+            Some(AccessModifier::Private) => "private ",
+            Some(AccessModifier::Protected) => "protected ",
+            Some(AccessModifier::Public) | None => "",
+        }
+    }
+
+    /// `---@class Name<T> : Parent, Interface` header
+    fn class_header(
+        &self,
+        name: &str,
+        type_parameters: Option<&[TypeParameter]>,
+        parents: &[String],
+    ) -> String {
+        let mut header = format!("class {}", name);
+        let generics: Vec<String> = type_parameters
+            .into_iter()
+            .flatten()
+            .map(|p| self.resolve(p.name.node))
+            .collect();
+        if !generics.is_empty() {
+            header.push_str(&format!("<{}>", generics.join(", ")));
+        }
+        if !parents.is_empty() {
+            header.push_str(&format!(" : {}", parents.join(", ")));
+        }
+        header
+    }
+
+    pub(crate) fn annotate_class(&mut self, class_decl: &ClassDeclaration, class_name: &str) {
+        if !self.annotations_enabled() {
+            return;
+        }
+        let parents: Vec<String> = class_decl
+            .extends
+            .iter()
+            .chain(class_decl.implements.iter())
+            .map(|ty| self.lua_type(ty))
+            .collect();
+        let header = self.class_header(class_name, class_decl.type_parameters.as_deref(), &parents);
+        self.write_annotation(&header);
+
+        for member in class_decl.members.iter() {
+            match member {
+                ClassMember::Property(prop) if !prop.is_static => {
+                    let name = self.resolve(prop.name.node);
+                    let ty = self.lua_type(&prop.type_annotation);
+                    let access = Self::access_prefix(prop.access.as_ref());
+                    self.write_annotation(&format!("field {}{} {}", access, name, ty));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Static properties belong to the class table, not to instances, so they
+    /// are declared on it: a typed `Class.name = nil` that assigns nothing
+    pub(crate) fn annotate_static_fields(
+        &mut self,
+        class_decl: &ClassDeclaration,
+        class_name: &str,
+    ) {
+        if !self.annotations_enabled() {
+            return;
+        }
+        for member in class_decl.members.iter() {
+            match member {
+                ClassMember::Property(prop) if prop.is_static => {
+                    let name = self.resolve(prop.name.node);
+                    let ty = self.lua_type(&prop.type_annotation);
+                    self.write_annotation(&format!("type {}", ty));
+                    self.write_indent();
+                    self.writeln(&format!("{}.{} = nil", class_name, name));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Interfaces produce no code, so their annotation block stands alone
+    pub(crate) fn annotate_interface(&mut self, iface_decl: &InterfaceDeclaration) {
+        if !self.annotations_enabled() {
+            return;
+        }
+        let name = self.resolve(iface_decl.name.node);
+        let parents: Vec<String> = iface_decl
+            .extends
+            .iter()
+            .map(|ty| self.lua_type(ty))
+            .collect();
+        let header = self.class_header(&name, iface_decl.type_parameters.as_deref(), &parents);
+        self.write_annotation(&header);
+
+        for member in iface_decl.members.iter() {
+            let field = match member {
+                InterfaceMember::Property(prop) => format!(
+                    "field {}{} {}",
+                    self.resolve(prop.name.node),
+                    if prop.is_optional { "?" } else { "" },
+                    self.lua_type(&prop.type_annotation)
+                ),
+                InterfaceMember::Method(method) => {
+                    let mut params = vec![format!("self: {}", name)];
+                    params.extend(method.parameters.iter().enumerate().map(|(i, param)| {
+                        let (param_name, ty) = self.param_annotation(i, param);
+                        format!("{}: {}", param_name, ty)
+                    }));
+                    let ret = self.lua_type(&method.return_type);
+                    let signature = if ret == "nil" {
+                        format!("fun({})", params.join(", "))
+                    } else {
+                        format!("fun({}): {}", params.join(", "), ret)
+                    };
+                    format!("field {} {}", self.resolve(method.name.node), signature)
+                }
+                InterfaceMember::Index(index) => format!(
+                    "field [{}] {}",
+                    Self::index_key_type(&index.key_type),
+                    self.lua_type(&index.value_type)
+                ),
+            };
+            self.write_annotation(&field);
+        }
+        self.writeln("");
+    }
+
+    pub(crate) fn annotate_type_alias(&mut self, alias: &TypeAliasDeclaration) {
+        if !self.annotations_enabled() {
+            return;
+        }
+        let mut name = self.resolve(alias.name.node);
+        let generics: Vec<String> = alias
+            .type_parameters
+            .iter()
+            .flat_map(|params| params.iter())
+            .map(|p| self.resolve(p.name.node))
+            .collect();
+        if !generics.is_empty() {
+            name.push_str(&format!("<{}>", generics.join(", ")));
+        }
+        let ty = self.lua_type(&alias.type_annotation);
+        self.write_annotation(&format!("alias {} {}", name, ty));
+        self.writeln("");
+    }
+
+    pub(crate) fn annotate_enum(&mut self, enum_name: &str, is_rich: bool) {
+        if !self.annotations_enabled() {
+            return;
+        }
+        if is_rich {
+            self.write_annotation(&format!("class {}", enum_name));
+        } else {
+            self.write_annotation(&format!("enum {}", enum_name));
+        }
+    }
+}
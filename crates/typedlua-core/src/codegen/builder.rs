@@ -20,7 +20,10 @@
 use std::sync::Arc;
 use typedlua_parser::string_interner::StringInterner;
 
-use super::{CodeGenMode, CodeGenerator, LuaTarget, ReflectionMode};
+use super::{
+    AccessChecks, CodeGenMode, CodeGenerator, Instrumentation, LuaTarget, PrinterOptions,
+    ReflectionMode, Traces, TypeIdRegistry, ValidationErrors, ValidationMode,
+};
 use crate::config::{OptimizationLevel, OutputFormat};
 use crate::optimizer::WholeProgramAnalysis;
 
@@ -40,6 +43,14 @@ use crate::optimizer::WholeProgramAnalysis;
 /// - `source_map`: Enable source map generation with a source file name
 /// - `mode`: Code generation mode - Require or Bundle (defaults to Require)
 /// - `optimization_level`: Optimization level O0-O3 (defaults to O0)
+/// - `printer_options`: Indentation, line width, quote style and comments
+///   for readable output
+/// - `annotations`: Emit LuaLS/EmmyLua `---@` type annotations
+/// - `type_ids`: Program-wide reflection type IDs shared across modules
+/// - `validation`: Runtime parameter validation generated from types
+/// - `access_checks`: Runtime `private`/`protected` checks in debug builds
+/// - `traces`: Which builds keep `@trace` decorators
+/// - `instrument`: Execution counters for profile-guided optimization
 ///
 /// # Example
 ///
@@ -61,6 +72,14 @@ pub struct CodeGeneratorBuilder {
     whole_program_analysis: Option<WholeProgramAnalysis>,
     reachable_exports: Option<std::collections::HashSet<String>>,
     reflection_mode: ReflectionMode,
+    printer_options: PrinterOptions,
+    source_text: Option<Arc<str>>,
+    annotations: bool,
+    type_ids: Option<(Arc<TypeIdRegistry>, String)>,
+    validation: (ValidationMode, ValidationErrors),
+    access_checks: AccessChecks,
+    traces: Traces,
+    instrumentation: (Instrumentation, String),
 }
 
 impl CodeGeneratorBuilder {
@@ -97,6 +116,14 @@ impl CodeGeneratorBuilder {
             whole_program_analysis: None,
             reachable_exports: None,
             reflection_mode: ReflectionMode::default(),
+            printer_options: PrinterOptions::default(),
+            source_text: None,
+            annotations: false,
+            type_ids: None,
+            validation: Default::default(),
+            access_checks: AccessChecks::default(),
+            traces: Traces::default(),
+            instrumentation: Default::default(),
         }
     }
 
@@ -243,6 +270,185 @@ impl CodeGeneratorBuilder {
         self
     }
 
+    /// Sets the layout options for readable output.
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - The [`PrinterOptions`] (indentation, max line width, quote
+    ///   style, comment preservation)
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::sync::Arc;
+    /// use typedlua_parser::string_interner::StringInterner;
+    /// use typedlua_core::codegen::{CodeGeneratorBuilder, PrinterOptions, QuoteStyle};
+    ///
+    /// let interner = Arc::new(StringInterner::new());
+    /// let generator = CodeGeneratorBuilder::new(interner)
+    ///     .printer_options(PrinterOptions {
+    ///         use_tabs: true,
+    ///         max_line_width: Some(100),
+    ///         quote_style: QuoteStyle::Single,
+    ///         ..Default::default()
+    ///     })
+    ///     .build();
+    /// ```
+    pub fn printer_options(mut self, options: PrinterOptions) -> Self {
+        self.printer_options = options;
+        self
+    }
+
+    /// Provides the `.tl` source text, which is needed to carry comments into
+    /// the output when [`PrinterOptions::preserve_comments`] is set.
+    pub fn source_text(mut self, source: impl Into<Arc<str>>) -> Self {
+        self.source_text = Some(source.into());
+        self
+    }
+
+    /// Enables LuaLS/EmmyLua annotations (`---@class`, `---@param`, ...) so
+    /// editors get type information for the generated Lua. Ignored for
+    /// minified output.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::sync::Arc;
+    /// use typedlua_parser::string_interner::StringInterner;
+    /// use typedlua_core::codegen::CodeGeneratorBuilder;
+    ///
+    /// let interner = Arc::new(StringInterner::new());
+    /// let generator = CodeGeneratorBuilder::new(interner)
+    ///     .annotations(true)
+    ///     .build();
+    /// ```
+    pub fn annotations(mut self, enabled: bool) -> Self {
+        self.annotations = enabled;
+        self
+    }
+
+    /// Uses program-wide reflection type IDs so classes from different
+    /// modules never share a `__typeId`.
+    ///
+    /// # Arguments
+    ///
+    /// * `registry` - The [`TypeIdRegistry`] built over every module in the program
+    /// * `module_id` - The ID this module was registered under
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::sync::Arc;
+    /// use typedlua_parser::string_interner::StringInterner;
+    /// use typedlua_core::codegen::{CodeGeneratorBuilder, TypeIdRegistry};
+    ///
+    /// let interner = Arc::new(StringInterner::new());
+    /// let registry = Arc::new(TypeIdRegistry::new());
+    /// let generator = CodeGeneratorBuilder::new(interner)
+    ///     .type_ids(registry, "src/main.tl".to_string())
+    ///     .build();
+    /// ```
+    pub fn type_ids(mut self, registry: Arc<TypeIdRegistry>, module_id: String) -> Self {
+        self.type_ids = Some((registry, module_id));
+        self
+    }
+
+    /// Generates runtime validators from parameter types and checks them on
+    /// entry to the functions `mode` selects (exported functions for
+    /// [`ValidationMode::Auto`], `@validate` methods for both modes).
+    ///
+    /// # Arguments
+    ///
+    /// * `mode` - Which functions are checked
+    /// * `errors` - Stop at the first failure or collect every failure
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::sync::Arc;
+    /// use typedlua_parser::string_interner::StringInterner;
+    /// use typedlua_core::codegen::{CodeGeneratorBuilder, ValidationErrors, ValidationMode};
+    ///
+    /// let interner = Arc::new(StringInterner::new());
+    /// let generator = CodeGeneratorBuilder::new(interner)
+    ///     .validation(ValidationMode::Auto, ValidationErrors::FailFast)
+    ///     .build();
+    /// ```
+    pub fn validation(mut self, mode: ValidationMode, errors: ValidationErrors) -> Self {
+        self.validation = (mode, errors);
+        self
+    }
+
+    /// Enforces `private` and `protected` instance members at runtime.
+    ///
+    /// With [`AccessChecks::Debug`], instances are wrapped in access guards
+    /// when the optimization level is O0; optimized builds are unaffected.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::sync::Arc;
+    /// use typedlua_parser::string_interner::StringInterner;
+    /// use typedlua_core::codegen::{AccessChecks, CodeGeneratorBuilder};
+    ///
+    /// let interner = Arc::new(StringInterner::new());
+    /// let generator = CodeGeneratorBuilder::new(interner)
+    ///     .access_checks(AccessChecks::Debug)
+    ///     .build();
+    /// ```
+    pub fn access_checks(mut self, mode: AccessChecks) -> Self {
+        self.access_checks = mode;
+        self
+    }
+
+    /// Chooses which builds keep `@trace` decorators.
+    ///
+    /// [`Traces::Debug`] (the default) keeps them at O0 only, so the default
+    /// O1 build drops them; [`Traces::Always`] keeps them at every level.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::sync::Arc;
+    /// use typedlua_parser::string_interner::StringInterner;
+    /// use typedlua_core::codegen::{CodeGeneratorBuilder, Traces};
+    ///
+    /// let interner = Arc::new(StringInterner::new());
+    /// let generator = CodeGeneratorBuilder::new(interner)
+    ///     .traces(Traces::Always)
+    ///     .build();
+    /// ```
+    pub fn traces(mut self, traces: Traces) -> Self {
+        self.traces = traces;
+        self
+    }
+
+    /// Counts how often functions, branches and loops run, writing a
+    /// profile for [`crate::optimizer::Optimizer::set_profile`] when the
+    /// program exits.
+    ///
+    /// # Arguments
+    ///
+    /// * `mode` - Whether to count executions
+    /// * `module_id` - Module id the profile is read back with
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::sync::Arc;
+    /// use typedlua_parser::string_interner::StringInterner;
+    /// use typedlua_core::codegen::{CodeGeneratorBuilder, Instrumentation};
+    ///
+    /// let interner = Arc::new(StringInterner::new());
+    /// let generator = CodeGeneratorBuilder::new(interner)
+    ///     .instrument(Instrumentation::Pgo, "src/main.tl".to_string())
+    ///     .build();
+    /// ```
+    pub fn instrument(mut self, mode: Instrumentation, module_id: String) -> Self {
+        self.instrumentation = (mode, module_id);
+        self
+    }
+
     /// Sets the whole-program analysis for cross-module optimizations.
     ///
     /// This is optional and only needed for O3+ optimizations that benefit
@@ -340,7 +546,22 @@ impl CodeGeneratorBuilder {
         generator = generator.with_mode(self.mode);
         generator = generator.with_optimization_level(self.optimization_level);
         generator = generator.with_output_format(self.output_format);
+        generator = generator.with_printer_options(self.printer_options);
         generator = generator.with_reflection_mode(self.reflection_mode);
+        generator = generator.with_annotations(self.annotations);
+        generator = generator.with_validation(self.validation.0, self.validation.1);
+        generator =
+            generator.with_enforce_access_modifiers(self.access_checks == AccessChecks::Debug);
+        generator = generator.with_traces(self.traces);
+        generator = generator.with_instrumentation(self.instrumentation.0, self.instrumentation.1);
+
+        if let Some((registry, module_id)) = self.type_ids {
+            generator = generator.with_type_ids(registry, module_id);
+        }
+
+        if let Some(source) = self.source_text {
+            generator = generator.with_source_text(source);
+        }
 
         if let Some(source_file) = self.source_map {
             generator = generator.with_source_map(source_file);
@@ -0,0 +1,389 @@
+//! Read-only scan of every call and binding in a module.
+//!
+//! Codegen decides some things before writing a module's body: which
+//! intrinsics it lowers and which runtime helpers it embeds. [`scan`] walks
+//! the module for them, matching every statement, expression and pattern
+//! variant without a wildcard arm so no nested body is skipped, and reports
+//! each call and each name the module binds to a [`CallScan`].
+
+use typedlua_parser::ast::expression::*;
+use typedlua_parser::ast::pattern::{ArrayPatternElement, Pattern, PatternWithDefault};
+use typedlua_parser::ast::statement::*;
+use typedlua_parser::ast::types::Type;
+use typedlua_parser::prelude::MatchArmBody;
+use typedlua_parser::string_interner::{StringId, StringInterner};
+
+/// Hooks called by [`scan`]; both default to doing nothing
+pub(crate) trait CallScan<'a, 'arena: 'a> {
+    /// A call `callee<type_args>(args)`, in source order
+    fn call(
+        &mut self,
+        _callee: &'a Expression<'arena>,
+        _args: &'a [Argument<'arena>],
+        _type_args: Option<&'a [Type<'arena>]>,
+    ) {
+    }
+
+    /// A name the module binds: function, variable, parameter, loop
+    /// variable, catch variable, class, enum or import
+    fn bind(&mut self, _name: StringId) {}
+}
+
+/// Walk every statement of a module
+pub(crate) fn scan<'a, 'arena: 'a, S: CallScan<'a, 'arena>>(
+    scanner: &mut S,
+    statements: &'a [Statement<'arena>],
+) {
+    Walk(scanner).statements(statements);
+}
+
+/// Whether the module calls the global function `name` anywhere
+pub(crate) fn calls_function(
+    interner: &StringInterner,
+    statements: &[Statement<'_>],
+    name: &str,
+) -> bool {
+    struct Finder<'i> {
+        interner: &'i StringInterner,
+        name: &'i str,
+        found: bool,
+    }
+
+    impl<'a, 'arena: 'a> CallScan<'a, 'arena> for Finder<'_> {
+        fn call(
+            &mut self,
+            callee: &'a Expression<'arena>,
+            _args: &'a [Argument<'arena>],
+            _type_args: Option<&'a [Type<'arena>]>,
+        ) {
+            if let ExpressionKind::Identifier(id) = &callee.kind {
+                self.found |= self.interner.resolve(*id) == self.name;
+            }
+        }
+    }
+
+    let mut finder = Finder {
+        interner,
+        name,
+        found: false,
+    };
+    scan(&mut finder, statements);
+    finder.found
+}
+
+struct Walk<'s, S>(&'s mut S);
+
+impl<'a, 'arena: 'a, S: CallScan<'a, 'arena>> Walk<'_, S> {
+    fn statements(&mut self, statements: &'a [Statement<'arena>]) {
+        for stmt in statements {
+            self.statement(stmt);
+        }
+    }
+
+    fn parameters(&mut self, params: &'a [Parameter<'arena>]) {
+        for param in params {
+            self.pattern(&param.pattern);
+            if let Some(default) = &param.default {
+                self.expression(default);
+            }
+        }
+    }
+
+    fn statement(&mut self, stmt: &'a Statement<'arena>) {
+        match stmt {
+            Statement::Variable(decl) => {
+                self.pattern(&decl.pattern);
+                self.expression(&decl.initializer);
+            }
+            Statement::Expression(expr) => self.expression(expr),
+            Statement::Function(func) => {
+                self.0.bind(func.name.node);
+                self.parameters(func.parameters);
+                self.statements(func.body.statements);
+            }
+            Statement::If(if_stmt) => {
+                self.expression(&if_stmt.condition);
+                self.statements(if_stmt.then_block.statements);
+                for else_if in if_stmt.else_ifs.iter() {
+                    self.expression(&else_if.condition);
+                    self.statements(else_if.block.statements);
+                }
+                if let Some(else_block) = &if_stmt.else_block {
+                    self.statements(else_block.statements);
+                }
+            }
+            Statement::While(while_stmt) => {
+                self.expression(&while_stmt.condition);
+                self.statements(while_stmt.body.statements);
+            }
+            Statement::For(for_stmt) => match &**for_stmt {
+                ForStatement::Numeric(numeric) => {
+                    self.0.bind(numeric.variable.node);
+                    self.expression(&numeric.start);
+                    self.expression(&numeric.end);
+                    if let Some(step) = &numeric.step {
+                        self.expression(step);
+                    }
+                    self.statements(numeric.body.statements);
+                }
+                ForStatement::Generic(generic) => {
+                    for variable in generic.variables.iter() {
+                        self.0.bind(variable.node);
+                    }
+                    if let Some(pattern) = &generic.pattern {
+                        self.pattern(pattern);
+                    }
+                    for iterator in generic.iterators.iter() {
+                        self.expression(iterator);
+                    }
+                    self.statements(generic.body.statements);
+                }
+            },
+            Statement::Repeat(repeat_stmt) => {
+                self.statements(repeat_stmt.body.statements);
+                self.expression(&repeat_stmt.until);
+            }
+            Statement::Return(ret) => {
+                for value in ret.values.iter() {
+                    self.expression(value);
+                }
+            }
+            Statement::Block(block) => self.statements(block.statements),
+            Statement::Class(class_decl) => {
+                self.0.bind(class_decl.name.node);
+                for member in class_decl.members.iter() {
+                    match member {
+                        ClassMember::Method(method) => {
+                            self.parameters(method.parameters);
+                            if let Some(body) = &method.body {
+                                self.statements(body.statements);
+                            }
+                        }
+                        ClassMember::Constructor(ctor) => {
+                            self.parameters(ctor.parameters);
+                            self.statements(ctor.body.statements);
+                        }
+                        ClassMember::Getter(getter) => self.statements(getter.body.statements),
+                        ClassMember::Setter(setter) => {
+                            self.parameters(std::slice::from_ref(&setter.parameter));
+                            self.statements(setter.body.statements);
+                        }
+                        ClassMember::Operator(op) => {
+                            self.parameters(op.parameters);
+                            self.statements(op.body.statements);
+                        }
+                        ClassMember::Property(_) => {}
+                    }
+                }
+            }
+            Statement::Enum(enum_decl) => {
+                self.0.bind(enum_decl.name.node);
+                for member in enum_decl.members.iter() {
+                    for arg in member.arguments.iter() {
+                        self.expression(arg);
+                    }
+                }
+                if let Some(ctor) = &enum_decl.constructor {
+                    self.parameters(ctor.parameters);
+                    self.statements(ctor.body.statements);
+                }
+                for method in enum_decl.methods.iter() {
+                    self.parameters(method.parameters);
+                    self.statements(method.body.statements);
+                }
+            }
+            Statement::Import(import) => match &import.clause {
+                ImportClause::Default(ident) => self.0.bind(ident.node),
+                ImportClause::Named(specs) => {
+                    for spec in specs.iter() {
+                        self.0
+                            .bind(spec.local.as_ref().unwrap_or(&spec.imported).node);
+                    }
+                }
+                ImportClause::Mixed { default, named } => {
+                    self.0.bind(default.node);
+                    for spec in named.iter() {
+                        self.0
+                            .bind(spec.local.as_ref().unwrap_or(&spec.imported).node);
+                    }
+                }
+                ImportClause::Namespace(_) | ImportClause::TypeOnly(_) => {}
+            },
+            Statement::Export(export) => match &export.kind {
+                ExportKind::Declaration(decl) => self.statement(decl),
+                ExportKind::Default(expr) => self.expression(expr),
+                ExportKind::Named { .. } => {}
+            },
+            Statement::Throw(throw_stmt) => self.expression(&throw_stmt.expression),
+            Statement::Try(try_stmt) => {
+                self.statements(try_stmt.try_block.statements);
+                for clause in try_stmt.catch_clauses.iter() {
+                    match &clause.pattern {
+                        CatchPattern::Untyped { variable, .. }
+                        | CatchPattern::Typed { variable, .. }
+                        | CatchPattern::MultiTyped { variable, .. } => self.0.bind(variable.node),
+                    }
+                    self.statements(clause.body.statements);
+                }
+                if let Some(finally_block) = &try_stmt.finally_block {
+                    self.statements(finally_block.statements);
+                }
+            }
+            // Declarations and jumps without runtime expressions
+            Statement::Interface(_)
+            | Statement::TypeAlias(_)
+            | Statement::Namespace(_)
+            | Statement::DeclareFunction(_)
+            | Statement::DeclareNamespace(_)
+            | Statement::DeclareType(_)
+            | Statement::DeclareInterface(_)
+            | Statement::DeclareConst(_)
+            | Statement::Break(_)
+            | Statement::Continue(_)
+            | Statement::Rethrow(_)
+            | Statement::Label(_)
+            | Statement::Goto(_) => {}
+        }
+    }
+
+    fn pattern(&mut self, pattern: &'a Pattern<'arena>) {
+        match pattern {
+            Pattern::Identifier(ident) => self.0.bind(ident.node),
+            Pattern::Literal(..) | Pattern::Wildcard(_) => {}
+            Pattern::Array(array) => {
+                for element in array.elements.iter() {
+                    match element {
+                        ArrayPatternElement::Pattern(PatternWithDefault { pattern, default }) => {
+                            self.pattern(pattern);
+                            if let Some(default) = default {
+                                self.expression(default);
+                            }
+                        }
+                        ArrayPatternElement::Rest(ident) => self.0.bind(ident.node),
+                        ArrayPatternElement::Hole => {}
+                    }
+                }
+            }
+            Pattern::Object(object) => {
+                for prop in object.properties.iter() {
+                    if let Some(key) = &prop.computed_key {
+                        self.expression(key);
+                    }
+                    match &prop.value {
+                        Some(value) => self.pattern(value),
+                        None => self.0.bind(prop.key.node),
+                    }
+                    if let Some(default) = &prop.default {
+                        self.expression(default);
+                    }
+                }
+            }
+            Pattern::Or(or_pattern) => {
+                for alternative in or_pattern.alternatives.iter() {
+                    self.pattern(alternative);
+                }
+            }
+        }
+    }
+
+    fn arguments(&mut self, args: &'a [Argument<'arena>]) {
+        for arg in args {
+            self.expression(&arg.value);
+        }
+    }
+
+    fn expression(&mut self, expr: &'a Expression<'arena>) {
+        match &expr.kind {
+            ExpressionKind::Call(callee, args, type_args) => {
+                self.0.call(callee, args, *type_args);
+                self.expression(callee);
+                self.arguments(args);
+            }
+            ExpressionKind::MethodCall(object, _, args, _)
+            | ExpressionKind::OptionalMethodCall(object, _, args, _)
+            | ExpressionKind::OptionalCall(object, args, _)
+            | ExpressionKind::New(object, args, _) => {
+                self.expression(object);
+                self.arguments(args);
+            }
+            ExpressionKind::Binary(_, left, right)
+            | ExpressionKind::Assignment(left, _, right)
+            | ExpressionKind::Index(left, right)
+            | ExpressionKind::OptionalIndex(left, right)
+            | ExpressionKind::Pipe(left, right)
+            | ExpressionKind::ErrorChain(left, right) => {
+                self.expression(left);
+                self.expression(right);
+            }
+            ExpressionKind::Unary(_, inner)
+            | ExpressionKind::Member(inner, _)
+            | ExpressionKind::OptionalMember(inner, _)
+            | ExpressionKind::Parenthesized(inner)
+            | ExpressionKind::TypeAssertion(inner, _) => self.expression(inner),
+            ExpressionKind::Conditional(cond, then_expr, else_expr) => {
+                self.expression(cond);
+                self.expression(then_expr);
+                self.expression(else_expr);
+            }
+            ExpressionKind::Array(elements) => {
+                for elem in elements.iter() {
+                    match elem {
+                        ArrayElement::Expression(e) | ArrayElement::Spread(e) => self.expression(e),
+                    }
+                }
+            }
+            ExpressionKind::Object(props) => {
+                for prop in props.iter() {
+                    match prop {
+                        ObjectProperty::Property { value, .. }
+                        | ObjectProperty::Spread { value, .. } => self.expression(value),
+                        ObjectProperty::Computed { key, value, .. } => {
+                            self.expression(key);
+                            self.expression(value);
+                        }
+                    }
+                }
+            }
+            ExpressionKind::Function(func) => {
+                self.parameters(func.parameters);
+                self.statements(func.body.statements);
+            }
+            ExpressionKind::Arrow(arrow) => {
+                self.parameters(arrow.parameters);
+                match &arrow.body {
+                    ArrowBody::Expression(body) => self.expression(body),
+                    ArrowBody::Block(block) => self.statements(block.statements),
+                }
+            }
+            ExpressionKind::Template(template) => {
+                for part in template.parts.iter() {
+                    match part {
+                        TemplatePart::String(_) => {}
+                        TemplatePart::Expression(e) => self.expression(e),
+                    }
+                }
+            }
+            ExpressionKind::Match(match_expr) => {
+                self.expression(match_expr.value);
+                for arm in match_expr.arms.iter() {
+                    self.pattern(&arm.pattern);
+                    if let Some(guard) = &arm.guard {
+                        self.expression(guard);
+                    }
+                    match &arm.body {
+                        MatchArmBody::Expression(body) => self.expression(body),
+                        MatchArmBody::Block(block) => self.statements(block.statements),
+                    }
+                }
+            }
+            ExpressionKind::Try(try_expr) => {
+                self.expression(try_expr.expression);
+                self.expression(try_expr.catch_expression);
+            }
+            ExpressionKind::Identifier(_)
+            | ExpressionKind::Literal(_)
+            | ExpressionKind::SelfKeyword
+            | ExpressionKind::SuperKeyword => {}
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use super::decorators::DecoratorContext;
 use super::CodeGenerator;
 use typedlua_parser as parser;
 use typedlua_parser::ast::statement::*;
@@ -19,7 +20,12 @@ impl CodeGenerator {
         };
 
         self.current_class_parent = base_class_name;
+        self.register_bound_methods(&class_name, class_decl);
+        if self.class_has_instance_initializers(class_decl) {
+            self.instance_initializer_classes.insert(class_name.clone());
+        }
 
+        self.annotate_class(class_decl, &class_name);
         self.write_indent();
         self.write("local ");
         self.write(&class_name);
@@ -30,8 +36,10 @@ impl CodeGenerator {
         self.write(".__index = ");
         self.write(&class_name);
         self.writeln("");
+        self.annotate_static_fields(class_decl, &class_name);
 
-        if let Some(base_name) = &base_class_name {
+        let flatten = self.flattens_class(class_decl);
+        if let Some(base_name) = base_class_name.as_ref().filter(|_| !flatten) {
             self.writeln("");
             self.write_indent();
             self.write("setmetatable(");
@@ -60,6 +68,7 @@ impl CodeGenerator {
         } else {
             // Generate default constructor
             self.writeln("");
+            self.annotate_constructor(&class_name, &[]);
             self.write_indent();
             self.write("function ");
             self.write(&class_name);
@@ -86,6 +95,9 @@ impl CodeGenerator {
             self.write("local self = setmetatable({}, ");
             self.write(&class_name);
             self.writeln(")");
+            self.write_access_guard(&class_name);
+            self.write_instance_initializers(&class_name);
+            self.write_field_initializers(&class_name);
             self.write_indent();
             self.writeln("return self");
             self.dedent();
@@ -107,7 +119,21 @@ impl CodeGenerator {
                 ClassMember::Operator(op) => {
                     self.generate_operator_declaration(&class_name, op);
                 }
-                ClassMember::Property(_) | ClassMember::Constructor(_) => {}
+                ClassMember::Property(prop) => {
+                    let prop_name = self.resolve(prop.name.node);
+                    self.generate_decorator_application(
+                        &class_name,
+                        &prop_name,
+                        prop.decorators,
+                        &DecoratorContext::member(
+                            "field",
+                            prop_name.clone(),
+                            prop.is_static,
+                            prop.access.as_ref(),
+                        ),
+                    );
+                }
+                ClassMember::Constructor(_) => {}
             }
         }
 
@@ -148,15 +174,21 @@ impl CodeGenerator {
             self.writeln("}");
         }
 
+        if let Some(base_name) = base_class_name.filter(|_| flatten) {
+            let base_name_str = self.resolve(base_name);
+            self.write_copy_down(&class_name, &base_name_str);
+        }
+
+        self.write_access_registration(&class_name);
+
         if !class_decl.decorators.is_empty() {
             self.writeln("");
-            for decorator in class_decl.decorators.iter() {
-                self.write_indent();
-                self.write(&class_name);
-                self.write(" = ");
-                self.generate_decorator_call(decorator, &class_name);
-                self.writeln("");
-            }
+            self.generate_decorator_application(
+                &class_name,
+                &class_name,
+                class_decl.decorators,
+                &DecoratorContext::class(&class_name),
+            );
         }
 
         self.writeln("");
@@ -204,8 +236,10 @@ impl CodeGenerator {
         self.writeln("");
 
         // -- Class infrastructure (always emitted) --
-        let type_id = self.next_type_id;
-        self.next_type_id += 1;
+        let type_id = match self.reserved_type_ids.remove(&class_decl.span.start) {
+            Some(type_id) => type_id,
+            None => self.allocate_type_id(&class_name),
+        };
 
         self.write_indent();
         self.write(&class_name);
@@ -216,6 +250,7 @@ impl CodeGenerator {
         self.write_indent();
         self.write(&class_name);
         self.writeln(&format!(".__typeId = {}", type_id));
+        self.write_access_table(&class_name, class_decl);
 
         // Mark class as final if needed
         if class_decl.is_final {
@@ -354,10 +389,11 @@ impl CodeGenerator {
                     let prop_name = self.resolve(prop.name.node).to_string();
                     let flags = Self::encode_field_flags(prop);
                     let type_code = self.encode_type_code(&prop.type_annotation);
+                    let extras = self.reflect_field_extras(prop);
                     self.write_indent();
                     self.writeln(&format!(
-                        "{{ name = \"{}\", type = \"{}\", _flags = {} }},",
-                        prop_name, type_code, flags
+                        "{{ name = \"{}\", type = \"{}\", _flags = {}{} }},",
+                        prop_name, type_code, flags, extras
                     ));
                 }
             }
@@ -388,16 +424,18 @@ impl CodeGenerator {
                         .as_ref()
                         .map(|ty| self.encode_type_code(ty))
                         .unwrap_or_else(|| "v".to_string());
+                    let extras = self.reflect_method_extras(method);
                     self.write_indent();
                     self.writeln(&format!(
-                        "{{ name = \"{}\", params = \"{}\", ret = \"{}\" }},",
-                        method_name, params, ret
+                        "{{ name = \"{}\", params = \"{}\", ret = \"{}\"{} }},",
+                        method_name, params, ret, extras
                     ));
                 }
             }
             self.dedent();
             self.write_indent();
             self.writeln("}");
+            self.write_class_type_metadata(&class_name, class_decl);
 
             self.writeln("");
             self.writeln(
@@ -428,18 +466,74 @@ impl CodeGenerator {
         self.current_class_parent = prev_parent;
     }
 
+    /// Whether inherited methods are copied into the class table (O2+) rather
+    /// than found through `setmetatable(Class, { __index = Parent })`
+    fn flattens_class(&self, class_decl: &ClassDeclaration) -> bool {
+        self.class_hierarchy
+            .as_ref()
+            .is_some_and(|hierarchy| hierarchy.can_flatten(class_decl.name.node))
+    }
+
+    /// Copy every function the ancestors define and the class does not,
+    /// nearest ancestor first
+    fn write_copy_down(&mut self, class_name: &str, base_name: &str) {
+        self.writeln("");
+        self.write_indent();
+        self.writeln("do");
+        self.indent();
+        self.write_indent();
+        self.writeln(&format!("local __base = {}", base_name));
+        self.write_indent();
+        self.writeln("while __base do");
+        self.indent();
+        self.write_indent();
+        self.writeln("for k, v in pairs(__base) do");
+        self.indent();
+        self.write_indent();
+        self.writeln(&format!(
+            "if type(v) == \"function\" and rawget({}, k) == nil then",
+            class_name
+        ));
+        self.indent();
+        self.write_indent();
+        self.writeln(&format!("{}[k] = v", class_name));
+        self.dedent();
+        self.write_indent();
+        self.writeln("end");
+        self.dedent();
+        self.write_indent();
+        self.writeln("end");
+        self.write_indent();
+        self.writeln("__base = rawget(__base, \"__parent\")");
+        self.dedent();
+        self.write_indent();
+        self.writeln("end");
+        self.dedent();
+        self.write_indent();
+        self.writeln("end");
+    }
+
     /// Encode field access modifiers as bit flags per v2 reflection spec.
     /// Bit 0=Public(1), Bit 1=Private(2), Bit 2=Protected(4), Bit 3=Readonly(8), Bit 4=Static(16)
-    fn encode_field_flags(prop: &PropertyDeclaration) -> u8 {
-        let mut flags: u8 = match prop.access {
+    pub(crate) fn encode_field_flags(prop: &PropertyDeclaration) -> u8 {
+        Self::encode_access_flags(prop.access.as_ref(), prop.is_readonly, prop.is_static)
+    }
+
+    /// The same bit flags for any member
+    pub(crate) fn encode_access_flags(
+        access: Option<&AccessModifier>,
+        is_readonly: bool,
+        is_static: bool,
+    ) -> u8 {
+        let mut flags: u8 = match access {
             Some(AccessModifier::Public) | None => 1,
             Some(AccessModifier::Private) => 2,
             Some(AccessModifier::Protected) => 4,
         };
-        if prop.is_readonly {
+        if is_readonly {
             flags |= 8;
         }
-        if prop.is_static {
+        if is_static {
             flags |= 16;
         }
         flags
@@ -473,6 +567,7 @@ impl CodeGenerator {
 
     pub fn generate_interface_declaration(&mut self, iface_decl: &InterfaceDeclaration) {
         let interface_name = self.resolve(iface_decl.name.node).to_string();
+        self.annotate_interface(iface_decl);
 
         for member in iface_decl.members.iter() {
             if let InterfaceMember::Method(method) = member {
@@ -536,6 +631,7 @@ impl CodeGenerator {
             self.writeln("end");
 
             self.writeln("");
+            self.annotate_constructor(class_name, &ctor.parameters);
             self.write_indent();
             self.write("function ");
             self.write(class_name);
@@ -555,6 +651,8 @@ impl CodeGenerator {
             self.write("local self = setmetatable({}, ");
             self.write(class_name);
             self.writeln(")");
+            self.write_access_guard(class_name);
+            self.write_instance_initializers(class_name);
 
             // Check for abstract class instantiation
             if is_abstract {
@@ -580,6 +678,7 @@ impl CodeGenerator {
                 self.generate_pattern(&param.pattern);
             }
             self.writeln(")");
+            self.write_field_initializers(class_name);
 
             self.write_indent();
             self.writeln("return self");
@@ -589,6 +688,7 @@ impl CodeGenerator {
             self.writeln("end");
         } else {
             self.writeln("");
+            self.annotate_constructor(class_name, &ctor.parameters);
             self.write_indent();
             self.write("function ");
             self.write(class_name);
@@ -608,6 +708,8 @@ impl CodeGenerator {
             self.write("local self = setmetatable({}, ");
             self.write(class_name);
             self.writeln(")");
+            self.write_access_guard(class_name);
+            self.write_instance_initializers(class_name);
 
             // Check for abstract class instantiation
             if is_abstract {
@@ -626,6 +728,7 @@ impl CodeGenerator {
             }
 
             self.generate_block(&ctor.body);
+            self.write_field_initializers(class_name);
 
             self.write_indent();
             self.writeln("return self");
@@ -697,6 +800,7 @@ impl CodeGenerator {
         self.writeln("end");
 
         self.writeln("");
+        self.annotate_primary_constructor(class_decl, class_name);
         self.write_indent();
         self.write("function ");
         self.write(class_name);
@@ -717,6 +821,8 @@ impl CodeGenerator {
         self.write("local self = setmetatable({}, ");
         self.write(class_name);
         self.writeln(")");
+        self.write_access_guard(class_name);
+        self.write_instance_initializers(class_name);
 
         // Check for abstract class instantiation
         if class_decl.is_abstract {
@@ -743,6 +849,7 @@ impl CodeGenerator {
             self.write(&param_name);
         }
         self.writeln(")");
+        self.write_field_initializers(class_name);
 
         self.write_indent();
         self.writeln("return self");
@@ -758,6 +865,7 @@ impl CodeGenerator {
         }
 
         self.writeln("");
+        self.annotate_method(method);
         self.write_indent();
         self.write("function ");
         self.write(class_name);
@@ -782,31 +890,33 @@ impl CodeGenerator {
 
         if let Some(body) = &method.body {
             self.indent();
+            let qualified_name = format!("{}.{}", class_name, method_name);
+            self.write_validation_checks(&qualified_name);
+            let outer = self.enter_profiled_function(method.span, &qualified_name);
             self.generate_block(body);
+            self.leave_profiled_function(outer);
             self.dedent();
         }
 
         self.write_indent();
         self.writeln("end");
 
-        if !method.decorators.is_empty() {
-            for decorator in method.decorators.iter() {
-                self.write_indent();
-                self.write(class_name);
-                self.write(".");
-                let method_name = self.resolve(method.name.node);
-                self.write(&method_name);
-                self.write(" = ");
-
-                let method_ref = format!("{}.{}", class_name, method_name);
-                self.generate_decorator_call(decorator, &method_ref);
-                self.writeln("");
-            }
-        }
+        self.generate_decorator_application(
+            class_name,
+            &format!("{}.{}", class_name, method_name),
+            method.decorators,
+            &DecoratorContext::member(
+                "method",
+                method_name.clone(),
+                method.is_static,
+                method.access.as_ref(),
+            ),
+        );
     }
 
     pub fn generate_class_getter(&mut self, class_name: &str, getter: &GetterDeclaration) {
         self.writeln("");
+        self.annotate_getter(getter);
         self.write_indent();
         self.write("function ");
         self.write(class_name);
@@ -828,10 +938,23 @@ impl CodeGenerator {
 
         self.write_indent();
         self.writeln("end");
+
+        self.generate_decorator_application(
+            class_name,
+            &format!("{}.get_{}", class_name, getter_name),
+            getter.decorators,
+            &DecoratorContext::member(
+                "getter",
+                getter_name,
+                getter.is_static,
+                getter.access.as_ref(),
+            ),
+        );
     }
 
     pub fn generate_class_setter(&mut self, class_name: &str, setter: &SetterDeclaration) {
         self.writeln("");
+        self.annotate_setter(setter);
         self.write_indent();
         self.write("function ");
         self.write(class_name);
@@ -855,6 +978,18 @@ impl CodeGenerator {
 
         self.write_indent();
         self.writeln("end");
+
+        self.generate_decorator_application(
+            class_name,
+            &format!("{}.set_{}", class_name, setter_name),
+            setter.decorators,
+            &DecoratorContext::member(
+                "setter",
+                setter_name,
+                setter.is_static,
+                setter.access.as_ref(),
+            ),
+        );
     }
 
     pub fn generate_operator_declaration(&mut self, class_name: &str, op: &OperatorDeclaration) {
@@ -883,17 +1018,13 @@ impl CodeGenerator {
         self.write_indent();
         self.writeln("end");
 
-        for decorator in op.decorators.iter() {
-            self.write_indent();
-            self.write(class_name);
-            self.write(".");
-            self.write(&self.operator_kind_name(&op.operator));
-            self.write(" = ");
-
-            let method_ref = format!("{}.{}", class_name, self.operator_kind_name(&op.operator));
-            self.generate_decorator_call(decorator, &method_ref);
-            self.writeln("");
-        }
+        let metamethod_name = self.operator_kind_name(&op.operator);
+        self.generate_decorator_application(
+            class_name,
+            &format!("{}.{}", class_name, metamethod_name),
+            op.decorators,
+            &DecoratorContext::member("method", metamethod_name, false, None),
+        );
     }
 
     pub fn operator_kind_name(&self, op: &typedlua_parser::ast::statement::OperatorKind) -> String {
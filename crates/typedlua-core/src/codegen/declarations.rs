@@ -0,0 +1,749 @@
+//! Declaration file (`.d.tl`) emission.
+//!
+//! Produces `declare` forms for everything a module exports so libraries can
+//! ship type information without the implementation. Classes keep their
+//! public members and constructor signature in a `declare class`, so
+//! consumers can still construct and extend them.
+//!
+//! Types the source leaves out (an unannotated `const`, parameter or return
+//! type) come from the type checker's view of the export, see
+//! [`CheckedType`]; `unknown` is written only when the checker has none.
+
+use rustc_hash::FxHashMap as HashMap;
+use rustc_hash::FxHashSet as HashSet;
+use typedlua_parser::ast::expression::{ExpressionKind, Literal};
+use typedlua_parser::ast::pattern::Pattern;
+use typedlua_parser::ast::statement::*;
+use typedlua_parser::ast::types::{
+    IndexKeyType, ObjectTypeMember, PrimitiveType, Type, TypeKind, TypeParameter,
+};
+use typedlua_parser::string_interner::{StringId, StringInterner};
+
+/// The type the checker gave an export, rendered in declaration syntax
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CheckedType {
+    /// The export's type
+    pub ty: String,
+    /// Parameter types, when the export is a function
+    pub parameters: Vec<String>,
+    /// Return type, when the export is a function
+    pub return_type: Option<String>,
+}
+
+impl CheckedType {
+    pub fn new(ty: &Type, interner: &StringInterner) -> Self {
+        let renderer = DeclarationGenerator::new(interner);
+        let (parameters, return_type) = match &ty.kind {
+            TypeKind::Function(func) => (
+                func.parameters
+                    .iter()
+                    .map(|param| {
+                        param
+                            .type_annotation
+                            .as_ref()
+                            .map_or_else(|| "unknown".to_string(), |ty| renderer.type_to_string(ty))
+                    })
+                    .collect(),
+                Some(renderer.type_to_string(&func.return_type)),
+            ),
+            _ => (Vec::new(), None),
+        };
+        CheckedType {
+            ty: renderer.type_to_string(ty),
+            parameters,
+            return_type,
+        }
+    }
+}
+
+pub struct DeclarationGenerator<'a> {
+    interner: &'a StringInterner,
+    /// Names reported by `TypeChecker::extract_exports`; `None` trusts the AST alone
+    exported: Option<HashSet<String>>,
+    /// Checked types of the exports, by name
+    checked: HashMap<String, CheckedType>,
+    emitted: HashSet<String>,
+    output: String,
+    indent: usize,
+    /// Inside a `declare namespace` block members drop the `export declare` prefix
+    in_namespace: bool,
+}
+
+impl<'a> DeclarationGenerator<'a> {
+    pub fn new(interner: &'a StringInterner) -> Self {
+        DeclarationGenerator {
+            interner,
+            exported: None,
+            checked: HashMap::default(),
+            emitted: HashSet::default(),
+            output: String::new(),
+            indent: 0,
+            in_namespace: false,
+        }
+    }
+
+    /// Restrict output to the given export names
+    pub fn with_exports(mut self, names: impl IntoIterator<Item = String>) -> Self {
+        self.exported = Some(names.into_iter().collect());
+        self
+    }
+
+    /// Restrict output to the exports `TypeChecker::extract_exports` reported
+    /// and fill the types their declarations leave out from the checked ones
+    pub fn with_checked_exports(
+        mut self,
+        exports: impl IntoIterator<Item = (String, CheckedType)>,
+    ) -> Self {
+        self.checked = exports.into_iter().collect();
+        self.exported = Some(self.checked.keys().cloned().collect());
+        self
+    }
+
+    pub fn generate(mut self, statements: &[Statement]) -> String {
+        let namespace = statements.iter().find_map(|stmt| match stmt {
+            Statement::Namespace(ns) => Some(
+                ns.path
+                    .iter()
+                    .map(|ident| self.resolve(ident.node))
+                    .collect::<Vec<_>>(),
+            ),
+            _ => None,
+        });
+
+        if let Some(path) = namespace.as_ref().filter(|path| !path.is_empty()) {
+            self.line(&format!("export declare namespace {} {{", path[0]));
+            self.indent += 1;
+            for segment in &path[1..] {
+                self.line(&format!("namespace {} {{", segment));
+                self.indent += 1;
+            }
+            self.in_namespace = true;
+            self.generate_exports(statements);
+            for _ in 0..path.len() {
+                self.indent -= 1;
+                self.line("}");
+            }
+        } else {
+            self.generate_exports(statements);
+        }
+
+        self.output
+    }
+
+    fn generate_exports(&mut self, statements: &[Statement]) {
+        for stmt in statements {
+            let Statement::Export(export) = stmt else {
+                continue;
+            };
+            match &export.kind {
+                ExportKind::Declaration(decl) => self.generate_declaration(decl),
+                ExportKind::Named { specifiers, source } => {
+                    if let Some(source) = source {
+                        let names: Vec<String> = specifiers
+                            .iter()
+                            .map(|spec| self.resolve(spec.local.node))
+                            .collect();
+                        self.line(&format!(
+                            "export {{ {} }} from \"{}\"",
+                            names.join(", "),
+                            source
+                        ));
+                        continue;
+                    }
+                    for spec in specifiers.iter() {
+                        if let Some(decl) = Self::find_local(statements, spec.local.node) {
+                            self.generate_declaration(decl);
+                        }
+                    }
+                }
+                // The value of a default export has no declared type to emit
+                ExportKind::Default(_) => {}
+            }
+        }
+    }
+
+    fn find_local<'s, 'arena>(
+        statements: &'s [Statement<'arena>],
+        name: StringId,
+    ) -> Option<&'s Statement<'arena>> {
+        statements.iter().find(|stmt| match stmt {
+            Statement::Function(decl) => decl.name.node == name,
+            Statement::Class(decl) => decl.name.node == name,
+            Statement::Interface(decl) => decl.name.node == name,
+            Statement::TypeAlias(decl) => decl.name.node == name,
+            Statement::Enum(decl) => decl.name.node == name,
+            Statement::Variable(decl) => {
+                matches!(&decl.pattern, Pattern::Identifier(ident) if ident.node == name)
+            }
+            _ => false,
+        })
+    }
+
+    fn generate_declaration(&mut self, stmt: &Statement) {
+        let name = match stmt {
+            Statement::Function(decl) => decl.name.node,
+            Statement::Class(decl) => decl.name.node,
+            Statement::Interface(decl) => decl.name.node,
+            Statement::TypeAlias(decl) => decl.name.node,
+            Statement::Enum(decl) => decl.name.node,
+            Statement::Variable(decl) => match &decl.pattern {
+                Pattern::Identifier(ident) => ident.node,
+                _ => return,
+            },
+            _ => return,
+        };
+        let name = self.resolve(name);
+        if self
+            .exported
+            .as_ref()
+            .is_some_and(|exported| !exported.contains(&name))
+            || !self.emitted.insert(name.clone())
+        {
+            return;
+        }
+
+        match stmt {
+            Statement::Function(decl) => self.generate_function(&name, decl),
+            Statement::Class(decl) => self.generate_class(&name, decl),
+            Statement::Interface(decl) => self.generate_interface(&name, decl),
+            Statement::TypeAlias(decl) => {
+                let generics = self.type_parameters(decl.type_parameters.as_deref());
+                let ty = self.type_to_string(&decl.type_annotation);
+                let prefix = self.prefix("type");
+                self.line(&format!("{}{}{} = {}", prefix, name, generics, ty));
+            }
+            Statement::Enum(decl) => self.generate_enum(&name, decl),
+            Statement::Variable(decl) => {
+                let ty = match (&decl.type_annotation, self.checked.get(&name)) {
+                    (Some(ty), _) => self.type_to_string(ty),
+                    (None, Some(checked)) => checked.ty.clone(),
+                    (None, None) => Self::literal_type(&decl.initializer.kind).to_string(),
+                };
+                if self.in_namespace {
+                    self.line(&format!("declare {}: {}", name, ty));
+                } else {
+                    self.line(&format!("export declare const {}: {}", name, ty));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// `export declare <keyword> ` at top level, `<keyword> ` inside a namespace
+    fn prefix(&self, keyword: &str) -> String {
+        if self.in_namespace {
+            format!("{} ", keyword)
+        } else {
+            format!("export declare {} ", keyword)
+        }
+    }
+
+    fn generate_function(&mut self, name: &str, decl: &FunctionDeclaration) {
+        let signature = self.checked_signature(
+            decl.type_parameters.as_deref(),
+            &decl.parameters,
+            decl.return_type.as_ref(),
+            self.checked.get(name),
+        );
+        let prefix = self.prefix("function");
+        self.line(&format!("{}{}{}", prefix, name, signature));
+    }
+
+    fn generate_class(&mut self, name: &str, class_decl: &ClassDeclaration) {
+        let generics = self.type_parameters(class_decl.type_parameters.as_deref());
+        let keyword = if class_decl.is_abstract {
+            "abstract class"
+        } else {
+            "class"
+        };
+        let mut header = format!("{}{}{}", self.prefix(keyword), name, generics);
+        for parent in class_decl.extends.iter() {
+            header.push_str(&format!(" extends {}", self.type_to_string(parent)));
+        }
+        if !class_decl.implements.is_empty() {
+            let interfaces: Vec<String> = class_decl
+                .implements
+                .iter()
+                .map(|ty| self.type_to_string(ty))
+                .collect();
+            header.push_str(&format!(" implements {}", interfaces.join(", ")));
+        }
+        self.line(&format!("{} {{", header));
+        self.indent += 1;
+
+        let constructor = class_decl
+            .primary_constructor
+            .map(|params| {
+                params
+                    .iter()
+                    .map(|param| {
+                        format!(
+                            "{}: {}",
+                            self.resolve(param.name.node),
+                            self.type_to_string(&param.type_annotation)
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            })
+            .or_else(|| {
+                class_decl.members.iter().find_map(|member| match member {
+                    ClassMember::Constructor(ctor) => Some(self.parameters(&ctor.parameters, &[])),
+                    _ => None,
+                })
+            });
+        if let Some(params) = constructor {
+            self.line(&format!("constructor({})", params));
+        }
+
+        for param in class_decl.primary_constructor.into_iter().flatten() {
+            if matches!(
+                param.access,
+                Some(AccessModifier::Private) | Some(AccessModifier::Protected)
+            ) {
+                continue;
+            }
+            let readonly = if param.is_readonly { "readonly " } else { "" };
+            let ty = self.type_to_string(&param.type_annotation);
+            self.line(&format!(
+                "{}{}: {}",
+                readonly,
+                self.resolve(param.name.node),
+                ty
+            ));
+        }
+
+        for member in class_decl.members.iter() {
+            let (access, is_static) = match member {
+                ClassMember::Property(prop) => (prop.access.as_ref(), prop.is_static),
+                ClassMember::Method(method) => (method.access.as_ref(), method.is_static),
+                ClassMember::Getter(getter) => (getter.access.as_ref(), getter.is_static),
+                ClassMember::Setter(setter) => (setter.access.as_ref(), setter.is_static),
+                ClassMember::Constructor(_) | ClassMember::Operator(_) => continue,
+            };
+            if matches!(
+                access,
+                Some(AccessModifier::Private) | Some(AccessModifier::Protected)
+            ) {
+                continue;
+            }
+            let modifier = if is_static { "static " } else { "" };
+            match member {
+                ClassMember::Property(prop) => {
+                    let readonly = if prop.is_readonly { "readonly " } else { "" };
+                    let ty = self.type_to_string(&prop.type_annotation);
+                    self.line(&format!(
+                        "{}{}{}: {}",
+                        modifier,
+                        readonly,
+                        self.resolve(prop.name.node),
+                        ty
+                    ));
+                }
+                ClassMember::Method(method) => {
+                    let signature = self.signature(
+                        method.type_parameters.as_deref(),
+                        &method.parameters,
+                        method.return_type.as_ref(),
+                    );
+                    self.line(&format!(
+                        "{}{}{}",
+                        modifier,
+                        self.resolve(method.name.node),
+                        signature
+                    ));
+                }
+                ClassMember::Getter(getter) => {
+                    let has_setter = class_decl.members.iter().any(|other| {
+                        matches!(other, ClassMember::Setter(setter) if setter.name.node == getter.name.node)
+                    });
+                    let readonly = if has_setter { "" } else { "readonly " };
+                    let ty = self.type_to_string(&getter.return_type);
+                    self.line(&format!(
+                        "{}{}{}: {}",
+                        modifier,
+                        readonly,
+                        self.resolve(getter.name.node),
+                        ty
+                    ));
+                }
+                ClassMember::Setter(setter) => {
+                    let has_getter = class_decl.members.iter().any(|other| {
+                        matches!(other, ClassMember::Getter(getter) if getter.name.node == setter.name.node)
+                    });
+                    if !has_getter {
+                        let ty = setter
+                            .parameter
+                            .type_annotation
+                            .as_ref()
+                            .map_or_else(|| "unknown".to_string(), |ty| self.type_to_string(ty));
+                        self.line(&format!(
+                            "{}{}: {}",
+                            modifier,
+                            self.resolve(setter.name.node),
+                            ty
+                        ));
+                    }
+                }
+                ClassMember::Constructor(_) | ClassMember::Operator(_) => {}
+            }
+        }
+
+        self.indent -= 1;
+        self.line("}");
+    }
+
+    fn generate_interface(&mut self, name: &str, iface_decl: &InterfaceDeclaration) {
+        let generics = self.type_parameters(iface_decl.type_parameters.as_deref());
+        let mut header = format!("{}{}{}", self.prefix("interface"), name, generics);
+        if !iface_decl.extends.is_empty() {
+            let parents: Vec<String> = iface_decl
+                .extends
+                .iter()
+                .map(|ty| self.type_to_string(ty))
+                .collect();
+            header.push_str(&format!(" extends {}", parents.join(", ")));
+        }
+        self.line(&format!("{} {{", header));
+        self.indent += 1;
+        for member in iface_decl.members.iter() {
+            let line = match member {
+                InterfaceMember::Property(prop) => format!(
+                    "{}{}{}: {}",
+                    if prop.is_readonly { "readonly " } else { "" },
+                    self.resolve(prop.name.node),
+                    if prop.is_optional { "?" } else { "" },
+                    self.type_to_string(&prop.type_annotation)
+                ),
+                InterfaceMember::Method(method) => format!(
+                    "{}{}",
+                    self.resolve(method.name.node),
+                    self.signature(
+                        method.type_parameters.as_deref(),
+                        &method.parameters,
+                        Some(&method.return_type),
+                    )
+                ),
+                InterfaceMember::Index(index) => format!(
+                    "[{}: {}]: {}",
+                    self.resolve(index.key_name.node),
+                    Self::index_key_type(&index.key_type),
+                    self.type_to_string(&index.value_type)
+                ),
+            };
+            self.line(&line);
+        }
+        self.indent -= 1;
+        self.line("}");
+    }
+
+    /// Simple enums become a literal union type plus a namespace of constants;
+    /// rich enums an interface for their instances plus the same namespace.
+    fn generate_enum(&mut self, name: &str, enum_decl: &EnumDeclaration) {
+        let is_rich = !enum_decl.fields.is_empty()
+            || enum_decl.constructor.is_some()
+            || !enum_decl.methods.is_empty();
+
+        let member_types: Vec<String> = enum_decl
+            .members
+            .iter()
+            .enumerate()
+            .map(|(i, member)| match &member.value {
+                Some(EnumValue::Number(n)) => n.to_string(),
+                Some(EnumValue::String(s)) => Self::quote(s),
+                None => i.to_string(),
+            })
+            .collect();
+
+        if is_rich {
+            let mut header = format!("{}{}", self.prefix("interface"), name);
+            if !enum_decl.implements.is_empty() {
+                let parents: Vec<String> = enum_decl
+                    .implements
+                    .iter()
+                    .map(|ty| self.type_to_string(ty))
+                    .collect();
+                header.push_str(&format!(" extends {}", parents.join(", ")));
+            }
+            self.line(&format!("{} {{", header));
+            self.indent += 1;
+            for field in enum_decl.fields.iter() {
+                let ty = self.type_to_string(&field.type_annotation);
+                self.line(&format!(
+                    "readonly {}: {}",
+                    self.resolve(field.name.node),
+                    ty
+                ));
+            }
+            for method in enum_decl.methods.iter() {
+                let signature =
+                    self.signature(None, &method.parameters, method.return_type.as_ref());
+                self.line(&format!("{}{}", self.resolve(method.name.node), signature));
+            }
+            self.indent -= 1;
+            self.line("}");
+        } else {
+            let prefix = self.prefix("type");
+            self.line(&format!(
+                "{}{} = {}",
+                prefix,
+                name,
+                member_types.join(" | ")
+            ));
+        }
+
+        let prefix = self.prefix("namespace");
+        self.line(&format!("{}{} {{", prefix, name));
+        self.indent += 1;
+        for (member, literal) in enum_decl.members.iter().zip(&member_types) {
+            let ty = if is_rich { name } else { literal.as_str() };
+            self.line(&format!(
+                "declare {}: {}",
+                self.resolve(member.name.node),
+                ty
+            ));
+        }
+        self.indent -= 1;
+        self.line("}");
+    }
+
+    /// `<T>(params): R` for function-like declarations; a missing annotation
+    /// becomes `unknown`
+    fn signature(
+        &self,
+        type_parameters: Option<&[TypeParameter]>,
+        parameters: &[Parameter],
+        return_type: Option<&Type>,
+    ) -> String {
+        self.checked_signature(type_parameters, parameters, return_type, None)
+    }
+
+    /// [`Self::signature`] taking missing annotations from the checked type
+    fn checked_signature(
+        &self,
+        type_parameters: Option<&[TypeParameter]>,
+        parameters: &[Parameter],
+        return_type: Option<&Type>,
+        checked: Option<&CheckedType>,
+    ) -> String {
+        let return_type = match (return_type, checked.and_then(|c| c.return_type.as_ref())) {
+            (Some(ty), _) => self.type_to_string(ty),
+            (None, Some(checked)) => checked.clone(),
+            (None, None) => "unknown".to_string(),
+        };
+        format!(
+            "{}({}): {}",
+            self.type_parameters(type_parameters),
+            self.parameters(parameters, checked.map_or(&[], |c| c.parameters.as_slice())),
+            return_type
+        )
+    }
+
+    /// Parameter list, with `checked` types for parameters left unannotated
+    fn parameters(&self, parameters: &[Parameter], checked: &[String]) -> String {
+        parameters
+            .iter()
+            .enumerate()
+            .map(|(i, param)| {
+                let name = match &param.pattern {
+                    Pattern::Identifier(ident) => self.resolve(ident.node),
+                    _ => format!("p{}", i + 1),
+                };
+                let ty = match (&param.type_annotation, checked.get(i)) {
+                    (Some(ty), _) => self.type_to_string(ty),
+                    (None, Some(checked)) => checked.clone(),
+                    (None, None) => "unknown".to_string(),
+                };
+                if param.is_rest {
+                    format!("...{}: {}", name, ty)
+                } else if param.is_optional || param.default.is_some() {
+                    format!("{}?: {}", name, ty)
+                } else {
+                    format!("{}: {}", name, ty)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn type_parameters(&self, type_parameters: Option<&[TypeParameter]>) -> String {
+        let params: Vec<String> = type_parameters
+            .into_iter()
+            .flatten()
+            .map(|param| {
+                let mut text = self.resolve(param.name.node);
+                if let Some(constraint) = &param.constraint {
+                    text.push_str(&format!(" extends {}", self.type_to_string(constraint)));
+                }
+                if let Some(default) = &param.default {
+                    text.push_str(&format!(" = {}", self.type_to_string(default)));
+                }
+                text
+            })
+            .collect();
+        if params.is_empty() {
+            String::new()
+        } else {
+            format!("<{}>", params.join(", "))
+        }
+    }
+
+    /// Render a type back into TypedLua source syntax
+    pub fn type_to_string(&self, ty: &Type) -> String {
+        match &ty.kind {
+            TypeKind::Primitive(p) => match p {
+                PrimitiveType::Nil => "nil",
+                PrimitiveType::Boolean => "boolean",
+                PrimitiveType::Number => "number",
+                PrimitiveType::Integer => "integer",
+                PrimitiveType::String => "string",
+                PrimitiveType::Unknown => "unknown",
+                PrimitiveType::Never => "never",
+                PrimitiveType::Void => "void",
+                PrimitiveType::Table => "table",
+                PrimitiveType::Coroutine => "coroutine",
+            }
+            .to_string(),
+            TypeKind::Reference(type_ref) => {
+                let name = self.resolve(type_ref.name.node);
+                match &type_ref.type_arguments {
+                    Some(args) if !args.is_empty() => format!(
+                        "{}<{}>",
+                        name,
+                        args.iter()
+                            .map(|arg| self.type_to_string(arg))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                    _ => name,
+                }
+            }
+            TypeKind::Union(types) => self.join_types(types, " | "),
+            TypeKind::Intersection(types) => self.join_types(types, " & "),
+            TypeKind::Object(object) => {
+                let members: Vec<String> = object
+                    .members
+                    .iter()
+                    .map(|member| match member {
+                        ObjectTypeMember::Property(prop) => format!(
+                            "{}{}{}: {}",
+                            if prop.is_readonly { "readonly " } else { "" },
+                            self.resolve(prop.name.node),
+                            if prop.is_optional { "?" } else { "" },
+                            self.type_to_string(&prop.type_annotation)
+                        ),
+                        ObjectTypeMember::Method(method) => format!(
+                            "{}{}",
+                            self.resolve(method.name.node),
+                            self.signature(
+                                method.type_parameters.as_deref(),
+                                &method.parameters,
+                                Some(&method.return_type),
+                            )
+                        ),
+                        ObjectTypeMember::Index(index) => format!(
+                            "[{}: {}]: {}",
+                            self.resolve(index.key_name.node),
+                            Self::index_key_type(&index.key_type),
+                            self.type_to_string(&index.value_type)
+                        ),
+                    })
+                    .collect();
+                format!("{{ {} }}", members.join(", "))
+            }
+            TypeKind::Array(inner) => format!("{}[]", self.operand(inner)),
+            TypeKind::Tuple(types) => format!("[{}]", self.join_types(types, ", ")),
+            TypeKind::Function(func) => format!(
+                "({}) => {}",
+                self.parameters(&func.parameters, &[]),
+                self.type_to_string(&func.return_type)
+            ),
+            TypeKind::Literal(literal) => match literal {
+                Literal::Nil => "nil".to_string(),
+                Literal::Boolean(b) => b.to_string(),
+                Literal::Number(n) => n.to_string(),
+                Literal::Integer(i) => i.to_string(),
+                Literal::String(s) => Self::quote(s),
+            },
+            TypeKind::KeyOf(inner) => format!("keyof {}", self.operand(inner)),
+            TypeKind::IndexAccess(object, index) => {
+                format!("{}[{}]", self.operand(object), self.type_to_string(index))
+            }
+            TypeKind::Conditional(cond) => format!(
+                "{} extends {} ? {} : {}",
+                self.operand(&cond.check_type),
+                self.operand(&cond.extends_type),
+                self.type_to_string(&cond.true_type),
+                self.type_to_string(&cond.false_type)
+            ),
+            TypeKind::Mapped(mapped) => format!(
+                "{{ {}[{} in {}]{}: {} }}",
+                if mapped.is_readonly { "readonly " } else { "" },
+                self.resolve(mapped.type_parameter.name.node),
+                self.type_to_string(&mapped.in_type),
+                if mapped.is_optional { "?" } else { "" },
+                self.type_to_string(&mapped.value_type)
+            ),
+            TypeKind::Nullable(inner) => format!("{}?", self.operand(inner)),
+            TypeKind::Parenthesized(inner) => format!("({})", self.type_to_string(inner)),
+            // Template literal types widen to their base type; `typeof` queries
+            // refer to values that a declaration file does not carry
+            TypeKind::TemplateLiteral(_) => "string".to_string(),
+            TypeKind::TypeQuery(_) => "unknown".to_string(),
+        }
+    }
+
+    /// Parenthesize compound types used as the operand of a postfix operator
+    fn operand(&self, ty: &Type) -> String {
+        let text = self.type_to_string(ty);
+        match ty.kind {
+            TypeKind::Union(_)
+            | TypeKind::Intersection(_)
+            | TypeKind::Function(_)
+            | TypeKind::Conditional(_)
+            | TypeKind::KeyOf(_) => format!("({})", text),
+            _ => text,
+        }
+    }
+
+    fn join_types(&self, types: &[Type], separator: &str) -> String {
+        types
+            .iter()
+            .map(|ty| self.type_to_string(ty))
+            .collect::<Vec<_>>()
+            .join(separator)
+    }
+
+    fn index_key_type(key_type: &IndexKeyType) -> &'static str {
+        match key_type {
+            IndexKeyType::String => "string",
+            IndexKeyType::Number => "number",
+        }
+    }
+
+    fn literal_type(kind: &ExpressionKind) -> &'static str {
+        match kind {
+            ExpressionKind::Literal(Literal::Boolean(_)) => "boolean",
+            ExpressionKind::Literal(Literal::Number(_)) => "number",
+            ExpressionKind::Literal(Literal::Integer(_)) => "integer",
+            ExpressionKind::Literal(Literal::String(_)) => "string",
+            _ => "unknown",
+        }
+    }
+
+    fn quote(s: &str) -> String {
+        format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+
+    fn line(&mut self, text: &str) {
+        for _ in 0..self.indent {
+            self.output.push_str("    ");
+        }
+        self.output.push_str(text);
+        self.output.push('\n');
+    }
+
+    fn resolve(&self, id: StringId) -> String {
+        self.interner.resolve(id).to_string()
+    }
+}
@@ -1,45 +1,127 @@
-use super::CodeGenerator;
+use super::{CodeGenerator, OptimizationLevel};
+use serde::{Deserialize, Serialize};
+use typedlua_parser::ast::expression::ExpressionKind;
+use typedlua_parser::ast::pattern::Pattern;
 use typedlua_parser::ast::statement::*;
+use typedlua_parser::ast::types::TypeKind;
+use typedlua_parser::string_interner::StringId;
+
+/// Which builds keep `@trace` decorators
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Traces {
+    /// Unoptimized (O0) builds only (default)
+    #[default]
+    Debug,
+    /// Every build, whatever the optimization level
+    Always,
+    /// No build; `@trace` is removed
+    Off,
+}
+
+/// How the compiler applies a decorator
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Resolution {
+    /// Passed to `__decorate` at runtime
+    Runtime,
+    /// `@bind` on an instance method, lowered to a binding in `new`
+    Bind,
+    /// Handled entirely at compile time (`@validate`, `@trace` when
+    /// [`Traces`] leaves it out, optimizer hints)
+    Drop,
+}
+
+/// The static part of the context table a decorator receives
+pub(crate) struct DecoratorContext {
+    /// `class`, `method`, `getter`, `setter` or `field`
+    pub kind: &'static str,
+    pub name: String,
+    pub is_static: bool,
+    pub is_private: bool,
+}
+
+impl DecoratorContext {
+    pub fn class(name: &str) -> Self {
+        DecoratorContext {
+            kind: "class",
+            name: name.to_string(),
+            is_static: false,
+            is_private: false,
+        }
+    }
+
+    pub fn member(
+        kind: &'static str,
+        name: String,
+        is_static: bool,
+        access: Option<&AccessModifier>,
+    ) -> Self {
+        DecoratorContext {
+            kind,
+            name,
+            is_static,
+            is_private: matches!(access, Some(AccessModifier::Private)),
+        }
+    }
+
+    fn render(&self, generator: &CodeGenerator) -> String {
+        let kind = generator.quote_string(self.kind);
+        let name = generator.quote_string(&self.name);
+        if self.kind == "class" {
+            return format!("{{ kind = {}, name = {} }}", kind, name);
+        }
+        format!(
+            "{{ kind = {}, name = {}, static = {}, private = {} }}",
+            kind, name, self.is_static, self.is_private
+        )
+    }
+}
 
 impl CodeGenerator {
-    pub fn generate_decorator_call(&mut self, decorator: &Decorator, target: &str) {
-        use typedlua_parser::ast::statement::DecoratorExpression;
+    /// Apply `decorators` through the runtime `__decorate` helper. Decorator
+    /// expressions are evaluated top to bottom and applied bottom-up; the
+    /// result is assigned back to `target` unless this is a field.
+    pub(crate) fn generate_decorator_application(
+        &mut self,
+        class_name: &str,
+        target: &str,
+        decorators: &[Decorator],
+        context: &DecoratorContext,
+    ) {
+        let decorators: Vec<&Decorator> = decorators
+            .iter()
+            .filter(|d| {
+                self.resolve_decorator(d, context.kind, context.is_static) == Resolution::Runtime
+            })
+            .collect();
+        if decorators.is_empty() {
+            return;
+        }
 
-        match &decorator.expression {
-            DecoratorExpression::Identifier(name) => {
-                let decorator_name = self.resolve(name.node);
-                self.write(&decorator_name);
-                self.write("(");
-                self.write(target);
-                self.write(")");
-            }
-            DecoratorExpression::Call {
-                callee, arguments, ..
-            } => {
-                self.generate_decorator_expression(callee);
-                self.write("(");
-                for (i, arg) in arguments.iter().enumerate() {
-                    if i > 0 {
-                        self.write(", ");
-                    }
-                    self.generate_expression(arg);
-                }
-                self.write(")(");
-                self.write(target);
-                self.write(")");
-            }
-            DecoratorExpression::Member {
-                object, property, ..
-            } => {
-                self.generate_decorator_expression(object);
-                self.write(".");
-                let prop_name = self.resolve(property.node);
-                self.write(&prop_name);
-                self.write("(");
-                self.write(target);
-                self.write(")");
+        if matches!(context.kind, "method" | "getter" | "setter") {
+            self.write_access_owner(class_name, target);
+        }
+        if self.emit_full_reflection() {
+            for decorator in &decorators {
+                self.capture_decorator_args(&decorator.expression);
             }
         }
+        self.write_indent();
+        if context.kind == "field" {
+            self.write("__decorate(");
+            self.write(class_name);
+            self.write(", nil, {");
+        } else {
+            self.write(&format!(
+                "{} = __decorate({}, {}, {{",
+                target, class_name, target
+            ));
+        }
+        for (i, decorator) in decorators.iter().enumerate() {
+            self.write(if i > 0 { ", " } else { " " });
+            self.generate_decorator_expression(&decorator.expression);
+        }
+        self.writeln(&format!(" }}, {})", context.render(self)));
     }
 
     pub fn generate_decorator_expression(
@@ -54,15 +136,21 @@ impl CodeGenerator {
                 self.write(&name_str);
             }
             DecoratorExpression::Call {
-                callee, arguments, ..
+                callee,
+                arguments,
+                span,
             } => {
                 self.generate_decorator_expression(callee);
                 self.write("(");
+                let captured = self.decorator_args.get(&(span.start, span.end)).cloned();
                 for (i, arg) in arguments.iter().enumerate() {
                     if i > 0 {
                         self.write(", ");
                     }
-                    self.generate_expression(arg);
+                    match &captured {
+                        Some(args) => self.write(&format!("{}[{}]", args, i + 1)),
+                        None => self.generate_expression(arg),
+                    }
                 }
                 self.write(")");
             }
@@ -77,8 +165,105 @@ impl CodeGenerator {
         }
     }
 
+    /// Evaluate the arguments of a decorator call into a local table first,
+    /// so the call and the full reflection metadata share one evaluation.
+    /// All-literal argument lists are left inline; re-printing them is free.
+    fn capture_decorator_args(&mut self, expr: &DecoratorExpression) {
+        let DecoratorExpression::Call {
+            arguments, span, ..
+        } = expr
+        else {
+            return;
+        };
+        if arguments
+            .iter()
+            .all(|arg| matches!(arg.kind, ExpressionKind::Literal(..)))
+        {
+            return;
+        }
+        let local = format!("__decoratorArgs{}", self.decorator_args.len() + 1);
+        self.write_indent();
+        self.write(&format!("local {} = {{ ", local));
+        for (i, arg) in arguments.iter().enumerate() {
+            if i > 0 {
+                self.write(", ");
+            }
+            self.generate_expression(arg);
+        }
+        self.writeln(" }");
+        self.decorator_args.insert((span.start, span.end), local);
+    }
+
     pub fn is_built_in_decorator(&self, name: &str) -> bool {
-        matches!(name, "readonly" | "sealed" | "deprecated")
+        matches!(
+            name,
+            "readonly" | "sealed" | "deprecated" | "memoize" | "bind" | "frozen" | "once" | "trace"
+        )
+    }
+
+    /// `@inline`, `@noinline`, `@pure` and `@cold` only guide the optimizer
+    pub fn is_optimizer_hint(&self, name: &str) -> bool {
+        matches!(name, "inline" | "noinline" | "pure" | "cold")
+    }
+
+    /// Decide how a decorator on a `kind` target is applied. Built-ins whose
+    /// effect is known at compile time are resolved here instead of wrapping
+    /// the target at runtime: `@trace` is kept only in the builds [`Traces`]
+    /// selects, and `@bind` on an instance method becomes a plain binding in
+    /// `new`.
+    fn resolve_decorator(&self, decorator: &Decorator, kind: &str, is_static: bool) -> Resolution {
+        if self.is_validate_decorator(decorator) {
+            return Resolution::Drop;
+        }
+        match self
+            .statically_known_decorator(&decorator.expression)
+            .as_deref()
+        {
+            Some("trace") if !self.traces_enabled() => Resolution::Drop,
+            Some("bind") if kind == "method" && !is_static => Resolution::Bind,
+            Some(name) if self.is_optimizer_hint(name) => Resolution::Drop,
+            _ => Resolution::Runtime,
+        }
+    }
+
+    fn traces_enabled(&self) -> bool {
+        match self.traces {
+            Traces::Debug => self.optimization_level.effective() == OptimizationLevel::O0,
+            Traces::Always => true,
+            Traces::Off => false,
+        }
+    }
+
+    /// Name of a built-in decorator or optimizer hint used without arguments,
+    /// as `@name` or `@TypedLua.name`, unless the module declares its own `name`
+    fn statically_known_decorator(&self, expr: &DecoratorExpression) -> Option<String> {
+        let name = match expr {
+            DecoratorExpression::Identifier(name) => {
+                let name = self.resolve(name.node);
+                if self.shadowed_decorators.contains(&name) {
+                    return None;
+                }
+                name
+            }
+            DecoratorExpression::Member {
+                object: DecoratorExpression::Identifier(object),
+                property,
+                ..
+            } if self.resolve(object.node) == "TypedLua" => self.resolve(property.node),
+            _ => return None,
+        };
+        (self.is_built_in_decorator(&name) || self.is_optimizer_hint(&name)).then_some(name)
+    }
+
+    /// Record names of built-in decorators and optimizer hints the module
+    /// declares or imports
+    fn collect_shadowed_decorators(&mut self, statements: &[Statement]) {
+        for id in top_level_bindings(statements) {
+            let name = self.resolve(id);
+            if self.is_built_in_decorator(&name) || self.is_optimizer_hint(&name) {
+                self.shadowed_decorators.insert(name);
+            }
+        }
     }
 
     pub fn detect_decorators(&mut self, program: &typedlua_parser::ast::Program) {
@@ -89,11 +274,139 @@ impl CodeGenerator {
         &mut self,
         statements: &[typedlua_parser::ast::statement::Statement],
     ) {
+        self.collect_shadowed_decorators(statements);
         for statement in statements {
+            let statement = match statement {
+                Statement::Export(export) => match &export.kind {
+                    ExportKind::Declaration(decl) => &**decl,
+                    _ => continue,
+                },
+                other => other,
+            };
             if self.statement_uses_built_in_decorators(statement) {
                 self.uses_built_in_decorators = true;
-                return;
             }
+            if let Statement::Class(class_decl) = statement {
+                if self.class_uses_decorators(class_decl) {
+                    self.uses_decorators = true;
+                }
+            }
+        }
+    }
+
+    /// Decorators on a class member with the given resolution
+    fn member_decorators<'d, 'arena>(
+        &self,
+        member: &'d ClassMember<'arena>,
+        resolution: Resolution,
+    ) -> Vec<&'d Decorator<'arena>> {
+        let (decorators, kind, is_static): (&[Decorator], &str, bool) = match member {
+            ClassMember::Method(method) => (method.decorators, "method", method.is_static),
+            ClassMember::Property(prop) => (prop.decorators, "field", prop.is_static),
+            ClassMember::Getter(getter) => (getter.decorators, "getter", getter.is_static),
+            ClassMember::Setter(setter) => (setter.decorators, "setter", setter.is_static),
+            ClassMember::Operator(op) => (op.decorators, "method", false),
+            ClassMember::Constructor(_) => (&[], "method", false),
+        };
+        decorators
+            .iter()
+            .filter(|d| self.resolve_decorator(d, kind, is_static) == resolution)
+            .collect()
+    }
+
+    /// Name of the class this one extends, when it is a plain reference
+    fn parent_class_name(&self, class_decl: &ClassDeclaration) -> Option<String> {
+        match class_decl.extends.as_ref().map(|ty| &ty.kind) {
+            Some(TypeKind::Reference(type_ref)) => Some(self.resolve(type_ref.name.node)),
+            _ => None,
+        }
+    }
+
+    /// Record the methods `new` binds to `self` for statically resolved
+    /// `@bind`, including those inherited from a class in this module
+    pub(crate) fn register_bound_methods(
+        &mut self,
+        class_name: &str,
+        class_decl: &ClassDeclaration,
+    ) {
+        let mut methods = self
+            .parent_class_name(class_decl)
+            .and_then(|parent| self.bound_methods.get(&parent).cloned())
+            .unwrap_or_default();
+        for member in class_decl.members.iter() {
+            if let ClassMember::Method(method) = member {
+                let name = self.resolve(method.name.node);
+                if !self.member_decorators(member, Resolution::Bind).is_empty()
+                    && !methods.contains(&name)
+                {
+                    methods.push(name);
+                }
+            }
+        }
+        if !methods.is_empty() {
+            self.bound_methods.insert(class_name.to_string(), methods);
+        }
+    }
+
+    fn class_uses_decorators(&self, class_decl: &ClassDeclaration) -> bool {
+        class_decl
+            .decorators
+            .iter()
+            .any(|d| self.resolve_decorator(d, "class", false) == Resolution::Runtime)
+            || class_decl.members.iter().any(|member| {
+                !self
+                    .member_decorators(member, Resolution::Runtime)
+                    .is_empty()
+            })
+    }
+
+    /// Whether instances of the class run decorator initializers: it has
+    /// decorated instance members, or extends a class in this module that does
+    pub(crate) fn class_has_instance_initializers(&self, class_decl: &ClassDeclaration) -> bool {
+        let is_static = |member: &ClassMember| match member {
+            ClassMember::Method(method) => method.is_static,
+            ClassMember::Property(prop) => prop.is_static,
+            ClassMember::Getter(getter) => getter.is_static,
+            ClassMember::Setter(setter) => setter.is_static,
+            ClassMember::Operator(_) | ClassMember::Constructor(_) => false,
+        };
+        let own = class_decl.members.iter().any(|member| {
+            !is_static(member)
+                && !self
+                    .member_decorators(member, Resolution::Runtime)
+                    .is_empty()
+        });
+        let inherited = self
+            .parent_class_name(class_decl)
+            .is_some_and(|parent| self.instance_initializer_classes.contains(&parent));
+        own || inherited
+    }
+
+    /// `__initInstance(Class, self)` and the statically bound methods at the
+    /// start of `new`; field initializers follow the constructor body (see
+    /// [`Self::write_field_initializers`])
+    pub(crate) fn write_instance_initializers(&mut self, class_name: &str) {
+        if self.instance_initializer_classes.contains(class_name) {
+            self.write_indent();
+            self.writeln(&format!("__initInstance({}, self)", class_name));
+        }
+        if let Some(methods) = self.bound_methods.get(class_name).cloned() {
+            for method in methods {
+                self.write_indent();
+                self.writeln(&format!(
+                    "self.{} = function(...) return {}.{}(self, ...) end",
+                    method, class_name, method
+                ));
+            }
+        }
+    }
+
+    /// `__initFields(Class, self)` after the constructor body, so field
+    /// decorator initializers see the value the constructor assigned
+    pub(crate) fn write_field_initializers(&mut self, class_name: &str) {
+        if self.instance_initializer_classes.contains(class_name) {
+            self.write_indent();
+            self.writeln(&format!("__initFields({}, self)", class_name));
         }
     }
 
@@ -127,12 +440,7 @@ impl CodeGenerator {
         }
     }
 
-    pub fn is_decorator_built_in(
-        &self,
-        expr: &typedlua_parser::ast::statement::DecoratorExpression,
-    ) -> bool {
-        use typedlua_parser::ast::statement::DecoratorExpression;
-
+    pub fn is_decorator_built_in(&self, expr: &DecoratorExpression) -> bool {
         match expr {
             DecoratorExpression::Identifier(name) => {
                 let name_str = self.resolve(name.node);
@@ -164,4 +472,50 @@ impl CodeGenerator {
         self.writeln(typedlua_runtime::decorator::DECORATOR_RUNTIME);
         self.writeln("");
     }
+
+    pub fn embed_decorator_protocol(&mut self) {
+        self.writeln(typedlua_runtime::decorator::DECORATOR_PROTOCOL);
+    }
+}
+
+/// Names of functions, variables and imports declared at the top level of a
+/// module, which shadow decorators of the same name
+pub(crate) fn top_level_bindings(statements: &[Statement]) -> Vec<StringId> {
+    let mut declared = Vec::new();
+    for statement in statements {
+        let statement = match statement {
+            Statement::Export(export) => match &export.kind {
+                ExportKind::Declaration(decl) => &**decl,
+                _ => continue,
+            },
+            other => other,
+        };
+        match statement {
+            Statement::Function(func) => declared.push(func.name.node),
+            Statement::Variable(decl) => {
+                if let Pattern::Identifier(ident) = &decl.pattern {
+                    declared.push(ident.node);
+                }
+            }
+            Statement::Import(import) => match &import.clause {
+                ImportClause::Default(ident) => declared.push(ident.node),
+                ImportClause::Named(specs) => declared.extend(
+                    specs
+                        .iter()
+                        .map(|spec| spec.local.as_ref().unwrap_or(&spec.imported).node),
+                ),
+                ImportClause::Mixed { default, named } => {
+                    declared.push(default.node);
+                    declared.extend(
+                        named
+                            .iter()
+                            .map(|spec| spec.local.as_ref().unwrap_or(&spec.imported).node),
+                    );
+                }
+                ImportClause::Namespace(_) | ImportClause::TypeOnly(_) => {}
+            },
+            _ => {}
+        }
+    }
+    declared
 }
@@ -50,21 +50,26 @@ impl Emitter {
         }
     }
 
+    /// Write a line. Minified output keeps its line breaks here as well: the
+    /// generator's finished chunk is run through the minifier, which decides
+    /// where separators are actually needed.
     pub fn writeln(&mut self, s: &str) {
-        match self.output_format {
-            OutputFormat::Minified => {
-                self.output.push_str(s);
-            }
-            OutputFormat::Compact | OutputFormat::Readable => {
-                self.output.push_str(s);
-                self.output.push('\n');
-            }
-        }
+        self.output.push_str(s);
+        self.output.push('\n');
         if let Some(source_map) = &mut self.source_map {
             source_map.advance(s);
-            if !matches!(self.output_format, OutputFormat::Minified) {
-                source_map.advance("\n");
-            }
+            source_map.advance("\n");
+        }
+    }
+
+    /// Append text to the last written line, before its line break
+    pub fn append_to_last_line(&mut self, s: &str) {
+        if self.output.ends_with('\n') {
+            self.output.pop();
+            self.output.push_str(s);
+            self.output.push('\n');
+        } else {
+            self.write(s);
         }
     }
 
@@ -13,6 +13,7 @@ impl CodeGenerator {
             && enum_decl.constructor.is_none()
             && enum_decl.methods.is_empty()
         {
+            self.annotate_enum(&enum_name, false);
             self.write_indent();
             self.write("local ");
             self.write(&enum_name);
@@ -46,6 +47,7 @@ impl CodeGenerator {
             self.dedent();
             self.write_indent();
             self.writeln("}");
+            self.write_enum_metadata(&enum_name, enum_decl);
         } else {
             self.generate_rich_enum_declaration(enum_decl, &enum_name);
         }
@@ -59,6 +61,7 @@ impl CodeGenerator {
         let mt_name = format!("{}__mt", enum_name);
 
         self.writeln("");
+        self.annotate_enum(enum_name, true);
         self.write_indent();
         self.writeln(&format!("local {} = {}", enum_name, "{}"));
 
@@ -202,6 +205,7 @@ impl CodeGenerator {
         }
         self.write_indent();
         self.writeln(&format!("setmetatable({}, {})", enum_name, mt_name));
+        self.write_enum_metadata(enum_name, enum_decl);
 
         let is_o3 = self.optimization_level.effective() >= OptimizationLevel::O3;
 
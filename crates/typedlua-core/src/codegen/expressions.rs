@@ -1,6 +1,7 @@
 use super::dedent;
 use super::CodeGenerator;
 use crate::config::OptimizationLevel;
+use crate::optimizer::{match_arm_span, CounterKind};
 use typedlua_parser::ast::expression::*;
 use typedlua_parser::ast::pattern::Pattern;
 use typedlua_parser::prelude::{MatchArmBody, MatchExpression};
@@ -116,19 +117,16 @@ impl CodeGenerator {
             ExpressionKind::Assignment(target, op, value) => {
                 self.generate_assignment_expression(target, *op, value);
             }
-            ExpressionKind::Call(callee, args, _) => {
-                self.generate_call_expression(callee, args);
+            ExpressionKind::Call(callee, args, type_args) => {
+                if !self.generate_intrinsic_call(callee, args, *type_args) {
+                    self.generate_call_expression(callee, args);
+                }
             }
             ExpressionKind::New(constructor, args, _type_args) => {
                 self.write("(");
                 self.generate_expression(constructor);
                 self.write(".new(");
-                for (i, arg) in args.iter().enumerate() {
-                    if i > 0 {
-                        self.write(", ");
-                    }
-                    self.generate_argument(arg);
-                }
+                self.generate_arguments(args);
                 self.write("))");
             }
             ExpressionKind::Member(object, member) => {
@@ -170,24 +168,7 @@ impl CodeGenerator {
                     }
                     self.write("}");
                 } else {
-                    self.write("(function() local __arr = {} ");
-
-                    for elem in elements.iter() {
-                        match elem {
-                            ArrayElement::Expression(expr) => {
-                                self.write("table.insert(__arr, ");
-                                self.generate_expression(expr);
-                                self.write(") ");
-                            }
-                            ArrayElement::Spread(expr) => {
-                                self.write("for _, __v in ipairs(");
-                                self.generate_expression(expr);
-                                self.write(") do table.insert(__arr, __v) end ");
-                            }
-                        }
-                    }
-
-                    self.write("return __arr end)()");
+                    self.generate_spread_array(elements);
                 }
             }
             ExpressionKind::Object(props) => {
@@ -205,7 +186,27 @@ impl CodeGenerator {
                     }
                     self.write("}");
                 } else {
-                    self.write("(function() local __obj = {} ");
+                    // Declare the literal's own keys up front so the constructor
+                    // sizes the hash part and the assignments don't rehash
+                    let mut keys: Vec<String> = Vec::new();
+                    for prop in props.iter() {
+                        if let ObjectProperty::Property { key, .. } = prop {
+                            let key_str = self.resolve(key.node);
+                            if !keys.contains(&key_str) {
+                                keys.push(key_str);
+                            }
+                        }
+                    }
+                    if keys.is_empty() {
+                        self.write("(function() local __obj = {} ");
+                    } else {
+                        let slots: Vec<String> =
+                            keys.iter().map(|key| format!("{} = nil", key)).collect();
+                        self.write(&format!(
+                            "(function() local __obj = {{ {} }} ",
+                            slots.join(", ")
+                        ));
+                    }
 
                     for prop in props.iter() {
                         match prop {
@@ -237,14 +238,11 @@ impl CodeGenerator {
             }
             ExpressionKind::Function(func_expr) => {
                 self.write("function(");
-                for (i, param) in func_expr.parameters.iter().enumerate() {
-                    if i > 0 {
-                        self.write(", ");
-                    }
-                    self.generate_pattern(&param.pattern);
-                }
+                let rest_param = self.generate_parameter_list(&func_expr.parameters);
                 self.write(")\n");
                 self.indent();
+                self.generate_rest_parameter_init(rest_param);
+                self.write_profile_count(CounterKind::Closure, expr.span);
                 self.generate_block(&func_expr.body);
                 self.dedent();
                 self.write_indent();
@@ -252,14 +250,11 @@ impl CodeGenerator {
             }
             ExpressionKind::Arrow(arrow_expr) => {
                 self.write("function(");
-                for (i, param) in arrow_expr.parameters.iter().enumerate() {
-                    if i > 0 {
-                        self.write(", ");
-                    }
-                    self.generate_pattern(&param.pattern);
-                }
+                let rest_param = self.generate_parameter_list(&arrow_expr.parameters);
                 self.write(")\n");
                 self.indent();
+                self.generate_rest_parameter_init(rest_param);
+                self.write_profile_count(CounterKind::Closure, expr.span);
                 match &arrow_expr.body {
                     ArrowBody::Expression(expr) => {
                         self.write_indent();
@@ -294,18 +289,7 @@ impl CodeGenerator {
                     self.generate_expression(left);
                     if !arguments.is_empty() {
                         self.write(", ");
-                        for (i, arg) in arguments.iter().enumerate() {
-                            if i > 0 {
-                                self.write(", ");
-                            }
-                            if arg.is_spread {
-                                self.write("table.unpack(");
-                                self.generate_expression(&arg.value);
-                                self.write(")");
-                            } else {
-                                self.generate_expression(&arg.value);
-                            }
-                        }
+                        self.generate_arguments(arguments);
                     }
                     self.write(")");
                 }
@@ -362,10 +346,8 @@ impl CodeGenerator {
                     }
                     first = false;
 
-                    let dedented = dedent(s);
-                    self.write("\"");
-                    self.write(&dedented.replace('\\', "\\\\").replace('"', "\\\""));
-                    self.write("\"");
+                    let quoted = self.quote_string(&dedent(s));
+                    self.write(&quoted);
 
                     if expression_iter.peek().is_some() {
                         self.write(" .. tostring(");
@@ -375,7 +357,8 @@ impl CodeGenerator {
                 }
 
                 if first {
-                    self.write("\"\"");
+                    let quoted = self.quote_string("");
+                    self.write(&quoted);
                 }
                 self.write(")");
             }
@@ -461,12 +444,7 @@ impl CodeGenerator {
                     let method_str = self.resolve(method.node);
                     self.write(&method_str);
                     self.write("(");
-                    for (i, arg) in args.iter().enumerate() {
-                        if i > 0 {
-                            self.write(", ");
-                        }
-                        self.generate_argument(arg);
-                    }
+                    self.generate_arguments(args);
                     self.write(")");
                 } else if self.is_simple_expression(object) {
                     self.write("(");
@@ -477,12 +455,7 @@ impl CodeGenerator {
                     let method_str = self.resolve(method.node);
                     self.write(&method_str);
                     self.write("(");
-                    for (i, arg) in args.iter().enumerate() {
-                        if i > 0 {
-                            self.write(", ");
-                        }
-                        self.generate_argument(arg);
-                    }
+                    self.generate_arguments(args);
                     self.write(") or nil)");
                 } else {
                     self.write("(function() local __t = ");
@@ -491,12 +464,7 @@ impl CodeGenerator {
                     let method_str = self.resolve(method.node);
                     self.write(&method_str);
                     self.write("(");
-                    for (i, arg) in args.iter().enumerate() {
-                        if i > 0 {
-                            self.write(", ");
-                        }
-                        self.generate_argument(arg);
-                    }
+                    self.generate_arguments(args);
                     self.writeln(") else return nil end end)()");
                 }
             }
@@ -583,6 +551,7 @@ impl CodeGenerator {
             self.indent();
 
             self.generate_pattern_bindings(&arm.pattern, "__match_value");
+            self.write_profile_count(CounterKind::Branch, match_arm_span(&arm.body));
 
             self.write_indent();
             match &arm.body {
@@ -1,5 +1,5 @@
 use super::super::CodeGenerator;
-use typedlua_parser::ast::expression::ExpressionKind;
+use typedlua_parser::ast::expression::{Argument, Expression, ExpressionKind};
 
 impl CodeGenerator {
     pub fn generate_call_expression(
@@ -15,12 +15,7 @@ impl CodeGenerator {
                 if !args.is_empty() {
                     self.write(", ");
                 }
-                for (i, arg) in args.iter().enumerate() {
-                    if i > 0 {
-                        self.write(", ");
-                    }
-                    self.generate_argument(arg);
-                }
+                self.generate_arguments(args);
                 self.write(")");
             } else {
                 self.write("nil -- super() without parent class");
@@ -43,12 +38,7 @@ impl CodeGenerator {
             }
         }
 
-        for (i, arg) in args.iter().enumerate() {
-            if i > 0 {
-                self.write(", ");
-            }
-            self.generate_argument(arg);
-        }
+        self.generate_arguments(args);
         self.write(")");
     }
 
@@ -59,17 +49,24 @@ impl CodeGenerator {
         args: &[typedlua_parser::ast::expression::Argument],
     ) {
         let method_str = self.resolve(method.node);
+        // `super:method()` calls the parent's method directly on `self`
+        if let (ExpressionKind::SuperKeyword, Some(parent)) = (&obj.kind, self.current_class_parent)
+        {
+            let parent_str = self.resolve(parent);
+            self.write(&format!("{}.{}(self", parent_str, method_str));
+            if !args.is_empty() {
+                self.write(", ");
+            }
+            self.generate_arguments(args);
+            self.write(")");
+            return;
+        }
         self.generate_expression(obj);
         self.write(":");
         self.write(&method_str);
         self.write("(");
 
-        for (i, arg) in args.iter().enumerate() {
-            if i > 0 {
-                self.write(", ");
-            }
-            self.generate_argument(arg);
-        }
+        self.generate_arguments(args);
         self.write(")");
     }
 
@@ -81,12 +78,44 @@ impl CodeGenerator {
         self.write("(");
         self.generate_expression(callee);
         self.write(".new(");
-        for (i, arg) in args.iter().enumerate() {
-            if i > 0 {
-                self.write(", ");
+        self.generate_arguments(args);
+        self.write("))");
+    }
+
+    /// Generate a comma-separated argument list.
+    ///
+    /// Lua only expands a multi-value expression in the last argument position, so
+    /// when a spread appears earlier, everything from the first spread onward is
+    /// collected into one table and unpacked as the final argument.
+    pub fn generate_arguments(&mut self, args: &[Argument]) {
+        let first_spread = args.iter().position(|arg| arg.is_spread);
+        let needs_packing = matches!(first_spread, Some(pos) if pos + 1 < args.len());
+
+        if !needs_packing {
+            for (i, arg) in args.iter().enumerate() {
+                if i > 0 {
+                    self.write(", ");
+                }
+                self.generate_argument(arg);
             }
-            self.generate_argument(arg);
+            return;
         }
-        self.write("))");
+
+        let first_spread = first_spread.unwrap_or(0);
+        for arg in &args[..first_spread] {
+            self.generate_expression(&arg.value);
+            self.write(", ");
+        }
+
+        let items: Vec<(bool, &Expression)> = args[first_spread..]
+            .iter()
+            .map(|arg| (arg.is_spread, &arg.value))
+            .collect();
+        let unpack = self.strategy.generate_unpack("__arr", "1", "__n");
+        self.write("(function() ");
+        self.write_spread_accumulation(&items);
+        self.write("return ");
+        self.write(&unpack);
+        self.write(" end)()");
     }
 }
@@ -1,5 +1,5 @@
 use super::super::CodeGenerator;
-use typedlua_parser::ast::expression::{ArrayElement, Literal, ObjectProperty};
+use typedlua_parser::ast::expression::{ArrayElement, Expression, Literal, ObjectProperty};
 
 impl CodeGenerator {
     pub fn generate_literal(&mut self, lit: &Literal) {
@@ -9,15 +9,77 @@ impl CodeGenerator {
             Literal::Number(n) => self.write(&n.to_string()),
             Literal::Integer(i) => self.write(&i.to_string()),
             Literal::String(s) => {
-                self.write("\"");
-                self.write(&s.replace('\\', "\\\\").replace('"', "\\\""));
-                self.write("\"");
+                let quoted = self.quote_string(s);
+                self.write(&quoted);
             }
         }
     }
 
     pub fn generate_argument(&mut self, arg: &typedlua_parser::ast::expression::Argument) {
-        self.generate_expression(&arg.value);
+        if arg.is_spread {
+            self.generate_spread_argument(&arg.value);
+        } else {
+            self.generate_expression(&arg.value);
+        }
+    }
+
+    /// Generate `unpack(t, 1, t.n or #t)` so packed varargs keep their trailing nils
+    pub fn generate_spread_argument(&mut self, expr: &Expression) {
+        if self.is_simple_expression(expr) {
+            let table = self.expression_to_string(expr);
+            let end = format!("{}.n or #{}", table, table);
+            let unpack = self.strategy.generate_unpack(&table, "1", &end);
+            self.write(&unpack);
+        } else {
+            let unpack = self.strategy.generate_unpack("__s", "1", "__s.n or #__s");
+            self.write("(function(__s) return ");
+            self.write(&unpack);
+            self.write(" end)(");
+            self.generate_expression(expr);
+            self.write(")");
+        }
+    }
+
+    /// Generate an array constructor containing spread elements.
+    ///
+    /// Elements are written by explicit index instead of `table.insert`, so `nil`
+    /// values and holes in spread sources do not cut the copy short.
+    pub fn generate_spread_array(&mut self, elements: &[ArrayElement]) {
+        let items: Vec<(bool, &Expression)> = elements
+            .iter()
+            .map(|elem| match elem {
+                ArrayElement::Expression(expr) => (false, expr),
+                ArrayElement::Spread(expr) => (true, expr),
+            })
+            .collect();
+
+        self.write("(function() ");
+        self.write_spread_accumulation(&items);
+        self.write("return __arr end)()");
+    }
+
+    /// Write the body that accumulates `items` into `local __arr, __n`.
+    /// Spread sources are copied with `table.move` where available and a counted loop otherwise.
+    pub(crate) fn write_spread_accumulation(&mut self, items: &[(bool, &Expression)]) {
+        self.write("local __arr, __n = {}, 0 ");
+
+        for (is_spread, expr) in items {
+            if *is_spread {
+                self.write("do local __s = ");
+                self.generate_expression(expr);
+                self.write(" local __len = __s.n or #__s ");
+                if self.strategy.supports_table_move() {
+                    self.write("table.move(__s, 1, __len, __n + 1, __arr) ");
+                } else {
+                    self.write("for __i = 1, __len do __arr[__n + __i] = __s[__i] end ");
+                }
+                self.write("__n = __n + __len end ");
+            } else {
+                self.write("__n = __n + 1 __arr[__n] = ");
+                self.generate_expression(expr);
+                self.write(" ");
+            }
+        }
     }
 
     pub fn generate_object_property(
@@ -0,0 +1,147 @@
+//! Counters for profile-guided optimization.
+//!
+//! With [`Instrumentation::Pgo`], the module embeds
+//! `typedlua_runtime::profile::PGO_RUNTIME` and counts every function and
+//! method entry, anonymous function call, `if`/`match` arm and loop body
+//! under the keys of [`crate::optimizer::counter_key`]. The counts are
+//! written to a profile file when the program exits, for
+//! [`crate::optimizer::Optimizer::set_profile`] in a later build.
+
+use super::CodeGenerator;
+use crate::optimizer::{counter_key, CounterKind};
+use serde::{Deserialize, Serialize};
+use typedlua_parser::span::Span;
+
+/// Whether generated code counts how often its parts run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Instrumentation {
+    /// No counters (default)
+    #[default]
+    Off,
+    /// Count executions for profile-guided optimization
+    Pgo,
+}
+
+impl CodeGenerator {
+    /// Count executions under `module_id`, the module id the profile will be
+    /// read back with
+    pub fn with_instrumentation(
+        mut self,
+        mode: Instrumentation,
+        module_id: impl Into<String>,
+    ) -> Self {
+        self.profile_module = match mode {
+            Instrumentation::Pgo => Some(module_id.into()),
+            Instrumentation::Off => None,
+        };
+        self
+    }
+
+    pub(crate) fn embed_profile_runtime(&mut self) {
+        if self.profile_module.is_some() {
+            self.writeln(typedlua_runtime::profile::PGO_RUNTIME);
+        }
+    }
+
+    /// Count entries to the function or method `name` declared at `span`;
+    /// returns the enclosing function's name for
+    /// [`Self::leave_profiled_function`]
+    pub(crate) fn enter_profiled_function(&mut self, span: Span, name: &str) -> String {
+        self.write_profile_counter(CounterKind::Function, span, name);
+        std::mem::replace(&mut self.profile_function, name.to_string())
+    }
+
+    pub(crate) fn leave_profiled_function(&mut self, outer: String) {
+        self.profile_function = outer;
+    }
+
+    /// Count runs of the arm, loop body or anonymous function at `span`,
+    /// labelled with the enclosing function
+    pub(crate) fn write_profile_count(&mut self, kind: CounterKind, span: Span) {
+        let label = self.profile_function.clone();
+        self.write_profile_counter(kind, span, &label);
+    }
+
+    fn write_profile_counter(&mut self, kind: CounterKind, span: Span, label: &str) {
+        let Some(module) = &self.profile_module else {
+            return;
+        };
+        let key = self.quote_string(&counter_key(module, kind, span.line, span.column, label));
+        self.write_indent();
+        self.writeln(&format!("__pgo[{}] = (__pgo[{}] or 0) + 1", key, key));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MutableProgram;
+    use bumpalo::Bump;
+    use std::sync::Arc;
+    use typedlua_parser::diagnostics::CollectingDiagnosticHandler;
+    use typedlua_parser::lexer::Lexer;
+    use typedlua_parser::parser::Parser;
+    use typedlua_parser::string_interner::StringInterner;
+
+    fn generate(source: &str, mode: Instrumentation) -> String {
+        let handler = Arc::new(CollectingDiagnosticHandler::new());
+        let (interner, common) = StringInterner::new_with_common_identifiers();
+        let interner = Arc::new(interner);
+        let arena = Bump::new();
+        let mut lexer = Lexer::new(source, handler.clone(), &interner);
+        let tokens = lexer.tokenize().expect("Lexing failed");
+        let mut parser = Parser::new(tokens, handler, &interner, &common, &arena);
+        let program = parser.parse().expect("Parsing failed");
+        let mutable = MutableProgram::from_program(&program);
+
+        let mut generator =
+            CodeGenerator::new(interner.clone()).with_instrumentation(mode, "src/main.tl");
+        generator.generate(&mutable)
+    }
+
+    const SOURCE: &str = r#"
+        function sign(x: number): number
+            if x > 0 then
+                return 1
+            else
+                return -1
+            end
+        end
+
+        class Counter {
+            count: number = 0
+
+            bump(): void {
+                for i = 1, 3 do
+                    self.count = self.count + sign(i)
+                end
+            }
+        }
+
+        const offset = (x: number) => x + 1
+    "#;
+
+    #[test]
+    fn test_pgo_counts_functions_arms_and_loops() {
+        let output = generate(SOURCE, Instrumentation::Pgo);
+        assert!(output.contains("local __pgo = rawget(_G"), "{}", output);
+        for counter in [
+            ":function:sign\"",
+            ":branch:sign\"",
+            ":function:Counter.bump\"",
+            ":loop:Counter.bump\"",
+            ":closure:\"",
+        ] {
+            assert!(output.contains(counter), "missing {}: {}", counter, output);
+        }
+        assert!(output.contains("\"src/main.tl:2:"), "{}", output);
+        assert_eq!(output.matches(":branch:sign\"").count(), 4, "{}", output);
+    }
+
+    #[test]
+    fn test_off_has_no_counters() {
+        let output = generate(SOURCE, Instrumentation::Off);
+        assert!(!output.contains("__pgo"), "{}", output);
+    }
+}
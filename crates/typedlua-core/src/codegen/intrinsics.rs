@@ -0,0 +1,525 @@
+//! `is<T>(v)`, `parse<T>(v)` and `safeParse<T>(v)`.
+//!
+//! These are compiler intrinsics rather than library functions: a call with
+//! one explicit type argument and one argument is lowered to a call of the
+//! generated validator for `T`. They emit checks whatever the validation mode
+//! is, so the module is scanned for them before its body is generated and
+//! their validators are written with the rest (see `prepare_validation`).
+//! A type argument with no runtime check (an imported type, a generic
+//! parameter) is a codegen error. A module that defines or imports its own `is`, `parse` or `safeParse`
+//! keeps calling it.
+
+use super::call_scan::{scan, CallScan};
+use super::declarations::DeclarationGenerator;
+use super::validation::ValidatorBuilder;
+use super::CodeGenerator;
+use rustc_hash::FxHashMap as HashMap;
+use rustc_hash::FxHashSet as HashSet;
+use typedlua_parser::ast::expression::*;
+use typedlua_parser::ast::statement::*;
+use typedlua_parser::ast::types::Type;
+use typedlua_parser::string_interner::{StringId, StringInterner};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub(crate) enum Intrinsic {
+    /// `is<T>(v)`: boolean
+    Is,
+    /// `parse<T>(v)`: `v`, or raises a structured validation error
+    Parse,
+    /// `safeParse<T>(v)`: `{ ok = true, value = v }` or `{ ok = false, errors = ... }`
+    SafeParse,
+}
+
+impl Intrinsic {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "is" => Some(Intrinsic::Is),
+            "parse" => Some(Intrinsic::Parse),
+            "safeParse" => Some(Intrinsic::SafeParse),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Intrinsic::Is => "is",
+            Intrinsic::Parse => "parse",
+            Intrinsic::SafeParse => "safeParse",
+        }
+    }
+}
+
+/// The intrinsic a call invokes and its type argument, if it is one. Names the
+/// module binds itself (`shadowed`) are ordinary calls.
+fn match_intrinsic<'a, 'arena>(
+    interner: &StringInterner,
+    shadowed: &HashSet<Intrinsic>,
+    callee: &Expression,
+    args: &[Argument],
+    type_args: Option<&'a [Type<'arena>]>,
+) -> Option<(Intrinsic, &'a Type<'arena>)> {
+    let ExpressionKind::Identifier(name) = &callee.kind else {
+        return None;
+    };
+    let ([ty], [arg]) = (type_args?, args) else {
+        return None;
+    };
+    if arg.is_spread {
+        return None;
+    }
+    let intrinsic = Intrinsic::from_name(&interner.resolve(*name))?;
+    if shadowed.contains(&intrinsic) {
+        return None;
+    }
+    Some((intrinsic, ty))
+}
+
+/// Intrinsic calls found in a module, in source order.
+///
+/// Any binding of an intrinsic's name anywhere in the module (function,
+/// variable, parameter, loop or catch variable, class, enum or import) refers
+/// to the module's own definition, so calls of that name are left alone.
+pub(crate) struct IntrinsicCalls<'a, 'arena: 'a> {
+    interner: &'a StringInterner,
+    calls: Vec<(Intrinsic, &'a Type<'arena>)>,
+    shadowed: HashSet<Intrinsic>,
+}
+
+impl<'a, 'arena: 'a> IntrinsicCalls<'a, 'arena> {
+    pub(crate) fn collect(
+        interner: &'a StringInterner,
+        statements: &'a [Statement<'arena>],
+    ) -> Self {
+        let mut calls = IntrinsicCalls {
+            interner,
+            calls: Vec::new(),
+            shadowed: HashSet::default(),
+        };
+        scan(&mut calls, statements);
+        // Shadowing anywhere applies to every call, so filter once all are seen
+        let shadowed = calls.shadowed.clone();
+        calls
+            .calls
+            .retain(|(intrinsic, _)| !shadowed.contains(intrinsic));
+        calls
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.calls.is_empty()
+    }
+
+    /// Intrinsic names the module binds itself
+    pub(crate) fn shadowed(&self) -> &HashSet<Intrinsic> {
+        &self.shadowed
+    }
+
+    /// Build a validator for every type argument, keyed by the type's text.
+    /// A type argument that can't be fully checked at runtime is an error
+    /// rather than a validator that lets part of the value through.
+    pub(crate) fn validators(
+        &self,
+        builder: &mut ValidatorBuilder<'a, 'arena>,
+        errors: &mut Vec<String>,
+    ) -> HashMap<String, String> {
+        let mut validators = HashMap::default();
+        let mut reported = HashSet::default();
+        for &(intrinsic, ty) in &self.calls {
+            let key = DeclarationGenerator::new(self.interner).type_to_string(ty);
+            if validators.contains_key(&key) || !reported.insert((intrinsic, key.clone())) {
+                continue;
+            }
+            let unchecked = builder.unchecked_names(ty);
+            if !unchecked.is_empty() {
+                errors.push(format!(
+                    "{}<{}>: no runtime check for {}; only this module's type aliases, \
+                     interfaces, classes and enums without fields or methods can be checked",
+                    intrinsic.name(),
+                    key,
+                    unchecked
+                        .iter()
+                        .map(|name| format!("`{}`", name))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ));
+                continue;
+            }
+            validators.insert(key, builder.validator_for(ty));
+        }
+        validators
+    }
+
+    /// Runtime helpers the lowered calls need
+    pub(crate) fn runtime(&self) -> &'static str {
+        if self
+            .calls
+            .iter()
+            .any(|(intrinsic, _)| *intrinsic != Intrinsic::Is)
+        {
+            typedlua_runtime::validation::PARSE
+        } else {
+            ""
+        }
+    }
+}
+
+impl<'a, 'arena: 'a> CallScan<'a, 'arena> for IntrinsicCalls<'a, 'arena> {
+    fn call(
+        &mut self,
+        callee: &'a Expression<'arena>,
+        args: &'a [Argument<'arena>],
+        type_args: Option<&'a [Type<'arena>]>,
+    ) {
+        if let Some(call) =
+            match_intrinsic(self.interner, &HashSet::default(), callee, args, type_args)
+        {
+            self.calls.push(call);
+        }
+    }
+
+    fn bind(&mut self, name: StringId) {
+        if let Some(intrinsic) = Intrinsic::from_name(&self.interner.resolve(name)) {
+            self.shadowed.insert(intrinsic);
+        }
+    }
+}
+
+impl CodeGenerator {
+    /// Lower an intrinsic call; returns false for any other call
+    pub(crate) fn generate_intrinsic_call(
+        &mut self,
+        callee: &Expression,
+        args: &[Argument],
+        type_args: Option<&[Type]>,
+    ) -> bool {
+        let Some((intrinsic, ty)) = match_intrinsic(
+            &self.interner,
+            &self.shadowed_intrinsics,
+            callee,
+            args,
+            type_args,
+        ) else {
+            return false;
+        };
+        let key = DeclarationGenerator::new(&self.interner).type_to_string(ty);
+        let Some(validator) = self.intrinsic_validators.get(&key).cloned() else {
+            return false;
+        };
+
+        // Failure paths start at the argument's name, e.g. `user.address.zip`
+        let value = &args[0].value;
+        let root = match &value.kind {
+            ExpressionKind::Identifier(name) => self.resolve(*name),
+            _ => "value".to_string(),
+        };
+        let path = self.quote_string(&root);
+
+        match intrinsic {
+            // Parenthesized so only the boolean survives in argument lists
+            Intrinsic::Is => {
+                self.write(&format!("({}(", validator));
+                self.generate_expression(value);
+                self.write(&format!(", {}))", path));
+            }
+            Intrinsic::Parse | Intrinsic::SafeParse => {
+                let helper = if intrinsic == Intrinsic::Parse {
+                    "__parse"
+                } else {
+                    "__safeParse"
+                };
+                self.write(&format!("{}({}, ", helper, validator));
+                self.generate_expression(value);
+                self.write(&format!(", {})", path));
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::{ValidationErrors, ValidationMode};
+    use crate::MutableProgram;
+    use bumpalo::Bump;
+    use std::sync::Arc;
+    use typedlua_parser::diagnostics::CollectingDiagnosticHandler;
+    use typedlua_parser::lexer::Lexer;
+    use typedlua_parser::parser::Parser;
+
+    fn generate(source: &str, mode: ValidationMode, errors: ValidationErrors) -> String {
+        generate_with_errors(source, mode, errors).0
+    }
+
+    fn generate_with_errors(
+        source: &str,
+        mode: ValidationMode,
+        errors: ValidationErrors,
+    ) -> (String, Vec<String>) {
+        let handler = Arc::new(CollectingDiagnosticHandler::new());
+        let (interner, common) = StringInterner::new_with_common_identifiers();
+        let interner = Arc::new(interner);
+        let arena = Bump::new();
+        let mut lexer = Lexer::new(source, handler.clone(), &interner);
+        let tokens = lexer.tokenize().expect("Lexing failed");
+        let mut parser = Parser::new(tokens, handler, &interner, &common, &arena);
+        let program = parser.parse().expect("Parsing failed");
+        let mutable = MutableProgram::from_program(&program);
+
+        let mut generator = CodeGenerator::new(interner.clone()).with_validation(mode, errors);
+        let output = generator.generate(&mutable);
+        (output, generator.take_errors())
+    }
+
+    #[test]
+    fn test_intrinsics_validate_with_validation_off() {
+        let source = r#"
+            interface Address { zip: number }
+            interface User { name: string, address: Address }
+            function load(user: unknown): User
+                if is<User>(user) then
+                    print("valid")
+                end
+                return parse<User>(user)
+            end
+        "#;
+        let output = generate(source, ValidationMode::Off, ValidationErrors::FailFast);
+        assert!(
+            output.contains("local __validate_User, __validate_Address"),
+            "validators: {}",
+            output
+        );
+        assert!(
+            output.contains("if (__validate_User(user, \"user\")) then"),
+            "is: {}",
+            output
+        );
+        assert!(
+            output.contains("return __parse(__validate_User, user, \"user\")"),
+            "parse: {}",
+            output
+        );
+        assert!(
+            output.contains("__validate_Address(val.address, path .. \".address\")"),
+            "nested path: {}",
+            output
+        );
+        assert!(
+            output.contains("local function __parse(validator, value, path)"),
+            "runtime helper: {}",
+            output
+        );
+    }
+
+    #[test]
+    fn test_safe_parse_and_recursive_types() {
+        let source = r#"
+            type Tree = { value: number, children: Tree[] }
+            const result = safeParse<Tree>(load())
+        "#;
+        let output = generate(source, ValidationMode::Off, ValidationErrors::Collect);
+        assert!(
+            output.contains("__safeParse(__validate_Tree, load(), \"value\")"),
+            "safeParse: {}",
+            output
+        );
+        assert!(
+            output
+                .contains("__validate_Tree(item1, path .. \".children[\" .. k1 .. \"]\", errors)"),
+            "recursive validator calls itself: {}",
+            output
+        );
+    }
+
+    #[test]
+    fn test_is_only_needs_no_runtime_and_plain_calls_are_untouched() {
+        let source = r#"
+            const ok = is<number>(x)
+            const n = parse(text)
+        "#;
+        let output = generate(source, ValidationMode::Off, ValidationErrors::FailFast);
+        assert!(
+            output.contains("(__validate_number(x, \"x\"))"),
+            "is: {}",
+            output
+        );
+        assert!(output.contains("parse(text)"), "plain call: {}", output);
+        assert!(!output.contains("__parse"), "no parse helper: {}", output);
+        assert!(
+            !output.contains("__ValidationError"),
+            "no runtime: {}",
+            output
+        );
+    }
+
+    #[test]
+    fn test_user_defined_and_imported_names_are_not_intrinsics() {
+        let source = r#"
+            function parse<T>(text: string): T
+                return decode(text)
+            end
+            const n = parse<number>(text)
+        "#;
+        let output = generate(source, ValidationMode::Off, ValidationErrors::FailFast);
+        assert!(output.contains("parse(text)"), "own function: {}", output);
+        assert!(!output.contains("__validate_"), "{}", output);
+
+        let source = r#"
+            import { is } from "./guards"
+            const ok = is<number>(x)
+            const user = safeParse<number>(y)
+        "#;
+        let output = generate(source, ValidationMode::Off, ValidationErrors::FailFast);
+        assert!(output.contains("is(x)"), "imported: {}", output);
+        assert!(
+            output.contains("__safeParse(__validate_number, y, \"y\")"),
+            "other intrinsics still lower: {}",
+            output
+        );
+    }
+
+    #[test]
+    fn test_intrinsics_in_nested_bodies() {
+        let source = r#"
+            enum Level {
+                rank: number
+
+                Low(1),
+                High(2),
+
+                constructor(rank: number) {
+                    self.rank = rank
+                }
+
+                check(v: unknown): boolean {
+                    return is<number>(v)
+                }
+            }
+            function read(v: unknown, ok: boolean = is<string>(v)): boolean
+                return ok
+            end
+        "#;
+        let output = generate(source, ValidationMode::Off, ValidationErrors::FailFast);
+        assert!(
+            output.contains("(__validate_number(v, \"v\"))"),
+            "enum method: {}",
+            output
+        );
+        assert!(
+            output.contains("(__validate_string(v, \"v\"))"),
+            "parameter default: {}",
+            output
+        );
+    }
+
+    #[test]
+    fn test_class_types_check_instances_by_type_id() {
+        let source = r#"
+            class Point {
+                x: number
+            }
+            class Point3 extends Point {
+                z: number
+            }
+            function check(v: unknown): boolean
+                return is<Point>(v)
+            end
+        "#;
+        let output = generate(source, ValidationMode::Off, ValidationErrors::FailFast);
+        let type_id = output
+            .lines()
+            .find_map(|line| line.trim().strip_prefix("Point.__typeId = "))
+            .expect("class type ID");
+        assert!(
+            output.contains("local function __isInstance(val, typeId)"),
+            "runtime helper: {}",
+            output
+        );
+        assert!(
+            output.contains(&format!("if not (__isInstance(val, {})) then", type_id)),
+            "instance check against the class's own type ID: {}",
+            output
+        );
+        assert!(
+            output.contains("(__validate_Point(v, \"v\"))"),
+            "is: {}",
+            output
+        );
+    }
+
+    #[test]
+    fn test_enum_types_check_member_values() {
+        let source = r#"
+            enum Color { Red, Green = 5 }
+            enum Dir { Up = "up", Down = "down" }
+            const a = is<Color>(x)
+            const b = parse<Dir>(y)
+        "#;
+        let output = generate(source, ValidationMode::Off, ValidationErrors::FailFast);
+        assert!(
+            output.contains("if not (val == 0 or val == 5) then"),
+            "numeric members: {}",
+            output
+        );
+        assert!(
+            output.contains("if not (val == \"up\" or val == \"down\") then"),
+            "string members: {}",
+            output
+        );
+    }
+
+    #[test]
+    fn test_unchecked_types_are_errors() {
+        let source = r#"
+            import { User } from "./user"
+            interface Box { item: User }
+            enum Level {
+                rank: number
+
+                Low(1),
+
+                constructor(rank: number) {
+                    self.rank = rank
+                }
+            }
+            function guard<T>(v: unknown): boolean
+                return is<T>(v)
+            end
+            const a = parse<User>(x)
+            const b = is<Box>(x)
+            const c = safeParse<Level>(x)
+            const d = is<number>(x)
+        "#;
+        let (output, errors) =
+            generate_with_errors(source, ValidationMode::Off, ValidationErrors::FailFast);
+        for expected in [
+            "is<T>: no runtime check for `T`",
+            "parse<User>: no runtime check for `User`",
+            "is<Box>: no runtime check for `User`",
+            "safeParse<Level>: no runtime check for `Level`",
+        ] {
+            assert!(
+                errors.iter().any(|e| e.starts_with(expected)),
+                "{}: {:?}",
+                expected,
+                errors
+            );
+        }
+        assert_eq!(errors.len(), 4, "{:?}", errors);
+        assert!(!output.contains("__validate_User"), "{}", output);
+        assert!(
+            output.contains("(__validate_number(x, \"x\"))"),
+            "checkable types still lower: {}",
+            output
+        );
+    }
+
+    #[test]
+    fn test_no_intrinsics_no_prelude() {
+        let output = generate(
+            "const n = tonumber(\"1\")",
+            ValidationMode::Off,
+            ValidationErrors::FailFast,
+        );
+        assert!(!output.contains("__validate_"), "{}", output);
+    }
+}
@@ -0,0 +1,1071 @@
+//! Lua minifier backing [`OutputFormat::Minified`](crate::config::OutputFormat).
+//!
+//! The code generator always emits line-structured Lua; when minified output
+//! is requested the finished chunk is re-tokenized and rewritten here:
+//!
+//! - comments and layout whitespace are dropped, and a separator is only
+//!   inserted where two tokens would otherwise lex as one (`local x`, `a - -b`)
+//! - locals and parameters are renamed to the shortest free names, while
+//!   globals, `_ENV`, `self` and caller-preserved names (exports, reflected
+//!   classes) keep their spelling
+//! - parentheses that do not change how the expression parses are removed
+//!
+//! Every emitted token is reported as a [`TokenRelocation`] so the source map
+//! can be moved onto the minified positions and record the original names.
+//!
+//! The minifier only ever sees code produced by the generator, so instead of
+//! reporting diagnostics it gives up (returns `None`) on anything it does not
+//! understand, and the caller reports that as a code generation error.
+
+use super::sourcemap::TokenRelocation;
+use rustc_hash::FxHashSet as HashSet;
+
+/// Result of minifying a chunk
+#[derive(Debug, Clone)]
+pub struct MinifiedChunk {
+    pub code: String,
+    pub relocations: Vec<TokenRelocation>,
+}
+
+/// Minify a chunk of generated Lua.
+///
+/// Locals whose name is in `preserved` are never renamed. Returns `None` if
+/// the input could not be tokenized or parsed.
+pub fn minify(source: &str, preserved: &HashSet<String>) -> Option<MinifiedChunk> {
+    let tokens = tokenize(source)?;
+    let mut parser = Parser::new(&tokens);
+    parser.chunk()?;
+    let names = assign_names(&parser, preserved);
+    Some(emit(&tokens, &parser, &names))
+}
+
+pub(crate) const KEYWORDS: &[&str] = &[
+    "and", "break", "do", "else", "elseif", "end", "false", "for", "function", "goto", "if", "in",
+    "local", "nil", "not", "or", "repeat", "return", "then", "true", "until", "while",
+];
+
+/// Multi-character symbols, longest first so the lexer can match greedily
+const SYMBOLS: &[&str] = &[
+    "...", "..", "==", "~=", "<=", ">=", "<<", ">>", "//", "::", "+", "-", "*", "/", "%", "^", "#",
+    "&", "~", "|", "<", ">", "=", "(", ")", "{", "}", "[", "]", ";", ":", ",", ".",
+];
+
+/// Character pairs that lex as a single token (or open a comment / long
+/// bracket) when written next to each other
+const GLUING_PAIRS: &[&str] = &[
+    "--", "..", "==", "~=", "<=", ">=", "<<", ">>", "//", "::", "[[", "[=",
+];
+
+/// Precedence of unary operators, as in the reference Lua parser
+const UNARY_PRIORITY: u8 = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum TokenKind {
+    Name,
+    Keyword,
+    Number,
+    String,
+    Symbol,
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct Token<'a> {
+    pub(super) kind: TokenKind,
+    pub(super) text: &'a str,
+    /// Byte offset into the chunk
+    pub(super) offset: usize,
+    pub(super) line: usize,
+    pub(super) column: usize,
+}
+
+fn is_name_start(b: u8) -> bool {
+    b.is_ascii_alphabetic() || b == b'_'
+}
+
+fn is_name_char(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_'
+}
+
+/// Length of the long bracket opener (`[[`, `[==[`) at `pos`, if there is one
+fn long_bracket_level(bytes: &[u8], pos: usize) -> Option<usize> {
+    if bytes.get(pos) != Some(&b'[') {
+        return None;
+    }
+    let mut level = 0;
+    while bytes.get(pos + 1 + level) == Some(&b'=') {
+        level += 1;
+    }
+    (bytes.get(pos + 1 + level) == Some(&b'[')).then_some(level)
+}
+
+/// End (exclusive) of the long bracket starting at `pos` with the given level
+fn long_bracket_end(bytes: &[u8], pos: usize, level: usize) -> Option<usize> {
+    let mut i = pos + level + 2;
+    while i < bytes.len() {
+        if bytes[i] == b']'
+            && bytes[i + 1..].iter().take(level).all(|&b| b == b'=')
+            && bytes.get(i + 1 + level) == Some(&b']')
+        {
+            return Some(i + level + 2);
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Split a chunk into tokens, skipping whitespace and comments
+pub(super) fn tokenize(source: &str) -> Option<Vec<Token<'_>>> {
+    let bytes = source.as_bytes();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+    let mut line = 0;
+    let mut column = 0;
+
+    // Moves line/column over the consumed bytes (columns count characters)
+    let advance = |from: usize, to: usize, line: &mut usize, column: &mut usize| {
+        for &b in &bytes[from..to] {
+            if b == b'\n' {
+                *line += 1;
+                *column = 0;
+            } else if b & 0xC0 != 0x80 {
+                *column += 1;
+            }
+        }
+    };
+
+    while pos < bytes.len() {
+        let b = bytes[pos];
+        let start = pos;
+
+        if b.is_ascii_whitespace() {
+            pos += 1;
+            advance(start, pos, &mut line, &mut column);
+            continue;
+        }
+
+        if source[pos..].starts_with("--") {
+            pos = match long_bracket_level(bytes, pos + 2) {
+                Some(level) => long_bracket_end(bytes, pos + 2, level)?,
+                None => source[pos..].find('\n').map_or(bytes.len(), |i| pos + i),
+            };
+            advance(start, pos, &mut line, &mut column);
+            continue;
+        }
+
+        let kind = if is_name_start(b) {
+            while pos < bytes.len() && is_name_char(bytes[pos]) {
+                pos += 1;
+            }
+            if KEYWORDS.contains(&&source[start..pos]) {
+                TokenKind::Keyword
+            } else {
+                TokenKind::Name
+            }
+        } else if b.is_ascii_digit()
+            || (b == b'.' && bytes.get(pos + 1).is_some_and(u8::is_ascii_digit))
+        {
+            let hex = source[pos..].starts_with("0x") || source[pos..].starts_with("0X");
+            while pos < bytes.len() && (is_name_char(bytes[pos]) || bytes[pos] == b'.') {
+                let exponent = if hex { b"pP" } else { b"eE" };
+                pos += 1;
+                if exponent.contains(&bytes[pos - 1])
+                    && matches!(bytes.get(pos), Some(b'+') | Some(b'-'))
+                {
+                    pos += 1;
+                }
+            }
+            TokenKind::Number
+        } else if b == b'"' || b == b'\'' {
+            pos += 1;
+            loop {
+                match bytes.get(pos)? {
+                    b'\\' => pos += 2,
+                    &c if c == b => break,
+                    b'\n' => return None,
+                    _ => pos += 1,
+                }
+            }
+            pos += 1;
+            TokenKind::String
+        } else if let Some(level) = long_bracket_level(bytes, pos) {
+            pos = long_bracket_end(bytes, pos, level)?;
+            TokenKind::String
+        } else {
+            let symbol = SYMBOLS.iter().find(|s| source[pos..].starts_with(*s))?;
+            pos += symbol.len();
+            TokenKind::Symbol
+        };
+
+        tokens.push(Token {
+            kind,
+            text: &source[start..pos],
+            offset: start,
+            line,
+            column,
+        });
+        advance(start, pos, &mut line, &mut column);
+    }
+
+    Some(tokens)
+}
+
+/// A local variable or parameter, live from its declaration to the end of
+/// its enclosing block
+#[derive(Debug)]
+struct Binding<'a> {
+    name: &'a str,
+    start: usize,
+    end: usize,
+}
+
+/// What the surrounding expression needs to know about a (sub)expression to
+/// decide whether parentheses around it can go.
+#[derive(Debug, Clone, Copy)]
+struct ExprInfo {
+    /// Lowest left/right priority of any operator not hidden behind parens
+    min_left: u8,
+    min_right: u8,
+    /// Call or `...`: parentheses truncate it to one value
+    multi: bool,
+    /// Name, field access or call: may be followed by `.x`, `[k]`, `(args)`
+    prefix: bool,
+    /// Token indices of the parentheses if this is a parenthesized expression
+    group: Option<(usize, usize)>,
+}
+
+impl ExprInfo {
+    fn atom() -> Self {
+        Self {
+            min_left: u8::MAX,
+            min_right: u8::MAX,
+            multi: false,
+            prefix: false,
+            group: None,
+        }
+    }
+
+    fn prefix(multi: bool) -> Self {
+        Self {
+            multi,
+            prefix: true,
+            ..Self::atom()
+        }
+    }
+}
+
+/// Left and right priority of a binary operator, as in the reference Lua parser
+fn binary_priority(token: &Token) -> Option<(u8, u8)> {
+    if !matches!(token.kind, TokenKind::Symbol | TokenKind::Keyword) {
+        return None;
+    }
+    Some(match token.text {
+        "or" => (1, 1),
+        "and" => (2, 2),
+        "<" | ">" | "<=" | ">=" | "~=" | "==" => (3, 3),
+        "|" => (4, 4),
+        "~" => (5, 5),
+        "&" => (6, 6),
+        "<<" | ">>" => (7, 7),
+        ".." => (9, 8),
+        "+" | "-" => (10, 10),
+        "*" | "/" | "//" | "%" => (11, 11),
+        "^" => (14, 13),
+        _ => return None,
+    })
+}
+
+/// Scope-resolving parser over the token stream. It does not build a tree;
+/// it records which binding every name token refers to, which parentheses
+/// can be dropped, and where statements start.
+struct Parser<'t, 'a> {
+    tokens: &'t [Token<'a>],
+    pos: usize,
+    scopes: Vec<Vec<usize>>,
+    bindings: Vec<Binding<'a>>,
+    resolved: Vec<Option<usize>>,
+    free_names: HashSet<&'a str>,
+    dropped: Vec<bool>,
+    statement_starts: Vec<bool>,
+}
+
+impl<'t, 'a> Parser<'t, 'a> {
+    fn new(tokens: &'t [Token<'a>]) -> Self {
+        Self {
+            tokens,
+            pos: 0,
+            scopes: Vec::new(),
+            bindings: Vec::new(),
+            resolved: vec![None; tokens.len()],
+            free_names: HashSet::default(),
+            dropped: vec![false; tokens.len()],
+            statement_starts: vec![false; tokens.len()],
+        }
+    }
+
+    fn peek(&self) -> Option<&'t Token<'a>> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&'t Token<'a>> {
+        self.tokens.get(self.pos + offset)
+    }
+
+    fn check(&self, text: &str) -> bool {
+        self.peek().is_some_and(|t| {
+            t.text == text && matches!(t.kind, TokenKind::Symbol | TokenKind::Keyword)
+        })
+    }
+
+    fn accept(&mut self, text: &str) -> bool {
+        let matched = self.check(text);
+        if matched {
+            self.pos += 1;
+        }
+        matched
+    }
+
+    fn expect(&mut self, text: &str) -> Option<()> {
+        self.accept(text).then_some(())
+    }
+
+    fn expect_name(&mut self) -> Option<usize> {
+        let index = self.pos;
+        (self.peek()?.kind == TokenKind::Name).then(|| {
+            self.pos += 1;
+            index
+        })
+    }
+
+    fn open_scope(&mut self) {
+        self.scopes.push(Vec::new());
+    }
+
+    fn close_scope(&mut self) {
+        if let Some(scope) = self.scopes.pop() {
+            for id in scope {
+                self.bindings[id].end = self.pos;
+            }
+        }
+    }
+
+    fn bind(&mut self, name: &'a str, start: usize) -> usize {
+        let id = self.bindings.len();
+        self.bindings.push(Binding {
+            name,
+            start,
+            end: self.tokens.len(),
+        });
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.push(id);
+        }
+        id
+    }
+
+    fn declare(&mut self, index: usize) {
+        let id = self.bind(self.tokens[index].text, index);
+        self.resolved[index] = Some(id);
+    }
+
+    fn reference(&mut self, index: usize) {
+        let name = self.tokens[index].text;
+        let found = self
+            .scopes
+            .iter()
+            .rev()
+            .flat_map(|scope| scope.iter().rev())
+            .find(|&&id| self.bindings[id].name == name);
+        match found {
+            Some(&id) => self.resolved[index] = Some(id),
+            None => {
+                self.free_names.insert(name);
+            }
+        }
+    }
+
+    fn chunk(&mut self) -> Option<()> {
+        self.open_scope();
+        self.block()?;
+        self.close_scope();
+        (self.pos == self.tokens.len()).then_some(())
+    }
+
+    fn block_follows(&self) -> bool {
+        match self.peek() {
+            None => true,
+            Some(t) => {
+                t.kind == TokenKind::Keyword
+                    && matches!(t.text, "end" | "else" | "elseif" | "until")
+            }
+        }
+    }
+
+    fn block(&mut self) -> Option<()> {
+        while !self.block_follows() {
+            if self.check("return") {
+                self.statement_starts[self.pos] = true;
+                self.pos += 1;
+                if !self.block_follows() && !self.check(";") {
+                    self.expression_list()?;
+                }
+                self.accept(";");
+                break;
+            }
+            self.statement()?;
+        }
+        Some(())
+    }
+
+    /// `do ... end`-style scoped block
+    fn scoped_block(&mut self) -> Option<()> {
+        self.open_scope();
+        self.block()?;
+        self.close_scope();
+        Some(())
+    }
+
+    fn statement(&mut self) -> Option<()> {
+        self.statement_starts[self.pos] = true;
+        let token = self.peek()?;
+        if token.kind == TokenKind::Symbol {
+            match token.text {
+                ";" => {
+                    self.pos += 1;
+                    return Some(());
+                }
+                "::" => {
+                    self.pos += 1;
+                    self.expect_name()?;
+                    return self.expect("::");
+                }
+                _ => return self.expression_statement(),
+            }
+        }
+        if token.kind != TokenKind::Keyword {
+            return self.expression_statement();
+        }
+
+        self.pos += 1;
+        match token.text {
+            "if" => {
+                self.expression()?;
+                self.expect("then")?;
+                self.scoped_block()?;
+                while self.accept("elseif") {
+                    self.expression()?;
+                    self.expect("then")?;
+                    self.scoped_block()?;
+                }
+                if self.accept("else") {
+                    self.scoped_block()?;
+                }
+                self.expect("end")
+            }
+            "while" => {
+                self.expression()?;
+                self.expect("do")?;
+                self.scoped_block()?;
+                self.expect("end")
+            }
+            "do" => {
+                self.scoped_block()?;
+                self.expect("end")
+            }
+            "for" => self.for_statement(),
+            "repeat" => {
+                // The `until` condition still sees the body's locals
+                self.open_scope();
+                self.block()?;
+                self.expect("until")?;
+                self.expression()?;
+                self.close_scope();
+                Some(())
+            }
+            "function" => {
+                let name = self.expect_name()?;
+                self.reference(name);
+                let mut is_method = false;
+                while self.check(".") || self.check(":") {
+                    is_method = self.check(":");
+                    self.pos += 1;
+                    self.expect_name()?;
+                    if is_method {
+                        break;
+                    }
+                }
+                self.function_body(is_method)
+            }
+            "local" => {
+                if self.accept("function") {
+                    let name = self.expect_name()?;
+                    self.declare(name);
+                    return self.function_body(false);
+                }
+                let mut names = vec![self.expect_name()?];
+                self.attribute()?;
+                while self.accept(",") {
+                    names.push(self.expect_name()?);
+                    self.attribute()?;
+                }
+                if self.accept("=") {
+                    self.expression_list()?;
+                }
+                for name in names {
+                    self.declare(name);
+                }
+                Some(())
+            }
+            "goto" => self.expect_name().map(|_| ()),
+            "break" => Some(()),
+            _ => None,
+        }
+    }
+
+    /// Optional Lua 5.4 `<const>` / `<close>` attribute
+    fn attribute(&mut self) -> Option<()> {
+        if self.accept("<") {
+            self.expect_name()?;
+            self.expect(">")?;
+        }
+        Some(())
+    }
+
+    fn for_statement(&mut self) -> Option<()> {
+        let mut names = vec![self.expect_name()?];
+        if self.accept("=") {
+            self.expression()?;
+            self.expect(",")?;
+            self.expression()?;
+            if self.accept(",") {
+                self.expression()?;
+            }
+        } else {
+            while self.accept(",") {
+                names.push(self.expect_name()?);
+            }
+            self.expect("in")?;
+            self.expression_list()?;
+        }
+        self.expect("do")?;
+        self.open_scope();
+        for name in names {
+            self.declare(name);
+        }
+        self.block()?;
+        self.close_scope();
+        self.expect("end")
+    }
+
+    fn expression_statement(&mut self) -> Option<()> {
+        self.suffixed_expression()?;
+        if self.check("=") || self.check(",") {
+            while self.accept(",") {
+                self.suffixed_expression()?;
+            }
+            self.expect("=")?;
+            self.expression_list()?;
+        }
+        Some(())
+    }
+
+    /// Parameter list and body; the function's own scope holds the parameters
+    fn function_body(&mut self, is_method: bool) -> Option<()> {
+        self.expect("(")?;
+        self.open_scope();
+        if is_method {
+            self.bind("self", self.pos);
+        }
+        if !self.check(")") {
+            loop {
+                if self.accept("...") {
+                    break;
+                }
+                let name = self.expect_name()?;
+                self.declare(name);
+                if !self.accept(",") {
+                    break;
+                }
+            }
+        }
+        self.expect(")")?;
+        self.block()?;
+        self.close_scope();
+        self.expect("end")
+    }
+
+    fn expression_list(&mut self) -> Option<()> {
+        self.expression()?;
+        while self.accept(",") {
+            self.expression()?;
+        }
+        Some(())
+    }
+
+    fn expression(&mut self) -> Option<ExprInfo> {
+        self.subexpression(0)
+    }
+
+    /// Precedence climbing exactly like `subexpr` in the reference parser, so
+    /// the parenthesis folding sees the same grouping Lua would
+    fn subexpression(&mut self, limit: u8) -> Option<ExprInfo> {
+        let token = self.peek()?;
+        let is_unary = matches!(token.kind, TokenKind::Symbol | TokenKind::Keyword)
+            && matches!(token.text, "not" | "-" | "#" | "~");
+        let mut left = if is_unary {
+            self.pos += 1;
+            let operand = self.subexpression(UNARY_PRIORITY)?;
+            ExprInfo {
+                min_right: operand.min_right.min(UNARY_PRIORITY),
+                ..ExprInfo::atom()
+            }
+        } else {
+            let operand = self.simple_expression()?;
+            self.fold_parentheses(operand, limit)
+        };
+
+        while let Some((left_priority, right_priority)) = self.peek().and_then(binary_priority) {
+            if left_priority <= limit {
+                break;
+            }
+            self.pos += 1;
+            let right = self.subexpression(right_priority)?;
+            left = ExprInfo {
+                min_left: left_priority.min(left.min_left).min(right.min_left),
+                min_right: right_priority.min(left.min_right).min(right.min_right),
+                ..ExprInfo::atom()
+            };
+        }
+        Some(left)
+    }
+
+    /// Drop the parentheses around an operand when re-parsing without them
+    /// gives the same tree: every exposed operator must still bind inside
+    /// (`limit` is the priority of the operator on the left) and must not
+    /// steal the operand of the operator on the right. Multi-value
+    /// expressions keep their parentheses unless an operator truncates them
+    /// anyway.
+    fn fold_parentheses(&mut self, info: ExprInfo, limit: u8) -> ExprInfo {
+        let Some((open, close)) = info.group else {
+            return info;
+        };
+        let next_left = self.peek().and_then(binary_priority).map(|(left, _)| left);
+        let in_operator = limit > 0 || next_left.is_some();
+        let removable = (!info.multi || in_operator)
+            && info.min_left > limit
+            && !matches!(next_left, Some(left) if left > info.min_right);
+        if removable {
+            self.dropped[open] = true;
+            self.dropped[close] = true;
+            ExprInfo {
+                group: None,
+                ..info
+            }
+        } else {
+            ExprInfo::atom()
+        }
+    }
+
+    fn simple_expression(&mut self) -> Option<ExprInfo> {
+        let token = self.peek()?;
+        match token.kind {
+            TokenKind::Number | TokenKind::String => {
+                self.pos += 1;
+                return Some(ExprInfo::atom());
+            }
+            TokenKind::Keyword if matches!(token.text, "nil" | "true" | "false") => {
+                self.pos += 1;
+                return Some(ExprInfo::atom());
+            }
+            TokenKind::Keyword if token.text == "function" => {
+                self.pos += 1;
+                self.function_body(false)?;
+                return Some(ExprInfo::atom());
+            }
+            TokenKind::Symbol if token.text == "..." => {
+                self.pos += 1;
+                return Some(ExprInfo {
+                    multi: true,
+                    ..ExprInfo::atom()
+                });
+            }
+            TokenKind::Symbol if token.text == "{" => {
+                self.table_constructor()?;
+                return Some(ExprInfo::atom());
+            }
+            _ => {}
+        }
+        self.suffixed_expression()
+    }
+
+    fn primary_expression(&mut self) -> Option<ExprInfo> {
+        let token = self.peek()?;
+        if token.kind == TokenKind::Name {
+            self.reference(self.pos);
+            self.pos += 1;
+            return Some(ExprInfo::prefix(false));
+        }
+        let open = self.pos;
+        self.expect("(")?;
+        let inner = self.expression()?;
+        let close = self.pos;
+        self.expect(")")?;
+        Some(ExprInfo {
+            group: Some((open, close)),
+            ..inner
+        })
+    }
+
+    fn at_suffix(&self) -> bool {
+        match self.peek() {
+            Some(t) if t.kind == TokenKind::String => true,
+            Some(t) if t.kind == TokenKind::Symbol => {
+                matches!(t.text, "." | "[" | ":" | "(" | "{")
+            }
+            _ => false,
+        }
+    }
+
+    fn suffixed_expression(&mut self) -> Option<ExprInfo> {
+        let mut info = self.primary_expression()?;
+        if !self.at_suffix() {
+            return Some(info);
+        }
+        // `(a.b).c` -> `a.b.c`; literals and operators keep their parens
+        if let Some((open, close)) = info.group {
+            if info.prefix {
+                self.dropped[open] = true;
+                self.dropped[close] = true;
+            }
+        }
+        while self.at_suffix() {
+            let token = self.peek()?;
+            info = match token.text {
+                "." => {
+                    self.pos += 1;
+                    self.expect_name()?;
+                    ExprInfo::prefix(false)
+                }
+                "[" => {
+                    self.pos += 1;
+                    self.expression()?;
+                    self.expect("]")?;
+                    ExprInfo::prefix(false)
+                }
+                ":" => {
+                    self.pos += 1;
+                    self.expect_name()?;
+                    self.call_arguments()?;
+                    ExprInfo::prefix(true)
+                }
+                _ => {
+                    self.call_arguments()?;
+                    ExprInfo::prefix(true)
+                }
+            };
+        }
+        Some(info)
+    }
+
+    fn call_arguments(&mut self) -> Option<()> {
+        let token = self.peek()?;
+        if token.kind == TokenKind::String {
+            self.pos += 1;
+            return Some(());
+        }
+        if token.text == "{" {
+            return self.table_constructor();
+        }
+        self.expect("(")?;
+        if !self.check(")") {
+            self.expression_list()?;
+        }
+        self.expect(")")
+    }
+
+    fn table_constructor(&mut self) -> Option<()> {
+        self.expect("{")?;
+        while !self.check("}") {
+            if self.accept("[") {
+                self.expression()?;
+                self.expect("]")?;
+                self.expect("=")?;
+                self.expression()?;
+            } else if self.peek()?.kind == TokenKind::Name
+                && self.peek_at(1).is_some_and(|t| t.text == "=")
+            {
+                // Record key, not a variable reference
+                self.pos += 2;
+                self.expression()?;
+            } else {
+                self.expression()?;
+            }
+            if !self.accept(",") && !self.accept(";") {
+                break;
+            }
+        }
+        self.expect("}")
+    }
+}
+
+/// Short identifiers in order: `a`..`Z`, `_`, then two characters and so on
+fn short_name(mut index: usize) -> String {
+    const FIRST: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ_";
+    const REST: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ_0123456789";
+
+    let mut name = vec![FIRST[index % FIRST.len()]];
+    index /= FIRST.len();
+    while index > 0 {
+        index -= 1;
+        name.push(REST[index % REST.len()]);
+        index /= REST.len();
+    }
+    String::from_utf8(name).unwrap_or_default()
+}
+
+fn is_renamable(name: &str, preserved: &HashSet<String>) -> bool {
+    !matches!(name, "self" | "_ENV") && !preserved.contains(name)
+}
+
+/// Pick new names for renamable bindings. Two bindings whose live ranges
+/// overlap never share a name, so every reference still resolves to the
+/// same binding; live ranges are intervals, so greedy assignment in
+/// declaration order uses the fewest names.
+fn assign_names(parser: &Parser, preserved: &HashSet<String>) -> Vec<Option<String>> {
+    let mut reserved: HashSet<&str> = parser.free_names.clone();
+    reserved.extend(KEYWORDS.iter().copied());
+    for binding in &parser.bindings {
+        if !is_renamable(binding.name, preserved) {
+            reserved.insert(binding.name);
+        }
+    }
+
+    let mut pool: Vec<String> = Vec::new();
+    let mut next_candidate = 0;
+    let mut name_for_slot = |slot: usize, pool: &mut Vec<String>| -> String {
+        while pool.len() <= slot {
+            let candidate = short_name(next_candidate);
+            next_candidate += 1;
+            if !reserved.contains(candidate.as_str()) {
+                pool.push(candidate);
+            }
+        }
+        pool[slot].clone()
+    };
+
+    let mut order: Vec<usize> = (0..parser.bindings.len()).collect();
+    order.sort_by_key(|&id| parser.bindings[id].start);
+
+    let mut names = vec![None; parser.bindings.len()];
+    let mut live: Vec<(usize, usize)> = Vec::new();
+    for id in order {
+        let binding = &parser.bindings[id];
+        if !is_renamable(binding.name, preserved) {
+            continue;
+        }
+        live.retain(|&(end, _)| end >= binding.start);
+        let slot = (0..)
+            .find(|slot| live.iter().all(|&(_, used)| used != *slot))
+            .unwrap_or_default();
+        live.push((binding.end, slot));
+        names[id] = Some(name_for_slot(slot, &mut pool));
+    }
+    names
+}
+
+fn needs_space(previous: &str, next: &str) -> bool {
+    let (Some(last), Some(first)) = (previous.bytes().last(), next.bytes().next()) else {
+        return false;
+    };
+    if is_name_char(last) && is_name_char(first) {
+        return true;
+    }
+    // `1 ..x` would lex as a malformed number
+    if last.is_ascii_digit() && first == b'.' {
+        return true;
+    }
+    let pair = [last, first];
+    GLUING_PAIRS.iter().any(|p| p.as_bytes() == pair)
+}
+
+fn emit(tokens: &[Token], parser: &Parser, names: &[Option<String>]) -> MinifiedChunk {
+    let mut code = String::new();
+    // Length of `code` in characters, the column relocations are measured in
+    let mut column = 0;
+    let mut relocations = Vec::with_capacity(tokens.len());
+    let mut previous = "";
+    let mut at_statement_start = false;
+
+    for (index, token) in tokens.iter().enumerate() {
+        at_statement_start |= parser.statement_starts[index];
+        if parser.dropped[index] {
+            continue;
+        }
+
+        let renamed = parser.resolved[index].and_then(|id| names[id].as_deref());
+        let text = renamed.unwrap_or(token.text);
+
+        // `a = b (f)()` would parse as a call on `b`
+        if at_statement_start && text == "(" && !code.is_empty() && previous != ";" {
+            code.push(';');
+            column += 1;
+            previous = ";";
+        }
+        at_statement_start = false;
+
+        if needs_space(previous, text) {
+            code.push(' ');
+            column += 1;
+        }
+        relocations.push(TokenRelocation {
+            from: (token.line, token.column),
+            to: (0, column),
+            original_name: renamed.map(|_| token.text.to_string()),
+        });
+        code.push_str(text);
+        column += text.chars().count();
+        previous = text;
+    }
+
+    MinifiedChunk { code, relocations }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn min(source: &str) -> String {
+        minify(source, &HashSet::default())
+            .expect("generated Lua should minify")
+            .code
+    }
+
+    #[test]
+    fn test_whitespace_only_where_needed() {
+        assert_eq!(
+            min("local x = 1\nlocal y = x + 2\nprint(y)\n"),
+            "local a=1 local b=a+2 print(b)"
+        );
+        assert_eq!(min("print(1 - -x)"), "print(1- -x)");
+        assert_eq!(min("print(1 .. \"a\")"), "print(1 ..\"a\")");
+        assert_eq!(min("local t = a[ [[s]] ]"), "local b=a[ [[s]]]");
+    }
+
+    #[test]
+    fn test_comments_are_dropped() {
+        assert_eq!(
+            min("-- header\nlocal x = 1 --[[ inline ]] + 2\nreturn x"),
+            "local a=1+2 return a"
+        );
+    }
+
+    #[test]
+    fn test_globals_and_fields_keep_names() {
+        assert_eq!(
+            min("local value = {count = 1}\nvalue.count = math.max(value.count, 2)"),
+            "local a={count=1}a.count=math.max(a.count,2)"
+        );
+    }
+
+    #[test]
+    fn test_shadowing_local_reads_outer_value() {
+        // `local print = print` must keep reading the global
+        assert_eq!(
+            min("local print = print\nlocal x = 1\ndo local x = x + 1 print(x) end"),
+            "local a=print local b=1 do local c=b+1 a(c)end"
+        );
+    }
+
+    #[test]
+    fn test_short_names_avoid_globals_and_keywords() {
+        let out = min("local x = a\nlocal y = b\nreturn x, y");
+        assert_eq!(out, "local c=a local d=b return c,d");
+    }
+
+    #[test]
+    fn test_sibling_scopes_reuse_names() {
+        assert_eq!(
+            min("local function f(first) return first end\nlocal function g(second) return second end"),
+            "local function a(b)return b end local function b(c)return c end"
+        );
+    }
+
+    #[test]
+    fn test_preserved_names_and_self() {
+        let mut preserved = HashSet::default();
+        preserved.insert("Animal".to_string());
+        let out = minify(
+            "local Animal = {}\nfunction Animal:speak(volume) return self.name .. volume end",
+            &preserved,
+        )
+        .unwrap()
+        .code;
+        assert_eq!(
+            out,
+            "local Animal={}function Animal:speak(a)return self.name..a end"
+        );
+    }
+
+    #[test]
+    fn test_redundant_parentheses_are_folded() {
+        assert_eq!(min("local x = ((1 + 2))"), "local a=1+2");
+        assert_eq!(min("local x = (a * b) + c"), "local d=a*b+c");
+        assert_eq!(min("local x = a * (b + c)"), "local d=a*(b+c)");
+        assert_eq!(min("local x = (a .. b) .. c"), "local d=(a..b)..c");
+        assert_eq!(min("local x = (-a) ^ 2"), "local b=(-a)^2");
+        assert_eq!(min("local x = not (a == b)"), "local c=not(a==b)");
+        assert_eq!(min("local x = (a).b"), "local b=a.b");
+        assert_eq!(min("local x = (\"s\"):upper()"), "local a=(\"s\"):upper()");
+    }
+
+    #[test]
+    fn test_multi_value_parentheses_kept() {
+        assert_eq!(min("return (f())"), "return(f())");
+        assert_eq!(min("return (f()) + 1"), "return f()+1");
+    }
+
+    #[test]
+    fn test_statement_starting_with_paren_is_separated() {
+        assert_eq!(
+            min("local t = {}\n(\"x\"):rep(2)"),
+            "local a={};(\"x\"):rep(2)"
+        );
+    }
+
+    #[test]
+    fn test_repeat_until_sees_body_locals() {
+        assert_eq!(
+            min("repeat local done = check() until done"),
+            "repeat local a=check()until a"
+        );
+    }
+
+    #[test]
+    fn test_relocations_record_original_names() {
+        let chunk = minify(
+            "local counter = 0\ncounter = counter + 1",
+            &HashSet::default(),
+        )
+        .unwrap();
+        let renamed: Vec<_> = chunk
+            .relocations
+            .iter()
+            .filter_map(|r| r.original_name.as_deref())
+            .collect();
+        assert_eq!(renamed, vec!["counter", "counter", "counter"]);
+        let second = chunk.relocations.iter().find(|r| r.from == (1, 0)).unwrap();
+        assert_eq!(second.to, (0, 10));
+    }
+
+    #[test]
+    fn test_unparseable_input_is_rejected() {
+        assert!(minify("local = = 1", &HashSet::default()).is_none());
+        assert!(minify("print(\"unterminated)", &HashSet::default()).is_none());
+    }
+
+    #[test]
+    fn test_short_name_sequence() {
+        assert_eq!(short_name(0), "a");
+        assert_eq!(short_name(52), "_");
+        assert_eq!(short_name(53), "aa");
+        assert_eq!(short_name(54), "ba");
+    }
+}
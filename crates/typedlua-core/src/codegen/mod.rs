@@ -1,20 +1,38 @@
+pub mod annotations;
 pub mod builder;
 pub mod emitter;
+pub mod minifier;
 pub mod sourcemap;
 pub mod strategies;
 pub mod traits;
 
+pub mod access_checks;
+mod call_scan;
 pub mod classes;
+pub mod declarations;
 pub mod decorators;
 pub mod enums;
 pub mod expressions;
+pub mod instrumentation;
+pub mod intrinsics;
 pub mod modules;
 pub mod patterns;
+pub mod printer;
+pub mod reflection_metadata;
 pub mod scope_hoisting;
 pub mod statements;
 pub mod tree_shaking;
+pub mod type_ids;
+pub mod validation;
 
+pub use access_checks::AccessChecks;
+pub use declarations::{CheckedType, DeclarationGenerator};
+pub use decorators::Traces;
 pub use emitter::Emitter;
+pub use instrumentation::Instrumentation;
+pub use printer::{PrinterOptions, QuoteStyle};
+pub use type_ids::TypeIdRegistry;
+pub use validation::{ValidationErrors, ValidationMode};
 
 pub use builder::CodeGeneratorBuilder;
 pub use sourcemap::{SourceMap, SourceMapBuilder};
@@ -121,6 +139,15 @@ pub struct CodeGenerator {
     target: LuaTarget,
     current_class_parent: Option<StringId>,
     uses_built_in_decorators: bool,
+    /// Any class in the module is decorated, so `__decorate` is embedded
+    uses_decorators: bool,
+    /// Classes whose `new` runs decorator instance initializers
+    instance_initializer_classes: std::collections::HashSet<String>,
+    /// Methods each class binds to `self` in `new`, from statically resolved `@bind`
+    bound_methods: HashMap<String, Vec<String>>,
+    /// Built-in decorator names the module declares itself, which therefore
+    /// cannot be resolved statically
+    shadowed_decorators: std::collections::HashSet<String>,
     /// Module generation mode
     mode: CodeGenMode,
     /// Track exported symbols for module mode
@@ -141,26 +168,69 @@ pub struct CodeGenerator {
     current_namespace: Option<Vec<String>>,
     /// Namespace exports to attach: (local_name, namespace_path_string)
     namespace_exports: Vec<(String, String)>,
-    /// Reflection: counter for assigning unique type IDs
-    next_type_id: u32,
+    /// Reflection: program-wide type IDs shared by all modules, when available
+    type_ids: Option<Arc<TypeIdRegistry>>,
+    /// Module ID the type ID registry knows this module by
+    type_id_module: Option<String>,
+    /// Type IDs already handed out in this module
+    used_type_ids: std::collections::HashSet<u32>,
+    /// Type IDs allocated ahead of generation for validators that check
+    /// class instances, by the class declaration's span start
+    reserved_type_ids: HashMap<u32, u32>,
     /// Reflection: track registered types for __TypeRegistry
     registered_types: std::collections::HashMap<String, u32>,
     /// Reflection: generation mode (selective, full, none)
     reflection_mode: ReflectionMode,
     /// Reflection: whether current module imports @std/reflection
     has_reflection_import: bool,
+    /// Reflection: local holding the evaluated arguments of a decorator call
+    /// applied at runtime, by span, so full metadata records the same values
+    decorator_args: HashMap<(u32, u32), String>,
     /// Code generation strategy for Lua version-specific logic
     strategy: Box<dyn strategies::CodeGenStrategy>,
     /// Enforce access modifiers (private/protected/public) at runtime
     enforce_access_modifiers: bool,
+    /// Builds that keep `@trace` decorators
+    traces: Traces,
+    /// Access checks are active for this module: requested, a debug build,
+    /// and some class has non-public instance members
+    uses_access_checks: bool,
+    /// Profiling: module id counters are recorded under, when instrumented
+    profile_module: Option<String>,
+    /// Profiling: name of the function or method being generated, which
+    /// labels the counters inside it
+    profile_function: String,
     /// Whole-program analysis for O3+ cross-module optimizations
     whole_program_analysis: Option<crate::optimizer::WholeProgramAnalysis>,
+    /// Class hierarchy used to flatten inherited methods at O2+: the
+    /// whole-program one when available, otherwise this module's
+    class_hierarchy: Option<Arc<crate::optimizer::ClassHierarchy>>,
     /// Tree shaking: reachable exports for bundle mode
     reachable_exports: Option<std::collections::HashSet<String>>,
     /// Tree shaking: whether tree shaking is enabled
     tree_shaking_enabled: bool,
     /// Scope hoisting: whether scope hoisting is enabled for bundles
     scope_hoisting_enabled: bool,
+    /// Layout options for readable output
+    printer_options: PrinterOptions,
+    /// Original `.tl` source, used to carry comments into the output
+    source_text: Option<Arc<str>>,
+    /// Byte offset in `source_text` up to which comments have been emitted
+    comment_cursor: usize,
+    /// Emit LuaLS/EmmyLua `---@` annotations for declarations
+    emit_annotations: bool,
+    /// Validation: which functions check their parameters on entry
+    validation_mode: ValidationMode,
+    /// Validation: fail-fast or collect error reporting
+    validation_errors: ValidationErrors,
+    /// Validation: entry checks per function (`name`) or method (`Class.method`)
+    validation_checks: HashMap<String, Vec<String>>,
+    /// Validation: validator per type text used by `is`/`parse`/`safeParse` calls
+    intrinsic_validators: HashMap<String, String>,
+    /// Validation: intrinsic names the module binds itself; their calls stay plain calls
+    shadowed_intrinsics: rustc_hash::FxHashSet<intrinsics::Intrinsic>,
+    /// Errors from the last [`generate`](Self::generate), for [`take_errors`](Self::take_errors)
+    errors: Vec<String>,
 }
 
 impl CodeGenerator {
@@ -171,6 +241,10 @@ impl CodeGenerator {
             target,
             current_class_parent: None,
             uses_built_in_decorators: false,
+            uses_decorators: false,
+            instance_initializer_classes: std::collections::HashSet::new(),
+            bound_methods: HashMap::default(),
+            shadowed_decorators: std::collections::HashSet::new(),
             mode: CodeGenMode::Require,
             exports: Vec::new(),
             has_default_export: false,
@@ -181,16 +255,35 @@ impl CodeGenerator {
             interface_default_methods: Default::default(),
             current_namespace: None,
             namespace_exports: Vec::new(),
-            next_type_id: 1,
+            type_ids: None,
+            type_id_module: None,
+            used_type_ids: Default::default(),
+            reserved_type_ids: HashMap::default(),
             registered_types: Default::default(),
             reflection_mode: ReflectionMode::default(),
             has_reflection_import: false,
+            decorator_args: HashMap::default(),
             strategy: Self::create_strategy(target),
             enforce_access_modifiers: false,
+            traces: Traces::default(),
+            uses_access_checks: false,
+            profile_module: None,
+            profile_function: String::new(),
             whole_program_analysis: None,
+            class_hierarchy: None,
             reachable_exports: None,
             tree_shaking_enabled: false,
             scope_hoisting_enabled: true,
+            printer_options: PrinterOptions::default(),
+            source_text: None,
+            comment_cursor: 0,
+            emit_annotations: false,
+            validation_mode: ValidationMode::default(),
+            validation_errors: ValidationErrors::default(),
+            validation_checks: HashMap::default(),
+            intrinsic_validators: HashMap::default(),
+            shadowed_intrinsics: Default::default(),
+            errors: Vec::new(),
         }
     }
 
@@ -204,6 +297,11 @@ impl CodeGenerator {
         self
     }
 
+    pub fn with_traces(mut self, traces: Traces) -> Self {
+        self.traces = traces;
+        self
+    }
+
     /// Create a strategy for the given Lua target
     fn create_strategy(target: LuaTarget) -> Box<dyn strategies::CodeGenStrategy> {
         match target {
@@ -295,6 +393,8 @@ impl CodeGenerator {
     }
 
     pub fn generate(&mut self, program: &crate::MutableProgram<'_>) -> String {
+        self.errors.clear();
+
         // Emit strategy-specific preamble (e.g., library includes)
         if let Some(preamble) = self.strategy.emit_preamble() {
             self.writeln(&preamble);
@@ -308,6 +408,31 @@ impl CodeGenerator {
         if self.uses_built_in_decorators {
             self.embed_runtime_library();
         }
+        if self.uses_decorators {
+            self.embed_decorator_protocol();
+        }
+
+        if self.optimization_level.effective() >= OptimizationLevel::O2 {
+            self.class_hierarchy = Some(match &self.whole_program_analysis {
+                Some(analysis) => analysis.class_hierarchy.clone(),
+                None => Arc::new(crate::optimizer::ClassHierarchy::from_statements(
+                    &program.statements,
+                )),
+            });
+        }
+
+        self.uses_access_checks =
+            self.access_checks_enabled() && self.needs_access_checks(&program.statements);
+        if self.uses_access_checks {
+            self.embed_access_checks();
+        }
+        self.embed_profile_runtime();
+        // Table preallocation sizes arrays through `__tableCreate(n)`
+        if call_scan::calls_function(&self.interner, &program.statements, "__tableCreate") {
+            self.writeln(typedlua_runtime::table::TABLE_CREATE);
+        }
+
+        self.prepare_validation(&program.statements);
 
         for statement in &program.statements {
             self.generate_statement(statement);
@@ -326,10 +451,9 @@ impl CodeGenerator {
             self.writeln("-- Type Registry for Reflection");
             self.writeln("-- ============================================================");
 
-            // Generate registry tables (name -> id and id -> class)
-            self.writeln("__TypeRegistry = {}");
-            self.writeln("__TypeIdToClass = {}");
-            self.writeln("");
+            // Shared registry tables (name -> id and id -> class); modules add
+            // to them rather than replace them
+            self.writeln(reflection::TYPE_REGISTRY);
 
             // Collect into a Vec to avoid borrow checker issues
             let type_entries: Vec<(String, u32)> = self
@@ -338,9 +462,15 @@ impl CodeGenerator {
                 .map(|(k, v)| (k.clone(), *v))
                 .collect();
 
-            // Populate __TypeRegistry (name -> id mapping)
+            // Populate __TypeRegistry (qualified name -> id mapping); keying by
+            // module keeps same-named classes in different modules apart
+            let module_id = self.type_id_module();
             for (type_name, type_id) in &type_entries {
-                self.writeln(&format!("__TypeRegistry[\"{}\"] = {}", type_name, type_id));
+                self.writeln(&format!(
+                    "__TypeRegistry[\"{}\"] = {}",
+                    TypeIdRegistry::qualified_name(&module_id, type_name),
+                    type_id
+                ));
             }
             self.writeln("");
 
@@ -354,9 +484,39 @@ impl CodeGenerator {
             self.writeln(reflection::REFLECTION_MODULE);
         }
 
+        if self.emitter.is_minified() {
+            self.minify_output();
+        } else {
+            self.wrap_long_lines();
+        }
+
         self.emitter.clone_output()
     }
 
+    /// Rewrite the generated chunk with the minifier, keeping exported and
+    /// reflected names intact, and move the source map onto the new positions.
+    /// If the minifier cannot handle the chunk the readable output is kept and
+    /// an error is recorded for [`take_errors`](Self::take_errors).
+    fn minify_output(&mut self) {
+        let mut preserved: rustc_hash::FxHashSet<String> = self.exports.iter().cloned().collect();
+        preserved.extend(self.registered_types.keys().cloned());
+        preserved.extend(
+            self.namespace_exports
+                .iter()
+                .map(|(local_name, _)| local_name.clone()),
+        );
+
+        let Some(chunk) = minifier::minify(self.emitter.output_ref(), &preserved) else {
+            self.errors
+                .push("the minifier could not parse the generated Lua".to_string());
+            return;
+        };
+        if let Some(source_map) = self.emitter.source_map_mut() {
+            source_map.apply_relocations(&chunk.relocations);
+        }
+        *self.emitter.output_mut() = chunk.code;
+    }
+
     /// Generate a bundle from multiple modules
     ///
     /// # Arguments
@@ -369,7 +529,8 @@ impl CodeGenerator {
     /// * `reachable_set` - Optional reachability analysis for tree shaking
     ///
     /// # Returns
-    /// Returns a tuple of (generated_code, optional_source_map)
+    /// Returns a tuple of (generated_code, optional_source_map), or an error
+    /// if the bundle could not be produced
     pub fn generate_bundle<'arena>(
         modules: &[(
             String,
@@ -382,7 +543,7 @@ impl CodeGenerator {
         output_file: Option<String>,
         interner: Option<Arc<StringInterner>>,
         reachable_set: Option<&tree_shaking::ReachableSet>,
-    ) -> (String, Option<SourceMap>) {
+    ) -> Result<(String, Option<SourceMap>), String> {
         Self::generate_bundle_with_options(
             modules,
             entry_module_id,
@@ -392,6 +553,7 @@ impl CodeGenerator {
             interner,
             reachable_set,
             true, // scope_hoisting_enabled by default
+            crate::config::OutputFormat::Readable,
         )
     }
 
@@ -406,9 +568,11 @@ impl CodeGenerator {
     /// * `interner` - The string interner used during parsing (required for resolving StringIds)
     /// * `reachable_set` - Optional reachability analysis for tree shaking
     /// * `scope_hoisting_enabled` - Whether to hoist declarations to top-level scope
+    /// * `output_format` - Layout of the bundle; minified bundles are minified as a whole
     ///
     /// # Returns
-    /// Returns a tuple of (generated_code, optional_source_map)
+    /// Returns a tuple of (generated_code, optional_source_map), or an error
+    /// if the bundle could not be minified
     #[allow(clippy::too_many_arguments)]
     pub fn generate_bundle_with_options<'arena>(
         modules: &[(
@@ -423,7 +587,8 @@ impl CodeGenerator {
         interner: Option<Arc<StringInterner>>,
         reachable_set: Option<&tree_shaking::ReachableSet>,
         scope_hoisting_enabled: bool,
-    ) -> (String, Option<SourceMap>) {
+        output_format: crate::config::OutputFormat,
+    ) -> Result<(String, Option<SourceMap>), String> {
         let mut output = String::new();
 
         // Initialize source map builder if requested
@@ -470,6 +635,14 @@ impl CodeGenerator {
             scope_hoisting_enabled,
         );
 
+        // Type IDs are assigned once for the whole bundle so reflection
+        // metadata from different modules never collides
+        let mut type_ids = TypeIdRegistry::new();
+        for (module_id, program, _) in modules.iter() {
+            type_ids.register_module(module_id, program.statements, &interner_for_hoisting);
+        }
+        let type_ids = Arc::new(type_ids);
+
         // Generate hoisted declarations at the top level
         if scope_hoisting_enabled && !hoisting_context.hoistable_by_module.is_empty() {
             advance!("-- Hoisted declarations (scope hoisting)\n");
@@ -550,12 +723,12 @@ impl CodeGenerator {
             let interner = interner
                 .clone()
                 .unwrap_or_else(|| Arc::new(StringInterner::new()));
-            let mut generator =
-                CodeGenerator::new(interner)
-                    .with_target(target)
-                    .with_mode(CodeGenMode::Bundle {
-                        module_id: module_id.clone(),
-                    });
+            let mut generator = CodeGenerator::new(interner)
+                .with_target(target)
+                .with_mode(CodeGenMode::Bundle {
+                    module_id: module_id.clone(),
+                })
+                .with_type_ids(type_ids.clone(), module_id.clone());
 
             // Set the import map so imports can be resolved to module IDs
             generator.import_map = import_map.clone();
@@ -612,9 +785,20 @@ impl CodeGenerator {
         advance!("-- Execute entry point\n");
         advance!(&format!("__require(\"{}\")\n", entry_module_id));
 
+        // Modules reach each other through `__modules`, so no name needs to
+        // survive minification
+        if matches!(output_format, crate::config::OutputFormat::Minified) {
+            let chunk = minifier::minify(&output, &Default::default())
+                .ok_or_else(|| "the minifier could not parse the generated bundle".to_string())?;
+            if let Some(ref mut builder) = source_map_builder {
+                builder.apply_relocations(&chunk.relocations);
+            }
+            output = chunk.code;
+        }
+
         let source_map = source_map_builder.map(|builder| builder.build());
 
-        (output, source_map)
+        Ok((output, source_map))
     }
 
     /// Generate a hoisted declaration if it's hoistable
@@ -653,26 +837,12 @@ impl CodeGenerator {
                         temp_gen.write("(");
 
                         // Generate parameters
-                        let mut has_rest = false;
-                        for (i, param) in func_decl.parameters.iter().enumerate() {
-                            if param.is_rest {
-                                if i > 0 {
-                                    temp_gen.write(", ");
-                                }
-                                temp_gen.write("...");
-                                has_rest = true;
-                            } else {
-                                if i > 0 {
-                                    temp_gen.write(", ");
-                                }
-                                temp_gen.generate_pattern(&param.pattern);
-                            }
-                        }
-                        let _ = has_rest; // silence unused variable warning
+                        let rest_param = temp_gen.generate_parameter_list(&func_decl.parameters);
                         temp_gen.writeln(")");
 
                         // Generate body
                         temp_gen.indent();
+                        temp_gen.generate_rest_parameter_init(rest_param);
                         for body_stmt in func_decl.body.statements.iter() {
                             temp_gen.generate_statement(body_stmt);
                         }
@@ -799,6 +969,12 @@ impl CodeGenerator {
         self.emitter.take_source_map()
     }
 
+    /// Errors that made the last [`generate`](Self::generate) fall short of
+    /// the requested output, such as a chunk the minifier could not handle
+    pub fn take_errors(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.errors)
+    }
+
     fn write(&mut self, s: &str) {
         self.emitter.write(s);
     }
@@ -1056,6 +1232,52 @@ mod tests {
         assert!(output.contains("local x = math.floor(a / b)"));
     }
 
+    #[test]
+    fn test_array_spread_uses_table_move_on_lua53() {
+        let source = "const xs = [0, ...ys, 4]";
+        let output = generate_code_with_target(source, LuaTarget::Lua53);
+        assert!(output.contains("table.move(__s, 1, __len, __n + 1, __arr)"));
+        assert!(output.contains("local __len = __s.n or #__s"));
+        assert!(!output.contains("ipairs"));
+        assert!(!output.contains("table.insert"));
+    }
+
+    #[test]
+    fn test_array_spread_uses_counted_loop_on_lua51() {
+        let source = "const xs = [...ys, 4]";
+        let output = generate_code_with_target(source, LuaTarget::Lua51);
+        assert!(output.contains("for __i = 1, __len do __arr[__n + __i] = __s[__i] end"));
+        assert!(!output.contains("table.move"));
+    }
+
+    #[test]
+    fn test_call_spread_unpacks_with_count() {
+        let source = "f(1, ...args)";
+        let output = generate_code_with_target(source, LuaTarget::Lua54);
+        assert!(output.contains("f(1, table.unpack(args, 1, args.n or #args))"));
+
+        let output = generate_code_with_target(source, LuaTarget::Lua51);
+        assert!(output.contains("f(1, unpack(args, 1, args.n or #args))"));
+    }
+
+    #[test]
+    fn test_call_spread_before_last_argument_is_packed() {
+        let source = "f(...args, 1)";
+        let output = generate_code_with_target(source, LuaTarget::Lua54);
+        assert!(output.contains("return table.unpack(__arr, 1, __n) end)()"));
+    }
+
+    #[test]
+    fn test_rest_parameter_keeps_trailing_nils() {
+        let source = "function f(...args: number[]) return args end";
+        let output = generate_code_with_target(source, LuaTarget::Lua54);
+        assert!(output.contains("local function f(...)"));
+        assert!(output.contains("local args = table.pack(...)"));
+
+        let output = generate_code_with_target(source, LuaTarget::Lua51);
+        assert!(output.contains(r#"local args = { n = select("#", ...), ... }"#));
+    }
+
     // Test that target selection works with currently supported operators
     #[test]
     fn test_target_selection() {
@@ -1773,6 +1995,143 @@ end
         );
     }
 
+    #[test]
+    fn test_reflection_registry_is_shared_between_modules() {
+        let source = r#"
+            class Animal {
+                name: string
+            }
+        "#;
+        let output = generate_code_with_reflection(source, super::ReflectionMode::Full);
+        assert!(
+            output.contains("__TypeRegistry = __TypeRegistry or {}"),
+            "registry should be extended, not replaced: {}",
+            output
+        );
+        assert!(
+            output.contains("__TypeIdToClass = __TypeIdToClass or {}"),
+            "reverse registry should be extended, not replaced: {}",
+            output
+        );
+        assert!(!output.contains("__TypeRegistry = {}"));
+    }
+
+    fn type_ids_in(output: &str) -> Vec<u32> {
+        output
+            .lines()
+            .filter_map(|line| line.split(".__typeId = ").nth(1))
+            .map(|id| id.trim().parse().expect("numeric type id"))
+            .collect()
+    }
+
+    #[test]
+    fn test_type_ids_unique_across_modules() {
+        let (interner, common) = StringInterner::new_with_common_identifiers();
+        let interner = Arc::new(interner);
+        let arena = Bump::new();
+        let parse = |source: &str| {
+            let handler = Arc::new(CollectingDiagnosticHandler::new());
+            let mut lexer = Lexer::new(source, handler.clone(), &interner);
+            let tokens = lexer.tokenize().expect("Lexing failed");
+            let mut parser = Parser::new(tokens, handler, &interner, &common, &arena);
+            parser.parse().expect("Parsing failed")
+        };
+        let first = parse("class Point {}\nclass Line {}");
+        let second = parse("class Point {}");
+
+        let mut registry = super::TypeIdRegistry::new();
+        registry.register_module("a.tl", first.statements, &interner);
+        registry.register_module("b.tl", second.statements, &interner);
+        let registry = Arc::new(registry);
+
+        let mut ids = Vec::new();
+        for (module_id, program) in [("a.tl", &first), ("b.tl", &second)] {
+            let mut generator =
+                CodeGenerator::new(interner.clone()).with_type_ids(registry.clone(), module_id);
+            ids.extend(type_ids_in(
+                &generator.generate(&MutableProgram::from_program(program)),
+            ));
+        }
+        assert_eq!(
+            ids,
+            vec![
+                super::TypeIdRegistry::stable_id("a.tl", "Point"),
+                super::TypeIdRegistry::stable_id("a.tl", "Line"),
+                super::TypeIdRegistry::stable_id("b.tl", "Point"),
+            ]
+        );
+
+        // A module generated without the registry (as when the other module
+        // comes from the incremental cache) still gets the same IDs
+        let mut generator = CodeGenerator::new(interner.clone())
+            .with_type_ids(Arc::new(super::TypeIdRegistry::new()), "b.tl");
+        let output = generator.generate(&MutableProgram::from_program(&second));
+        assert_eq!(type_ids_in(&output), vec![ids[2]]);
+    }
+
+    #[test]
+    fn test_type_registry_keyed_by_qualified_name() {
+        let (interner, common) = StringInterner::new_with_common_identifiers();
+        let interner = Arc::new(interner);
+        let arena = Bump::new();
+        let handler = Arc::new(CollectingDiagnosticHandler::new());
+        let mut lexer = Lexer::new("class Point {}", handler.clone(), &interner);
+        let tokens = lexer.tokenize().expect("Lexing failed");
+        let mut parser = Parser::new(tokens, handler, &interner, &common, &arena);
+        let program = parser.parse().expect("Parsing failed");
+
+        let mut generator = CodeGenerator::new(interner.clone())
+            .with_reflection_mode(super::ReflectionMode::Full)
+            .with_type_ids(Arc::new(super::TypeIdRegistry::new()), "src/geo.tl");
+        let output = generator.generate(&MutableProgram::from_program(&program));
+        let type_id = super::TypeIdRegistry::stable_id("src/geo.tl", "Point");
+        assert!(
+            output.contains(&format!(
+                "__TypeRegistry[\"src/geo.tl::Point\"] = {}",
+                type_id
+            )),
+            "{}",
+            output
+        );
+        assert!(output.contains(&format!("__TypeIdToClass[{}] = Point", type_id)));
+    }
+
+    #[test]
+    fn test_bundle_type_ids_unique_across_modules() {
+        let (interner, common) = StringInterner::new_with_common_identifiers();
+        let interner = Arc::new(interner);
+        let arena = Bump::new();
+        let parse = |source: &str| {
+            let handler = Arc::new(CollectingDiagnosticHandler::new());
+            let mut lexer = Lexer::new(source, handler.clone(), &interner);
+            let tokens = lexer.tokenize().expect("Lexing failed");
+            let mut parser = Parser::new(tokens, handler, &interner, &common, &arena);
+            parser.parse().expect("Parsing failed")
+        };
+        let first = parse("export class Point {}");
+        let second = parse("export class Point {}");
+        let modules = vec![
+            ("a".to_string(), &first, std::collections::HashMap::new()),
+            ("b".to_string(), &second, std::collections::HashMap::new()),
+        ];
+
+        let (output, _) = CodeGenerator::generate_bundle_with_options(
+            &modules,
+            "a",
+            LuaTarget::Lua54,
+            false,
+            None,
+            Some(interner.clone()),
+            None,
+            false,
+            crate::config::OutputFormat::Readable,
+        )
+        .expect("readable bundles always generate");
+        let ids = type_ids_in(&output);
+        assert_eq!(ids.len(), 2, "{}", output);
+        assert_ne!(ids[0], ids[1], "{}", output);
+    }
+
     #[test]
     fn test_reflection_none_mode() {
         let source = r#"
@@ -1901,4 +2260,584 @@ end
             output
         );
     }
+
+    #[test]
+    fn test_reflection_full_interfaces_and_generics() {
+        let source = r#"
+            interface Comparable<T> {
+                compareTo(other: T): number
+            }
+            class Box<T> {
+                value: T
+            }
+            class NumberBox extends Box<number> implements Comparable<NumberBox> {
+                compareTo(other: NumberBox): number {
+                    return 0
+                }
+            }
+        "#;
+        let output = generate_code_with_reflection(source, super::ReflectionMode::Full);
+        assert!(
+            output.contains(r#"Box.__typeParams = { { name = "T" } }"#),
+            "class type params: {}",
+            output
+        );
+        assert!(
+            output.contains(
+                r#"NumberBox.__interfaces = { { name = "Comparable", typeArgs = { "NumberBox" } } }"#
+            ),
+            "interfaces with type args: {}",
+            output
+        );
+        assert!(
+            output
+                .contains(r#"NumberBox.__parentType = { name = "Box", typeArgs = { "number" } }"#),
+            "parent type args: {}",
+            output
+        );
+        assert!(
+            output.contains(r#"typeName = "T""#),
+            "generic field type: {}",
+            output
+        );
+    }
+
+    #[test]
+    fn test_reflection_full_signatures_and_decorators() {
+        let source = r#"
+            @entity("users")
+            class Repo {
+                @column
+                name: string | nil
+
+                @log("debug", 2)
+                find(id: number, tags: string[], limit?: number): Repo | nil {
+                    return nil
+                }
+            }
+        "#;
+        let output = generate_code_with_reflection(source, super::ReflectionMode::Full);
+        assert!(
+            output.contains(
+                r#"signature = { typeParams = {}, params = { { name = "id", type = "number" }, { name = "tags", type = "string[]" }, { name = "limit", type = "number", optional = true } }, returnType = "Repo | nil" }"#
+            ),
+            "full method signature: {}",
+            output
+        );
+        assert!(
+            output.contains(r#"decorators = { { name = "log", args = { "debug", 2 } } }"#),
+            "method decorator args: {}",
+            output
+        );
+        assert!(
+            output.contains(
+                r#"typeName = "string | nil", decorators = { { name = "column", args = {} } }"#
+            ),
+            "field type and decorator: {}",
+            output
+        );
+        assert!(
+            output.contains(r#"Repo.__decorators = { { name = "entity", args = { "users" } } }"#),
+            "class decorators: {}",
+            output
+        );
+        // Compact codes stay for the existing APIs
+        assert!(
+            output.contains(r#"params = "n[s]n""#),
+            "compact: {}",
+            output
+        );
+    }
+
+    #[test]
+    fn test_reflection_full_decorator_args_evaluated_once() {
+        let source = r#"
+            class Service {
+                @inject(makeToken(), "db")
+                connect(): void {}
+            }
+        "#;
+        let output = generate_code_with_reflection(source, super::ReflectionMode::Full);
+        assert_eq!(
+            output.matches("makeToken()").count(),
+            1,
+            "argument evaluated once: {}",
+            output
+        );
+        assert!(
+            output.contains(r#"local __decoratorArgs1 = { makeToken(), "db" }"#),
+            "captured args: {}",
+            output
+        );
+        assert!(
+            output.contains("inject(__decoratorArgs1[1], __decoratorArgs1[2])"),
+            "decorator call uses captured args: {}",
+            output
+        );
+        assert!(
+            output.contains(r#"decorators = { { name = "inject", args = __decoratorArgs1 } }"#),
+            "metadata records captured args: {}",
+            output
+        );
+    }
+
+    #[test]
+    fn test_reflection_full_enum_values() {
+        let source = r#"
+            enum Color { Red, Green = 5 }
+        "#;
+        let output = generate_code_with_reflection(source, super::ReflectionMode::Full);
+        assert!(
+            output.contains(
+                r#"__EnumMetadata[Color] = { typeName = "Color", values = { { name = "Red", value = Color.Red }, { name = "Green", value = Color.Green } } }"#
+            ),
+            "enum values: {}",
+            output
+        );
+        // Nothing is added to the enum table itself, so pairs() sees only members
+        assert!(
+            !output.contains("Color.__"),
+            "enum table untouched: {}",
+            output
+        );
+
+        let output = generate_code_with_reflection(source, super::ReflectionMode::Selective);
+        assert!(
+            !output.contains("__EnumMetadata"),
+            "enum metadata is full mode only: {}",
+            output
+        );
+    }
+
+    #[test]
+    fn test_reflection_runtime_api_reads_full_metadata() {
+        // The Reflect functions and the metadata they read are emitted together
+        let source = r#"
+            import { Reflect } from "@std/reflection"
+            interface Shape {
+                area(): number
+            }
+            enum Color { Red, Green }
+            @entity("shapes")
+            class Square implements Shape {
+                @column
+                side: number
+                area(): number {
+                    return self.side * self.side
+                }
+            }
+        "#;
+        let output = generate_code_with_reflection(source, super::ReflectionMode::Full);
+
+        // getInterfaces walks __interfaces up the __parent chain
+        assert!(output.contains("function Reflect.getInterfaces(obj)"));
+        assert!(
+            output.contains(r#"Square.__interfaces = { { name = "Shape", typeArgs = {} } }"#),
+            "interfaces: {}",
+            output
+        );
+
+        // getDecorators reads class and member decorators
+        assert!(output.contains("function Reflect.getDecorators(obj, memberName)"));
+        assert!(
+            output
+                .contains(r#"Square.__decorators = { { name = "entity", args = { "shapes" } } }"#),
+            "class decorators: {}",
+            output
+        );
+        assert!(
+            output.contains(r#"decorators = { { name = "column", args = {} } }"#),
+            "field decorators: {}",
+            output
+        );
+
+        // getEnumValues reads the side table, not the enum
+        assert!(output.contains("function Reflect.getEnumValues(enum)"));
+        assert!(output.contains("__EnumMetadata[enum]"));
+        assert!(output.contains("__EnumMetadata[Color] = {"), "{}", output);
+
+        // getMethodSignature prefers the full signature over compact codes
+        assert!(output.contains("function Reflect.getMethodSignature(obj, methodName)"));
+        assert!(
+            output
+                .contains(r#"signature = { typeParams = {}, params = {}, returnType = "number" }"#),
+            "method signature: {}",
+            output
+        );
+    }
+
+    #[test]
+    fn test_reflection_extended_metadata_only_in_full_mode() {
+        let source = r#"
+            import { Reflect } from "@std/reflection"
+            class Point implements Shape {
+                x: number
+                move(dx: number): void {}
+            }
+        "#;
+        let output = generate_code_with_reflection(source, super::ReflectionMode::Selective);
+        assert!(
+            output.contains("__ownMethods"),
+            "selective reflection: {}",
+            output
+        );
+        assert!(
+            !output.contains("__interfaces"),
+            "no interfaces: {}",
+            output
+        );
+        assert!(!output.contains("signature ="), "no signatures: {}", output);
+    }
+
+    fn generate_code_minified(source: &str) -> String {
+        let handler = Arc::new(CollectingDiagnosticHandler::new());
+        let (interner, common) = StringInterner::new_with_common_identifiers();
+        let interner = Arc::new(interner);
+        let arena = Bump::new();
+        let mut lexer = Lexer::new(source, handler.clone(), &interner);
+        let tokens = lexer.tokenize().expect("Lexing failed");
+        let mut parser = Parser::new(tokens, handler, &interner, &common, &arena);
+        let program = parser.parse().expect("Parsing failed");
+        let mutable = MutableProgram::from_program(&program);
+
+        let mut generator = CodeGenerator::new(interner.clone())
+            .with_output_format(crate::config::OutputFormat::Minified);
+        generator.generate(&mutable)
+    }
+
+    #[test]
+    fn test_minified_renames_locals_and_params() {
+        let source = r#"
+            function add(first: number, second: number): number {
+                const total = first + second
+                return total
+            }
+        "#;
+        let output = generate_code_minified(source);
+        assert!(!output.contains('\n'), "no line breaks: {}", output);
+        assert!(!output.contains("first"), "params renamed: {}", output);
+        assert!(!output.contains("total"), "locals renamed: {}", output);
+        assert!(
+            output.contains("local d=b+c return d"),
+            "parens folded: {}",
+            output
+        );
+    }
+
+    #[test]
+    fn test_minified_keeps_exported_names() {
+        let source = r#"
+            export function greet(name: string): string {
+                return "hi " .. name
+            }
+        "#;
+        let output = generate_code_minified(source);
+        assert!(output.contains("greet"), "export kept: {}", output);
+        assert!(
+            output.contains("M.greet=greet"),
+            "export wiring: {}",
+            output
+        );
+        assert!(!output.contains("name"), "param renamed: {}", output);
+    }
+
+    #[test]
+    fn test_minified_keeps_globals() {
+        let output = generate_code_minified("const x = 1\nprint(x)");
+        assert!(output.contains("print("), "global kept: {}", output);
+        assert!(output.contains("local a=1"), "local renamed: {}", output);
+    }
+
+    #[test]
+    fn test_minified_bundle() {
+        let (interner, common) = StringInterner::new_with_common_identifiers();
+        let interner = Arc::new(interner);
+        let arena = Bump::new();
+        let handler = Arc::new(CollectingDiagnosticHandler::new());
+        let mut lexer = Lexer::new(
+            "const total = 1 + 2\nprint(total)",
+            handler.clone(),
+            &interner,
+        );
+        let tokens = lexer.tokenize().expect("Lexing failed");
+        let mut parser = Parser::new(tokens, handler, &interner, &common, &arena);
+        let program = parser.parse().expect("Parsing failed");
+        let modules = vec![(
+            "main".to_string(),
+            &program,
+            std::collections::HashMap::new(),
+        )];
+
+        let (output, _) = CodeGenerator::generate_bundle_with_options(
+            &modules,
+            "main",
+            LuaTarget::Lua54,
+            false,
+            None,
+            Some(interner.clone()),
+            None,
+            false,
+            crate::config::OutputFormat::Minified,
+        )
+        .expect("bundle should minify");
+        assert!(!output.contains('\n'), "no line breaks: {}", output);
+        assert!(!output.contains("total"), "locals renamed: {}", output);
+        assert!(
+            !output.contains("-- Module"),
+            "comments dropped: {}",
+            output
+        );
+        assert!(output.contains("print("), "global kept: {}", output);
+    }
+
+    fn generate_code_with_printer(source: &str, options: super::PrinterOptions) -> String {
+        let handler = Arc::new(CollectingDiagnosticHandler::new());
+        let (interner, common) = StringInterner::new_with_common_identifiers();
+        let interner = Arc::new(interner);
+        let arena = Bump::new();
+        let mut lexer = Lexer::new(source, handler.clone(), &interner);
+        let tokens = lexer.tokenize().expect("Lexing failed");
+        let mut parser = Parser::new(tokens, handler, &interner, &common, &arena);
+        let program = parser.parse().expect("Parsing failed");
+        let mutable = MutableProgram::from_program(&program);
+
+        let mut generator = CodeGenerator::new(interner.clone())
+            .with_printer_options(options)
+            .with_source_text(source);
+        generator.generate(&mutable)
+    }
+
+    #[test]
+    fn test_printer_indent_with_tabs() {
+        let source = "function f(): number {\n    return 1\n}";
+        let options = super::PrinterOptions {
+            use_tabs: true,
+            ..Default::default()
+        };
+        let output = generate_code_with_printer(source, options);
+        assert!(output.contains("\n\treturn 1\n"), "tab indent: {}", output);
+    }
+
+    #[test]
+    fn test_printer_quote_style() {
+        let options = super::PrinterOptions {
+            quote_style: super::QuoteStyle::Single,
+            ..Default::default()
+        };
+        let output = generate_code_with_printer("const s = \"it's\"", options);
+        assert!(
+            output.contains(r"local s = 'it\'s'"),
+            "single quotes: {}",
+            output
+        );
+    }
+
+    #[test]
+    fn test_printer_wraps_long_calls() {
+        let source = "print(first_argument, second_argument, third_argument)";
+        let options = super::PrinterOptions {
+            max_line_width: Some(40),
+            ..Default::default()
+        };
+        let output = generate_code_with_printer(source, options);
+        assert!(
+            output.contains(
+                "print(\n    first_argument,\n    second_argument,\n    third_argument\n)"
+            ),
+            "wrapped call: {}",
+            output
+        );
+    }
+
+    #[test]
+    fn test_printer_preserves_comments() {
+        let source = r#"
+-- module header
+
+--- Adds two numbers
+---@param a number
+function add(a: number, b: number): number {
+    -- sum them
+    return a + b -- done
+}
+const x = add(1, 2) -- three
+"#;
+        let options = super::PrinterOptions {
+            preserve_comments: true,
+            ..Default::default()
+        };
+        let output = generate_code_with_printer(source, options);
+        assert!(
+            output.contains(
+                "-- module header\n--- Adds two numbers\n---@param a number\nlocal function add"
+            ),
+            "leading and doc comments: {}",
+            output
+        );
+        assert!(
+            output.contains("    -- sum them\n"),
+            "body comment: {}",
+            output
+        );
+        assert!(output.contains("-- done"), "trailing in body: {}", output);
+        assert!(
+            output.contains("local x = add(1, 2) -- three"),
+            "trailing comment: {}",
+            output
+        );
+    }
+
+    #[test]
+    fn test_printer_comments_off_by_default() {
+        let output =
+            generate_code_with_printer("-- hidden\nconst x = 1", super::PrinterOptions::default());
+        assert!(!output.contains("hidden"), "comments dropped: {}", output);
+    }
+
+    fn generate_code_annotated(source: &str) -> String {
+        let handler = Arc::new(CollectingDiagnosticHandler::new());
+        let (interner, common) = StringInterner::new_with_common_identifiers();
+        let interner = Arc::new(interner);
+        let arena = Bump::new();
+        let mut lexer = Lexer::new(source, handler.clone(), &interner);
+        let tokens = lexer.tokenize().expect("Lexing failed");
+        let mut parser = Parser::new(tokens, handler, &interner, &common, &arena);
+        let program = parser.parse().expect("Parsing failed");
+        let mutable = MutableProgram::from_program(&program);
+
+        let mut generator = CodeGenerator::new(interner.clone()).with_annotations(true);
+        generator.generate(&mutable)
+    }
+
+    #[test]
+    fn test_annotations_function_declaration() {
+        let source = r#"
+            function greet<T>(name: string, times?: number, ...rest: T[]): string {
+                return name
+            }
+        "#;
+        let output = generate_code_annotated(source);
+        assert!(
+            output.contains(
+                "---@generic T\n---@param name string\n---@param times? number\n---@param ... T\n---@return string\nlocal function greet"
+            ),
+            "function annotations: {}",
+            output
+        );
+    }
+
+    #[test]
+    fn test_annotations_class_declaration() {
+        let source = r#"
+            class Point {
+                x: number
+                private tag: string | nil
+                move(dx: number): void {
+                    self.x = self.x + dx
+                }
+                get length(): number {
+                    return self.x
+                }
+            }
+        "#;
+        let output = generate_code_annotated(source);
+        assert!(
+            output.contains(
+                "---@class Point\n---@field x number\n---@field private tag string|nil\nlocal Point = {}"
+            ),
+            "class annotations: {}",
+            output
+        );
+        assert!(
+            output.contains("---@param dx number\nfunction Point:move(dx)"),
+            "method annotations: {}",
+            output
+        );
+        assert!(
+            output.contains("---@return number\nfunction Point:get_length()"),
+            "getter annotations: {}",
+            output
+        );
+    }
+
+    #[test]
+    fn test_annotations_constructor_and_static_members() {
+        let source = r#"
+            class Counter {
+                static instances: number
+                value: number
+                constructor(start: number) {
+                    self.value = start
+                }
+            }
+            class Pair(public first: string, public second: number) {}
+            class Empty {}
+        "#;
+        let output = generate_code_annotated(source);
+        assert!(
+            output.contains("---@class Counter\n---@field value number\nlocal Counter = {}"),
+            "static property is not an instance field: {}",
+            output
+        );
+        assert!(
+            output.contains("---@type number\nCounter.instances = nil"),
+            "static property on the class table: {}",
+            output
+        );
+        assert!(
+            output.contains(
+                "---@param start number\n---@return Counter\nfunction Counter.new(start)"
+            ),
+            "constructor annotations: {}",
+            output
+        );
+        assert!(
+            output.contains(
+                "---@param first string\n---@param second number\n---@return Pair\nfunction Pair.new(first, second)"
+            ),
+            "primary constructor annotations: {}",
+            output
+        );
+        assert!(
+            output.contains("---@return Empty\nfunction Empty.new()"),
+            "default constructor annotation: {}",
+            output
+        );
+    }
+
+    #[test]
+    fn test_annotations_interface_alias_and_enum() {
+        let source = r#"
+            interface Shape {
+                name: string
+                area(scale: number): number
+            }
+            type Callback = (value: number) => boolean
+            enum Color { Red, Green }
+        "#;
+        let output = generate_code_annotated(source);
+        assert!(
+            output.contains(
+                "---@class Shape\n---@field name string\n---@field area fun(self: Shape, scale: number): number"
+            ),
+            "interface annotations: {}",
+            output
+        );
+        assert!(
+            output.contains("---@alias Callback fun(value: number): boolean"),
+            "alias annotation: {}",
+            output
+        );
+        assert!(
+            output.contains("---@enum Color\nlocal Color = {"),
+            "enum annotation: {}",
+            output
+        );
+    }
+
+    #[test]
+    fn test_annotations_off_by_default() {
+        let output = generate_code("function f(a: number): number { return a }");
+        assert!(!output.contains("---@"), "no annotations: {}", output);
+    }
 }
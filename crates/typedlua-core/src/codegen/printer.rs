@@ -0,0 +1,524 @@
+//! Pretty-printer settings for readable output: indentation, line width,
+//! quote style and carrying source comments into the generated Lua.
+
+use super::minifier::{self, Token};
+use super::sourcemap::TokenRelocation;
+use super::CodeGenerator;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use typedlua_parser::ast::statement::Statement;
+use typedlua_parser::span::Span;
+
+/// Quote character used for string literals
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QuoteStyle {
+    #[default]
+    Double,
+    Single,
+    /// Double quotes, unless the string contains double quotes but no single ones
+    Auto,
+}
+
+/// Layout options for readable and compact output. Minified output ignores
+/// all of them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PrinterOptions {
+    /// Spaces per indentation level (ignored when `use_tabs` is set)
+    pub indent_width: usize,
+    pub use_tabs: bool,
+    /// Lines longer than this are wrapped at argument lists and table
+    /// constructors; `None` never wraps
+    pub max_line_width: Option<usize>,
+    pub quote_style: QuoteStyle,
+    /// Carry leading, trailing and doc (`---`) comments from the source
+    pub preserve_comments: bool,
+}
+
+impl Default for PrinterOptions {
+    fn default() -> Self {
+        Self {
+            indent_width: 4,
+            use_tabs: false,
+            max_line_width: None,
+            quote_style: QuoteStyle::Double,
+            preserve_comments: false,
+        }
+    }
+}
+
+impl PrinterOptions {
+    /// The string written once per indentation level
+    pub fn indent_unit(&self) -> String {
+        if self.use_tabs {
+            "\t".to_string()
+        } else {
+            " ".repeat(self.indent_width)
+        }
+    }
+
+    /// Quote and escape a string literal
+    pub fn quote(&self, s: &str) -> String {
+        let quote = match self.quote_style {
+            QuoteStyle::Double => '"',
+            QuoteStyle::Single => '\'',
+            QuoteStyle::Auto if s.contains('"') && !s.contains('\'') => '\'',
+            QuoteStyle::Auto => '"',
+        };
+        let mut out = String::with_capacity(s.len() + 2);
+        out.push(quote);
+        for c in s.chars() {
+            if c == '\\' || c == quote {
+                out.push('\\');
+            }
+            out.push(c);
+        }
+        out.push(quote);
+        out
+    }
+}
+
+/// Span of a statement, for the statement kinds comments are attached to
+pub(crate) fn statement_span(stmt: &Statement) -> Option<Span> {
+    Some(match stmt {
+        Statement::Variable(decl) => decl.span,
+        Statement::Function(decl) => decl.span,
+        Statement::If(stmt) => stmt.span,
+        Statement::While(stmt) => stmt.span,
+        Statement::Repeat(stmt) => stmt.span,
+        Statement::Return(stmt) => stmt.span,
+        Statement::Expression(expr) => expr.span,
+        Statement::Block(block) => block.span,
+        Statement::Interface(decl) => decl.span,
+        Statement::TypeAlias(decl) => decl.span,
+        Statement::Enum(decl) => decl.span,
+        Statement::Class(decl) => decl.span,
+        Statement::Import(import) => import.span,
+        Statement::Export(export) => export.span,
+        _ => return None,
+    })
+}
+
+/// Byte ranges of the comments in `source[from..to]`, skipping string literals
+fn scan_comments(source: &str, from: usize, to: usize) -> Vec<(usize, usize)> {
+    let bytes = source.as_bytes();
+    let to = to.min(bytes.len());
+    let mut comments = Vec::new();
+    let mut pos = from;
+
+    while pos < to {
+        match bytes[pos] {
+            quote @ (b'"' | b'\'' | b'`') => {
+                pos += 1;
+                while pos < to && bytes[pos] != quote {
+                    pos += if bytes[pos] == b'\\' { 2 } else { 1 };
+                }
+                pos += 1;
+            }
+            b'-' if bytes.get(pos + 1) == Some(&b'-') => {
+                let end = comment_end(source, pos);
+                comments.push((pos, end));
+                pos = end;
+            }
+            _ => pos += 1,
+        }
+    }
+    comments
+}
+
+/// End of the comment starting at `pos` (a `--` line comment or a `--[[ ]]`
+/// block comment)
+fn comment_end(source: &str, pos: usize) -> usize {
+    let rest = &source[pos + 2..];
+    if let Some(after_open) = rest.strip_prefix('[') {
+        let level = after_open.bytes().take_while(|&b| b == b'=').count();
+        if after_open[level..].starts_with('[') {
+            let close = format!("]{}]", "=".repeat(level));
+            if let Some(i) = rest.find(&close) {
+                return pos + 2 + i + close.len();
+            }
+            return source.len();
+        }
+    }
+    rest.find('\n').map_or(source.len(), |i| pos + 2 + i)
+}
+
+impl CodeGenerator {
+    /// Apply printer options. Call after `with_output_format`, which resets
+    /// the indentation for its format.
+    pub fn with_printer_options(mut self, options: PrinterOptions) -> Self {
+        if self.emitter.is_readable() {
+            self.emitter = self.emitter.with_indent_str(options.indent_unit());
+        }
+        self.printer_options = options;
+        self
+    }
+
+    /// The `.tl` source, needed to carry comments into the output
+    pub fn with_source_text(mut self, source: impl Into<Arc<str>>) -> Self {
+        self.source_text = Some(source.into());
+        self
+    }
+
+    /// Quote a string literal in the configured style
+    pub(crate) fn quote_string(&self, s: &str) -> String {
+        self.printer_options.quote(s)
+    }
+
+    /// Span to attach comments to, if comments are being carried over
+    pub(crate) fn comment_span(&self, stmt: &Statement) -> Option<Span> {
+        if !self.printer_options.preserve_comments
+            || self.source_text.is_none()
+            || self.emitter.is_minified()
+        {
+            return None;
+        }
+        statement_span(stmt).filter(|span| span.end > span.start)
+    }
+
+    /// Write the comments between the previous statement and this one
+    pub(crate) fn emit_leading_comments(&mut self, span: Span) {
+        let Some(source) = self.source_text.clone() else {
+            return;
+        };
+        let start = span.start as usize;
+        if start < self.comment_cursor {
+            return;
+        }
+        for (from, to) in scan_comments(&source, self.comment_cursor, start) {
+            for line in source[from..to].lines() {
+                self.write_indent();
+                self.writeln(line.trim());
+            }
+        }
+        self.comment_cursor = start;
+    }
+
+    /// Append a comment that follows the statement on its last source line
+    pub(crate) fn emit_trailing_comment(&mut self, span: Span) {
+        let Some(source) = self.source_text.clone() else {
+            return;
+        };
+        let end = (span.end as usize).min(source.len());
+        if end < self.comment_cursor {
+            return;
+        }
+        self.comment_cursor = end;
+
+        let rest = &source[end..];
+        let offset = rest.len() - rest.trim_start_matches([' ', '\t', ';']).len();
+        let start = end + offset;
+        if !source[start..].starts_with("--") {
+            return;
+        }
+        let comment_end = comment_end(&source, start);
+        let comment = &source[start..comment_end];
+        if comment.contains('\n') {
+            // Multi-line block comments are left for the next statement
+            return;
+        }
+        self.emitter
+            .append_to_last_line(&format!(" {}", comment.trim_end()));
+        self.comment_cursor = comment_end;
+    }
+
+    /// Wrap lines longer than the configured width and move the source map
+    /// along with the tokens
+    pub(crate) fn wrap_long_lines(&mut self) {
+        let Some(width) = self.printer_options.max_line_width else {
+            return;
+        };
+        let unit = self.printer_options.indent_unit();
+        let Some((code, relocations)) = wrap_lines(self.emitter.output_ref(), width, &unit) else {
+            return;
+        };
+        if let Some(source_map) = self.emitter.source_map_mut() {
+            source_map.apply_relocations(&relocations);
+        }
+        *self.emitter.output_mut() = code;
+    }
+}
+
+/// One output line: indentation plus a byte range of the original chunk
+#[derive(Debug, Clone)]
+struct Piece {
+    indent: String,
+    start: usize,
+    end: usize,
+}
+
+/// Deepest nesting of wraps within one original line
+const MAX_WRAP_DEPTH: usize = 16;
+
+/// Wrap lines of `code` longer than `width` at their widest argument list or
+/// table constructor, one element per line. Lines spanned by long strings
+/// are left alone. Returns `None` if `code` cannot be tokenized.
+pub(crate) fn wrap_lines(
+    code: &str,
+    width: usize,
+    indent_unit: &str,
+) -> Option<(String, Vec<TokenRelocation>)> {
+    let tokens = minifier::tokenize(code)?;
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(code.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+
+    let mut frozen = vec![false; line_starts.len()];
+    for token in &tokens {
+        let extra_lines = token.text.matches('\n').count();
+        for line in token.line..=token.line + extra_lines {
+            if extra_lines > 0 {
+                frozen[line] = true;
+            }
+        }
+    }
+
+    let mut lines = Vec::with_capacity(line_starts.len());
+    let mut relocations = Vec::with_capacity(tokens.len());
+    let mut next_token = 0;
+
+    for (line_no, &start) in line_starts.iter().enumerate() {
+        let end = line_starts
+            .get(line_no + 1)
+            .map_or(code.len(), |&next| next - 1);
+        let line = &code[start..end];
+
+        let first = next_token;
+        while next_token < tokens.len() && tokens[next_token].line == line_no {
+            next_token += 1;
+        }
+        let line_tokens = &tokens[first..next_token];
+
+        let mut pieces = Vec::new();
+        if !frozen[line_no] && !line_tokens.is_empty() && line.chars().count() > width {
+            let content = line.trim_start();
+            let indent_len = line.len() - content.len();
+            let piece = Piece {
+                indent: line[..indent_len].to_string(),
+                start: start + indent_len,
+                end: start + indent_len + content.trim_end().len(),
+            };
+            wrap_piece(code, line_tokens, piece, width, indent_unit, 0, &mut pieces);
+        } else {
+            pieces.push(Piece {
+                indent: String::new(),
+                start,
+                end,
+            });
+        }
+
+        for piece in pieces {
+            let indent_columns = piece.indent.chars().count();
+            for token in line_tokens
+                .iter()
+                .filter(|t| t.offset >= piece.start && t.offset < piece.end)
+            {
+                relocations.push(TokenRelocation {
+                    from: (token.line, token.column),
+                    to: (
+                        lines.len(),
+                        indent_columns + code[piece.start..token.offset].chars().count(),
+                    ),
+                    original_name: None,
+                });
+            }
+            lines.push(format!("{}{}", piece.indent, &code[piece.start..piece.end]));
+        }
+    }
+
+    Some((lines.join("\n"), relocations))
+}
+
+fn is_opener(text: &str) -> bool {
+    matches!(text, "(" | "{" | "[")
+}
+
+fn is_closer(text: &str) -> bool {
+    matches!(text, ")" | "}" | "]")
+}
+
+fn wrap_piece(
+    code: &str,
+    line_tokens: &[Token],
+    piece: Piece,
+    width: usize,
+    indent_unit: &str,
+    depth: usize,
+    out: &mut Vec<Piece>,
+) {
+    let length = piece.indent.chars().count() + code[piece.start..piece.end].chars().count();
+    let tokens: Vec<&Token> = line_tokens
+        .iter()
+        .filter(|t| t.offset >= piece.start && t.offset < piece.end)
+        .collect();
+    if length <= width || depth >= MAX_WRAP_DEPTH {
+        out.push(piece);
+        return;
+    }
+
+    // The widest top-level `(...)` or `{...}` with something inside
+    let mut best: Option<(usize, usize)> = None;
+    let mut stack = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        if token.kind != minifier::TokenKind::Symbol {
+            continue;
+        }
+        if is_opener(token.text) {
+            stack.push(i);
+        } else if is_closer(token.text) {
+            let Some(open) = stack.pop() else {
+                continue;
+            };
+            let span = token.offset - tokens[open].offset;
+            if stack.is_empty()
+                && tokens[open].text != "["
+                && i > open + 1
+                && !matches!(best, Some((o, c)) if span <= tokens[c].offset - tokens[o].offset)
+            {
+                best = Some((open, i));
+            }
+        }
+    }
+    let Some((open, close)) = best else {
+        out.push(piece);
+        return;
+    };
+
+    let skip_whitespace = |mut pos: usize| {
+        while code.as_bytes()[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        pos
+    };
+
+    let mut items = Vec::new();
+    let mut item_start = tokens[open + 1].offset;
+    let mut nesting = 0;
+    for k in open + 1..close {
+        let text = tokens[k].text;
+        if tokens[k].kind != minifier::TokenKind::Symbol {
+            continue;
+        }
+        if is_opener(text) {
+            nesting += 1;
+        } else if is_closer(text) {
+            nesting -= 1;
+        } else if nesting == 0 && (text == "," || text == ";") {
+            items.push((item_start, tokens[k].offset + 1));
+            item_start = skip_whitespace(tokens[k].offset + 1);
+        }
+    }
+    let last = tokens[close - 1];
+    if item_start < last.offset + last.text.len() {
+        items.push((item_start, last.offset + last.text.len()));
+    }
+
+    let inner_indent = format!("{}{}", piece.indent, indent_unit);
+    let head = Piece {
+        indent: piece.indent.clone(),
+        start: piece.start,
+        end: tokens[open].offset + 1,
+    };
+    wrap_piece(code, line_tokens, head, width, indent_unit, depth + 1, out);
+    for (start, end) in items {
+        let item = Piece {
+            indent: inner_indent.clone(),
+            start,
+            end,
+        };
+        wrap_piece(code, line_tokens, item, width, indent_unit, depth + 1, out);
+    }
+    let tail = Piece {
+        indent: piece.indent,
+        start: tokens[close].offset,
+        end: piece.end,
+    };
+    wrap_piece(code, line_tokens, tail, width, indent_unit, depth + 1, out);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wrap(code: &str, width: usize) -> String {
+        wrap_lines(code, width, "    ").unwrap().0
+    }
+
+    #[test]
+    fn test_quote_styles() {
+        let mut options = PrinterOptions::default();
+        assert_eq!(options.quote(r#"say "hi""#), r#""say \"hi\"""#);
+
+        options.quote_style = QuoteStyle::Single;
+        assert_eq!(options.quote("it's"), r"'it\'s'");
+
+        options.quote_style = QuoteStyle::Auto;
+        assert_eq!(options.quote(r#"say "hi""#), r#"'say "hi"'"#);
+        assert_eq!(options.quote("plain"), "\"plain\"");
+    }
+
+    #[test]
+    fn test_indent_unit() {
+        let mut options = PrinterOptions {
+            indent_width: 2,
+            ..Default::default()
+        };
+        assert_eq!(options.indent_unit(), "  ");
+        options.use_tabs = true;
+        assert_eq!(options.indent_unit(), "\t");
+    }
+
+    #[test]
+    fn test_short_lines_untouched() {
+        let code = "local x = f(a, b)\nreturn x\n";
+        assert_eq!(wrap(code, 80), code);
+    }
+
+    #[test]
+    fn test_wraps_argument_list() {
+        assert_eq!(
+            wrap("    print(alpha, beta, gamma)", 20),
+            "    print(\n        alpha,\n        beta,\n        gamma\n    )"
+        );
+    }
+
+    #[test]
+    fn test_wraps_widest_constructor_recursively() {
+        assert_eq!(
+            wrap("local t = { name = f(x), items = { 1, 2, 3 } }", 24),
+            "local t = {\n    name = f(x),\n    items = { 1, 2, 3 }\n}"
+        );
+        assert_eq!(
+            wrap("local t = { items = { first, second, third } }", 24),
+            "local t = {\n    items = {\n        first,\n        second,\n        third\n    }\n}"
+        );
+    }
+
+    #[test]
+    fn test_keeps_trailing_comment_and_long_strings() {
+        assert_eq!(
+            wrap("f(aaaa, bbbb) -- note", 10),
+            "f(\n    aaaa,\n    bbbb\n) -- note"
+        );
+        let long = "local s = f([[a,\nb]], c, d, e, f, g)";
+        assert_eq!(wrap(long, 10), long);
+    }
+
+    #[test]
+    fn test_relocations_follow_wrapped_tokens() {
+        let (_, relocations) = wrap_lines("print(alpha, beta)", 10, "  ").unwrap();
+        let beta = relocations.iter().find(|r| r.from == (0, 13)).unwrap();
+        assert_eq!(beta.to, (2, 2));
+    }
+
+    #[test]
+    fn test_scan_comments_skips_strings() {
+        let source = "x = \"--no\" -- yes\n--[[ block ]] y";
+        let comments: Vec<&str> = scan_comments(source, 0, source.len())
+            .into_iter()
+            .map(|(s, e)| &source[s..e])
+            .collect();
+        assert_eq!(comments, vec!["-- yes", "--[[ block ]]"]);
+    }
+}
@@ -0,0 +1,254 @@
+//! Extended reflection metadata for `ReflectionMode::Full`.
+//!
+//! Selective reflection only records compact type codes (see
+//! `encode_type_code`). In full mode classes also record the interfaces they
+//! implement, their generic parameters, the type arguments given to their
+//! supertypes, full method signatures and decorator applications, and enums
+//! record their members. Types are rendered in TypedLua syntax.
+
+use super::declarations::DeclarationGenerator;
+use super::{CodeGenerator, ReflectionMode};
+use typedlua_parser::ast::pattern::Pattern;
+use typedlua_parser::ast::statement::*;
+use typedlua_parser::ast::types::{Type, TypeKind, TypeParameter};
+use typedlua_runtime::reflection;
+
+/// Lua array constructor for already-rendered items
+fn lua_list(items: &[String]) -> String {
+    if items.is_empty() {
+        "{}".to_string()
+    } else {
+        format!("{{ {} }}", items.join(", "))
+    }
+}
+
+impl CodeGenerator {
+    pub(crate) fn emit_full_reflection(&self) -> bool {
+        self.reflection_mode == ReflectionMode::Full
+    }
+
+    /// Render a type as a quoted Lua string in TypedLua syntax
+    fn reflect_type(&self, ty: &Type) -> String {
+        self.quote_string(&DeclarationGenerator::new(&self.interner).type_to_string(ty))
+    }
+
+    /// `{ name = "Base", typeArgs = { "number" } }` for a supertype reference
+    fn reflect_supertype(&self, ty: &Type) -> String {
+        let (name, type_args) = match &ty.kind {
+            TypeKind::Reference(type_ref) => (
+                self.resolve(type_ref.name.node),
+                type_ref
+                    .type_arguments
+                    .iter()
+                    .flat_map(|args| args.iter())
+                    .map(|arg| self.reflect_type(arg))
+                    .collect::<Vec<_>>(),
+            ),
+            _ => (
+                DeclarationGenerator::new(&self.interner).type_to_string(ty),
+                Vec::new(),
+            ),
+        };
+        format!(
+            "{{ name = {}, typeArgs = {} }}",
+            self.quote_string(&name),
+            lua_list(&type_args)
+        )
+    }
+
+    fn reflect_type_params(&self, type_parameters: Option<&[TypeParameter]>) -> String {
+        let params: Vec<String> = type_parameters
+            .into_iter()
+            .flatten()
+            .map(|param| {
+                let name = self.quote_string(&self.resolve(param.name.node));
+                match &param.constraint {
+                    Some(constraint) => format!(
+                        "{{ name = {}, constraint = {} }}",
+                        name,
+                        self.reflect_type(constraint)
+                    ),
+                    None => format!("{{ name = {} }}", name),
+                }
+            })
+            .collect();
+        lua_list(&params)
+    }
+
+    /// Decorator name as written, e.g. `log` or `metrics.timed`
+    fn decorator_name(&self, expr: &DecoratorExpression) -> String {
+        match expr {
+            DecoratorExpression::Identifier(name) => self.resolve(name.node),
+            DecoratorExpression::Call { callee, .. } => self.decorator_name(callee),
+            DecoratorExpression::Member {
+                object, property, ..
+            } => format!(
+                "{}.{}",
+                self.decorator_name(object),
+                self.resolve(property.node)
+            ),
+        }
+    }
+
+    /// `{ { name = "log", args = { "debug" } } }`. Arguments are evaluated
+    /// once at class definition time: decorators applied at runtime record
+    /// the table their call was made with (see `capture_decorator_args`),
+    /// the others evaluate theirs here.
+    fn reflect_decorators(&mut self, decorators: &[Decorator]) -> String {
+        let entries: Vec<String> = decorators
+            .iter()
+            .map(|decorator| {
+                let name = self.quote_string(&self.decorator_name(&decorator.expression));
+                let args = match &decorator.expression {
+                    DecoratorExpression::Call {
+                        arguments, span, ..
+                    } => match self.decorator_args.get(&(span.start, span.end)) {
+                        Some(local) => local.clone(),
+                        None => lua_list(
+                            &arguments
+                                .iter()
+                                .map(|arg| self.expression_to_string(arg))
+                                .collect::<Vec<_>>(),
+                        ),
+                    },
+                    _ => lua_list(&[]),
+                };
+                format!("{{ name = {}, args = {} }}", name, args)
+            })
+            .collect();
+        lua_list(&entries)
+    }
+
+    fn reflect_signature(
+        &self,
+        type_parameters: Option<&[TypeParameter]>,
+        parameters: &[Parameter],
+        return_type: Option<&Type>,
+    ) -> String {
+        let params: Vec<String> = parameters
+            .iter()
+            .enumerate()
+            .map(|(i, param)| {
+                let name = match &param.pattern {
+                    Pattern::Identifier(ident) => self.resolve(ident.node),
+                    _ => format!("p{}", i + 1),
+                };
+                let mut entry = format!(
+                    "{{ name = {}, type = {}",
+                    self.quote_string(&name),
+                    param
+                        .type_annotation
+                        .as_ref()
+                        .map_or_else(|| self.quote_string("unknown"), |ty| self.reflect_type(ty))
+                );
+                if param.is_optional || param.default.is_some() {
+                    entry.push_str(", optional = true");
+                }
+                if param.is_rest {
+                    entry.push_str(", rest = true");
+                }
+                entry.push_str(" }");
+                entry
+            })
+            .collect();
+        format!(
+            "{{ typeParams = {}, params = {}, returnType = {} }}",
+            self.reflect_type_params(type_parameters),
+            lua_list(&params),
+            return_type.map_or_else(|| self.quote_string("void"), |ty| self.reflect_type(ty))
+        )
+    }
+
+    /// Extra `__ownFields` entry keys in full mode
+    pub(crate) fn reflect_field_extras(&mut self, prop: &PropertyDeclaration) -> String {
+        if !self.emit_full_reflection() {
+            return String::new();
+        }
+        format!(
+            ", typeName = {}, decorators = {}",
+            self.reflect_type(&prop.type_annotation),
+            self.reflect_decorators(prop.decorators)
+        )
+    }
+
+    /// Extra `__ownMethods` entry keys in full mode
+    pub(crate) fn reflect_method_extras(&mut self, method: &MethodDeclaration) -> String {
+        if !self.emit_full_reflection() {
+            return String::new();
+        }
+        format!(
+            ", signature = {}, decorators = {}",
+            self.reflect_signature(
+                method.type_parameters.as_deref(),
+                method.parameters,
+                method.return_type.as_ref()
+            ),
+            self.reflect_decorators(method.decorators)
+        )
+    }
+
+    /// Class-level `__interfaces`, `__parentType`, `__typeParams` and
+    /// `__decorators` tables
+    pub(crate) fn write_class_type_metadata(
+        &mut self,
+        class_name: &str,
+        class_decl: &ClassDeclaration,
+    ) {
+        if !self.emit_full_reflection() {
+            return;
+        }
+        let interfaces: Vec<String> = class_decl
+            .implements
+            .iter()
+            .map(|ty| self.reflect_supertype(ty))
+            .collect();
+        self.write_indent();
+        self.writeln(&format!(
+            "{}.__interfaces = {}",
+            class_name,
+            lua_list(&interfaces)
+        ));
+        if let Some(parent) = &class_decl.extends {
+            let parent = self.reflect_supertype(parent);
+            self.write_indent();
+            self.writeln(&format!("{}.__parentType = {}", class_name, parent));
+        }
+        let type_params = self.reflect_type_params(class_decl.type_parameters.as_deref());
+        self.write_indent();
+        self.writeln(&format!("{}.__typeParams = {}", class_name, type_params));
+        let decorators = self.reflect_decorators(class_decl.decorators);
+        self.write_indent();
+        self.writeln(&format!("{}.__decorators = {}", class_name, decorators));
+    }
+
+    /// Enum name and members in declaration order, written after the members
+    /// exist. They go in the `__EnumMetadata` side table rather than the enum
+    /// itself so iterating the enum yields only its members.
+    pub(crate) fn write_enum_metadata(&mut self, enum_name: &str, enum_decl: &EnumDeclaration) {
+        if !self.emit_full_reflection() {
+            return;
+        }
+        let entries: Vec<String> = enum_decl
+            .members
+            .iter()
+            .map(|member| {
+                let member_name = self.resolve(member.name.node);
+                format!(
+                    "{{ name = {}, value = {}.{} }}",
+                    self.quote_string(&member_name),
+                    enum_name,
+                    member_name
+                )
+            })
+            .collect();
+        self.write_indent();
+        self.writeln(reflection::ENUM_METADATA);
+        self.write_indent();
+        self.writeln(&format!(
+            "__EnumMetadata[{}] = {{ typeName = {}, values = {} }}",
+            enum_name,
+            self.quote_string(enum_name),
+            lua_list(&entries)
+        ));
+    }
+}
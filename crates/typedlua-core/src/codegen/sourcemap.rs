@@ -26,6 +26,16 @@ struct Mapping {
     name_index: Option<usize>,
 }
 
+/// Where a token of the generated output ended up after the output was
+/// rewritten (e.g. by the minifier). Positions are zero-based (line, column).
+#[derive(Debug, Clone)]
+pub struct TokenRelocation {
+    pub from: (usize, usize),
+    pub to: (usize, usize),
+    /// The token's original spelling, if the rewrite renamed it
+    pub original_name: Option<String>,
+}
+
 /// The JSON structure for source maps
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SourceMap {
@@ -185,6 +195,74 @@ impl SourceMapBuilder {
         }
     }
 
+    /// Move all mappings onto the positions of a rewritten output.
+    ///
+    /// Each mapping follows the closest token at or before it. Renamed tokens
+    /// get a mapping of their own carrying the original name, pointing at the
+    /// source position of the mapping that covers them.
+    pub fn apply_relocations(&mut self, relocations: &[TokenRelocation]) {
+        let mut relocations: Vec<&TokenRelocation> = relocations.iter().collect();
+        relocations.sort_by_key(|r| r.from);
+        self.mappings
+            .sort_by_key(|m| (m.generated_line, m.generated_column));
+
+        let covering = |mappings: &[Mapping], position: (usize, usize)| {
+            let index =
+                mappings.partition_point(|m| (m.generated_line, m.generated_column) <= position);
+            index.checked_sub(1).map(|i| &mappings[i])
+        };
+
+        let mut renamed = Vec::new();
+        for relocation in &relocations {
+            let Some(name) = &relocation.original_name else {
+                continue;
+            };
+            let (source_index, source_line, source_column) =
+                covering(&self.mappings, relocation.from).map_or((0, 0, 0), |m| {
+                    (m.source_index, m.source_line, m.source_column)
+                });
+            renamed.push((
+                relocation.to,
+                source_index,
+                source_line,
+                source_column,
+                name.clone(),
+            ));
+        }
+
+        for mapping in &mut self.mappings {
+            let position = (mapping.generated_line, mapping.generated_column);
+            let index = relocations.partition_point(|r| r.from <= position);
+            let to = index.checked_sub(1).map_or((0, 0), |i| relocations[i].to);
+            mapping.generated_line = to.0;
+            mapping.generated_column = to.1;
+        }
+
+        for ((line, column), source_index, source_line, source_column, name) in renamed {
+            let name_index = match self.names.iter().position(|existing| existing == &name) {
+                Some(idx) => idx,
+                None => {
+                    self.names.push(name);
+                    self.names.len() - 1
+                }
+            };
+            self.mappings.push(Mapping {
+                generated_line: line,
+                generated_column: column,
+                source_index,
+                source_line,
+                source_column,
+                name_index: Some(name_index),
+            });
+        }
+        self.mappings
+            .sort_by_key(|m| (m.generated_line, m.generated_column));
+
+        if let Some(last) = relocations.iter().map(|r| r.to).max() {
+            (self.generated_line, self.generated_column) = last;
+        }
+    }
+
     /// Encode mappings using VLQ (Variable Length Quantity) encoding
     fn encode_mappings(&self) -> String {
         let mut result = String::new();
@@ -486,4 +564,35 @@ mod tests {
         assert_eq!(SourceMapBuilder::encode_vlq(16), "gB");
         assert_eq!(SourceMapBuilder::encode_vlq(-16), "hB");
     }
+
+    #[test]
+    fn test_apply_relocations() {
+        let mut builder = SourceMapBuilder::new("input.tl".to_string());
+
+        builder.advance("local ");
+        builder.add_mapping(Span::new(6, 11, 1, 7), None);
+        builder.advance("count = 1\n");
+        builder.add_mapping(Span::new(12, 17, 2, 1), None);
+        builder.advance("count = count + 1");
+
+        // "local count = 1\ncount = count + 1" minified to "local a=1 a=a+1"
+        let relocations = vec![
+            TokenRelocation {
+                from: (0, 6),
+                to: (0, 6),
+                original_name: Some("count".to_string()),
+            },
+            TokenRelocation {
+                from: (1, 0),
+                to: (0, 10),
+                original_name: Some("count".to_string()),
+            },
+        ];
+        builder.apply_relocations(&relocations);
+
+        assert_eq!(builder.current_position(), (0, 10));
+        let source_map = builder.build();
+        assert_eq!(source_map.names, vec!["count".to_string()]);
+        assert!(!source_map.mappings.contains(';'));
+    }
 }
@@ -1,5 +1,6 @@
 use super::super::config::OptimizationLevel;
 use super::CodeGenerator;
+use crate::optimizer::CounterKind;
 use typedlua_parser::ast::pattern::{
     ArrayPattern, ArrayPatternElement, ObjectPattern, Pattern, PatternWithDefault,
 };
@@ -9,6 +10,17 @@ use typedlua_parser::prelude::Expression;
 
 impl CodeGenerator {
     pub fn generate_statement(&mut self, stmt: &Statement) {
+        let comment_span = self.comment_span(stmt);
+        if let Some(span) = comment_span {
+            self.emit_leading_comments(span);
+        }
+        self.generate_statement_kind(stmt);
+        if let Some(span) = comment_span {
+            self.emit_trailing_comment(span);
+        }
+    }
+
+    fn generate_statement_kind(&mut self, stmt: &Statement) {
         match stmt {
             Statement::Variable(decl) => self.generate_variable_declaration(decl),
             Statement::Function(decl) => self.generate_function_declaration(decl),
@@ -32,7 +44,7 @@ impl CodeGenerator {
             }
             Statement::Block(block) => self.generate_block(block),
             Statement::Interface(iface_decl) => self.generate_interface_declaration(iface_decl),
-            Statement::TypeAlias(_) => {}
+            Statement::TypeAlias(alias) => self.annotate_type_alias(alias),
             Statement::Enum(decl) => self.generate_enum_declaration(decl),
             Statement::Class(class_decl) => self.generate_class_declaration(class_decl),
             Statement::Import(import) => self.generate_import(import),
@@ -287,58 +299,74 @@ impl CodeGenerator {
     }
 
     pub fn generate_function_declaration(&mut self, decl: &FunctionDeclaration) {
+        self.annotate_function(decl);
         self.write_indent();
         self.write("local function ");
         let fn_name = self.resolve(decl.name.node);
         self.write(&fn_name);
         self.write("(");
+        let rest_param_name = self.generate_parameter_list(&decl.parameters);
+        self.writeln(")");
+        self.indent();
+        self.generate_rest_parameter_init(rest_param_name);
+        self.write_validation_checks(&fn_name);
+        let outer = self.enter_profiled_function(decl.span, &fn_name);
+
+        self.generate_block(&decl.body);
+        self.leave_profiled_function(outer);
+        self.dedent();
+        self.write_indent();
+        self.writeln("end");
+
+        // If in a namespace, attach the function to the namespace
+        if let Some(ns_path) = &self.current_namespace {
+            let ns_full_path = ns_path.join(".");
+            self.namespace_exports
+                .push((fn_name.clone(), ns_full_path.clone()));
 
-        let mut rest_param_name: Option<typedlua_parser::string_interner::StringId> = None;
+            self.write_indent();
+            self.writeln(&format!("{}.{} = {}", ns_full_path, fn_name, fn_name));
+        }
+    }
+
+    /// Write a parameter list, emitting `...` for a rest parameter.
+    /// Returns the rest parameter's name so the body can bind it.
+    pub fn generate_parameter_list(
+        &mut self,
+        parameters: &[Parameter],
+    ) -> Option<typedlua_parser::string_interner::StringId> {
+        let mut rest_param_name = None;
 
-        for (i, param) in decl.parameters.iter().enumerate() {
+        for (i, param) in parameters.iter().enumerate() {
+            if i > 0 {
+                self.write(", ");
+            }
             if param.is_rest {
-                // For rest parameters, just write ... in the parameter list
-                if i > 0 {
-                    self.write(", ");
-                }
                 self.write("...");
-                // Save the parameter name to initialize it in the function body
                 if let Pattern::Identifier(ident) = &param.pattern {
                     rest_param_name = Some(ident.node);
                 }
             } else {
-                if i > 0 {
-                    self.write(", ");
-                }
                 self.generate_pattern(&param.pattern);
             }
         }
 
-        self.writeln(")");
-        self.indent();
+        rest_param_name
+    }
 
-        // If there's a rest parameter, initialize it from ...
+    /// Bind a rest parameter from `...`, packing with an `n` count so trailing nils are kept
+    pub fn generate_rest_parameter_init(
+        &mut self,
+        rest_param_name: Option<typedlua_parser::string_interner::StringId>,
+    ) {
         if let Some(rest_name) = rest_param_name {
             self.write_indent();
             self.write("local ");
             let rest_name_str = self.resolve(rest_name);
             self.write(&rest_name_str);
-            self.writeln(" = {...}");
-        }
-
-        self.generate_block(&decl.body);
-        self.dedent();
-        self.write_indent();
-        self.writeln("end");
-
-        // If in a namespace, attach the function to the namespace
-        if let Some(ns_path) = &self.current_namespace {
-            let ns_full_path = ns_path.join(".");
-            self.namespace_exports
-                .push((fn_name.clone(), ns_full_path.clone()));
-
-            self.write_indent();
-            self.writeln(&format!("{}.{} = {}", ns_full_path, fn_name, fn_name));
+            self.write(" = ");
+            let pack = self.strategy.generate_pack_varargs();
+            self.writeln(&pack);
         }
     }
 
@@ -348,6 +376,7 @@ impl CodeGenerator {
         self.generate_expression(&if_stmt.condition);
         self.writeln(" then");
         self.indent();
+        self.write_profile_count(CounterKind::Branch, if_stmt.then_block.span);
         self.generate_block(&if_stmt.then_block);
         self.dedent();
 
@@ -357,6 +386,7 @@ impl CodeGenerator {
             self.generate_expression(&else_if.condition);
             self.writeln(" then");
             self.indent();
+            self.write_profile_count(CounterKind::Branch, else_if.block.span);
             self.generate_block(&else_if.block);
             self.dedent();
         }
@@ -365,6 +395,7 @@ impl CodeGenerator {
             self.write_indent();
             self.writeln("else");
             self.indent();
+            self.write_profile_count(CounterKind::Branch, else_block.span);
             self.generate_block(else_block);
             self.dedent();
         }
@@ -379,6 +410,7 @@ impl CodeGenerator {
         self.generate_expression(&while_stmt.condition);
         self.writeln(" do");
         self.indent();
+        self.write_profile_count(CounterKind::Loop, while_stmt.body.span);
         self.generate_block(&while_stmt.body);
         self.dedent();
         self.write_indent();
@@ -402,6 +434,7 @@ impl CodeGenerator {
                 }
                 self.writeln(" do");
                 self.indent();
+                self.write_profile_count(CounterKind::Loop, numeric.body.span);
                 self.generate_block(&numeric.body);
                 self.dedent();
                 self.write_indent();
@@ -431,6 +464,7 @@ impl CodeGenerator {
                         }
                         _ => {}
                     }
+                    self.write_profile_count(CounterKind::Loop, generic.body.span);
                     self.generate_block(&generic.body);
                     self.dedent();
                     self.write_indent();
@@ -454,6 +488,7 @@ impl CodeGenerator {
                     }
                     self.writeln(" do");
                     self.indent();
+                    self.write_profile_count(CounterKind::Loop, generic.body.span);
                     self.generate_block(&generic.body);
                     self.dedent();
                     self.write_indent();
@@ -467,6 +502,7 @@ impl CodeGenerator {
         self.write_indent();
         self.writeln("repeat");
         self.indent();
+        self.write_profile_count(CounterKind::Loop, repeat_stmt.body.span);
         self.generate_block(&repeat_stmt.body);
         self.dedent();
         self.write_indent();
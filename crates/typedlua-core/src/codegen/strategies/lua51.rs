@@ -6,6 +6,7 @@ use typedlua_parser::string_interner::StringId;
 /// - No native bitwise operators (requires helpers)
 /// - No goto/continue
 /// - No integer division
+/// - Global `unpack`, no `table.pack`
 pub struct Lua51Strategy;
 
 impl CodeGenStrategy for Lua51Strategy {
@@ -49,4 +50,16 @@ impl CodeGenStrategy for Lua51Strategy {
     fn supports_native_integer_divide(&self) -> bool {
         false
     }
+
+    fn generate_unpack(&self, table_expr: &str, start: &str, end: &str) -> String {
+        format!("unpack({}, {}, {})", table_expr, start, end)
+    }
+
+    fn generate_pack_varargs(&self) -> String {
+        "{ n = select(\"#\", ...), ... }".to_string()
+    }
+
+    fn supports_table_move(&self) -> bool {
+        false
+    }
 }
@@ -49,4 +49,16 @@ impl CodeGenStrategy for Lua52Strategy {
     fn supports_native_integer_divide(&self) -> bool {
         false
     }
+
+    fn generate_unpack(&self, table_expr: &str, start: &str, end: &str) -> String {
+        format!("table.unpack({}, {}, {})", table_expr, start, end)
+    }
+
+    fn generate_pack_varargs(&self) -> String {
+        "table.pack(...)".to_string()
+    }
+
+    fn supports_table_move(&self) -> bool {
+        false
+    }
 }
@@ -6,6 +6,7 @@ use typedlua_parser::string_interner::StringId;
 /// - Native bitwise operators (& | ~ << >>)
 /// - Supports goto/labels
 /// - Native integer division
+/// - `table.move` for bulk array copies
 pub struct Lua53Strategy;
 
 impl CodeGenStrategy for Lua53Strategy {
@@ -49,4 +50,16 @@ impl CodeGenStrategy for Lua53Strategy {
     fn supports_native_integer_divide(&self) -> bool {
         true
     }
+
+    fn generate_unpack(&self, table_expr: &str, start: &str, end: &str) -> String {
+        format!("table.unpack({}, {}, {})", table_expr, start, end)
+    }
+
+    fn generate_pack_varargs(&self) -> String {
+        "table.pack(...)".to_string()
+    }
+
+    fn supports_table_move(&self) -> bool {
+        true
+    }
 }
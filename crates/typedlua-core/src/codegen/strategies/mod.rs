@@ -31,4 +31,14 @@ pub trait CodeGenStrategy {
 
     /// Check if this strategy supports integer division
     fn supports_native_integer_divide(&self) -> bool;
+
+    /// Generate a call that unpacks `table_expr[start..=end]` into multiple values
+    fn generate_unpack(&self, table_expr: &str, start: &str, end: &str) -> String;
+
+    /// Generate an expression that packs the current varargs into a table with an `n` field,
+    /// so trailing nils survive
+    fn generate_pack_varargs(&self) -> String;
+
+    /// Check if this strategy supports `table.move` for bulk array copies
+    fn supports_table_move(&self) -> bool;
 }
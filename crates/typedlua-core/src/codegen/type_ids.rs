@@ -0,0 +1,194 @@
+//! Program-wide reflection type IDs.
+//!
+//! Every class gets a `__typeId` that its `__ancestors` table and the shared
+//! `__TypeRegistry` refer to. An ID is a stable hash of the module and class
+//! name, so a module compiled on its own (or restored from the incremental
+//! cache) gets the same IDs as in a full build. The registry, filled once for
+//! the modules being generated before parallel codegen, settles the rare hash
+//! collision between them.
+
+use super::CodeGenerator;
+use rustc_hash::FxHashMap as HashMap;
+use rustc_hash::FxHashSet as HashSet;
+use typedlua_parser::ast::statement::{ClassDeclaration, ExportKind, Statement};
+use typedlua_parser::string_interner::StringInterner;
+
+#[derive(Debug, Clone)]
+pub struct TypeIdRegistry {
+    ids: HashMap<String, u32>,
+    taken: HashSet<u32>,
+}
+
+impl TypeIdRegistry {
+    pub fn new() -> Self {
+        TypeIdRegistry {
+            ids: HashMap::default(),
+            taken: HashSet::default(),
+        }
+    }
+
+    /// Assign IDs to every class declared in `statements`. Register modules
+    /// in a deterministic order (e.g. sorted by path) so a hash collision is
+    /// resolved the same way on every build.
+    pub fn register_module(
+        &mut self,
+        module_id: &str,
+        statements: &[Statement],
+        interner: &StringInterner,
+    ) {
+        let mut classes = Vec::new();
+        Self::collect_classes(statements, &mut classes);
+        for class_decl in classes {
+            let class_name = interner.resolve(class_decl.name.node);
+            let key = Self::key(module_id, &class_name);
+            if self.ids.contains_key(&key) {
+                continue;
+            }
+            let mut type_id = Self::stable_id(module_id, &class_name);
+            while !self.taken.insert(type_id) {
+                type_id = type_id % 0x7fff_ffff + 1;
+            }
+            self.ids.insert(key, type_id);
+        }
+    }
+
+    pub fn get(&self, module_id: &str, class_name: &str) -> Option<u32> {
+        self.ids.get(&Self::key(module_id, class_name)).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    pub(crate) fn collect_classes<'s, 'arena>(
+        statements: &'s [Statement<'arena>],
+        out: &mut Vec<&'s ClassDeclaration<'arena>>,
+    ) {
+        for stmt in statements {
+            match stmt {
+                Statement::Class(class_decl) => out.push(class_decl),
+                Statement::Export(export) => {
+                    if let ExportKind::Declaration(Statement::Class(class_decl)) = &export.kind {
+                        out.push(class_decl);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn key(module_id: &str, class_name: &str) -> String {
+        format!("{}::{}", module_id, class_name)
+    }
+
+    /// The `__TypeRegistry` key of a class: `module::Class`, or the bare
+    /// class name when the module has no identity
+    pub fn qualified_name(module_id: &str, class_name: &str) -> String {
+        if module_id.is_empty() {
+            class_name.to_string()
+        } else {
+            Self::key(module_id, class_name)
+        }
+    }
+
+    /// 31-bit FNV-1a of the qualified class name. Kept positive and non-zero
+    /// so it is a plain Lua integer key.
+    pub fn stable_id(module_id: &str, class_name: &str) -> u32 {
+        let mut hash: u32 = 0x811c_9dc5;
+        for byte in Self::key(module_id, class_name).bytes() {
+            hash ^= u32::from(byte);
+            hash = hash.wrapping_mul(0x0100_0193);
+        }
+        (hash & 0x7fff_ffff).max(1)
+    }
+}
+
+impl Default for TypeIdRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CodeGenerator {
+    /// Use program-wide type IDs; `module_id` must match the ID the module
+    /// was registered under
+    pub fn with_type_ids(
+        mut self,
+        registry: std::sync::Arc<TypeIdRegistry>,
+        module_id: impl Into<String>,
+    ) -> Self {
+        self.type_ids = Some(registry);
+        self.type_id_module = Some(module_id.into());
+        self
+    }
+
+    /// Module identity used to qualify class names for type IDs
+    pub(crate) fn type_id_module(&self) -> String {
+        match (&self.type_id_module, &self.mode) {
+            (Some(module_id), _) | (None, super::CodeGenMode::Bundle { module_id }) => {
+                module_id.clone()
+            }
+            (None, super::CodeGenMode::Require) => String::new(),
+        }
+    }
+
+    pub(crate) fn allocate_type_id(&mut self, class_name: &str) -> u32 {
+        let module_id = self.type_id_module();
+        let mut type_id = self
+            .type_ids
+            .as_ref()
+            .and_then(|registry| registry.get(&module_id, class_name))
+            .unwrap_or_else(|| TypeIdRegistry::stable_id(&module_id, class_name));
+        // Probe past IDs already taken in this module (nested classes that the
+        // registry does not see, or hash collisions)
+        while !self.used_type_ids.insert(type_id) {
+            type_id = type_id % 0x7fff_ffff + 1;
+        }
+        type_id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stable_id_is_deterministic_and_module_qualified() {
+        let a = TypeIdRegistry::stable_id("src/a.tl", "Point");
+        assert_eq!(a, TypeIdRegistry::stable_id("src/a.tl", "Point"));
+        assert_ne!(a, TypeIdRegistry::stable_id("src/b.tl", "Point"));
+        assert!(a > 0 && a <= 0x7fff_ffff);
+    }
+
+    #[test]
+    fn test_registry_ids_match_stable_ids() {
+        let mut registry = TypeIdRegistry::new();
+        registry.ids.insert(
+            TypeIdRegistry::key("src/a.tl", "Point"),
+            TypeIdRegistry::stable_id("src/a.tl", "Point"),
+        );
+        registry
+            .taken
+            .insert(TypeIdRegistry::stable_id("src/a.tl", "Point"));
+        assert_eq!(
+            registry.get("src/a.tl", "Point"),
+            Some(TypeIdRegistry::stable_id("src/a.tl", "Point"))
+        );
+        assert_eq!(
+            TypeIdRegistry::qualified_name("src/a.tl", "Point"),
+            "src/a.tl::Point"
+        );
+        assert_eq!(TypeIdRegistry::qualified_name("", "Point"), "Point");
+    }
+
+    #[test]
+    fn test_registry_lookup_misses_unknown_classes() {
+        let registry = TypeIdRegistry::new();
+        assert!(registry.is_empty());
+        assert_eq!(registry.get("src/a.tl", "Point"), None);
+    }
+}
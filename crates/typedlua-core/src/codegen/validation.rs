@@ -0,0 +1,1730 @@
+//! Runtime validators generated from type annotations.
+//!
+//! See `docs/designs/Runtime-Validation.md`. Before a module body is
+//! generated, [`ValidatorBuilder`] walks the functions selected by the
+//! validation mode, emits one `__validate_<Type>` function per type their
+//! parameters use, and renders the checks each function runs on entry.
+//! Named types resolve against the module's own type aliases, interfaces,
+//! classes (by type ID) and enums without fields or methods (by member
+//! value). Entry checks accept anything else (imported types, generic
+//! parameters) unchecked; the intrinsics report it as an error instead.
+
+use super::declarations::DeclarationGenerator;
+use super::intrinsics::IntrinsicCalls;
+use super::minifier::KEYWORDS;
+use super::type_ids::TypeIdRegistry;
+use super::CodeGenerator;
+use crate::config::OptimizationLevel;
+use rustc_hash::FxHashMap as HashMap;
+use serde::{Deserialize, Serialize};
+use typedlua_parser::ast::expression::Literal;
+use typedlua_parser::ast::pattern::Pattern;
+use typedlua_parser::ast::statement::*;
+use typedlua_parser::ast::types::{
+    ObjectTypeMember, PrimitiveType, PropertySignature, Type, TypeKind, TypeParameter,
+};
+use typedlua_parser::string_interner::StringInterner;
+
+/// Which functions check their parameters on entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ValidationMode {
+    /// Exported functions and `@validate` methods
+    Auto,
+    /// Only methods decorated with `@validate`
+    Explicit,
+    /// No generated checks (default)
+    #[default]
+    Off,
+}
+
+/// How validators report failures
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ValidationErrors {
+    /// Stop at the first failure with a `path: expected T, got U` message
+    #[default]
+    FailFast,
+    /// Accumulate `{ path, expected, got }` entries for every failure
+    Collect,
+}
+
+/// Where a failed check goes
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Sink {
+    /// `return false, message` from a fail-fast validator
+    Return,
+    /// Append to the `errors` list of a collect-mode validator
+    Collect,
+    /// `error(...)` from a check inlined into a function body
+    Raise,
+}
+
+/// Piece of a Lua string concatenation
+#[derive(Clone)]
+enum Part {
+    Lit(String),
+    Expr(String),
+}
+
+/// A Lua string-valued expression built by concatenation; adjacent literal
+/// pieces are merged so constant paths render as one string
+#[derive(Clone, Default)]
+struct LuaStr(Vec<Part>);
+
+impl LuaStr {
+    fn lit(s: &str) -> Self {
+        LuaStr::default().then_lit(s)
+    }
+
+    fn expr(e: &str) -> Self {
+        LuaStr::default().then_expr(e)
+    }
+
+    fn then_lit(mut self, s: &str) -> Self {
+        match self.0.last_mut() {
+            Some(Part::Lit(last)) => last.push_str(s),
+            _ => self.0.push(Part::Lit(s.to_string())),
+        }
+        self
+    }
+
+    fn then_expr(mut self, e: &str) -> Self {
+        self.0.push(Part::Expr(e.to_string()));
+        self
+    }
+
+    fn then(self, other: &LuaStr) -> Self {
+        other.0.iter().fold(self, |acc, part| match part {
+            Part::Lit(s) => acc.then_lit(s),
+            Part::Expr(e) => acc.then_expr(e),
+        })
+    }
+
+    fn render(&self) -> String {
+        self.0
+            .iter()
+            .map(|part| match part {
+                Part::Lit(s) => lua_string(s),
+                Part::Expr(e) => e.clone(),
+            })
+            .collect::<Vec<_>>()
+            .join(" .. ")
+    }
+}
+
+struct Failure {
+    constraint: Option<&'static str>,
+    /// Shown in messages
+    expected_text: String,
+    /// Lua value stored in collect-mode entries
+    expected: String,
+    got: String,
+}
+
+impl Failure {
+    fn expected(type_text: &str, got: String) -> Self {
+        Failure {
+            constraint: None,
+            expected_text: type_text.to_string(),
+            expected: lua_string(type_text),
+            got,
+        }
+    }
+
+    fn constraint(name: &'static str, value: &str, got: String) -> Self {
+        Failure {
+            constraint: Some(name),
+            expected_text: value.to_string(),
+            expected: value.to_string(),
+            got,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Lines {
+    lines: Vec<String>,
+    level: usize,
+}
+
+impl Lines {
+    fn at(level: usize) -> Self {
+        Lines {
+            lines: Vec::new(),
+            level,
+        }
+    }
+
+    fn line(&mut self, text: &str) {
+        self.lines
+            .push(format!("{}{}", "    ".repeat(self.level), text));
+    }
+
+    fn append(&mut self, other: Lines) {
+        self.lines.extend(other.lines);
+    }
+}
+
+fn lua_string(s: &str) -> String {
+    format!(
+        "\"{}\"",
+        s.replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n")
+    )
+}
+
+fn is_lua_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !KEYWORDS.contains(&name)
+}
+
+fn field_access(value: &str, name: &str) -> String {
+    if is_lua_identifier(name) {
+        format!("{}.{}", value, name)
+    } else {
+        format!("{}[{}]", value, lua_string(name))
+    }
+}
+
+fn lua_literal(literal: &Literal) -> String {
+    match literal {
+        Literal::Nil => "nil".to_string(),
+        Literal::Boolean(b) => b.to_string(),
+        Literal::Number(n) => n.to_string(),
+        Literal::Integer(i) => i.to_string(),
+        Literal::String(s) => lua_string(s),
+    }
+}
+
+fn lua_type_name(primitive: &PrimitiveType) -> Option<&'static str> {
+    match primitive {
+        PrimitiveType::Boolean => Some("boolean"),
+        PrimitiveType::Number | PrimitiveType::Integer => Some("number"),
+        PrimitiveType::String => Some("string"),
+        PrimitiveType::Table => Some("table"),
+        PrimitiveType::Coroutine => Some("thread"),
+        _ => None,
+    }
+}
+
+fn is_nil_type(ty: &Type) -> bool {
+    matches!(
+        ty.kind,
+        TypeKind::Primitive(PrimitiveType::Nil | PrimitiveType::Void)
+            | TypeKind::Literal(Literal::Nil)
+    )
+}
+
+/// Aliases may refer to each other; stop following them this deep
+const MAX_ALIAS_DEPTH: usize = 8;
+
+/// Entry checks up to this many conditions are inlined at O2+
+const MAX_INLINE_COST: usize = 12;
+
+pub(crate) struct ValidatorBuilder<'a, 'arena: 'a> {
+    interner: &'a StringInterner,
+    errors: ValidationErrors,
+    /// Inline entry checks within [`MAX_INLINE_COST`] instead of calling
+    /// their validator (O2+ with fail-fast errors)
+    inline_checks: bool,
+    aliases: HashMap<String, &'a TypeAliasDeclaration<'arena>>,
+    interfaces: HashMap<String, &'a InterfaceDeclaration<'arena>>,
+    /// Module classes, with the type ID their instances are checked against
+    classes: HashMap<String, u32>,
+    /// Enums without fields or methods, with their member values as Lua
+    enums: HashMap<String, Vec<String>>,
+    /// Type parameters in scope; references to them are not checked
+    generic_scope: Vec<String>,
+    /// Named types being expanded inline; a reference back to one calls
+    /// its validator instead
+    inlining: Vec<String>,
+    /// Type key (alias name or rendered type) -> validator function name
+    names: HashMap<String, String>,
+    order: Vec<String>,
+    validators: Vec<String>,
+    uses_ok_err: bool,
+    uses_assert: bool,
+    uses_is_instance: bool,
+    loop_depth: usize,
+}
+
+impl<'a, 'arena: 'a> ValidatorBuilder<'a, 'arena> {
+    pub(crate) fn new(
+        interner: &'a StringInterner,
+        errors: ValidationErrors,
+        inline_checks: bool,
+    ) -> Self {
+        ValidatorBuilder {
+            interner,
+            errors,
+            inline_checks: inline_checks && errors == ValidationErrors::FailFast,
+            aliases: HashMap::default(),
+            interfaces: HashMap::default(),
+            classes: HashMap::default(),
+            enums: HashMap::default(),
+            generic_scope: Vec::new(),
+            inlining: Vec::new(),
+            names: HashMap::default(),
+            order: Vec::new(),
+            validators: Vec::new(),
+            uses_ok_err: false,
+            uses_assert: false,
+            uses_is_instance: false,
+            loop_depth: 0,
+        }
+    }
+
+    /// Make the module's type aliases, interfaces and enums resolvable by
+    /// name; classes come from [`Self::register_class`]
+    pub(crate) fn register_types(&mut self, statements: &'a [Statement<'arena>]) {
+        for stmt in statements {
+            let stmt = match stmt {
+                Statement::Export(export) => match &export.kind {
+                    ExportKind::Declaration(decl) => &**decl,
+                    _ => continue,
+                },
+                other => other,
+            };
+            match stmt {
+                Statement::TypeAlias(alias) => {
+                    self.aliases.insert(self.resolve(alias.name.node), alias);
+                }
+                Statement::Interface(iface) => {
+                    self.interfaces.insert(self.resolve(iface.name.node), iface);
+                }
+                // Members of enums with fields or methods are tables that only
+                // the enum itself tells apart, and validators are defined first
+                Statement::Enum(enum_decl) => {
+                    if enum_decl.fields.is_empty()
+                        && enum_decl.constructor.is_none()
+                        && enum_decl.methods.is_empty()
+                    {
+                        // Same values as `generate_enum_declaration` assigns
+                        let values = enum_decl
+                            .members
+                            .iter()
+                            .enumerate()
+                            .map(|(i, member)| match &member.value {
+                                Some(EnumValue::Number(n)) => n.to_string(),
+                                Some(EnumValue::String(s)) => lua_string(s),
+                                None => i.to_string(),
+                            })
+                            .collect();
+                        self.enums.insert(self.resolve(enum_decl.name.node), values);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Check instances of a module class against its type ID
+    pub(crate) fn register_class(&mut self, name: String, type_id: u32) {
+        self.classes.insert(name, type_id);
+    }
+
+    /// Checks to run at the top of a function body, one Lua line per entry
+    pub(crate) fn entry_checks(
+        &mut self,
+        type_parameters: Option<&'a [TypeParameter<'arena>]>,
+        parameters: &'a [Parameter<'arena>],
+    ) -> Vec<String> {
+        let scope_len = self.generic_scope.len();
+        self.push_type_parameters(type_parameters);
+
+        let mut out = Lines::at(0);
+        for param in parameters {
+            let (Pattern::Identifier(ident), Some(ty)) = (&param.pattern, &param.type_annotation)
+            else {
+                continue;
+            };
+            if self.is_unchecked(ty) {
+                continue;
+            }
+            let name = self.resolve(ident.node);
+            let path = LuaStr::lit(&name);
+            let optional = param.is_optional || param.default.is_some();
+            self.optional(&mut out, &name, optional, |b, out| {
+                if b.inline_checks && b.inline_cost(ty, &mut Vec::new()) <= MAX_INLINE_COST {
+                    b.check(out, ty, &name, &path, Sink::Raise);
+                } else {
+                    let validator = b.validator_for(ty);
+                    b.call(out, &validator, &name, &path, Sink::Raise);
+                }
+            });
+        }
+
+        self.generic_scope.truncate(scope_len);
+        out.lines
+    }
+
+    /// Forward declarations, helpers and validator definitions
+    pub(crate) fn prelude(&self) -> String {
+        let mut text = String::new();
+        if self.uses_assert {
+            text.push_str(typedlua_runtime::validation::ASSERT_VALID);
+        }
+        if self.uses_is_instance {
+            text.push_str(typedlua_runtime::validation::IS_INSTANCE);
+        }
+        if !self.order.is_empty() {
+            text.push_str(&format!("local {}\n", self.order.join(", ")));
+            for validator in &self.validators {
+                text.push_str(validator);
+            }
+        }
+        text
+    }
+
+    fn resolve(&self, id: typedlua_parser::string_interner::StringId) -> String {
+        self.interner.resolve(id).to_string()
+    }
+
+    fn type_text(&self, ty: &Type) -> String {
+        DeclarationGenerator::new(self.interner).type_to_string(ty)
+    }
+
+    fn push_type_parameters(&mut self, type_parameters: Option<&[TypeParameter]>) {
+        for param in type_parameters.into_iter().flatten() {
+            let name = self.resolve(param.name.node);
+            self.generic_scope.push(name);
+        }
+    }
+
+    /// Types that produce no checks at all
+    fn is_unchecked(&self, ty: &Type) -> bool {
+        match &ty.kind {
+            TypeKind::Primitive(p) => matches!(p, PrimitiveType::Unknown | PrimitiveType::Never),
+            TypeKind::Reference(type_ref) => {
+                let name = self.resolve(type_ref.name.node);
+                self.generic_scope.contains(&name)
+                    || !(matches!(name.as_str(), "Array" | "Record" | "Map" | "Refined")
+                        || self.aliases.contains_key(&name)
+                        || self.interfaces.contains_key(&name)
+                        || self.classes.contains_key(&name)
+                        || self.enums.contains_key(&name))
+            }
+            TypeKind::Parenthesized(inner) => self.is_unchecked(inner),
+            TypeKind::Conditional(_)
+            | TypeKind::Mapped(_)
+            | TypeKind::KeyOf(_)
+            | TypeKind::IndexAccess(..)
+            | TypeKind::TypeQuery(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Whether a reference to the named alias or interface is expanded in
+    /// place; generic ones and those already `expanding` are called
+    fn expands(&self, name: &str, expanding: &[String]) -> bool {
+        let generic = match (self.aliases.get(name), self.interfaces.get(name)) {
+            (Some(alias), _) => alias.type_parameters.is_some(),
+            (None, Some(iface)) => iface.type_parameters.is_some(),
+            (None, None) => return false,
+        };
+        !generic && expanding.len() < MAX_ALIAS_DEPTH && !expanding.iter().any(|n| n == name)
+    }
+
+    /// Roughly how many conditions checking `ty` inline emits; named types
+    /// that are not expanded count as one call
+    fn inline_cost(&self, ty: &Type, expanding: &mut Vec<String>) -> usize {
+        match &ty.kind {
+            TypeKind::Nullable(inner) | TypeKind::Parenthesized(inner) => {
+                self.inline_cost(inner, expanding)
+            }
+            TypeKind::Union(types) | TypeKind::Intersection(types) => {
+                types.iter().map(|t| self.inline_cost(t, expanding)).sum()
+            }
+            TypeKind::Tuple(types) => {
+                1 + types
+                    .iter()
+                    .map(|t| self.inline_cost(t, expanding))
+                    .sum::<usize>()
+            }
+            TypeKind::Array(element) => 1 + self.inline_cost(element, expanding),
+            TypeKind::Object(object) => {
+                1 + object
+                    .members
+                    .iter()
+                    .map(|member| match member {
+                        ObjectTypeMember::Property(prop) => {
+                            self.inline_cost(&prop.type_annotation, expanding)
+                        }
+                        _ => 0,
+                    })
+                    .sum::<usize>()
+            }
+            TypeKind::Primitive(PrimitiveType::Integer) => 2,
+            TypeKind::Reference(type_ref) => {
+                let name = self.resolve(type_ref.name.node);
+                let args = type_ref.type_arguments.as_deref().unwrap_or(&[]);
+                if self.generic_scope.contains(&name) {
+                    return 0;
+                }
+                match (name.as_str(), args) {
+                    ("Refined", [base, constraints]) => {
+                        let count = match &constraints.kind {
+                            TypeKind::Object(object) => object.members.len(),
+                            _ => 0,
+                        };
+                        self.inline_cost(base, expanding) + count
+                    }
+                    ("Array", [element]) => 1 + self.inline_cost(element, expanding),
+                    ("Record" | "Map", [_, value]) => 1 + self.inline_cost(value, expanding),
+                    _ if self.expands(&name, expanding) => {
+                        expanding.push(name.clone());
+                        let cost = match (self.aliases.get(&name), self.interfaces.get(&name)) {
+                            (Some(alias), _) => self.inline_cost(&alias.type_annotation, expanding),
+                            (None, Some(iface)) => {
+                                let parents: usize = iface
+                                    .extends
+                                    .iter()
+                                    .map(|parent| self.inline_cost(parent, expanding))
+                                    .sum();
+                                let members: usize = iface
+                                    .members
+                                    .iter()
+                                    .map(|member| match member {
+                                        InterfaceMember::Property(prop) => {
+                                            self.inline_cost(&prop.type_annotation, expanding)
+                                        }
+                                        _ => 0,
+                                    })
+                                    .sum();
+                                1 + parents + members
+                            }
+                            (None, None) => 0,
+                        };
+                        expanding.pop();
+                        cost
+                    }
+                    _ if self.aliases.contains_key(&name)
+                        || self.interfaces.contains_key(&name)
+                        || self.classes.contains_key(&name)
+                        || self.enums.contains_key(&name) =>
+                    {
+                        1
+                    }
+                    _ => 0,
+                }
+            }
+            _ => 1,
+        }
+    }
+
+    /// Names in `ty` that no check can be generated for: imported types,
+    /// generic parameters of the caller and enums with fields or methods.
+    /// Type parameters of the module's own generic types are not reported.
+    pub(crate) fn unchecked_names(&self, ty: &Type) -> Vec<String> {
+        let mut names = Vec::new();
+        self.collect_unchecked(ty, &mut Vec::new(), &mut names);
+        names
+    }
+
+    fn collect_unchecked(&self, ty: &Type, seen: &mut Vec<String>, out: &mut Vec<String>) {
+        match &ty.kind {
+            TypeKind::Nullable(inner) | TypeKind::Parenthesized(inner) => {
+                self.collect_unchecked(inner, seen, out)
+            }
+            TypeKind::Union(types) | TypeKind::Intersection(types) | TypeKind::Tuple(types) => {
+                for member in types.iter() {
+                    self.collect_unchecked(member, seen, out);
+                }
+            }
+            TypeKind::Array(element) => self.collect_unchecked(element, seen, out),
+            TypeKind::Object(object) => {
+                for member in object.members.iter() {
+                    if let ObjectTypeMember::Property(prop) = member {
+                        self.collect_unchecked(&prop.type_annotation, seen, out);
+                    }
+                }
+            }
+            TypeKind::Conditional(_)
+            | TypeKind::Mapped(_)
+            | TypeKind::KeyOf(_)
+            | TypeKind::IndexAccess(..)
+            | TypeKind::TypeQuery(_) => out.push(self.type_text(ty)),
+            TypeKind::Reference(type_ref) => {
+                let name = self.resolve(type_ref.name.node);
+                if seen.contains(&name)
+                    || self.classes.contains_key(&name)
+                    || self.enums.contains_key(&name)
+                {
+                    return;
+                }
+                let args = type_ref.type_arguments.as_deref().unwrap_or(&[]);
+                match (name.as_str(), args) {
+                    ("Array", [element]) => self.collect_unchecked(element, seen, out),
+                    ("Record" | "Map", [_, value]) => self.collect_unchecked(value, seen, out),
+                    ("Refined", [base, _]) => self.collect_unchecked(base, seen, out),
+                    _ => {
+                        seen.push(name.clone());
+                        if let Some(alias) = self.aliases.get(&name) {
+                            for param in alias.type_parameters.iter().flat_map(|p| p.iter()) {
+                                seen.push(self.resolve(param.name.node));
+                            }
+                            self.collect_unchecked(&alias.type_annotation, seen, out);
+                        } else if let Some(iface) = self.interfaces.get(&name) {
+                            for param in iface.type_parameters.iter().flat_map(|p| p.iter()) {
+                                seen.push(self.resolve(param.name.node));
+                            }
+                            for parent in iface.extends.iter() {
+                                self.collect_unchecked(parent, seen, out);
+                            }
+                            for member in iface.members.iter() {
+                                if let InterfaceMember::Property(prop) = member {
+                                    self.collect_unchecked(&prop.type_annotation, seen, out);
+                                }
+                            }
+                        } else {
+                            out.push(name);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn is_class_or_enum(&self, id: typedlua_parser::string_interner::StringId) -> bool {
+        let name = self.resolve(id);
+        self.classes.contains_key(&name) || self.enums.contains_key(&name)
+    }
+
+    /// Boolean Lua expression for "`v` is an instance of the module class or
+    /// a member of the enum `name`"
+    fn named_predicate(&mut self, name: &str, v: &str) -> Option<String> {
+        if let Some(type_id) = self.classes.get(name) {
+            self.uses_is_instance = true;
+            return Some(format!("__isInstance({}, {})", v, type_id));
+        }
+        let values = self.enums.get(name)?;
+        if values.is_empty() {
+            return Some("false".to_string());
+        }
+        Some(
+            values
+                .iter()
+                .map(|value| format!("{} == {}", v, value))
+                .collect::<Vec<_>>()
+                .join(" or "),
+        )
+    }
+
+    /// Validator function for a type, generating it on first use
+    pub(crate) fn validator_for(&mut self, ty: &'a Type<'arena>) -> String {
+        if let TypeKind::Reference(type_ref) = &ty.kind {
+            let name = self.resolve(type_ref.name.node);
+            if self.aliases.contains_key(&name) || self.interfaces.contains_key(&name) {
+                return self.named_validator(&name);
+            }
+        }
+
+        let key = self.type_text(ty);
+        if let Some(existing) = self.names.get(&key) {
+            return existing.clone();
+        }
+        let fn_name = if is_lua_identifier(&key) {
+            format!("__validate_{}", key)
+        } else {
+            format!("__validate_{}", self.order.len() + 1)
+        };
+        self.names.insert(key, fn_name.clone());
+        self.order.push(fn_name.clone());
+        self.define(&fn_name, |b, out, sink| {
+            b.check(out, ty, "val", &LuaStr::expr("path"), sink)
+        });
+        fn_name
+    }
+
+    fn named_validator(&mut self, name: &str) -> String {
+        if let Some(existing) = self.names.get(name) {
+            return existing.clone();
+        }
+        let fn_name = format!("__validate_{}", name);
+        // Registered before the body is built so recursive types call themselves
+        self.names.insert(name.to_string(), fn_name.clone());
+        self.order.push(fn_name.clone());
+
+        let scope_len = self.generic_scope.len();
+        if let Some(alias) = self.aliases.get(name).copied() {
+            self.push_type_parameters(alias.type_parameters.as_deref());
+        } else if let Some(iface) = self.interfaces.get(name).copied() {
+            self.push_type_parameters(iface.type_parameters.as_deref());
+        }
+        self.define(&fn_name, |b, out, sink| {
+            b.check_named(out, name, "val", &LuaStr::expr("path"), sink)
+        });
+        self.generic_scope.truncate(scope_len);
+        fn_name
+    }
+
+    /// Checks for the body of a module alias or interface
+    fn check_named(&mut self, out: &mut Lines, name: &str, v: &str, path: &LuaStr, sink: Sink) {
+        if let Some(alias) = self.aliases.get(name).copied() {
+            self.check(out, &alias.type_annotation, v, path, sink);
+        } else if let Some(iface) = self.interfaces.get(name).copied() {
+            self.with_type(out, "table", name, v, path, sink, |b, out| {
+                for parent in iface.extends.iter() {
+                    b.check(out, parent, v, path, sink);
+                }
+                for member in iface.members.iter() {
+                    if let InterfaceMember::Property(prop) = member {
+                        b.check_property(out, prop, v, path, sink);
+                    }
+                }
+            });
+        }
+    }
+
+    fn define(&mut self, fn_name: &str, body: impl FnOnce(&mut Self, &mut Lines, Sink)) {
+        let saved_ok_err = std::mem::replace(&mut self.uses_ok_err, false);
+        let saved_depth = std::mem::replace(&mut self.loop_depth, 0);
+        let sink = match self.errors {
+            ValidationErrors::FailFast => Sink::Return,
+            ValidationErrors::Collect => Sink::Collect,
+        };
+        let mut out = Lines::at(1);
+        body(self, &mut out, sink);
+
+        let mut text = String::new();
+        match self.errors {
+            ValidationErrors::FailFast => {
+                text.push_str(&format!("function {}(val, path)\n", fn_name));
+            }
+            ValidationErrors::Collect => {
+                text.push_str(&format!("function {}(val, path, errors)\n", fn_name));
+                text.push_str("    errors = errors or {}\n");
+            }
+        }
+        if self.uses_ok_err {
+            text.push_str("    local ok, err\n");
+        }
+        for line in &out.lines {
+            text.push_str(line);
+            text.push('\n');
+        }
+        text.push_str(match self.errors {
+            ValidationErrors::FailFast => "    return true\n",
+            ValidationErrors::Collect => "    return #errors == 0, errors\n",
+        });
+        text.push_str("end\n");
+        self.validators.push(text);
+
+        self.uses_ok_err = saved_ok_err;
+        self.loop_depth = saved_depth;
+    }
+
+    fn check(&mut self, out: &mut Lines, ty: &'a Type<'arena>, v: &str, path: &LuaStr, sink: Sink) {
+        match &ty.kind {
+            TypeKind::Primitive(p) => match p {
+                PrimitiveType::Nil | PrimitiveType::Void => self.fail_if(
+                    out,
+                    &format!("{} ~= nil", v),
+                    Failure::expected("nil", format!("type({})", v)),
+                    path,
+                    sink,
+                ),
+                PrimitiveType::Integer => {
+                    self.with_type(out, "number", "integer", v, path, sink, |_, _| {});
+                    self.constraint(
+                        out,
+                        v,
+                        "number",
+                        false,
+                        &format!("{} ~= math.floor({})", v, v),
+                        Failure::constraint("integer", "true", v.to_string()),
+                        path,
+                        sink,
+                    );
+                }
+                _ => {
+                    if let Some(lua_type) = lua_type_name(p) {
+                        let text = self.type_text(ty);
+                        self.with_type(out, lua_type, &text, v, path, sink, |_, _| {});
+                    }
+                }
+            },
+            TypeKind::TemplateLiteral(_) => {
+                self.with_type(out, "string", "string", v, path, sink, |_, _| {})
+            }
+            TypeKind::Function(_) => {
+                let text = self.type_text(ty);
+                self.with_type(out, "function", &text, v, path, sink, |_, _| {});
+            }
+            TypeKind::Literal(literal) => {
+                let text = self.type_text(ty);
+                self.fail_if(
+                    out,
+                    &format!("{} ~= {}", v, lua_literal(literal)),
+                    Failure::expected(&text, format!("tostring({})", v)),
+                    path,
+                    sink,
+                );
+            }
+            TypeKind::Parenthesized(inner) => self.check(out, inner, v, path, sink),
+            TypeKind::Nullable(inner) => {
+                self.optional(out, v, true, |b, out| b.check(out, inner, v, path, sink))
+            }
+            TypeKind::Union(types) => self.check_union(out, ty, types, v, path, sink),
+            TypeKind::Intersection(types) => {
+                for member in types.iter() {
+                    self.check(out, member, v, path, sink);
+                }
+            }
+            TypeKind::Array(element) => self.check_array(out, ty, element, v, path, sink),
+            TypeKind::Tuple(types) => {
+                let text = self.type_text(ty);
+                self.with_type(out, "table", &text, v, path, sink, |b, out| {
+                    for (i, element) in types.iter().enumerate() {
+                        let index = format!("[{}]", i + 1);
+                        let element_path = path.clone().then_lit(&index);
+                        b.check(
+                            out,
+                            element,
+                            &format!("{}{}", v, index),
+                            &element_path,
+                            sink,
+                        );
+                    }
+                });
+            }
+            TypeKind::Object(object) => {
+                let text = self.type_text(ty);
+                self.with_type(out, "table", &text, v, path, sink, |b, out| {
+                    for member in object.members.iter() {
+                        if let ObjectTypeMember::Property(prop) = member {
+                            b.check_property(out, prop, v, path, sink);
+                        }
+                    }
+                });
+            }
+            TypeKind::Reference(type_ref) => {
+                let name = self.resolve(type_ref.name.node);
+                if self.generic_scope.contains(&name) {
+                    return;
+                }
+                let args = type_ref.type_arguments.as_deref().unwrap_or(&[]);
+                match (name.as_str(), args) {
+                    ("Array", [element]) => self.check_array(out, ty, element, v, path, sink),
+                    ("Record" | "Map", [_, value]) => {
+                        self.check_record(out, ty, value, v, path, sink)
+                    }
+                    ("Refined", [base, constraints]) => {
+                        self.check_refined(out, base, constraints, v, path, sink)
+                    }
+                    _ if self.is_class_or_enum(type_ref.name.node) => {
+                        let got = if self.classes.contains_key(&name) {
+                            format!("type({})", v)
+                        } else {
+                            format!("tostring({})", v)
+                        };
+                        if let Some(predicate) = self.named_predicate(&name, v) {
+                            self.fail_if(
+                                out,
+                                &format!("not ({})", predicate),
+                                Failure::expected(&name, got),
+                                path,
+                                sink,
+                            );
+                        }
+                    }
+                    // Inline entry checks expand named types in place
+                    _ if sink == Sink::Raise && self.expands(&name, &self.inlining) => {
+                        self.inlining.push(name.clone());
+                        self.check_named(out, &name, v, path, sink);
+                        self.inlining.pop();
+                    }
+                    _ if self.aliases.contains_key(&name)
+                        || self.interfaces.contains_key(&name) =>
+                    {
+                        let validator = self.named_validator(&name);
+                        self.call(out, &validator, v, path, sink);
+                    }
+                    // Imported types are not known here
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn check_property(
+        &mut self,
+        out: &mut Lines,
+        prop: &'a PropertySignature<'arena>,
+        v: &str,
+        path: &LuaStr,
+        sink: Sink,
+    ) {
+        let name = self.resolve(prop.name.node);
+        let value = field_access(v, &name);
+        let field_path = path.clone().then_lit(&format!(".{}", name));
+        self.optional(out, &value, prop.is_optional, |b, out| {
+            b.check(out, &prop.type_annotation, &value, &field_path, sink)
+        });
+    }
+
+    fn check_union(
+        &mut self,
+        out: &mut Lines,
+        ty: &'a Type<'arena>,
+        types: &'a [Type<'arena>],
+        v: &str,
+        path: &LuaStr,
+        sink: Sink,
+    ) {
+        let members: Vec<&'a Type<'arena>> = types.iter().filter(|t| !is_nil_type(t)).collect();
+        let nilable = members.len() < types.len();
+        self.optional(out, v, nilable, |b, out| {
+            if let [single] = members.as_slice() {
+                b.check(out, single, v, path, sink);
+                return;
+            }
+            let mut predicates = Vec::new();
+            for member in &members {
+                match b.predicate(member, v, path) {
+                    Some(predicate) => predicates.push(predicate),
+                    // One member accepts anything, so the union does too
+                    None => return,
+                }
+            }
+            let text = b.type_text(ty);
+            b.fail_if(
+                out,
+                &format!("not ({})", predicates.join(" or ")),
+                Failure::expected(&text, format!("type({})", v)),
+                path,
+                sink,
+            );
+        });
+    }
+
+    /// Boolean Lua expression for "`v` is a `ty`"; `None` when every value is
+    fn predicate(&mut self, ty: &'a Type<'arena>, v: &str, path: &LuaStr) -> Option<String> {
+        match &ty.kind {
+            TypeKind::Primitive(PrimitiveType::Integer) => Some(format!(
+                "(type({}) == \"number\" and {} == math.floor({}))",
+                v, v, v
+            )),
+            TypeKind::Primitive(PrimitiveType::Never) => Some("false".to_string()),
+            TypeKind::Primitive(p) => {
+                lua_type_name(p).map(|t| format!("type({}) == {}", v, lua_string(t)))
+            }
+            TypeKind::TemplateLiteral(_) => Some(format!("type({}) == \"string\"", v)),
+            TypeKind::Function(_) => Some(format!("type({}) == \"function\"", v)),
+            TypeKind::Literal(literal) => Some(format!("{} == {}", v, lua_literal(literal))),
+            TypeKind::Parenthesized(inner) => self.predicate(inner, v, path),
+            TypeKind::Reference(type_ref) if self.is_class_or_enum(type_ref.name.node) => {
+                let name = self.resolve(type_ref.name.node);
+                self.named_predicate(&name, v)
+                    .map(|predicate| format!("({})", predicate))
+            }
+            _ if self.is_unchecked(ty) => None,
+            _ => {
+                let validator = self.validator_for(ty);
+                let extra = match self.errors {
+                    ValidationErrors::FailFast => "",
+                    ValidationErrors::Collect => ", {}",
+                };
+                Some(format!(
+                    "({}({}, {}{}))",
+                    validator,
+                    v,
+                    path.render(),
+                    extra
+                ))
+            }
+        }
+    }
+
+    fn check_array(
+        &mut self,
+        out: &mut Lines,
+        ty: &'a Type<'arena>,
+        element: &'a Type<'arena>,
+        v: &str,
+        path: &LuaStr,
+        sink: Sink,
+    ) {
+        let text = self.type_text(ty);
+        self.with_type(out, "table", &text, v, path, sink, |b, out| {
+            b.each(out, "ipairs", element, v, path, sink, |i| {
+                LuaStr::lit("[").then_expr(i).then_lit("]")
+            });
+        });
+    }
+
+    fn check_record(
+        &mut self,
+        out: &mut Lines,
+        ty: &'a Type<'arena>,
+        value: &'a Type<'arena>,
+        v: &str,
+        path: &LuaStr,
+        sink: Sink,
+    ) {
+        let text = self.type_text(ty);
+        self.with_type(out, "table", &text, v, path, sink, |b, out| {
+            b.each(out, "pairs", value, v, path, sink, |k| {
+                LuaStr::lit("[")
+                    .then_expr(&format!("tostring({})", k))
+                    .then_lit("]")
+            });
+        });
+    }
+
+    /// Loop over a table and check every value; nothing is emitted when the
+    /// value type has no checks
+    #[allow(clippy::too_many_arguments)]
+    fn each(
+        &mut self,
+        out: &mut Lines,
+        iterator: &str,
+        element: &'a Type<'arena>,
+        v: &str,
+        path: &LuaStr,
+        sink: Sink,
+        suffix: impl Fn(&str) -> LuaStr,
+    ) {
+        self.loop_depth += 1;
+        let key = format!("k{}", self.loop_depth);
+        let item = format!("item{}", self.loop_depth);
+        let item_path = path.clone().then(&suffix(&key));
+        let mut body = Lines::at(out.level + 1);
+        self.check(&mut body, element, &item, &item_path, sink);
+        self.loop_depth -= 1;
+
+        if !body.lines.is_empty() {
+            out.line(&format!("for {}, {} in {}({}) do", key, item, iterator, v));
+            out.append(body);
+            out.line("end");
+        }
+    }
+
+    fn check_refined(
+        &mut self,
+        out: &mut Lines,
+        base: &'a Type<'arena>,
+        constraints: &'a Type<'arena>,
+        v: &str,
+        path: &LuaStr,
+        sink: Sink,
+    ) {
+        self.check(out, base, v, path, sink);
+        let TypeKind::Object(object) = &constraints.kind else {
+            return;
+        };
+
+        // With a plain base type a fail-fast check has already bailed out on
+        // the wrong type, so constraints need no guard of their own
+        let subject = match &base.kind {
+            TypeKind::Primitive(PrimitiveType::String) | TypeKind::TemplateLiteral(_) => {
+                Some("string")
+            }
+            TypeKind::Primitive(PrimitiveType::Number | PrimitiveType::Integer) => Some("number"),
+            TypeKind::Array(_) => Some("table"),
+            TypeKind::Reference(type_ref) if self.resolve(type_ref.name.node) == "Array" => {
+                Some("table")
+            }
+            _ => None,
+        };
+        let guarded = subject.is_none();
+        let length_subject = subject.unwrap_or("string");
+
+        for member in object.members.iter() {
+            let ObjectTypeMember::Property(prop) = member else {
+                continue;
+            };
+            let TypeKind::Literal(literal) = &prop.type_annotation.kind else {
+                continue;
+            };
+            let value = lua_literal(literal);
+            let key = self.resolve(prop.name.node);
+            let length = format!("#{}", v);
+            let (subject, condition, failure) = match (key.as_str(), literal) {
+                ("minLength", _) => (
+                    length_subject,
+                    format!("{} < {}", length, value),
+                    Failure::constraint("minLength", &value, length.clone()),
+                ),
+                ("maxLength", _) => (
+                    length_subject,
+                    format!("{} > {}", length, value),
+                    Failure::constraint("maxLength", &value, length.clone()),
+                ),
+                ("nonEmpty", Literal::Boolean(true)) => (
+                    length_subject,
+                    format!("{} < 1", length),
+                    Failure::constraint("minLength", "1", length.clone()),
+                ),
+                ("min", _) => (
+                    "number",
+                    format!("{} < {}", v, value),
+                    Failure::constraint("min", &value, v.to_string()),
+                ),
+                ("max", _) => (
+                    "number",
+                    format!("{} > {}", v, value),
+                    Failure::constraint("max", &value, v.to_string()),
+                ),
+                ("integer", Literal::Boolean(true)) => (
+                    "number",
+                    format!("{} ~= math.floor({})", v, v),
+                    Failure::constraint("integer", "true", v.to_string()),
+                ),
+                ("pattern", Literal::String(_)) => (
+                    "string",
+                    format!("not string.find({}, {})", v, value),
+                    Failure::constraint("pattern", &value, v.to_string()),
+                ),
+                // `custom` predicates and unknown keys are not enforced
+                _ => continue,
+            };
+            self.constraint(out, v, subject, guarded, &condition, failure, path, sink);
+        }
+    }
+
+    /// A refinement condition; guarded by a type test when the value may not
+    /// have the expected type yet (collect mode, or an unresolved base)
+    #[allow(clippy::too_many_arguments)]
+    fn constraint(
+        &mut self,
+        out: &mut Lines,
+        v: &str,
+        subject: &str,
+        guarded: bool,
+        condition: &str,
+        failure: Failure,
+        path: &LuaStr,
+        sink: Sink,
+    ) {
+        let condition = if guarded || sink == Sink::Collect {
+            format!("type({}) == {} and {}", v, lua_string(subject), condition)
+        } else {
+            condition.to_string()
+        };
+        self.fail_if(out, &condition, failure, path, sink);
+    }
+
+    /// Test `type(v)`, then run `body` only when it matched
+    #[allow(clippy::too_many_arguments)]
+    fn with_type(
+        &mut self,
+        out: &mut Lines,
+        lua_type: &str,
+        expected_text: &str,
+        v: &str,
+        path: &LuaStr,
+        sink: Sink,
+        body: impl FnOnce(&mut Self, &mut Lines),
+    ) {
+        out.line(&format!("if type({}) ~= {} then", v, lua_string(lua_type)));
+        out.level += 1;
+        self.fail(
+            out,
+            Failure::expected(expected_text, format!("type({})", v)),
+            path,
+            sink,
+        );
+        out.level -= 1;
+
+        if sink == Sink::Collect {
+            // Collecting keeps going, so the rest must not see the wrong type
+            let mut inner = Lines::at(out.level + 1);
+            body(self, &mut inner);
+            if !inner.lines.is_empty() {
+                out.line("else");
+                out.append(inner);
+            }
+            out.line("end");
+        } else {
+            out.line("end");
+            body(self, out);
+        }
+    }
+
+    /// Run `body` only when `v` is not nil
+    fn optional(
+        &mut self,
+        out: &mut Lines,
+        v: &str,
+        optional: bool,
+        body: impl FnOnce(&mut Self, &mut Lines),
+    ) {
+        if !optional {
+            body(self, out);
+            return;
+        }
+        let mut inner = Lines::at(out.level + 1);
+        body(self, &mut inner);
+        if !inner.lines.is_empty() {
+            out.line(&format!("if {} ~= nil then", v));
+            out.append(inner);
+            out.line("end");
+        }
+    }
+
+    fn fail_if(
+        &mut self,
+        out: &mut Lines,
+        condition: &str,
+        failure: Failure,
+        path: &LuaStr,
+        sink: Sink,
+    ) {
+        out.line(&format!("if {} then", condition));
+        out.level += 1;
+        self.fail(out, failure, path, sink);
+        out.level -= 1;
+        out.line("end");
+    }
+
+    fn fail(&mut self, out: &mut Lines, failure: Failure, path: &LuaStr, sink: Sink) {
+        let detail = match failure.constraint {
+            Some(constraint) => format!(": {} {}, got ", constraint, failure.expected_text),
+            None => format!(": expected {}, got ", failure.expected_text),
+        };
+        let message = path.clone().then_lit(&detail).then_expr(&failure.got);
+        match sink {
+            Sink::Return => out.line(&format!("return false, {}", message.render())),
+            Sink::Raise => out.line(&format!(
+                "error({}, 2)",
+                LuaStr::lit("Validation failed: ").then(&message).render()
+            )),
+            Sink::Collect => {
+                let constraint = failure
+                    .constraint
+                    .map(|c| format!("constraint = {}, ", lua_string(c)))
+                    .unwrap_or_default();
+                out.line(&format!(
+                    "errors[#errors + 1] = {{ path = {}, {}expected = {}, got = {} }}",
+                    path.render(),
+                    constraint,
+                    failure.expected,
+                    failure.got
+                ));
+            }
+        }
+    }
+
+    fn call(&mut self, out: &mut Lines, validator: &str, v: &str, path: &LuaStr, sink: Sink) {
+        match sink {
+            Sink::Return => {
+                self.uses_ok_err = true;
+                out.line(&format!(
+                    "ok, err = {}({}, {})",
+                    validator,
+                    v,
+                    path.render()
+                ));
+                out.line("if not ok then");
+                out.line("    return false, err");
+                out.line("end");
+            }
+            Sink::Collect => {
+                out.line(&format!("{}({}, {}, errors)", validator, v, path.render()));
+            }
+            Sink::Raise => {
+                self.uses_assert = true;
+                out.line(&format!(
+                    "__assertValid({}({}, {}))",
+                    validator,
+                    v,
+                    path.render()
+                ));
+            }
+        }
+    }
+}
+
+impl CodeGenerator {
+    /// Validate parameters of the functions `mode` selects on entry
+    pub fn with_validation(mut self, mode: ValidationMode, errors: ValidationErrors) -> Self {
+        self.validation_mode = mode;
+        self.validation_errors = errors;
+        self
+    }
+
+    /// `@validate` is handled by the compiler and has no runtime function
+    pub(crate) fn is_validate_decorator(&self, decorator: &Decorator) -> bool {
+        matches!(&decorator.expression, DecoratorExpression::Identifier(name)
+            if self.resolve(name.node) == "validate")
+    }
+
+    /// Build the validators this module needs and write them ahead of the
+    /// module body; the per-function checks are written by
+    /// [`Self::write_validation_checks`]
+    pub(crate) fn prepare_validation(&mut self, statements: &[Statement]) {
+        let interner = self.interner.clone();
+        // `is`/`parse`/`safeParse` validate whatever the mode is
+        let intrinsics = IntrinsicCalls::collect(&interner, statements);
+        self.shadowed_intrinsics = intrinsics.shadowed().clone();
+        if self.validation_mode == ValidationMode::Off && intrinsics.is_empty() {
+            return;
+        }
+        let inline_checks = self.optimization_level.effective() >= OptimizationLevel::O2;
+        let mut builder = ValidatorBuilder::new(&interner, self.validation_errors, inline_checks);
+        builder.register_types(statements);
+        // Validators are written before the classes they check, so instances
+        // are recognized by a type ID allocated here and used by the class
+        self.reserved_type_ids.clear();
+        let mut classes = Vec::new();
+        TypeIdRegistry::collect_classes(statements, &mut classes);
+        for class_decl in classes {
+            let name = self.resolve(class_decl.name.node).to_string();
+            let type_id = self.allocate_type_id(&name);
+            self.reserved_type_ids
+                .insert(class_decl.span.start, type_id);
+            builder.register_class(name, type_id);
+        }
+
+        let mut checks = HashMap::default();
+        if self.validation_mode != ValidationMode::Off {
+            for (key, type_parameters, parameters) in self.validation_targets(statements) {
+                let lines = builder.entry_checks(type_parameters, parameters);
+                if !lines.is_empty() {
+                    checks.insert(key, lines);
+                }
+            }
+        }
+        self.intrinsic_validators = intrinsics.validators(&mut builder, &mut self.errors);
+        let prelude = format!("{}{}", intrinsics.runtime(), builder.prelude());
+
+        self.validation_checks = checks;
+        if !prelude.is_empty() {
+            self.writeln(&prelude);
+        }
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn validation_targets<'s, 'arena>(
+        &self,
+        statements: &'s [Statement<'arena>],
+    ) -> Vec<(
+        String,
+        Option<&'s [TypeParameter<'arena>]>,
+        &'s [Parameter<'arena>],
+    )> {
+        let mut targets = Vec::new();
+        let auto = self.validation_mode == ValidationMode::Auto;
+        let find_function = |name: &str| {
+            statements.iter().find_map(|stmt| match stmt {
+                Statement::Function(func) if self.resolve(func.name.node) == name => Some(func),
+                _ => None,
+            })
+        };
+
+        for stmt in statements {
+            let (stmt, exported) = match stmt {
+                Statement::Export(export) => match &export.kind {
+                    ExportKind::Declaration(decl) => (&**decl, true),
+                    ExportKind::Named {
+                        specifiers,
+                        source: None,
+                    } if auto => {
+                        for spec in specifiers.iter() {
+                            let name = self.resolve(spec.local.node);
+                            if let Some(func) = find_function(&name) {
+                                targets.push((
+                                    name,
+                                    func.type_parameters.as_deref(),
+                                    &func.parameters[..],
+                                ));
+                            }
+                        }
+                        continue;
+                    }
+                    _ => continue,
+                },
+                other => (other, false),
+            };
+            match stmt {
+                Statement::Function(func) if auto && exported => targets.push((
+                    self.resolve(func.name.node),
+                    func.type_parameters.as_deref(),
+                    &func.parameters[..],
+                )),
+                Statement::Class(class_decl) => {
+                    let class_name = self.resolve(class_decl.name.node);
+                    for member in class_decl.members.iter() {
+                        if let ClassMember::Method(method) = member {
+                            if method
+                                .decorators
+                                .iter()
+                                .any(|d| self.is_validate_decorator(d))
+                            {
+                                targets.push((
+                                    format!("{}.{}", class_name, self.resolve(method.name.node)),
+                                    method.type_parameters.as_deref(),
+                                    &method.parameters[..],
+                                ));
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        targets
+    }
+
+    /// Entry checks for a function (`name`) or method (`Class.method`)
+    pub(crate) fn write_validation_checks(&mut self, key: &str) {
+        if let Some(lines) = self.validation_checks.get(key).cloned() {
+            for line in lines {
+                self.write_indent();
+                self.writeln(&line);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MutableProgram;
+    use bumpalo::Bump;
+    use std::sync::Arc;
+    use typedlua_parser::diagnostics::CollectingDiagnosticHandler;
+    use typedlua_parser::lexer::Lexer;
+    use typedlua_parser::parser::Parser;
+
+    fn generate(
+        source: &str,
+        mode: ValidationMode,
+        errors: ValidationErrors,
+        level: OptimizationLevel,
+    ) -> String {
+        let handler = Arc::new(CollectingDiagnosticHandler::new());
+        let (interner, common) = StringInterner::new_with_common_identifiers();
+        let interner = Arc::new(interner);
+        let arena = Bump::new();
+        let mut lexer = Lexer::new(source, handler.clone(), &interner);
+        let tokens = lexer.tokenize().expect("Lexing failed");
+        let mut parser = Parser::new(tokens, handler, &interner, &common, &arena);
+        let program = parser.parse().expect("Parsing failed");
+        let mutable = MutableProgram::from_program(&program);
+
+        let mut generator = CodeGenerator::new(interner.clone())
+            .with_optimization_level(level)
+            .with_validation(mode, errors);
+        generator.generate(&mutable)
+    }
+
+    #[test]
+    fn test_off_by_default() {
+        let source = "export function greet(name: string): string { return name }";
+        let output = generate(
+            source,
+            ValidationMode::Off,
+            ValidationErrors::FailFast,
+            OptimizationLevel::O0,
+        );
+        assert!(!output.contains("__validate"), "{}", output);
+    }
+
+    #[test]
+    fn test_exported_function_calls_validators_once_per_type() {
+        let source = r#"
+            type Username = Refined<string, { minLength: 3, maxLength: 20 }>
+            export function register(name: Username, alias: Username, age?: number): void {}
+            function internal(name: Username): void {}
+        "#;
+        let output = generate(
+            source,
+            ValidationMode::Auto,
+            ValidationErrors::FailFast,
+            OptimizationLevel::O0,
+        );
+        assert_eq!(
+            output
+                .matches("function __validate_Username(val, path)")
+                .count(),
+            1,
+            "one validator per type: {}",
+            output
+        );
+        assert!(
+            output.contains(
+                "if #val < 3 then\n        return false, path .. \": minLength 3, got \" .. #val"
+            ),
+            "refined constraint: {}",
+            output
+        );
+        assert!(
+            output.contains("__assertValid(__validate_Username(name, \"name\"))")
+                && output.contains("__assertValid(__validate_Username(alias, \"alias\"))"),
+            "entry checks: {}",
+            output
+        );
+        assert!(
+            output.contains(
+                "if age ~= nil then\n        __assertValid(__validate_number(age, \"age\"))"
+            ),
+            "optional parameter: {}",
+            output
+        );
+        assert_eq!(
+            output.matches("__assertValid(__validate_").count(),
+            3,
+            "internal functions are not checked: {}",
+            output
+        );
+    }
+
+    #[test]
+    fn test_simple_types_inline_at_o2() {
+        let source = r#"
+            type Port = Refined<number, { min: 1, max: 65535, integer: true }>
+            export function listen(port: Port): void {}
+        "#;
+        let output = generate(
+            source,
+            ValidationMode::Auto,
+            ValidationErrors::FailFast,
+            OptimizationLevel::O2,
+        );
+        assert!(!output.contains("__validate_Port"), "inlined: {}", output);
+        assert!(
+            output.contains(
+                "if type(port) ~= \"number\" then\n        error(\"Validation failed: port: expected number, got \" .. type(port), 2)"
+            ),
+            "inline type check: {}",
+            output
+        );
+        assert!(
+            output.contains("if port ~= math.floor(port) then"),
+            "inline integer check: {}",
+            output
+        );
+    }
+
+    #[test]
+    fn test_nested_tables_arrays_and_unions() {
+        let source = r#"
+            interface Address { zip: string }
+            interface User {
+                name: string
+                tags: string[]
+                address?: Address
+                id: string | number
+            }
+            export function save(user: User): void {}
+        "#;
+        let output = generate(
+            source,
+            ValidationMode::Auto,
+            ValidationErrors::FailFast,
+            OptimizationLevel::O0,
+        );
+        assert!(
+            output.contains("local __validate_User, __validate_Address"),
+            "forward declarations: {}",
+            output
+        );
+        assert!(
+            output.contains("for k1, item1 in ipairs(val.tags) do"),
+            "array loop: {}",
+            output
+        );
+        assert!(
+            output.contains("path .. \".tags[\" .. k1 .. \"]: expected string, got \""),
+            "element path: {}",
+            output
+        );
+        assert!(
+            output.contains("if val.address ~= nil then\n        ok, err = __validate_Address(val.address, path .. \".address\")"),
+            "optional nested interface: {}",
+            output
+        );
+        assert!(
+            output
+                .contains("if not (type(val.id) == \"string\" or type(val.id) == \"number\") then"),
+            "union: {}",
+            output
+        );
+        assert!(
+            output.contains("__assertValid(__validate_User(user, \"user\"))"),
+            "validator call at O0: {}",
+            output
+        );
+    }
+
+    #[test]
+    fn test_objects_and_arrays_inline_at_o2() {
+        let source = r#"
+            interface Address { zip: string }
+            interface User {
+                name: string
+                tags: string[]
+                address?: Address
+            }
+            export function save(user: User): void {}
+        "#;
+        let output = generate(
+            source,
+            ValidationMode::Auto,
+            ValidationErrors::FailFast,
+            OptimizationLevel::O2,
+        );
+        assert!(!output.contains("__validate_User"), "inlined: {}", output);
+        assert!(!output.contains("__validate_Address"), "{}", output);
+        assert!(
+            output.contains(
+                "error(\"Validation failed: user: expected User, got \" .. type(user), 2)"
+            ),
+            "inline interface check: {}",
+            output
+        );
+        assert!(
+            output.contains("for k1, item1 in ipairs(user.tags) do"),
+            "inline array loop: {}",
+            output
+        );
+        assert!(
+            output.contains(
+                "\"Validation failed: user.tags[\" .. k1 .. \"]: expected string, got \""
+            ),
+            "element path: {}",
+            output
+        );
+        assert!(
+            output.contains("if user.address ~= nil then")
+                && output.contains("if type(user.address.zip) ~= \"string\" then"),
+            "nested interface expanded: {}",
+            output
+        );
+    }
+
+    #[test]
+    fn test_recursive_and_large_types_call_validators_at_o2() {
+        let source = r#"
+            interface Node { value: number, next?: Node }
+            type Wide = {
+                a: number, b: number, c: number, d: number, e: number, f: number,
+                g: number, h: number, i: number, j: number, k: number, l: number
+            }
+            export function walk(node: Node, wide: Wide): void {}
+        "#;
+        let output = generate(
+            source,
+            ValidationMode::Auto,
+            ValidationErrors::FailFast,
+            OptimizationLevel::O2,
+        );
+        assert!(
+            output.contains("if type(node.value) ~= \"number\" then")
+                && output.contains("__assertValid(__validate_Node(node.next, \"node.next\"))"),
+            "recursive reference calls its validator: {}",
+            output
+        );
+        assert!(
+            output.contains("__assertValid(__validate_Wide(wide, \"wide\"))"),
+            "over the inline budget: {}",
+            output
+        );
+    }
+
+    #[test]
+    fn test_class_and_enum_parameters_are_checked() {
+        let source = r#"
+            class Point {
+                x: number
+            }
+            enum Color { Red, Green }
+            export function paint(p: Point, c?: Color): void {}
+        "#;
+        let output = generate(
+            source,
+            ValidationMode::Auto,
+            ValidationErrors::FailFast,
+            OptimizationLevel::O2,
+        );
+        let type_id = output
+            .lines()
+            .find_map(|line| line.trim().strip_prefix("Point.__typeId = "))
+            .expect("class type ID");
+        assert!(
+            output.contains(&format!(
+                "if not (__isInstance(p, {})) then\n        error(\"Validation failed: p: expected Point, got \" .. type(p), 2)",
+                type_id
+            )),
+            "class instance check: {}",
+            output
+        );
+        assert!(
+            output.contains("if not (c == 0 or c == 1) then"),
+            "enum member check: {}",
+            output
+        );
+    }
+
+    #[test]
+    fn test_collect_mode_accumulates_errors() {
+        let source = r#"
+            type Person = { name: Refined<string, { nonEmpty: true }>, age: number }
+            export function add(person: Person): void {}
+        "#;
+        let output = generate(
+            source,
+            ValidationMode::Auto,
+            ValidationErrors::Collect,
+            OptimizationLevel::O2,
+        );
+        assert!(
+            output.contains(
+                "function __validate_Person(val, path, errors)\n    errors = errors or {}"
+            ),
+            "collect signature: {}",
+            output
+        );
+        assert!(
+            output.contains("errors[#errors + 1] = { path = path .. \".age\", expected = \"number\", got = type(val.age) }"),
+            "collected type error: {}",
+            output
+        );
+        assert!(
+            output.contains("if type(val.name) == \"string\" and #val.name < 1 then"),
+            "guarded constraint: {}",
+            output
+        );
+        assert!(output.contains("return #errors == 0, errors"), "{}", output);
+    }
+
+    #[test]
+    fn test_explicit_mode_uses_validate_decorator() {
+        let source = r#"
+            export function open(path: string): void {}
+            class Service {
+                @validate
+                handle(request: { id: number }): void {}
+            }
+        "#;
+        let output = generate(
+            source,
+            ValidationMode::Explicit,
+            ValidationErrors::FailFast,
+            OptimizationLevel::O0,
+        );
+        assert!(
+            !output.contains("__validate_string"),
+            "exports are not checked in explicit mode: {}",
+            output
+        );
+        assert!(
+            output.contains("function Service:handle(request)\n    __assertValid(__validate_1(request, \"request\"))"),
+            "decorated method: {}",
+            output
+        );
+        assert!(
+            !output.contains("validate(Service.handle)"),
+            "@validate has no runtime call: {}",
+            output
+        );
+    }
+}
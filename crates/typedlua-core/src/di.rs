@@ -1,10 +1,10 @@
-use super::codegen::CodeGenerator;
+use super::codegen::{CheckedType, CodeGenerator, DeclarationGenerator};
 use super::config::{CompilerConfig, OptimizationLevel};
 use super::diagnostics::{
     CollectingDiagnosticHandler, ConsoleDiagnosticHandler, DiagnosticHandler,
 };
 use super::fs::{FileSystem, MockFileSystem, RealFileSystem};
-use super::optimizer::Optimizer;
+use super::optimizer::{Optimizer, TypeFacts};
 use bumpalo::Bump;
 use std::any::{Any, TypeId};
 use std::sync::Arc;
@@ -217,6 +217,11 @@ impl DiContainer {
         let mut mutable_program = crate::MutableProgram::from_program(&program);
 
         let mut optimizer = Optimizer::new(level, typecheck_handler.clone(), interner.clone());
+        optimizer.set_type_facts(TypeFacts::from_checked_program(
+            &mutable_program,
+            &interner,
+            &arena,
+        ));
         if let Err(err_msg) = optimizer.optimize(&mut mutable_program, &arena) {
             typecheck_handler.warning(
                 typedlua_parser::span::Span::dummy(),
@@ -224,7 +229,7 @@ impl DiContainer {
             );
         }
 
-        let mut codegen = CodeGenerator::new(interner.clone());
+        let mut codegen = CodeGenerator::new(interner.clone()).with_optimization_level(level);
         let output = codegen.generate(&mutable_program);
 
         Ok(output)
@@ -274,6 +279,11 @@ impl DiContainer {
         let mut mutable_program = crate::MutableProgram::from_program(&program);
 
         let mut optimizer = Optimizer::new(level, typecheck_handler.clone(), interner.clone());
+        optimizer.set_type_facts(TypeFacts::from_checked_program(
+            &mutable_program,
+            &interner,
+            &arena,
+        ));
         if let Err(err_msg) = optimizer.optimize(&mut mutable_program, &arena) {
             typecheck_handler.warning(
                 typedlua_parser::span::Span::dummy(),
@@ -281,11 +291,55 @@ impl DiContainer {
             );
         }
 
-        let mut codegen = CodeGenerator::new(interner.clone());
+        let mut codegen = CodeGenerator::new(interner.clone()).with_optimization_level(level);
         let output = codegen.generate(&mutable_program);
 
         Ok(output)
     }
+
+    /// Type-check `source` and render the `.d.tl` declaration file for its exports
+    pub fn emit_declarations(&mut self, source: &str) -> Result<String, String> {
+        let arena = Bump::new();
+        let parser_handler =
+            Arc::new(ParserCollectingHandler::new()) as Arc<dyn typedlua_parser::DiagnosticHandler>;
+        let typecheck_handler = self
+            .resolve::<Arc<dyn DiagnosticHandler>>()
+            .unwrap_or_else(|| Arc::new(ConsoleDiagnosticHandler::new(false)));
+        let (interner, common_ids) = StringInterner::new_with_common_identifiers();
+
+        let mut lexer = Lexer::new(source, parser_handler.clone(), &interner);
+        let tokens = lexer
+            .tokenize()
+            .map_err(|e| format!("Lexing failed: {:?}", e))?;
+
+        let mut parser = Parser::new(
+            tokens,
+            parser_handler.clone(),
+            &interner,
+            &common_ids,
+            &arena,
+        );
+        let program = parser
+            .parse()
+            .map_err(|e| format!("Parsing failed: {:?}", e))?;
+
+        let mut type_checker =
+            TypeChecker::new(typecheck_handler.clone(), &interner, &common_ids, &arena);
+        type_checker
+            .check_program(&program)
+            .map_err(|e| e.message)?;
+        let exports = type_checker.extract_exports(&program);
+
+        let checked = exports.named.iter().map(|(name, exported)| {
+            (
+                name.clone(),
+                CheckedType::new(&exported.symbol.typ, &interner),
+            )
+        });
+        Ok(DeclarationGenerator::new(&interner)
+            .with_checked_exports(checked)
+            .generate(program.statements))
+    }
 }
 
 impl Default for DiContainer {
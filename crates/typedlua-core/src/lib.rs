@@ -4,6 +4,7 @@ pub mod cache;
 pub mod codegen;
 pub mod di;
 pub mod optimizer;
+pub mod project_config;
 pub mod type_checker;
 
 // Re-export arena for convenience
@@ -1,14 +1,15 @@
 //! Class hierarchy analysis for devirtualization
 //!
-//! Provides class hierarchy information used for cross-module optimizations.
-//! The actual devirtualization pass is temporarily disabled during arena migration
-//! (see devirtualization.rs.pre_arena for the full implementation).
+//! Provides class hierarchy information used for cross-module optimizations
+//! (devirtualization, copy-down method flattening), and the O3 pass that
+//! rewrites `obj:method()` into a direct `Class.method(obj)` call when the
+//! hierarchy proves a single target.
 
 use rustc_hash::FxHashMap;
 use rustc_hash::FxHashSet;
 use tracing::debug;
 use typedlua_parser::ast::expression::{Expression, ExpressionKind};
-use typedlua_parser::ast::statement::{ClassMember, Statement};
+use typedlua_parser::ast::statement::{ClassMember, ExportKind, Statement};
 use typedlua_parser::ast::types::TypeKind;
 use typedlua_parser::ast::Program;
 use typedlua_parser::string_interner::StringId;
@@ -38,50 +39,39 @@ pub struct ClassHierarchy {
     instantiation_counts: FxHashMap<StringId, usize>,
     /// RTA: Set of all classes that have any instantiations
     classes_with_instantiations: FxHashSet<StringId>,
+    /// Classes whose tables change after their declaration (members assigned
+    /// from outside, static state, decorators), so subclasses must keep
+    /// looking them up through the metatable chain
+    patched: FxHashSet<StringId>,
+    /// (class, method) -> non-static method with a body, callable as `Class.method`
+    instance_methods: FxHashSet<(StringId, StringId)>,
+    /// Exported classes of a single-module hierarchy, which other modules may
+    /// extend or instantiate without the analysis seeing it
+    open: FxHashSet<StringId>,
 }
 
 impl ClassHierarchy {
     /// Build class hierarchy by scanning all class declarations in the program
     pub fn build<'arena>(program: &Program<'arena>) -> Self {
-        let mut hierarchy = ClassHierarchy::default();
-
-        for stmt in program.statements.iter() {
-            if let Statement::Class(class) = stmt {
-                let class_id = class.name.node;
-                hierarchy.known_classes.insert(class_id, true);
-                hierarchy.is_final.insert(class_id, class.is_final);
-
-                let parent_id = class.extends.as_ref().and_then(|ext| {
-                    if let TypeKind::Reference(type_ref) = &ext.kind {
-                        Some(type_ref.name.node)
-                    } else {
-                        None
-                    }
-                });
-                hierarchy.parent_of.insert(class_id, parent_id);
-
-                if let Some(parent) = parent_id {
-                    hierarchy
-                        .children_of
-                        .entry(parent)
-                        .or_default()
-                        .push(class_id);
-                }
+        Self::from_statements(program.statements)
+    }
 
-                for member in class.members.iter() {
-                    if let ClassMember::Method(method) = member {
-                        let method_id = method.name.node;
-                        hierarchy
-                            .declares_method
-                            .insert((class_id, method_id), true);
-                        if method.is_final {
-                            hierarchy.final_methods.insert((class_id, method_id), true);
-                        }
-                    }
+    /// Build class hierarchy for a single module's top-level statements
+    pub fn from_statements<'arena>(statements: &[Statement<'arena>]) -> Self {
+        let mut hierarchy = ClassHierarchy::default();
+        hierarchy.record_classes(statements);
+        hierarchy.record_patches(statements);
+        for stmt in statements {
+            if let Statement::Export(export) = stmt {
+                if let ExportKind::Declaration(Statement::Class(class)) = &export.kind {
+                    hierarchy.open.insert(class.name.node);
                 }
             }
         }
-
+        for stmt in statements {
+            hierarchy.collect_instantiations_from_statement(stmt);
+        }
+        hierarchy.compute_single_instantiated_subclasses();
         hierarchy
     }
 
@@ -90,42 +80,10 @@ impl ClassHierarchy {
         let mut hierarchy = ClassHierarchy::default();
 
         for program in programs {
-            for stmt in program.statements.iter() {
-                if let Statement::Class(class) = stmt {
-                    let class_id = class.name.node;
-                    hierarchy.known_classes.insert(class_id, true);
-                    hierarchy.is_final.insert(class_id, class.is_final);
-
-                    let parent_id = class.extends.as_ref().and_then(|ext| {
-                        if let TypeKind::Reference(type_ref) = &ext.kind {
-                            Some(type_ref.name.node)
-                        } else {
-                            None
-                        }
-                    });
-                    hierarchy.parent_of.insert(class_id, parent_id);
-
-                    if let Some(parent) = parent_id {
-                        hierarchy
-                            .children_of
-                            .entry(parent)
-                            .or_default()
-                            .push(class_id);
-                    }
-
-                    for member in class.members.iter() {
-                        if let ClassMember::Method(method) = member {
-                            let method_id = method.name.node;
-                            hierarchy
-                                .declares_method
-                                .insert((class_id, method_id), true);
-                            if method.is_final {
-                                hierarchy.final_methods.insert((class_id, method_id), true);
-                            }
-                        }
-                    }
-                }
-            }
+            hierarchy.record_classes(program.statements);
+        }
+        for program in programs {
+            hierarchy.record_patches(program.statements);
         }
 
         // Second pass: collect all instantiations for RTA
@@ -139,6 +97,73 @@ impl ClassHierarchy {
         hierarchy
     }
 
+    fn record_classes<'arena>(&mut self, statements: &[Statement<'arena>]) {
+        for stmt in statements.iter() {
+            let class = match stmt {
+                Statement::Class(class) => class,
+                Statement::Export(export) => match &export.kind {
+                    ExportKind::Declaration(decl) => match &**decl {
+                        Statement::Class(class) => class,
+                        _ => continue,
+                    },
+                    _ => continue,
+                },
+                _ => continue,
+            };
+            let class_id = class.name.node;
+            self.known_classes.insert(class_id, true);
+            self.is_final.insert(class_id, class.is_final);
+
+            let parent_id = class.extends.as_ref().and_then(|ext| {
+                if let TypeKind::Reference(type_ref) = &ext.kind {
+                    Some(type_ref.name.node)
+                } else {
+                    None
+                }
+            });
+            self.parent_of.insert(class_id, parent_id);
+
+            if let Some(parent) = parent_id {
+                self.children_of.entry(parent).or_default().push(class_id);
+            }
+
+            if !class.decorators.is_empty() {
+                self.patched.insert(class_id);
+            }
+
+            for member in class.members.iter() {
+                match member {
+                    ClassMember::Method(method) => {
+                        let method_id = method.name.node;
+                        self.declares_method.insert((class_id, method_id), true);
+                        if method.is_final {
+                            self.final_methods.insert((class_id, method_id), true);
+                        }
+                        if !method.is_static && method.body.is_some() {
+                            self.instance_methods.insert((class_id, method_id));
+                        }
+                        if !method.decorators.is_empty() {
+                            self.patched.insert(class_id);
+                        }
+                    }
+                    ClassMember::Property(prop) if prop.is_static => {
+                        self.patched.insert(class_id);
+                    }
+                    ClassMember::Property(prop) if !prop.decorators.is_empty() => {
+                        self.patched.insert(class_id);
+                    }
+                    ClassMember::Getter(getter) if !getter.decorators.is_empty() => {
+                        self.patched.insert(class_id);
+                    }
+                    ClassMember::Setter(setter) if !setter.decorators.is_empty() => {
+                        self.patched.insert(class_id);
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
     /// Collect all `new ClassName()` instantiations from a program
     fn collect_instantiations<'arena>(&mut self, program: &Program<'arena>) {
         for stmt in program.statements.iter() {
@@ -220,6 +245,11 @@ impl ClassHierarchy {
                     self.collect_instantiations_from_statement(s);
                 }
             }
+            Statement::Export(export) => {
+                if let ExportKind::Declaration(decl) = &export.kind {
+                    self.collect_instantiations_from_statement(decl);
+                }
+            }
             Statement::Class(class) => {
                 for member in class.members.iter() {
                     if let ClassMember::Method(method) = member {
@@ -243,7 +273,7 @@ impl ClassHierarchy {
     fn collect_instantiations_from_expression<'arena>(&mut self, expr: &Expression<'arena>) {
         match &expr.kind {
             New(callee, args, _) => {
-                self.record_instantiation_from_expression(callee);
+                self.record_instantiation_from_callee(callee);
                 for arg in args.iter() {
                     self.collect_instantiations_from_expression(&arg.value);
                 }
@@ -390,10 +420,17 @@ impl ClassHierarchy {
         }
     }
 
-    fn record_instantiation_from_expression<'arena>(&mut self, expr: &Expression<'arena>) {
-        if let Call(func, _args, _) = &expr.kind {
-            self.record_instantiation_from_callee(func);
-        }
+    /// Mark classes with members assigned anywhere in `statements`, including
+    /// function and method bodies, closures and try blocks
+    fn record_patches<'arena>(&mut self, statements: &[Statement<'arena>]) {
+        let mut scan = PatchScan {
+            known_classes: &self.known_classes,
+            patched: FxHashSet::default(),
+        };
+        let arena = Bump::new();
+        let mut statements = statements.to_vec();
+        scan.visit_block(&mut statements, &arena);
+        self.patched.extend(scan.patched);
     }
 
     fn record_instantiation_from_callee<'arena>(&mut self, expr: &Expression<'arena>) {
@@ -452,6 +489,26 @@ impl ClassHierarchy {
         self.known_classes.contains_key(&class)
     }
 
+    /// Whether inherited methods can be copied into `class` instead of being
+    /// looked up through the metatable chain: it extends a class, every
+    /// ancestor is known (the hierarchy is closed), and no ancestor's table
+    /// changes after its declaration.
+    pub fn can_flatten(&self, class: StringId) -> bool {
+        let mut current = match self.parent_of.get(&class) {
+            Some(Some(parent)) => *parent,
+            _ => return false,
+        };
+        loop {
+            if !self.is_known_class(current) || self.patched.contains(&current) {
+                return false;
+            }
+            match self.parent_of.get(&current) {
+                Some(Some(parent)) => current = *parent,
+                _ => return true,
+            }
+        }
+    }
+
     pub fn can_devirtualize(&self, class: StringId, method: StringId) -> bool {
         if self.is_final.get(&class) == Some(&true) {
             return true;
@@ -517,7 +574,10 @@ impl ClassHierarchy {
             return (true, None);
         }
         if let Some(subclass) = self.single_instantiated_subclass.get(&class) {
-            return (true, Some(*subclass));
+            // Instances of the class itself dispatch to its own methods
+            if !self.classes_with_instantiations.contains(&class) {
+                return (true, Some(*subclass));
+            }
         }
         let has_overrides = self.any_descendant_overrides(class, method);
         if !has_overrides {
@@ -525,28 +585,121 @@ impl ClassHierarchy {
         }
         (false, None)
     }
+
+    /// The class whose own `method` a call on an instance of `class` runs:
+    /// the nearest ancestor-or-self declaring it. `None` when the method is
+    /// abstract, static, or not declared in the known hierarchy, or when a
+    /// class below the declaring one is patched and may have gained an
+    /// override at runtime.
+    pub fn resolve_method(&self, class: StringId, method: StringId) -> Option<StringId> {
+        let mut current = class;
+        loop {
+            if !self.is_known_class(current) {
+                return None;
+            }
+            if self.declares_method.get(&(current, method)) == Some(&true) {
+                return self
+                    .instance_methods
+                    .contains(&(current, method))
+                    .then_some(current);
+            }
+            if self.patched.contains(&current) {
+                return None;
+            }
+            match self.parent_of.get(&current) {
+                Some(Some(parent)) => current = *parent,
+                _ => return None,
+            }
+        }
+    }
+
+    /// Whether no class in the subtree rooted at `class` is patched or open
+    fn is_subtree_closed(&self, class: StringId) -> bool {
+        if self.patched.contains(&class) || self.open.contains(&class) {
+            return false;
+        }
+        self.children_of
+            .get(&class)
+            .into_iter()
+            .flatten()
+            .all(|&child| self.is_subtree_closed(child))
+    }
+
+    /// The class to call `method` on directly for a receiver statically typed
+    /// as `class`, when every instance the program creates dispatches to it.
+    /// `exact` receivers are known to be instances of `class` itself.
+    pub fn devirtualize_target(
+        &self,
+        class: StringId,
+        method: StringId,
+        exact: bool,
+    ) -> Option<StringId> {
+        if exact {
+            return self.resolve_method(class, method);
+        }
+        if !self.is_known_class(class) || !self.is_subtree_closed(class) {
+            return None;
+        }
+        match self.can_devirtualize_with_rta(class, method) {
+            (true, subclass) => self.resolve_method(subclass.unwrap_or(class), method),
+            (false, _) => None,
+        }
+    }
+}
+
+/// Read-only walk that finds `Class.member = ...` and `Class[key] = ...`
+/// on known classes
+struct PatchScan<'h> {
+    known_classes: &'h FxHashMap<StringId, bool>,
+    patched: FxHashSet<StringId>,
+}
+
+impl<'arena> MutVisitor<'arena> for PatchScan<'_> {
+    fn visit_expression(&mut self, expr: &mut Expression<'arena>, arena: &'arena Bump) -> bool {
+        if let Assignment(target, _, _) = &expr.kind {
+            if let Member(object, _) | Index(object, _) = &target.kind {
+                if let Identifier(id) = &object.kind {
+                    if self.known_classes.contains_key(id) {
+                        self.patched.insert(*id);
+                    }
+                }
+            }
+        }
+        walk_expression(self, expr, arena)
+    }
 }
 
 // =============================================================================
-// DevirtualizationPass — stub during arena migration
+// DevirtualizationPass
 // =============================================================================
 
 use crate::config::OptimizationLevel;
 use crate::MutableProgram;
 use bumpalo::Bump;
 use std::sync::Arc;
+use typedlua_parser::ast::expression::{Argument, ReceiverClassInfo};
+use typedlua_parser::ast::pattern::{ArrayPatternElement, Pattern};
+use typedlua_parser::ast::statement::{
+    Block, CatchPattern, ForStatement, ImportClause, Parameter, VariableDeclaration, VariableKind,
+};
+use typedlua_parser::ast::types::Type;
 use typedlua_parser::string_interner::StringInterner;
 
-use super::{AstFeatures, WholeProgramPass};
+use super::{walk_block, walk_expression, walk_pattern, walk_statement, MutVisitor};
+use super::{AstFeatures, RemarkSink, WholeProgramPass};
 
 /// Devirtualization optimization pass (O3).
 ///
-/// Replaces virtual method calls with direct calls when the class hierarchy
-/// allows safe devirtualization. Currently a stub during arena migration.
+/// Replaces `obj:method(...)` with `Class.method(obj, ...)` when the class
+/// hierarchy and rapid type analysis prove every receiver dispatches to the
+/// same method. Uses the cross-module hierarchy from [`WholeProgramAnalysis`]
+/// when one is set, and otherwise analyzes the module on its own.
+///
+/// [`WholeProgramAnalysis`]: super::WholeProgramAnalysis
 pub struct DevirtualizationPass {
-    #[allow(dead_code)]
     interner: Arc<StringInterner>,
     class_hierarchy: Option<ClassHierarchy>,
+    remarks: RemarkSink,
 }
 
 impl DevirtualizationPass {
@@ -554,9 +707,15 @@ impl DevirtualizationPass {
         Self {
             interner,
             class_hierarchy: None,
+            remarks: RemarkSink::default(),
         }
     }
 
+    pub fn with_remarks(mut self, remarks: RemarkSink) -> Self {
+        self.remarks = remarks;
+        self
+    }
+
     pub fn set_class_hierarchy(&mut self, hierarchy: ClassHierarchy) {
         self.class_hierarchy = Some(hierarchy);
     }
@@ -577,14 +736,510 @@ impl<'arena> WholeProgramPass<'arena> for DevirtualizationPass {
 
     fn run(
         &mut self,
-        _program: &mut MutableProgram<'arena>,
-        _arena: &'arena Bump,
+        program: &mut MutableProgram<'arena>,
+        arena: &'arena Bump,
     ) -> Result<bool, String> {
-        // TODO: Re-implement devirtualization with arena-allocated AST
-        Ok(false)
+        let module_hierarchy;
+        let hierarchy = match &self.class_hierarchy {
+            Some(hierarchy) => hierarchy,
+            None => {
+                module_hierarchy = ClassHierarchy::from_statements(&program.statements);
+                &module_hierarchy
+            }
+        };
+
+        let mut rewriter = CallRewriter {
+            hierarchy,
+            interner: &self.interner,
+            remarks: &self.remarks,
+            visible_classes: visible_classes(&program.statements, hierarchy),
+            scopes: vec![FxHashMap::default()],
+        };
+        Ok(rewriter.visit_block(&mut program.statements, arena))
     }
 
     fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
         self
     }
 }
+
+/// Known classes bound by name at the top of the module: declared here or
+/// imported under their own name. A direct call can only name these.
+fn visible_classes(
+    statements: &[Statement<'_>],
+    hierarchy: &ClassHierarchy,
+) -> FxHashSet<StringId> {
+    let mut visible = FxHashSet::default();
+    for stmt in statements {
+        let stmt = match stmt {
+            Statement::Export(export) => match &export.kind {
+                ExportKind::Declaration(decl) => &**decl,
+                _ => continue,
+            },
+            other => other,
+        };
+        match stmt {
+            Statement::Class(class) => {
+                visible.insert(class.name.node);
+            }
+            Statement::Import(import) => {
+                let named = match &import.clause {
+                    ImportClause::Named(specs) => &specs[..],
+                    ImportClause::Mixed { named, .. } => &named[..],
+                    _ => continue,
+                };
+                visible.extend(
+                    named
+                        .iter()
+                        .filter(|spec| spec.local.is_none())
+                        .map(|spec| spec.imported.node)
+                        .filter(|id| hierarchy.is_known_class(*id)),
+                );
+            }
+            _ => {}
+        }
+    }
+    visible
+}
+
+/// Static type of a local: the class it holds, and whether it is known to be
+/// an instance of exactly that class (`const x = new C()`)
+#[derive(Debug, Clone, Copy)]
+struct LocalClass {
+    class: StringId,
+    exact: bool,
+}
+
+struct CallRewriter<'a> {
+    hierarchy: &'a ClassHierarchy,
+    interner: &'a StringInterner,
+    remarks: &'a RemarkSink,
+    visible_classes: FxHashSet<StringId>,
+    /// Locals in scope; `None` for locals of no known class type, which
+    /// still shadow outer names
+    scopes: Vec<FxHashMap<StringId, Option<LocalClass>>>,
+}
+
+impl CallRewriter<'_> {
+    fn lookup(&self, name: StringId) -> Option<Option<LocalClass>> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(&name).copied())
+    }
+
+    fn bind(&mut self, name: StringId, local: Option<LocalClass>) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name, local);
+        }
+    }
+
+    fn class_of_type(&self, ty: &Type<'_>) -> Option<StringId> {
+        match &ty.kind {
+            TypeKind::Reference(type_ref) if self.hierarchy.is_known_class(type_ref.name.node) => {
+                Some(type_ref.name.node)
+            }
+            _ => None,
+        }
+    }
+
+    fn bind_variable(&mut self, decl: &VariableDeclaration<'_>) {
+        let Pattern::Identifier(ident) = &decl.pattern else {
+            self.shadow_pattern(&decl.pattern);
+            return;
+        };
+        let declared = decl
+            .type_annotation
+            .as_ref()
+            .and_then(|ty| self.class_of_type(ty))
+            .map(|class| LocalClass {
+                class,
+                exact: false,
+            });
+        let constructed = match &decl.initializer.kind {
+            New(callee, _, _) => match &callee.kind {
+                Identifier(class) if self.hierarchy.is_known_class(*class) => Some(LocalClass {
+                    class: *class,
+                    exact: decl.kind == VariableKind::Const,
+                }),
+                _ => None,
+            },
+            _ => None,
+        };
+        self.bind(ident.node, declared.or(constructed));
+    }
+
+    /// Bind every name a destructuring pattern introduces as untyped
+    fn shadow_pattern(&mut self, pattern: &Pattern<'_>) {
+        match pattern {
+            Pattern::Identifier(ident) => self.bind(ident.node, None),
+            Pattern::Array(array) => {
+                for element in array.elements.iter() {
+                    match element {
+                        ArrayPatternElement::Pattern(with_default) => {
+                            self.shadow_pattern(&with_default.pattern)
+                        }
+                        ArrayPatternElement::Rest(ident) => self.bind(ident.node, None),
+                        ArrayPatternElement::Hole => {}
+                    }
+                }
+            }
+            Pattern::Object(object) => {
+                for prop in object.properties.iter() {
+                    match &prop.value {
+                        Some(value) => self.shadow_pattern(value),
+                        None => self.bind(prop.key.node, None),
+                    }
+                }
+            }
+            Pattern::Wildcard(_) | Pattern::Literal(..) | Pattern::Or(_) => {}
+        }
+    }
+
+    fn bind_parameters(&mut self, parameters: &[Parameter<'_>]) {
+        for param in parameters {
+            let Pattern::Identifier(ident) = &param.pattern else {
+                self.shadow_pattern(&param.pattern);
+                continue;
+            };
+            let local = param
+                .type_annotation
+                .as_ref()
+                .and_then(|ty| self.class_of_type(ty))
+                .map(|class| LocalClass {
+                    class,
+                    exact: false,
+                });
+            self.bind(ident.node, local);
+        }
+    }
+
+    /// Static class of a method call receiver
+    fn receiver_class(
+        &self,
+        obj: &Expression<'_>,
+        receiver: Option<&ReceiverClassInfo>,
+    ) -> Option<LocalClass> {
+        match &obj.kind {
+            New(callee, _, _) => {
+                if let Identifier(class) = &callee.kind {
+                    if self.hierarchy.is_known_class(*class) {
+                        return Some(LocalClass {
+                            class: *class,
+                            exact: true,
+                        });
+                    }
+                }
+            }
+            Parenthesized(inner) => return self.receiver_class(inner, receiver),
+            Identifier(name) => {
+                if let Some(local) = self.lookup(*name) {
+                    return local;
+                }
+            }
+            _ => {}
+        }
+        if let Some(info) = receiver {
+            if !info.is_static && self.hierarchy.is_known_class(info.class_name) {
+                return Some(LocalClass {
+                    class: info.class_name,
+                    exact: false,
+                });
+            }
+        }
+        obj.annotated_type
+            .as_ref()
+            .and_then(|ty| self.class_of_type(ty))
+            .map(|class| LocalClass {
+                class,
+                exact: false,
+            })
+    }
+
+    /// Run `walk` in a scope of its own
+    fn scoped<T>(&mut self, walk: impl FnOnce(&mut Self) -> T) -> T {
+        self.scopes.push(FxHashMap::default());
+        let result = walk(self);
+        self.scopes.pop();
+        result
+    }
+
+    /// A function body: parameter defaults, then the body with the
+    /// parameters bound in a scope of their own
+    fn function<'p, 'arena: 'p>(
+        &mut self,
+        parameters: &mut &'p [Parameter<'arena>],
+        body: &mut Block<'arena>,
+        arena: &'arena Bump,
+    ) -> bool {
+        self.scoped(|s| {
+            let mut params = parameters.to_vec();
+            let mut changed = false;
+            for param in &mut params {
+                changed |= walk_pattern(s, &mut param.pattern, arena);
+                if let Some(default) = &mut param.default {
+                    changed |= s.visit_expression(default, arena);
+                }
+            }
+            if changed {
+                *parameters = arena.alloc_slice_clone(&params);
+            }
+            s.bind_parameters(parameters);
+            changed |= s.body(body, arena);
+            changed
+        })
+    }
+
+    /// A block's statements, walked without opening another scope
+    fn body<'arena>(&mut self, block: &mut Block<'arena>, arena: &'arena Bump) -> bool {
+        let mut stmts = block.statements.to_vec();
+        let changed = walk_block(self, &mut stmts, arena);
+        if changed {
+            block.statements = arena.alloc_slice_clone(&stmts);
+        }
+        changed
+    }
+
+    /// Replace a method call whose receiver provably dispatches to one class
+    /// with a direct call, reporting the receivers it cannot resolve
+    fn devirtualize<'arena>(&mut self, expr: &mut Expression<'arena>, arena: &'arena Bump) -> bool {
+        let MethodCall(obj, method_name, args, _) = &expr.kind else {
+            return false;
+        };
+        let receiver = self.receiver_class(obj, expr.receiver_class.as_ref());
+        let resolved = receiver.and_then(|receiver| {
+            self.hierarchy
+                .devirtualize_target(receiver.class, method_name.node, receiver.exact)
+        });
+        let target = resolved.filter(|target| {
+            self.visible_classes.contains(target) && self.lookup(*target).is_none()
+        });
+
+        if let (Some(receiver), None) = (receiver, target) {
+            let method = || self.interner.resolve(method_name.node);
+            let class = || self.interner.resolve(receiver.class);
+            self.remarks.missed(
+                "devirtualization",
+                expr.span,
+                || format!("not devirtualized `:{}()` on `{}`", method(), class()),
+                || match resolved {
+                    Some(resolved) => format!(
+                        "`{}` is not bound by name here",
+                        self.interner.resolve(resolved)
+                    ),
+                    None if receiver.exact => format!(
+                        "`{}.{}` is abstract, static or patched at runtime",
+                        class(),
+                        method()
+                    ),
+                    None => format!(
+                        "classes below `{}` are open, patched or override `{}`",
+                        class(),
+                        method()
+                    ),
+                },
+            );
+        }
+        let Some(target) = target else {
+            return false;
+        };
+
+        debug!(
+            "Devirtualized :{}() to {}.{}",
+            self.interner.resolve(method_name.node),
+            self.interner.resolve(target),
+            self.interner.resolve(method_name.node)
+        );
+        self.remarks.applied("devirtualization", expr.span, || {
+            format!(
+                "devirtualized `:{}()` to `{}.{}`",
+                self.interner.resolve(method_name.node),
+                self.interner.resolve(target),
+                self.interner.resolve(method_name.node)
+            )
+        });
+        let span = expr.span;
+        let callee = Expression::new(
+            Member(
+                arena.alloc(Expression::new(Identifier(target), span)),
+                method_name.clone(),
+            ),
+            span,
+        );
+        let call_args: Vec<_> = std::iter::once(Argument {
+            value: (**obj).clone(),
+            is_spread: false,
+            span,
+        })
+        .chain(args.iter().cloned())
+        .collect();
+        expr.kind = Call(
+            arena.alloc(callee),
+            arena.alloc_slice_clone(&call_args),
+            None,
+        );
+        expr.receiver_class = None;
+        true
+    }
+}
+
+impl<'arena> MutVisitor<'arena> for CallRewriter<'_> {
+    fn visit_block(&mut self, stmts: &mut Vec<Statement<'arena>>, arena: &'arena Bump) -> bool {
+        self.scoped(|s| walk_block(s, stmts, arena))
+    }
+
+    fn visit_statement(&mut self, stmt: &mut Statement<'arena>, arena: &'arena Bump) -> bool {
+        match stmt {
+            Statement::Variable(decl) => {
+                // The initializer still sees the names the declaration shadows
+                let mut changed = self.visit_expression(&mut decl.initializer, arena);
+                changed |= walk_pattern(self, &mut decl.pattern, arena);
+                self.bind_variable(decl);
+                changed
+            }
+            Statement::Function(func) => {
+                self.bind(func.name.node, None);
+                self.function(&mut func.parameters, &mut func.body, arena)
+            }
+            Statement::Class(class) => {
+                let mut members = class.members.to_vec();
+                let mut changed = false;
+                for member in &mut members {
+                    changed |= match member {
+                        ClassMember::Method(method) => match &mut method.body {
+                            Some(body) => self.function(&mut method.parameters, body, arena),
+                            None => false,
+                        },
+                        ClassMember::Constructor(ctor) => {
+                            self.function(&mut ctor.parameters, &mut ctor.body, arena)
+                        }
+                        ClassMember::Getter(getter) => {
+                            let mut none: &[Parameter<'arena>] = &[];
+                            self.function(&mut none, &mut getter.body, arena)
+                        }
+                        ClassMember::Setter(setter) => {
+                            let own = [setter.parameter.clone()];
+                            let mut parameter: &[Parameter<'arena>] = &own;
+                            let changed = self.function(&mut parameter, &mut setter.body, arena);
+                            if changed {
+                                setter.parameter = parameter[0].clone();
+                            }
+                            changed
+                        }
+                        ClassMember::Operator(op) => {
+                            self.function(&mut op.parameters, &mut op.body, arena)
+                        }
+                        ClassMember::Property(_) => false,
+                    };
+                }
+                if changed {
+                    class.members = arena.alloc_slice_clone(&members);
+                }
+                changed
+            }
+            Statement::Enum(decl) => {
+                let mut changed = false;
+                let mut members = decl.members.to_vec();
+                let mut members_changed = false;
+                for member in &mut members {
+                    let mut args = member.arguments.to_vec();
+                    let mut args_changed = false;
+                    for arg in &mut args {
+                        args_changed |= self.visit_expression(arg, arena);
+                    }
+                    if args_changed {
+                        member.arguments = arena.alloc_slice_clone(&args);
+                        members_changed = true;
+                    }
+                }
+                if members_changed {
+                    decl.members = arena.alloc_slice_clone(&members);
+                    changed = true;
+                }
+                if let Some(ctor) = &mut decl.constructor {
+                    changed |= self.function(&mut ctor.parameters, &mut ctor.body, arena);
+                }
+                let mut methods = decl.methods.to_vec();
+                let mut methods_changed = false;
+                for method in &mut methods {
+                    methods_changed |=
+                        self.function(&mut method.parameters, &mut method.body, arena);
+                }
+                if methods_changed {
+                    decl.methods = arena.alloc_slice_clone(&methods);
+                    changed = true;
+                }
+                changed
+            }
+            Statement::For(for_stmt) => {
+                let for_stmt: &'arena ForStatement<'arena> = *for_stmt;
+                self.scoped(|s| {
+                    match for_stmt {
+                        ForStatement::Numeric(for_num) => s.bind(for_num.variable.node, None),
+                        ForStatement::Generic(for_gen) => {
+                            for var in for_gen.variables.iter() {
+                                s.bind(var.node, None);
+                            }
+                            if let Some(pattern) = &for_gen.pattern {
+                                s.shadow_pattern(pattern);
+                            }
+                        }
+                    }
+                    walk_statement(s, stmt, arena)
+                })
+            }
+            Statement::Repeat(repeat_stmt) => {
+                // The `until` condition sees the body's locals, so both share a scope
+                self.scoped(|s| {
+                    let mut changed = s.body(&mut repeat_stmt.body, arena);
+                    changed |= s.visit_expression(&mut repeat_stmt.until, arena);
+                    changed
+                })
+            }
+            Statement::Try(try_stmt) => {
+                let mut changed = self.scoped(|s| s.body(&mut try_stmt.try_block, arena));
+                let mut clauses = try_stmt.catch_clauses.to_vec();
+                let mut clauses_changed = false;
+                for clause in &mut clauses {
+                    clauses_changed |= self.scoped(|s| {
+                        match &clause.pattern {
+                            CatchPattern::Untyped { variable, .. }
+                            | CatchPattern::Typed { variable, .. }
+                            | CatchPattern::MultiTyped { variable, .. } => {
+                                s.bind(variable.node, None)
+                            }
+                        }
+                        s.body(&mut clause.body, arena)
+                    });
+                }
+                if clauses_changed {
+                    try_stmt.catch_clauses = arena.alloc_slice_clone(&clauses);
+                    changed = true;
+                }
+                if let Some(finally_block) = &mut try_stmt.finally_block {
+                    changed |= self.scoped(|s| s.body(finally_block, arena));
+                }
+                changed
+            }
+            _ => walk_statement(self, stmt, arena),
+        }
+    }
+
+    fn visit_expression(&mut self, expr: &mut Expression<'arena>, arena: &'arena Bump) -> bool {
+        match &mut expr.kind {
+            Function(func) => self.function(&mut func.parameters, &mut func.body, arena),
+            Arrow(arrow) => {
+                let parameters = arrow.parameters;
+                self.scoped(|s| {
+                    s.bind_parameters(parameters);
+                    walk_expression(s, expr, arena)
+                })
+            }
+            _ => {
+                // Receivers and arguments first, so nested calls are rewritten
+                let changed = walk_expression(self, expr, arena);
+                self.devirtualize(expr, arena) || changed
+            }
+        }
+    }
+}
@@ -0,0 +1,392 @@
+//! Summaries of small exports for cross-module inlining
+//!
+//! Every module is compiled with its own string interner, so a summary names
+//! exports by string and keeps function bodies as a small owned expression
+//! tree that the importing module rebuilds in its own arena.
+
+use super::passes::pattern_binders;
+use rustc_hash::FxHashMap;
+use typedlua_parser::ast::expression::{BinaryOp, Expression, ExpressionKind, Literal, UnaryOp};
+use typedlua_parser::ast::pattern::Pattern;
+use typedlua_parser::ast::statement::{
+    ExportKind, FunctionDeclaration, ImportClause, Statement, VariableKind,
+};
+use typedlua_parser::ast::Program;
+use typedlua_parser::string_interner::{StringId, StringInterner};
+
+/// Larger bodies are not worth copying into every caller
+const MAX_BODY_NODES: usize = 12;
+
+/// Longer strings stay behind their export
+const MAX_CONSTANT_STRING_LEN: usize = 32;
+
+/// `math` functions without side effects
+const PURE_MATH_FUNCTIONS: &[&str] = &[
+    "abs", "ceil", "cos", "exp", "floor", "log", "max", "min", "sin", "sqrt", "tan",
+];
+
+/// The body of a summarized function, over its parameters
+#[derive(Clone, Debug)]
+pub enum SummaryExpr {
+    Literal(Literal),
+    Parameter(usize),
+    /// `math.<name>(args)` on the global `math` table
+    MathCall(String, Vec<SummaryExpr>),
+    Unary(UnaryOp, Box<SummaryExpr>),
+    Binary(BinaryOp, Box<SummaryExpr>, Box<SummaryExpr>),
+    Conditional(Box<SummaryExpr>, Box<SummaryExpr>, Box<SummaryExpr>),
+}
+
+impl SummaryExpr {
+    fn node_count(&self) -> usize {
+        1 + match self {
+            SummaryExpr::Literal(_) | SummaryExpr::Parameter(_) => 0,
+            SummaryExpr::MathCall(_, args) => args.iter().map(Self::node_count).sum(),
+            SummaryExpr::Unary(_, operand) => operand.node_count(),
+            SummaryExpr::Binary(_, left, right) => left.node_count() + right.node_count(),
+            SummaryExpr::Conditional(cond, then_expr, else_expr) => {
+                cond.node_count() + then_expr.node_count() + else_expr.node_count()
+            }
+        }
+    }
+
+    pub fn uses_math(&self) -> bool {
+        match self {
+            SummaryExpr::Literal(_) | SummaryExpr::Parameter(_) => false,
+            SummaryExpr::MathCall(..) => true,
+            SummaryExpr::Unary(_, operand) => operand.uses_math(),
+            SummaryExpr::Binary(_, left, right) => left.uses_math() || right.uses_math(),
+            SummaryExpr::Conditional(cond, then_expr, else_expr) => {
+                cond.uses_math() || then_expr.uses_math() || else_expr.uses_math()
+            }
+        }
+    }
+
+    /// Counts parameter uses; `branch` is set below `and`/`or` right-hand
+    /// sides and conditional arms, which may not be evaluated
+    fn count_uses(&self, branch: bool, uses: &mut [(usize, bool)]) {
+        match self {
+            SummaryExpr::Literal(_) => {}
+            SummaryExpr::Parameter(index) => {
+                uses[*index].0 += 1;
+                uses[*index].1 |= branch;
+            }
+            SummaryExpr::MathCall(_, args) => {
+                for arg in args {
+                    arg.count_uses(branch, uses);
+                }
+            }
+            SummaryExpr::Unary(_, operand) => operand.count_uses(branch, uses),
+            SummaryExpr::Binary(op, left, right) => {
+                left.count_uses(branch, uses);
+                let short_circuit = matches!(op, BinaryOp::And | BinaryOp::Or);
+                right.count_uses(branch || short_circuit, uses);
+            }
+            SummaryExpr::Conditional(cond, then_expr, else_expr) => {
+                cond.count_uses(branch, uses);
+                then_expr.count_uses(true, uses);
+                else_expr.count_uses(true, uses);
+            }
+        }
+    }
+}
+
+/// An exported function whose body is a single `return <expr>`
+#[derive(Clone, Debug)]
+pub struct InlineFunction {
+    pub body: SummaryExpr,
+    /// Per parameter: read exactly once, and always. Only such parameters
+    /// may receive an argument with side effects, since inlining moves the
+    /// argument to its use.
+    pub single_use: Vec<bool>,
+}
+
+impl InlineFunction {
+    pub fn arity(&self) -> usize {
+        self.single_use.len()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum ExportSummary {
+    /// `export const NAME = <literal>`
+    Constant(Literal),
+    Function(InlineFunction),
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ModuleSummary {
+    pub exports: FxHashMap<String, ExportSummary>,
+    /// Loading the module only defines functions, literals and types, so
+    /// an import that no longer names anything can be dropped
+    pub side_effect_free: bool,
+}
+
+/// Inlinable exports of every module, keyed by module id
+#[derive(Clone, Debug, Default)]
+pub struct ExportSummaries {
+    modules: FxHashMap<String, ModuleSummary>,
+}
+
+impl ExportSummaries {
+    pub fn is_empty(&self) -> bool {
+        self.modules.is_empty()
+    }
+
+    pub fn module(&self, module_id: &str) -> Option<&ModuleSummary> {
+        self.modules.get(module_id)
+    }
+
+    /// Summarize the exports of a type-checked module
+    pub fn add_module(
+        &mut self,
+        module_id: impl Into<String>,
+        program: &Program<'_>,
+        interner: &StringInterner,
+    ) {
+        let summary = Summarizer::new(program.statements, interner).summarize(program.statements);
+        self.modules.insert(module_id.into(), summary);
+    }
+}
+
+/// Top-level statements, looking through `export` declarations, and whether
+/// they were exported
+fn declarations<'a, 'arena>(
+    statements: &'a [Statement<'arena>],
+) -> impl Iterator<Item = (&'a Statement<'arena>, bool)> {
+    statements.iter().map(|stmt| match stmt {
+        Statement::Export(export) => match &export.kind {
+            ExportKind::Declaration(decl) => (&**decl, true),
+            _ => (stmt, false),
+        },
+        _ => (stmt, false),
+    })
+}
+
+/// A literal, or a negated number literal
+fn literal_value(expr: &Expression<'_>) -> Option<Literal> {
+    match &expr.kind {
+        ExpressionKind::Literal(Literal::String(s)) if s.len() > MAX_CONSTANT_STRING_LEN => None,
+        ExpressionKind::Literal(literal) => Some(literal.clone()),
+        ExpressionKind::Parenthesized(inner) => literal_value(inner),
+        ExpressionKind::Unary(UnaryOp::Negate, operand) => match literal_value(operand)? {
+            Literal::Number(n) => Some(Literal::Number(-n)),
+            Literal::Integer(i) => Some(Literal::Integer(i.checked_neg()?)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+struct Summarizer<'i> {
+    interner: &'i StringInterner,
+    /// Top-level `const` literals, exported or not
+    constants: FxHashMap<StringId, Literal>,
+    /// The module binds `math` at the top level
+    shadows_math: bool,
+}
+
+impl<'i> Summarizer<'i> {
+    fn new(statements: &[Statement<'_>], interner: &'i StringInterner) -> Self {
+        let math = interner.get_or_intern("math");
+        let mut bound = Vec::new();
+        let mut constants = FxHashMap::default();
+        for (stmt, _) in declarations(statements) {
+            match stmt {
+                Statement::Variable(decl) => {
+                    pattern_binders(&decl.pattern, &mut bound);
+                    if let (VariableKind::Const, Pattern::Identifier(ident)) =
+                        (&decl.kind, &decl.pattern)
+                    {
+                        if let Some(value) = literal_value(&decl.initializer) {
+                            constants.insert(ident.node, value);
+                        }
+                    }
+                }
+                Statement::Function(func) => bound.push(func.name.node),
+                Statement::Class(class) => bound.push(class.name.node),
+                Statement::Enum(decl) => bound.push(decl.name.node),
+                Statement::Import(import) => match &import.clause {
+                    ImportClause::Default(ident) | ImportClause::Namespace(ident) => {
+                        bound.push(ident.node)
+                    }
+                    ImportClause::Named(specs) => bound.extend(
+                        specs
+                            .iter()
+                            .map(|spec| spec.local.as_ref().unwrap_or(&spec.imported).node),
+                    ),
+                    ImportClause::Mixed { default, named } => {
+                        bound.push(default.node);
+                        bound.extend(
+                            named
+                                .iter()
+                                .map(|spec| spec.local.as_ref().unwrap_or(&spec.imported).node),
+                        );
+                    }
+                    ImportClause::TypeOnly(_) => {}
+                },
+                _ => {}
+            }
+        }
+        // A name bound twice is not a constant
+        for (i, name) in bound.iter().enumerate() {
+            if bound[..i].contains(name) {
+                constants.remove(name);
+            }
+        }
+        Self {
+            interner,
+            constants,
+            shadows_math: bound.contains(&math),
+        }
+    }
+
+    fn summarize(&self, statements: &[Statement<'_>]) -> ModuleSummary {
+        let mut summary = ModuleSummary {
+            exports: FxHashMap::default(),
+            side_effect_free: true,
+        };
+        for (stmt, exported) in declarations(statements) {
+            summary.side_effect_free &= match stmt {
+                Statement::Variable(decl) => literal_value(&decl.initializer).is_some(),
+                Statement::Function(_)
+                | Statement::Interface(_)
+                | Statement::TypeAlias(_)
+                | Statement::DeclareFunction(_)
+                | Statement::DeclareNamespace(_)
+                | Statement::DeclareType(_)
+                | Statement::DeclareInterface(_)
+                | Statement::DeclareConst(_) => true,
+                _ => false,
+            };
+            if !exported {
+                continue;
+            }
+            let (name, export) = match stmt {
+                Statement::Variable(decl) => {
+                    let Pattern::Identifier(ident) = &decl.pattern else {
+                        continue;
+                    };
+                    let Some(value) = self.constants.get(&ident.node) else {
+                        continue;
+                    };
+                    (ident.node, ExportSummary::Constant(value.clone()))
+                }
+                Statement::Function(func) => match self.function(func) {
+                    Some(function) => (func.name.node, ExportSummary::Function(function)),
+                    None => continue,
+                },
+                _ => continue,
+            };
+            summary
+                .exports
+                .insert(self.interner.resolve(name).to_string(), export);
+        }
+        summary
+    }
+
+    fn function(&self, func: &FunctionDeclaration<'_>) -> Option<InlineFunction> {
+        let mut params = Vec::with_capacity(func.parameters.len());
+        for param in func.parameters.iter() {
+            let Pattern::Identifier(ident) = &param.pattern else {
+                return None;
+            };
+            if param.is_rest || param.default.is_some() {
+                return None;
+            }
+            params.push(ident.node);
+        }
+        let [Statement::Return(ret)] = func.body.statements else {
+            return None;
+        };
+        let [value] = ret.values else {
+            return None;
+        };
+        let body = self.expression(value, &params)?;
+        if body.node_count() > MAX_BODY_NODES {
+            return None;
+        }
+        let mut uses = vec![(0, false); params.len()];
+        body.count_uses(false, &mut uses);
+        Some(InlineFunction {
+            body,
+            single_use: uses
+                .into_iter()
+                .map(|(count, branch)| count == 1 && !branch)
+                .collect(),
+        })
+    }
+
+    fn expression(&self, expr: &Expression<'_>, params: &[StringId]) -> Option<SummaryExpr> {
+        let summary = match &expr.kind {
+            ExpressionKind::Literal(literal) => SummaryExpr::Literal(literal.clone()),
+            ExpressionKind::Identifier(name) => match params.iter().rposition(|p| p == name) {
+                Some(index) => SummaryExpr::Parameter(index),
+                None => SummaryExpr::Literal(self.constants.get(name)?.clone()),
+            },
+            ExpressionKind::Parenthesized(inner) => self.expression(inner, params)?,
+            ExpressionKind::Unary(op, operand) => match op {
+                UnaryOp::Negate | UnaryOp::Not | UnaryOp::Length => {
+                    SummaryExpr::Unary(*op, Box::new(self.expression(operand, params)?))
+                }
+                _ => return None,
+            },
+            ExpressionKind::Binary(op, left, right) => match op {
+                BinaryOp::Add
+                | BinaryOp::Subtract
+                | BinaryOp::Multiply
+                | BinaryOp::Divide
+                | BinaryOp::Modulo
+                | BinaryOp::Power
+                | BinaryOp::Concatenate
+                | BinaryOp::Equal
+                | BinaryOp::NotEqual
+                | BinaryOp::LessThan
+                | BinaryOp::LessThanOrEqual
+                | BinaryOp::GreaterThan
+                | BinaryOp::GreaterThanOrEqual
+                | BinaryOp::And
+                | BinaryOp::Or => SummaryExpr::Binary(
+                    *op,
+                    Box::new(self.expression(left, params)?),
+                    Box::new(self.expression(right, params)?),
+                ),
+                _ => return None,
+            },
+            ExpressionKind::Conditional(cond, then_expr, else_expr) => SummaryExpr::Conditional(
+                Box::new(self.expression(cond, params)?),
+                Box::new(self.expression(then_expr, params)?),
+                Box::new(self.expression(else_expr, params)?),
+            ),
+            ExpressionKind::Call(callee, args, _) => {
+                let ExpressionKind::Member(object, function) = &callee.kind else {
+                    return None;
+                };
+                let ExpressionKind::Identifier(table) = object.kind else {
+                    return None;
+                };
+                let function = self.interner.resolve(function.node);
+                if self.interner.resolve(table) != "math"
+                    || self.shadows_math
+                    || params.contains(&table)
+                    || !PURE_MATH_FUNCTIONS.contains(&&*function)
+                {
+                    return None;
+                }
+                let args = args
+                    .iter()
+                    .map(|arg| {
+                        if arg.is_spread {
+                            None
+                        } else {
+                            self.expression(&arg.value, params)
+                        }
+                    })
+                    .collect::<Option<Vec<_>>>()?;
+                SummaryExpr::MathCall(function.to_string(), args)
+            }
+            _ => return None,
+        };
+        Some(summary)
+    }
+}
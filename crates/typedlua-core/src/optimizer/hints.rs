@@ -0,0 +1,118 @@
+//! Source-level optimization hints
+//!
+//! `@inline`, `@noinline`, `@pure` and `@cold` on methods (or their
+//! `@TypedLua.` spellings) are read once per [`Optimizer::optimize`] run into
+//! a table shared with the passes that respect them. The decorators
+//! themselves are dropped by codegen. A bare `@name` is not a hint when the
+//! module declares or imports its own `name`, matching how codegen resolves
+//! built-in decorators. Function declarations take no decorators yet, so
+//! only methods carry hints.
+//!
+//! [`Optimizer::optimize`]: super::Optimizer::optimize
+
+use crate::codegen::decorators::top_level_bindings;
+use bitflags::bitflags;
+use rustc_hash::{FxHashMap, FxHashSet};
+use std::cell::RefCell;
+use std::rc::Rc;
+use typedlua_parser::ast::statement::{
+    ClassMember, Decorator, DecoratorExpression, ExportKind, Statement,
+};
+use typedlua_parser::string_interner::{StringId, StringInterner};
+
+bitflags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub struct Hints: u8 {
+        /// Inline regardless of the size limit
+        const INLINE = 0b0001;
+        /// Never inline
+        const NOINLINE = 0b0010;
+        /// No side effects; calls whose result is unused may be removed
+        const PURE = 0b0100;
+        /// Rarely run; not worth growing call sites for
+        const COLD = 0b1000;
+    }
+}
+
+impl Hints {
+    /// The hint a decorator spells, if any. `shadowed` holds the module's own
+    /// bindings, which take precedence over the bare hint names.
+    pub fn from_decorator(
+        decorator: &Decorator<'_>,
+        shadowed: &FxHashSet<StringId>,
+        interner: &StringInterner,
+    ) -> Self {
+        let name = match &decorator.expression {
+            DecoratorExpression::Identifier(name) if !shadowed.contains(&name.node) => {
+                interner.resolve(name.node)
+            }
+            DecoratorExpression::Member {
+                object: DecoratorExpression::Identifier(object),
+                property,
+                ..
+            } if interner.resolve(object.node) == "TypedLua" => interner.resolve(property.node),
+            _ => return Hints::empty(),
+        };
+        match &*name {
+            "inline" => Hints::INLINE,
+            "noinline" => Hints::NOINLINE,
+            "pure" => Hints::PURE,
+            "cold" => Hints::COLD,
+            _ => Hints::empty(),
+        }
+    }
+
+    fn from_decorators(
+        decorators: &[Decorator<'_>],
+        shadowed: &FxHashSet<StringId>,
+        interner: &StringInterner,
+    ) -> Self {
+        decorators.iter().fold(Hints::empty(), |hints, d| {
+            hints | Hints::from_decorator(d, shadowed, interner)
+        })
+    }
+}
+
+/// Hints by (class, method), shared between the optimizer and its passes.
+/// Empty unless filled by [`collect`](Self::collect).
+#[derive(Clone, Default)]
+pub struct OptimizationHints {
+    methods: Rc<RefCell<FxHashMap<(StringId, StringId), Hints>>>,
+}
+
+impl OptimizationHints {
+    /// Replace the table with the hints declared in `statements`
+    pub fn collect(&self, statements: &[Statement<'_>], interner: &StringInterner) {
+        let shadowed: FxHashSet<StringId> = top_level_bindings(statements).into_iter().collect();
+        let mut methods = FxHashMap::default();
+        for stmt in statements {
+            let stmt = match stmt {
+                Statement::Export(export) => match &export.kind {
+                    ExportKind::Declaration(decl) => &**decl,
+                    _ => continue,
+                },
+                other => other,
+            };
+            let Statement::Class(class) = stmt else {
+                continue;
+            };
+            for member in class.members.iter() {
+                if let ClassMember::Method(method) = member {
+                    let hints = Hints::from_decorators(method.decorators, &shadowed, interner);
+                    if !hints.is_empty() {
+                        methods.insert((class.name.node, method.name.node), hints);
+                    }
+                }
+            }
+        }
+        *self.methods.borrow_mut() = methods;
+    }
+
+    pub fn method(&self, class: StringId, method: StringId) -> Hints {
+        self.methods
+            .borrow()
+            .get(&(class, method))
+            .copied()
+            .unwrap_or_default()
+    }
+}
@@ -9,6 +9,7 @@
 //! 2. Implementing class is `final` or all subclasses are known and don't override
 //! 3. Method body contains 10 or fewer statements
 //! 4. Method has no `self` mutation (read-only `self`)
+//! 5. Method is not marked `@noinline` or `@cold`; `@inline` lifts the size limit
 //!
 //! This pass works by:
 //! 1. Building a map: Interface -> ImplementingClass[]
@@ -21,7 +22,7 @@ use crate::config::OptimizationLevel;
 use crate::MutableProgram;
 use bumpalo::Bump;
 
-use crate::optimizer::{StmtVisitor, WholeProgramPass};
+use crate::optimizer::{Hints, OptimizationHints, StmtVisitor, WholeProgramPass};
 use rustc_hash::FxHashMap;
 use std::sync::Arc;
 use typedlua_parser::ast::expression::{AssignmentOp, Expression, ExpressionKind};
@@ -296,11 +297,25 @@ impl<'arena> InterfaceImplementationMap<'arena> {
     }
 }
 
-pub struct InterfaceMethodInliningPass;
+#[derive(Default)]
+pub struct InterfaceMethodInliningPass {
+    hints: OptimizationHints,
+}
 
 impl InterfaceMethodInliningPass {
     pub fn new(_interner: Arc<StringInterner>) -> Self {
-        Self
+        Self::default()
+    }
+
+    pub fn with_hints(mut self, hints: OptimizationHints) -> Self {
+        self.hints = hints;
+        self
+    }
+
+    fn may_inline(&self, class: StringId, method: StringId, body_len: usize) -> bool {
+        let hints = self.hints.method(class, method);
+        !hints.intersects(Hints::NOINLINE | Hints::COLD)
+            && (body_len <= MAX_INLINABLE_STATEMENTS || hints.contains(Hints::INLINE))
     }
 
     fn process_statement<'arena>(
@@ -493,9 +508,11 @@ impl InterfaceMethodInliningPass {
                                 if let Some(method_body) =
                                     impl_map.get_method_body(implementing_class, method_id)
                                 {
-                                    if impl_map.count_statement_depth(method_body)
-                                        <= MAX_INLINABLE_STATEMENTS
-                                        && !impl_map.mutates_self(method_body, implementing_class)
+                                    if self.may_inline(
+                                        implementing_class,
+                                        method_id,
+                                        impl_map.count_statement_depth(method_body),
+                                    ) && !impl_map.mutates_self(method_body, implementing_class)
                                     {
                                         if let Some(inlined) = self.inline_interface_method(
                                             &new_obj,
@@ -1053,12 +1070,6 @@ impl<'arena> WholeProgramPass<'arena> for InterfaceMethodInliningPass {
     }
 }
 
-impl Default for InterfaceMethodInliningPass {
-    fn default() -> Self {
-        Self
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -5,15 +5,18 @@
 //! immutable `&'arena` references, so passes use a clone-and-rebuild pattern:
 //! clone sub-expressions to owned values, mutate, then allocate back into the arena.
 
+use crate::codegen::LuaTarget;
 use crate::config::OptimizationLevel;
 use crate::diagnostics::DiagnosticHandler;
+use crate::project_config::OptimizerOptions;
 use crate::MutableProgram;
 
 use bumpalo::Bump;
+use rustc_hash::FxHashMap;
 use std::sync::Arc;
 use tracing::{debug, info};
 use typedlua_parser::ast::expression::{Expression, ExpressionKind};
-use typedlua_parser::ast::statement::{Block, ForStatement, Statement};
+use typedlua_parser::ast::statement::{ForStatement, Statement};
 use typedlua_parser::string_interner::StringInterner;
 
 use bitflags::bitflags;
@@ -170,6 +173,15 @@ impl AstFeatureDetector {
 mod passes;
 use passes::*;
 
+mod visitor;
+pub use visitor::{walk_block, walk_expression, walk_pattern, walk_statement, MutVisitor};
+
+mod remarks;
+pub use remarks::{OptimizationReport, PassTiming, Remark, RemarkKind, RemarkSink};
+
+mod hints;
+pub use hints::{Hints, OptimizationHints};
+
 mod rich_enum_optimization;
 use rich_enum_optimization::RichEnumOptimizationPass;
 
@@ -183,6 +195,18 @@ use devirtualization::DevirtualizationPass;
 mod whole_program_analysis;
 pub use whole_program_analysis::WholeProgramAnalysis;
 
+mod export_summary;
+pub use export_summary::{
+    ExportSummaries, ExportSummary, InlineFunction, ModuleSummary, SummaryExpr,
+};
+
+mod type_facts;
+pub use type_facts::TypeFacts;
+
+mod profile;
+pub use profile::{counter_key, Counter, CounterKind, ModuleProfile, Profile, ProfileCounts};
+pub(crate) use profile::{match_arm_span, SharedProfile};
+
 mod operator_inlining;
 use operator_inlining::OperatorInliningPass;
 
@@ -192,6 +216,35 @@ use interface_inlining::InterfaceMethodInliningPass;
 mod aggressive_inlining;
 use aggressive_inlining::AggressiveInliningPass;
 
+/// Every pass the optimizer can register, by the name used in
+/// [`Optimizer::pass_names`] and the `optimizer.passes` config section
+pub const PASS_NAMES: &[&str] = &[
+    "constant-folding",
+    "constant-propagation",
+    "algebraic-simplification",
+    "operator-inlining",
+    "dead-code-elimination",
+    "dead-store-elimination",
+    "function-inlining",
+    "tail-call-optimization",
+    "method-to-function-conversion",
+    "aggressive-inlining",
+    "interface-method-inlining",
+    "table-preallocation",
+    "string-concat-optimization",
+    "cross-module-inlining",
+    "profile-guided-optimization",
+    "ipairs-specialization",
+    "scalar-replacement",
+    "loop-optimization",
+    "closure-hoisting",
+    "common-subexpression-elimination",
+    "rich-enum-optimization",
+    "devirtualization",
+    "generic-specialization",
+    "global-localization",
+];
+
 // =============================================================================
 // Visitor Traits - Core of the pass merging architecture
 // =============================================================================
@@ -212,6 +265,10 @@ pub trait ExprVisitor<'arena> {
 pub trait StmtVisitor<'arena> {
     fn visit_stmt(&mut self, stmt: &mut Statement<'arena>, arena: &'arena Bump) -> bool;
 
+    /// Called once per run of the composite pass, before any statement is
+    /// visited, by visitors that analyze the whole program first
+    fn prepare(&mut self, _program: &mut MutableProgram<'arena>, _arena: &'arena Bump) {}
+
     fn required_features(&self) -> AstFeatures {
         AstFeatures::EMPTY
     }
@@ -268,18 +325,22 @@ pub trait WholeProgramPass<'arena> {
 
 /// Composite pass that runs multiple expression visitors in one traversal.
 ///
-/// Uses clone-and-rebuild for sub-expression mutation: inner `&'arena` references
-/// are cloned to owned values, mutated, then allocated back into the arena.
+/// The traversal is the shared [`MutVisitor`] walk, so visitors also reach
+/// class members, try/catch blocks, pattern defaults and function bodies
+/// nested inside expressions. Block visitors run on each statement list after its expressions, for
+/// rewrites that depend on neighbouring statements (table preallocation).
 pub struct ExpressionCompositePass<'arena> {
     visitors: Vec<Box<dyn ExprVisitor<'arena> + 'arena>>,
-    _name: &'static str,
+    block_visitors: Vec<Box<dyn BlockVisitor<'arena> + 'arena>>,
+    name: &'static str,
 }
 
 impl<'arena> ExpressionCompositePass<'arena> {
     pub fn new(name: &'static str) -> Self {
         Self {
             visitors: Vec::new(),
-            _name: name,
+            block_visitors: Vec::new(),
+            name,
         }
     }
 
@@ -287,8 +348,16 @@ impl<'arena> ExpressionCompositePass<'arena> {
         self.visitors.push(visitor);
     }
 
+    pub fn add_block_visitor(&mut self, visitor: Box<dyn BlockVisitor<'arena> + 'arena>) {
+        self.block_visitors.push(visitor);
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
     pub fn visitor_count(&self) -> usize {
-        self.visitors.len()
+        self.visitors.len() + self.block_visitors.len()
     }
 
     pub fn required_features(&self) -> AstFeatures {
@@ -296,6 +365,9 @@ impl<'arena> ExpressionCompositePass<'arena> {
         for visitor in &self.visitors {
             combined |= visitor.required_features();
         }
+        for visitor in &self.block_visitors {
+            combined |= visitor.required_features();
+        }
         combined
     }
 
@@ -304,128 +376,25 @@ impl<'arena> ExpressionCompositePass<'arena> {
         program: &mut MutableProgram<'arena>,
         arena: &'arena Bump,
     ) -> Result<bool, String> {
-        let mut changed = false;
-        for stmt in &mut program.statements {
-            changed |= self.visit_stmt(stmt, arena);
-        }
-        Ok(changed)
-    }
-
-    fn visit_stmt(&mut self, stmt: &mut Statement<'arena>, arena: &'arena Bump) -> bool {
-        let mut changed = false;
-
-        match stmt {
-            Statement::Variable(decl) => {
-                changed |= self.visit_expr(&mut decl.initializer, arena);
-            }
-            Statement::Expression(expr) => {
-                changed |= self.visit_expr(expr, arena);
-            }
-            Statement::If(if_stmt) => {
-                changed |= self.visit_expr(&mut if_stmt.condition, arena);
-                changed |= self.visit_block(&mut if_stmt.then_block, arena);
-                let mut new_else_ifs: Vec<_> = if_stmt.else_ifs.to_vec();
-                let mut eic = false;
-                for else_if in &mut new_else_ifs {
-                    eic |= self.visit_expr(&mut else_if.condition, arena);
-                    eic |= self.visit_block(&mut else_if.block, arena);
-                }
-                if eic {
-                    if_stmt.else_ifs = arena.alloc_slice_clone(&new_else_ifs);
-                    changed = true;
-                }
-                if let Some(else_block) = &mut if_stmt.else_block {
-                    changed |= self.visit_block(else_block, arena);
-                }
-            }
-            Statement::While(while_stmt) => {
-                changed |= self.visit_expr(&mut while_stmt.condition, arena);
-                changed |= self.visit_block(&mut while_stmt.body, arena);
-            }
-            Statement::For(for_stmt) => match &**for_stmt {
-                ForStatement::Numeric(for_num_ref) => {
-                    let mut new_num = (**for_num_ref).clone();
-                    let mut fc = false;
-                    fc |= self.visit_expr(&mut new_num.start, arena);
-                    fc |= self.visit_expr(&mut new_num.end, arena);
-                    if let Some(step) = &mut new_num.step {
-                        fc |= self.visit_expr(step, arena);
-                    }
-                    fc |= self.visit_block(&mut new_num.body, arena);
-                    if fc {
-                        *stmt = Statement::For(
-                            arena.alloc(ForStatement::Numeric(arena.alloc(new_num))),
-                        );
-                        changed = true;
-                    }
-                }
-                ForStatement::Generic(for_gen_ref) => {
-                    let mut new_gen = for_gen_ref.clone();
-                    let mut fc = false;
-                    let mut new_iters: Vec<_> = new_gen.iterators.to_vec();
-                    for expr in &mut new_iters {
-                        fc |= self.visit_expr(expr, arena);
-                    }
-                    if fc {
-                        new_gen.iterators = arena.alloc_slice_clone(&new_iters);
-                    }
-                    fc |= self.visit_block(&mut new_gen.body, arena);
-                    if fc {
-                        *stmt = Statement::For(arena.alloc(ForStatement::Generic(new_gen)));
-                        changed = true;
-                    }
-                }
-            },
-            Statement::Repeat(repeat_stmt) => {
-                changed |= self.visit_expr(&mut repeat_stmt.until, arena);
-                changed |= self.visit_block(&mut repeat_stmt.body, arena);
-            }
-            Statement::Return(ret_stmt) => {
-                let mut values: Vec<Expression<'arena>> = ret_stmt.values.to_vec();
-                let mut ret_changed = false;
-                for expr in &mut values {
-                    ret_changed |= self.visit_expr(expr, arena);
-                }
-                if ret_changed {
-                    ret_stmt.values = arena.alloc_slice_clone(&values);
-                    changed = true;
-                }
-            }
-            Statement::Function(func) => {
-                changed |= self.visit_block(&mut func.body, arena);
-            }
-            Statement::Block(block) => {
-                changed |= self.visit_block(block, arena);
-            }
-            _ => {}
-        }
-
-        changed
+        Ok(self.visit_block(&mut program.statements, arena))
     }
+}
 
-    fn visit_block(&mut self, block: &mut Block<'arena>, arena: &'arena Bump) -> bool {
-        let mut stmts: Vec<Statement<'arena>> = block.statements.to_vec();
-        let mut changed = false;
-        for stmt in &mut stmts {
-            changed |= self.visit_stmt(stmt, arena);
-        }
-        if changed {
-            block.statements = arena.alloc_slice_clone(&stmts);
+impl<'arena> MutVisitor<'arena> for ExpressionCompositePass<'arena> {
+    fn visit_block(&mut self, stmts: &mut Vec<Statement<'arena>>, arena: &'arena Bump) -> bool {
+        let mut changed = walk_block(self, stmts, arena);
+        for visitor in &mut self.block_visitors {
+            changed |= visitor.visit_block_stmts(stmts, arena);
         }
         changed
     }
 
-    fn visit_expr(&mut self, expr: &mut Expression<'arena>, arena: &'arena Bump) -> bool {
-        let mut changed = false;
-
-        // First, visit children recursively (clone-and-rebuild for &'arena refs)
-        changed |= visit_expr_children(expr, arena, &mut |e, a| self.visit_expr(e, a));
-
-        // Then, apply all visitors to this expression
+    fn visit_expression(&mut self, expr: &mut Expression<'arena>, arena: &'arena Bump) -> bool {
+        // Children first, so visitors see already-rewritten operands
+        let mut changed = walk_expression(self, expr, arena);
         for visitor in &mut self.visitors {
             changed |= visitor.visit_expr(expr, arena);
         }
-
         changed
     }
 }
@@ -439,7 +408,7 @@ impl<'arena> ExpressionCompositePass<'arena> {
 pub struct StatementCompositePass<'arena> {
     stmt_visitors: Vec<Box<dyn StmtVisitor<'arena> + 'arena>>,
     block_visitors: Vec<Box<dyn BlockVisitor<'arena> + 'arena>>,
-    _name: &'static str,
+    name: &'static str,
 }
 
 impl<'arena> StatementCompositePass<'arena> {
@@ -447,7 +416,7 @@ impl<'arena> StatementCompositePass<'arena> {
         Self {
             stmt_visitors: Vec::new(),
             block_visitors: Vec::new(),
-            _name: name,
+            name,
         }
     }
 
@@ -459,6 +428,10 @@ impl<'arena> StatementCompositePass<'arena> {
         self.block_visitors.push(visitor);
     }
 
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
     pub fn visitor_count(&self) -> usize {
         self.stmt_visitors.len() + self.block_visitors.len()
     }
@@ -479,106 +452,27 @@ impl<'arena> StatementCompositePass<'arena> {
         program: &mut MutableProgram<'arena>,
         arena: &'arena Bump,
     ) -> Result<bool, String> {
-        let mut changed = false;
-
-        // Run per-statement visitors
-        for stmt in &mut program.statements {
-            changed |= self.visit_stmt(stmt, arena);
-        }
-
-        // Run block-level visitors on the top-level statement list
-        for visitor in &mut self.block_visitors {
-            changed |= visitor.visit_block_stmts(&mut program.statements, arena);
-        }
-
-        Ok(changed)
-    }
-
-    fn visit_stmt(&mut self, stmt: &mut Statement<'arena>, arena: &'arena Bump) -> bool {
-        let mut changed = false;
-
-        changed |= self.visit_stmt_children(stmt, arena);
-
         for visitor in &mut self.stmt_visitors {
-            changed |= visitor.visit_stmt(stmt, arena);
+            visitor.prepare(program, arena);
         }
-
-        changed
+        Ok(self.visit_block(&mut program.statements, arena))
     }
+}
 
-    fn visit_stmt_children(&mut self, stmt: &mut Statement<'arena>, arena: &'arena Bump) -> bool {
-        let mut changed = false;
-
-        match stmt {
-            Statement::Function(func) => {
-                changed |= self.visit_block(&mut func.body, arena);
-            }
-            Statement::If(if_stmt) => {
-                changed |= self.visit_block(&mut if_stmt.then_block, arena);
-                let mut new_else_ifs: Vec<_> = if_stmt.else_ifs.to_vec();
-                let mut eic = false;
-                for else_if in &mut new_else_ifs {
-                    eic |= self.visit_block(&mut else_if.block, arena);
-                }
-                if eic {
-                    if_stmt.else_ifs = arena.alloc_slice_clone(&new_else_ifs);
-                    changed = true;
-                }
-                if let Some(else_block) = &mut if_stmt.else_block {
-                    changed |= self.visit_block(else_block, arena);
-                }
-            }
-            Statement::While(while_stmt) => {
-                changed |= self.visit_block(&mut while_stmt.body, arena);
-            }
-            Statement::For(for_stmt) => match &**for_stmt {
-                ForStatement::Numeric(for_num_ref) => {
-                    let mut new_num = (**for_num_ref).clone();
-                    let fc = self.visit_block(&mut new_num.body, arena);
-                    if fc {
-                        *stmt = Statement::For(
-                            arena.alloc(ForStatement::Numeric(arena.alloc(new_num))),
-                        );
-                        changed = true;
-                    }
-                }
-                ForStatement::Generic(for_gen_ref) => {
-                    let mut new_gen = for_gen_ref.clone();
-                    let fc = self.visit_block(&mut new_gen.body, arena);
-                    if fc {
-                        *stmt = Statement::For(arena.alloc(ForStatement::Generic(new_gen)));
-                        changed = true;
-                    }
-                }
-            },
-            Statement::Repeat(repeat_stmt) => {
-                changed |= self.visit_block(&mut repeat_stmt.body, arena);
-            }
-            Statement::Block(block) => {
-                changed |= self.visit_block(block, arena);
-            }
-            _ => {}
+impl<'arena> MutVisitor<'arena> for StatementCompositePass<'arena> {
+    fn visit_block(&mut self, stmts: &mut Vec<Statement<'arena>>, arena: &'arena Bump) -> bool {
+        // Per-statement visitors, then block-level visitors
+        let mut changed = walk_block(self, stmts, arena);
+        for visitor in &mut self.block_visitors {
+            changed |= visitor.visit_block_stmts(stmts, arena);
         }
-
         changed
     }
 
-    fn visit_block(&mut self, block: &mut Block<'arena>, arena: &'arena Bump) -> bool {
-        let mut stmts: Vec<Statement<'arena>> = block.statements.to_vec();
-        let mut changed = false;
-
-        // Per-statement visitors
-        for stmt in &mut stmts {
-            changed |= self.visit_stmt(stmt, arena);
-        }
-
-        // Block-level visitors
-        for visitor in &mut self.block_visitors {
-            changed |= visitor.visit_block_stmts(&mut stmts, arena);
-        }
-
-        if changed {
-            block.statements = arena.alloc_slice_clone(&stmts);
+    fn visit_statement(&mut self, stmt: &mut Statement<'arena>, arena: &'arena Bump) -> bool {
+        let mut changed = walk_statement(self, stmt, arena);
+        for visitor in &mut self.stmt_visitors {
+            changed |= visitor.visit_stmt(stmt, arena);
         }
         changed
     }
@@ -588,7 +482,7 @@ impl<'arena> StatementCompositePass<'arena> {
 pub struct AnalysisCompositePass<'arena> {
     pre_analyzers: Vec<Box<dyn PreAnalysisPass<'arena> + 'arena>>,
     visitors: Vec<Box<dyn StmtVisitor<'arena> + 'arena>>,
-    _name: &'static str,
+    name: &'static str,
 }
 
 impl<'arena> AnalysisCompositePass<'arena> {
@@ -596,7 +490,7 @@ impl<'arena> AnalysisCompositePass<'arena> {
         Self {
             pre_analyzers: Vec::new(),
             visitors: Vec::new(),
-            _name: name,
+            name,
         }
     }
 
@@ -608,6 +502,10 @@ impl<'arena> AnalysisCompositePass<'arena> {
         self.visitors.push(visitor);
     }
 
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
     pub fn visitor_count(&self) -> usize {
         self.visitors.len()
     }
@@ -631,393 +529,22 @@ impl<'arena> AnalysisCompositePass<'arena> {
         for analyzer in &mut self.pre_analyzers {
             analyzer.analyze(program);
         }
-
-        let mut changed = false;
-        for stmt in &mut program.statements {
-            changed |= self.visit_stmt(stmt, arena);
-        }
-        Ok(changed)
-    }
-
-    fn visit_stmt(&mut self, stmt: &mut Statement<'arena>, arena: &'arena Bump) -> bool {
-        let mut changed = false;
-
-        changed |= self.visit_stmt_children(stmt, arena);
-
         for visitor in &mut self.visitors {
-            changed |= visitor.visit_stmt(stmt, arena);
+            visitor.prepare(program, arena);
         }
 
-        changed
-    }
-
-    fn visit_stmt_children(&mut self, stmt: &mut Statement<'arena>, arena: &'arena Bump) -> bool {
-        let mut changed = false;
-
-        match stmt {
-            Statement::Function(func) => {
-                changed |= self.visit_block(&mut func.body, arena);
-            }
-            Statement::If(if_stmt) => {
-                changed |= self.visit_block(&mut if_stmt.then_block, arena);
-                let mut new_else_ifs: Vec<_> = if_stmt.else_ifs.to_vec();
-                let mut eic = false;
-                for else_if in &mut new_else_ifs {
-                    eic |= self.visit_block(&mut else_if.block, arena);
-                }
-                if eic {
-                    if_stmt.else_ifs = arena.alloc_slice_clone(&new_else_ifs);
-                    changed = true;
-                }
-                if let Some(else_block) = &mut if_stmt.else_block {
-                    changed |= self.visit_block(else_block, arena);
-                }
-            }
-            Statement::While(while_stmt) => {
-                changed |= self.visit_block(&mut while_stmt.body, arena);
-            }
-            Statement::For(for_stmt) => match &**for_stmt {
-                ForStatement::Numeric(for_num_ref) => {
-                    let mut new_num = (**for_num_ref).clone();
-                    let fc = self.visit_block(&mut new_num.body, arena);
-                    if fc {
-                        *stmt = Statement::For(
-                            arena.alloc(ForStatement::Numeric(arena.alloc(new_num))),
-                        );
-                        changed = true;
-                    }
-                }
-                ForStatement::Generic(for_gen_ref) => {
-                    let mut new_gen = for_gen_ref.clone();
-                    let fc = self.visit_block(&mut new_gen.body, arena);
-                    if fc {
-                        *stmt = Statement::For(arena.alloc(ForStatement::Generic(new_gen)));
-                        changed = true;
-                    }
-                }
-            },
-            Statement::Repeat(repeat_stmt) => {
-                changed |= self.visit_block(&mut repeat_stmt.body, arena);
-            }
-            Statement::Block(block) => {
-                changed |= self.visit_block(block, arena);
-            }
-            _ => {}
-        }
-
-        changed
-    }
-
-    fn visit_block(&mut self, block: &mut Block<'arena>, arena: &'arena Bump) -> bool {
-        let mut stmts: Vec<Statement<'arena>> = block.statements.to_vec();
-        let mut changed = false;
-        for stmt in &mut stmts {
-            changed |= self.visit_stmt(stmt, arena);
-        }
-        if changed {
-            block.statements = arena.alloc_slice_clone(&stmts);
-        }
-        changed
+        Ok(self.visit_block(&mut program.statements, arena))
     }
 }
 
-// =============================================================================
-// Shared expression child visitor — clone-and-rebuild pattern
-// =============================================================================
-
-/// Visit children of an expression using the clone-and-rebuild pattern.
-/// The `visit_fn` closure handles recursive visitation.
-fn visit_expr_children<'arena>(
-    expr: &mut Expression<'arena>,
-    arena: &'arena Bump,
-    visit_fn: &mut dyn FnMut(&mut Expression<'arena>, &'arena Bump) -> bool,
-) -> bool {
-    use typedlua_parser::ast::expression::{ArrayElement, ObjectProperty};
-
-    let mut changed = false;
-
-    match &expr.kind {
-        ExpressionKind::Binary(op, left, right) => {
-            let op = *op;
-            let mut new_left = (**left).clone();
-            let mut new_right = (**right).clone();
-            let lc = visit_fn(&mut new_left, arena);
-            let rc = visit_fn(&mut new_right, arena);
-            if lc || rc {
-                expr.kind =
-                    ExpressionKind::Binary(op, arena.alloc(new_left), arena.alloc(new_right));
-                changed = true;
-            }
-        }
-        ExpressionKind::Unary(op, operand) => {
-            let op = *op;
-            let mut new_operand = (**operand).clone();
-            if visit_fn(&mut new_operand, arena) {
-                expr.kind = ExpressionKind::Unary(op, arena.alloc(new_operand));
-                changed = true;
-            }
-        }
-        ExpressionKind::Call(func, args, type_args) => {
-            let type_args = *type_args;
-            let mut new_func = (**func).clone();
-            let fc = visit_fn(&mut new_func, arena);
-            let mut new_args: Vec<_> = args.to_vec();
-            let mut ac = false;
-            for arg in &mut new_args {
-                ac |= visit_fn(&mut arg.value, arena);
-            }
-            if fc || ac {
-                expr.kind = ExpressionKind::Call(
-                    arena.alloc(new_func),
-                    arena.alloc_slice_clone(&new_args),
-                    type_args,
-                );
-                changed = true;
-            }
-        }
-        ExpressionKind::MethodCall(obj, method, args, type_args) => {
-            let method = method.clone();
-            let type_args = *type_args;
-            let mut new_obj = (**obj).clone();
-            let oc = visit_fn(&mut new_obj, arena);
-            let mut new_args: Vec<_> = args.to_vec();
-            let mut ac = false;
-            for arg in &mut new_args {
-                ac |= visit_fn(&mut arg.value, arena);
-            }
-            if oc || ac {
-                expr.kind = ExpressionKind::MethodCall(
-                    arena.alloc(new_obj),
-                    method,
-                    arena.alloc_slice_clone(&new_args),
-                    type_args,
-                );
-                changed = true;
-            }
-        }
-        ExpressionKind::Member(obj, member) => {
-            let member = member.clone();
-            let mut new_obj = (**obj).clone();
-            if visit_fn(&mut new_obj, arena) {
-                expr.kind = ExpressionKind::Member(arena.alloc(new_obj), member);
-                changed = true;
-            }
-        }
-        ExpressionKind::Index(obj, index) => {
-            let mut new_obj = (**obj).clone();
-            let mut new_index = (**index).clone();
-            let oc = visit_fn(&mut new_obj, arena);
-            let ic = visit_fn(&mut new_index, arena);
-            if oc || ic {
-                expr.kind = ExpressionKind::Index(arena.alloc(new_obj), arena.alloc(new_index));
-                changed = true;
-            }
-        }
-        ExpressionKind::Conditional(cond, then_expr, else_expr) => {
-            let mut new_cond = (**cond).clone();
-            let mut new_then = (**then_expr).clone();
-            let mut new_else = (**else_expr).clone();
-            let cc = visit_fn(&mut new_cond, arena);
-            let tc = visit_fn(&mut new_then, arena);
-            let ec = visit_fn(&mut new_else, arena);
-            if cc || tc || ec {
-                expr.kind = ExpressionKind::Conditional(
-                    arena.alloc(new_cond),
-                    arena.alloc(new_then),
-                    arena.alloc(new_else),
-                );
-                changed = true;
-            }
-        }
-        ExpressionKind::Array(elements) => {
-            let mut new_elements: Vec<_> = elements.to_vec();
-            let mut ec = false;
-            for elem in &mut new_elements {
-                match elem {
-                    ArrayElement::Expression(e) => ec |= visit_fn(e, arena),
-                    ArrayElement::Spread(e) => ec |= visit_fn(e, arena),
-                }
-            }
-            if ec {
-                expr.kind = ExpressionKind::Array(arena.alloc_slice_clone(&new_elements));
-                changed = true;
-            }
-        }
-        ExpressionKind::Object(props) => {
-            let mut new_props: Vec<_> = props.to_vec();
-            let mut pc = false;
-            for prop in &mut new_props {
-                match prop {
-                    ObjectProperty::Property { key, value, span } => {
-                        let mut new_val = (**value).clone();
-                        if visit_fn(&mut new_val, arena) {
-                            *prop = ObjectProperty::Property {
-                                key: key.clone(),
-                                value: arena.alloc(new_val),
-                                span: *span,
-                            };
-                            pc = true;
-                        }
-                    }
-                    ObjectProperty::Computed { key, value, span } => {
-                        let mut new_key = (**key).clone();
-                        let mut new_val = (**value).clone();
-                        let kc = visit_fn(&mut new_key, arena);
-                        let vc = visit_fn(&mut new_val, arena);
-                        if kc || vc {
-                            *prop = ObjectProperty::Computed {
-                                key: arena.alloc(new_key),
-                                value: arena.alloc(new_val),
-                                span: *span,
-                            };
-                            pc = true;
-                        }
-                    }
-                    ObjectProperty::Spread { value, span } => {
-                        let mut new_val = (**value).clone();
-                        if visit_fn(&mut new_val, arena) {
-                            *prop = ObjectProperty::Spread {
-                                value: arena.alloc(new_val),
-                                span: *span,
-                            };
-                            pc = true;
-                        }
-                    }
-                }
-            }
-            if pc {
-                expr.kind = ExpressionKind::Object(arena.alloc_slice_clone(&new_props));
-                changed = true;
-            }
-        }
-        ExpressionKind::Pipe(left, right) => {
-            let mut new_left = (**left).clone();
-            let mut new_right = (**right).clone();
-            let lc = visit_fn(&mut new_left, arena);
-            let rc = visit_fn(&mut new_right, arena);
-            if lc || rc {
-                expr.kind = ExpressionKind::Pipe(arena.alloc(new_left), arena.alloc(new_right));
-                changed = true;
-            }
-        }
-        ExpressionKind::ErrorChain(left, right) => {
-            let mut new_left = (**left).clone();
-            let mut new_right = (**right).clone();
-            let lc = visit_fn(&mut new_left, arena);
-            let rc = visit_fn(&mut new_right, arena);
-            if lc || rc {
-                expr.kind =
-                    ExpressionKind::ErrorChain(arena.alloc(new_left), arena.alloc(new_right));
-                changed = true;
-            }
-        }
-        ExpressionKind::New(callee, args, type_args) => {
-            let type_args = *type_args;
-            let mut new_callee = (**callee).clone();
-            let cc = visit_fn(&mut new_callee, arena);
-            let mut new_args: Vec<_> = args.to_vec();
-            let mut ac = false;
-            for arg in &mut new_args {
-                ac |= visit_fn(&mut arg.value, arena);
-            }
-            if cc || ac {
-                expr.kind = ExpressionKind::New(
-                    arena.alloc(new_callee),
-                    arena.alloc_slice_clone(&new_args),
-                    type_args,
-                );
-                changed = true;
-            }
-        }
-        ExpressionKind::OptionalMember(obj, member) => {
-            let member = member.clone();
-            let mut new_obj = (**obj).clone();
-            if visit_fn(&mut new_obj, arena) {
-                expr.kind = ExpressionKind::OptionalMember(arena.alloc(new_obj), member);
-                changed = true;
-            }
-        }
-        ExpressionKind::OptionalIndex(obj, index) => {
-            let mut new_obj = (**obj).clone();
-            let mut new_index = (**index).clone();
-            let oc = visit_fn(&mut new_obj, arena);
-            let ic = visit_fn(&mut new_index, arena);
-            if oc || ic {
-                expr.kind =
-                    ExpressionKind::OptionalIndex(arena.alloc(new_obj), arena.alloc(new_index));
-                changed = true;
-            }
-        }
-        ExpressionKind::OptionalCall(obj, args, type_args) => {
-            let type_args = *type_args;
-            let mut new_obj = (**obj).clone();
-            let oc = visit_fn(&mut new_obj, arena);
-            let mut new_args: Vec<_> = args.to_vec();
-            let mut ac = false;
-            for arg in &mut new_args {
-                ac |= visit_fn(&mut arg.value, arena);
-            }
-            if oc || ac {
-                expr.kind = ExpressionKind::OptionalCall(
-                    arena.alloc(new_obj),
-                    arena.alloc_slice_clone(&new_args),
-                    type_args,
-                );
-                changed = true;
-            }
-        }
-        ExpressionKind::OptionalMethodCall(obj, method, args, type_args) => {
-            let method = method.clone();
-            let type_args = *type_args;
-            let mut new_obj = (**obj).clone();
-            let oc = visit_fn(&mut new_obj, arena);
-            let mut new_args: Vec<_> = args.to_vec();
-            let mut ac = false;
-            for arg in &mut new_args {
-                ac |= visit_fn(&mut arg.value, arena);
-            }
-            if oc || ac {
-                expr.kind = ExpressionKind::OptionalMethodCall(
-                    arena.alloc(new_obj),
-                    method,
-                    arena.alloc_slice_clone(&new_args),
-                    type_args,
-                );
-                changed = true;
-            }
-        }
-        ExpressionKind::TypeAssertion(inner, ty) => {
-            let ty = ty.clone();
-            let mut new_inner = (**inner).clone();
-            if visit_fn(&mut new_inner, arena) {
-                expr.kind = ExpressionKind::TypeAssertion(arena.alloc(new_inner), ty);
-                changed = true;
-            }
-        }
-        ExpressionKind::Parenthesized(inner) => {
-            let mut new_inner = (**inner).clone();
-            if visit_fn(&mut new_inner, arena) {
-                expr.kind = ExpressionKind::Parenthesized(arena.alloc(new_inner));
-                changed = true;
-            }
-        }
-        ExpressionKind::Assignment(target, op, value) => {
-            let op = *op;
-            let mut new_target = (**target).clone();
-            let mut new_value = (**value).clone();
-            let tc = visit_fn(&mut new_target, arena);
-            let vc = visit_fn(&mut new_value, arena);
-            if tc || vc {
-                expr.kind =
-                    ExpressionKind::Assignment(arena.alloc(new_target), op, arena.alloc(new_value));
-                changed = true;
-            }
+impl<'arena> MutVisitor<'arena> for AnalysisCompositePass<'arena> {
+    fn visit_statement(&mut self, stmt: &mut Statement<'arena>, arena: &'arena Bump) -> bool {
+        let mut changed = walk_statement(self, stmt, arena);
+        for visitor in &mut self.visitors {
+            changed |= visitor.visit_stmt(stmt, arena);
         }
-        // Leaf nodes — no children to visit
-        _ => {}
+        changed
     }
-
-    changed
 }
 
 // =============================================================================
@@ -1030,6 +557,8 @@ fn visit_expr_children<'arena>(
 /// Passes are organized into composite groups to minimize AST traversals.
 pub struct Optimizer<'arena> {
     level: OptimizationLevel,
+    // Lua version the output runs on, for passes that emit version-specific code
+    target: LuaTarget,
     #[allow(dead_code)]
     handler: Arc<dyn DiagnosticHandler>,
     interner: Arc<StringInterner>,
@@ -1045,6 +574,24 @@ pub struct Optimizer<'arena> {
 
     // Whole-program analysis results (for O3+ cross-module optimizations)
     whole_program_analysis: Option<WholeProgramAnalysis>,
+    // Declared types, trusted only once the program passed type checking
+    type_facts: TypeFacts,
+    // Import sources of this module resolved to whole-program module ids
+    module_imports: FxHashMap<String, String>,
+    // Execution counts of this module from an instrumented run, shared with
+    // the inliners of the function composite
+    profile: SharedProfile,
+
+    // Shared with passes that explain their decisions; disabled until enable_remarks()
+    remarks: RemarkSink,
+    timings: Vec<PassTiming>,
+    iterations: usize,
+
+    // Per-pass overrides and thresholds from the `optimizer:` config section
+    options: OptimizerOptions,
+    // Filled from source decorators at the start of every optimize() run
+    hints: OptimizationHints,
+    pass_names: Vec<&'static str>,
 }
 
 impl<'arena> Optimizer<'arena> {
@@ -1056,6 +603,7 @@ impl<'arena> Optimizer<'arena> {
     ) -> Self {
         let mut optimizer = Self {
             level,
+            target: LuaTarget::default(),
             handler,
             interner,
             expr_pass: None,
@@ -1064,12 +612,48 @@ impl<'arena> Optimizer<'arena> {
             data_pass: None,
             standalone_passes: Vec::new(),
             whole_program_analysis: None,
+            type_facts: TypeFacts::default(),
+            module_imports: FxHashMap::default(),
+            profile: SharedProfile::default(),
+            remarks: RemarkSink::default(),
+            timings: Vec::new(),
+            iterations: 0,
+            options: OptimizerOptions::default(),
+            hints: OptimizationHints::default(),
+            pass_names: Vec::new(),
         };
 
         optimizer.register_passes();
         optimizer
     }
 
+    /// Apply the `optimizer:` config section, re-registering passes
+    pub fn with_options(mut self, options: OptimizerOptions) -> Self {
+        self.options = options;
+        self.expr_pass = None;
+        self.elim_pass = None;
+        self.func_pass = None;
+        self.data_pass = None;
+        self.standalone_passes.clear();
+        self.register_passes();
+        if let Some(analysis) = self.whole_program_analysis.take() {
+            self.set_whole_program_analysis(analysis);
+        }
+        self.set_type_facts(std::mem::take(&mut self.type_facts));
+        self.set_module_imports(std::mem::take(&mut self.module_imports));
+        if let Some(profile) = self.profile.get() {
+            self.set_profile(profile);
+        }
+        self
+    }
+
+    /// Optimize for the given Lua version, re-registering passes
+    pub fn with_target(mut self, target: LuaTarget) -> Self {
+        self.target = target;
+        let options = std::mem::take(&mut self.options);
+        self.with_options(options)
+    }
+
     /// Set whole-program analysis results for cross-module optimizations
     pub fn set_whole_program_analysis(&mut self, analysis: WholeProgramAnalysis) {
         self.whole_program_analysis = Some(analysis.clone());
@@ -1078,166 +662,213 @@ impl<'arena> Optimizer<'arena> {
             if let Some(devirt) = pass.as_any_mut().downcast_mut::<DevirtualizationPass>() {
                 devirt.set_class_hierarchy((*analysis.class_hierarchy).clone());
             }
+            if let Some(inliner) = pass.as_any_mut().downcast_mut::<CrossModuleInliningPass>() {
+                inliner.set_exports(analysis.exports.clone());
+            }
+        }
+    }
+
+    /// Map the import sources of the module being optimized (`"./math"`) to
+    /// the module ids its exports were summarized under in whole-program
+    /// analysis. Sources without an entry are looked up as written.
+    pub fn set_module_imports(&mut self, imports: FxHashMap<String, String>) {
+        for pass in &mut self.standalone_passes {
+            if let Some(inliner) = pass.as_any_mut().downcast_mut::<CrossModuleInliningPass>() {
+                inliner.set_module_imports(imports.clone());
+            }
+        }
+        self.module_imports = imports;
+    }
+
+    /// Set the execution counts recorded for this module by a build with
+    /// `--instrument pgo`, for profile-guided optimization
+    pub fn set_profile(&mut self, profile: Arc<ModuleProfile>) {
+        for pass in &mut self.standalone_passes {
+            if let Some(pgo) = pass.as_any_mut().downcast_mut::<ProfileGuidedPass>() {
+                pgo.set_profile(profile.clone());
+            }
+            if let Some(inliner) = pass.as_any_mut().downcast_mut::<CrossModuleInliningPass>() {
+                inliner.set_profile(profile.clone());
+            }
+        }
+        self.profile.set(profile);
+    }
+
+    /// Set facts from a successful type check for type-directed passes
+    pub fn set_type_facts(&mut self, facts: TypeFacts) {
+        for pass in &mut self.standalone_passes {
+            if let Some(ipairs) = pass.as_any_mut().downcast_mut::<IpairsSpecializationPass>() {
+                ipairs.set_type_facts(facts.clone());
+            }
+        }
+        self.type_facts = facts;
+    }
+
+    /// Record optimization remarks during [`optimize`](Self::optimize), for
+    /// [`take_report`](Self::take_report)
+    pub fn enable_remarks(&self) {
+        self.remarks.enable();
+    }
+
+    /// Remarks and pass timings from the last [`optimize`](Self::optimize) run.
+    /// Remarks are empty unless [`enable_remarks`](Self::enable_remarks) was called.
+    pub fn take_report(&mut self) -> OptimizationReport {
+        OptimizationReport {
+            remarks: self.remarks.take(),
+            timings: std::mem::take(&mut self.timings),
+            iterations: self.iterations,
         }
     }
 
-    /// Register optimization passes based on the optimization level
+    /// Register optimization passes based on the optimization level and the
+    /// per-pass overrides in the optimizer options
     fn register_passes(&mut self) {
+        use OptimizationLevel::{O0, O1, O2, O3};
+
         let interner = self.interner.clone();
-        let level = self.level;
+        let remarks = self.remarks.clone();
+        let hints = self.hints.clone();
+        let profile = self.profile.clone();
+        let options = self.options.clone();
+        let level = self.level.effective();
+        let target = self.target;
+        let mut names = Vec::new();
+        let mut enabled = |name: &'static str, min_level: OptimizationLevel| {
+            debug_assert!(PASS_NAMES.contains(&name), "unlisted pass {}", name);
+            let on = options
+                .passes
+                .get(name)
+                .copied()
+                .unwrap_or(level >= min_level);
+            if on {
+                names.push(name);
+            }
+            on
+        };
 
-        // O1 passes - Expression transformations
-        if level >= OptimizationLevel::O1 {
-            let mut expr_pass = ExpressionCompositePass::new("expression-transforms");
+        // Expression transformations
+        let mut expr_pass = ExpressionCompositePass::new("expression-transforms");
+        if enabled("constant-folding", O1) {
             expr_pass.add_visitor(Box::new(ConstantFoldingPass::new()));
+        }
+        if enabled("algebraic-simplification", O1) {
             expr_pass.add_visitor(Box::new(AlgebraicSimplificationPass::new()));
-            self.expr_pass = Some(expr_pass);
         }
-
-        // O1 passes - Dead code elimination (block-level: truncates after return)
-        if level >= OptimizationLevel::O1 {
-            let mut elim_pass = StatementCompositePass::new("elimination-transforms");
-            elim_pass.add_block_visitor(Box::new(DeadCodeEliminationPass::new()));
-            self.elim_pass = Some(elim_pass);
+        if enabled("operator-inlining", O3) {
+            expr_pass.add_visitor(Box::new(OperatorInliningPass::new(interner.clone())));
         }
 
-        // O2 passes - Elimination and data structure transforms
-        if level >= OptimizationLevel::O2 {
-            // Dead store elimination (block-level: reverse liveness analysis)
-            if let Some(ref mut elim_pass) = self.elim_pass {
-                elim_pass.add_block_visitor(Box::new(DeadStoreEliminationPass::new()));
-            }
-
-            let mut data_pass = ExpressionCompositePass::new("data-structure-transforms");
-            data_pass.add_visitor(Box::new(TablePreallocationPass::new()));
-            data_pass.add_visitor(Box::new(StringConcatOptimizationPass::new(
-                interner.clone(),
-            )));
-            self.data_pass = Some(data_pass);
+        // Block-level elimination: truncation after return, constant branches,
+        // reverse liveness
+        let mut elim_pass = StatementCompositePass::new("elimination-transforms");
+        if enabled("dead-code-elimination", O1) {
+            elim_pass.add_block_visitor(Box::new(
+                DeadCodeEliminationPass::new()
+                    .with_hints(hints.clone())
+                    .with_remarks(remarks.clone()),
+            ));
+        }
+        if enabled("dead-store-elimination", O2) {
+            elim_pass.add_block_visitor(Box::new(DeadStoreEliminationPass::new()));
+        }
 
-            let mut func_pass = AnalysisCompositePass::new("function-transforms");
-            func_pass.add_pre_analyzer(Box::new(FunctionInliningPass::new(interner.clone())));
-            func_pass.add_visitor(Box::new(FunctionInliningPass::new(interner.clone())));
+        let mut func_pass = AnalysisCompositePass::new("function-transforms");
+        if enabled("function-inlining", O2) {
+            func_pass.add_visitor(Box::new(
+                FunctionInliningPass::new(interner.clone())
+                    .with_threshold(options.inline_threshold)
+                    .with_hints(hints.clone())
+                    .with_profile(profile.clone())
+                    .with_remarks(remarks.clone()),
+            ));
+        }
+        if enabled("tail-call-optimization", O2) {
             func_pass.add_visitor(Box::new(TailCallOptimizationPass::new()));
+        }
+        if enabled("method-to-function-conversion", O2) {
             func_pass.add_visitor(Box::new(MethodToFunctionConversionPass::new(
                 interner.clone(),
             )));
-            self.func_pass = Some(func_pass);
-
-            self.standalone_passes
-                .push(Box::new(LoopOptimizationPass::new()));
-            self.standalone_passes
-                .push(Box::new(RichEnumOptimizationPass::new()));
+        }
+        if enabled("aggressive-inlining", O3) {
+            func_pass.add_visitor(Box::new(
+                AggressiveInliningPass::new(interner.clone())
+                    .with_threshold(options.aggressive_inline_threshold)
+                    .with_hints(hints.clone())
+                    .with_profile(profile)
+                    .with_remarks(remarks.clone()),
+            ));
+        }
+        if enabled("interface-method-inlining", O3) {
+            func_pass.add_visitor(Box::new(
+                InterfaceMethodInliningPass::new(interner.clone()).with_hints(hints.clone()),
+            ));
         }
 
-        // O3 passes - Aggressive optimizations
-        if level >= OptimizationLevel::O3 {
-            if let Some(ref mut expr_pass) = self.expr_pass {
-                expr_pass.add_visitor(Box::new(OperatorInliningPass::new(interner.clone())));
-            }
-
-            if let Some(ref mut func_pass) = self.func_pass {
-                func_pass.add_visitor(Box::new(AggressiveInliningPass::new(interner.clone())));
-                func_pass.add_visitor(Box::new(InterfaceMethodInliningPass::new(interner.clone())));
-            }
+        let mut data_pass = ExpressionCompositePass::new("data-structure-transforms");
+        if enabled("table-preallocation", O2) {
+            data_pass.add_block_visitor(Box::new(
+                TablePreallocationPass::new(interner.clone()).with_target(target),
+            ));
+        }
+        if enabled("string-concat-optimization", O2) {
+            data_pass.add_visitor(Box::new(StringConcatOptimizationPass::new(
+                interner.clone(),
+            )));
+        }
 
+        let standalone: [Box<dyn WholeProgramPass<'arena>>; 11] = [
+            Box::new(CrossModuleInliningPass::new(interner.clone()).with_remarks(remarks.clone())),
+            Box::new(
+                ProfileGuidedPass::new(interner.clone())
+                    .with_threshold(options.hot_inline_threshold)
+                    .with_remarks(remarks.clone()),
+            ),
+            Box::new(
+                ConstantPropagationPass::new(interner.clone())
+                    .with_copies(level >= O2)
+                    .with_remarks(remarks.clone()),
+            ),
+            Box::new(IpairsSpecializationPass::new(interner.clone())),
+            Box::new(ScalarReplacementPass::new(interner.clone())),
+            Box::new(LoopOptimizationPass::new().with_remarks(remarks.clone())),
+            Box::new(ClosureHoistingPass::new(interner.clone())),
+            Box::new(
+                CommonSubexpressionEliminationPass::new(interner.clone())
+                    .with_remarks(remarks.clone()),
+            ),
+            Box::new(RichEnumOptimizationPass::new()),
+            Box::new(DevirtualizationPass::new(interner.clone()).with_remarks(remarks.clone())),
+            Box::new(GenericSpecializationPass::new(interner.clone())),
+        ];
+        for pass in standalone {
+            if enabled(pass.name(), pass.min_level()) {
+                self.standalone_passes.push(pass);
+            }
+        }
+
+        // Global localization runs from O1 like every other pass; O0 skips
+        // the optimizer entirely
+        if enabled("global-localization", O1) {
             self.standalone_passes
-                .push(Box::new(DevirtualizationPass::new(interner.clone())));
-            self.standalone_passes
-                .push(Box::new(GenericSpecializationPass::new(interner.clone())));
+                .push(Box::new(GlobalLocalizationPass::new(interner.clone())));
         }
 
-        // Global localization runs at all optimization levels
-        self.standalone_passes
-            .push(Box::new(GlobalLocalizationPass::new(interner.clone())));
+        self.expr_pass = (expr_pass.visitor_count() > 0).then_some(expr_pass);
+        self.elim_pass = (elim_pass.visitor_count() > 0).then_some(elim_pass);
+        self.func_pass = (func_pass.visitor_count() > 0).then_some(func_pass);
+        self.data_pass = (data_pass.visitor_count() > 0).then_some(data_pass);
+        self.pass_names = names;
     }
 
     /// Returns the number of registered passes (counting individual visitors within composites)
     pub fn pass_count(&self) -> usize {
-        let mut count = 0;
-
-        if let Some(ref expr_pass) = self.expr_pass {
-            count += expr_pass.visitor_count();
-        }
-        if let Some(ref elim_pass) = self.elim_pass {
-            count += elim_pass.visitor_count();
-        }
-        if let Some(ref func_pass) = self.func_pass {
-            count += func_pass.visitor_count();
-        }
-        if let Some(ref data_pass) = self.data_pass {
-            count += data_pass.visitor_count();
-        }
-
-        count += self.standalone_passes.len();
-
-        count
+        self.pass_names.len()
     }
 
     /// Returns the names of all registered passes
     pub fn pass_names(&self) -> Vec<&'static str> {
-        let mut names = Vec::new();
-
-        // expr_pass contains: constant-folding, algebraic-simplification, [operator-inlining at O3]
-        if let Some(ref ep) = self.expr_pass {
-            // O1 base passes
-            if ep.visitor_count() >= 1 {
-                names.push("constant-folding");
-            }
-            if ep.visitor_count() >= 2 {
-                names.push("algebraic-simplification");
-            }
-            // O3 adds operator-inlining
-            if ep.visitor_count() >= 3 {
-                names.push("operator-inlining");
-            }
-        }
-
-        // elim_pass contains: dead-code-elimination, dead-store-elimination (block visitors)
-        if let Some(ref elp) = self.elim_pass {
-            if elp.visitor_count() >= 1 {
-                names.push("dead-code-elimination");
-            }
-            if elp.visitor_count() >= 2 {
-                names.push("dead-store-elimination");
-            }
-        }
-
-        // data_pass contains: table-preallocation, string-concat-optimization
-        if let Some(ref dp) = self.data_pass {
-            if dp.visitor_count() >= 1 {
-                names.push("table-preallocation");
-            }
-            if dp.visitor_count() >= 2 {
-                names.push("string-concat-optimization");
-            }
-        }
-
-        // func_pass contains: function-inlining, tail-call-optimization, method-to-function-conversion
-        //   [O3 adds: aggressive-inlining, interface-method-inlining]
-        if let Some(ref fp) = self.func_pass {
-            if fp.visitor_count() >= 1 {
-                names.push("function-inlining");
-            }
-            if fp.visitor_count() >= 2 {
-                names.push("tail-call-optimization");
-            }
-            if fp.visitor_count() >= 3 {
-                names.push("method-to-function-conversion");
-            }
-            if fp.visitor_count() >= 4 {
-                names.push("aggressive-inlining");
-            }
-            if fp.visitor_count() >= 5 {
-                names.push("interface-method-inlining");
-            }
-        }
-
-        for pass in &self.standalone_passes {
-            names.push(pass.name());
-        }
-
-        names
+        self.pass_names.clone()
     }
 
     /// Optimize the program AST.
@@ -1250,6 +881,8 @@ impl<'arena> Optimizer<'arena> {
         use std::time::Instant;
 
         let effective_level = self.level.effective();
+        self.timings.clear();
+        self.iterations = 0;
 
         if effective_level == OptimizationLevel::O0 {
             return Ok(());
@@ -1257,11 +890,13 @@ impl<'arena> Optimizer<'arena> {
 
         let start_total = Instant::now();
 
+        self.hints.collect(&program.statements, &self.interner);
+
         let features = AstFeatureDetector::detect(program);
         debug!("Detected AST features: {:?}", features);
 
         let mut iteration = 0;
-        let max_iterations = 10;
+        let max_iterations = self.options.max_iterations;
 
         loop {
             let mut changed = false;
@@ -1272,85 +907,105 @@ impl<'arena> Optimizer<'arena> {
             }
 
             if let Some(ref mut pass) = self.expr_pass {
-                if effective_level >= OptimizationLevel::O1 {
-                    let required = pass.required_features();
-                    if required.is_empty() || features.contains(required) {
-                        let start = Instant::now();
-                        let pass_changed = pass.run(program, arena)?;
-                        let elapsed = start.elapsed();
-                        debug!(
-                            "  [Iter {}] ExpressionCompositePass: {:?} (changed: {})",
-                            iteration, elapsed, pass_changed
-                        );
-                        changed |= pass_changed;
-                    }
+                let required = pass.required_features();
+                if required.is_empty() || features.contains(required) {
+                    let start = Instant::now();
+                    let pass_changed = pass.run(program, arena)?;
+                    let elapsed = start.elapsed();
+                    debug!(
+                        "  [Iter {}] ExpressionCompositePass: {:?} (changed: {})",
+                        iteration, elapsed, pass_changed
+                    );
+                    self.timings.push(PassTiming {
+                        pass: pass.name(),
+                        iteration,
+                        elapsed,
+                        changed: pass_changed,
+                    });
+                    changed |= pass_changed;
                 }
             }
 
             if let Some(ref mut pass) = self.elim_pass {
-                if effective_level >= OptimizationLevel::O1 {
-                    let required = pass.required_features();
-                    if required.is_empty() || features.contains(required) {
-                        let start = Instant::now();
-                        let pass_changed = pass.run(program, arena)?;
-                        let elapsed = start.elapsed();
-                        debug!(
-                            "  [Iter {}] EliminationCompositePass: {:?} (changed: {})",
-                            iteration, elapsed, pass_changed
-                        );
-                        changed |= pass_changed;
-                    }
+                let required = pass.required_features();
+                if required.is_empty() || features.contains(required) {
+                    let start = Instant::now();
+                    let pass_changed = pass.run(program, arena)?;
+                    let elapsed = start.elapsed();
+                    debug!(
+                        "  [Iter {}] EliminationCompositePass: {:?} (changed: {})",
+                        iteration, elapsed, pass_changed
+                    );
+                    self.timings.push(PassTiming {
+                        pass: pass.name(),
+                        iteration,
+                        elapsed,
+                        changed: pass_changed,
+                    });
+                    changed |= pass_changed;
                 }
             }
 
             if let Some(ref mut pass) = self.func_pass {
-                if effective_level >= OptimizationLevel::O2 {
-                    let required = pass.required_features();
-                    if required.is_empty() || features.contains(required) {
-                        let start = Instant::now();
-                        let pass_changed = pass.run(program, arena)?;
-                        let elapsed = start.elapsed();
-                        debug!(
-                            "  [Iter {}] FunctionCompositePass: {:?} (changed: {})",
-                            iteration, elapsed, pass_changed
-                        );
-                        changed |= pass_changed;
-                    }
+                let required = pass.required_features();
+                if required.is_empty() || features.contains(required) {
+                    let start = Instant::now();
+                    let pass_changed = pass.run(program, arena)?;
+                    let elapsed = start.elapsed();
+                    debug!(
+                        "  [Iter {}] FunctionCompositePass: {:?} (changed: {})",
+                        iteration, elapsed, pass_changed
+                    );
+                    self.timings.push(PassTiming {
+                        pass: pass.name(),
+                        iteration,
+                        elapsed,
+                        changed: pass_changed,
+                    });
+                    changed |= pass_changed;
                 }
             }
 
             if let Some(ref mut pass) = self.data_pass {
-                if effective_level >= OptimizationLevel::O2 {
-                    let required = pass.required_features();
-                    if required.is_empty() || features.contains(required) {
-                        let start = Instant::now();
-                        let pass_changed = pass.run(program, arena)?;
-                        let elapsed = start.elapsed();
-                        debug!(
-                            "  [Iter {}] DataStructureCompositePass: {:?} (changed: {})",
-                            iteration, elapsed, pass_changed
-                        );
-                        changed |= pass_changed;
-                    }
+                let required = pass.required_features();
+                if required.is_empty() || features.contains(required) {
+                    let start = Instant::now();
+                    let pass_changed = pass.run(program, arena)?;
+                    let elapsed = start.elapsed();
+                    debug!(
+                        "  [Iter {}] DataStructureCompositePass: {:?} (changed: {})",
+                        iteration, elapsed, pass_changed
+                    );
+                    self.timings.push(PassTiming {
+                        pass: pass.name(),
+                        iteration,
+                        elapsed,
+                        changed: pass_changed,
+                    });
+                    changed |= pass_changed;
                 }
             }
 
             for pass in &mut self.standalone_passes {
-                if pass.min_level() <= effective_level {
-                    let required = pass.required_features();
-                    if required.is_empty() || features.contains(required) {
-                        let start = Instant::now();
-                        let pass_changed = pass.run(program, arena)?;
-                        let elapsed = start.elapsed();
-                        debug!(
-                            "  [Iter {}] {}: {:?} (changed: {})",
-                            iteration,
-                            pass.name(),
-                            elapsed,
-                            pass_changed
-                        );
-                        changed |= pass_changed;
-                    }
+                let required = pass.required_features();
+                if required.is_empty() || features.contains(required) {
+                    let start = Instant::now();
+                    let pass_changed = pass.run(program, arena)?;
+                    let elapsed = start.elapsed();
+                    debug!(
+                        "  [Iter {}] {}: {:?} (changed: {})",
+                        iteration,
+                        pass.name(),
+                        elapsed,
+                        pass_changed
+                    );
+                    self.timings.push(PassTiming {
+                        pass: pass.name(),
+                        iteration,
+                        elapsed,
+                        changed: pass_changed,
+                    });
+                    changed |= pass_changed;
                 }
             }
 
@@ -1358,6 +1013,7 @@ impl<'arena> Optimizer<'arena> {
                 break;
             }
         }
+        self.iterations = iteration.min(max_iterations);
 
         let total_elapsed = start_total.elapsed();
         info!(
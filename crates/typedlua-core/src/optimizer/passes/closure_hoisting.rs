@@ -0,0 +1,339 @@
+// =============================================================================
+// O2: Closure Hoisting Pass
+// =============================================================================
+
+use super::pattern_binders;
+use crate::config::OptimizationLevel;
+use crate::optimizer::{walk_expression, walk_pattern, walk_statement};
+use crate::optimizer::{MutVisitor, WholeProgramPass};
+use crate::MutableProgram;
+use bumpalo::Bump;
+use rustc_hash::FxHashMap;
+use std::sync::Arc;
+use typedlua_parser::ast::expression::{Expression, ExpressionKind};
+use typedlua_parser::ast::pattern::Pattern;
+use typedlua_parser::ast::statement::{
+    CatchPattern, ForStatement, Statement, VariableDeclaration, VariableKind,
+};
+use typedlua_parser::ast::Spanned;
+use typedlua_parser::string_interner::{StringId, StringInterner};
+
+/// Closure hoisting pass
+/// Moves function expressions and arrows created inside a loop into a
+/// `local __closure_N` in front of the outermost loop of the same function
+/// that binds none of the names they capture, so the closure is allocated
+/// once instead of on every iteration. A closure capturing nothing but
+/// module-level names or globals leaves every loop; one capturing a local
+/// of an outer loop stays inside that loop.
+///
+/// Hoisted closures are shared between iterations, so code comparing closures
+/// by identity sees one function where it used to see many. Closures outside
+/// loops are left alone: a function returning a closure may be a factory
+/// expected to hand out distinct ones.
+pub struct ClosureHoistingPass {
+    next_temp_id: usize,
+    interner: Arc<StringInterner>,
+}
+
+impl ClosureHoistingPass {
+    pub fn new(interner: Arc<StringInterner>) -> Self {
+        Self {
+            next_temp_id: 0,
+            interner,
+        }
+    }
+}
+
+impl<'arena> WholeProgramPass<'arena> for ClosureHoistingPass {
+    fn name(&self) -> &'static str {
+        "closure-hoisting"
+    }
+
+    fn min_level(&self) -> OptimizationLevel {
+        OptimizationLevel::O2
+    }
+
+    fn run(
+        &mut self,
+        program: &mut MutableProgram<'arena>,
+        arena: &'arena Bump,
+    ) -> Result<bool, String> {
+        let mut hoister = Hoister {
+            interner: &self.interner,
+            next_temp_id: &mut self.next_temp_id,
+            scopes: Vec::new(),
+            level: 0,
+            loops: Vec::new(),
+            entering_loop: None,
+            finished_loop: Vec::new(),
+            ready: Vec::new(),
+            block_has_label: Vec::new(),
+            open: Vec::new(),
+            pinned: 0,
+        };
+        Ok(hoister.visit_block(&mut program.statements, arena))
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+/// Where a name was bound
+#[derive(Clone, Copy)]
+struct Binding {
+    /// Function nesting level
+    level: usize,
+    /// Loops of that function enclosing the binding
+    depth: usize,
+}
+
+struct LoopFrame<'arena> {
+    /// A new local before the loop would not sit between a `goto` and its
+    /// label
+    insertable: bool,
+    hoisted: Vec<Statement<'arena>>,
+}
+
+/// A closure being walked
+struct OpenClosure {
+    /// Level of the function the closure is created in
+    outer_level: usize,
+    /// Deepest loop depth among the captured bindings of that function
+    needed: usize,
+}
+
+struct Hoister<'p, 'arena> {
+    interner: &'p StringInterner,
+    next_temp_id: &'p mut usize,
+    scopes: Vec<FxHashMap<StringId, Binding>>,
+    level: usize,
+    /// Loops of the current function, outermost first
+    loops: Vec<LoopFrame<'arena>>,
+    /// Set by a loop statement for its body block: loop variables and
+    /// whether the loop's own block allows inserting locals
+    entering_loop: Option<(Vec<StringId>, bool)>,
+    /// Declarations hoisted in front of the loop whose body just ended
+    finished_loop: Vec<Statement<'arena>>,
+    /// Declarations to insert before the statement just visited
+    ready: Vec<Statement<'arena>>,
+    block_has_label: Vec<bool>,
+    open: Vec<OpenClosure>,
+    /// Inside a `repeat ... until` condition, which sees the body's locals
+    /// after the body's scope was left
+    pinned: usize,
+}
+
+impl<'arena> Hoister<'_, 'arena> {
+    fn declare(&mut self, name: StringId) {
+        let binding = Binding {
+            level: self.level,
+            depth: self.loops.len(),
+        };
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name, binding);
+        }
+    }
+
+    fn reference(&mut self, name: StringId) {
+        let Some(binding) = self
+            .scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(&name).copied())
+        else {
+            return;
+        };
+        for closure in &mut self.open {
+            if binding.level == closure.outer_level {
+                closure.needed = closure.needed.max(binding.depth);
+            }
+        }
+    }
+
+    /// Walks a function body, which starts with no enclosing loops
+    fn function<R>(&mut self, walk: impl FnOnce(&mut Self) -> R) -> R {
+        let loops = std::mem::take(&mut self.loops);
+        let entering_loop = self.entering_loop.take();
+        self.scopes.push(FxHashMap::default());
+        self.level += 1;
+        let result = walk(self);
+        self.level -= 1;
+        self.scopes.pop();
+        self.entering_loop = entering_loop;
+        self.loops = loops;
+        result
+    }
+
+    /// Walks a loop statement, leaving the declarations hoisted in front of
+    /// it in `ready`
+    fn loop_statement<R>(
+        &mut self,
+        variables: Vec<StringId>,
+        walk: impl FnOnce(&mut Self) -> R,
+    ) -> R {
+        let outer_finished = std::mem::take(&mut self.finished_loop);
+        let insertable = !self.block_has_label.last().copied().unwrap_or(false);
+        self.entering_loop = Some((variables, insertable));
+        let result = walk(self);
+        self.entering_loop = None;
+        self.ready = std::mem::replace(&mut self.finished_loop, outer_finished);
+        result
+    }
+
+    /// Replaces the closure in `expr` with a local in front of the outermost
+    /// loop it may leave
+    fn hoist(&mut self, expr: &mut Expression<'arena>, needed: usize) -> bool {
+        if self.pinned > 0 {
+            return false;
+        }
+        let Some(target) = (needed..self.loops.len()).find(|&i| self.loops[i].insertable) else {
+            return false;
+        };
+        let name = format!("__closure_{}", *self.next_temp_id);
+        *self.next_temp_id += 1;
+        let name = self.interner.get_or_intern(&name);
+        let span = expr.span;
+        let closure = std::mem::replace(
+            expr,
+            Expression::new(ExpressionKind::Identifier(name), span),
+        );
+        self.loops[target]
+            .hoisted
+            .push(Statement::Variable(VariableDeclaration {
+                kind: VariableKind::Local,
+                pattern: Pattern::Identifier(Spanned::new(name, span)),
+                type_annotation: None,
+                initializer: closure,
+                span,
+            }));
+        true
+    }
+}
+
+impl<'arena> MutVisitor<'arena> for Hoister<'_, 'arena> {
+    fn visit_block(&mut self, stmts: &mut Vec<Statement<'arena>>, arena: &'arena Bump) -> bool {
+        let entering_loop = self.entering_loop.take();
+        self.scopes.push(FxHashMap::default());
+        if let Some((variables, insertable)) = entering_loop.as_ref() {
+            self.loops.push(LoopFrame {
+                insertable: *insertable,
+                hoisted: Vec::new(),
+            });
+            for var in variables {
+                self.declare(*var);
+            }
+        }
+        self.block_has_label
+            .push(stmts.iter().any(|stmt| matches!(stmt, Statement::Label(_))));
+
+        let mut changed = false;
+        let mut i = 0;
+        while i < stmts.len() {
+            changed |= self.visit_statement(&mut stmts[i], arena);
+            for decl in std::mem::take(&mut self.ready) {
+                stmts.insert(i, decl);
+                i += 1;
+                changed = true;
+            }
+            i += 1;
+        }
+
+        self.block_has_label.pop();
+        if entering_loop.is_some() {
+            if let Some(frame) = self.loops.pop() {
+                self.finished_loop = frame.hoisted;
+            }
+        }
+        self.scopes.pop();
+        changed
+    }
+
+    fn visit_statement(&mut self, stmt: &mut Statement<'arena>, arena: &'arena Bump) -> bool {
+        match stmt {
+            Statement::Function(func) => {
+                self.declare(func.name.node);
+                self.function(|s| walk_statement(s, stmt, arena))
+            }
+            Statement::Class(class) => {
+                self.declare(class.name.node);
+                self.function(|s| walk_statement(s, stmt, arena))
+            }
+            Statement::Enum(decl) => {
+                self.declare(decl.name.node);
+                self.function(|s| walk_statement(s, stmt, arena))
+            }
+            Statement::While(_) => {
+                self.loop_statement(Vec::new(), |s| walk_statement(s, stmt, arena))
+            }
+            Statement::For(for_stmt) => {
+                let variables = match &**for_stmt {
+                    ForStatement::Numeric(for_num) => vec![for_num.variable.node],
+                    ForStatement::Generic(for_gen) => {
+                        let mut names: Vec<_> =
+                            for_gen.variables.iter().map(|var| var.node).collect();
+                        if let Some(pattern) = &for_gen.pattern {
+                            pattern_binders(pattern, &mut names);
+                        }
+                        names
+                    }
+                };
+                self.loop_statement(variables, |s| walk_statement(s, stmt, arena))
+            }
+            Statement::Repeat(repeat_stmt) => self.loop_statement(Vec::new(), |s| {
+                let mut body = repeat_stmt.body.statements.to_vec();
+                let mut changed = s.visit_block(&mut body, arena);
+                if changed {
+                    repeat_stmt.body.statements = arena.alloc_slice_clone(&body);
+                }
+                s.pinned += 1;
+                changed |= s.visit_expression(&mut repeat_stmt.until, arena);
+                s.pinned -= 1;
+                changed
+            }),
+            Statement::Try(try_stmt) => {
+                for clause in try_stmt.catch_clauses.iter() {
+                    match &clause.pattern {
+                        CatchPattern::Untyped { variable, .. }
+                        | CatchPattern::Typed { variable, .. }
+                        | CatchPattern::MultiTyped { variable, .. } => self.declare(variable.node),
+                    }
+                }
+                walk_statement(self, stmt, arena)
+            }
+            _ => walk_statement(self, stmt, arena),
+        }
+    }
+
+    fn visit_expression(&mut self, expr: &mut Expression<'arena>, arena: &'arena Bump) -> bool {
+        match &expr.kind {
+            ExpressionKind::Identifier(name) => {
+                self.reference(*name);
+                false
+            }
+            ExpressionKind::Function(_) | ExpressionKind::Arrow(_) => {
+                self.open.push(OpenClosure {
+                    outer_level: self.level,
+                    needed: 0,
+                });
+                let changed = self.function(|s| walk_expression(s, expr, arena));
+                let needed = self.open.pop().map_or(0, |closure| closure.needed);
+                self.hoist(expr, needed) || changed
+            }
+            ExpressionKind::Try(try_expr) => {
+                self.declare(try_expr.catch_variable.node);
+                walk_expression(self, expr, arena)
+            }
+            _ => walk_expression(self, expr, arena),
+        }
+    }
+
+    fn visit_pattern(&mut self, pattern: &mut Pattern<'arena>, arena: &'arena Bump) -> bool {
+        let mut names = Vec::new();
+        pattern_binders(pattern, &mut names);
+        for name in names {
+            self.declare(name);
+        }
+        walk_pattern(self, pattern, arena)
+    }
+}
@@ -0,0 +1,1174 @@
+// =============================================================================
+// O2: Common Subexpression Elimination Pass
+// =============================================================================
+
+use super::constant_propagation::BindingCensus;
+use crate::config::OptimizationLevel;
+use crate::optimizer::{walk_block, walk_expression, walk_statement};
+use crate::optimizer::{MutVisitor, RemarkSink, WholeProgramPass};
+use crate::MutableProgram;
+use bumpalo::Bump;
+use rustc_hash::{FxHashMap, FxHashSet};
+use std::sync::Arc;
+use typedlua_parser::ast::expression::{
+    Argument, ArrayElement, BinaryOp, Expression, ExpressionKind, Literal, ObjectProperty,
+    TemplatePart, UnaryOp,
+};
+use typedlua_parser::ast::pattern::Pattern;
+use typedlua_parser::ast::statement::{
+    ClassMember, ExportKind, ForNumeric, ForStatement, Statement, VariableDeclaration, VariableKind,
+};
+use typedlua_parser::ast::Spanned;
+use typedlua_parser::string_interner::{StringId, StringInterner};
+
+/// `math` functions that neither read nor write program state
+const PURE_MATH_FUNCTIONS: &[&str] = &[
+    "abs", "acos", "asin", "atan", "ceil", "cos", "deg", "exp", "floor", "fmod", "log", "max",
+    "min", "rad", "sin", "sqrt", "tan",
+];
+
+/// Global iterator factories whose loops run no program code per iteration
+const PURE_ITERATORS: &[&str] = &["ipairs", "pairs"];
+
+const PASS: &str = "common-subexpression-elimination";
+
+/// Bounds the rewrites of one block (or one loop) per run
+const MAX_REWRITES_PER_BLOCK: usize = 32;
+
+/// Common subexpression elimination pass
+/// Caches repeated pure expressions (field reads such as `self.config.width`,
+/// `#t`, arithmetic on locals) in fresh `__cse_N` locals, both within a block
+/// and across loop iterations when the expression is loop-invariant.
+///
+/// Field reads and arithmetic are treated as pure loads, so modules that
+/// declare operator overloads are left alone, and table reads are not cached
+/// in modules that declare getters or set up metatables of their own, where
+/// `__index` or `__len` may run program code. A call kills every cached value
+/// except for the `math` library and `ipairs`/`pairs`; a field write kills
+/// reads of that field name (through any table, since locals may alias) and
+/// every indexed read; an indexed write kills all reads.
+///
+/// A loop-invariant table read is only hoisted when the loop is known to
+/// reach it on its first iteration, since reading a field of `nil` raises.
+pub struct CommonSubexpressionEliminationPass {
+    next_temp_id: usize,
+    interner: Arc<StringInterner>,
+    remarks: RemarkSink,
+}
+
+impl CommonSubexpressionEliminationPass {
+    pub fn new(interner: Arc<StringInterner>) -> Self {
+        Self {
+            next_temp_id: 0,
+            interner,
+            remarks: RemarkSink::default(),
+        }
+    }
+
+    pub fn with_remarks(mut self, remarks: RemarkSink) -> Self {
+        self.remarks = remarks;
+        self
+    }
+
+    /// Library functions the program does not rebind
+    fn pure_callees(&self, census: &BindingCensus) -> PureCallees {
+        let math = self.interner.get_or_intern("math");
+        let intern_all = |names: &[&str]| -> FxHashSet<StringId> {
+            names
+                .iter()
+                .map(|name| self.interner.get_or_intern(name))
+                .filter(|name| !census.binds(*name))
+                .collect()
+        };
+        PureCallees {
+            math: (!census.binds(math)).then_some(math),
+            math_functions: intern_all(PURE_MATH_FUNCTIONS),
+            iterators: intern_all(PURE_ITERATORS),
+        }
+    }
+}
+
+impl<'arena> WholeProgramPass<'arena> for CommonSubexpressionEliminationPass {
+    fn name(&self) -> &'static str {
+        PASS
+    }
+
+    fn min_level(&self) -> OptimizationLevel {
+        OptimizationLevel::O2
+    }
+
+    fn run(
+        &mut self,
+        program: &mut MutableProgram<'arena>,
+        arena: &'arena Bump,
+    ) -> Result<bool, String> {
+        let mut metamethods = MetamethodFinder::new(&self.interner);
+        metamethods.visit_block(&mut program.statements, arena);
+        if metamethods.overloads {
+            return Ok(false);
+        }
+
+        let mut census = BindingCensus::default();
+        census.visit_block(&mut program.statements, arena);
+
+        let mut rewriter = Rewriter {
+            pure: self.pure_callees(&census),
+            cache_loads: !metamethods.loads,
+            interner: &self.interner,
+            remarks: &self.remarks,
+            next_temp_id: &mut self.next_temp_id,
+        };
+        Ok(rewriter.visit_block(&mut program.statements, arena))
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+// -----------------------------------------------------------------------------
+// Expression keys
+// -----------------------------------------------------------------------------
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Arith {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    Power,
+    IntegerDivide,
+}
+
+impl Arith {
+    fn from_op(op: BinaryOp) -> Option<Self> {
+        match op {
+            BinaryOp::Add => Some(Self::Add),
+            BinaryOp::Subtract => Some(Self::Subtract),
+            BinaryOp::Multiply => Some(Self::Multiply),
+            BinaryOp::Divide => Some(Self::Divide),
+            BinaryOp::Modulo => Some(Self::Modulo),
+            BinaryOp::Power => Some(Self::Power),
+            BinaryOp::IntegerDivide => Some(Self::IntegerDivide),
+            _ => None,
+        }
+    }
+}
+
+/// Structural identity of a pure expression
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Key {
+    Name(StringId),
+    SelfRef,
+    Number(u64),
+    Integer(i64),
+    Str(String),
+    Bool(bool),
+    Member(Box<Key>, StringId),
+    Index(Box<Key>, Box<Key>),
+    Length(Box<Key>),
+    Arith(Arith, Box<Key>, Box<Key>),
+}
+
+impl Key {
+    fn literal(literal: &Literal) -> Option<Key> {
+        match literal {
+            Literal::Number(n) => Some(Key::Number(n.to_bits())),
+            Literal::Integer(i) => Some(Key::Integer(*i)),
+            Literal::String(s) => Some(Key::Str(s.clone())),
+            Literal::Boolean(b) => Some(Key::Bool(*b)),
+            Literal::Nil => None,
+        }
+    }
+
+    fn of(expr: &Expression<'_>) -> Option<Key> {
+        Some(match &expr.kind {
+            ExpressionKind::Identifier(name) => Key::Name(*name),
+            ExpressionKind::SelfKeyword => Key::SelfRef,
+            ExpressionKind::Literal(literal) => Key::literal(literal)?,
+            ExpressionKind::Parenthesized(inner) | ExpressionKind::TypeAssertion(inner, _) => {
+                Key::of(inner)?
+            }
+            ExpressionKind::Member(obj, field) => Key::Member(Box::new(Key::of(obj)?), field.node),
+            ExpressionKind::Index(obj, index) => {
+                Key::Index(Box::new(Key::of(obj)?), Box::new(Key::of(index)?))
+            }
+            ExpressionKind::Unary(UnaryOp::Length, inner) => Key::Length(Box::new(Key::of(inner)?)),
+            ExpressionKind::Binary(op, left, right) => Key::Arith(
+                Arith::from_op(*op)?,
+                Box::new(Key::of(left)?),
+                Box::new(Key::of(right)?),
+            ),
+            _ => return None,
+        })
+    }
+
+    /// Worth caching: an operation on at least one name
+    fn is_candidate(&self) -> bool {
+        matches!(
+            self,
+            Key::Member(..) | Key::Index(..) | Key::Length(_) | Key::Arith(..)
+        ) && self.info().rooted
+    }
+
+    fn info(&self) -> KeyInfo {
+        let mut info = KeyInfo::default();
+        self.collect(&mut info);
+        info
+    }
+
+    fn collect(&self, info: &mut KeyInfo) {
+        info.size += 1;
+        match self {
+            Key::Name(name) => {
+                info.names.push(*name);
+                info.rooted = true;
+            }
+            Key::SelfRef => info.rooted = true,
+            Key::Number(_) | Key::Integer(_) | Key::Str(_) | Key::Bool(_) => {}
+            Key::Member(obj, field) => {
+                info.fields.push(*field);
+                info.memory = true;
+                obj.collect(info);
+            }
+            Key::Index(obj, index) => {
+                info.indexed = true;
+                info.memory = true;
+                obj.collect(info);
+                index.collect(info);
+            }
+            Key::Length(inner) => {
+                info.memory = true;
+                inner.collect(info);
+            }
+            Key::Arith(op, left, right) => {
+                info.may_raise |= matches!(op, Arith::Modulo | Arith::IntegerDivide);
+                left.collect(info);
+                right.collect(info);
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct KeyInfo {
+    names: Vec<StringId>,
+    fields: Vec<StringId>,
+    /// Reads through a computed index
+    indexed: bool,
+    /// Reads a table (field, index or length)
+    memory: bool,
+    /// Integer division or modulo, which raise on a zero divisor and so are
+    /// never evaluated speculatively
+    may_raise: bool,
+    rooted: bool,
+    size: usize,
+}
+
+/// Something that invalidates cached values
+#[derive(Clone, Copy)]
+enum Kill {
+    /// Assignment to or redeclaration of a name
+    Local(StringId),
+    /// `t.field = v`
+    Field(StringId),
+    /// `t[k] = v`
+    Memory,
+    /// Arbitrary code
+    Call,
+}
+
+impl Kill {
+    fn kills(self, info: &KeyInfo) -> bool {
+        match self {
+            Kill::Local(name) => info.names.contains(&name),
+            Kill::Field(field) => info.fields.contains(&field) || info.indexed,
+            Kill::Memory => info.memory,
+            Kill::Call => true,
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
+// Scanning: uses and kills in evaluation order
+// -----------------------------------------------------------------------------
+
+enum Event<'arena> {
+    Use {
+        key: Key,
+        expr: Expression<'arena>,
+        /// Under `and`/`or`/`?:` or an optional chain
+        conditional: bool,
+        /// Blocks (and loop conditions) of the scanned statement enclosing it
+        depth: usize,
+        /// Evaluated whenever the scanned statement runs: no loop that may
+        /// run zero times and no `break`, `return` or the like comes first
+        reached: bool,
+    },
+    Kill(Kill),
+}
+
+struct PureCallees {
+    math: Option<StringId>,
+    math_functions: FxHashSet<StringId>,
+    iterators: FxHashSet<StringId>,
+}
+
+impl PureCallees {
+    fn contains(&self, callee: &Expression<'_>) -> bool {
+        match &callee.kind {
+            ExpressionKind::Identifier(name) => self.iterators.contains(name),
+            ExpressionKind::Member(obj, function) => {
+                matches!(obj.kind, ExpressionKind::Identifier(base) if Some(base) == self.math)
+                    && self.math_functions.contains(&function.node)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Records candidate uses and kills of one statement. Closures, `match` arms
+/// and `try` expressions are opaque: they record no uses, and their effects
+/// are covered by [`Kill::Call`].
+struct Scanner<'s, 'arena> {
+    pure: &'s PureCallees,
+    /// Record table reads as candidates
+    cache_loads: bool,
+    events: Vec<Event<'arena>>,
+    conditional: usize,
+    depth: usize,
+    reached: bool,
+}
+
+impl<'s, 'arena> Scanner<'s, 'arena> {
+    fn scan(
+        pure: &'s PureCallees,
+        cache_loads: bool,
+        stmt: &Statement<'arena>,
+    ) -> Vec<Event<'arena>> {
+        let mut scanner = Scanner {
+            pure,
+            cache_loads,
+            events: Vec::new(),
+            conditional: 0,
+            depth: 0,
+            reached: true,
+        };
+        scanner.statement(stmt);
+        scanner.events
+    }
+
+    fn kill(&mut self, kill: Kill) {
+        self.events.push(Event::Kill(kill));
+    }
+
+    fn conditionally(&mut self, f: impl FnOnce(&mut Self)) {
+        self.conditional += 1;
+        f(self);
+        self.conditional -= 1;
+    }
+
+    fn nested(&mut self, f: impl FnOnce(&mut Self)) {
+        self.depth += 1;
+        f(self);
+        self.depth -= 1;
+    }
+
+    fn block(&mut self, stmts: &[Statement<'arena>]) {
+        for stmt in stmts {
+            self.statement(stmt);
+        }
+    }
+
+    fn statement(&mut self, stmt: &Statement<'arena>) {
+        match stmt {
+            Statement::Variable(decl) => {
+                self.expr(&decl.initializer);
+                match &decl.pattern {
+                    Pattern::Identifier(ident) => self.kill(Kill::Local(ident.node)),
+                    // Destructuring defaults and reads
+                    _ => self.kill(Kill::Call),
+                }
+            }
+            Statement::Expression(expr) => {
+                self.expr(expr);
+            }
+            Statement::Return(ret) => {
+                for value in ret.values.iter() {
+                    self.expr(value);
+                }
+                self.reached = false;
+            }
+            Statement::Throw(throw_stmt) => {
+                self.expr(&throw_stmt.expression);
+                self.reached = false;
+            }
+            Statement::If(if_stmt) => {
+                self.expr(&if_stmt.condition);
+                self.nested(|s| {
+                    s.block(if_stmt.then_block.statements);
+                    for else_if in if_stmt.else_ifs.iter() {
+                        s.expr(&else_if.condition);
+                        s.block(else_if.block.statements);
+                    }
+                    if let Some(else_block) = &if_stmt.else_block {
+                        s.block(else_block.statements);
+                    }
+                });
+            }
+            // Twice, so a kill in one iteration precedes the reads of the next
+            Statement::While(while_stmt) => self.nested(|s| {
+                let runs = matches!(
+                    while_stmt.condition.kind,
+                    ExpressionKind::Literal(Literal::Boolean(true))
+                );
+                for _ in 0..2 {
+                    s.expr(&while_stmt.condition);
+                    s.reached &= runs;
+                    s.block(while_stmt.body.statements);
+                }
+            }),
+            Statement::Repeat(repeat_stmt) => self.nested(|s| {
+                for _ in 0..2 {
+                    s.block(repeat_stmt.body.statements);
+                    s.expr(&repeat_stmt.until);
+                }
+            }),
+            Statement::For(for_stmt) => match &**for_stmt {
+                ForStatement::Numeric(for_num) => {
+                    self.expr(&for_num.start);
+                    self.expr(&for_num.end);
+                    if let Some(step) = &for_num.step {
+                        self.expr(step);
+                    }
+                    self.nested(|s| {
+                        s.reached &= runs_at_least_once(for_num);
+                        for _ in 0..2 {
+                            s.kill(Kill::Local(for_num.variable.node));
+                            s.block(for_num.body.statements);
+                        }
+                    });
+                }
+                ForStatement::Generic(for_gen) => {
+                    for iterator in for_gen.iterators.iter() {
+                        self.expr(iterator);
+                    }
+                    let pure_iteration = matches!(
+                        &*for_gen.iterators,
+                        [Expression { kind: ExpressionKind::Call(callee, _, _), .. }]
+                            if self.pure.contains(callee)
+                    );
+                    self.nested(|s| {
+                        s.reached = false;
+                        for _ in 0..2 {
+                            if !pure_iteration {
+                                s.kill(Kill::Call);
+                            }
+                            for variable in for_gen.variables.iter() {
+                                s.kill(Kill::Local(variable.node));
+                            }
+                            s.block(for_gen.body.statements);
+                        }
+                    });
+                }
+            },
+            Statement::Function(func) => self.kill(Kill::Local(func.name.node)),
+            Statement::Block(block) => self.nested(|s| s.block(block.statements)),
+            Statement::Export(export) => match &export.kind {
+                ExportKind::Declaration(decl) => self.statement(decl),
+                ExportKind::Default(expr) => {
+                    self.expr(expr);
+                }
+                ExportKind::Named { .. } => {}
+            },
+            // Class and enum bodies run setup code, imports run modules, and
+            // labels make the block's control flow non-linear
+            Statement::Class(_)
+            | Statement::Enum(_)
+            | Statement::Import(_)
+            | Statement::Try(_)
+            | Statement::Label(_) => self.kill(Kill::Call),
+            Statement::Goto(_) => {
+                self.kill(Kill::Call);
+                self.reached = false;
+            }
+            Statement::Break(_) | Statement::Continue(_) | Statement::Rethrow(_) => {
+                self.reached = false;
+            }
+            Statement::Interface(_)
+            | Statement::TypeAlias(_)
+            | Statement::Namespace(_)
+            | Statement::DeclareFunction(_)
+            | Statement::DeclareNamespace(_)
+            | Statement::DeclareType(_)
+            | Statement::DeclareInterface(_)
+            | Statement::DeclareConst(_) => {}
+        }
+    }
+
+    fn arguments(&mut self, args: &[Argument<'arena>]) {
+        for arg in args {
+            self.expr(&arg.value);
+        }
+    }
+
+    fn call(&mut self, callee: &Expression<'arena>, args: &[Argument<'arena>]) {
+        if self.pure.contains(callee) {
+            self.arguments(args);
+        } else {
+            self.expr(callee);
+            self.arguments(args);
+            self.kill(Kill::Call);
+        }
+    }
+
+    fn assignment(&mut self, target: &Expression<'arena>, value: &Expression<'arena>) {
+        match &target.kind {
+            ExpressionKind::Identifier(name) => {
+                self.expr(value);
+                self.kill(Kill::Local(*name));
+            }
+            ExpressionKind::Member(obj, field) => {
+                self.expr(obj);
+                self.expr(value);
+                self.kill(Kill::Field(field.node));
+            }
+            ExpressionKind::Index(obj, index) => {
+                self.expr(obj);
+                self.expr(index);
+                self.expr(value);
+                self.kill(Kill::Memory);
+            }
+            // Destructuring assignment
+            _ => {
+                self.expr(value);
+                self.kill(Kill::Call);
+            }
+        }
+    }
+
+    /// Scans an expression, returning its key
+    fn expr(&mut self, expr: &Expression<'arena>) -> Option<Key> {
+        let key = match &expr.kind {
+            ExpressionKind::Identifier(name) => Some(Key::Name(*name)),
+            ExpressionKind::SelfKeyword => Some(Key::SelfRef),
+            ExpressionKind::Literal(literal) => Key::literal(literal),
+            ExpressionKind::SuperKeyword => None,
+            // Transparent: the wrapper and its operand are one use
+            ExpressionKind::Parenthesized(inner) | ExpressionKind::TypeAssertion(inner, _) => {
+                return self.expr(inner)
+            }
+            ExpressionKind::Member(obj, field) => self
+                .expr(obj)
+                .map(|obj| Key::Member(Box::new(obj), field.node)),
+            ExpressionKind::Index(obj, index) => {
+                let obj = self.expr(obj);
+                let index = self.expr(index);
+                Some(Key::Index(Box::new(obj?), Box::new(index?)))
+            }
+            ExpressionKind::Unary(op, inner) => {
+                let inner = self.expr(inner);
+                match op {
+                    UnaryOp::Length => inner.map(|inner| Key::Length(Box::new(inner))),
+                    _ => None,
+                }
+            }
+            ExpressionKind::Binary(
+                BinaryOp::And | BinaryOp::Or | BinaryOp::NullCoalesce,
+                left,
+                right,
+            ) => {
+                self.expr(left);
+                self.conditionally(|s| {
+                    s.expr(right);
+                });
+                None
+            }
+            ExpressionKind::Binary(op, left, right) => {
+                let left = self.expr(left);
+                let right = self.expr(right);
+                Some(Key::Arith(
+                    Arith::from_op(*op)?,
+                    Box::new(left?),
+                    Box::new(right?),
+                ))
+            }
+            ExpressionKind::Conditional(cond, then_expr, else_expr) => {
+                self.expr(cond);
+                self.conditionally(|s| {
+                    s.expr(then_expr);
+                    s.expr(else_expr);
+                });
+                None
+            }
+            ExpressionKind::Call(callee, args, _) | ExpressionKind::New(callee, args, _) => {
+                self.call(callee, args);
+                None
+            }
+            ExpressionKind::MethodCall(obj, _, args, _) => {
+                self.expr(obj);
+                self.arguments(args);
+                self.kill(Kill::Call);
+                None
+            }
+            ExpressionKind::OptionalMember(obj, _) => {
+                self.expr(obj);
+                None
+            }
+            ExpressionKind::OptionalIndex(obj, index) => {
+                self.expr(obj);
+                self.conditionally(|s| {
+                    s.expr(index);
+                });
+                None
+            }
+            ExpressionKind::OptionalCall(obj, args, _)
+            | ExpressionKind::OptionalMethodCall(obj, _, args, _) => {
+                self.expr(obj);
+                self.conditionally(|s| s.arguments(args));
+                self.kill(Kill::Call);
+                None
+            }
+            ExpressionKind::Assignment(target, _, value) => {
+                self.assignment(target, value);
+                None
+            }
+            ExpressionKind::Array(elements) => {
+                for element in elements.iter() {
+                    match element {
+                        ArrayElement::Expression(e) | ArrayElement::Spread(e) => {
+                            self.expr(e);
+                        }
+                    }
+                }
+                None
+            }
+            ExpressionKind::Object(props) => {
+                for prop in props.iter() {
+                    match prop {
+                        ObjectProperty::Property { value, .. }
+                        | ObjectProperty::Spread { value, .. } => {
+                            self.expr(value);
+                        }
+                        ObjectProperty::Computed { key, value, .. } => {
+                            self.expr(key);
+                            self.expr(value);
+                        }
+                    }
+                }
+                None
+            }
+            ExpressionKind::Template(template) => {
+                for part in template.parts.iter() {
+                    if let TemplatePart::Expression(e) = part {
+                        self.expr(e);
+                    }
+                }
+                None
+            }
+            ExpressionKind::Pipe(left, right) => {
+                self.expr(left);
+                self.expr(right);
+                self.kill(Kill::Call);
+                None
+            }
+            ExpressionKind::ErrorChain(left, _) => {
+                self.expr(left);
+                self.kill(Kill::Call);
+                None
+            }
+            // Creating a closure runs nothing
+            ExpressionKind::Function(_) | ExpressionKind::Arrow(_) => None,
+            ExpressionKind::Match(match_expr) => {
+                self.expr(match_expr.value);
+                self.kill(Kill::Call);
+                None
+            }
+            ExpressionKind::Try(_) => {
+                self.kill(Kill::Call);
+                None
+            }
+        };
+        let cache_loads = self.cache_loads;
+        let candidate = |key: &&Key| key.is_candidate() && (cache_loads || !key.info().memory);
+        if let Some(key) = key.as_ref().filter(candidate) {
+            self.events.push(Event::Use {
+                key: key.clone(),
+                expr: expr.clone(),
+                conditional: self.conditional > 0,
+                depth: self.depth,
+                reached: self.reached,
+            });
+        }
+        key
+    }
+}
+
+// -----------------------------------------------------------------------------
+// Rewriting
+// -----------------------------------------------------------------------------
+
+/// Statements `start..=end` of a block that read `key` `uses` times with
+/// nothing in between that could change it
+struct Run<'arena> {
+    key: Key,
+    exemplar: Expression<'arena>,
+    start: usize,
+    end: usize,
+    uses: usize,
+    size: usize,
+}
+
+impl Run<'_> {
+    /// Larger expressions first, then more reads, then the earliest
+    fn beats(&self, other: &Run<'_>) -> bool {
+        (self.size, self.uses, std::cmp::Reverse(self.start))
+            > (other.size, other.uses, std::cmp::Reverse(other.start))
+    }
+}
+
+/// How one statement reads one key
+struct StatementUse<'e, 'arena> {
+    /// Reads outside nested blocks, which a run may replace
+    uses: usize,
+    /// The first read, when evaluating it ahead of the statement is safe
+    start: Option<&'e Expression<'arena>>,
+    /// Read again after something in the statement changed it
+    unclean: bool,
+}
+
+struct Rewriter<'p> {
+    pure: PureCallees,
+    cache_loads: bool,
+    interner: &'p StringInterner,
+    remarks: &'p RemarkSink,
+    next_temp_id: &'p mut usize,
+}
+
+impl Rewriter<'_> {
+    /// `local __cse_N = <expr>`, returning the new name
+    fn declare<'arena>(&mut self, expr: Expression<'arena>) -> (StringId, Statement<'arena>) {
+        let name = format!("__cse_{}", *self.next_temp_id);
+        *self.next_temp_id += 1;
+        let name = self.interner.get_or_intern(&name);
+        let span = expr.span;
+        let decl = Statement::Variable(VariableDeclaration {
+            kind: VariableKind::Local,
+            pattern: Pattern::Identifier(Spanned::new(name, span)),
+            type_annotation: None,
+            initializer: expr,
+            span,
+        });
+        (name, decl)
+    }
+
+    /// Hoists invariant expressions out of the loops directly in `stmts`
+    fn hoist_invariants<'arena>(
+        &mut self,
+        stmts: &mut Vec<Statement<'arena>>,
+        arena: &'arena Bump,
+    ) -> bool {
+        let mut changed = false;
+        let mut i = 0;
+        while i < stmts.len() {
+            if matches!(
+                stmts[i],
+                Statement::While(_) | Statement::Repeat(_) | Statement::For(_)
+            ) {
+                for _ in 0..MAX_REWRITES_PER_BLOCK {
+                    let Some(expr) = self.invariant(&stmts[i]) else {
+                        break;
+                    };
+                    let key = Key::of(&expr);
+                    let span = expr.span;
+                    let (name, decl) = self.declare(expr);
+                    let mut replacer = Replacer {
+                        key: key.as_ref(),
+                        name,
+                        deep: true,
+                    };
+                    if !replacer.visit_statement(&mut stmts[i], arena) {
+                        break;
+                    }
+                    self.remarks.applied(PASS, span, || {
+                        format!(
+                            "hoisted loop-invariant expression into `{}`",
+                            self.interner.resolve(name)
+                        )
+                    });
+                    stmts.insert(i, decl);
+                    i += 1;
+                    changed = true;
+                }
+            }
+            i += 1;
+        }
+        changed
+    }
+
+    /// The largest expression read on every iteration of `loop_stmt` that
+    /// nothing in the loop can change. Hoisting evaluates it even when the
+    /// loop never gets to it. That is safe for arithmetic the type checker
+    /// accepted, but not for `//` and `%`, which raise on zero, nor for table
+    /// reads, which raise on `nil`; those are only hoisted from where the
+    /// first iteration is sure to read them.
+    fn invariant<'arena>(&self, loop_stmt: &Statement<'arena>) -> Option<Expression<'arena>> {
+        let events = Scanner::scan(&self.pure, self.cache_loads, loop_stmt);
+        let kills: Vec<Kill> = events
+            .iter()
+            .filter_map(|event| match event {
+                Event::Kill(kill) => Some(*kill),
+                Event::Use { .. } => None,
+            })
+            .collect();
+        if kills.iter().any(|kill| matches!(kill, Kill::Call)) {
+            return None;
+        }
+
+        let mut best: Option<(usize, &Expression<'arena>)> = None;
+        for event in &events {
+            // Only reads directly in the body (or the loop condition) run
+            // on every iteration; header expressions run once anyway
+            let Event::Use {
+                key,
+                expr,
+                conditional: false,
+                depth: 1,
+                reached,
+            } = event
+            else {
+                continue;
+            };
+            let info = key.info();
+            if info.may_raise
+                || (info.memory && !reached)
+                || kills.iter().any(|kill| kill.kills(&info))
+            {
+                continue;
+            }
+            if !matches!(best, Some((size, _)) if size >= info.size) {
+                best = Some((info.size, expr));
+            }
+        }
+        best.map(|(_, expr)| expr.clone())
+    }
+
+    /// Caches expressions read more than once across the statements of
+    /// `stmts`
+    fn eliminate_common<'arena>(
+        &mut self,
+        stmts: &mut Vec<Statement<'arena>>,
+        arena: &'arena Bump,
+    ) -> bool {
+        let mut changed = false;
+        for _ in 0..MAX_REWRITES_PER_BLOCK {
+            let Some(run) = self.best_run(stmts) else {
+                break;
+            };
+            let span = run.exemplar.span;
+            let (name, decl) = self.declare(run.exemplar);
+            let mut replacer = Replacer {
+                key: Some(&run.key),
+                name,
+                deep: false,
+            };
+            let mut replaced = false;
+            for stmt in &mut stmts[run.start..=run.end] {
+                replaced |= replacer.visit_statement(stmt, arena);
+            }
+            if !replaced {
+                break;
+            }
+            self.remarks.applied(PASS, span, || {
+                format!(
+                    "cached expression read {} times in `{}`",
+                    run.uses,
+                    self.interner.resolve(name)
+                )
+            });
+            stmts.insert(run.start, decl);
+            changed = true;
+        }
+        changed
+    }
+
+    /// The most valuable run in `stmts`. A run starts at an unconditional
+    /// read that comes before any effect in its statement, so evaluating it
+    /// just ahead of that statement changes nothing, and ends at the last
+    /// read before a kill.
+    fn best_run<'arena>(&self, stmts: &[Statement<'arena>]) -> Option<Run<'arena>> {
+        let mut open: FxHashMap<Key, Run<'arena>> = FxHashMap::default();
+        let mut best: Option<Run<'arena>> = None;
+        let mut close = |run: Run<'arena>| match &best {
+            Some(current) if !run.beats(current) => {}
+            _ if run.uses < 2 => {}
+            _ => best = Some(run),
+        };
+
+        for (i, stmt) in stmts.iter().enumerate() {
+            let events = Scanner::scan(&self.pure, self.cache_loads, stmt);
+            let mut kills: Vec<Kill> = Vec::new();
+            let mut reads: FxHashMap<&Key, StatementUse<'_, 'arena>> = FxHashMap::default();
+            for event in &events {
+                match event {
+                    Event::Use {
+                        key,
+                        expr,
+                        conditional,
+                        depth,
+                        ..
+                    } => {
+                        let read = reads.entry(key).or_insert_with(|| StatementUse {
+                            uses: 0,
+                            start: (!conditional && *depth == 0 && kills.is_empty())
+                                .then_some(expr),
+                            unclean: false,
+                        });
+                        if *depth == 0 {
+                            read.uses += 1;
+                        }
+                        if !kills.is_empty() {
+                            let info = key.info();
+                            read.unclean |= kills.iter().any(|kill| kill.kills(&info));
+                        }
+                    }
+                    Event::Kill(kill) => kills.push(*kill),
+                }
+            }
+            let killed = |key: &Key| {
+                let info = key.info();
+                kills.iter().any(|kill| kill.kills(&info))
+            };
+
+            // Extend or close the open runs
+            let continued: FxHashSet<Key> = open.keys().cloned().collect();
+            for key in continued.iter().cloned() {
+                let read = reads.get(&key);
+                if read.is_some_and(|read| read.unclean) {
+                    close(open.remove(&key).unwrap());
+                    continue;
+                }
+                let run = open.get_mut(&key).unwrap();
+                if let Some(read) = read.filter(|read| read.uses > 0) {
+                    run.uses += read.uses;
+                    run.end = i;
+                }
+                if killed(&key) {
+                    close(open.remove(&key).unwrap());
+                }
+            }
+
+            // Start runs at this statement
+            for (key, read) in reads {
+                let Some(exemplar) = read.start else {
+                    continue;
+                };
+                if continued.contains(key) || read.unclean {
+                    continue;
+                }
+                let run = Run {
+                    key: key.clone(),
+                    exemplar: exemplar.clone(),
+                    start: i,
+                    end: i,
+                    uses: read.uses,
+                    size: key.info().size,
+                };
+                if killed(key) {
+                    close(run);
+                } else {
+                    open.insert(key.clone(), run);
+                }
+            }
+        }
+        for run in open.into_values() {
+            close(run);
+        }
+        best
+    }
+}
+
+impl<'arena> MutVisitor<'arena> for Rewriter<'_> {
+    /// Inner blocks first, so invariants climb out of nested loops one level
+    /// per enclosing block
+    fn visit_block(&mut self, stmts: &mut Vec<Statement<'arena>>, arena: &'arena Bump) -> bool {
+        let mut changed = walk_block(self, stmts, arena);
+        // A new local between a `goto` and its label is a Lua compile error
+        if stmts.iter().any(|stmt| matches!(stmt, Statement::Label(_))) {
+            return changed;
+        }
+        changed |= self.hoist_invariants(stmts, arena);
+        changed |= self.eliminate_common(stmts, arena);
+        changed
+    }
+}
+
+/// Replaces reads of `key` with the local `name`. Closures are skipped since
+/// they may run after the cached value changed; so are `match` and `try`
+/// expressions, which the scanner treats as opaque. Without `deep`, only the
+/// reads a run counted are replaced: none inside nested blocks or in
+/// re-evaluated loop conditions.
+struct Replacer<'k> {
+    key: Option<&'k Key>,
+    name: StringId,
+    deep: bool,
+}
+
+impl<'arena> MutVisitor<'arena> for Replacer<'_> {
+    fn visit_block(&mut self, stmts: &mut Vec<Statement<'arena>>, arena: &'arena Bump) -> bool {
+        self.deep && walk_block(self, stmts, arena)
+    }
+
+    fn visit_statement(&mut self, stmt: &mut Statement<'arena>, arena: &'arena Bump) -> bool {
+        match stmt {
+            Statement::Function(_)
+            | Statement::Class(_)
+            | Statement::Enum(_)
+            | Statement::Try(_) => false,
+            Statement::If(if_stmt) if !self.deep => {
+                self.visit_expression(&mut if_stmt.condition, arena)
+            }
+            Statement::While(_) | Statement::Repeat(_) if !self.deep => false,
+            _ => walk_statement(self, stmt, arena),
+        }
+    }
+
+    fn visit_expression(&mut self, expr: &mut Expression<'arena>, arena: &'arena Bump) -> bool {
+        let replaceable = matches!(
+            expr.kind,
+            ExpressionKind::Member(..)
+                | ExpressionKind::Index(..)
+                | ExpressionKind::Unary(..)
+                | ExpressionKind::Binary(..)
+                | ExpressionKind::Parenthesized(_)
+                | ExpressionKind::TypeAssertion(..)
+        );
+        if replaceable && Key::of(expr).as_ref() == self.key {
+            expr.kind = ExpressionKind::Identifier(self.name);
+            return true;
+        }
+        match &mut expr.kind {
+            ExpressionKind::Function(_)
+            | ExpressionKind::Arrow(_)
+            | ExpressionKind::Match(_)
+            | ExpressionKind::Try(_) => false,
+            // Only the table (and key) of a target are reads
+            ExpressionKind::Assignment(target, _, value) => {
+                let mut target_expr = (**target).clone();
+                let mut changed = match &mut target_expr.kind {
+                    ExpressionKind::Member(obj, _) => self.child(obj, arena),
+                    ExpressionKind::Index(obj, index) => {
+                        self.child(obj, arena) | self.child(index, arena)
+                    }
+                    _ => false,
+                };
+                if changed {
+                    *target = arena.alloc(target_expr);
+                }
+                changed |= self.child(value, arena);
+                changed
+            }
+            _ => walk_expression(self, expr, arena),
+        }
+    }
+
+    fn visit_pattern(&mut self, _pattern: &mut Pattern<'arena>, _arena: &'arena Bump) -> bool {
+        false
+    }
+}
+
+impl Replacer<'_> {
+    fn child<'arena>(
+        &mut self,
+        expr: &mut &'arena Expression<'arena>,
+        arena: &'arena Bump,
+    ) -> bool {
+        let mut owned = (**expr).clone();
+        let changed = self.visit_expression(&mut owned, arena);
+        if changed {
+            *expr = arena.alloc(owned);
+        }
+        changed
+    }
+}
+
+/// Whether a numeric `for` with literal bounds runs its body at least once
+fn runs_at_least_once(for_num: &ForNumeric<'_>) -> bool {
+    let number = |expr: &Expression<'_>| match &expr.kind {
+        ExpressionKind::Literal(Literal::Integer(i)) => Some(*i as f64),
+        ExpressionKind::Literal(Literal::Number(n)) => Some(*n),
+        _ => None,
+    };
+    let (Some(start), Some(end)) = (number(&for_num.start), number(&for_num.end)) else {
+        return false;
+    };
+    match for_num.step.as_ref().map(number) {
+        None => start <= end,
+        Some(Some(step)) if step > 0.0 => start <= end,
+        Some(Some(step)) if step < 0.0 => start >= end,
+        _ => false,
+    }
+}
+
+/// Finds what makes pure-looking expressions run program code: class
+/// operator overloads for arithmetic, and getters or metatables the module
+/// sets up itself (`setmetatable`, `__index` or `__len` keys) for table reads
+struct MetamethodFinder {
+    setmetatable: StringId,
+    metamethods: [StringId; 2],
+    overloads: bool,
+    loads: bool,
+}
+
+impl MetamethodFinder {
+    fn new(interner: &StringInterner) -> Self {
+        Self {
+            setmetatable: interner.get_or_intern("setmetatable"),
+            metamethods: [
+                interner.get_or_intern("__index"),
+                interner.get_or_intern("__len"),
+            ],
+            overloads: false,
+            loads: false,
+        }
+    }
+}
+
+impl<'arena> MutVisitor<'arena> for MetamethodFinder {
+    fn visit_statement(&mut self, stmt: &mut Statement<'arena>, arena: &'arena Bump) -> bool {
+        if let Statement::Class(class) = stmt {
+            for member in class.members.iter() {
+                match member {
+                    ClassMember::Operator(_) => self.overloads = true,
+                    ClassMember::Getter(_) => self.loads = true,
+                    _ => {}
+                }
+            }
+        }
+        !self.overloads && walk_statement(self, stmt, arena)
+    }
+
+    fn visit_expression(&mut self, expr: &mut Expression<'arena>, arena: &'arena Bump) -> bool {
+        match &expr.kind {
+            ExpressionKind::Call(callee, _, _) => {
+                let setmetatable = self.setmetatable;
+                self.loads |= matches!(
+                    callee.kind,
+                    ExpressionKind::Identifier(name) if name == setmetatable
+                );
+            }
+            ExpressionKind::Member(_, field) => {
+                self.loads |= self.metamethods.contains(&field.node);
+            }
+            ExpressionKind::Object(props) => {
+                self.loads |= props.iter().any(|prop| {
+                    matches!(prop, ObjectProperty::Property { key, .. }
+                        if self.metamethods.contains(&key.node))
+                });
+            }
+            _ => {}
+        }
+        walk_expression(self, expr, arena)
+    }
+}
@@ -1,5 +1,5 @@
 use crate::config::OptimizationLevel;
-use crate::optimizer::{ExprVisitor, WholeProgramPass};
+use crate::optimizer::{walk_expression, ExprVisitor, MutVisitor, WholeProgramPass};
 use crate::MutableProgram;
 use bumpalo::Bump;
 use typedlua_parser::ast::expression::{BinaryOp, Expression, ExpressionKind, Literal, UnaryOp};
@@ -32,13 +32,7 @@ impl<'arena> WholeProgramPass<'arena> for ConstantFoldingPass {
         program: &mut MutableProgram<'arena>,
         arena: &'arena Bump,
     ) -> Result<bool, String> {
-        let mut changed = false;
-
-        for stmt in &mut program.statements {
-            changed |= self.fold_statement(stmt, arena);
-        }
-
-        Ok(changed)
+        Ok(self.visit_block(&mut program.statements, arena))
     }
 
     fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
@@ -46,106 +40,14 @@ impl<'arena> WholeProgramPass<'arena> for ConstantFoldingPass {
     }
 }
 
-impl ConstantFoldingPass {
-    fn fold_statement<'arena>(
-        &mut self,
-        stmt: &mut typedlua_parser::ast::statement::Statement<'arena>,
-        arena: &'arena Bump,
-    ) -> bool {
-        use typedlua_parser::ast::statement::{ForStatement, Statement};
-
-        match stmt {
-            Statement::Variable(decl) => self.fold_expression(&mut decl.initializer, arena),
-            Statement::Expression(expr) => self.fold_expression(expr, arena),
-            Statement::If(if_stmt) => {
-                let mut changed = self.fold_expression(&mut if_stmt.condition, arena);
-                changed |= self.fold_block(&mut if_stmt.then_block, arena);
-                let mut new_else_ifs: Vec<_> = if_stmt.else_ifs.to_vec();
-                let mut eic = false;
-                for else_if in &mut new_else_ifs {
-                    eic |= self.fold_expression(&mut else_if.condition, arena);
-                    eic |= self.fold_block(&mut else_if.block, arena);
-                }
-                if eic {
-                    if_stmt.else_ifs = arena.alloc_slice_clone(&new_else_ifs);
-                    changed = true;
-                }
-                if let Some(else_block) = &mut if_stmt.else_block {
-                    changed |= self.fold_block(else_block, arena);
-                }
-                changed
-            }
-            Statement::While(while_stmt) => {
-                let mut changed = self.fold_expression(&mut while_stmt.condition, arena);
-                changed |= self.fold_block(&mut while_stmt.body, arena);
-                changed
-            }
-            Statement::For(for_stmt) => match &**for_stmt {
-                ForStatement::Numeric(for_num_ref) => {
-                    let mut new_num = (**for_num_ref).clone();
-                    let mut changed = self.fold_expression(&mut new_num.start, arena);
-                    changed |= self.fold_expression(&mut new_num.end, arena);
-                    if let Some(step) = &mut new_num.step {
-                        changed |= self.fold_expression(step, arena);
-                    }
-                    changed |= self.fold_block(&mut new_num.body, arena);
-                    if changed {
-                        *stmt = Statement::For(
-                            arena.alloc(ForStatement::Numeric(arena.alloc(new_num))),
-                        );
-                    }
-                    changed
-                }
-                ForStatement::Generic(for_gen_ref) => {
-                    let mut new_gen = for_gen_ref.clone();
-                    let mut iters: Vec<_> = new_gen.iterators.to_vec();
-                    let mut changed = false;
-                    for expr in &mut iters {
-                        changed |= self.fold_expression(expr, arena);
-                    }
-                    if changed {
-                        new_gen.iterators = arena.alloc_slice_clone(&iters);
-                    }
-                    changed |= self.fold_block(&mut new_gen.body, arena);
-                    if changed {
-                        *stmt = Statement::For(arena.alloc(ForStatement::Generic(new_gen)));
-                    }
-                    changed
-                }
-            },
-            Statement::Return(ret_stmt) => {
-                let mut vals: Vec<_> = ret_stmt.values.to_vec();
-                let mut changed = false;
-                for expr in &mut vals {
-                    changed |= self.fold_expression(expr, arena);
-                }
-                if changed {
-                    ret_stmt.values = arena.alloc_slice_clone(&vals);
-                }
-                changed
-            }
-            Statement::Function(func) => self.fold_block(&mut func.body, arena),
-            Statement::Class(_) => false,
-            _ => false,
-        }
-    }
-
-    fn fold_block<'arena>(
-        &mut self,
-        block: &mut typedlua_parser::ast::statement::Block<'arena>,
-        arena: &'arena Bump,
-    ) -> bool {
-        let mut stmts: Vec<_> = block.statements.to_vec();
-        let mut changed = false;
-        for stmt in &mut stmts {
-            changed |= self.fold_statement(stmt, arena);
-        }
-        if changed {
-            block.statements = arena.alloc_slice_clone(&stmts);
-        }
-        changed
+impl<'arena> MutVisitor<'arena> for ConstantFoldingPass {
+    fn visit_expression(&mut self, expr: &mut Expression<'arena>, arena: &'arena Bump) -> bool {
+        let changed = walk_expression(self, expr, arena);
+        self.fold_expression(expr, arena) || changed
     }
+}
 
+impl ConstantFoldingPass {
     fn fold_expression<'arena>(
         &mut self,
         expr: &mut Expression<'arena>,
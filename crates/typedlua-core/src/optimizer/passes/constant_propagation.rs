@@ -0,0 +1,430 @@
+// =============================================================================
+// O1: Constant and Copy Propagation Pass
+// =============================================================================
+
+use crate::config::OptimizationLevel;
+use crate::optimizer::{walk_block, walk_expression, walk_pattern, walk_statement};
+use crate::optimizer::{MutVisitor, RemarkSink, WholeProgramPass};
+use crate::MutableProgram;
+use bumpalo::Bump;
+use rustc_hash::{FxHashMap, FxHashSet};
+use std::sync::Arc;
+use typedlua_parser::ast::expression::{
+    ArrayElement, Expression, ExpressionKind, Literal, ObjectProperty,
+};
+use typedlua_parser::ast::pattern::{ArrayPatternElement, Pattern};
+use typedlua_parser::ast::statement::{CatchPattern, ForStatement, ImportClause, Statement};
+use typedlua_parser::string_interner::{StringId, StringInterner};
+
+/// Longer string literals stay behind their local rather than being copied
+/// to every use
+const MAX_PROPAGATED_STRING_LEN: usize = 32;
+
+/// What a use of a single-assignment local can be replaced with
+#[derive(Clone)]
+enum Value<'arena> {
+    /// A literal initializer
+    Constant(ExpressionKind<'arena>),
+    /// `local a = b` where `b` is itself never reassigned; identified by the
+    /// binding `b` resolved to at the declaration
+    Copy(StringId, usize),
+}
+
+struct Binding<'arena> {
+    id: usize,
+    /// Declared once by a plain `local`/`const` and never assigned
+    stable: bool,
+    value: Option<Value<'arena>>,
+}
+
+/// Constant and copy propagation pass
+/// Replaces uses of `local`/`const` bindings that are never reassigned with
+/// their literal value (and, when copies are enabled, with the local they
+/// copy), leaving constant folding, dead code and dead store elimination to
+/// clean up after it
+pub struct ConstantPropagationPass {
+    copies: bool,
+    interner: Arc<StringInterner>,
+    remarks: RemarkSink,
+}
+
+impl ConstantPropagationPass {
+    pub fn new(interner: Arc<StringInterner>) -> Self {
+        Self {
+            copies: false,
+            interner,
+            remarks: RemarkSink::default(),
+        }
+    }
+
+    pub fn with_remarks(mut self, remarks: RemarkSink) -> Self {
+        self.remarks = remarks;
+        self
+    }
+
+    /// Also propagate copies between locals (O2)
+    pub fn with_copies(mut self, copies: bool) -> Self {
+        self.copies = copies;
+        self
+    }
+}
+
+impl<'arena> WholeProgramPass<'arena> for ConstantPropagationPass {
+    fn name(&self) -> &'static str {
+        "constant-propagation"
+    }
+
+    fn min_level(&self) -> OptimizationLevel {
+        OptimizationLevel::O1
+    }
+
+    fn run(
+        &mut self,
+        program: &mut MutableProgram<'arena>,
+        arena: &'arena Bump,
+    ) -> Result<bool, String> {
+        let mut census = BindingCensus::default();
+        census.visit_block(&mut program.statements, arena);
+
+        let mut propagator = Propagator {
+            interner: &self.interner,
+            remarks: &self.remarks,
+            copies: self.copies,
+            candidates: census.candidates(),
+            scopes: Vec::new(),
+            next_id: 0,
+            prefix: false,
+        };
+        Ok(propagator.visit_block(&mut program.statements, arena))
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+/// Scoped substitution walk over the whole program
+struct Propagator<'p, 'arena> {
+    interner: &'p StringInterner,
+    remarks: &'p RemarkSink,
+    copies: bool,
+    /// Names bound only by plain declarations and never assigned anywhere
+    candidates: FxHashSet<StringId>,
+    /// Innermost scope last
+    scopes: Vec<FxHashMap<StringId, Binding<'arena>>>,
+    next_id: usize,
+    /// The next expression visited is the object of a member access, call or
+    /// assignment, where a literal cannot stand in for a name
+    prefix: bool,
+}
+
+impl<'arena> Propagator<'_, 'arena> {
+    fn declare(&mut self, name: StringId, value: Option<Value<'arena>>) {
+        let binding = Binding {
+            id: self.next_id,
+            stable: self.candidates.contains(&name),
+            value,
+        };
+        self.next_id += 1;
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name, binding);
+        }
+    }
+
+    fn resolve(&self, name: StringId) -> Option<&Binding<'arena>> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(&name))
+    }
+
+    /// The value a declaration's (already propagated) initializer gives its name
+    fn value_of(&self, initializer: &Expression<'arena>) -> Option<Value<'arena>> {
+        match &initializer.kind {
+            ExpressionKind::Literal(Literal::String(s)) if s.len() > MAX_PROPAGATED_STRING_LEN => {
+                None
+            }
+            ExpressionKind::Literal(_) => Some(Value::Constant(initializer.kind.clone())),
+            ExpressionKind::Identifier(source) if self.copies => self
+                .resolve(*source)
+                .filter(|binding| binding.stable)
+                .map(|binding| Value::Copy(*source, binding.id)),
+            _ => None,
+        }
+    }
+
+    /// What a use of `name` becomes, if anything
+    fn substitute(&self, name: StringId, prefix: bool) -> Option<ExpressionKind<'arena>> {
+        let binding = self.resolve(name).filter(|binding| binding.stable)?;
+        match binding.value.as_ref()? {
+            Value::Constant(kind) if !prefix => Some(kind.clone()),
+            Value::Constant(_) => None,
+            // Only while `source` still names the binding it copied
+            Value::Copy(source, id) => self
+                .resolve(*source)
+                .is_some_and(|current| current.id == *id)
+                .then_some(ExpressionKind::Identifier(*source)),
+        }
+    }
+}
+
+impl<'arena> MutVisitor<'arena> for Propagator<'_, 'arena> {
+    fn visit_block(&mut self, stmts: &mut Vec<Statement<'arena>>, arena: &'arena Bump) -> bool {
+        self.scopes.push(FxHashMap::default());
+        let changed = walk_block(self, stmts, arena);
+        self.scopes.pop();
+        changed
+    }
+
+    fn visit_statement(&mut self, stmt: &mut Statement<'arena>, arena: &'arena Bump) -> bool {
+        match stmt {
+            Statement::Variable(decl) => {
+                // The initializer is evaluated before the name is in scope
+                let mut changed = self.visit_expression(&mut decl.initializer, arena);
+                changed |= self.visit_pattern(&mut decl.pattern, arena);
+                match &decl.pattern {
+                    Pattern::Identifier(ident) => {
+                        let value = self.value_of(&decl.initializer);
+                        self.declare(ident.node, value);
+                    }
+                    pattern => {
+                        let mut names = Vec::new();
+                        pattern_binders(pattern, &mut names);
+                        for name in names {
+                            self.declare(name, None);
+                        }
+                    }
+                }
+                changed
+            }
+            // `until` sees the locals of the loop body
+            Statement::Repeat(repeat_stmt) => {
+                self.scopes.push(FxHashMap::default());
+                let mut stmts = repeat_stmt.body.statements.to_vec();
+                let mut changed = walk_block(self, &mut stmts, arena);
+                if changed {
+                    repeat_stmt.body.statements = arena.alloc_slice_clone(&stmts);
+                }
+                changed |= self.visit_expression(&mut repeat_stmt.until, arena);
+                self.scopes.pop();
+                changed
+            }
+            _ => walk_statement(self, stmt, arena),
+        }
+    }
+
+    fn visit_expression(&mut self, expr: &mut Expression<'arena>, arena: &'arena Bump) -> bool {
+        let prefix = std::mem::take(&mut self.prefix);
+        match &expr.kind {
+            ExpressionKind::Identifier(name) => match self.substitute(*name, prefix) {
+                Some(kind) => {
+                    let name = *name;
+                    self.remarks.applied("constant-propagation", expr.span, || {
+                        let name = self.interner.resolve(name);
+                        match &kind {
+                            ExpressionKind::Identifier(source) => format!(
+                                "replaced `{}` with `{}`, which it copies",
+                                name,
+                                self.interner.resolve(*source)
+                            ),
+                            _ => format!("replaced `{}` with its constant value", name),
+                        }
+                    });
+                    expr.kind = kind;
+                    true
+                }
+                None => false,
+            },
+            // The walker visits the object or target first
+            ExpressionKind::Member(..)
+            | ExpressionKind::OptionalMember(..)
+            | ExpressionKind::Index(..)
+            | ExpressionKind::OptionalIndex(..)
+            | ExpressionKind::Call(..)
+            | ExpressionKind::OptionalCall(..)
+            | ExpressionKind::MethodCall(..)
+            | ExpressionKind::OptionalMethodCall(..)
+            | ExpressionKind::New(..)
+            | ExpressionKind::Assignment(..) => {
+                self.prefix = true;
+                let changed = walk_expression(self, expr, arena);
+                self.prefix = false;
+                changed
+            }
+            _ => walk_expression(self, expr, arena),
+        }
+    }
+}
+
+/// Finds the names that propagation may treat as single-assignment: declared
+/// only by plain `local`/`const` declarations and never assigned. Any other
+/// binder of the same name (parameter, loop or catch variable, destructuring,
+/// function or class name, import) disqualifies it everywhere, so shadowing by those
+/// needs no scope tracking.
+#[derive(Default)]
+pub(super) struct BindingCensus {
+    declared: FxHashSet<StringId>,
+    excluded: FxHashSet<StringId>,
+    /// How many `function` statements declare each name
+    functions: FxHashMap<StringId, usize>,
+}
+
+impl BindingCensus {
+    /// Whether the program binds or assigns `name` anywhere
+    pub(super) fn binds(&self, name: StringId) -> bool {
+        self.declared.contains(&name)
+            || self.excluded.contains(&name)
+            || self.functions.contains_key(&name)
+    }
+
+    /// Whether `name` is declared by exactly one `function` statement and
+    /// bound or assigned nowhere else
+    pub(super) fn sole_function(&self, name: StringId) -> bool {
+        self.functions.get(&name) == Some(&1)
+            && !self.declared.contains(&name)
+            && !self.excluded.contains(&name)
+    }
+
+    fn candidates(self) -> FxHashSet<StringId> {
+        let excluded = self.excluded;
+        let functions = self.functions;
+        self.declared
+            .into_iter()
+            .filter(|name| !excluded.contains(name) && !functions.contains_key(name))
+            .collect()
+    }
+
+    fn assigned(&mut self, target: &Expression<'_>) {
+        match &target.kind {
+            ExpressionKind::Identifier(name) => {
+                self.excluded.insert(*name);
+            }
+            ExpressionKind::Parenthesized(inner) => self.assigned(inner),
+            // Destructuring assignment
+            ExpressionKind::Array(elements) => {
+                for element in elements.iter() {
+                    match element {
+                        ArrayElement::Expression(e) | ArrayElement::Spread(e) => self.assigned(e),
+                    }
+                }
+            }
+            ExpressionKind::Object(props) => {
+                for prop in props.iter() {
+                    if let ObjectProperty::Property { value, .. } = prop {
+                        self.assigned(value);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl<'arena> MutVisitor<'arena> for BindingCensus {
+    fn visit_statement(&mut self, stmt: &mut Statement<'arena>, arena: &'arena Bump) -> bool {
+        match stmt {
+            Statement::Variable(decl) => {
+                if let Pattern::Identifier(ident) = &decl.pattern {
+                    self.declared.insert(ident.node);
+                    return self.visit_expression(&mut decl.initializer, arena);
+                }
+            }
+            Statement::Function(func) => {
+                *self.functions.entry(func.name.node).or_default() += 1;
+            }
+            Statement::Class(class) => {
+                self.excluded.insert(class.name.node);
+            }
+            Statement::Enum(decl) => {
+                self.excluded.insert(decl.name.node);
+            }
+            Statement::For(for_stmt) => match &**for_stmt {
+                ForStatement::Numeric(for_num) => {
+                    self.excluded.insert(for_num.variable.node);
+                }
+                ForStatement::Generic(for_gen) => {
+                    self.excluded
+                        .extend(for_gen.variables.iter().map(|var| var.node));
+                }
+            },
+            Statement::Try(try_stmt) => {
+                for clause in try_stmt.catch_clauses.iter() {
+                    match &clause.pattern {
+                        CatchPattern::Untyped { variable, .. }
+                        | CatchPattern::Typed { variable, .. }
+                        | CatchPattern::MultiTyped { variable, .. } => {
+                            self.excluded.insert(variable.node);
+                        }
+                    }
+                }
+            }
+            Statement::Import(import) => match &import.clause {
+                ImportClause::Default(ident) | ImportClause::Namespace(ident) => {
+                    self.excluded.insert(ident.node);
+                }
+                ImportClause::Named(specs) => self.excluded.extend(
+                    specs
+                        .iter()
+                        .map(|spec| spec.local.as_ref().unwrap_or(&spec.imported).node),
+                ),
+                ImportClause::Mixed { default, named } => {
+                    self.excluded.insert(default.node);
+                    self.excluded.extend(
+                        named
+                            .iter()
+                            .map(|spec| spec.local.as_ref().unwrap_or(&spec.imported).node),
+                    );
+                }
+                ImportClause::TypeOnly(_) => {}
+            },
+            _ => {}
+        }
+        walk_statement(self, stmt, arena)
+    }
+
+    fn visit_expression(&mut self, expr: &mut Expression<'arena>, arena: &'arena Bump) -> bool {
+        match &expr.kind {
+            ExpressionKind::Assignment(target, _, _) => self.assigned(target),
+            ExpressionKind::Try(try_expr) => {
+                self.excluded.insert(try_expr.catch_variable.node);
+            }
+            _ => {}
+        }
+        walk_expression(self, expr, arena)
+    }
+
+    fn visit_pattern(&mut self, pattern: &mut Pattern<'arena>, arena: &'arena Bump) -> bool {
+        let mut names = Vec::new();
+        pattern_binders(pattern, &mut names);
+        self.excluded.extend(names);
+        walk_pattern(self, pattern, arena)
+    }
+}
+
+/// Every name a pattern binds
+pub(crate) fn pattern_binders(pattern: &Pattern<'_>, names: &mut Vec<StringId>) {
+    match pattern {
+        Pattern::Identifier(ident) => names.push(ident.node),
+        Pattern::Array(array) => {
+            for element in array.elements.iter() {
+                match element {
+                    ArrayPatternElement::Pattern(p) => pattern_binders(&p.pattern, names),
+                    ArrayPatternElement::Rest(ident) => names.push(ident.node),
+                    ArrayPatternElement::Hole => {}
+                }
+            }
+        }
+        Pattern::Object(object) => {
+            for prop in object.properties.iter() {
+                match &prop.value {
+                    Some(value) => pattern_binders(value, names),
+                    // `{ key }` binds `key`
+                    None => names.push(prop.key.node),
+                }
+            }
+        }
+        Pattern::Or(or_pattern) => {
+            for alternative in or_pattern.alternatives.iter() {
+                pattern_binders(alternative, names);
+            }
+        }
+        Pattern::Literal(..) | Pattern::Wildcard(_) => {}
+    }
+}
@@ -0,0 +1,389 @@
+// =============================================================================
+// O3: Cross-Module Inlining Pass
+// =============================================================================
+
+use super::constant_propagation::BindingCensus;
+use super::scalar_replacement::Identifiers;
+use crate::config::OptimizationLevel;
+use crate::optimizer::export_summary::{
+    ExportSummaries, ExportSummary, InlineFunction, ModuleSummary, SummaryExpr,
+};
+use crate::optimizer::{
+    walk_expression, ModuleProfile, MutVisitor, ProfileCounts, RemarkSink, WholeProgramPass,
+};
+use crate::MutableProgram;
+use bumpalo::Bump;
+use rustc_hash::{FxHashMap, FxHashSet};
+use std::sync::Arc;
+use typedlua_parser::ast::expression::{Argument, Expression, ExpressionKind, UnaryOp};
+use typedlua_parser::ast::statement::{ImportClause, ImportSpecifier, Statement};
+use typedlua_parser::ast::Spanned;
+use typedlua_parser::span::Span;
+use typedlua_parser::string_interner::{StringId, StringInterner};
+
+/// Cross-module inlining pass
+/// Uses the export summaries of whole-program analysis to replace imported
+/// constants (`export const MAX = 10`) with their value and calls to small
+/// imported functions whose body is a single `return <expr>` with that
+/// expression. Named imports that are no longer read are dropped from the
+/// import, and an import left without names is removed when loading its
+/// module has no side effects.
+///
+/// With a profile, calls at sites the profile shows cold are left alone:
+/// inlining them grows the code without saving any time.
+///
+/// Without whole-program analysis, or for imports the analysis has no
+/// summary for, the pass does nothing.
+pub struct CrossModuleInliningPass {
+    interner: Arc<StringInterner>,
+    exports: Arc<ExportSummaries>,
+    /// Import sources of this module resolved to module ids; unresolved
+    /// sources are looked up as written
+    imports: FxHashMap<String, String>,
+    profile: Option<Arc<ModuleProfile>>,
+    remarks: RemarkSink,
+}
+
+impl CrossModuleInliningPass {
+    pub fn new(interner: Arc<StringInterner>) -> Self {
+        Self {
+            interner,
+            exports: Arc::default(),
+            imports: FxHashMap::default(),
+            profile: None,
+            remarks: RemarkSink::default(),
+        }
+    }
+
+    pub fn with_remarks(mut self, remarks: RemarkSink) -> Self {
+        self.remarks = remarks;
+        self
+    }
+
+    pub fn set_exports(&mut self, exports: Arc<ExportSummaries>) {
+        self.exports = exports;
+    }
+
+    pub fn set_module_imports(&mut self, imports: FxHashMap<String, String>) {
+        self.imports = imports;
+    }
+
+    pub fn set_profile(&mut self, profile: Arc<ModuleProfile>) {
+        self.profile = Some(profile);
+    }
+
+    fn module(&self, source: &str) -> Option<&ModuleSummary> {
+        let id = self.imports.get(source).map_or(source, String::as_str);
+        self.exports.module(id)
+    }
+}
+
+impl<'arena> WholeProgramPass<'arena> for CrossModuleInliningPass {
+    fn name(&self) -> &'static str {
+        "cross-module-inlining"
+    }
+
+    fn min_level(&self) -> OptimizationLevel {
+        OptimizationLevel::O3
+    }
+
+    fn run(
+        &mut self,
+        program: &mut MutableProgram<'arena>,
+        arena: &'arena Bump,
+    ) -> Result<bool, String> {
+        if self.exports.is_empty() {
+            return Ok(false);
+        }
+
+        // Bindings other than the imports themselves
+        let mut census = BindingCensus::default();
+        for stmt in program.statements.iter_mut() {
+            if !matches!(stmt, Statement::Import(_)) {
+                census.visit_statement(stmt, arena);
+            }
+        }
+        let math = self.interner.get_or_intern("math");
+
+        let mut targets = FxHashMap::default();
+        for stmt in &program.statements {
+            let Statement::Import(import) = stmt else {
+                continue;
+            };
+            let Some(module) = self.module(&import.source) else {
+                continue;
+            };
+            let specs = match &import.clause {
+                ImportClause::Named(specs) => &specs[..],
+                ImportClause::Mixed { named, .. } => &named[..],
+                _ => &[],
+            };
+            for spec in specs {
+                let local = local_name(spec);
+                if census.binds(local) {
+                    continue;
+                }
+                let imported = self.interner.resolve(spec.imported.node);
+                match module.exports.get(&*imported) {
+                    Some(ExportSummary::Function(function))
+                        if function.body.uses_math() && census.binds(math) => {}
+                    Some(export) => {
+                        targets.insert(local, export);
+                    }
+                    None => {}
+                }
+            }
+        }
+        if targets.is_empty() {
+            return Ok(false);
+        }
+
+        let counts = self
+            .profile
+            .as_ref()
+            .map(|profile| profile.bind(program, &self.interner, arena))
+            .unwrap_or_default();
+        let mut inliner = Inliner {
+            interner: &self.interner,
+            remarks: &self.remarks,
+            targets: &targets,
+            counts: &counts,
+            math,
+        };
+        let mut changed = inliner.visit_block(&mut program.statements, arena);
+
+        let mut identifiers = Identifiers::default();
+        identifiers.visit_block(&mut program.statements, arena);
+        let unused: FxHashSet<StringId> = targets
+            .keys()
+            .copied()
+            .filter(|name| !identifiers.0.contains(name))
+            .collect();
+
+        let mut i = 0;
+        while i < program.statements.len() {
+            let mut remove = false;
+            if let Statement::Import(import) = &mut program.statements[i] {
+                let kept = |specs: &[ImportSpecifier]| -> Vec<ImportSpecifier> {
+                    specs
+                        .iter()
+                        .filter(|spec| !unused.contains(&local_name(spec)))
+                        .cloned()
+                        .collect()
+                };
+                match &import.clause {
+                    ImportClause::Named(specs) => {
+                        let named = kept(specs);
+                        if named.is_empty() {
+                            remove = self
+                                .module(&import.source)
+                                .is_some_and(|module| module.side_effect_free);
+                        } else if named.len() < specs.len() {
+                            import.clause = ImportClause::Named(arena.alloc_slice_clone(&named));
+                            changed = true;
+                        }
+                    }
+                    ImportClause::Mixed { default, named } => {
+                        let default = default.clone();
+                        let kept_named = kept(named);
+                        if kept_named.is_empty() {
+                            import.clause = ImportClause::Default(default);
+                            changed = true;
+                        } else if kept_named.len() < named.len() {
+                            import.clause = ImportClause::Mixed {
+                                default,
+                                named: arena.alloc_slice_clone(&kept_named),
+                            };
+                            changed = true;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            if remove {
+                program.statements.remove(i);
+                changed = true;
+            } else {
+                i += 1;
+            }
+        }
+
+        Ok(changed)
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+fn local_name(spec: &ImportSpecifier) -> StringId {
+    spec.local.as_ref().unwrap_or(&spec.imported).node
+}
+
+/// How an argument may be substituted into an inlined body
+#[derive(PartialEq)]
+enum ArgumentKind {
+    /// Literals, free to copy and reorder
+    Constant,
+    /// Names, also free to copy
+    Name,
+    /// Operators over names and literals: no side effects, but not copied
+    Pure,
+    /// Anything else runs exactly where the call evaluated it
+    Effectful,
+}
+
+fn argument_kind(expr: &Expression<'_>) -> ArgumentKind {
+    match &expr.kind {
+        ExpressionKind::Literal(_) => ArgumentKind::Constant,
+        ExpressionKind::Unary(UnaryOp::Negate, operand)
+            if matches!(operand.kind, ExpressionKind::Literal(_)) =>
+        {
+            ArgumentKind::Constant
+        }
+        ExpressionKind::Identifier(_) => ArgumentKind::Name,
+        ExpressionKind::Parenthesized(inner) => argument_kind(inner),
+        ExpressionKind::Unary(_, operand) => match argument_kind(operand) {
+            ArgumentKind::Effectful => ArgumentKind::Effectful,
+            _ => ArgumentKind::Pure,
+        },
+        ExpressionKind::Binary(_, left, right) => {
+            match (argument_kind(left), argument_kind(right)) {
+                (ArgumentKind::Effectful, _) | (_, ArgumentKind::Effectful) => {
+                    ArgumentKind::Effectful
+                }
+                _ => ArgumentKind::Pure,
+            }
+        }
+        _ => ArgumentKind::Effectful,
+    }
+}
+
+struct Inliner<'p> {
+    interner: &'p StringInterner,
+    remarks: &'p RemarkSink,
+    targets: &'p FxHashMap<StringId, &'p ExportSummary>,
+    counts: &'p ProfileCounts,
+    math: StringId,
+}
+
+impl Inliner<'_> {
+    /// Whether the arguments of a call survive substitution into `function`:
+    /// compound arguments must be read exactly once, and a call with side
+    /// effects is only moved past literals
+    fn accepts(function: &InlineFunction, args: &[Argument<'_>]) -> bool {
+        if args.len() != function.arity() || args.iter().any(|arg| arg.is_spread) {
+            return false;
+        }
+        let kinds: Vec<_> = args.iter().map(|arg| argument_kind(&arg.value)).collect();
+        let effectful = kinds
+            .iter()
+            .filter(|kind| **kind == ArgumentKind::Effectful)
+            .count();
+        kinds
+            .iter()
+            .zip(&function.single_use)
+            .all(|(kind, single_use)| match kind {
+                ArgumentKind::Constant => true,
+                ArgumentKind::Name => effectful == 0,
+                ArgumentKind::Pure => *single_use && effectful == 0,
+                ArgumentKind::Effectful => *single_use && effectful == 1,
+            })
+    }
+
+    fn instantiate<'arena>(
+        &self,
+        body: &SummaryExpr,
+        args: &[Argument<'arena>],
+        span: Span,
+        arena: &'arena Bump,
+    ) -> Expression<'arena> {
+        let kind = match body {
+            SummaryExpr::Literal(literal) => ExpressionKind::Literal(literal.clone()),
+            SummaryExpr::Parameter(index) => return args[*index].value.clone(),
+            SummaryExpr::MathCall(function, call_args) => {
+                let math = Expression::new(ExpressionKind::Identifier(self.math), span);
+                let function = Spanned::new(self.interner.get_or_intern(function), span);
+                let callee =
+                    Expression::new(ExpressionKind::Member(arena.alloc(math), function), span);
+                let call_args: Vec<_> = call_args
+                    .iter()
+                    .map(|arg| Argument {
+                        value: self.instantiate(arg, args, span, arena),
+                        is_spread: false,
+                        span,
+                    })
+                    .collect();
+                ExpressionKind::Call(
+                    arena.alloc(callee),
+                    arena.alloc_slice_clone(&call_args),
+                    None,
+                )
+            }
+            SummaryExpr::Unary(op, operand) => ExpressionKind::Unary(
+                *op,
+                arena.alloc(self.instantiate(operand, args, span, arena)),
+            ),
+            SummaryExpr::Binary(op, left, right) => ExpressionKind::Binary(
+                *op,
+                arena.alloc(self.instantiate(left, args, span, arena)),
+                arena.alloc(self.instantiate(right, args, span, arena)),
+            ),
+            SummaryExpr::Conditional(cond, then_expr, else_expr) => ExpressionKind::Conditional(
+                arena.alloc(self.instantiate(cond, args, span, arena)),
+                arena.alloc(self.instantiate(then_expr, args, span, arena)),
+                arena.alloc(self.instantiate(else_expr, args, span, arena)),
+            ),
+        };
+        Expression::new(kind, span)
+    }
+}
+
+impl<'arena> MutVisitor<'arena> for Inliner<'_> {
+    fn visit_expression(&mut self, expr: &mut Expression<'arena>, arena: &'arena Bump) -> bool {
+        let changed = walk_expression(self, expr, arena);
+        match &expr.kind {
+            ExpressionKind::Identifier(name) => {
+                if let Some(ExportSummary::Constant(value)) = self.targets.get(name) {
+                    expr.kind = ExpressionKind::Literal(value.clone());
+                    return true;
+                }
+            }
+            ExpressionKind::Call(callee, args, _) => {
+                let args = *args;
+                let ExpressionKind::Identifier(name) = callee.kind else {
+                    return changed;
+                };
+                let Some(ExportSummary::Function(function)) = self.targets.get(&name) else {
+                    return changed;
+                };
+                if self.counts.is_cold(expr.span) {
+                    self.remarks.missed(
+                        "cross-module-inlining",
+                        expr.span,
+                        || format!("not inlined imported `{}`", self.interner.resolve(name)),
+                        || "call site is cold in the profile".to_string(),
+                    );
+                    return changed;
+                }
+                if !Self::accepts(function, args) {
+                    self.remarks.missed(
+                        "cross-module-inlining",
+                        expr.span,
+                        || format!("not inlined imported `{}`", self.interner.resolve(name)),
+                        || "arguments would be duplicated or reordered".to_string(),
+                    );
+                    return changed;
+                }
+                self.remarks
+                    .applied("cross-module-inlining", expr.span, || {
+                        format!("inlined imported `{}`", self.interner.resolve(name))
+                    });
+                *expr = self.instantiate(&function.body, args, expr.span, arena);
+                return true;
+            }
+            _ => {}
+        }
+        changed
+    }
+}
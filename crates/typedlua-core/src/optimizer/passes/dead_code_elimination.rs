@@ -1,12 +1,33 @@
-use crate::optimizer::BlockVisitor;
+use crate::codegen::printer::statement_span;
+use crate::optimizer::{BlockVisitor, Hints, OptimizationHints, RemarkSink};
 use bumpalo::Bump;
+use typedlua_parser::ast::expression::{Expression, ExpressionKind, Literal};
 use typedlua_parser::ast::statement::{Block, ForStatement, Statement};
+use typedlua_parser::span::Span;
 
-pub struct DeadCodeEliminationPass;
+const PASS: &str = "dead-code-elimination";
+
+pub struct DeadCodeEliminationPass {
+    hints: OptimizationHints,
+    remarks: RemarkSink,
+}
 
 impl DeadCodeEliminationPass {
     pub fn new() -> Self {
-        Self
+        Self {
+            hints: OptimizationHints::default(),
+            remarks: RemarkSink::default(),
+        }
+    }
+
+    pub fn with_hints(mut self, hints: OptimizationHints) -> Self {
+        self.hints = hints;
+        self
+    }
+
+    pub fn with_remarks(mut self, remarks: RemarkSink) -> Self {
+        self.remarks = remarks;
+        self
     }
 }
 
@@ -38,12 +59,43 @@ impl DeadCodeEliminationPass {
             if is_terminal {
                 let new_len = i + 1;
                 if stmts.len() > new_len {
+                    let removed = stmts.len() - new_len;
+                    self.remarks.applied(PASS, span_of(&stmts[new_len]), || {
+                        let terminal = match stmts[i] {
+                            Statement::Return(_) => "return",
+                            Statement::Break(_) => "break",
+                            _ => "continue",
+                        };
+                        format!(
+                            "removed {} unreachable statement(s) after `{}`",
+                            removed, terminal
+                        )
+                    });
                     stmts.truncate(new_len);
                     changed = true;
                 }
                 break;
             }
 
+            if let Some(taken) = Self::fold_constant_branch(&stmts[i]) {
+                self.remarks.applied(PASS, span_of(&stmts[i]), || {
+                    "folded `if` with a constant condition".to_string()
+                });
+                // Revisit the spliced statements in place
+                stmts.splice(i..=i, taken);
+                changed = true;
+                continue;
+            }
+
+            if self.is_unused_pure_call(&stmts[i]) {
+                self.remarks.applied(PASS, span_of(&stmts[i]), || {
+                    "removed unused call to an @pure method".to_string()
+                });
+                stmts.remove(i);
+                changed = true;
+                continue;
+            }
+
             changed |= self.eliminate_in_stmt(&mut stmts[i], arena);
 
             i += 1;
@@ -100,6 +152,93 @@ impl DeadCodeEliminationPass {
         }
     }
 
+    /// The statements an `if` whose condition is a literal reduces to. A taken
+    /// block that declares locals is left in place, since spliced statements
+    /// would share the enclosing scope.
+    fn fold_constant_branch<'arena>(stmt: &Statement<'arena>) -> Option<Vec<Statement<'arena>>> {
+        let Statement::If(if_stmt) = stmt else {
+            return None;
+        };
+        let splice = |block: &Block<'arena>| {
+            let declares = block.statements.iter().any(|stmt| {
+                matches!(
+                    stmt,
+                    Statement::Variable(_)
+                        | Statement::Function(_)
+                        | Statement::Class(_)
+                        | Statement::Enum(_)
+                        | Statement::Label(_)
+                )
+            });
+            (!declares).then(|| block.statements.to_vec())
+        };
+        if Self::truthiness(&if_stmt.condition)? {
+            return splice(&if_stmt.then_block);
+        }
+        match if_stmt.else_ifs.split_first() {
+            // The first `elseif` becomes the head
+            Some((first, rest)) => {
+                let mut next = if_stmt.clone();
+                next.condition = first.condition.clone();
+                next.then_block = first.block.clone();
+                next.else_ifs = rest;
+                Some(vec![Statement::If(next)])
+            }
+            None => match &if_stmt.else_block {
+                Some(else_block) => splice(else_block),
+                None => Some(Vec::new()),
+            },
+        }
+    }
+
+    /// Whether a literal condition always or never holds; `None` when it is
+    /// not a literal
+    fn truthiness(condition: &Expression<'_>) -> Option<bool> {
+        match &condition.kind {
+            ExpressionKind::Literal(Literal::Boolean(b)) => Some(*b),
+            ExpressionKind::Literal(Literal::Nil) => Some(false),
+            ExpressionKind::Literal(_) => Some(true),
+            _ => None,
+        }
+    }
+
+    /// A call statement to an `@pure` method whose receiver and arguments
+    /// are plain names or literals, so dropping it loses nothing
+    fn is_unused_pure_call(&self, stmt: &Statement<'_>) -> bool {
+        let Statement::Expression(expr) = stmt else {
+            return false;
+        };
+        let (class, method, receiver, args) = match &expr.kind {
+            ExpressionKind::MethodCall(obj, method, args, _) => match &expr.receiver_class {
+                Some(info) => (info.class_name, method.node, Some(&**obj), *args),
+                None => return false,
+            },
+            // `Class.method(...)`, including method calls converted at O2
+            ExpressionKind::Call(callee, args, _) => match &callee.kind {
+                ExpressionKind::Member(object, method) => match &object.kind {
+                    ExpressionKind::Identifier(class) => (*class, method.node, None, *args),
+                    _ => return false,
+                },
+                _ => return false,
+            },
+            _ => return false,
+        };
+        self.hints.method(class, method).contains(Hints::PURE)
+            && receiver.into_iter().all(Self::is_trivial)
+            && args
+                .iter()
+                .all(|arg| !arg.is_spread && Self::is_trivial(&arg.value))
+    }
+
+    fn is_trivial(expr: &Expression<'_>) -> bool {
+        matches!(
+            expr.kind,
+            ExpressionKind::Identifier(_)
+                | ExpressionKind::Literal(_)
+                | ExpressionKind::SelfKeyword
+        )
+    }
+
     fn eliminate_in_block<'arena>(
         &mut self,
         block: &mut Block<'arena>,
@@ -119,3 +258,7 @@ impl Default for DeadCodeEliminationPass {
         Self::new()
     }
 }
+
+fn span_of(stmt: &Statement<'_>) -> Span {
+    statement_span(stmt).unwrap_or_else(Span::dummy)
+}
@@ -1,1304 +1,582 @@
 // O2: Function Inlining Pass
 // =============================================================================
 
-use crate::optimizer::{PreAnalysisPass, StmtVisitor};
+use super::constant_propagation::BindingCensus;
+use super::profile_guided::{Renamer, ShapeCheck};
+use super::scalar_replacement::Identifiers;
+use crate::optimizer::{
+    walk_expression, walk_statement, Hints, MutVisitor, OptimizationHints, ProfileCounts,
+    RemarkSink, SharedProfile, StmtVisitor,
+};
 use crate::MutableProgram;
 use bumpalo::Bump;
 use rustc_hash::FxHashMap as HashMap;
+use rustc_hash::FxHashSet as HashSet;
 use std::sync::Arc;
-use typedlua_parser::ast::expression::{ArrowBody, Expression, ExpressionKind};
+use typedlua_parser::ast::expression::{Argument, Expression, ExpressionKind, Literal};
 use typedlua_parser::ast::pattern::Pattern;
 use typedlua_parser::ast::statement::{
-    Block, ForStatement, FunctionDeclaration, Parameter, ReturnStatement, Statement,
-    VariableDeclaration, VariableKind,
+    Block, ClassMember, FunctionDeclaration, ReturnStatement, Statement, VariableDeclaration,
+    VariableKind,
 };
+use typedlua_parser::ast::Spanned;
 use typedlua_parser::span::Span;
 use typedlua_parser::string_interner::{StringId, StringInterner};
 
-enum InlineResult<'arena> {
-    /// Direct expression substitution - for simple single-return functions
-    /// The expression can be directly substituted for the call
-    Direct(Box<Expression<'arena>>),
-    /// Complex inlining - contains statements to insert and the result variable
-    Replaced {
-        stmts: Vec<Statement<'arena>>,
-        result_var: StringId,
-    },
-}
-
-/// Function inlining optimization pass (threshold: 5 statements)
+/// Function inlining optimization pass (default threshold: 5 statements)
 /// Inlines small functions at call sites
+///
+/// Only functions declared by a single `function` statement whose name
+/// nothing else binds or assigns are inlined, so every call by that name
+/// reaches the collected declaration. The body must be straight-line code
+/// that reads no name the module binds besides its parameters and locals.
+///
+/// A body that is just `return expr` replaces calls anywhere in an
+/// expression when every argument is a name or a literal, which the
+/// parameters are substituted by. Other calls are inlined where they form a
+/// whole statement, `local x = f(...)`, `f(...)` or `return f(...)`, with
+/// the parameters and locals bound to fresh locals.
+///
+/// `function` statements take no decorators, so `@inline` and `@noinline`
+/// only name methods. The hint this pass honors is `@cold` on the caller:
+/// calls inside a `@cold` method are left alone. With a profile, so are calls
+/// at sites the profile shows cold.
 pub struct FunctionInliningPass<'arena> {
+    /// Name the pass reports remarks under
+    pass: &'static str,
     threshold: usize,
     next_temp_id: usize,
+    interner: Arc<StringInterner>,
+    remarks: RemarkSink,
+    hints: OptimizationHints,
+    /// Functions declared by `function` statements anywhere in the program
     functions: HashMap<StringId, FunctionDeclaration<'arena>>,
-    interner: Option<Arc<StringInterner>>,
+    census: BindingCensus,
+    /// Spans of named functions, to name the caller in remarks
+    bodies: Vec<(Span, StringId)>,
+    /// Spans of `@cold` methods
+    cold: Vec<Span>,
+    profile: SharedProfile,
+    counts: ProfileCounts,
 }
 
-impl Default for FunctionInliningPass<'_> {
-    fn default() -> Self {
-        Self {
-            threshold: 5,
-            next_temp_id: 0,
-            functions: HashMap::default(),
-            interner: None,
-        }
-    }
+/// A function in the shape the inliner copies
+struct Callee<'arena> {
+    parameters: Vec<StringId>,
+    /// Statements before the trailing `return`
+    body: Vec<Statement<'arena>>,
+    /// Value of the trailing `return`; `None` without one
+    result: Option<Expression<'arena>>,
+    /// Locals the body declares
+    locals: Vec<StringId>,
 }
 
 impl<'arena> FunctionInliningPass<'arena> {
     pub fn new(interner: Arc<StringInterner>) -> Self {
         Self {
+            pass: "function-inlining",
             threshold: 5,
             next_temp_id: 0,
+            interner,
+            remarks: RemarkSink::default(),
+            hints: OptimizationHints::default(),
             functions: HashMap::default(),
-            interner: Some(interner),
+            census: BindingCensus::default(),
+            bodies: Vec::new(),
+            cold: Vec::new(),
+            profile: SharedProfile::default(),
+            counts: ProfileCounts::default(),
         }
     }
-}
 
-impl<'arena> PreAnalysisPass<'arena> for FunctionInliningPass<'arena> {
-    fn analyze(&mut self, program: &MutableProgram<'arena>) {
-        self.functions.clear();
-        self.collect_functions(program);
+    /// Report remarks under `pass` instead of `function-inlining`
+    pub(crate) fn with_pass_name(mut self, pass: &'static str) -> Self {
+        self.pass = pass;
+        self
     }
-}
 
-impl<'arena> StmtVisitor<'arena> for FunctionInliningPass<'arena> {
-    fn visit_stmt(&mut self, stmt: &mut Statement<'arena>, arena: &'arena Bump) -> bool {
-        self.inline_in_statement(stmt, arena)
+    pub fn with_hints(mut self, hints: OptimizationHints) -> Self {
+        self.hints = hints;
+        self
     }
-}
 
-impl<'arena> FunctionInliningPass<'arena> {
-    fn collect_functions(&mut self, program: &MutableProgram<'arena>) {
-        for stmt in &program.statements {
-            self.collect_functions_in_stmt(stmt);
-        }
+    pub fn with_remarks(mut self, remarks: RemarkSink) -> Self {
+        self.remarks = remarks;
+        self
     }
 
-    fn collect_functions_in_stmt(&mut self, stmt: &Statement<'arena>) {
-        match stmt {
-            Statement::Function(func) => {
-                self.functions.insert(func.name.node, func.clone());
-                for s in func.body.statements.iter() {
-                    self.collect_functions_in_stmt(s);
-                }
-            }
-            Statement::If(if_stmt) => {
-                for s in if_stmt.then_block.statements.iter() {
-                    self.collect_functions_in_stmt(s);
-                }
-                for else_if in if_stmt.else_ifs.iter() {
-                    for s in else_if.block.statements.iter() {
-                        self.collect_functions_in_stmt(s);
-                    }
-                }
-                if let Some(else_block) = &if_stmt.else_block {
-                    for s in else_block.statements.iter() {
-                        self.collect_functions_in_stmt(s);
-                    }
-                }
-            }
-            Statement::While(while_stmt) => {
-                for s in while_stmt.body.statements.iter() {
-                    self.collect_functions_in_stmt(s);
-                }
-            }
-            Statement::For(for_stmt) => {
-                let body = match &**for_stmt {
-                    ForStatement::Numeric(for_num) => &for_num.body,
-                    ForStatement::Generic(for_gen) => &for_gen.body,
-                };
-                for s in body.statements.iter() {
-                    self.collect_functions_in_stmt(s);
-                }
-            }
-            _ => {}
-        }
+    pub(crate) fn with_profile(mut self, profile: SharedProfile) -> Self {
+        self.profile = profile;
+        self
     }
-}
 
-impl<'arena> FunctionInliningPass<'arena> {
-    fn inline_in_statement(&mut self, stmt: &mut Statement<'arena>, arena: &'arena Bump) -> bool {
-        match stmt {
-            Statement::Function(func) => {
-                let mut changed = false;
-                let mut stmts: Vec<Statement<'arena>> = func.body.statements.to_vec();
-                let mut body_changed = false;
-                for s in &mut stmts {
-                    body_changed |= self.inline_in_statement(s, arena);
-                }
-                if body_changed {
-                    func.body.statements = arena.alloc_slice_clone(&stmts);
-                    changed = true;
-                }
-                changed
-            }
-            Statement::If(if_stmt) => {
-                let mut changed = self.inline_in_expression(&mut if_stmt.condition, arena);
-                changed |= self.inline_in_block(&mut if_stmt.then_block, arena);
-                let mut new_else_ifs: Vec<_> = if_stmt.else_ifs.to_vec();
-                let mut eic = false;
-                for else_if in &mut new_else_ifs {
-                    eic |= self.inline_in_expression(&mut else_if.condition, arena);
-                    eic |= self.inline_in_block(&mut else_if.block, arena);
-                }
-                if eic {
-                    if_stmt.else_ifs = arena.alloc_slice_clone(&new_else_ifs);
-                    changed = true;
-                }
-                if let Some(else_block) = &mut if_stmt.else_block {
-                    changed |= self.inline_in_block(else_block, arena);
-                }
-                changed
-            }
-            Statement::While(while_stmt) => {
-                let mut changed = self.inline_in_expression(&mut while_stmt.condition, arena);
-                changed |= self.inline_in_block(&mut while_stmt.body, arena);
-                changed
-            }
-            Statement::For(for_stmt) => match &**for_stmt {
-                ForStatement::Numeric(for_num_ref) => {
-                    let mut new_num = (**for_num_ref).clone();
-                    let mut fc = false;
-                    fc |= self.inline_in_expression(&mut new_num.start, arena);
-                    fc |= self.inline_in_expression(&mut new_num.end, arena);
-                    if let Some(step) = &mut new_num.step {
-                        fc |= self.inline_in_expression(step, arena);
-                    }
-                    fc |= self.inline_in_block(&mut new_num.body, arena);
-                    if fc {
-                        *stmt = Statement::For(
-                            arena.alloc(ForStatement::Numeric(arena.alloc(new_num))),
-                        );
-                    }
-                    fc
-                }
-                ForStatement::Generic(for_gen_ref) => {
-                    let mut new_gen = for_gen_ref.clone();
-                    let mut fc = false;
-                    let mut new_iters: Vec<_> = new_gen.iterators.to_vec();
-                    for expr in &mut new_iters {
-                        fc |= self.inline_in_expression(expr, arena);
-                    }
-                    if fc {
-                        new_gen.iterators = arena.alloc_slice_clone(&new_iters);
-                    }
-                    fc |= self.inline_in_block(&mut new_gen.body, arena);
-                    if fc {
-                        *stmt = Statement::For(arena.alloc(ForStatement::Generic(new_gen)));
-                    }
-                    fc
-                }
-            },
-            Statement::Variable(decl) => {
-                if let Some(result) = self.try_inline_call(&mut decl.initializer, arena) {
-                    match result {
-                        InlineResult::Direct(_) => {
-                            // Expression was directly substituted - no extra statements needed
-                            true
-                        }
-                        InlineResult::Replaced { stmts, .. } => {
-                            // decl.initializer has been modified to reference the result variable
-                            let span = decl.span;
-                            let var_stmt = Statement::Variable(decl.clone());
-                            let mut new_stmts = stmts;
-                            new_stmts.push(var_stmt);
-                            *stmt = Statement::Block(Block {
-                                statements: arena.alloc_slice_clone(&new_stmts),
-                                span,
-                            });
-                            true
-                        }
-                    }
-                } else {
-                    false
-                }
-            }
-            Statement::Expression(expr) => {
-                if let Some(result) = self.try_inline_call(expr, arena) {
-                    match result {
-                        InlineResult::Direct(_) => {
-                            // Expression was directly substituted - no extra statements needed
-                            true
-                        }
-                        InlineResult::Replaced { stmts, .. } => {
-                            let span = expr.span;
-                            *stmt = Statement::Block(Block {
-                                statements: arena.alloc_slice_clone(&stmts),
-                                span,
-                            });
-                            true
-                        }
-                    }
-                } else {
-                    false
-                }
-            }
-            Statement::Return(ret) => {
-                let ret_span = ret.span;
-                let mut values: Vec<Expression<'arena>> = ret.values.to_vec();
+    pub fn with_threshold(mut self, threshold: usize) -> Self {
+        self.threshold = threshold;
+        self
+    }
+}
 
-                let mut changed = false;
-                let mut replaced_block = None;
-                let mut i = 0;
-                while i < values.len() {
-                    if let Some(result) = self.try_inline_call(&mut values[i], arena) {
-                        match result {
-                            InlineResult::Direct(_) => {
-                                // Expression was directly substituted - no extra statements needed
-                                changed = true;
-                            }
-                            InlineResult::Replaced { stmts, .. } => {
-                                // values[i] has been modified to reference the result variable
-                                let new_ret = ReturnStatement {
-                                    values: arena.alloc_slice_clone(&values),
-                                    span: ret_span,
-                                };
-                                let mut new_stmts = stmts;
-                                new_stmts.push(Statement::Return(new_ret));
-                                replaced_block = Some(Statement::Block(Block {
-                                    statements: arena.alloc_slice_clone(&new_stmts),
-                                    span: ret_span,
-                                }));
-                                changed = true;
-                                break;
-                            }
-                        }
-                    }
-                    i += 1;
-                }
-                if let Some(block_stmt) = replaced_block {
-                    *stmt = block_stmt;
-                } else if changed {
-                    // Only Direct results -- update the values slice in the Return
-                    if let Statement::Return(ret) = stmt {
-                        ret.values = arena.alloc_slice_clone(&values);
-                    }
-                }
-                changed
-            }
-            _ => false,
-        }
+impl<'arena> StmtVisitor<'arena> for FunctionInliningPass<'arena> {
+    fn prepare(&mut self, program: &mut MutableProgram<'arena>, arena: &'arena Bump) {
+        self.census = BindingCensus::default();
+        self.census.visit_block(&mut program.statements, arena);
+        let mut collector = FunctionCollector::default();
+        collector.visit_block(&mut program.statements, arena);
+        self.functions = collector.functions;
+        self.bodies = collector.bodies;
+        self.cold = collector
+            .methods
+            .into_iter()
+            .filter(|&(_, class, method)| self.hints.method(class, method).contains(Hints::COLD))
+            .map(|(span, _, _)| span)
+            .collect();
+        self.counts = self
+            .profile
+            .get()
+            .map(|profile| profile.bind(program, &self.interner, arena))
+            .unwrap_or_default();
     }
 
-    fn inline_in_block(&mut self, block: &mut Block<'arena>, arena: &'arena Bump) -> bool {
-        let mut stmts: Vec<Statement<'arena>> = block.statements.to_vec();
-        let mut changed = false;
-        let mut i = 0;
-        while i < stmts.len() {
-            changed |= self.inline_in_statement(&mut stmts[i], arena);
-            i += 1;
-        }
-        if changed {
-            block.statements = arena.alloc_slice_clone(&stmts);
+    fn visit_stmt(&mut self, stmt: &mut Statement<'arena>, arena: &'arena Bump) -> bool {
+        // Calls inside the statement's expressions first. The call of an
+        // expression statement stays a call, so the statement stays valid
+        let mut changed = match stmt {
+            Statement::Expression(expr) => walk_expression(self, expr, arena),
+            _ => walk_statement(self, stmt, arena),
+        };
+        if let Some(block) = self.inline_statement(stmt, arena) {
+            *stmt = block;
+            changed = true;
         }
         changed
     }
+}
 
-    fn inline_in_expression(&mut self, expr: &mut Expression<'arena>, arena: &'arena Bump) -> bool {
-        match &expr.kind {
-            ExpressionKind::Call(func_ref, args_ref, type_args) => {
-                let type_args = *type_args;
-                let mut new_func = (**func_ref).clone();
-                let fc = self.inline_in_expression(&mut new_func, arena);
-                let mut new_args: Vec<_> = args_ref.to_vec();
-                let mut ac = false;
-                for arg in &mut new_args {
-                    ac |= self.inline_in_expression(&mut arg.value, arena);
-                }
-                if fc || ac {
-                    expr.kind = ExpressionKind::Call(
-                        arena.alloc(new_func),
-                        arena.alloc_slice_clone(&new_args),
-                        type_args,
-                    );
-                }
-                let mut changed = fc || ac;
-                // Also try to inline this call itself (for nested inlining)
-                if let Some(InlineResult::Direct(_)) = self.try_inline_call(expr, arena) {
-                    changed = true;
-                }
-                changed
-            }
-            ExpressionKind::MethodCall(obj_ref, method, args_ref, type_args) => {
-                let method = method.clone();
-                let type_args = *type_args;
-                let mut new_obj = (**obj_ref).clone();
-                let oc = self.inline_in_expression(&mut new_obj, arena);
-                let mut new_args: Vec<_> = args_ref.to_vec();
-                let mut ac = false;
-                for arg in &mut new_args {
-                    ac |= self.inline_in_expression(&mut arg.value, arena);
-                }
-                if oc || ac {
-                    expr.kind = ExpressionKind::MethodCall(
-                        arena.alloc(new_obj),
-                        method,
-                        arena.alloc_slice_clone(&new_args),
-                        type_args,
-                    );
-                }
-                oc || ac
-            }
-            ExpressionKind::Binary(op, left_ref, right_ref) => {
-                let op = *op;
-                let mut new_left = (**left_ref).clone();
-                let mut new_right = (**right_ref).clone();
-                let lc = self.inline_in_expression(&mut new_left, arena);
-                let rc = self.inline_in_expression(&mut new_right, arena);
-                if lc || rc {
-                    expr.kind =
-                        ExpressionKind::Binary(op, arena.alloc(new_left), arena.alloc(new_right));
-                }
-                lc || rc
-            }
-            ExpressionKind::Unary(op, operand_ref) => {
-                let op = *op;
-                let mut new_operand = (**operand_ref).clone();
-                let changed = self.inline_in_expression(&mut new_operand, arena);
-                if changed {
-                    expr.kind = ExpressionKind::Unary(op, arena.alloc(new_operand));
-                }
-                changed
-            }
-            ExpressionKind::Conditional(cond_ref, then_ref, else_ref) => {
-                let mut new_cond = (**cond_ref).clone();
-                let mut new_then = (**then_ref).clone();
-                let mut new_else = (**else_ref).clone();
-                let cc = self.inline_in_expression(&mut new_cond, arena);
-                let tc = self.inline_in_expression(&mut new_then, arena);
-                let ec = self.inline_in_expression(&mut new_else, arena);
-                if cc || tc || ec {
-                    expr.kind = ExpressionKind::Conditional(
-                        arena.alloc(new_cond),
-                        arena.alloc(new_then),
-                        arena.alloc(new_else),
-                    );
-                }
-                cc || tc || ec
-            }
-            ExpressionKind::Pipe(left_ref, right_ref) => {
-                let mut new_left = (**left_ref).clone();
-                let mut new_right = (**right_ref).clone();
-                let lc = self.inline_in_expression(&mut new_left, arena);
-                let rc = self.inline_in_expression(&mut new_right, arena);
-                if lc || rc {
-                    expr.kind = ExpressionKind::Pipe(arena.alloc(new_left), arena.alloc(new_right));
-                }
-                lc || rc
-            }
-            ExpressionKind::Match(match_expr) => {
-                let mut new_value = (*match_expr.value).clone();
-                let vc = self.inline_in_expression(&mut new_value, arena);
-                let mut new_arms: Vec<_> = match_expr.arms.to_vec();
-                let mut ac = false;
-                for arm in &mut new_arms {
-                    match &mut arm.body {
-                        MatchArmBody::Expression(expr_ref) => {
-                            let mut new_expr = (**expr_ref).clone();
-                            if self.inline_in_expression(&mut new_expr, arena) {
-                                arm.body = MatchArmBody::Expression(arena.alloc(new_expr));
-                                ac = true;
-                            }
-                        }
-                        MatchArmBody::Block(block) => {
-                            ac |= self.inline_in_block(block, arena);
-                        }
-                    }
-                }
-                if vc || ac {
-                    expr.kind =
-                        ExpressionKind::Match(typedlua_parser::ast::expression::MatchExpression {
-                            value: arena.alloc(new_value),
-                            arms: arena.alloc_slice_clone(&new_arms),
-                            span: match_expr.span,
-                        });
-                }
-                vc || ac
-            }
-            ExpressionKind::Arrow(arrow) => {
-                let mut new_arrow = arrow.clone();
-                let mut changed = false;
-                let mut new_params: Vec<_> = new_arrow.parameters.to_vec();
-                let mut pc = false;
-                for param in &mut new_params {
-                    if let Some(default) = &mut param.default {
-                        pc |= self.inline_in_expression(default, arena);
-                    }
-                }
-                if pc {
-                    new_arrow.parameters = arena.alloc_slice_clone(&new_params);
-                    changed = true;
-                }
-                match &mut new_arrow.body {
-                    ArrowBody::Expression(expr_ref) => {
-                        let mut new_expr = (**expr_ref).clone();
-                        if self.inline_in_expression(&mut new_expr, arena) {
-                            new_arrow.body = ArrowBody::Expression(arena.alloc(new_expr));
-                            changed = true;
-                        }
-                    }
-                    ArrowBody::Block(block) => {
-                        changed |= self.inline_in_block(block, arena);
-                    }
-                }
-                if changed {
-                    expr.kind = ExpressionKind::Arrow(new_arrow);
-                }
-                changed
-            }
-            ExpressionKind::New(callee_ref, args_ref, type_args) => {
-                let type_args = *type_args;
-                let mut new_callee = (**callee_ref).clone();
-                let cc = self.inline_in_expression(&mut new_callee, arena);
-                let mut new_args: Vec<_> = args_ref.to_vec();
-                let mut ac = false;
-                for arg in &mut new_args {
-                    ac |= self.inline_in_expression(&mut arg.value, arena);
-                }
-                if cc || ac {
-                    expr.kind = ExpressionKind::New(
-                        arena.alloc(new_callee),
-                        arena.alloc_slice_clone(&new_args),
-                        type_args,
-                    );
-                }
-                cc || ac
-            }
-            ExpressionKind::Try(try_expr) => {
-                let mut new_expression = (*try_expr.expression).clone();
-                let mut new_catch = (*try_expr.catch_expression).clone();
-                let ec = self.inline_in_expression(&mut new_expression, arena);
-                let cc = self.inline_in_expression(&mut new_catch, arena);
-                if ec || cc {
-                    expr.kind =
-                        ExpressionKind::Try(typedlua_parser::ast::expression::TryExpression {
-                            expression: arena.alloc(new_expression),
-                            catch_variable: try_expr.catch_variable.clone(),
-                            catch_expression: arena.alloc(new_catch),
-                            span: try_expr.span,
-                        });
-                }
-                ec || cc
-            }
-            ExpressionKind::ErrorChain(left_ref, right_ref) => {
-                let mut new_left = (**left_ref).clone();
-                let mut new_right = (**right_ref).clone();
-                let lc = self.inline_in_expression(&mut new_left, arena);
-                let rc = self.inline_in_expression(&mut new_right, arena);
-                if lc || rc {
-                    expr.kind =
-                        ExpressionKind::ErrorChain(arena.alloc(new_left), arena.alloc(new_right));
-                }
-                lc || rc
-            }
-            ExpressionKind::OptionalMember(obj_ref, member) => {
-                let member = member.clone();
-                let mut new_obj = (**obj_ref).clone();
-                let changed = self.inline_in_expression(&mut new_obj, arena);
-                if changed {
-                    expr.kind = ExpressionKind::OptionalMember(arena.alloc(new_obj), member);
-                }
-                changed
-            }
-            ExpressionKind::OptionalIndex(obj_ref, index_ref) => {
-                let mut new_obj = (**obj_ref).clone();
-                let mut new_index = (**index_ref).clone();
-                let oc = self.inline_in_expression(&mut new_obj, arena);
-                let ic = self.inline_in_expression(&mut new_index, arena);
-                if oc || ic {
-                    expr.kind =
-                        ExpressionKind::OptionalIndex(arena.alloc(new_obj), arena.alloc(new_index));
-                }
-                oc || ic
-            }
-            ExpressionKind::OptionalCall(obj_ref, args_ref, type_args) => {
-                let type_args = *type_args;
-                let mut new_obj = (**obj_ref).clone();
-                let oc = self.inline_in_expression(&mut new_obj, arena);
-                let mut new_args: Vec<_> = args_ref.to_vec();
-                let mut ac = false;
-                for arg in &mut new_args {
-                    ac |= self.inline_in_expression(&mut arg.value, arena);
-                }
-                if oc || ac {
-                    expr.kind = ExpressionKind::OptionalCall(
-                        arena.alloc(new_obj),
-                        arena.alloc_slice_clone(&new_args),
-                        type_args,
-                    );
-                }
-                oc || ac
-            }
-            ExpressionKind::OptionalMethodCall(obj_ref, method, args_ref, type_args) => {
-                let method = method.clone();
-                let type_args = *type_args;
-                let mut new_obj = (**obj_ref).clone();
-                let oc = self.inline_in_expression(&mut new_obj, arena);
-                let mut new_args: Vec<_> = args_ref.to_vec();
-                let mut ac = false;
-                for arg in &mut new_args {
-                    ac |= self.inline_in_expression(&mut arg.value, arena);
-                }
-                if oc || ac {
-                    expr.kind = ExpressionKind::OptionalMethodCall(
-                        arena.alloc(new_obj),
-                        method,
-                        arena.alloc_slice_clone(&new_args),
-                        type_args,
-                    );
-                }
-                oc || ac
-            }
-            ExpressionKind::TypeAssertion(inner_ref, ty) => {
-                let ty = ty.clone();
-                let mut new_inner = (**inner_ref).clone();
-                let changed = self.inline_in_expression(&mut new_inner, arena);
-                if changed {
-                    expr.kind = ExpressionKind::TypeAssertion(arena.alloc(new_inner), ty);
-                }
-                changed
-            }
-            ExpressionKind::Member(obj_ref, member) => {
-                let member = member.clone();
-                let mut new_obj = (**obj_ref).clone();
-                let changed = self.inline_in_expression(&mut new_obj, arena);
-                if changed {
-                    expr.kind = ExpressionKind::Member(arena.alloc(new_obj), member);
-                }
-                changed
-            }
-            ExpressionKind::Index(obj_ref, index_ref) => {
-                let mut new_obj = (**obj_ref).clone();
-                let mut new_index = (**index_ref).clone();
-                let oc = self.inline_in_expression(&mut new_obj, arena);
-                let ic = self.inline_in_expression(&mut new_index, arena);
-                if oc || ic {
-                    expr.kind = ExpressionKind::Index(arena.alloc(new_obj), arena.alloc(new_index));
-                }
-                oc || ic
-            }
-            _ => false,
-        }
+impl<'arena> MutVisitor<'arena> for FunctionInliningPass<'arena> {
+    fn visit_block(&mut self, _stmts: &mut Vec<Statement<'arena>>, _arena: &'arena Bump) -> bool {
+        // Nested statements get their own `visit_stmt` call from the
+        // composite pass
+        false
     }
 
-    fn try_inline_call(
-        &mut self,
-        expr: &mut Expression<'arena>,
-        arena: &'arena Bump,
-    ) -> Option<InlineResult<'arena>> {
-        if let ExpressionKind::Call(func, args, _) = &expr.kind.clone() {
-            if let ExpressionKind::Identifier(func_name) = &func.kind {
-                if let Some(func_decl) = self.find_function_definition(expr, *func_name) {
-                    if self.is_inlinable(func_decl) {
-                        let args_vec: Vec<_> = args.to_vec();
-                        let result = self.inline_call(func_decl.clone(), &args_vec, arena);
-                        // Replace the call expression based on the inline result
-                        match &result {
-                            InlineResult::Direct(inlined_expr) => {
-                                // Direct substitution - replace call with the inlined expression
-                                *expr = *inlined_expr.clone();
-                            }
-                            InlineResult::Replaced { result_var, .. } => {
-                                // Reference the result variable
-                                expr.kind = ExpressionKind::Identifier(*result_var);
-                            }
-                        }
-                        return Some(result);
-                    }
-                }
-            }
-        }
-        None
+    fn visit_expression(&mut self, expr: &mut Expression<'arena>, arena: &'arena Bump) -> bool {
+        let changed = walk_expression(self, expr, arena);
+        self.substitute_call(expr, arena) || changed
     }
+}
 
-    fn find_function_definition(
-        &self,
-        _expr: &Expression<'arena>,
-        name: StringId,
-    ) -> Option<&FunctionDeclaration<'arena>> {
-        self.functions.get(&name)
+impl<'arena> FunctionInliningPass<'arena> {
+    /// `name(args)` calling a collected function
+    fn call(&self, expr: &Expression<'arena>) -> Option<(StringId, &'arena [Argument<'arena>])> {
+        let ExpressionKind::Call(callee, args, _) = &expr.kind else {
+            return None;
+        };
+        let ExpressionKind::Identifier(name) = callee.kind else {
+            return None;
+        };
+        self.functions.contains_key(&name).then_some((name, *args))
     }
 
-    fn is_inlinable(&self, func: &FunctionDeclaration<'arena>) -> bool {
-        // Skip generic functions - let GenericSpecializationPass handle them first
-        if func.type_parameters.is_some() {
+    /// Replaces a call to a function whose body is `return expr` by `expr`,
+    /// when every argument is a name or a literal
+    fn substitute_call(&mut self, expr: &mut Expression<'arena>, arena: &'arena Bump) -> bool {
+        let Some((name, args)) = self.call(expr) else {
             return false;
-        }
-        if func.body.statements.len() > self.threshold {
+        };
+        if self.cold_site(expr.span).is_some() {
             return false;
         }
-        if self.is_recursive(func) {
+        let Ok(callee) = self.callee(name, arena) else {
             return false;
-        }
-        if self.has_complex_control_flow(&func.body) {
+        };
+        let Some(result) = &callee.result else {
+            return false;
+        };
+        let simple = |arg: &Argument<'_>| {
+            !arg.is_spread
+                && matches!(
+                    arg.value.kind,
+                    ExpressionKind::Identifier(_) | ExpressionKind::Literal(_)
+                )
+        };
+        if !callee.body.is_empty()
+            || args.len() != callee.parameters.len()
+            || !args.iter().all(simple)
+        {
             return false;
         }
-        if self.has_closures(&func.body) {
+        let mut value = result.clone();
+        let mut assignments = Assignments::default();
+        assignments.visit_expression(&mut value, arena);
+        if assignments.0 {
             return false;
         }
+
+        let values: HashMap<StringId, Expression<'arena>> = callee
+            .parameters
+            .iter()
+            .copied()
+            .zip(args.iter().map(|arg| arg.value.clone()))
+            .collect();
+        Substituter { values: &values }.visit_expression(&mut value, arena);
+        self.report_inlined(name, expr.span);
+        expr.kind = value.kind;
         true
     }
 
-    fn is_recursive(&self, func: &FunctionDeclaration<'arena>) -> bool {
-        let name = func.name.node;
-        for stmt in func.body.statements.iter() {
-            if self.contains_call_to(stmt, name) {
-                return true;
+    /// The block replacing `stmt` when it is a call to an inlinable function
+    fn inline_statement(
+        &mut self,
+        stmt: &Statement<'arena>,
+        arena: &'arena Bump,
+    ) -> Option<Statement<'arena>> {
+        let (statements, span) = match stmt {
+            // local x = f(...)
+            Statement::Variable(decl) if matches!(decl.pattern, Pattern::Identifier(_)) => {
+                let (name, callee, args) = self.inlinable_call(&decl.initializer, arena)?;
+                let stmt_span = decl.span;
+                let span = decl.initializer.span;
+                let (mut statements, result) = self.expand(&callee, args, span, arena);
+                let mut decl = decl.clone();
+                decl.initializer = result.unwrap_or_else(|| {
+                    Expression::new(ExpressionKind::Literal(Literal::Nil), span)
+                });
+                statements.push(Statement::Variable(decl));
+                self.report_inlined(name, span);
+                (statements, stmt_span)
             }
-        }
-        false
+            // f(...)
+            Statement::Expression(expr) => {
+                // The result is dropped; a call in it still has to run
+                let (name, callee, args) = self.inlinable_call(expr, arena)?;
+                let discardable = callee.result.iter().all(|value| {
+                    matches!(
+                        value.kind,
+                        ExpressionKind::Literal(_)
+                            | ExpressionKind::Identifier(_)
+                            | ExpressionKind::Call(..)
+                            | ExpressionKind::MethodCall(..)
+                    )
+                });
+                if !discardable {
+                    return None;
+                }
+                let (mut statements, result) = self.expand(&callee, args, expr.span, arena);
+                statements.extend(
+                    result
+                        .filter(|value| {
+                            matches!(
+                                value.kind,
+                                ExpressionKind::Call(..) | ExpressionKind::MethodCall(..)
+                            )
+                        })
+                        .map(Statement::Expression),
+                );
+                self.report_inlined(name, expr.span);
+                (statements, expr.span)
+            }
+            // return f(...)
+            Statement::Return(ret) if ret.values.len() == 1 => {
+                let (name, callee, args) = self.inlinable_call(&ret.values[0], arena)?;
+                let (mut statements, result) = self.expand(&callee, args, ret.span, arena);
+                let values: Vec<_> = result.into_iter().collect();
+                statements.push(Statement::Return(ReturnStatement {
+                    values: arena.alloc_slice_clone(&values),
+                    span: ret.span,
+                }));
+                self.report_inlined(name, ret.values[0].span);
+                (statements, ret.span)
+            }
+            _ => return None,
+        };
+        Some(Statement::Block(Block {
+            statements: arena.alloc_slice_clone(&statements),
+            span,
+        }))
     }
 
-    fn contains_call_to(&self, stmt: &Statement<'arena>, name: StringId) -> bool {
-        match stmt {
-            Statement::Expression(expr) => self.expr_contains_call_to(expr, name),
-            Statement::Variable(decl) => self.expr_contains_call_to(&decl.initializer, name),
-            Statement::Return(ret) => ret
-                .values
-                .iter()
-                .any(|e| self.expr_contains_call_to(e, name)),
-            Statement::If(if_stmt) => {
-                self.expr_contains_call_to(&if_stmt.condition, name)
-                    || if_stmt
-                        .then_block
-                        .statements
-                        .iter()
-                        .any(|s| self.contains_call_to(s, name))
-                    || if_stmt.else_ifs.iter().any(|ei| {
-                        self.expr_contains_call_to(&ei.condition, name)
-                            || ei
-                                .block
-                                .statements
-                                .iter()
-                                .any(|s| self.contains_call_to(s, name))
-                    })
-                    || if_stmt.else_block.as_ref().is_some_and(|eb| {
-                        eb.statements.iter().any(|s| self.contains_call_to(s, name))
-                    })
-            }
-            Statement::While(while_stmt) => {
-                self.expr_contains_call_to(&while_stmt.condition, name)
-                    || while_stmt
-                        .body
-                        .statements
-                        .iter()
-                        .any(|s| self.contains_call_to(s, name))
-            }
-            Statement::For(for_stmt) => {
-                let stmts = match &**for_stmt {
-                    ForStatement::Numeric(for_num) => for_num.body.statements,
-                    ForStatement::Generic(for_gen) => for_gen.body.statements,
-                };
-                stmts.iter().any(|s| self.contains_call_to(s, name))
-            }
-            _ => false,
-        }
+    /// The function and arguments of `name(args)` when the call can be
+    /// inlined; reports why not otherwise
+    fn inlinable_call(
+        &mut self,
+        expr: &Expression<'arena>,
+        arena: &'arena Bump,
+    ) -> Option<(StringId, Callee<'arena>, &'arena [Argument<'arena>])> {
+        let (name, args) = self.call(expr)?;
+        let callee = match self.cold_site(expr.span) {
+            Some(reason) => Err(reason.to_string()),
+            None => self.callee(name, arena),
+        };
+        let reason = match callee {
+            Ok(callee)
+                if args.len() == callee.parameters.len() && !args.iter().any(|a| a.is_spread) =>
+            {
+                return Some((name, callee, args));
+            }
+            Ok(callee) => format!(
+                "{} arguments for {} parameters",
+                args.len(),
+                callee.parameters.len()
+            ),
+            Err(reason) => reason,
+        };
+        self.remarks.missed(
+            self.pass,
+            expr.span,
+            || format!("not inlined {}", self.describe_call(name, expr.span)),
+            || reason,
+        );
+        None
     }
 
-    fn expr_contains_call_to(&self, expr: &Expression<'arena>, name: StringId) -> bool {
-        match &expr.kind {
-            ExpressionKind::Call(func, args, _) => {
-                if let ExpressionKind::Identifier(id) = &func.kind {
-                    if *id == name {
-                        return true;
-                    }
-                }
-                self.expr_contains_call_to(func, name)
-                    || args
-                        .iter()
-                        .any(|a| self.expr_contains_call_to(&a.value, name))
-            }
-            ExpressionKind::MethodCall(obj, method_name, args, _) => {
-                if method_name.node == name {
-                    return true;
-                }
-                self.expr_contains_call_to(obj, name)
-                    || args
-                        .iter()
-                        .any(|a| self.expr_contains_call_to(&a.value, name))
-            }
-            ExpressionKind::Binary(_, left, right) => {
-                self.expr_contains_call_to(left, name) || self.expr_contains_call_to(right, name)
-            }
-            ExpressionKind::Unary(_, operand) => self.expr_contains_call_to(operand, name),
-            ExpressionKind::Conditional(cond, then_expr, else_expr) => {
-                self.expr_contains_call_to(cond, name)
-                    || self.expr_contains_call_to(then_expr, name)
-                    || self.expr_contains_call_to(else_expr, name)
-            }
-            ExpressionKind::Arrow(arrow) => {
-                for param in arrow.parameters.iter() {
-                    if let Some(default) = &param.default {
-                        if self.expr_contains_call_to(default, name) {
-                            return true;
-                        }
-                    }
-                }
-                match &arrow.body {
-                    ArrowBody::Expression(expr) => self.expr_contains_call_to(expr, name),
-                    ArrowBody::Block(block) => block
-                        .statements
-                        .iter()
-                        .any(|s| self.contains_call_to(s, name)),
-                }
-            }
-            ExpressionKind::Match(match_expr) => {
-                self.expr_contains_call_to(match_expr.value, name)
-                    || match_expr.arms.iter().any(|arm| match &arm.body {
-                        MatchArmBody::Expression(expr) => self.expr_contains_call_to(expr, name),
-                        MatchArmBody::Block(block) => block
-                            .statements
-                            .iter()
-                            .any(|s| self.contains_call_to(s, name)),
-                    })
-            }
-            ExpressionKind::New(callee, args, _) => {
-                self.expr_contains_call_to(callee, name)
-                    || args
-                        .iter()
-                        .any(|a| self.expr_contains_call_to(&a.value, name))
-            }
-            ExpressionKind::Try(try_expr) => {
-                self.expr_contains_call_to(try_expr.expression, name)
-                    || self.expr_contains_call_to(try_expr.catch_expression, name)
-            }
-            ExpressionKind::ErrorChain(left, right) => {
-                self.expr_contains_call_to(left, name) || self.expr_contains_call_to(right, name)
-            }
-            ExpressionKind::OptionalMember(obj, _) => self.expr_contains_call_to(obj, name),
-            ExpressionKind::OptionalIndex(obj, index) => {
-                self.expr_contains_call_to(obj, name) || self.expr_contains_call_to(index, name)
-            }
-            ExpressionKind::OptionalCall(obj, args, _) => {
-                self.expr_contains_call_to(obj, name)
-                    || args
-                        .iter()
-                        .any(|a| self.expr_contains_call_to(&a.value, name))
-            }
-            ExpressionKind::OptionalMethodCall(obj, _, args, _) => {
-                self.expr_contains_call_to(obj, name)
-                    || args
-                        .iter()
-                        .any(|a| self.expr_contains_call_to(&a.value, name))
-            }
-            ExpressionKind::TypeAssertion(expr, _) => self.expr_contains_call_to(expr, name),
-            ExpressionKind::Member(obj, _) => self.expr_contains_call_to(obj, name),
-            ExpressionKind::Index(obj, index) => {
-                self.expr_contains_call_to(obj, name) || self.expr_contains_call_to(index, name)
-            }
-            _ => false,
+    /// Why calls at `span` are left alone, when they are
+    fn cold_site(&self, span: Span) -> Option<&'static str> {
+        if self
+            .cold
+            .iter()
+            .any(|method| method.start <= span.start && span.end <= method.end)
+        {
+            Some("call site is in a @cold method")
+        } else if self.counts.is_cold(span) {
+            Some("call site is cold in the profile")
+        } else {
+            None
         }
     }
 
-    fn has_complex_control_flow(&self, body: &Block<'arena>) -> bool {
-        for stmt in body.statements.iter() {
-            if self.stmt_has_complex_flow(stmt) {
-                return true;
-            }
+    /// `name`'s declaration in the shape the inliner copies, or why it cannot
+    /// be inlined
+    fn callee(&self, name: StringId, arena: &'arena Bump) -> Result<Callee<'arena>, String> {
+        let func = &self.functions[&name];
+        // Skip generic functions - let GenericSpecializationPass handle them first
+        if func.type_parameters.is_some() {
+            return Err("generic functions are specialized first".to_string());
+        }
+        if !self.census.sole_function(name) {
+            return Err("the function's name is rebound".to_string());
+        }
+        let len = func.body.statements.len();
+        if len > self.threshold {
+            return Err(format!("body has {} statements > {}", len, self.threshold));
         }
-        false
-    }
 
-    fn stmt_has_complex_flow(&self, stmt: &Statement<'arena>) -> bool {
-        match stmt {
-            Statement::If(if_stmt) => {
-                if self.block_has_multiple_returns(&if_stmt.then_block) {
-                    return true;
-                }
-                for else_if in if_stmt.else_ifs.iter() {
-                    if self.block_has_multiple_returns(&else_if.block) {
-                        return true;
-                    }
+        let mut parameters = Vec::new();
+        for param in func.parameters.iter() {
+            match &param.pattern {
+                Pattern::Identifier(ident) if param.default.is_none() && !param.is_rest => {
+                    parameters.push(ident.node)
                 }
-                if let Some(else_block) = &if_stmt.else_block {
-                    if self.block_has_multiple_returns(else_block) {
-                        return true;
-                    }
-                }
-                false
+                _ => return Err("parameters are not plain names".to_string()),
             }
-            Statement::While(_) | Statement::For(_) | Statement::Repeat(_) => true,
-            _ => false,
         }
-    }
 
-    fn block_has_multiple_returns(&self, block: &Block<'arena>) -> bool {
-        let mut return_count = 0;
-        for stmt in block.statements.iter() {
-            if matches!(stmt, Statement::Return(_)) {
-                return_count += 1;
-                if return_count > 1 {
-                    return true;
+        let mut statements = func.body.statements.to_vec();
+        let mut result = match statements.last() {
+            Some(Statement::Return(ret)) if ret.values.len() > 1 => {
+                return Err("body returns several values".to_string());
+            }
+            Some(Statement::Return(ret)) => {
+                let value = ret.values.first().cloned();
+                statements.pop();
+                value
+            }
+            _ => None,
+        };
+
+        // Locals must be declared once and read only after their declaration,
+        // so renaming them leaves every read on the same binding
+        let mut locals = Vec::new();
+        let mut read = HashSet::default();
+        for stmt in statements.iter_mut() {
+            let mut identifiers = Identifiers::default();
+            let declared = match stmt {
+                Statement::Variable(decl) => {
+                    let Pattern::Identifier(ident) = &decl.pattern else {
+                        return Err("body destructures".to_string());
+                    };
+                    identifiers.visit_expression(&mut decl.initializer, arena);
+                    Some(ident.node)
+                }
+                Statement::Expression(expr) => {
+                    identifiers.visit_expression(expr, arena);
+                    None
                 }
+                _ => return Err("body has loops, branches or early returns".to_string()),
+            };
+            read.extend(identifiers.0);
+            if let Some(local) = declared {
+                if read.contains(&local) || locals.contains(&local) || parameters.contains(&local) {
+                    return Err(format!(
+                        "local `{}` shadows another name",
+                        self.interner.resolve(local)
+                    ));
+                }
+                locals.push(local);
             }
         }
-        false
-    }
-
-    fn has_closures(&self, body: &Block<'arena>) -> bool {
-        self.block_has_closures(body)
-    }
-
-    fn block_has_closures(&self, block: &Block<'arena>) -> bool {
-        for stmt in block.statements.iter() {
-            if self.stmt_has_closures(stmt) {
-                return true;
-            }
+        let mut shape = ShapeCheck::default();
+        for stmt in statements.iter_mut() {
+            shape.visit_statement(stmt, arena);
         }
-        false
-    }
-
-    fn stmt_has_closures(&self, stmt: &Statement<'arena>) -> bool {
-        match stmt {
-            Statement::Function(func) => self.block_has_closures(&func.body),
-            Statement::Variable(decl) => self.expr_has_closures(&decl.initializer),
-            Statement::Expression(expr) => self.expr_has_closures(expr),
-            Statement::If(if_stmt) => {
-                self.expr_has_closures(&if_stmt.condition)
-                    || self.block_has_closures(&if_stmt.then_block)
-                    || if_stmt.else_ifs.iter().any(|ei| {
-                        self.expr_has_closures(&ei.condition) || self.block_has_closures(&ei.block)
-                    })
-                    || if_stmt
-                        .else_block
-                        .as_ref()
-                        .is_some_and(|eb| self.block_has_closures(eb))
-            }
-            Statement::While(while_stmt) => {
-                self.expr_has_closures(&while_stmt.condition)
-                    || self.block_has_closures(&while_stmt.body)
-            }
-            Statement::For(for_stmt) => {
-                let body = match &**for_stmt {
-                    ForStatement::Numeric(for_num) => &for_num.body,
-                    ForStatement::Generic(for_gen) => &for_gen.body,
-                };
-                self.block_has_closures(body)
-            }
-            Statement::Return(ret) => ret.values.iter().any(|e| self.expr_has_closures(e)),
-            _ => false,
+        if let Some(value) = &mut result {
+            shape.visit_expression(value, arena);
+            let mut identifiers = Identifiers::default();
+            identifiers.visit_expression(value, arena);
+            read.extend(identifiers.0);
+        }
+        if shape.nested_functions {
+            return Err("body creates closures or matches".to_string());
+        }
+        if read.contains(&name) {
+            return Err("function is recursive".to_string());
         }
-    }
 
-    fn expr_has_closures(&self, expr: &Expression<'arena>) -> bool {
-        match &expr.kind {
-            ExpressionKind::Function(func) => self.block_has_closures(&func.body),
-            ExpressionKind::Arrow(arrow) => match &arrow.body {
-                ArrowBody::Expression(expr) => self.expr_has_closures(expr),
-                ArrowBody::Block(block) => self.block_has_closures(block),
-            },
-            ExpressionKind::Call(func, args, _) => {
-                self.expr_has_closures(func)
-                    || args.iter().any(|a| self.expr_has_closures(&a.value))
+        // Every other name must mean at the call site what it means here
+        for &free in read
+            .iter()
+            .filter(|name| !parameters.contains(name) && !locals.contains(name))
+        {
+            if self.census.binds(free) {
+                return Err(format!(
+                    "body reads `{}`, which the module binds",
+                    self.interner.resolve(free)
+                ));
             }
-            ExpressionKind::MethodCall(obj, _, args, _) => {
-                self.expr_has_closures(obj) || args.iter().any(|a| self.expr_has_closures(&a.value))
-            }
-            ExpressionKind::Binary(_, left, right) => {
-                self.expr_has_closures(left) || self.expr_has_closures(right)
-            }
-            ExpressionKind::Unary(_, operand) => self.expr_has_closures(operand),
-            ExpressionKind::Conditional(cond, then_expr, else_expr) => {
-                self.expr_has_closures(cond)
-                    || self.expr_has_closures(then_expr)
-                    || self.expr_has_closures(else_expr)
-            }
-            ExpressionKind::Pipe(left, right) => {
-                self.expr_has_closures(left) || self.expr_has_closures(right)
-            }
-            ExpressionKind::Match(match_expr) => {
-                self.expr_has_closures(match_expr.value)
-                    || match_expr.arms.iter().any(|arm| match &arm.body {
-                        MatchArmBody::Expression(expr) => self.expr_has_closures(expr),
-                        MatchArmBody::Block(block) => self.block_has_closures(block),
-                    })
-            }
-            ExpressionKind::New(callee, args, _) => {
-                self.expr_has_closures(callee)
-                    || args.iter().any(|a| self.expr_has_closures(&a.value))
-            }
-            ExpressionKind::Try(try_expr) => {
-                self.expr_has_closures(try_expr.expression)
-                    || self.expr_has_closures(try_expr.catch_expression)
-            }
-            ExpressionKind::ErrorChain(left, right) => {
-                self.expr_has_closures(left) || self.expr_has_closures(right)
-            }
-            ExpressionKind::OptionalMember(obj, _) => self.expr_has_closures(obj),
-            ExpressionKind::OptionalIndex(obj, index) => {
-                self.expr_has_closures(obj) || self.expr_has_closures(index)
-            }
-            ExpressionKind::OptionalCall(obj, args, _) => {
-                self.expr_has_closures(obj) || args.iter().any(|a| self.expr_has_closures(&a.value))
-            }
-            ExpressionKind::OptionalMethodCall(obj, _, args, _) => {
-                self.expr_has_closures(obj) || args.iter().any(|a| self.expr_has_closures(&a.value))
-            }
-            ExpressionKind::TypeAssertion(expr, _) => self.expr_has_closures(expr),
-            ExpressionKind::Member(obj, _) => self.expr_has_closures(obj),
-            ExpressionKind::Index(obj, index) => {
-                self.expr_has_closures(obj) || self.expr_has_closures(index)
-            }
-            _ => false,
         }
+
+        Ok(Callee {
+            parameters,
+            body: statements,
+            result,
+            locals,
+        })
     }
 
-    fn inline_call(
+    /// Parameters bound to the arguments, then the body, over fresh names;
+    /// returns the statements and the renamed `return` value
+    fn expand(
         &mut self,
-        func: FunctionDeclaration<'arena>,
+        callee: &Callee<'arena>,
         args: &[Argument<'arena>],
+        span: Span,
         arena: &'arena Bump,
-    ) -> InlineResult<'arena> {
-        let param_subst = self.create_parameter_substitution(func.parameters, args);
-
-        // Check for simple single-return function: just `return expr`
-        if func.body.statements.len() == 1 {
-            if let Statement::Return(ret) = &func.body.statements[0] {
-                if ret.values.len() == 1 {
-                    // Simple case: directly substitute the return expression
-                    let mut inlined_expr = ret.values[0].clone();
-                    self.inline_expression(&mut inlined_expr, &param_subst, arena);
-                    return InlineResult::Direct(Box::new(inlined_expr));
-                }
-            }
-        }
-
-        // Complex case: create intermediate variable
-        let mut inlined_body = Vec::new();
-        let return_var = self.create_temp_variable();
-        let has_return = self.has_return_value(&func.body);
-
-        let body_stmts: Vec<_> = func.body.statements.to_vec();
-        for stmt in &body_stmts {
-            let inlined_stmt =
-                self.inline_statement(stmt, &param_subst, &return_var, has_return, arena);
-            inlined_body.push(inlined_stmt);
+    ) -> (Vec<Statement<'arena>>, Option<Expression<'arena>>) {
+        let mut names = HashMap::default();
+        for &name in callee.parameters.iter().chain(&callee.locals) {
+            let fresh = format!(
+                "_inline_{}_{}",
+                self.interner.resolve(name),
+                self.next_temp_id
+            );
+            self.next_temp_id += 1;
+            names.insert(name, self.interner.get_or_intern(&fresh));
         }
+        let mut renamer = Renamer { names: &names };
+
+        let mut statements: Vec<Statement<'arena>> = callee
+            .parameters
+            .iter()
+            .zip(args)
+            .map(|(param, arg)| {
+                Statement::Variable(VariableDeclaration {
+                    kind: VariableKind::Local,
+                    pattern: Pattern::Identifier(Spanned::new(names[param], span)),
+                    type_annotation: None,
+                    initializer: arg.value.clone(),
+                    span,
+                })
+            })
+            .collect();
+        let mut body = callee.body.clone();
+        renamer.visit_block(&mut body, arena);
+        statements.extend(body);
+        let result = callee.result.clone().map(|mut value| {
+            renamer.visit_expression(&mut value, arena);
+            value
+        });
+        (statements, result)
+    }
 
-        InlineResult::Replaced {
-            stmts: inlined_body,
-            result_var: return_var,
-        }
+    fn report_inlined(&self, name: StringId, span: Span) {
+        self.remarks.applied(self.pass, span, || {
+            format!("inlined {}", self.describe_call(name, span))
+        });
     }
 
-    fn create_parameter_substitution(
-        &self,
-        parameters: &[Parameter<'arena>],
-        args: &[Argument<'arena>],
-    ) -> HashMap<StringId, Expression<'arena>> {
-        let mut subst = HashMap::default();
-        for (param, arg) in parameters.iter().zip(args.iter()) {
-            if let Pattern::Identifier(ident) = &param.pattern {
-                subst.insert(ident.node, arg.value.clone());
-            }
+    /// "`callee` into `caller`" for remarks, naming the innermost function
+    /// around the call
+    fn describe_call(&self, callee: StringId, span: Span) -> String {
+        let caller = self
+            .bodies
+            .iter()
+            .filter(|(body, _)| body.start <= span.start && span.end <= body.end)
+            .min_by_key(|(body, _)| body.end - body.start)
+            .map(|(_, name)| *name);
+        match caller {
+            Some(caller) => format!(
+                "`{}` into `{}`",
+                self.interner.resolve(callee),
+                self.interner.resolve(caller)
+            ),
+            None => format!("`{}` at top level", self.interner.resolve(callee)),
         }
-        subst
     }
+}
 
-    #[cold]
-    fn unreachable_interner(&self) -> ! {
-        unsafe { std::hint::unreachable_unchecked() }
-    }
+/// Every function a `function` statement declares, with its span, and the
+/// span, class and name of every method
+#[derive(Default)]
+struct FunctionCollector<'arena> {
+    functions: HashMap<StringId, FunctionDeclaration<'arena>>,
+    bodies: Vec<(Span, StringId)>,
+    methods: Vec<(Span, StringId, StringId)>,
+}
 
-    fn create_temp_variable(&mut self) -> StringId {
-        let name = format!("_inline_result_{}", self.next_temp_id);
-        self.next_temp_id += 1;
-        debug_assert!(
-            self.interner.is_some(),
-            "String interner not set for FunctionInliningPass"
-        );
-        match &self.interner {
-            Some(interner) => interner.get_or_intern(&name),
-            None => self.unreachable_interner(),
+impl<'arena> MutVisitor<'arena> for FunctionCollector<'arena> {
+    fn visit_statement(&mut self, stmt: &mut Statement<'arena>, arena: &'arena Bump) -> bool {
+        if let Statement::Function(func) = stmt {
+            self.functions.insert(func.name.node, func.clone());
+            self.bodies.push((func.span, func.name.node));
         }
-    }
-
-    fn has_return_value(&self, body: &Block<'arena>) -> bool {
-        for stmt in body.statements.iter() {
-            if let Statement::Return(ret) = stmt {
-                if !ret.values.is_empty() {
-                    return true;
+        if let Statement::Class(class) = stmt {
+            for member in class.members.iter() {
+                if let ClassMember::Method(method) = member {
+                    self.methods
+                        .push((method.span, class.name.node, method.name.node));
                 }
             }
         }
-        false
+        walk_statement(self, stmt, arena)
     }
+}
 
-    fn inline_statement(
-        &self,
-        stmt: &Statement<'arena>,
-        param_subst: &HashMap<StringId, Expression<'arena>>,
-        return_var: &StringId,
-        has_return: bool,
-        arena: &'arena Bump,
-    ) -> Statement<'arena> {
-        match stmt {
-            Statement::Variable(decl) => {
-                let mut new_decl = decl.clone();
-                self.inline_expression(&mut new_decl.initializer, param_subst, arena);
-                Statement::Variable(new_decl)
-            }
-            Statement::Expression(expr) => {
-                let mut new_expr = expr.clone();
-                self.inline_expression(&mut new_expr, param_subst, arena);
-                Statement::Expression(new_expr)
-            }
-            Statement::Return(ret) => {
-                if !ret.values.is_empty() && has_return {
-                    let values: Vec<Expression<'arena>> = ret
-                        .values
-                        .iter()
-                        .map(|v| {
-                            let mut substituted = v.clone();
-                            self.inline_expression(&mut substituted, param_subst, arena);
-                            substituted
-                        })
-                        .collect();
-                    let initializer_kind = if values.len() == 1 {
-                        values[0].kind.clone()
-                    } else {
-                        ExpressionKind::Array(
-                            arena.alloc_slice_clone(
-                                &values
-                                    .iter()
-                                    .map(|e| ArrayElement::Expression(e.clone()))
-                                    .collect::<Vec<_>>(),
-                            ),
-                        )
-                    };
-                    Statement::Variable(VariableDeclaration {
-                        kind: VariableKind::Local,
-                        pattern: Pattern::Identifier(typedlua_parser::ast::Spanned::new(
-                            *return_var,
-                            Span::dummy(),
-                        )),
-                        type_annotation: None,
-                        initializer: Expression::new(initializer_kind, Span::dummy()),
-                        span: Span::dummy(),
-                    })
-                } else {
-                    Statement::Return(ret.clone())
-                }
+/// Replaces reads of parameters by the arguments of the call
+struct Substituter<'p, 'arena> {
+    values: &'p HashMap<StringId, Expression<'arena>>,
+}
+
+impl<'arena> MutVisitor<'arena> for Substituter<'_, 'arena> {
+    fn visit_expression(&mut self, expr: &mut Expression<'arena>, arena: &'arena Bump) -> bool {
+        if let ExpressionKind::Identifier(name) = expr.kind {
+            if let Some(value) = self.values.get(&name) {
+                expr.kind = value.kind.clone();
+                return true;
             }
-            _ => stmt.clone(),
         }
+        walk_expression(self, expr, arena)
     }
+}
 
-    fn inline_expression(
-        &self,
-        expr: &mut Expression<'arena>,
-        param_subst: &HashMap<StringId, Expression<'arena>>,
-        arena: &'arena Bump,
-    ) {
-        match &expr.kind {
-            ExpressionKind::Identifier(id) => {
-                if let Some(substituted) = param_subst.get(id) {
-                    expr.kind = substituted.kind.clone();
-                }
-            }
-            ExpressionKind::Call(func_ref, args_ref, type_args) => {
-                let type_args = *type_args;
-                let mut new_func = (**func_ref).clone();
-                self.inline_expression(&mut new_func, param_subst, arena);
-                let mut new_args: Vec<_> = args_ref.to_vec();
-                for arg in &mut new_args {
-                    self.inline_expression(&mut arg.value, param_subst, arena);
-                }
-                expr.kind = ExpressionKind::Call(
-                    arena.alloc(new_func),
-                    arena.alloc_slice_clone(&new_args),
-                    type_args,
-                );
-            }
-            ExpressionKind::MethodCall(obj_ref, method, args_ref, type_args) => {
-                let method = method.clone();
-                let type_args = *type_args;
-                let mut new_obj = (**obj_ref).clone();
-                self.inline_expression(&mut new_obj, param_subst, arena);
-                let mut new_args: Vec<_> = args_ref.to_vec();
-                for arg in &mut new_args {
-                    self.inline_expression(&mut arg.value, param_subst, arena);
-                }
-                expr.kind = ExpressionKind::MethodCall(
-                    arena.alloc(new_obj),
-                    method,
-                    arena.alloc_slice_clone(&new_args),
-                    type_args,
-                );
-            }
-            ExpressionKind::Binary(op, left_ref, right_ref) => {
-                let op = *op;
-                let mut new_left = (**left_ref).clone();
-                let mut new_right = (**right_ref).clone();
-                self.inline_expression(&mut new_left, param_subst, arena);
-                self.inline_expression(&mut new_right, param_subst, arena);
-                expr.kind =
-                    ExpressionKind::Binary(op, arena.alloc(new_left), arena.alloc(new_right));
-            }
-            ExpressionKind::Unary(op, operand_ref) => {
-                let op = *op;
-                let mut new_operand = (**operand_ref).clone();
-                self.inline_expression(&mut new_operand, param_subst, arena);
-                expr.kind = ExpressionKind::Unary(op, arena.alloc(new_operand));
-            }
-            ExpressionKind::Conditional(cond_ref, then_ref, else_ref) => {
-                let mut new_cond = (**cond_ref).clone();
-                let mut new_then = (**then_ref).clone();
-                let mut new_else = (**else_ref).clone();
-                self.inline_expression(&mut new_cond, param_subst, arena);
-                self.inline_expression(&mut new_then, param_subst, arena);
-                self.inline_expression(&mut new_else, param_subst, arena);
-                expr.kind = ExpressionKind::Conditional(
-                    arena.alloc(new_cond),
-                    arena.alloc(new_then),
-                    arena.alloc(new_else),
-                );
-            }
-            ExpressionKind::Arrow(arrow) => {
-                let mut new_arrow = arrow.clone();
-                let mut new_params: Vec<_> = new_arrow.parameters.to_vec();
-                for param in &mut new_params {
-                    if let Some(default) = &mut param.default {
-                        self.inline_expression(default, param_subst, arena);
-                    }
-                }
-                new_arrow.parameters = arena.alloc_slice_clone(&new_params);
-                match &mut new_arrow.body {
-                    ArrowBody::Expression(expr_ref) => {
-                        let mut new_body_expr = (**expr_ref).clone();
-                        self.inline_expression(&mut new_body_expr, param_subst, arena);
-                        new_arrow.body = ArrowBody::Expression(arena.alloc(new_body_expr));
-                    }
-                    ArrowBody::Block(_) => {}
-                }
-                expr.kind = ExpressionKind::Arrow(new_arrow);
-            }
-            ExpressionKind::Match(match_expr) => {
-                let mut new_value = (*match_expr.value).clone();
-                self.inline_expression(&mut new_value, param_subst, arena);
-                let mut new_arms: Vec<_> = match_expr.arms.to_vec();
-                for arm in &mut new_arms {
-                    match &mut arm.body {
-                        MatchArmBody::Expression(expr_ref) => {
-                            let mut new_expr = (**expr_ref).clone();
-                            self.inline_expression(&mut new_expr, param_subst, arena);
-                            arm.body = MatchArmBody::Expression(arena.alloc(new_expr));
-                        }
-                        MatchArmBody::Block(_) => {}
-                    }
-                }
-                expr.kind =
-                    ExpressionKind::Match(typedlua_parser::ast::expression::MatchExpression {
-                        value: arena.alloc(new_value),
-                        arms: arena.alloc_slice_clone(&new_arms),
-                        span: match_expr.span,
-                    });
-            }
-            ExpressionKind::New(callee_ref, args_ref, type_args) => {
-                let type_args = *type_args;
-                let mut new_callee = (**callee_ref).clone();
-                self.inline_expression(&mut new_callee, param_subst, arena);
-                let mut new_args: Vec<_> = args_ref.to_vec();
-                for arg in &mut new_args {
-                    self.inline_expression(&mut arg.value, param_subst, arena);
-                }
-                expr.kind = ExpressionKind::New(
-                    arena.alloc(new_callee),
-                    arena.alloc_slice_clone(&new_args),
-                    type_args,
-                );
-            }
-            ExpressionKind::Try(try_expr) => {
-                let mut new_expression = (*try_expr.expression).clone();
-                let mut new_catch = (*try_expr.catch_expression).clone();
-                self.inline_expression(&mut new_expression, param_subst, arena);
-                self.inline_expression(&mut new_catch, param_subst, arena);
-                expr.kind = ExpressionKind::Try(typedlua_parser::ast::expression::TryExpression {
-                    expression: arena.alloc(new_expression),
-                    catch_variable: try_expr.catch_variable.clone(),
-                    catch_expression: arena.alloc(new_catch),
-                    span: try_expr.span,
-                });
-            }
-            ExpressionKind::ErrorChain(left_ref, right_ref) => {
-                let mut new_left = (**left_ref).clone();
-                let mut new_right = (**right_ref).clone();
-                self.inline_expression(&mut new_left, param_subst, arena);
-                self.inline_expression(&mut new_right, param_subst, arena);
-                expr.kind =
-                    ExpressionKind::ErrorChain(arena.alloc(new_left), arena.alloc(new_right));
-            }
-            ExpressionKind::OptionalMember(obj_ref, member) => {
-                let member = member.clone();
-                let mut new_obj = (**obj_ref).clone();
-                self.inline_expression(&mut new_obj, param_subst, arena);
-                expr.kind = ExpressionKind::OptionalMember(arena.alloc(new_obj), member);
-            }
-            ExpressionKind::OptionalIndex(obj_ref, index_ref) => {
-                let mut new_obj = (**obj_ref).clone();
-                let mut new_index = (**index_ref).clone();
-                self.inline_expression(&mut new_obj, param_subst, arena);
-                self.inline_expression(&mut new_index, param_subst, arena);
-                expr.kind =
-                    ExpressionKind::OptionalIndex(arena.alloc(new_obj), arena.alloc(new_index));
-            }
-            ExpressionKind::OptionalCall(obj_ref, args_ref, type_args) => {
-                let type_args = *type_args;
-                let mut new_obj = (**obj_ref).clone();
-                self.inline_expression(&mut new_obj, param_subst, arena);
-                let mut new_args: Vec<_> = args_ref.to_vec();
-                for arg in &mut new_args {
-                    self.inline_expression(&mut arg.value, param_subst, arena);
-                }
-                expr.kind = ExpressionKind::OptionalCall(
-                    arena.alloc(new_obj),
-                    arena.alloc_slice_clone(&new_args),
-                    type_args,
-                );
-            }
-            ExpressionKind::OptionalMethodCall(obj_ref, method, args_ref, type_args) => {
-                let method = method.clone();
-                let type_args = *type_args;
-                let mut new_obj = (**obj_ref).clone();
-                self.inline_expression(&mut new_obj, param_subst, arena);
-                let mut new_args: Vec<_> = args_ref.to_vec();
-                for arg in &mut new_args {
-                    self.inline_expression(&mut arg.value, param_subst, arena);
-                }
-                expr.kind = ExpressionKind::OptionalMethodCall(
-                    arena.alloc(new_obj),
-                    method,
-                    arena.alloc_slice_clone(&new_args),
-                    type_args,
-                );
-            }
-            ExpressionKind::TypeAssertion(inner_ref, ty) => {
-                let ty = ty.clone();
-                let mut new_inner = (**inner_ref).clone();
-                self.inline_expression(&mut new_inner, param_subst, arena);
-                expr.kind = ExpressionKind::TypeAssertion(arena.alloc(new_inner), ty);
-            }
-            ExpressionKind::Member(obj_ref, member) => {
-                let member = member.clone();
-                let mut new_obj = (**obj_ref).clone();
-                self.inline_expression(&mut new_obj, param_subst, arena);
-                expr.kind = ExpressionKind::Member(arena.alloc(new_obj), member);
-            }
-            ExpressionKind::Index(obj_ref, index_ref) => {
-                let mut new_obj = (**obj_ref).clone();
-                let mut new_index = (**index_ref).clone();
-                self.inline_expression(&mut new_obj, param_subst, arena);
-                self.inline_expression(&mut new_index, param_subst, arena);
-                expr.kind = ExpressionKind::Index(arena.alloc(new_obj), arena.alloc(new_index));
-            }
-            _ => {}
+/// Whether an expression assigns, which substituting parameters would turn
+/// into assignments to the arguments
+#[derive(Default)]
+struct Assignments(bool);
+
+impl<'arena> MutVisitor<'arena> for Assignments {
+    fn visit_expression(&mut self, expr: &mut Expression<'arena>, arena: &'arena Bump) -> bool {
+        if matches!(expr.kind, ExpressionKind::Assignment(..)) {
+            self.0 = true;
         }
+        walk_expression(self, expr, arena)
     }
 }
-
-use typedlua_parser::ast::expression::Argument;
-use typedlua_parser::ast::expression::ArrayElement;
-use typedlua_parser::ast::expression::MatchArmBody;
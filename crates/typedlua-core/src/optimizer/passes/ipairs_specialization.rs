@@ -0,0 +1,319 @@
+// =============================================================================
+// O2: ipairs Loop Specialization Pass
+// =============================================================================
+
+use super::constant_propagation::BindingCensus;
+use crate::config::OptimizationLevel;
+use crate::optimizer::{walk_block, walk_expression, walk_statement};
+use crate::optimizer::{MutVisitor, TypeFacts, WholeProgramPass};
+use crate::MutableProgram;
+use bumpalo::Bump;
+use std::sync::Arc;
+use typedlua_parser::ast::expression::{BinaryOp, Expression, ExpressionKind, Literal, UnaryOp};
+use typedlua_parser::ast::pattern::Pattern;
+use typedlua_parser::ast::statement::{
+    Block, ForGeneric, ForNumeric, ForStatement, IfStatement, Statement, VariableDeclaration,
+    VariableKind,
+};
+use typedlua_parser::ast::Spanned;
+use typedlua_parser::string_interner::{StringId, StringInterner};
+
+/// ipairs loop specialization pass
+/// Rewrites `for i, v in ipairs(arr)` into `for i = 1, #arr do local v = arr[i]`
+/// when `arr` is an array literal or a name the type checker knows holds an
+/// array (see [`TypeFacts`]), avoiding the iterator call per element.
+///
+/// A typed array may still have holes (writes through another reference,
+/// values from `any`), and `#arr` may then be any border, so loops over a name
+/// keep `ipairs` semantics by stopping at the first `nil` element:
+/// `if v == nil then break end`. Only literals of non-nil values, bound to a
+/// fresh local, are known to be dense and skip the check.
+///
+/// The loop body may only read `arr[...]` and `#arr`; anything else that
+/// mentions the array disqualifies the loop. Like any `for` over `#arr`, the
+/// bound is evaluated once, so a body that resizes the array through another
+/// reference sees the original length where `ipairs` would have followed it.
+pub struct IpairsSpecializationPass {
+    next_temp_id: usize,
+    interner: Arc<StringInterner>,
+    facts: TypeFacts,
+}
+
+impl IpairsSpecializationPass {
+    pub fn new(interner: Arc<StringInterner>) -> Self {
+        Self {
+            next_temp_id: 0,
+            interner,
+            facts: TypeFacts::default(),
+        }
+    }
+
+    /// Facts from a successful type check; without them only loops over
+    /// array literals are rewritten
+    pub fn set_type_facts(&mut self, facts: TypeFacts) {
+        self.facts = facts;
+    }
+}
+
+impl<'arena> WholeProgramPass<'arena> for IpairsSpecializationPass {
+    fn name(&self) -> &'static str {
+        "ipairs-specialization"
+    }
+
+    fn min_level(&self) -> OptimizationLevel {
+        OptimizationLevel::O2
+    }
+
+    fn run(
+        &mut self,
+        program: &mut MutableProgram<'arena>,
+        arena: &'arena Bump,
+    ) -> Result<bool, String> {
+        let ipairs = self.interner.get_or_intern("ipairs");
+        let mut census = BindingCensus::default();
+        census.visit_block(&mut program.statements, arena);
+        if census.binds(ipairs) {
+            return Ok(false);
+        }
+
+        let mut specializer = Specializer {
+            ipairs,
+            facts: &self.facts,
+            interner: &self.interner,
+            next_temp_id: &mut self.next_temp_id,
+        };
+        Ok(specializer.visit_block(&mut program.statements, arena))
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+/// The array an `ipairs` loop walks
+enum Source<'arena> {
+    Name(StringId),
+    /// Hoisted into a fresh local ahead of the loop
+    Literal(Expression<'arena>),
+}
+
+struct Specializer<'p> {
+    ipairs: StringId,
+    facts: &'p TypeFacts,
+    interner: &'p StringInterner,
+    next_temp_id: &'p mut usize,
+}
+
+impl Specializer<'_> {
+    /// The array of `for ... in ipairs(arr)` when the loop can be rewritten
+    fn source<'arena>(
+        &self,
+        for_gen: &ForGeneric<'arena>,
+        arena: &'arena Bump,
+    ) -> Option<Source<'arena>> {
+        if for_gen.pattern.is_some() || !(1..=2).contains(&for_gen.variables.len()) {
+            return None;
+        }
+        let [iterator] = for_gen.iterators else {
+            return None;
+        };
+        let ExpressionKind::Call(callee, args, _) = &iterator.kind else {
+            return None;
+        };
+        if !matches!(callee.kind, ExpressionKind::Identifier(name) if name == self.ipairs) {
+            return None;
+        }
+        let [arg] = &args[..] else {
+            return None;
+        };
+        if arg.is_spread {
+            return None;
+        }
+        match &arg.value.kind {
+            ExpressionKind::Identifier(name) if self.facts.is_array(*name) => {
+                if for_gen.variables.iter().any(|var| var.node == *name) {
+                    return None;
+                }
+                let mut body = for_gen.body.statements.to_vec();
+                let mut uses = ArrayUses {
+                    array: *name,
+                    escapes: false,
+                };
+                uses.visit_block(&mut body, arena);
+                (!uses.escapes).then_some(Source::Name(*name))
+            }
+            _ if TypeFacts::is_dense_literal(&arg.value) => {
+                Some(Source::Literal(arg.value.clone()))
+            }
+            _ => None,
+        }
+    }
+
+    fn fresh_array<'arena>(
+        &mut self,
+        literal: Expression<'arena>,
+    ) -> (StringId, Statement<'arena>) {
+        let name = format!("__array_{}", *self.next_temp_id);
+        *self.next_temp_id += 1;
+        let name = self.interner.get_or_intern(&name);
+        let span = literal.span;
+        let decl = Statement::Variable(VariableDeclaration {
+            kind: VariableKind::Local,
+            pattern: Pattern::Identifier(Spanned::new(name, span)),
+            type_annotation: None,
+            initializer: literal,
+            span,
+        });
+        (name, decl)
+    }
+
+    /// The numeric loop; with `stop_at_nil`, it breaks at the first `nil`
+    /// element like `ipairs` would
+    fn specialize<'arena>(
+        for_gen: &ForGeneric<'arena>,
+        array: StringId,
+        stop_at_nil: bool,
+        arena: &'arena Bump,
+    ) -> Statement<'arena> {
+        let span = for_gen.span;
+        let index = for_gen.variables[0].clone();
+        let array_expr = || Expression::new(ExpressionKind::Identifier(array), span);
+        let element = || {
+            Expression::new(
+                ExpressionKind::Index(
+                    arena.alloc(array_expr()),
+                    arena.alloc(Expression::new(
+                        ExpressionKind::Identifier(index.node),
+                        span,
+                    )),
+                ),
+                span,
+            )
+        };
+
+        let mut body = Vec::with_capacity(for_gen.body.statements.len() + 2);
+        let value = for_gen.variables.get(1);
+        if let Some(value) = value {
+            body.push(Statement::Variable(VariableDeclaration {
+                kind: VariableKind::Local,
+                pattern: Pattern::Identifier(value.clone()),
+                type_annotation: None,
+                initializer: element(),
+                span: value.span,
+            }));
+        }
+        if stop_at_nil {
+            let checked = match value {
+                Some(value) => Expression::new(ExpressionKind::Identifier(value.node), span),
+                None => element(),
+            };
+            let nil = Expression::new(ExpressionKind::Literal(Literal::Nil), span);
+            body.push(Statement::If(IfStatement {
+                condition: Expression::new(
+                    ExpressionKind::Binary(BinaryOp::Equal, arena.alloc(checked), arena.alloc(nil)),
+                    span,
+                ),
+                then_block: Block {
+                    statements: arena.alloc_slice_clone(&[Statement::Break(span)]),
+                    span,
+                },
+                else_ifs: &[],
+                else_block: None,
+                span,
+            }));
+        }
+        body.extend(for_gen.body.statements.iter().cloned());
+
+        let for_num = ForNumeric {
+            variable: index,
+            start: Expression::new(ExpressionKind::Literal(Literal::Number(1.0)), span),
+            end: Expression::new(
+                ExpressionKind::Unary(UnaryOp::Length, arena.alloc(array_expr())),
+                span,
+            ),
+            step: None,
+            body: Block {
+                statements: arena.alloc_slice_clone(&body),
+                span: for_gen.body.span,
+            },
+            span,
+        };
+        Statement::For(arena.alloc(ForStatement::Numeric(arena.alloc(for_num))))
+    }
+}
+
+impl<'arena> MutVisitor<'arena> for Specializer<'_> {
+    fn visit_block(&mut self, stmts: &mut Vec<Statement<'arena>>, arena: &'arena Bump) -> bool {
+        let mut changed = walk_block(self, stmts, arena);
+        // A new local between a `goto` and its label is a Lua compile error
+        let has_label = stmts.iter().any(|stmt| matches!(stmt, Statement::Label(_)));
+
+        let mut i = 0;
+        while i < stmts.len() {
+            let Statement::For(&ForStatement::Generic(ref for_gen)) = stmts[i] else {
+                i += 1;
+                continue;
+            };
+            match self.source(for_gen, arena) {
+                Some(Source::Name(array)) => {
+                    stmts[i] = Self::specialize(for_gen, array, true, arena);
+                    changed = true;
+                }
+                Some(Source::Literal(literal)) if !has_label => {
+                    let (array, decl) = self.fresh_array(literal);
+                    stmts[i] = Self::specialize(for_gen, array, false, arena);
+                    stmts.insert(i, decl);
+                    i += 1;
+                    changed = true;
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+        changed
+    }
+}
+
+/// Finds mentions of `array` other than element reads and `#array`
+struct ArrayUses {
+    array: StringId,
+    escapes: bool,
+}
+
+impl ArrayUses {
+    fn is_array(&self, expr: &Expression<'_>) -> bool {
+        matches!(expr.kind, ExpressionKind::Identifier(name) if name == self.array)
+    }
+
+    fn is_element(&self, expr: &Expression<'_>) -> bool {
+        matches!(&expr.kind, ExpressionKind::Index(obj, _) if self.is_array(obj))
+    }
+}
+
+impl<'arena> MutVisitor<'arena> for ArrayUses {
+    fn visit_statement(&mut self, stmt: &mut Statement<'arena>, arena: &'arena Bump) -> bool {
+        !self.escapes && walk_statement(self, stmt, arena)
+    }
+
+    fn visit_expression(&mut self, expr: &mut Expression<'arena>, arena: &'arena Bump) -> bool {
+        if self.escapes {
+            return false;
+        }
+        match &expr.kind {
+            ExpressionKind::Identifier(_) if self.is_array(expr) => {
+                self.escapes = true;
+                false
+            }
+            ExpressionKind::Unary(UnaryOp::Length, operand) if self.is_array(operand) => false,
+            ExpressionKind::Index(obj, key) if self.is_array(obj) => {
+                self.visit_expression(&mut (**key).clone(), arena)
+            }
+            // `arr[k] = v` writes the array
+            ExpressionKind::Assignment(target, _, _) if self.is_element(target) => {
+                self.escapes = true;
+                false
+            }
+            _ => walk_expression(self, expr, arena),
+        }
+    }
+}
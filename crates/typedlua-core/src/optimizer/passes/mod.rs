@@ -1,6 +1,10 @@
 mod constant_folding;
 pub use constant_folding::ConstantFoldingPass;
 
+mod constant_propagation;
+pub(crate) use constant_propagation::pattern_binders;
+pub use constant_propagation::ConstantPropagationPass;
+
 mod dead_code_elimination;
 pub use dead_code_elimination::DeadCodeEliminationPass;
 
@@ -16,9 +20,27 @@ pub use global_localization::GlobalLocalizationPass;
 mod function_inlining;
 pub use function_inlining::FunctionInliningPass;
 
+mod cross_module_inlining;
+pub use cross_module_inlining::CrossModuleInliningPass;
+
+mod profile_guided;
+pub use profile_guided::ProfileGuidedPass;
+
+mod ipairs_specialization;
+pub use ipairs_specialization::IpairsSpecializationPass;
+
+mod scalar_replacement;
+pub use scalar_replacement::ScalarReplacementPass;
+
 mod loop_optimization;
 pub use loop_optimization::LoopOptimizationPass;
 
+mod closure_hoisting;
+pub use closure_hoisting::ClosureHoistingPass;
+
+mod common_subexpression_elimination;
+pub use common_subexpression_elimination::CommonSubexpressionEliminationPass;
+
 mod string_concat_optimization;
 pub use string_concat_optimization::StringConcatOptimizationPass;
 
@@ -0,0 +1,792 @@
+// =============================================================================
+// O1: Profile-Guided Optimization Pass
+// =============================================================================
+
+use super::constant_propagation::BindingCensus;
+use super::scalar_replacement::Identifiers;
+use crate::config::OptimizationLevel;
+use crate::optimizer::{match_arm_span, ModuleProfile, ProfileCounts};
+use crate::optimizer::{
+    walk_expression, walk_pattern, walk_statement, MutVisitor, RemarkSink, WholeProgramPass,
+};
+use crate::MutableProgram;
+use bumpalo::Bump;
+use rustc_hash::{FxHashMap, FxHashSet};
+use std::sync::Arc;
+use typedlua_parser::ast::expression::{
+    Argument, BinaryOp, Expression, ExpressionKind, Literal, MatchExpression,
+};
+use typedlua_parser::ast::pattern::Pattern;
+use typedlua_parser::ast::statement::{
+    Block, ExportKind, FunctionDeclaration, IfStatement, ReturnStatement, Statement,
+    VariableDeclaration, VariableKind,
+};
+use typedlua_parser::ast::Spanned;
+use typedlua_parser::span::Span;
+use typedlua_parser::string_interner::{StringId, StringInterner};
+
+/// Profile-guided optimization pass
+/// Uses the execution counts of an instrumented run (see
+/// [`crate::optimizer::Profile`]) to
+/// - try the arms of an `if`/`elseif` chain that compares one name or field
+///   against distinct literals in order of how often they were taken, and
+///   likewise runs of literal arms of a `match`
+/// - inline calls to module-level functions at call sites the profile shows
+///   hot, for functions up to `hot_inline_threshold` statements: far larger
+///   than the inliners accept without a profile
+///
+/// The inlined function must be straight-line code: `local` declarations and
+/// expression statements ending in an optional `return`, reading no name
+/// that the rest of the module binds. Its parameters and locals become fresh
+/// locals of the caller. Calls are inlined where they form a whole statement,
+/// `local x = f(...)` or `return f(...)`.
+///
+/// Without a profile for the module, the pass does nothing.
+pub struct ProfileGuidedPass {
+    interner: Arc<StringInterner>,
+    profile: Option<Arc<ModuleProfile>>,
+    threshold: usize,
+    next_temp_id: usize,
+    remarks: RemarkSink,
+}
+
+impl ProfileGuidedPass {
+    pub fn new(interner: Arc<StringInterner>) -> Self {
+        Self {
+            interner,
+            profile: None,
+            threshold: 40,
+            next_temp_id: 0,
+            remarks: RemarkSink::default(),
+        }
+    }
+
+    pub fn with_threshold(mut self, threshold: usize) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    pub fn with_remarks(mut self, remarks: RemarkSink) -> Self {
+        self.remarks = remarks;
+        self
+    }
+
+    pub fn set_profile(&mut self, profile: Arc<ModuleProfile>) {
+        self.profile = Some(profile);
+    }
+}
+
+impl<'arena> WholeProgramPass<'arena> for ProfileGuidedPass {
+    fn name(&self) -> &'static str {
+        "profile-guided-optimization"
+    }
+
+    fn min_level(&self) -> OptimizationLevel {
+        OptimizationLevel::O1
+    }
+
+    fn run(
+        &mut self,
+        program: &mut MutableProgram<'arena>,
+        arena: &'arena Bump,
+    ) -> Result<bool, String> {
+        let Some(profile) = &self.profile else {
+            return Ok(false);
+        };
+        let counts = profile.bind(program, &self.interner, arena);
+        if counts.is_empty() {
+            return Ok(false);
+        }
+
+        let mut orderer = BranchOrderer {
+            counts: &counts,
+            remarks: &self.remarks,
+        };
+        let mut changed = orderer.visit_block(&mut program.statements, arena);
+
+        // Names each top-level statement binds, to tell whether a function
+        // reads the same names at its call sites as where it is declared
+        let censuses: Vec<BindingCensus> = program
+            .statements
+            .iter_mut()
+            .map(|stmt| {
+                let mut census = BindingCensus::default();
+                census.visit_statement(stmt, arena);
+                census
+            })
+            .collect();
+        let functions: FxHashMap<StringId, (usize, FunctionDeclaration<'arena>)> = program
+            .statements
+            .iter()
+            .enumerate()
+            .filter_map(|(position, stmt)| {
+                top_level_function(stmt).map(|func| (func.name.node, (position, func.clone())))
+            })
+            .collect();
+        if functions.is_empty() {
+            return Ok(changed);
+        }
+
+        let mut inliner = HotInliner {
+            interner: &self.interner,
+            remarks: &self.remarks,
+            counts: &counts,
+            censuses: &censuses,
+            functions: &functions,
+            callees: FxHashMap::default(),
+            threshold: self.threshold,
+            next_temp_id: &mut self.next_temp_id,
+            position: 0,
+        };
+        let statements = std::mem::take(&mut program.statements);
+        for (position, stmt) in statements.into_iter().enumerate() {
+            inliner.position = position;
+            changed |= inliner.inline_into(stmt, &mut program.statements, arena);
+        }
+        Ok(changed)
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+fn top_level_function<'a, 'arena>(
+    stmt: &'a Statement<'arena>,
+) -> Option<&'a FunctionDeclaration<'arena>> {
+    match stmt {
+        Statement::Function(func) => Some(func),
+        Statement::Export(export) => match &export.kind {
+            ExportKind::Declaration(decl) => match &**decl {
+                Statement::Function(func) => Some(func),
+                _ => None,
+            },
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// =============================================================================
+// Branch ordering
+// =============================================================================
+
+struct BranchOrderer<'p> {
+    counts: &'p ProfileCounts,
+    remarks: &'p RemarkSink,
+}
+
+impl BranchOrderer<'_> {
+    /// Move the most frequently taken arms of a chain of mutually exclusive
+    /// conditions to the front; `else` stays last
+    fn reorder_if<'arena>(&self, if_stmt: &mut IfStatement<'arena>, arena: &'arena Bump) -> bool {
+        if if_stmt.else_ifs.is_empty() {
+            return false;
+        }
+        let arms: Vec<(Expression<'arena>, Block<'arena>)> =
+            std::iter::once((if_stmt.condition.clone(), if_stmt.then_block.clone()))
+                .chain(
+                    if_stmt
+                        .else_ifs
+                        .iter()
+                        .map(|else_if| (else_if.condition.clone(), else_if.block.clone())),
+                )
+                .collect();
+        let Some(tests) = arms
+            .iter()
+            .map(|(condition, _)| equality_test(condition))
+            .collect::<Option<Vec<_>>>()
+        else {
+            return false;
+        };
+        if !exclusive(&tests) {
+            return false;
+        }
+        let Some(counts) = arms
+            .iter()
+            .map(|(_, block)| self.counts.count_of(block.span))
+            .collect::<Option<Vec<_>>>()
+        else {
+            return false;
+        };
+        let Some(order) = hottest_first(&counts) else {
+            return false;
+        };
+
+        self.remarks
+            .applied("profile-guided-optimization", if_stmt.span, || {
+                format!("reordered {} `if` arms by profile counts", arms.len())
+            });
+        let mut arms: Vec<_> = order.into_iter().map(|i| arms[i].clone()).collect();
+        let mut rest = arms.split_off(1);
+        let (condition, then_block) = arms.remove(0);
+        if_stmt.condition = condition;
+        if_stmt.then_block = then_block;
+        let mut else_ifs = if_stmt.else_ifs.to_vec();
+        for (else_if, (condition, block)) in else_ifs.iter_mut().zip(rest.drain(..)) {
+            else_if.condition = condition;
+            else_if.block = block;
+        }
+        if_stmt.else_ifs = arena.alloc_slice_clone(&else_ifs);
+        true
+    }
+
+    /// Sort each run of guard-free literal arms by how often they matched
+    fn reorder_match<'arena>(
+        &self,
+        match_expr: &MatchExpression<'arena>,
+        arena: &'arena Bump,
+    ) -> Option<MatchExpression<'arena>> {
+        let mut arms = match_expr.arms.to_vec();
+        let mut changed = false;
+        let mut start = 0;
+        while start < arms.len() {
+            let literal = |i: usize| match &arms[i].pattern {
+                Pattern::Literal(literal, _) if arms[i].guard.is_none() => Some(literal.clone()),
+                _ => None,
+            };
+            let mut end = start;
+            let mut literals = Vec::new();
+            while end < arms.len() {
+                match literal(end) {
+                    Some(value) => literals.push(value),
+                    None => break,
+                }
+                end += 1;
+            }
+            if end == start {
+                start += 1;
+                continue;
+            }
+            let tests: Vec<_> = literals.iter().map(|value| ((), value)).collect();
+            let counts = arms[start..end]
+                .iter()
+                .map(|arm| self.counts.count_of(match_arm_span(&arm.body)))
+                .collect::<Option<Vec<_>>>();
+            if let (true, Some(counts)) = (distinct_values(&tests), counts) {
+                if let Some(order) = hottest_first(&counts) {
+                    let run: Vec<_> = order.into_iter().map(|i| arms[start + i].clone()).collect();
+                    for (offset, arm) in run.into_iter().enumerate() {
+                        arms[start + offset] = arm;
+                    }
+                    changed = true;
+                }
+            }
+            start = end;
+        }
+        if !changed {
+            return None;
+        }
+        self.remarks
+            .applied("profile-guided-optimization", match_expr.span, || {
+                "reordered `match` arms by profile counts".to_string()
+            });
+        Some(MatchExpression {
+            value: match_expr.value,
+            arms: arena.alloc_slice_clone(&arms),
+            span: match_expr.span,
+        })
+    }
+}
+
+impl<'arena> MutVisitor<'arena> for BranchOrderer<'_> {
+    fn visit_statement(&mut self, stmt: &mut Statement<'arena>, arena: &'arena Bump) -> bool {
+        let changed = walk_statement(self, stmt, arena);
+        match stmt {
+            Statement::If(if_stmt) => self.reorder_if(if_stmt, arena) || changed,
+            _ => changed,
+        }
+    }
+
+    fn visit_expression(&mut self, expr: &mut Expression<'arena>, arena: &'arena Bump) -> bool {
+        let changed = walk_expression(self, expr, arena);
+        if let ExpressionKind::Match(match_expr) = &expr.kind {
+            if let Some(reordered) = self.reorder_match(match_expr, arena) {
+                expr.kind = ExpressionKind::Match(reordered);
+                return true;
+            }
+        }
+        changed
+    }
+}
+
+/// Arm indices by descending count, or `None` when that is the current order
+fn hottest_first(counts: &[u64]) -> Option<Vec<usize>> {
+    let mut order: Vec<usize> = (0..counts.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse(counts[i]));
+    (order.iter().enumerate().any(|(position, &i)| position != i)).then_some(order)
+}
+
+/// `subject == literal` or `literal == subject`, with the subject a name or
+/// a field of one
+fn equality_test<'a, 'arena>(
+    expr: &'a Expression<'arena>,
+) -> Option<(&'a Expression<'arena>, &'a Literal)> {
+    match &expr.kind {
+        ExpressionKind::Parenthesized(inner) => equality_test(inner),
+        ExpressionKind::Binary(BinaryOp::Equal, left, right) => match (&left.kind, &right.kind) {
+            (_, ExpressionKind::Literal(literal)) if subject_path(left).is_some() => {
+                Some((*left, literal))
+            }
+            (ExpressionKind::Literal(literal), _) if subject_path(right).is_some() => {
+                Some((*right, literal))
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// `a.b.c` as `[a, b, c]`. Field reads are taken to have no side effects,
+/// as in common subexpression elimination.
+fn subject_path(expr: &Expression<'_>) -> Option<Vec<StringId>> {
+    match &expr.kind {
+        ExpressionKind::Identifier(name) => Some(vec![*name]),
+        ExpressionKind::Member(object, field) => {
+            let mut path = subject_path(object)?;
+            path.push(field.node);
+            Some(path)
+        }
+        ExpressionKind::Parenthesized(inner) => subject_path(inner),
+        _ => None,
+    }
+}
+
+/// Every test compares the same subject, each against a different value, so
+/// at most one of them holds
+fn exclusive(tests: &[(&Expression<'_>, &Literal)]) -> bool {
+    let subject = subject_path(tests[0].0);
+    tests.iter().all(|(expr, _)| subject_path(expr) == subject) && distinct_values(tests)
+}
+
+fn distinct_values<T>(tests: &[(T, &Literal)]) -> bool {
+    tests
+        .iter()
+        .enumerate()
+        .all(|(i, (_, a))| tests[i + 1..].iter().all(|(_, b)| different_values(a, b)))
+}
+
+/// Whether two literals are different Lua values; `1` and `1.0` are not
+fn different_values(a: &Literal, b: &Literal) -> bool {
+    let number = |literal: &Literal| match literal {
+        Literal::Number(n) => Some(*n),
+        Literal::Integer(i) => Some(*i as f64),
+        _ => None,
+    };
+    match (a, b) {
+        (Literal::Nil, Literal::Nil) => false,
+        (Literal::Boolean(x), Literal::Boolean(y)) => x != y,
+        (Literal::String(x), Literal::String(y)) => x != y,
+        _ => match (number(a), number(b)) {
+            (Some(x), Some(y)) => x != y,
+            // Values of different types never compare equal
+            _ => true,
+        },
+    }
+}
+
+// =============================================================================
+// Hot call inlining
+// =============================================================================
+
+/// A module-level function in the shape the hot inliner copies
+#[derive(Clone)]
+struct Callee<'arena> {
+    parameters: Vec<StringId>,
+    /// Statements before the trailing `return`
+    body: Vec<Statement<'arena>>,
+    /// Values of the trailing `return`; `None` without one
+    result: Option<Vec<Expression<'arena>>>,
+    /// Locals the body declares
+    locals: Vec<StringId>,
+}
+
+struct HotInliner<'p, 'arena> {
+    interner: &'p StringInterner,
+    remarks: &'p RemarkSink,
+    counts: &'p ProfileCounts,
+    /// Names bound by each top-level statement
+    censuses: &'p [BindingCensus],
+    /// Module-level functions with the position of their statement
+    functions: &'p FxHashMap<StringId, (usize, FunctionDeclaration<'arena>)>,
+    /// Functions analyzed so far, or why they cannot be inlined
+    callees: FxHashMap<StringId, Result<Callee<'arena>, String>>,
+    threshold: usize,
+    next_temp_id: &'p mut usize,
+    /// Top-level statement being visited
+    position: usize,
+}
+
+impl<'arena> HotInliner<'_, 'arena> {
+    /// Whether a top-level statement other than the one at `except` binds
+    /// or assigns `name`
+    fn bound_outside(&self, name: StringId, except: usize) -> bool {
+        self.censuses
+            .iter()
+            .enumerate()
+            .any(|(position, census)| position != except && census.binds(name))
+    }
+
+    fn callee(&mut self, name: StringId, arena: &'arena Bump) -> Result<Callee<'arena>, String> {
+        if let Some(callee) = self.callees.get(&name) {
+            return callee.clone();
+        }
+        let callee = self.analyze(name, arena);
+        self.callees.insert(name, callee.clone());
+        callee
+    }
+
+    fn analyze(&self, name: StringId, arena: &'arena Bump) -> Result<Callee<'arena>, String> {
+        let (position, func) = &self.functions[&name];
+        if func.type_parameters.is_some() {
+            return Err("generic functions are specialized first".to_string());
+        }
+        if self.bound_outside(name, *position) {
+            return Err("the function's name is rebound".to_string());
+        }
+        let len = func.body.statements.len();
+        if len > self.threshold {
+            return Err(format!("body has {} statements > {}", len, self.threshold));
+        }
+
+        let mut parameters = Vec::new();
+        for param in func.parameters.iter() {
+            match &param.pattern {
+                Pattern::Identifier(ident) if param.default.is_none() && !param.is_rest => {
+                    parameters.push(ident.node)
+                }
+                _ => return Err("parameters are not plain names".to_string()),
+            }
+        }
+
+        let mut statements = func.body.statements.to_vec();
+        let result = match statements.last() {
+            Some(Statement::Return(ret)) => {
+                let values = ret.values.to_vec();
+                statements.pop();
+                Some(values)
+            }
+            _ => None,
+        };
+
+        // Locals must be declared once and read only after their declaration,
+        // so renaming them leaves every read on the same binding
+        let mut locals = Vec::new();
+        let mut read = FxHashSet::default();
+        for stmt in statements.iter_mut() {
+            let mut identifiers = Identifiers::default();
+            let declared = match stmt {
+                Statement::Variable(decl) => {
+                    let Pattern::Identifier(ident) = &decl.pattern else {
+                        return Err("body destructures".to_string());
+                    };
+                    identifiers.visit_expression(&mut decl.initializer, arena);
+                    Some(ident.node)
+                }
+                Statement::Expression(expr) => {
+                    identifiers.visit_expression(expr, arena);
+                    None
+                }
+                _ => return Err("body is not straight-line code".to_string()),
+            };
+            read.extend(identifiers.0);
+            if let Some(local) = declared {
+                if read.contains(&local) || locals.contains(&local) || parameters.contains(&local) {
+                    return Err(format!(
+                        "local `{}` shadows another name",
+                        self.interner.resolve(local)
+                    ));
+                }
+                locals.push(local);
+            }
+        }
+        if let Some(values) = &result {
+            let mut identifiers = Identifiers::default();
+            for value in values.clone().iter_mut() {
+                identifiers.visit_expression(value, arena);
+            }
+            read.extend(identifiers.0);
+        }
+
+        let mut shape = ShapeCheck::default();
+        for stmt in statements.iter_mut() {
+            shape.visit_statement(stmt, arena);
+        }
+        for value in result.clone().iter_mut().flatten() {
+            shape.visit_expression(value, arena);
+        }
+        if shape.nested_functions {
+            return Err("body creates closures or matches".to_string());
+        }
+
+        // Every other name must mean at the call site what it means here
+        for &free in read
+            .iter()
+            .filter(|name| !parameters.contains(name) && !locals.contains(name))
+        {
+            if free == name || self.bound_outside(free, *position) {
+                return Err(format!(
+                    "body reads `{}`, which the module binds",
+                    self.interner.resolve(free)
+                ));
+            }
+        }
+
+        Ok(Callee {
+            parameters,
+            body: statements,
+            result,
+            locals,
+        })
+    }
+
+    /// The callee and arguments of `name(args)` at a hot call site, after
+    /// the function declared at an earlier top-level statement
+    fn hot_call(
+        &mut self,
+        expr: &Expression<'arena>,
+        arena: &'arena Bump,
+    ) -> Option<(StringId, Callee<'arena>, &'arena [Argument<'arena>])> {
+        let ExpressionKind::Call(callee, args, _) = &expr.kind else {
+            return None;
+        };
+        let ExpressionKind::Identifier(name) = callee.kind else {
+            return None;
+        };
+        let (position, _) = self.functions.get(&name)?;
+        if *position >= self.position || !self.counts.is_hot(expr.span) {
+            return None;
+        }
+        let callee = match self.callee(name, arena) {
+            Ok(callee) => callee,
+            Err(reason) => {
+                self.remarks.missed(
+                    "profile-guided-optimization",
+                    expr.span,
+                    || format!("not inlined hot call to `{}`", self.interner.resolve(name)),
+                    || reason,
+                );
+                return None;
+            }
+        };
+        if args.len() != callee.parameters.len() || args.iter().any(|arg| arg.is_spread) {
+            self.remarks.missed(
+                "profile-guided-optimization",
+                expr.span,
+                || format!("not inlined hot call to `{}`", self.interner.resolve(name)),
+                || {
+                    format!(
+                        "{} arguments for {} parameters",
+                        args.len(),
+                        callee.parameters.len()
+                    )
+                },
+            );
+            return None;
+        }
+        Some((name, callee, *args))
+    }
+
+    /// Parameters bound to the arguments, then the body, over fresh names;
+    /// returns the statements and the renamed `return` values
+    fn expand(
+        &mut self,
+        callee: &Callee<'arena>,
+        args: &[Argument<'arena>],
+        span: Span,
+        arena: &'arena Bump,
+    ) -> (Vec<Statement<'arena>>, Option<Vec<Expression<'arena>>>) {
+        let mut names = FxHashMap::default();
+        for &name in callee.parameters.iter().chain(&callee.locals) {
+            let fresh = format!("__{}_{}", self.interner.resolve(name), *self.next_temp_id);
+            *self.next_temp_id += 1;
+            names.insert(name, self.interner.get_or_intern(&fresh));
+        }
+        let mut renamer = Renamer { names: &names };
+
+        let mut statements: Vec<Statement<'arena>> = callee
+            .parameters
+            .iter()
+            .zip(args)
+            .map(|(param, arg)| {
+                Statement::Variable(VariableDeclaration {
+                    kind: VariableKind::Local,
+                    pattern: Pattern::Identifier(Spanned::new(names[param], span)),
+                    type_annotation: None,
+                    initializer: arg.value.clone(),
+                    span,
+                })
+            })
+            .collect();
+        let mut body = callee.body.clone();
+        renamer.visit_block(&mut body, arena);
+        statements.extend(body);
+        let result = callee.result.clone().map(|mut values| {
+            for value in values.iter_mut() {
+                renamer.visit_expression(value, arena);
+            }
+            values
+        });
+        (statements, result)
+    }
+
+    /// Push `stmt` onto `out`, or the statements of the call it inlines
+    fn inline_into(
+        &mut self,
+        mut stmt: Statement<'arena>,
+        out: &mut Vec<Statement<'arena>>,
+        arena: &'arena Bump,
+    ) -> bool {
+        let changed = walk_statement(self, &mut stmt, arena);
+        match self.inline_statement(&stmt, arena) {
+            Some(statements) => {
+                out.extend(statements);
+                true
+            }
+            None => {
+                out.push(stmt);
+                changed
+            }
+        }
+    }
+
+    fn report_inlined(&self, name: StringId, span: Span) {
+        self.remarks
+            .applied("profile-guided-optimization", span, || {
+                format!(
+                    "inlined hot call to `{}` ({} runs)",
+                    self.interner.resolve(name),
+                    self.counts.count_at(span).unwrap_or_default()
+                )
+            });
+    }
+
+    /// The statements replacing `stmt` when it calls a hot function
+    fn inline_statement(
+        &mut self,
+        stmt: &Statement<'arena>,
+        arena: &'arena Bump,
+    ) -> Option<Vec<Statement<'arena>>> {
+        match stmt {
+            // local x = f(...)
+            Statement::Variable(decl) if matches!(decl.pattern, Pattern::Identifier(_)) => {
+                let (name, callee, args) = self.hot_call(&decl.initializer, arena)?;
+                if callee
+                    .result
+                    .as_ref()
+                    .is_some_and(|values| values.len() > 1)
+                {
+                    return None;
+                }
+                let span = decl.initializer.span;
+                let (mut statements, result) = self.expand(&callee, args, span, arena);
+                let mut decl = decl.clone();
+                decl.initializer = result
+                    .and_then(|values| values.into_iter().next())
+                    .unwrap_or_else(|| {
+                        Expression::new(ExpressionKind::Literal(Literal::Nil), decl.span)
+                    });
+                statements.push(Statement::Variable(decl));
+                self.report_inlined(name, span);
+                Some(statements)
+            }
+            // f(...)
+            Statement::Expression(expr) => {
+                let (name, callee, args) = self.hot_call(expr, arena)?;
+                // Results are dropped; calls among them still have to run
+                let discardable = |value: &Expression<'_>| {
+                    matches!(
+                        value.kind,
+                        ExpressionKind::Literal(_)
+                            | ExpressionKind::Identifier(_)
+                            | ExpressionKind::Call(..)
+                            | ExpressionKind::MethodCall(..)
+                    )
+                };
+                if !callee.result.iter().flatten().all(discardable) {
+                    return None;
+                }
+                let (mut statements, result) = self.expand(&callee, args, expr.span, arena);
+                statements.extend(result.into_iter().flatten().filter_map(|value| {
+                    matches!(
+                        value.kind,
+                        ExpressionKind::Call(..) | ExpressionKind::MethodCall(..)
+                    )
+                    .then_some(Statement::Expression(value))
+                }));
+                self.report_inlined(name, expr.span);
+                Some(statements)
+            }
+            // return f(...)
+            Statement::Return(ret) if ret.values.len() == 1 => {
+                let (name, callee, args) = self.hot_call(&ret.values[0], arena)?;
+                let (mut statements, result) = self.expand(&callee, args, ret.span, arena);
+                let values = result.unwrap_or_default();
+                statements.push(Statement::Return(ReturnStatement {
+                    values: arena.alloc_slice_clone(&values),
+                    span: ret.span,
+                }));
+                self.report_inlined(name, ret.values[0].span);
+                Some(statements)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl<'arena> MutVisitor<'arena> for HotInliner<'_, 'arena> {
+    fn visit_block(&mut self, stmts: &mut Vec<Statement<'arena>>, arena: &'arena Bump) -> bool {
+        let mut changed = false;
+        for stmt in std::mem::take(stmts) {
+            changed |= self.inline_into(stmt, stmts, arena);
+        }
+        changed
+    }
+}
+
+/// Finds nested functions and `match` expressions, whose bindings renaming
+/// would not follow
+#[derive(Default)]
+pub(super) struct ShapeCheck {
+    pub(super) nested_functions: bool,
+}
+
+impl<'arena> MutVisitor<'arena> for ShapeCheck {
+    fn visit_expression(&mut self, expr: &mut Expression<'arena>, arena: &'arena Bump) -> bool {
+        if matches!(
+            expr.kind,
+            ExpressionKind::Arrow(_) | ExpressionKind::Function(_) | ExpressionKind::Match(_)
+        ) {
+            self.nested_functions = true;
+        }
+        walk_expression(self, expr, arena)
+    }
+}
+
+/// Renames parameters and locals of an inlined body
+pub(super) struct Renamer<'p> {
+    pub(super) names: &'p FxHashMap<StringId, StringId>,
+}
+
+impl<'arena> MutVisitor<'arena> for Renamer<'_> {
+    fn visit_expression(&mut self, expr: &mut Expression<'arena>, arena: &'arena Bump) -> bool {
+        let changed = walk_expression(self, expr, arena);
+        if let ExpressionKind::Identifier(name) = expr.kind {
+            if let Some(&fresh) = self.names.get(&name) {
+                expr.kind = ExpressionKind::Identifier(fresh);
+                return true;
+            }
+        }
+        changed
+    }
+
+    fn visit_pattern(&mut self, pattern: &mut Pattern<'arena>, arena: &'arena Bump) -> bool {
+        if let Pattern::Identifier(ident) = pattern {
+            if let Some(&fresh) = self.names.get(&ident.node) {
+                ident.node = fresh;
+                return true;
+            }
+        }
+        walk_pattern(self, pattern, arena)
+    }
+}
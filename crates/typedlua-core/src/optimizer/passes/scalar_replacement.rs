@@ -0,0 +1,370 @@
+// =============================================================================
+// O3: Scalar Replacement Pass
+// =============================================================================
+
+use super::constant_propagation::BindingCensus;
+use crate::config::OptimizationLevel;
+use crate::optimizer::{walk_block, walk_expression, walk_pattern, walk_statement};
+use crate::optimizer::{MutVisitor, WholeProgramPass};
+use crate::MutableProgram;
+use bumpalo::Bump;
+use rustc_hash::{FxHashMap, FxHashSet};
+use std::sync::Arc;
+use typedlua_parser::ast::expression::{Expression, ExpressionKind, Literal, ObjectProperty};
+use typedlua_parser::ast::pattern::Pattern;
+use typedlua_parser::ast::statement::{
+    CatchPattern, ForStatement, Statement, VariableDeclaration, VariableKind,
+};
+use typedlua_parser::ast::Spanned;
+use typedlua_parser::string_interner::{StringId, StringInterner};
+
+/// Larger literals are rarely temporaries and would crowd the local limit
+const MAX_FIELDS: usize = 8;
+
+/// Scalar replacement pass
+/// Replaces a table literal bound to a local (`local p = {x = 1, y = 2}`) with
+/// one local per field (`local p_x = 1`, `local p_y = 2`) when the rest of the
+/// block only reads and writes its fields through constant keys that the
+/// literal declares. Any other mention of the table (passing, storing or
+/// returning it, a method call, `#p`, iteration) or a mention inside a
+/// closure counts as an escape and keeps the table.
+///
+/// This is the block-local counterpart of the module-level `EscapeAnalysis`
+/// used for scope hoisting: it works on one declaration at a time instead of
+/// on module locals.
+pub struct ScalarReplacementPass {
+    interner: Arc<StringInterner>,
+}
+
+impl ScalarReplacementPass {
+    pub fn new(interner: Arc<StringInterner>) -> Self {
+        Self { interner }
+    }
+}
+
+impl<'arena> WholeProgramPass<'arena> for ScalarReplacementPass {
+    fn name(&self) -> &'static str {
+        "scalar-replacement"
+    }
+
+    fn min_level(&self) -> OptimizationLevel {
+        OptimizationLevel::O3
+    }
+
+    fn run(
+        &mut self,
+        program: &mut MutableProgram<'arena>,
+        arena: &'arena Bump,
+    ) -> Result<bool, String> {
+        let mut census = BindingCensus::default();
+        census.visit_block(&mut program.statements, arena);
+        let mut identifiers = Identifiers::default();
+        identifiers.visit_block(&mut program.statements, arena);
+
+        let mut replacer = Replacer {
+            interner: &self.interner,
+            census: &census,
+            identifiers: identifiers.0,
+        };
+        Ok(replacer.visit_block(&mut program.statements, arena))
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+/// Every name the program reads, including globals
+#[derive(Default)]
+pub(super) struct Identifiers(pub(super) FxHashSet<StringId>);
+
+impl<'arena> MutVisitor<'arena> for Identifiers {
+    fn visit_expression(&mut self, expr: &mut Expression<'arena>, arena: &'arena Bump) -> bool {
+        if let ExpressionKind::Identifier(name) = expr.kind {
+            self.0.insert(name);
+        }
+        walk_expression(self, expr, arena)
+    }
+}
+
+struct Replacer<'p> {
+    interner: &'p StringInterner,
+    census: &'p BindingCensus,
+    /// Read anywhere, or introduced by an earlier replacement
+    identifiers: FxHashSet<StringId>,
+}
+
+impl Replacer<'_> {
+    fn is_taken(&self, name: StringId) -> bool {
+        self.census.binds(name) || self.identifiers.contains(&name)
+    }
+
+    /// The table's name and field keys, when `stmt` binds a plain table
+    /// literal with distinct identifier keys
+    fn candidate(stmt: &Statement<'_>) -> Option<(StringId, Vec<StringId>)> {
+        let Statement::Variable(decl) = stmt else {
+            return None;
+        };
+        let Pattern::Identifier(ident) = &decl.pattern else {
+            return None;
+        };
+        let ExpressionKind::Object(props) = &decl.initializer.kind else {
+            return None;
+        };
+        if props.is_empty() || props.len() > MAX_FIELDS {
+            return None;
+        }
+        let mut keys = Vec::with_capacity(props.len());
+        for prop in props.iter() {
+            let ObjectProperty::Property { key, .. } = prop else {
+                return None;
+            };
+            if keys.contains(&key.node) {
+                return None;
+            }
+            keys.push(key.node);
+        }
+        Some((ident.node, keys))
+    }
+
+    /// `local p_x = <value>` for every field of the literal in `stmt`, in
+    /// source order so side effects keep their order
+    fn split<'arena>(
+        stmt: &Statement<'arena>,
+        fields: &FxHashMap<StringId, StringId>,
+    ) -> Vec<Statement<'arena>> {
+        let Statement::Variable(decl) = stmt else {
+            unreachable!("candidate() only accepts variable declarations");
+        };
+        let ExpressionKind::Object(props) = &decl.initializer.kind else {
+            unreachable!("candidate() only accepts table literals");
+        };
+        props
+            .iter()
+            .filter_map(|prop| match prop {
+                ObjectProperty::Property { key, value, span } => {
+                    Some(Statement::Variable(VariableDeclaration {
+                        kind: VariableKind::Local,
+                        pattern: Pattern::Identifier(Spanned::new(fields[&key.node], key.span)),
+                        type_annotation: None,
+                        initializer: (**value).clone(),
+                        span: *span,
+                    }))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Replaces the table declared by `stmts[at]` if it does not escape the
+    /// rest of the block
+    fn replace<'arena>(
+        &mut self,
+        stmts: &mut Vec<Statement<'arena>>,
+        at: usize,
+        arena: &'arena Bump,
+    ) -> Option<usize> {
+        let (table, keys) = Self::candidate(&stmts[at])?;
+
+        let mut uses = FieldUses {
+            table,
+            keys: &keys,
+            interner: self.interner,
+            closure_depth: 0,
+            escapes: false,
+        };
+        let mut rest = stmts[at + 1..].to_vec();
+        uses.visit_block(&mut rest, arena);
+        if uses.escapes {
+            return None;
+        }
+
+        let table_name = self.interner.resolve(table);
+        let mut fields = FxHashMap::default();
+        for key in &keys {
+            let name = format!("{}_{}", table_name, self.interner.resolve(*key));
+            let name = self.interner.get_or_intern(&name);
+            if self.is_taken(name) {
+                return None;
+            }
+            fields.insert(*key, name);
+        }
+        self.identifiers.extend(fields.values().copied());
+
+        let mut rewrite = FieldRewrite {
+            table,
+            fields: &fields,
+            interner: self.interner,
+        };
+        for stmt in &mut stmts[at + 1..] {
+            rewrite.visit_statement(stmt, arena);
+        }
+        let locals = Self::split(&stmts[at], &fields);
+        let count = locals.len();
+        stmts.remove(at);
+        for (offset, local) in locals.into_iter().enumerate() {
+            stmts.insert(at + offset, local);
+        }
+        Some(count)
+    }
+}
+
+impl<'arena> MutVisitor<'arena> for Replacer<'_> {
+    fn visit_block(&mut self, stmts: &mut Vec<Statement<'arena>>, arena: &'arena Bump) -> bool {
+        let mut changed = walk_block(self, stmts, arena);
+        let mut i = 0;
+        while i < stmts.len() {
+            match self.replace(stmts, i, arena) {
+                Some(count) => {
+                    i += count;
+                    changed = true;
+                }
+                None => i += 1,
+            }
+        }
+        changed
+    }
+}
+
+/// The key of a field access on `table`: `None` for any other expression,
+/// `Some(None)` when the key is not a constant
+fn field_key(
+    expr: &Expression<'_>,
+    table: StringId,
+    interner: &StringInterner,
+) -> Option<Option<StringId>> {
+    let (ExpressionKind::Member(obj, _) | ExpressionKind::Index(obj, _)) = &expr.kind else {
+        return None;
+    };
+    if !matches!(obj.kind, ExpressionKind::Identifier(name) if name == table) {
+        return None;
+    }
+    Some(match &expr.kind {
+        ExpressionKind::Member(_, field) => Some(field.node),
+        ExpressionKind::Index(_, index) => match &index.kind {
+            ExpressionKind::Literal(Literal::String(s)) => Some(interner.get_or_intern(s)),
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+/// Checks that every mention of `table` after its declaration is a field
+/// access through one of `keys`, outside any closure
+struct FieldUses<'k> {
+    table: StringId,
+    keys: &'k [StringId],
+    interner: &'k StringInterner,
+    closure_depth: usize,
+    escapes: bool,
+}
+
+impl FieldUses<'_> {
+    fn access(&mut self, expr: &Expression<'_>) -> Option<StringId> {
+        let key = field_key(expr, self.table, self.interner)?;
+        match key {
+            Some(key) if self.closure_depth == 0 && self.keys.contains(&key) => Some(key),
+            _ => {
+                self.escapes = true;
+                None
+            }
+        }
+    }
+
+    fn rebinds(&mut self, name: StringId) {
+        if name == self.table {
+            self.escapes = true;
+        }
+    }
+}
+
+impl<'arena> MutVisitor<'arena> for FieldUses<'_> {
+    fn visit_statement(&mut self, stmt: &mut Statement<'arena>, arena: &'arena Bump) -> bool {
+        if self.escapes {
+            return false;
+        }
+        let closure = matches!(
+            stmt,
+            Statement::Function(_) | Statement::Class(_) | Statement::Enum(_)
+        );
+        match stmt {
+            Statement::Function(func) => self.rebinds(func.name.node),
+            Statement::Class(class) => self.rebinds(class.name.node),
+            Statement::Enum(decl) => self.rebinds(decl.name.node),
+            Statement::For(for_stmt) => match &**for_stmt {
+                ForStatement::Numeric(for_num) => self.rebinds(for_num.variable.node),
+                ForStatement::Generic(for_gen) => {
+                    for var in for_gen.variables.iter() {
+                        self.rebinds(var.node);
+                    }
+                }
+            },
+            Statement::Try(try_stmt) => {
+                for clause in try_stmt.catch_clauses.iter() {
+                    match &clause.pattern {
+                        CatchPattern::Untyped { variable, .. }
+                        | CatchPattern::Typed { variable, .. }
+                        | CatchPattern::MultiTyped { variable, .. } => self.rebinds(variable.node),
+                    }
+                }
+            }
+            _ => {}
+        }
+        self.closure_depth += usize::from(closure);
+        walk_statement(self, stmt, arena);
+        self.closure_depth -= usize::from(closure);
+        false
+    }
+
+    fn visit_expression(&mut self, expr: &mut Expression<'arena>, arena: &'arena Bump) -> bool {
+        if self.escapes {
+            return false;
+        }
+        if self.access(expr).is_some() {
+            return false;
+        }
+        match &expr.kind {
+            ExpressionKind::Identifier(name) if *name == self.table => {
+                self.escapes = true;
+                false
+            }
+            ExpressionKind::Function(_) | ExpressionKind::Arrow(_) => {
+                self.closure_depth += 1;
+                walk_expression(self, expr, arena);
+                self.closure_depth -= 1;
+                false
+            }
+            ExpressionKind::Try(try_expr) => {
+                self.rebinds(try_expr.catch_variable.node);
+                walk_expression(self, expr, arena)
+            }
+            _ => walk_expression(self, expr, arena),
+        }
+    }
+
+    fn visit_pattern(&mut self, pattern: &mut Pattern<'arena>, arena: &'arena Bump) -> bool {
+        let mut names = Vec::new();
+        super::pattern_binders(pattern, &mut names);
+        for name in names {
+            self.rebinds(name);
+        }
+        walk_pattern(self, pattern, arena)
+    }
+}
+
+/// Turns `table.key` and `table["key"]` into the key's local
+struct FieldRewrite<'f> {
+    table: StringId,
+    fields: &'f FxHashMap<StringId, StringId>,
+    interner: &'f StringInterner,
+}
+
+impl<'arena> MutVisitor<'arena> for FieldRewrite<'_> {
+    fn visit_expression(&mut self, expr: &mut Expression<'arena>, arena: &'arena Bump) -> bool {
+        if let Some(Some(key)) = field_key(expr, self.table, self.interner) {
+            expr.kind = ExpressionKind::Identifier(self.fields[&key]);
+            return true;
+        }
+        walk_expression(self, expr, arena)
+    }
+}
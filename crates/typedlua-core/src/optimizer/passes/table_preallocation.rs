@@ -1,152 +1,213 @@
-use crate::config::OptimizationLevel;
-use crate::optimizer::{ExprVisitor, WholeProgramPass};
-use crate::MutableProgram;
-use bumpalo::Bump;
-use typedlua_parser::ast::expression::Expression;
-use typedlua_parser::ast::statement::Statement;
+// =============================================================================
+// O2: Table Preallocation Pass
+// =============================================================================
 
-pub struct TablePreallocationPass;
+use crate::codegen::LuaTarget;
+use crate::optimizer::BlockVisitor;
+use bumpalo::Bump;
+use std::sync::Arc;
+use typedlua_parser::ast::expression::{
+    Argument, ArrayElement, AssignmentOp, BinaryOp, Expression, ExpressionKind, Literal, UnaryOp,
+};
+use typedlua_parser::ast::pattern::Pattern;
+use typedlua_parser::ast::statement::{ForNumeric, ForStatement, Statement};
+use typedlua_parser::string_interner::{StringId, StringInterner};
 
-impl TablePreallocationPass {
-    pub fn new() -> Self {
-        Self
-    }
-}
+/// Largest loop bound sized with a `{nil, nil, ...}` constructor; longer
+/// arrays go through `__tableCreate` on the Lua 5.1 target
+const MAX_NIL_PREFILL: usize = 16;
 
-impl<'arena> ExprVisitor<'arena> for TablePreallocationPass {
-    fn visit_expr(&mut self, _expr: &mut Expression<'arena>, _arena: &'arena Bump) -> bool {
-        // This pass is currently analysis-only, no transformations
-        // Future: Could add metadata to table expressions for codegen hints
-        false
-    }
+/// Sizes empty arrays that the following counted loop fills.
+///
+/// ```lua
+/// local squares = {}
+/// for i = 1, n do squares[i] = i * i end
+/// ```
+///
+/// becomes `local squares = __tableCreate(n)` on the Lua 5.1 target, which
+/// codegen backs with `table.create` (Luau) or `table.new` (LuaJIT). PUC Lua
+/// 5.2-5.4 have no sized constructor callable from Lua, so the array stays
+/// `{}` there. A literal bound of up to [`MAX_NIL_PREFILL`] entries becomes
+/// `{nil, nil, ...}` on every target, whose constructor sizes the array part.
+pub struct TablePreallocationPass {
+    interner: Arc<StringInterner>,
+    target: LuaTarget,
 }
 
-impl<'arena> WholeProgramPass<'arena> for TablePreallocationPass {
-    fn name(&self) -> &'static str {
-        "table-preallocation"
+impl TablePreallocationPass {
+    pub fn new(interner: Arc<StringInterner>) -> Self {
+        Self {
+            interner,
+            target: LuaTarget::default(),
+        }
     }
 
-    fn min_level(&self) -> OptimizationLevel {
-        OptimizationLevel::O1
+    /// Lua version the output runs on; only 5.1 (LuaJIT, Luau) gets `__tableCreate`
+    pub fn with_target(mut self, target: LuaTarget) -> Self {
+        self.target = target;
+        self
     }
+}
 
-    fn run(
+impl<'arena> BlockVisitor<'arena> for TablePreallocationPass {
+    fn visit_block_stmts(
         &mut self,
-        program: &mut MutableProgram<'arena>,
-        _arena: &'arena Bump,
-    ) -> Result<bool, String> {
-        // Analyze table constructors and collect size information
-        // This pass doesn't modify the AST directly, but could add metadata
-        // for codegen to generate table.create() calls with size hints
-        let mut _table_count = 0;
-
-        for stmt in &program.statements {
-            _table_count += self.count_tables_in_statement(stmt);
+        stmts: &mut Vec<Statement<'arena>>,
+        arena: &'arena Bump,
+    ) -> bool {
+        let mut changed = false;
+        for i in 1..stmts.len() {
+            let Statement::For(for_stmt) = stmts[i] else {
+                continue;
+            };
+            let ForStatement::Numeric(for_num) = for_stmt else {
+                continue;
+            };
+            let Statement::Variable(decl) = &mut stmts[i - 1] else {
+                continue;
+            };
+            let Pattern::Identifier(ident) = &decl.pattern else {
+                continue;
+            };
+            if !matches!(&decl.initializer.kind, ExpressionKind::Array(elements) if elements.is_empty())
+            {
+                continue;
+            }
+            let array = ident.node;
+            if !self.loop_fills(for_num, array) {
+                continue;
+            }
+            if let Some(initializer) = self.sized_initializer(for_num, &decl.initializer, arena) {
+                decl.initializer = initializer;
+                changed = true;
+            }
         }
-
-        // Currently a no-op analysis pass - codegen handles preallocation
-        Ok(false)
-    }
-
-    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
-        self
+        changed
     }
 }
 
 impl TablePreallocationPass {
-    fn count_tables_in_statement<'arena>(&self, stmt: &Statement<'arena>) -> usize {
-        match stmt {
-            Statement::Variable(decl) => self.count_tables_in_expression(&decl.initializer),
-            Statement::Expression(expr) => self.count_tables_in_expression(expr),
-            Statement::If(if_stmt) => {
-                let mut count = 0;
-                for s in if_stmt.then_block.statements.iter() {
-                    count += self.count_tables_in_statement(s);
-                }
-                for else_if in if_stmt.else_ifs.iter() {
-                    for s in else_if.block.statements.iter() {
-                        count += self.count_tables_in_statement(s);
-                    }
-                }
-                if let Some(else_block) = &if_stmt.else_block {
-                    for s in else_block.statements.iter() {
-                        count += self.count_tables_in_statement(s);
-                    }
-                }
-                count
+    /// Whether the loop counts up to a stable bound and its body stores into
+    /// `array` through `array[k] = v` or `table.insert(array, v)`
+    fn loop_fills(&self, for_num: &ForNumeric<'_>, array: StringId) -> bool {
+        let counts_up = match &for_num.step {
+            None => true,
+            Some(step) => {
+                matches!(step.kind, ExpressionKind::Literal(Literal::Number(n)) if n == 1.0)
             }
-            Statement::Function(func) => {
-                let mut count = 0;
-                for s in func.body.statements.iter() {
-                    count += self.count_tables_in_statement(s);
+        };
+        if !counts_up || !is_stable_bound(&for_num.end, array) {
+            return false;
+        }
+        for_num.body.statements.iter().any(|stmt| {
+            let Statement::Expression(expr) = stmt else {
+                return false;
+            };
+            match &expr.kind {
+                ExpressionKind::Assignment(target, AssignmentOp::Assign, _) => matches!(
+                    &target.kind,
+                    ExpressionKind::Index(object, _)
+                        if matches!(object.kind, ExpressionKind::Identifier(id) if id == array)
+                ),
+                ExpressionKind::Call(callee, args, _) => {
+                    self.is_table_insert(callee)
+                        && args.len() == 2
+                        && matches!(args[0].value.kind, ExpressionKind::Identifier(id) if id == array)
                 }
-                count
+                _ => false,
             }
-            _ => 0,
-        }
+        })
     }
 
-    fn count_tables_in_expression<'arena>(&self, expr: &Expression<'arena>) -> usize {
-        use typedlua_parser::ast::expression::ExpressionKind;
+    fn is_table_insert(&self, callee: &Expression<'_>) -> bool {
+        let ExpressionKind::Member(object, member) = &callee.kind else {
+            return false;
+        };
+        matches!(object.kind, ExpressionKind::Identifier(id) if self.interner.resolve(id) == "table")
+            && self.interner.resolve(member.node) == "insert"
+    }
 
-        match &expr.kind {
-            ExpressionKind::Object(fields) => {
-                let mut count = 1; // Count this table
-                for field in fields.iter() {
-                    match field {
-                        typedlua_parser::ast::expression::ObjectProperty::Property {
-                            value,
-                            ..
-                        } => {
-                            count += self.count_tables_in_expression(value);
-                        }
-                        typedlua_parser::ast::expression::ObjectProperty::Computed {
-                            value,
-                            ..
-                        } => {
-                            count += self.count_tables_in_expression(value);
-                        }
-                        typedlua_parser::ast::expression::ObjectProperty::Spread {
-                            value, ..
-                        } => {
-                            count += self.count_tables_in_expression(value);
-                        }
-                    }
-                }
-                count
-            }
-            ExpressionKind::Array(elements) => {
-                let mut count = 1; // Count this array
-                for elem in elements.iter() {
-                    match elem {
-                        typedlua_parser::ast::expression::ArrayElement::Expression(expr) => {
-                            count += self.count_tables_in_expression(expr);
-                        }
-                        typedlua_parser::ast::expression::ArrayElement::Spread(expr) => {
-                            count += self.count_tables_in_expression(expr);
-                        }
-                    }
-                }
-                count
-            }
-            ExpressionKind::Binary(_, left, right) => {
-                self.count_tables_in_expression(left) + self.count_tables_in_expression(right)
+    /// The constructor for an array with room for one entry per iteration
+    fn sized_initializer<'arena>(
+        &self,
+        for_num: &ForNumeric<'arena>,
+        empty: &Expression<'arena>,
+        arena: &'arena Bump,
+    ) -> Option<Expression<'arena>> {
+        let ExpressionKind::Literal(Literal::Number(start)) = for_num.start.kind else {
+            return None;
+        };
+        if start.fract() != 0.0 {
+            return None;
+        }
+        let span = empty.span;
+
+        if let ExpressionKind::Literal(Literal::Number(end)) = for_num.end.kind {
+            let count = end.floor() - start + 1.0;
+            if count < 1.0 {
+                return None;
             }
-            ExpressionKind::Unary(_, operand) => self.count_tables_in_expression(operand),
-            ExpressionKind::Call(func, args, _) => {
-                let mut count = self.count_tables_in_expression(func);
-                for arg in args.iter() {
-                    count += self.count_tables_in_expression(&arg.value);
-                }
-                count
+            if count <= MAX_NIL_PREFILL as f64 {
+                let nil = Expression::new(ExpressionKind::Literal(Literal::Nil), span);
+                let nils = vec![ArrayElement::Expression(nil); count as usize];
+                return Some(Expression::new(
+                    ExpressionKind::Array(arena.alloc_slice_clone(&nils)),
+                    span,
+                ));
             }
-            _ => 0,
         }
+        if self.target != LuaTarget::Lua51 {
+            return None;
+        }
+
+        // `end - start + 1` entries; the bound is re-evaluated, so it must be stable
+        let count = if start == 1.0 {
+            for_num.end.clone()
+        } else {
+            Expression::new(
+                ExpressionKind::Binary(
+                    BinaryOp::Subtract,
+                    arena.alloc(for_num.end.clone()),
+                    arena.alloc(Expression::new(
+                        ExpressionKind::Literal(Literal::Number(start - 1.0)),
+                        span,
+                    )),
+                ),
+                span,
+            )
+        };
+        let create = self.interner.get_or_intern("__tableCreate");
+        Some(Expression::new(
+            ExpressionKind::Call(
+                arena.alloc(Expression::new(ExpressionKind::Identifier(create), span)),
+                arena.alloc_slice_clone(&[Argument {
+                    value: count,
+                    is_spread: false,
+                    span,
+                }]),
+                None,
+            ),
+            span,
+        ))
+    }
+}
+
+/// A loop bound that can be evaluated once more before the loop without
+/// side effects or a different result: a number, a local, or `#local`
+fn is_stable_bound(expr: &Expression<'_>, array: StringId) -> bool {
+    match &expr.kind {
+        ExpressionKind::Literal(Literal::Number(_)) => true,
+        ExpressionKind::Identifier(id) => *id != array,
+        ExpressionKind::Unary(UnaryOp::Length, operand) => {
+            matches!(operand.kind, ExpressionKind::Identifier(id) if id != array)
+        }
+        ExpressionKind::Parenthesized(inner) => is_stable_bound(inner, array),
+        _ => false,
     }
 }
 
 impl Default for TablePreallocationPass {
     fn default() -> Self {
-        Self::new()
+        Self::new(Arc::new(StringInterner::new()))
     }
 }
@@ -0,0 +1,560 @@
+//! Execution counts from instrumented runs, for profile-guided optimization
+//!
+//! Code generated with `--instrument=pgo` counts how often every function and
+//! method is entered, every anonymous function is called, every `if`/`match`
+//! arm is taken and every loop body runs, and writes the counts to a profile
+//! file when the program exits. Each line holds a counter key and its count:
+//!
+//! ```text
+//! src/main.tl:12:5:function:clamp	1043
+//! src/main.tl:14:9:branch:clamp	998
+//! ```
+//!
+//! A key names the module, the line and column of the counted construct, what
+//! it counts and a label: the name of a function, or of the function
+//! enclosing any other construct. [`ModuleProfile::bind`] maps the counters of
+//! a module back onto its AST. When the source was edited after the profile
+//! was recorded, functions are found again by name, other counters move with
+//! their enclosing function and then settle on the nearest construct of their
+//! kind within a few lines. Counters that cannot be placed are dropped, so a
+//! stale profile costs optimizations, never correctness.
+//!
+//! Instrumented code writes only the counters that fired. An arm, loop, closure
+//! or nested function with no counter inside a function the profile counted is
+//! therefore bound with a count of 0.
+
+use super::{walk_expression, walk_statement, MutVisitor};
+use crate::MutableProgram;
+use bumpalo::Bump;
+use rustc_hash::FxHashMap;
+use std::cell::RefCell;
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::Arc;
+use typedlua_parser::ast::expression::{Expression, ExpressionKind, MatchArmBody};
+use typedlua_parser::ast::statement::{ClassMember, ForStatement, Statement};
+use typedlua_parser::span::Span;
+use typedlua_parser::string_interner::StringInterner;
+
+/// Furthest a counter other than a function entry moves to find its construct
+const MAX_LINE_DRIFT: u32 = 8;
+
+/// A site is hot when it ran at least this often...
+const MIN_HOT_COUNT: u64 = 100;
+
+/// ...and at least a tenth as often as the hottest site of its module
+const HOT_RATIO: u64 = 10;
+
+/// A site is cold when it never ran, or ran a thousand times less often than
+/// the hottest site of its module
+const COLD_RATIO: u64 = 1000;
+
+/// What a counter counts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum CounterKind {
+    /// Entries to a named function or method, labelled with its name
+    /// (`clamp`, `Vector.length`)
+    Function,
+    /// Calls to an anonymous function
+    Closure,
+    /// Times an `if` or `match` arm was taken
+    Branch,
+    /// Runs of a loop body
+    Loop,
+}
+
+impl CounterKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CounterKind::Function => "function",
+            CounterKind::Closure => "closure",
+            CounterKind::Branch => "branch",
+            CounterKind::Loop => "loop",
+        }
+    }
+
+    fn parse(text: &str) -> Option<Self> {
+        match text {
+            "function" => Some(CounterKind::Function),
+            "closure" => Some(CounterKind::Closure),
+            "branch" => Some(CounterKind::Branch),
+            "loop" => Some(CounterKind::Loop),
+            _ => None,
+        }
+    }
+}
+
+/// Key instrumented code counts the construct at `line`:`column` under
+pub fn counter_key(module: &str, kind: CounterKind, line: u32, column: u32, label: &str) -> String {
+    format!("{}:{}:{}:{}:{}", module, line, column, kind.as_str(), label)
+}
+
+/// One counter of a module
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Counter {
+    pub kind: CounterKind,
+    pub line: u32,
+    pub column: u32,
+    pub label: String,
+    pub count: u64,
+}
+
+/// Counters read from a profile file, by module id
+#[derive(Debug, Clone, Default)]
+pub struct Profile {
+    modules: FxHashMap<String, Arc<ModuleProfile>>,
+}
+
+impl Profile {
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        Self::parse(&content).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Parse `key<TAB>count` lines. A key listed more than once has its counts
+    /// added up, so the profiles of several runs can be concatenated.
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let mut counts: FxHashMap<(String, CounterKind, u32, u32, String), u64> =
+            FxHashMap::default();
+        for (index, line) in content.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let invalid = || format!("line {}: invalid profile entry '{}'", index + 1, line);
+            let (key, count) = line.rsplit_once('\t').ok_or_else(invalid)?;
+            let count: u64 = count.trim().parse().map_err(|_| invalid())?;
+            let mut fields = key.rsplitn(5, ':');
+            let label = fields.next().ok_or_else(invalid)?;
+            let kind = fields
+                .next()
+                .and_then(CounterKind::parse)
+                .ok_or_else(invalid)?;
+            let column = fields
+                .next()
+                .and_then(|column| column.parse().ok())
+                .ok_or_else(invalid)?;
+            let line_number = fields
+                .next()
+                .and_then(|line| line.parse().ok())
+                .ok_or_else(invalid)?;
+            let module = fields.next().ok_or_else(invalid)?;
+            *counts
+                .entry((
+                    module.to_string(),
+                    kind,
+                    line_number,
+                    column,
+                    label.to_string(),
+                ))
+                .or_default() += count;
+        }
+
+        let mut modules: FxHashMap<String, ModuleProfile> = FxHashMap::default();
+        for ((module, kind, line, column, label), count) in counts {
+            modules.entry(module).or_default().counters.push(Counter {
+                kind,
+                line,
+                column,
+                label,
+                count,
+            });
+        }
+        Ok(Self {
+            modules: modules
+                .into_iter()
+                .map(|(id, mut module)| {
+                    module.counters.sort_by(|a, b| {
+                        (a.line, a.column, a.kind).cmp(&(b.line, b.column, b.kind))
+                    });
+                    (id, Arc::new(module))
+                })
+                .collect(),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.modules.is_empty()
+    }
+
+    /// Counters of the module instrumented as `id`
+    pub fn module(&self, id: &str) -> Option<Arc<ModuleProfile>> {
+        self.modules.get(id).cloned()
+    }
+}
+
+/// Counters of one module, in source order
+#[derive(Debug, Clone, Default)]
+pub struct ModuleProfile {
+    counters: Vec<Counter>,
+}
+
+impl ModuleProfile {
+    pub fn counters(&self) -> &[Counter] {
+        &self.counters
+    }
+
+    /// Place the counters on the constructs of `program` they were recorded for
+    pub fn bind<'arena>(
+        &self,
+        program: &MutableProgram<'arena>,
+        interner: &StringInterner,
+        arena: &'arena Bump,
+    ) -> ProfileCounts {
+        let mut collector = SiteCollector {
+            interner,
+            sites: Vec::new(),
+        };
+        let mut statements = program.statements.clone();
+        collector.visit_block(&mut statements, arena);
+        let mut sites = collector.sites;
+        label_sites(&mut sites);
+
+        let mut bound = vec![false; sites.len()];
+        let mut counts = FxHashMap::default();
+        // Line each function moved by since the profile was recorded
+        let mut shifts: FxHashMap<&str, i64> = FxHashMap::default();
+        let (functions, others): (Vec<&Counter>, Vec<&Counter>) = self
+            .counters
+            .iter()
+            .partition(|counter| counter.kind == CounterKind::Function);
+
+        for counter in functions {
+            if let Some(index) = closest_site(&sites, &bound, counter, counter.line, None) {
+                bound[index] = true;
+                let site = &sites[index];
+                shifts.insert(&counter.label, site.span.line as i64 - counter.line as i64);
+                counts.insert((site.span.start, site.span.end), counter.count);
+            }
+        }
+        for counter in others {
+            let shift = shifts.get(counter.label.as_str()).copied().unwrap_or(0);
+            let line = (counter.line as i64 + shift).max(0) as u32;
+            if let Some(index) = closest_site(&sites, &bound, counter, line, Some(MAX_LINE_DRIFT)) {
+                bound[index] = true;
+                let site = &sites[index];
+                counts.insert((site.span.start, site.span.end), counter.count);
+            }
+        }
+
+        // Instrumented code only writes the counters that fired, so a construct
+        // left without one inside a function the profile counted never ran
+        let functions: Vec<(Span, bool)> = sites
+            .iter()
+            .zip(&bound)
+            .filter(|(site, _)| site.kind == CounterKind::Function)
+            .map(|(site, bound)| (site.span, *bound))
+            .collect();
+        for (site, _) in sites.iter().zip(&bound).filter(|(_, bound)| !**bound) {
+            let enclosing = functions
+                .iter()
+                .filter(|(span, _)| {
+                    span.start <= site.span.start
+                        && site.span.end <= span.end
+                        && (span.start, span.end) != (site.span.start, site.span.end)
+                })
+                .min_by_key(|(span, _)| span.end - span.start);
+            if enclosing.is_some_and(|(_, counted)| *counted) {
+                counts.insert((site.span.start, site.span.end), 0);
+            }
+        }
+
+        let peak = counts.values().copied().max().unwrap_or(0);
+        ProfileCounts { counts, peak }
+    }
+}
+
+/// Profile of the module being optimized, shared by the optimizer with the
+/// passes inside its composites; empty until `Optimizer::set_profile`
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SharedProfile(Rc<RefCell<Option<Arc<ModuleProfile>>>>);
+
+impl SharedProfile {
+    pub(crate) fn set(&self, profile: Arc<ModuleProfile>) {
+        *self.0.borrow_mut() = Some(profile);
+    }
+
+    pub(crate) fn get(&self) -> Option<Arc<ModuleProfile>> {
+        self.0.borrow().clone()
+    }
+}
+
+/// Site of `counter`'s kind and label nearest to `line`, ties broken by
+/// column; `None` when nothing lies within `max_drift` lines or two sites are
+/// equally close
+fn closest_site(
+    sites: &[Site],
+    bound: &[bool],
+    counter: &Counter,
+    line: u32,
+    max_drift: Option<u32>,
+) -> Option<usize> {
+    let mut best: Option<((u32, u32), usize)> = None;
+    let mut tied = false;
+    for (index, site) in sites.iter().enumerate() {
+        if bound[index] || site.kind != counter.kind || site.label != counter.label {
+            continue;
+        }
+        let distance = (
+            site.span.line.abs_diff(line),
+            site.span.column.abs_diff(counter.column),
+        );
+        if max_drift.is_some_and(|max| distance.0 > max) {
+            continue;
+        }
+        match best {
+            Some((closest, _)) if distance > closest => {}
+            Some((closest, _)) if distance == closest => tied = true,
+            _ => {
+                best = Some((distance, index));
+                tied = false;
+            }
+        }
+    }
+    best.filter(|_| !tied).map(|(_, index)| index)
+}
+
+/// Profile counts placed on the spans of one program
+#[derive(Debug, Clone, Default)]
+pub struct ProfileCounts {
+    /// Count per counted construct, by span start and end
+    counts: FxHashMap<(u32, u32), u64>,
+    /// Count of the most frequently run construct
+    peak: u64,
+}
+
+impl ProfileCounts {
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    /// Count of the construct at exactly `span`: a function, an `if` or
+    /// `match` arm body or a loop body
+    pub fn count_of(&self, span: Span) -> Option<u64> {
+        self.counts.get(&(span.start, span.end)).copied()
+    }
+
+    /// How often code at `span` ran: the count of the innermost counted
+    /// construct around it. `None` outside any function, arm or loop.
+    pub fn count_at(&self, span: Span) -> Option<u64> {
+        self.counts
+            .iter()
+            .filter(|((start, end), _)| *start <= span.start && span.end <= *end)
+            .min_by_key(|((start, end), _)| end - start)
+            .map(|(_, count)| *count)
+    }
+
+    pub fn is_hot(&self, span: Span) -> bool {
+        self.count_at(span)
+            .is_some_and(|count| count >= MIN_HOT_COUNT && count * HOT_RATIO >= self.peak)
+    }
+
+    pub fn is_cold(&self, span: Span) -> bool {
+        self.count_at(span)
+            .is_some_and(|count| count == 0 || count.saturating_mul(COLD_RATIO) < self.peak)
+    }
+}
+
+/// A construct instrumented builds count
+struct Site {
+    kind: CounterKind,
+    span: Span,
+    /// Function name, or the name of the enclosing function
+    label: String,
+}
+
+/// Finds the constructs `CodeGenerator` instruments
+struct SiteCollector<'p> {
+    interner: &'p StringInterner,
+    sites: Vec<Site>,
+}
+
+impl SiteCollector<'_> {
+    fn push(&mut self, kind: CounterKind, span: Span) {
+        self.sites.push(Site {
+            kind,
+            span,
+            label: String::new(),
+        });
+    }
+}
+
+impl<'arena> MutVisitor<'arena> for SiteCollector<'_> {
+    fn visit_statement(&mut self, stmt: &mut Statement<'arena>, arena: &'arena Bump) -> bool {
+        match stmt {
+            Statement::Function(func) => self.sites.push(Site {
+                kind: CounterKind::Function,
+                span: func.span,
+                label: self.interner.resolve(func.name.node).to_string(),
+            }),
+            Statement::Class(class) => {
+                let class_name = self.interner.resolve(class.name.node);
+                for member in class.members.iter() {
+                    if let ClassMember::Method(method) = member {
+                        if method.body.is_some() {
+                            self.sites.push(Site {
+                                kind: CounterKind::Function,
+                                span: method.span,
+                                label: format!(
+                                    "{}.{}",
+                                    class_name,
+                                    self.interner.resolve(method.name.node)
+                                ),
+                            });
+                        }
+                    }
+                }
+            }
+            Statement::If(if_stmt) => {
+                self.push(CounterKind::Branch, if_stmt.then_block.span);
+                for else_if in if_stmt.else_ifs.iter() {
+                    self.push(CounterKind::Branch, else_if.block.span);
+                }
+                if let Some(else_block) = &if_stmt.else_block {
+                    self.push(CounterKind::Branch, else_block.span);
+                }
+            }
+            Statement::While(while_stmt) => self.push(CounterKind::Loop, while_stmt.body.span),
+            Statement::Repeat(repeat_stmt) => self.push(CounterKind::Loop, repeat_stmt.body.span),
+            Statement::For(for_stmt) => {
+                let body = match &**for_stmt {
+                    ForStatement::Numeric(numeric) => &numeric.body,
+                    ForStatement::Generic(generic) => &generic.body,
+                };
+                self.push(CounterKind::Loop, body.span);
+            }
+            _ => {}
+        }
+        walk_statement(self, stmt, arena)
+    }
+
+    fn visit_expression(&mut self, expr: &mut Expression<'arena>, arena: &'arena Bump) -> bool {
+        match &expr.kind {
+            ExpressionKind::Arrow(_) | ExpressionKind::Function(_) => {
+                self.push(CounterKind::Closure, expr.span)
+            }
+            ExpressionKind::Match(match_expr) => {
+                for arm in match_expr.arms.iter() {
+                    self.push(CounterKind::Branch, match_arm_span(&arm.body));
+                }
+            }
+            _ => {}
+        }
+        walk_expression(self, expr, arena)
+    }
+}
+
+/// Span a `match` arm is counted under
+pub(crate) fn match_arm_span(body: &MatchArmBody<'_>) -> Span {
+    match body {
+        MatchArmBody::Expression(expr) => expr.span,
+        MatchArmBody::Block(block) => block.span,
+    }
+}
+
+/// Label every site but a function with its innermost enclosing function
+fn label_sites(sites: &mut [Site]) {
+    let functions: Vec<(Span, String)> = sites
+        .iter()
+        .filter(|site| site.kind == CounterKind::Function)
+        .map(|site| (site.span, site.label.clone()))
+        .collect();
+    for site in sites.iter_mut() {
+        if site.kind == CounterKind::Function {
+            continue;
+        }
+        site.label = functions
+            .iter()
+            .filter(|(span, _)| span.start <= site.span.start && site.span.end <= span.end)
+            .min_by_key(|(span, _)| span.end - span.start)
+            .map(|(_, name)| name.clone())
+            .unwrap_or_default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_groups_counters_by_module() {
+        let profile = Profile::parse(
+            "src/a.tl:3:1:function:area\t12\nsrc/b.tl:7:5:loop:sum\t40\nsrc/a.tl:2:1:function:size\t3\n",
+        )
+        .unwrap();
+        let a = profile.module("src/a.tl").unwrap();
+        assert_eq!(
+            a.counters(),
+            &[
+                Counter {
+                    kind: CounterKind::Function,
+                    line: 2,
+                    column: 1,
+                    label: "size".to_string(),
+                    count: 3,
+                },
+                Counter {
+                    kind: CounterKind::Function,
+                    line: 3,
+                    column: 1,
+                    label: "area".to_string(),
+                    count: 12,
+                },
+            ]
+        );
+        assert_eq!(profile.module("src/b.tl").unwrap().counters()[0].count, 40);
+        assert!(profile.module("src/c.tl").is_none());
+    }
+
+    #[test]
+    fn test_parse_adds_up_repeated_keys() {
+        let profile =
+            Profile::parse("src/a.tl:4:9:branch:\t5\n\nsrc/a.tl:4:9:branch:\t7\n").unwrap();
+        let counters = profile.module("src/a.tl").unwrap();
+        assert_eq!(counters.counters().len(), 1);
+        assert_eq!(counters.counters()[0].count, 12);
+        assert_eq!(counters.counters()[0].label, "");
+    }
+
+    #[test]
+    fn test_parse_keeps_colons_in_module_ids() {
+        let key = counter_key(r"C:\game\main.tl", CounterKind::Closure, 9, 14, "update");
+        let profile = Profile::parse(&format!("{}\t2\n", key)).unwrap();
+        let counter = &profile.module(r"C:\game\main.tl").unwrap().counters()[0];
+        assert_eq!(counter.kind, CounterKind::Closure);
+        assert_eq!((counter.line, counter.column), (9, 14));
+        assert_eq!(counter.label, "update");
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed_lines() {
+        assert!(Profile::parse("src/a.tl:3:1:function:area 12\n").is_err());
+        assert!(Profile::parse("src/a.tl:3:1:method:area\t12\n").is_err());
+        assert!(Profile::parse("src/a.tl:x:1:function:area\t12\n").is_err());
+        assert!(Profile::parse("src/a.tl:3:1:function:area\tmany\n").is_err());
+    }
+
+    #[test]
+    fn test_hot_and_cold_sites() {
+        let counts = ProfileCounts {
+            counts: [
+                ((0, 100), 5000),
+                ((10, 20), 4000),
+                ((30, 40), 0),
+                ((50, 60), 300),
+            ]
+            .into_iter()
+            .collect(),
+            peak: 5000,
+        };
+        let span = |start, end| Span::new(start, end, 1, 1);
+        assert_eq!(counts.count_at(span(12, 15)), Some(4000));
+        assert_eq!(counts.count_at(span(42, 45)), Some(5000));
+        assert_eq!(counts.count_at(span(120, 125)), None);
+        assert!(counts.is_hot(span(12, 15)));
+        assert!(!counts.is_hot(span(52, 55)));
+        assert!(counts.is_cold(span(32, 35)));
+        assert!(!counts.is_cold(span(52, 55)));
+        assert!(!counts.is_cold(span(120, 125)));
+    }
+}
@@ -0,0 +1,288 @@
+//! Optimization remarks
+//!
+//! Passes record what they did, and what they declined to do and why, as
+//! structured remarks in the spirit of clang's `-Rpass` / `-Rpass-missed`.
+//! The [`Optimizer`](super::Optimizer) hands every pass a clone of one shared
+//! [`RemarkSink`]; recording is a no-op until the sink is enabled, so passes
+//! can report unconditionally without paying for message formatting.
+
+use rustc_hash::FxHashSet;
+use serde_json::{json, Value};
+use std::cell::RefCell;
+use std::fmt::Write;
+use std::rc::Rc;
+use std::time::Duration;
+use typedlua_parser::span::Span;
+
+/// Whether a remark describes a transformation that happened or one that was
+/// considered and rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemarkKind {
+    Applied,
+    Missed,
+}
+
+impl RemarkKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RemarkKind::Applied => "applied",
+            RemarkKind::Missed => "missed",
+        }
+    }
+}
+
+/// One optimization decision at a source location
+#[derive(Debug, Clone)]
+pub struct Remark {
+    pub pass: &'static str,
+    pub span: Span,
+    pub kind: RemarkKind,
+    /// What was done, e.g. "inlined `foo` into `bar`"
+    pub message: String,
+    /// Why, e.g. "body has 7 statements > 5"
+    pub reason: Option<String>,
+}
+
+/// Wall time of one pass run within the fixed-point loop
+#[derive(Debug, Clone)]
+pub struct PassTiming {
+    pub pass: &'static str,
+    pub iteration: usize,
+    pub elapsed: Duration,
+    pub changed: bool,
+}
+
+#[derive(Default)]
+struct RemarkLog {
+    enabled: bool,
+    remarks: Vec<Remark>,
+    /// (pass, span start, span end, message) of every recorded remark; the
+    /// fixed-point loop revisits the same call sites on every iteration
+    seen: FxHashSet<(&'static str, u32, u32, String)>,
+}
+
+/// Shared, cheaply cloned collector that passes record remarks into
+#[derive(Clone, Default)]
+pub struct RemarkSink {
+    log: Rc<RefCell<RemarkLog>>,
+}
+
+impl RemarkSink {
+    pub fn enable(&self) {
+        self.log.borrow_mut().enabled = true;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.log.borrow().enabled
+    }
+
+    /// Record a transformation the pass performed
+    pub fn applied(&self, pass: &'static str, span: Span, message: impl FnOnce() -> String) {
+        if self.is_enabled() {
+            self.record(pass, span, RemarkKind::Applied, message(), None);
+        }
+    }
+
+    /// Record a transformation the pass considered and rejected
+    pub fn missed(
+        &self,
+        pass: &'static str,
+        span: Span,
+        message: impl FnOnce() -> String,
+        reason: impl FnOnce() -> String,
+    ) {
+        if self.is_enabled() {
+            self.record(pass, span, RemarkKind::Missed, message(), Some(reason()));
+        }
+    }
+
+    fn record(
+        &self,
+        pass: &'static str,
+        span: Span,
+        kind: RemarkKind,
+        message: String,
+        reason: Option<String>,
+    ) {
+        let mut log = self.log.borrow_mut();
+        let key = (pass, span.start, span.end, message.clone());
+        if log.seen.insert(key) {
+            log.remarks.push(Remark {
+                pass,
+                span,
+                kind,
+                message,
+                reason,
+            });
+        }
+    }
+
+    /// Remove and return the recorded remarks in the order they were made
+    pub fn take(&self) -> Vec<Remark> {
+        let mut log = self.log.borrow_mut();
+        log.seen.clear();
+        std::mem::take(&mut log.remarks)
+    }
+}
+
+/// Remarks and pass timings from one [`Optimizer::optimize`] run
+///
+/// [`Optimizer::optimize`]: super::Optimizer::optimize
+#[derive(Debug, Clone, Default)]
+pub struct OptimizationReport {
+    pub remarks: Vec<Remark>,
+    pub timings: Vec<PassTiming>,
+    pub iterations: usize,
+}
+
+impl OptimizationReport {
+    /// Render remarks one per line as `module:line:col: kind: message [pass]`
+    pub fn remarks_to_text(&self, module: &str) -> String {
+        let mut out = String::new();
+        for remark in &self.remarks {
+            let _ = write!(
+                out,
+                "{}:{}:{}: {}: {}",
+                module,
+                remark.span.line,
+                remark.span.column,
+                remark.kind.as_str(),
+                remark.message
+            );
+            if let Some(reason) = &remark.reason {
+                let _ = write!(out, ": {}", reason);
+            }
+            let _ = writeln!(out, " [{}]", remark.pass);
+        }
+        out
+    }
+
+    /// Render pass timings, one line per pass run
+    pub fn timings_to_text(&self, module: &str) -> String {
+        let mut out = format!("{}: {} iteration(s)\n", module, self.iterations);
+        for timing in &self.timings {
+            let _ = writeln!(
+                out,
+                "  [iter {}] {}: {:?}{}",
+                timing.iteration,
+                timing.pass,
+                timing.elapsed,
+                if timing.changed { " (changed)" } else { "" }
+            );
+        }
+        out
+    }
+
+    /// The report as a JSON object; `timings` is included when requested
+    pub fn to_json(&self, module: &str, timings: bool) -> Value {
+        let remarks: Vec<Value> = self
+            .remarks
+            .iter()
+            .map(|remark| {
+                json!({
+                    "pass": remark.pass,
+                    "kind": remark.kind.as_str(),
+                    "line": remark.span.line,
+                    "column": remark.span.column,
+                    "message": remark.message,
+                    "reason": remark.reason,
+                })
+            })
+            .collect();
+        let mut report = json!({
+            "module": module,
+            "iterations": self.iterations,
+            "remarks": remarks,
+        });
+        if timings {
+            report["timings"] = self
+                .timings
+                .iter()
+                .map(|timing| {
+                    json!({
+                        "pass": timing.pass,
+                        "iteration": timing.iteration,
+                        "micros": timing.elapsed.as_micros() as u64,
+                        "changed": timing.changed,
+                    })
+                })
+                .collect();
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(start: u32, line: u32) -> Span {
+        Span::new(start, start + 3, line, 5)
+    }
+
+    #[test]
+    fn test_disabled_sink_records_nothing() {
+        let sink = RemarkSink::default();
+        sink.applied("function-inlining", span(0, 1), || {
+            unreachable!("message is not built while disabled")
+        });
+        assert!(sink.take().is_empty());
+    }
+
+    #[test]
+    fn test_repeated_remarks_are_recorded_once() {
+        let sink = RemarkSink::default();
+        sink.enable();
+        let clone = sink.clone();
+        for _ in 0..3 {
+            clone.missed(
+                "function-inlining",
+                span(10, 2),
+                || "not inlined `big` into `main`".to_string(),
+                || "body has 7 statements > 5".to_string(),
+            );
+        }
+        sink.applied("function-inlining", span(20, 3), || {
+            "inlined `add` into `main`".to_string()
+        });
+
+        let remarks = sink.take();
+        assert_eq!(remarks.len(), 2);
+        assert_eq!(remarks[0].kind, RemarkKind::Missed);
+        assert_eq!(remarks[1].message, "inlined `add` into `main`");
+    }
+
+    #[test]
+    fn test_report_formats() {
+        let report = OptimizationReport {
+            remarks: vec![Remark {
+                pass: "function-inlining",
+                span: span(10, 2),
+                kind: RemarkKind::Missed,
+                message: "not inlined `big` into `main`".to_string(),
+                reason: Some("body has 7 statements > 5".to_string()),
+            }],
+            timings: vec![PassTiming {
+                pass: "expression-transforms",
+                iteration: 1,
+                elapsed: Duration::from_micros(12),
+                changed: true,
+            }],
+            iterations: 1,
+        };
+
+        assert_eq!(
+            report.remarks_to_text("main.tl"),
+            "main.tl:2:5: missed: not inlined `big` into `main`: body has 7 statements > 5 [function-inlining]\n"
+        );
+        assert!(report
+            .timings_to_text("main.tl")
+            .contains("[iter 1] expression-transforms: 12µs (changed)"));
+
+        let json = report.to_json("main.tl", false);
+        assert_eq!(json["remarks"][0]["reason"], "body has 7 statements > 5");
+        assert_eq!(json["remarks"][0]["line"], 2);
+        assert!(json.get("timings").is_none());
+        assert_eq!(report.to_json("main.tl", true)["timings"][0]["micros"], 12);
+    }
+}
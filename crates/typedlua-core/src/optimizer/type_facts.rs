@@ -0,0 +1,325 @@
+//! Facts established by type checking
+//!
+//! The optimizer also runs on programs that were never checked, so declared
+//! types are only trusted once the caller hands over [`TypeFacts`] collected
+//! after `TypeChecker::check_program` succeeded. Without them, type-directed
+//! passes leave the program alone.
+
+use super::passes::pattern_binders;
+use super::{walk_expression, walk_pattern, walk_statement, MutVisitor};
+use crate::MutableProgram;
+use bumpalo::Bump;
+use rustc_hash::FxHashSet;
+use typedlua_parser::ast::expression::{
+    ArrayElement, BinaryOp, Expression, ExpressionKind, Literal, UnaryOp,
+};
+use typedlua_parser::ast::pattern::Pattern;
+use typedlua_parser::ast::statement::{
+    CatchPattern, ClassMember, ForStatement, ImportClause, Parameter, Statement,
+};
+use typedlua_parser::ast::types::{PrimitiveType, Type, TypeKind};
+use typedlua_parser::string_interner::{StringId, StringInterner};
+
+/// What a type-checked program guarantees about its names
+#[derive(Clone, Debug, Default)]
+pub struct TypeFacts {
+    arrays: FxHashSet<StringId>,
+}
+
+impl TypeFacts {
+    /// Collect the facts of a program that passed type checking
+    pub fn from_checked_program<'arena>(
+        program: &MutableProgram<'arena>,
+        interner: &StringInterner,
+        arena: &'arena Bump,
+    ) -> Self {
+        let mut census = ArrayCensus::new(interner.get_or_intern("Array"));
+        let mut statements = program.statements.clone();
+        census.visit_block(&mut statements, arena);
+        census.into_facts()
+    }
+
+    /// Whether every binding of `name` is declared as an array (`T[]` or
+    /// `Array<T>` with non-nil `T`, or a literal of non-nil values) that the
+    /// module only appends to. Arrays that arrive through parameters, aliases
+    /// or `any` may still have holes, so this does not make `#name` exact.
+    pub fn is_array(&self, name: StringId) -> bool {
+        self.arrays.contains(&name)
+    }
+
+    /// An array literal whose elements cannot be nil
+    pub(crate) fn is_dense_literal(expr: &Expression<'_>) -> bool {
+        let ExpressionKind::Array(elements) = &expr.kind else {
+            return false;
+        };
+        !elements.is_empty()
+            && elements.iter().all(|element| match element {
+                ArrayElement::Expression(e) => {
+                    matches!(
+                        &e.kind,
+                        ExpressionKind::Literal(literal) if !matches!(literal, Literal::Nil)
+                    ) || matches!(
+                        e.kind,
+                        ExpressionKind::Array(_)
+                            | ExpressionKind::Object(_)
+                            | ExpressionKind::Function(_)
+                            | ExpressionKind::Arrow(_)
+                            | ExpressionKind::Template(_)
+                            | ExpressionKind::New(..)
+                    )
+                }
+                ArrayElement::Spread(_) => false,
+            })
+    }
+}
+
+/// Finds the names bound only by dense-array `local`/`const` declarations and
+/// parameters. Any other binder of the same name disqualifies it everywhere,
+/// like [`BindingCensus`](super::passes::ConstantPropagationPass) does for
+/// constants.
+struct ArrayCensus<'arena> {
+    /// `Array`, for `Array<T>` annotations
+    array: StringId,
+    annotated: Vec<(StringId, Type<'arena>)>,
+    literals: FxHashSet<StringId>,
+    /// Parameters already judged by their annotation, by name and position
+    typed_params: FxHashSet<(StringId, usize)>,
+    /// Classes, interfaces and enums, whose values are never nil
+    nominal: FxHashSet<StringId>,
+    others: FxHashSet<StringId>,
+    /// Ambient declarations name globals the census cannot see
+    ambient: bool,
+}
+
+impl<'arena> ArrayCensus<'arena> {
+    fn new(array: StringId) -> Self {
+        Self {
+            array,
+            annotated: Vec::new(),
+            literals: FxHashSet::default(),
+            typed_params: FxHashSet::default(),
+            nominal: FxHashSet::default(),
+            others: FxHashSet::default(),
+            ambient: false,
+        }
+    }
+
+    fn into_facts(mut self) -> TypeFacts {
+        if self.ambient {
+            return TypeFacts::default();
+        }
+        let mut arrays = std::mem::take(&mut self.literals);
+        for (name, ty) in &self.annotated {
+            match self.element_type(ty) {
+                Some(element) if self.is_non_nil(element) => {
+                    arrays.insert(*name);
+                }
+                _ => {
+                    self.others.insert(*name);
+                }
+            }
+        }
+        arrays.retain(|name| !self.others.contains(name));
+        TypeFacts { arrays }
+    }
+
+    fn parameters(&mut self, params: &[Parameter<'arena>]) {
+        for param in params {
+            if let (Pattern::Identifier(ident), Some(ty)) = (&param.pattern, &param.type_annotation)
+            {
+                if !param.is_optional && !param.is_rest {
+                    self.annotated.push((ident.node, ty.clone()));
+                    self.typed_params.insert((ident.node, ident.span.start));
+                }
+            }
+        }
+    }
+
+    /// `T` of `T[]` or `Array<T>`
+    fn element_type<'t>(&self, ty: &'t Type<'arena>) -> Option<&'t Type<'arena>> {
+        match &ty.kind {
+            TypeKind::Array(element) => Some(element),
+            TypeKind::Reference(type_ref) if type_ref.name.node == self.array => {
+                match type_ref.type_arguments {
+                    Some([element]) => Some(element),
+                    _ => None,
+                }
+            }
+            TypeKind::Parenthesized(inner) => self.element_type(inner),
+            _ => None,
+        }
+    }
+
+    /// Type aliases and type parameters may stand for nil, so only nominal
+    /// references count
+    fn is_non_nil(&self, ty: &Type<'arena>) -> bool {
+        match &ty.kind {
+            TypeKind::Primitive(primitive) => !matches!(
+                primitive,
+                PrimitiveType::Nil
+                    | PrimitiveType::Void
+                    | PrimitiveType::Unknown
+                    | PrimitiveType::Never
+            ),
+            TypeKind::Literal(literal) => !matches!(literal, Literal::Nil),
+            TypeKind::Object(_)
+            | TypeKind::Array(_)
+            | TypeKind::Tuple(_)
+            | TypeKind::Function(_) => true,
+            TypeKind::Reference(type_ref) => self.nominal.contains(&type_ref.name.node),
+            TypeKind::Union(types) => types.iter().all(|ty| self.is_non_nil(ty)),
+            TypeKind::Parenthesized(inner) => self.is_non_nil(inner),
+            _ => false,
+        }
+    }
+}
+
+/// `#name + 1`
+fn is_append(name: StringId, key: &Expression<'_>) -> bool {
+    let ExpressionKind::Binary(BinaryOp::Add, left, right) = &key.kind else {
+        return false;
+    };
+    let length_of_name = matches!(
+        &left.kind,
+        ExpressionKind::Unary(UnaryOp::Length, operand)
+            if matches!(operand.kind, ExpressionKind::Identifier(id) if id == name)
+    );
+    let one = match right.kind {
+        ExpressionKind::Literal(Literal::Integer(n)) => n == 1,
+        ExpressionKind::Literal(Literal::Number(n)) => n == 1.0,
+        _ => false,
+    };
+    length_of_name && one
+}
+
+impl<'arena> MutVisitor<'arena> for ArrayCensus<'arena> {
+    fn visit_statement(&mut self, stmt: &mut Statement<'arena>, arena: &'arena Bump) -> bool {
+        match stmt {
+            Statement::Variable(decl) => {
+                if let Pattern::Identifier(ident) = &decl.pattern {
+                    match &decl.type_annotation {
+                        Some(ty) => self.annotated.push((ident.node, ty.clone())),
+                        None if TypeFacts::is_dense_literal(&decl.initializer) => {
+                            self.literals.insert(ident.node);
+                        }
+                        None => {
+                            self.others.insert(ident.node);
+                        }
+                    }
+                    return self.visit_expression(&mut decl.initializer, arena);
+                }
+            }
+            Statement::Function(func) => {
+                self.others.insert(func.name.node);
+                self.parameters(func.parameters);
+            }
+            Statement::Class(class) => {
+                self.others.insert(class.name.node);
+                self.nominal.insert(class.name.node);
+                for member in class.members.iter() {
+                    match member {
+                        ClassMember::Method(method) => self.parameters(method.parameters),
+                        ClassMember::Constructor(ctor) => self.parameters(ctor.parameters),
+                        ClassMember::Operator(op) => self.parameters(op.parameters),
+                        ClassMember::Getter(_)
+                        | ClassMember::Setter(_)
+                        | ClassMember::Property(_) => {}
+                    }
+                }
+            }
+            Statement::Enum(decl) => {
+                self.others.insert(decl.name.node);
+                self.nominal.insert(decl.name.node);
+                if let Some(ctor) = &decl.constructor {
+                    self.parameters(ctor.parameters);
+                }
+                for method in decl.methods.iter() {
+                    self.parameters(method.parameters);
+                }
+            }
+            Statement::Interface(decl) => {
+                self.nominal.insert(decl.name.node);
+            }
+            Statement::For(for_stmt) => match &**for_stmt {
+                ForStatement::Numeric(for_num) => {
+                    self.others.insert(for_num.variable.node);
+                }
+                ForStatement::Generic(for_gen) => {
+                    self.others
+                        .extend(for_gen.variables.iter().map(|var| var.node));
+                }
+            },
+            Statement::Try(try_stmt) => {
+                for clause in try_stmt.catch_clauses.iter() {
+                    match &clause.pattern {
+                        CatchPattern::Untyped { variable, .. }
+                        | CatchPattern::Typed { variable, .. }
+                        | CatchPattern::MultiTyped { variable, .. } => {
+                            self.others.insert(variable.node);
+                        }
+                    }
+                }
+            }
+            Statement::Import(import) => match &import.clause {
+                ImportClause::Default(ident) | ImportClause::Namespace(ident) => {
+                    self.others.insert(ident.node);
+                }
+                ImportClause::Named(specs) => self.others.extend(
+                    specs
+                        .iter()
+                        .map(|spec| spec.local.as_ref().unwrap_or(&spec.imported).node),
+                ),
+                ImportClause::Mixed { default, named } => {
+                    self.others.insert(default.node);
+                    self.others.extend(
+                        named
+                            .iter()
+                            .map(|spec| spec.local.as_ref().unwrap_or(&spec.imported).node),
+                    );
+                }
+                ImportClause::TypeOnly(_) => {}
+            },
+            Statement::DeclareFunction(_)
+            | Statement::DeclareConst(_)
+            | Statement::DeclareNamespace(_) => self.ambient = true,
+            _ => {}
+        }
+        walk_statement(self, stmt, arena)
+    }
+
+    fn visit_expression(&mut self, expr: &mut Expression<'arena>, arena: &'arena Bump) -> bool {
+        match &expr.kind {
+            // Only appends keep an array free of holes
+            ExpressionKind::Assignment(target, _, value) => {
+                if let ExpressionKind::Index(obj, key) = &target.kind {
+                    if let ExpressionKind::Identifier(name) = obj.kind {
+                        if !is_append(name, key)
+                            || matches!(value.kind, ExpressionKind::Literal(Literal::Nil))
+                        {
+                            self.others.insert(name);
+                        }
+                    }
+                }
+            }
+            ExpressionKind::Function(func) => self.parameters(func.parameters),
+            ExpressionKind::Arrow(arrow) => self.parameters(arrow.parameters),
+            ExpressionKind::Try(try_expr) => {
+                self.others.insert(try_expr.catch_variable.node);
+            }
+            _ => {}
+        }
+        walk_expression(self, expr, arena)
+    }
+
+    fn visit_pattern(&mut self, pattern: &mut Pattern<'arena>, arena: &'arena Bump) -> bool {
+        if let Pattern::Identifier(ident) = pattern {
+            if self.typed_params.contains(&(ident.node, ident.span.start)) {
+                return false;
+            }
+        }
+        let mut names = Vec::new();
+        pattern_binders(pattern, &mut names);
+        self.others.extend(names);
+        walk_pattern(self, pattern, arena)
+    }
+}
@@ -0,0 +1,563 @@
+//! Exhaustive mutable traversal of the arena AST.
+//!
+//! [`MutVisitor`] hooks default to the matching `walk_*` function, which
+//! descends into every child of the node. Implementors override a hook, do
+//! their work and call the walker to keep descending (or skip it to prune the
+//! subtree). The walkers match every `Statement`, `ExpressionKind` and
+//! `Pattern` variant without a wildcard arm, so a new AST variant does not
+//! compile until it is handled here.
+//!
+//! Children behind `&'arena` references and slices use the clone-and-rebuild
+//! pattern: they are cloned, visited, and written back into the arena only
+//! when a hook reports a change.
+
+use bumpalo::Bump;
+use typedlua_parser::ast::expression::{
+    Argument, ArrayElement, ArrowBody, Expression, ExpressionKind, MatchArmBody, ObjectProperty,
+    TemplatePart,
+};
+use typedlua_parser::ast::pattern::{ArrayPatternElement, Pattern, PatternWithDefault};
+use typedlua_parser::ast::statement::{
+    Block, ClassMember, ExportKind, ForStatement, Parameter, Statement,
+};
+
+/// Mutable visitor over statements, expressions and patterns.
+///
+/// Every hook returns whether it changed the tree.
+pub trait MutVisitor<'arena> {
+    /// A statement list: a program, function body or nested block
+    fn visit_block(&mut self, stmts: &mut Vec<Statement<'arena>>, arena: &'arena Bump) -> bool {
+        walk_block(self, stmts, arena)
+    }
+
+    fn visit_statement(&mut self, stmt: &mut Statement<'arena>, arena: &'arena Bump) -> bool {
+        walk_statement(self, stmt, arena)
+    }
+
+    fn visit_expression(&mut self, expr: &mut Expression<'arena>, arena: &'arena Bump) -> bool {
+        walk_expression(self, expr, arena)
+    }
+
+    /// A binding pattern; walked for its default values and computed keys
+    fn visit_pattern(&mut self, pattern: &mut Pattern<'arena>, arena: &'arena Bump) -> bool {
+        walk_pattern(self, pattern, arena)
+    }
+}
+
+pub fn walk_block<'arena, V>(
+    v: &mut V,
+    stmts: &mut Vec<Statement<'arena>>,
+    arena: &'arena Bump,
+) -> bool
+where
+    V: MutVisitor<'arena> + ?Sized,
+{
+    let mut changed = false;
+    for stmt in stmts.iter_mut() {
+        changed |= v.visit_statement(stmt, arena);
+    }
+    changed
+}
+
+pub fn walk_statement<'arena, V>(
+    v: &mut V,
+    stmt: &mut Statement<'arena>,
+    arena: &'arena Bump,
+) -> bool
+where
+    V: MutVisitor<'arena> + ?Sized,
+{
+    match stmt {
+        Statement::Variable(decl) => {
+            let mut changed = v.visit_pattern(&mut decl.pattern, arena);
+            changed |= v.visit_expression(&mut decl.initializer, arena);
+            changed
+        }
+        Statement::Function(func) => {
+            let mut changed = parameters(v, &mut func.parameters, arena);
+            changed |= block(v, &mut func.body, arena);
+            changed
+        }
+        Statement::Class(class) => slice(&mut class.members, arena, |member| match member {
+            ClassMember::Method(method) => {
+                let mut changed = parameters(v, &mut method.parameters, arena);
+                if let Some(body) = &mut method.body {
+                    changed |= block(v, body, arena);
+                }
+                changed
+            }
+            ClassMember::Constructor(ctor) => {
+                let mut changed = parameters(v, &mut ctor.parameters, arena);
+                changed |= block(v, &mut ctor.body, arena);
+                changed
+            }
+            ClassMember::Getter(getter) => block(v, &mut getter.body, arena),
+            ClassMember::Setter(setter) => {
+                let mut changed = parameter(v, &mut setter.parameter, arena);
+                changed |= block(v, &mut setter.body, arena);
+                changed
+            }
+            ClassMember::Operator(op) => {
+                let mut changed = parameters(v, &mut op.parameters, arena);
+                changed |= block(v, &mut op.body, arena);
+                changed
+            }
+            ClassMember::Property(_) => false,
+        }),
+        Statement::Enum(decl) => {
+            let mut changed = slice(&mut decl.members, arena, |member| {
+                slice(&mut member.arguments, arena, |arg| {
+                    v.visit_expression(arg, arena)
+                })
+            });
+            if let Some(ctor) = &mut decl.constructor {
+                changed |= parameters(v, &mut ctor.parameters, arena);
+                changed |= block(v, &mut ctor.body, arena);
+            }
+            changed |= slice(&mut decl.methods, arena, |method| {
+                let mut changed = parameters(v, &mut method.parameters, arena);
+                changed |= block(v, &mut method.body, arena);
+                changed
+            });
+            changed
+        }
+        Statement::Export(export) => match &mut export.kind {
+            ExportKind::Declaration(decl) => {
+                boxed(decl, arena, |decl| v.visit_statement(decl, arena))
+            }
+            ExportKind::Default(expr) => v.visit_expression(expr, arena),
+            ExportKind::Named { .. } => false,
+        },
+        Statement::If(if_stmt) => {
+            let mut changed = v.visit_expression(&mut if_stmt.condition, arena);
+            changed |= block(v, &mut if_stmt.then_block, arena);
+            changed |= slice(&mut if_stmt.else_ifs, arena, |else_if| {
+                let mut changed = v.visit_expression(&mut else_if.condition, arena);
+                changed |= block(v, &mut else_if.block, arena);
+                changed
+            });
+            if let Some(else_block) = &mut if_stmt.else_block {
+                changed |= block(v, else_block, arena);
+            }
+            changed
+        }
+        Statement::While(while_stmt) => {
+            let mut changed = v.visit_expression(&mut while_stmt.condition, arena);
+            changed |= block(v, &mut while_stmt.body, arena);
+            changed
+        }
+        Statement::For(for_stmt) => boxed(for_stmt, arena, |for_stmt| match for_stmt {
+            ForStatement::Numeric(for_num) => boxed(for_num, arena, |for_num| {
+                let mut changed = v.visit_expression(&mut for_num.start, arena);
+                changed |= v.visit_expression(&mut for_num.end, arena);
+                if let Some(step) = &mut for_num.step {
+                    changed |= v.visit_expression(step, arena);
+                }
+                changed |= block(v, &mut for_num.body, arena);
+                changed
+            }),
+            ForStatement::Generic(for_gen) => {
+                let mut changed = expressions(v, &mut for_gen.iterators, arena);
+                changed |= block(v, &mut for_gen.body, arena);
+                changed
+            }
+        }),
+        Statement::Repeat(repeat_stmt) => {
+            let mut changed = block(v, &mut repeat_stmt.body, arena);
+            changed |= v.visit_expression(&mut repeat_stmt.until, arena);
+            changed
+        }
+        Statement::Return(ret) => expressions(v, &mut ret.values, arena),
+        Statement::Expression(expr) => v.visit_expression(expr, arena),
+        Statement::Block(inner) => block(v, inner, arena),
+        Statement::Try(try_stmt) => {
+            let mut changed = block(v, &mut try_stmt.try_block, arena);
+            changed |= slice(&mut try_stmt.catch_clauses, arena, |clause| {
+                block(v, &mut clause.body, arena)
+            });
+            if let Some(finally_block) = &mut try_stmt.finally_block {
+                changed |= block(v, finally_block, arena);
+            }
+            changed
+        }
+        Statement::Throw(throw_stmt) => v.visit_expression(&mut throw_stmt.expression, arena),
+        // Declarations and jumps without runtime expressions
+        Statement::Interface(_)
+        | Statement::TypeAlias(_)
+        | Statement::Import(_)
+        | Statement::Namespace(_)
+        | Statement::DeclareFunction(_)
+        | Statement::DeclareNamespace(_)
+        | Statement::DeclareType(_)
+        | Statement::DeclareInterface(_)
+        | Statement::DeclareConst(_)
+        | Statement::Break(_)
+        | Statement::Continue(_)
+        | Statement::Rethrow(_)
+        | Statement::Label(_)
+        | Statement::Goto(_) => false,
+    }
+}
+
+pub fn walk_expression<'arena, V>(
+    v: &mut V,
+    expr: &mut Expression<'arena>,
+    arena: &'arena Bump,
+) -> bool
+where
+    V: MutVisitor<'arena> + ?Sized,
+{
+    match &mut expr.kind {
+        ExpressionKind::Identifier(_)
+        | ExpressionKind::Literal(_)
+        | ExpressionKind::SelfKeyword
+        | ExpressionKind::SuperKeyword => false,
+        ExpressionKind::Binary(_, left, right)
+        | ExpressionKind::Assignment(left, _, right)
+        | ExpressionKind::Index(left, right)
+        | ExpressionKind::OptionalIndex(left, right)
+        | ExpressionKind::Pipe(left, right)
+        | ExpressionKind::ErrorChain(left, right) => {
+            let mut changed = child(v, left, arena);
+            changed |= child(v, right, arena);
+            changed
+        }
+        ExpressionKind::Unary(_, inner)
+        | ExpressionKind::Member(inner, _)
+        | ExpressionKind::OptionalMember(inner, _)
+        | ExpressionKind::Parenthesized(inner)
+        | ExpressionKind::TypeAssertion(inner, _) => child(v, inner, arena),
+        ExpressionKind::Call(callee, args, _)
+        | ExpressionKind::OptionalCall(callee, args, _)
+        | ExpressionKind::New(callee, args, _)
+        | ExpressionKind::MethodCall(callee, _, args, _)
+        | ExpressionKind::OptionalMethodCall(callee, _, args, _) => {
+            let mut changed = child(v, callee, arena);
+            changed |= arguments(v, args, arena);
+            changed
+        }
+        ExpressionKind::Conditional(cond, then_expr, else_expr) => {
+            let mut changed = child(v, cond, arena);
+            changed |= child(v, then_expr, arena);
+            changed |= child(v, else_expr, arena);
+            changed
+        }
+        ExpressionKind::Array(elements) => slice(elements, arena, |element| match element {
+            ArrayElement::Expression(e) | ArrayElement::Spread(e) => v.visit_expression(e, arena),
+        }),
+        ExpressionKind::Object(props) => slice(props, arena, |prop| match prop {
+            ObjectProperty::Property { value, .. } | ObjectProperty::Spread { value, .. } => {
+                child(v, value, arena)
+            }
+            ObjectProperty::Computed { key, value, .. } => {
+                let mut changed = child(v, key, arena);
+                changed |= child(v, value, arena);
+                changed
+            }
+        }),
+        ExpressionKind::Function(func) => {
+            let mut changed = parameters(v, &mut func.parameters, arena);
+            changed |= block(v, &mut func.body, arena);
+            changed
+        }
+        ExpressionKind::Arrow(arrow) => {
+            let mut changed = parameters(v, &mut arrow.parameters, arena);
+            changed |= match &mut arrow.body {
+                ArrowBody::Expression(body) => child(v, body, arena),
+                ArrowBody::Block(body) => block(v, body, arena),
+            };
+            changed
+        }
+        ExpressionKind::Match(match_expr) => {
+            let mut changed = child(v, &mut match_expr.value, arena);
+            changed |= slice(&mut match_expr.arms, arena, |arm| {
+                let mut changed = v.visit_pattern(&mut arm.pattern, arena);
+                if let Some(guard) = &mut arm.guard {
+                    changed |= v.visit_expression(guard, arena);
+                }
+                changed |= match &mut arm.body {
+                    MatchArmBody::Expression(body) => child(v, body, arena),
+                    MatchArmBody::Block(body) => block(v, body, arena),
+                };
+                changed
+            });
+            changed
+        }
+        ExpressionKind::Template(template) => {
+            slice(&mut template.parts, arena, |part| match part {
+                TemplatePart::String(_) => false,
+                TemplatePart::Expression(e) => v.visit_expression(e, arena),
+            })
+        }
+        ExpressionKind::Try(try_expr) => {
+            let mut changed = child(v, &mut try_expr.expression, arena);
+            changed |= child(v, &mut try_expr.catch_expression, arena);
+            changed
+        }
+    }
+}
+
+pub fn walk_pattern<'arena, V>(
+    v: &mut V,
+    pattern: &mut Pattern<'arena>,
+    arena: &'arena Bump,
+) -> bool
+where
+    V: MutVisitor<'arena> + ?Sized,
+{
+    match pattern {
+        Pattern::Identifier(_) | Pattern::Literal(..) | Pattern::Wildcard(_) => false,
+        Pattern::Array(array) => slice(&mut array.elements, arena, |element| match element {
+            ArrayPatternElement::Pattern(PatternWithDefault { pattern, default }) => {
+                let mut changed = v.visit_pattern(pattern, arena);
+                if let Some(default) = default {
+                    changed |= v.visit_expression(default, arena);
+                }
+                changed
+            }
+            ArrayPatternElement::Rest(_) | ArrayPatternElement::Hole => false,
+        }),
+        Pattern::Object(object) => slice(&mut object.properties, arena, |prop| {
+            let mut changed = false;
+            if let Some(key) = &mut prop.computed_key {
+                changed |= v.visit_expression(key, arena);
+            }
+            if let Some(value) = &mut prop.value {
+                changed |= v.visit_pattern(value, arena);
+            }
+            if let Some(default) = &mut prop.default {
+                changed |= v.visit_expression(default, arena);
+            }
+            changed
+        }),
+        Pattern::Or(or_pattern) => slice(&mut or_pattern.alternatives, arena, |alternative| {
+            v.visit_pattern(alternative, arena)
+        }),
+    }
+}
+
+// -----------------------------------------------------------------------------
+// Clone-and-rebuild helpers
+// -----------------------------------------------------------------------------
+
+/// Visits a block's statements, reallocating them only when they changed
+fn block<'arena, V>(v: &mut V, block: &mut Block<'arena>, arena: &'arena Bump) -> bool
+where
+    V: MutVisitor<'arena> + ?Sized,
+{
+    let mut stmts: Vec<Statement<'arena>> = block.statements.to_vec();
+    let changed = v.visit_block(&mut stmts, arena);
+    if changed {
+        block.statements = arena.alloc_slice_clone(&stmts);
+    }
+    changed
+}
+
+fn child<'arena, V>(v: &mut V, expr: &mut &'arena Expression<'arena>, arena: &'arena Bump) -> bool
+where
+    V: MutVisitor<'arena> + ?Sized,
+{
+    boxed(expr, arena, |expr| v.visit_expression(expr, arena))
+}
+
+fn expressions<'arena, V>(
+    v: &mut V,
+    exprs: &mut &'arena [Expression<'arena>],
+    arena: &'arena Bump,
+) -> bool
+where
+    V: MutVisitor<'arena> + ?Sized,
+{
+    slice(exprs, arena, |expr| v.visit_expression(expr, arena))
+}
+
+fn arguments<'arena, V>(
+    v: &mut V,
+    args: &mut &'arena [Argument<'arena>],
+    arena: &'arena Bump,
+) -> bool
+where
+    V: MutVisitor<'arena> + ?Sized,
+{
+    slice(args, arena, |arg| v.visit_expression(&mut arg.value, arena))
+}
+
+fn parameters<'arena, V>(
+    v: &mut V,
+    params: &mut &'arena [Parameter<'arena>],
+    arena: &'arena Bump,
+) -> bool
+where
+    V: MutVisitor<'arena> + ?Sized,
+{
+    slice(params, arena, |param| parameter(v, param, arena))
+}
+
+fn parameter<'arena, V>(v: &mut V, param: &mut Parameter<'arena>, arena: &'arena Bump) -> bool
+where
+    V: MutVisitor<'arena> + ?Sized,
+{
+    let mut changed = v.visit_pattern(&mut param.pattern, arena);
+    if let Some(default) = &mut param.default {
+        changed |= v.visit_expression(default, arena);
+    }
+    changed
+}
+
+/// Clones an arena node, walks the copy, and writes it back when it changed
+fn boxed<'arena, T: Clone>(
+    node: &mut &'arena T,
+    arena: &'arena Bump,
+    walk: impl FnOnce(&mut T) -> bool,
+) -> bool {
+    let mut owned = (**node).clone();
+    let changed = walk(&mut owned);
+    if changed {
+        *node = arena.alloc(owned);
+    }
+    changed
+}
+
+/// Clones an arena slice, walks each element, and writes it back when any changed
+fn slice<'arena, T: Clone>(
+    items: &mut &'arena [T],
+    arena: &'arena Bump,
+    mut walk: impl FnMut(&mut T) -> bool,
+) -> bool {
+    let mut owned: Vec<T> = items.to_vec();
+    let mut changed = false;
+    for item in &mut owned {
+        changed |= walk(item);
+    }
+    if changed {
+        *items = arena.alloc_slice_clone(&owned);
+    }
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostics::CollectingDiagnosticHandler;
+    use crate::MutableProgram;
+    use std::sync::Arc;
+    use typedlua_parser::ast::expression::Literal;
+    use typedlua_parser::lexer::Lexer;
+    use typedlua_parser::parser::Parser;
+    use typedlua_parser::string_interner::StringInterner;
+
+    // Every walker above matches its node type without a wildcard arm, so
+    // the crate stops compiling when the parser adds a statement, expression
+    // or pattern variant. These tests check the walk reaches what it lists.
+
+    /// Records every number literal and optionally bumps it by 100
+    #[derive(Default)]
+    struct Numbers {
+        seen: Vec<f64>,
+        bump: bool,
+    }
+
+    impl<'arena> MutVisitor<'arena> for Numbers {
+        fn visit_expression(&mut self, expr: &mut Expression<'arena>, arena: &'arena Bump) -> bool {
+            let changed = walk_expression(self, expr, arena);
+            let ExpressionKind::Literal(Literal::Number(n)) = expr.kind else {
+                return changed;
+            };
+            self.seen.push(n);
+            if self.bump {
+                expr.kind = ExpressionKind::Literal(Literal::Number(n + 100.0));
+            }
+            self.bump || changed
+        }
+    }
+
+    fn parse<'arena>(source: &str, arena: &'arena Bump) -> MutableProgram<'arena> {
+        let (interner, common) = StringInterner::new_with_common_identifiers();
+        let handler = Arc::new(CollectingDiagnosticHandler::new());
+        let mut lexer = Lexer::new(source, handler.clone(), &interner);
+        let tokens = lexer.tokenize().expect("Lexing failed");
+        let mut parser = Parser::new(tokens, handler, &interner, &common, arena);
+        MutableProgram::from_program(&parser.parse().expect("Parsing failed"))
+    }
+
+    fn numbers(program: &mut MutableProgram<'_>, arena: &Bump) -> Vec<f64> {
+        let mut visitor = Numbers::default();
+        visitor.visit_block(&mut program.statements, arena);
+        visitor.seen.sort_by(f64::total_cmp);
+        visitor.seen
+    }
+
+    const NESTED: &str = r#"
+        export function scale(x: number, factor: number = 1): number
+            return x * 2
+        end
+
+        class Counter {
+            public count: number
+
+            constructor(start: number = 3) {
+                self.count = start + 4
+            }
+
+            public get value(): number {
+                return 5
+            }
+
+            public set value(v: number) {
+                self.count = v + 6
+            }
+
+            public bump(): number {
+                const step = (n: number) => n + 7
+                return step(8)
+            }
+        }
+
+        const picked = match scale(9) {
+            n if n > 10 => 11,
+            _ => 12
+        }
+        const safe = try scale(13) catch 14
+    "#;
+
+    #[test]
+    fn test_walk_reaches_nested_bodies() {
+        let arena = Bump::new();
+        let mut program = parse(NESTED, &arena);
+
+        let expected: Vec<f64> = (1..=14).map(f64::from).collect();
+        assert_eq!(numbers(&mut program, &arena), expected);
+    }
+
+    #[test]
+    fn test_changes_are_written_back() {
+        let arena = Bump::new();
+        let mut program = parse(NESTED, &arena);
+
+        let mut visitor = Numbers {
+            bump: true,
+            ..Numbers::default()
+        };
+        assert!(visitor.visit_block(&mut program.statements, &arena));
+
+        let expected: Vec<f64> = (101..=114).map(f64::from).collect();
+        assert_eq!(numbers(&mut program, &arena), expected);
+    }
+
+    #[test]
+    fn test_unchanged_walk_keeps_arena_slices() {
+        let arena = Bump::new();
+        let mut program = parse(NESTED, &arena);
+        let Statement::Class(class) = &program.statements[1] else {
+            panic!("expected a class");
+        };
+        let members = class.members.as_ptr();
+
+        assert!(!Numbers::default().visit_block(&mut program.statements, &arena));
+
+        let Statement::Class(class) = &program.statements[1] else {
+            panic!("expected a class");
+        };
+        assert!(std::ptr::eq(members, class.members.as_ptr()));
+    }
+}
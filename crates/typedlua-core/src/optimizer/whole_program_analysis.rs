@@ -6,8 +6,10 @@
 
 use crate::config::OptimizationLevel;
 use crate::optimizer::devirtualization::ClassHierarchy;
+use crate::optimizer::export_summary::ExportSummaries;
 use std::sync::Arc;
 use typedlua_parser::ast::Program;
+use typedlua_parser::string_interner::StringInterner;
 
 /// Thread-safe whole-program analysis results
 ///
@@ -18,6 +20,8 @@ use typedlua_parser::ast::Program;
 pub struct WholeProgramAnalysis {
     /// Class hierarchy for devirtualization
     pub class_hierarchy: Arc<ClassHierarchy>,
+    /// Small exported constants and functions for cross-module inlining (O3+)
+    pub exports: Arc<ExportSummaries>,
 }
 
 impl WholeProgramAnalysis {
@@ -30,8 +34,9 @@ impl WholeProgramAnalysis {
         programs: &[&Program<'arena>],
         optimization_level: OptimizationLevel,
     ) -> Self {
-        // Only build expensive analysis if O3+ optimization is enabled
-        let class_hierarchy = if optimization_level >= OptimizationLevel::O3 {
+        // Only build expensive analysis when a consumer runs: copy-down
+        // flattening (O2+) and devirtualization (O3+)
+        let class_hierarchy = if optimization_level >= OptimizationLevel::O2 {
             ClassHierarchy::build_multi_module(programs)
         } else {
             ClassHierarchy::default()
@@ -39,6 +44,21 @@ impl WholeProgramAnalysis {
 
         Self {
             class_hierarchy: Arc::new(class_hierarchy),
+            exports: Arc::new(ExportSummaries::default()),
         }
     }
+
+    /// Summarize the exports of one module under `module_id`, the id its
+    /// importers resolve `import ... from` sources to
+    ///
+    /// Modules carry their own string interner, so summaries are added one
+    /// module at a time with the interner the module was parsed with.
+    pub fn summarize_exports(
+        &mut self,
+        module_id: impl Into<String>,
+        program: &Program<'_>,
+        interner: &StringInterner,
+    ) {
+        Arc::make_mut(&mut self.exports).add_module(module_id, program, interner);
+    }
 }
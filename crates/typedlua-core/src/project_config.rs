@@ -0,0 +1,150 @@
+//! Emit and optimizer options read from `tlconfig.yaml` alongside [`crate::config::CompilerConfig`].
+//!
+//! `CompilerConfig` is shared with the type checker and only knows about the
+//! options it needs. Options that only affect what the compiler writes out,
+//! or how it optimizes, are parsed from the same file here; unknown keys are
+//! ignored so both readers can consume one config.
+
+use crate::codegen::{AccessChecks, Traces, ValidationErrors, ValidationMode};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ProjectConfig {
+    pub compiler_options: EmitOptions,
+    pub validation: ValidationOptions,
+    pub optimizer: OptimizerOptions,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct EmitOptions {
+    /// Write a `.d.tl` declaration file next to each output
+    pub declaration: bool,
+    /// Check `private`/`protected` member access at runtime in debug builds
+    pub access_checks: AccessChecks,
+    /// Builds that keep `@trace` decorators
+    pub traces: Traces,
+}
+
+/// The `validation:` section; see `docs/designs/Runtime-Validation.md`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ValidationOptions {
+    pub mode: ValidationMode,
+    pub errors: ValidationErrors,
+}
+
+/// The `optimizer:` section
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct OptimizerOptions {
+    /// Pass name (as in `Optimizer::pass_names`) to whether it runs,
+    /// overriding the optimization level's choice
+    pub passes: IndexMap<String, bool>,
+    /// Largest function body, in statements, that `function-inlining` inlines
+    pub inline_threshold: usize,
+    /// Largest function body, in statements, that `aggressive-inlining` inlines
+    pub aggressive_inline_threshold: usize,
+    /// Largest function body, in statements, that
+    /// `profile-guided-optimization` inlines at call sites a profile shows hot
+    pub hot_inline_threshold: usize,
+    /// Fixed-point iterations before the optimizer stops
+    pub max_iterations: usize,
+}
+
+impl Default for OptimizerOptions {
+    fn default() -> Self {
+        Self {
+            passes: IndexMap::new(),
+            inline_threshold: 5,
+            aggressive_inline_threshold: 15,
+            hot_inline_threshold: 40,
+            max_iterations: 10,
+        }
+    }
+}
+
+impl OptimizerOptions {
+    /// Names in `passes` that are not optimizer passes
+    pub fn unknown_passes(&self) -> Vec<&str> {
+        self.passes
+            .keys()
+            .map(String::as_str)
+            .filter(|name| !crate::optimizer::PASS_NAMES.contains(name))
+            .collect()
+    }
+}
+
+impl ProjectConfig {
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        Self::from_yaml(&content)
+    }
+
+    pub fn from_yaml(content: &str) -> Result<Self, String> {
+        serde_yaml::from_str(content).map_err(|e| format!("Invalid config: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_declaration_option() {
+        let config = ProjectConfig::from_yaml(
+            "compilerOptions:\n  target: \"5.4\"\n  declaration: true\ninclude:\n  - src/**/*.tl\n",
+        )
+        .unwrap();
+        assert!(config.compiler_options.declaration);
+    }
+
+    #[test]
+    fn test_access_checks_option() {
+        let config = ProjectConfig::from_yaml("compilerOptions:\n  accessChecks: debug\n").unwrap();
+        assert_eq!(config.compiler_options.access_checks, AccessChecks::Debug);
+    }
+
+    #[test]
+    fn test_traces_option() {
+        let config = ProjectConfig::from_yaml("compilerOptions:\n  traces: always\n").unwrap();
+        assert_eq!(config.compiler_options.traces, Traces::Always);
+    }
+
+    #[test]
+    fn test_defaults_when_absent() {
+        let config = ProjectConfig::from_yaml("include:\n  - src\n").unwrap();
+        assert!(!config.compiler_options.declaration);
+        assert_eq!(config.validation.mode, ValidationMode::Off);
+        assert_eq!(config.compiler_options.access_checks, AccessChecks::Off);
+        assert_eq!(config.compiler_options.traces, Traces::Debug);
+    }
+
+    #[test]
+    fn test_optimizer_section() {
+        let config = ProjectConfig::from_yaml(
+            "optimizer:\n  passes:\n    function-inlining: false\n    devirtualization: true\n    fast-math: true\n  inlineThreshold: 8\n  maxIterations: 3\n",
+        )
+        .unwrap();
+        let optimizer = &config.optimizer;
+        assert_eq!(optimizer.passes.get("function-inlining"), Some(&false));
+        assert_eq!(optimizer.passes.get("devirtualization"), Some(&true));
+        assert_eq!(optimizer.inline_threshold, 8);
+        assert_eq!(optimizer.aggressive_inline_threshold, 15);
+        assert_eq!(optimizer.hot_inline_threshold, 40);
+        assert_eq!(optimizer.max_iterations, 3);
+        assert_eq!(optimizer.unknown_passes(), vec!["fast-math"]);
+    }
+
+    #[test]
+    fn test_validation_section() {
+        let config =
+            ProjectConfig::from_yaml("validation:\n  mode: auto\n  errors: collect\n").unwrap();
+        assert_eq!(config.validation.mode, ValidationMode::Auto);
+        assert_eq!(config.validation.errors, ValidationErrors::Collect);
+    }
+}
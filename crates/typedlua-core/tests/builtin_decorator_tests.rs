@@ -1,3 +1,4 @@
+use typedlua_core::config::OptimizationLevel;
 use typedlua_core::di::DiContainer;
 
 fn compile_and_check(source: &str) -> Result<String, String> {
@@ -5,6 +6,11 @@ fn compile_and_check(source: &str) -> Result<String, String> {
     container.compile(source)
 }
 
+fn compile_with_optimization(source: &str, level: OptimizationLevel) -> Result<String, String> {
+    let mut container = DiContainer::test_default();
+    container.compile_with_optimization(source, level)
+}
+
 #[test]
 fn test_readonly_class_decorator() {
     let source = r#"
@@ -424,3 +430,188 @@ fn test_readonly_property_inheritance() {
     let result = compile_and_check(source);
     assert!(result.is_ok(), "readonly should work with inheritance");
 }
+
+#[test]
+fn test_standard_decorators_are_embedded() {
+    let source = r#"
+        class Fib {
+            @memoize
+            public calc(n: number): number
+                return n
+            end
+
+            @once
+            public setup(): void
+            end
+        }
+    "#;
+
+    let output = compile_and_check(source).unwrap();
+    for name in ["memoize", "bind", "frozen", "once", "trace"] {
+        assert!(
+            output.contains(&format!("function TypedLua.{}(", name)),
+            "runtime should define {}: {}",
+            name,
+            output
+        );
+    }
+    assert!(output.contains("Fib.calc = __decorate(Fib, Fib.calc, { memoize }"));
+    assert!(output.contains("Fib.setup = __decorate(Fib, Fib.setup, { once }"));
+}
+
+#[test]
+fn test_memoize_getter_caches_per_instance_with_weak_argument_keys() {
+    let source = r#"
+        class Area {
+            public side: number
+
+            @memoize
+            public get size(): number {
+                return self.side * self.side
+            }
+        }
+    "#;
+
+    let output = compile_and_check(source).unwrap();
+    assert!(
+        output.contains(
+            "Area.get_size = __decorate(Area, Area.get_size, { memoize }, { kind = \"getter\""
+        ),
+        "getter decorated: {}",
+        output
+    );
+    // Getters use the weak per-instance caches, not the shared table
+    assert!(
+        output.contains("(context.kind == \"method\" or context.kind == \"getter\")"),
+        "per-instance getter caches: {}",
+        output
+    );
+    // Every cache level is weak-keyed, one level per argument, so table
+    // arguments are not kept alive and values are never keyed by tostring
+    assert!(
+        output.contains("local shared = setmetatable({}, __weakKeys)")
+            && output.contains("cache = setmetatable({}, __weakKeys)")
+            && output.contains("child = setmetatable({}, __weakKeys)"),
+        "weak caches: {}",
+        output
+    );
+    assert!(
+        output.contains("local node = __cacheNode(cache, keyFn, ...)"),
+        "per-argument nodes: {}",
+        output
+    );
+    assert!(
+        !output.contains("tostring(v)"),
+        "no string keys: {}",
+        output
+    );
+}
+
+#[test]
+fn test_bind_is_lowered_into_constructor() {
+    let source = r#"
+        class Button {
+            @bind
+            public onClick(): void
+            end
+        }
+
+        class IconButton extends Button {
+        }
+    "#;
+
+    let output = compile_and_check(source).unwrap();
+    assert!(
+        output.contains("self.onClick = function(...) return Button.onClick(self, ...) end"),
+        "bind should become a binding in new: {}",
+        output
+    );
+    assert!(
+        output.contains("self.onClick = function(...) return IconButton.onClick(self, ...) end"),
+        "subclasses keep inherited bindings: {}",
+        output
+    );
+    assert!(!output.contains("__decorate(Button, Button.onClick"));
+    assert!(!output.contains("__initInstance(Button, self)"));
+}
+
+#[test]
+fn test_trace_only_in_debug_builds() {
+    let source = r#"
+        class Service {
+            @trace
+            public run(): void
+            end
+        }
+    "#;
+
+    let debug = compile_with_optimization(source, OptimizationLevel::O0).unwrap();
+    assert!(debug.contains("Service.run = __decorate(Service, Service.run, { trace }"));
+
+    let release = compile_with_optimization(source, OptimizationLevel::O2).unwrap();
+    assert!(
+        !release.contains("__decorate(Service, Service.run"),
+        "trace should be stripped outside O0: {}",
+        release
+    );
+}
+
+#[test]
+fn test_optimizer_hints_are_not_emitted() {
+    let source = r#"
+        class Vec2 {
+            @pure
+            public length(): number
+                return 0
+            end
+
+            @TypedLua.noinline
+            @cold
+            public report(): void
+            end
+        }
+    "#;
+
+    for level in [OptimizationLevel::O0, OptimizationLevel::O2] {
+        let output = compile_with_optimization(source, level).unwrap();
+        assert!(!output.contains("__decorate"), "{}", output);
+        assert!(!output.contains("function TypedLua.memoize("), "{}", output);
+    }
+}
+
+#[test]
+fn test_shadowed_built_in_is_applied_at_runtime() {
+    let source = r#"
+        function trace(target, context)
+            return target
+        end
+
+        class Service {
+            @trace
+            public run(): void
+            end
+        }
+    "#;
+
+    let release = compile_with_optimization(source, OptimizationLevel::O2).unwrap();
+    assert!(release.contains("Service.run = __decorate(Service, Service.run, { trace }"));
+}
+
+#[test]
+fn test_shadowed_optimizer_hint_is_applied_at_runtime() {
+    let source = r#"
+        function pure(target, context)
+            return target
+        end
+
+        class Units {
+            @pure
+            public static toMeters(feet: number): number
+                return feet * 0.3048
+            end
+        }
+    "#;
+
+    let release = compile_with_optimization(source, OptimizationLevel::O2).unwrap();
+    assert!(release.contains("Units.toMeters = __decorate(Units, Units.toMeters, { pure }"));
+}
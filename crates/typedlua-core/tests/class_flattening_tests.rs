@@ -0,0 +1,144 @@
+use typedlua_core::config::OptimizationLevel;
+use typedlua_core::di::DiContainer;
+
+fn compile_with_optimization_level(
+    source: &str,
+    level: OptimizationLevel,
+) -> Result<String, String> {
+    let mut container = DiContainer::test_default();
+    container.compile_with_optimization(source, level)
+}
+
+const HIERARCHY: &str = r#"
+    class Animal {
+        speak(): string {
+            return "..."
+        }
+
+        describe(): string {
+            return "animal"
+        }
+    }
+
+    class Dog extends Animal {
+        speak(): string {
+            return "woof"
+        }
+    }
+
+    class Puppy extends Dog {
+        describe(): string {
+            return super:describe()
+        }
+    }
+
+    const p = new Puppy()
+    const s = p:speak()
+"#;
+
+#[test]
+fn test_o2_copies_inherited_methods_down() {
+    let output = compile_with_optimization_level(HIERARCHY, OptimizationLevel::O2).unwrap();
+    assert!(
+        !output.contains("setmetatable(Dog, { __index = Animal })"),
+        "closed hierarchy should not chain metatables: {}",
+        output
+    );
+    assert!(!output.contains("setmetatable(Puppy, { __index = Dog })"));
+    assert!(output.contains("local __base = Animal"), "{}", output);
+    assert!(output.contains("local __base = Dog"), "{}", output);
+    assert!(output.contains("if type(v) == \"function\" and rawget(Puppy, k) == nil then"));
+    assert!(output.contains("Puppy.__parent = Dog"));
+}
+
+#[test]
+fn test_super_method_call_is_direct() {
+    let output = compile_with_optimization_level(HIERARCHY, OptimizationLevel::O2).unwrap();
+    assert!(
+        output.contains("return Dog.describe(self)"),
+        "super:describe() should call the parent directly: {}",
+        output
+    );
+}
+
+#[test]
+fn test_o0_keeps_metatable_chain() {
+    let output = compile_with_optimization_level(HIERARCHY, OptimizationLevel::O0).unwrap();
+    assert!(output.contains("setmetatable(Dog, { __index = Animal })"));
+    assert!(output.contains("setmetatable(Puppy, { __index = Dog })"));
+    assert!(!output.contains("local __base"));
+}
+
+#[test]
+fn test_monkey_patched_parent_keeps_chain() {
+    let source = r#"
+        class Shape {
+            area(): number {
+                return 0
+            }
+        }
+
+        class Square extends Shape {
+        }
+
+        Shape.area = (): number => 1
+    "#;
+
+    let output = compile_with_optimization_level(source, OptimizationLevel::O2).unwrap();
+    assert!(
+        output.contains("setmetatable(Square, { __index = Shape })"),
+        "patched parent must stay on the chain: {}",
+        output
+    );
+    assert!(!output.contains("local __base"));
+}
+
+#[test]
+fn test_parent_patched_in_nested_function_keeps_chain() {
+    let source = r#"
+        class Shape {
+            area(): number {
+                return 0
+            }
+        }
+
+        class Square extends Shape {
+            reset(): void {
+                try {
+                    Shape.area = (): number => 2
+                } catch (e) {
+                }
+            }
+        }
+
+        function install(): void {
+            const patch = (): void => {
+                Shape.area = (): number => 1
+            }
+            patch()
+        }
+    "#;
+
+    let output = compile_with_optimization_level(source, OptimizationLevel::O2).unwrap();
+    assert!(
+        output.contains("setmetatable(Square, { __index = Shape })"),
+        "parent patched inside a function body must stay on the chain: {}",
+        output
+    );
+    assert!(!output.contains("local __base"));
+}
+
+#[test]
+fn test_static_state_keeps_chain() {
+    let source = r#"
+        class Registry {
+            static count: number
+        }
+
+        class Users extends Registry {
+        }
+    "#;
+
+    let output = compile_with_optimization_level(source, OptimizationLevel::O2).unwrap();
+    assert!(output.contains("setmetatable(Users, { __index = Registry })"));
+}
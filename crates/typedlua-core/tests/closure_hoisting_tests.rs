@@ -0,0 +1,141 @@
+use typedlua_core::config::OptimizationLevel;
+use typedlua_core::di::DiContainer;
+
+fn compile(source: &str, level: OptimizationLevel) -> Result<String, String> {
+    let mut container = DiContainer::test_default();
+    container.compile_with_stdlib_and_optimization(source, level)
+}
+
+const SORT_ROWS: &str = r#"
+    function sortRows(rows: number[][]): void
+        for i = 1, #rows do
+            table.sort(rows[i], (a: number, b: number) => a > b)
+        end
+    end
+"#;
+
+fn position(output: &str, needle: &str) -> usize {
+    output
+        .find(needle)
+        .unwrap_or_else(|| panic!("missing {:?} in\n{}", needle, output))
+}
+
+// ============================================================================
+// Hoisted closures
+// ============================================================================
+
+#[test]
+fn test_non_capturing_callback_leaves_the_loop() {
+    let output = compile(SORT_ROWS, OptimizationLevel::O2).unwrap();
+    assert!(
+        output.contains("local __closure_0 = function(a, b)"),
+        "{}",
+        output
+    );
+    assert!(
+        output.contains("table.sort(rows[i], __closure_0)"),
+        "{}",
+        output
+    );
+    assert!(
+        position(&output, "local __closure_0") < position(&output, "for i = 1"),
+        "{}",
+        output
+    );
+}
+
+#[test]
+fn test_callback_capturing_invariant_local_leaves_the_loop() {
+    let source = r#"
+        function sortRows(rows: number[][], scale: number): void
+            for i = 1, #rows do
+                table.sort(rows[i], (a: number, b: number) => a * scale < b * scale)
+            end
+        end
+    "#;
+
+    let output = compile(source, OptimizationLevel::O2).unwrap();
+    assert!(
+        output.contains("table.sort(rows[i], __closure_0)"),
+        "{}",
+        output
+    );
+    assert!(
+        position(&output, "local __closure_0") < position(&output, "for i = 1"),
+        "{}",
+        output
+    );
+}
+
+#[test]
+fn test_callback_capturing_outer_loop_variable_leaves_only_inner_loop() {
+    let source = r#"
+        function sortGrid(grid: number[][][]): void
+            for i = 1, #grid do
+                for j = 1, #grid[i] do
+                    table.sort(grid[i][j], (a: number, b: number) => a + i < b + i)
+                end
+            end
+        end
+    "#;
+
+    let output = compile(source, OptimizationLevel::O2).unwrap();
+    let hoisted = position(&output, "local __closure_0");
+    assert!(position(&output, "for i = 1") < hoisted, "{}", output);
+    assert!(hoisted < position(&output, "for j = 1"), "{}", output);
+}
+
+// ============================================================================
+// Closures left alone
+// ============================================================================
+
+#[test]
+fn test_callback_capturing_loop_variable_stays() {
+    let source = r#"
+        function sortRows(rows: number[][]): void
+            for i = 1, #rows do
+                table.sort(rows[i], (a: number, b: number) => a + i < b)
+            end
+        end
+    "#;
+
+    let output = compile(source, OptimizationLevel::O2).unwrap();
+    assert!(!output.contains("__closure"), "{}", output);
+}
+
+#[test]
+fn test_callback_capturing_body_local_stays() {
+    let source = r#"
+        function sortRows(rows: number[][]): void
+            for i = 1, #rows do
+                local bias = #rows[i]
+                table.sort(rows[i], (a: number, b: number) => a + bias < b)
+            end
+        end
+    "#;
+
+    let output = compile(source, OptimizationLevel::O2).unwrap();
+    assert!(!output.contains("__closure"), "{}", output);
+}
+
+#[test]
+fn test_closure_outside_loop_stays() {
+    let source = r#"
+        function sortRow(row: number[]): void
+            table.sort(row, (a: number, b: number) => a > b)
+        end
+    "#;
+
+    let output = compile(source, OptimizationLevel::O2).unwrap();
+    assert!(!output.contains("__closure"), "{}", output);
+}
+
+// ============================================================================
+// Gating
+// ============================================================================
+
+#[test]
+fn test_not_applied_at_o1() {
+    let output = compile(SORT_ROWS, OptimizationLevel::O1).unwrap();
+    assert!(!output.contains("__closure"), "{}", output);
+}
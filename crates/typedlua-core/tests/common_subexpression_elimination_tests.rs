@@ -0,0 +1,272 @@
+use typedlua_core::config::OptimizationLevel;
+use typedlua_core::di::DiContainer;
+
+fn compile(source: &str, level: OptimizationLevel) -> Result<String, String> {
+    let mut container = DiContainer::test_default();
+    container.compile_with_stdlib_and_optimization(source, level)
+}
+
+const PHYSICS_STEP: &str = r#"
+    interface Vec2 {
+        x: number
+        y: number
+    }
+
+    interface Body {
+        pos: Vec2
+        vel: Vec2
+    }
+
+    function step(b: Body, dt: number): void
+        b.pos.x = b.pos.x + b.vel.x * dt
+        b.pos.y = b.pos.y + b.vel.y * dt
+    end
+"#;
+
+// ============================================================================
+// Within a block
+// ============================================================================
+
+#[test]
+fn test_repeated_field_chains_are_cached() {
+    let output = compile(PHYSICS_STEP, OptimizationLevel::O2).unwrap();
+    assert!(output.contains("local __cse_0 = b.pos"), "{}", output);
+    assert!(output.contains("local __cse_1 = b.vel"), "{}", output);
+    assert!(
+        output.contains("__cse_0.x = __cse_0.x + __cse_1.x * dt"),
+        "{}",
+        output
+    );
+    assert!(
+        output.contains("__cse_0.y = __cse_0.y + __cse_1.y * dt"),
+        "{}",
+        output
+    );
+}
+
+#[test]
+fn test_pure_math_call_does_not_invalidate() {
+    let source = r#"
+        interface Vec2 {
+            x: number
+            y: number
+        }
+
+        function normalize(v: Vec2): number
+            const len = math.sqrt(v.x * v.x + v.y * v.y)
+            return v.x / len
+        end
+    "#;
+
+    let output = compile(source, OptimizationLevel::O2).unwrap();
+    assert!(output.contains("local __cse_0 = v.x"), "{}", output);
+    assert!(output.contains("return __cse_0 / len"), "{}", output);
+}
+
+#[test]
+fn test_field_write_invalidates() {
+    let source = r#"
+        function twice(t: { n: number }): number
+            const a = t.n * 2
+            t.n = 5
+            const b = t.n * 2
+            return a + b
+        end
+    "#;
+
+    let output = compile(source, OptimizationLevel::O2).unwrap();
+    assert!(!output.contains("__cse"), "{}", output);
+}
+
+#[test]
+fn test_call_invalidates() {
+    let source = r#"
+        function reset(t: { n: number }): void
+            t.n = 0
+        end
+
+        function twice(t: { n: number }): number
+            const a = t.n
+            reset(t)
+            return a + t.n
+        end
+    "#;
+
+    let output = compile(source, OptimizationLevel::O2).unwrap();
+    assert!(!output.contains("__cse"), "{}", output);
+}
+
+#[test]
+fn test_conditional_first_read_is_not_cached_early() {
+    let source = r#"
+        function pick(flag: boolean, t: { n: number }): number
+            const a = flag and t.n or 0
+            const b = t.n
+            return a + b
+        end
+    "#;
+
+    let output = compile(source, OptimizationLevel::O2).unwrap();
+    assert!(!output.contains("__cse"), "{}", output);
+}
+
+// ============================================================================
+// Loop-invariant hoisting
+// ============================================================================
+
+#[test]
+fn test_invariant_expression_is_hoisted_out_of_loop() {
+    let source = r#"
+        interface Particle {
+            x: number
+        }
+
+        class World {
+            config: { gravity: number }
+
+            constructor(config: { gravity: number }) {
+                self.config = config
+            }
+
+            update(particles: Particle[], dt: number): void {
+                for i = 1, 4 do
+                    const p = particles[i]
+                    p.x = p.x + self.config.gravity * dt
+                end
+            }
+        }
+    "#;
+
+    let output = compile(source, OptimizationLevel::O2).unwrap();
+    assert!(
+        output.contains("local __cse_0 = self.config.gravity * dt"),
+        "{}",
+        output
+    );
+    assert!(output.contains("p.x = p.x + __cse_0"), "{}", output);
+    let hoisted = output.find("local __cse_0").unwrap();
+    let loop_start = output.find("for i = 1").unwrap();
+    assert!(hoisted < loop_start, "{}", output);
+}
+
+#[test]
+fn test_call_in_loop_blocks_hoisting() {
+    let source = r#"
+        interface Particle {
+            x: number
+        }
+
+        function update(particles: Particle[], settings: { gravity: number }): void
+            for i = 1, #particles do
+                const p = particles[i]
+                p.x = p.x + settings.gravity
+                print(p.x)
+            end
+        end
+    "#;
+
+    let output = compile(source, OptimizationLevel::O2).unwrap();
+    assert!(!output.contains("__cse"), "{}", output);
+}
+
+#[test]
+fn test_written_field_is_not_hoisted() {
+    let source = r#"
+        function drain(state: { level: number }, steps: number): void
+            for i = 1, steps do
+                state.level = state.level - 1
+            end
+        end
+    "#;
+
+    let output = compile(source, OptimizationLevel::O2).unwrap();
+    assert!(!output.contains("__cse"), "{}", output);
+}
+
+#[test]
+fn test_field_read_is_not_hoisted_out_of_loop_that_may_not_run() {
+    let source = r#"
+        function scan(list: { value: number }[], scale: number): number
+            local total = 0
+            for i = 1, #list do
+                total = total + list[1].value + scale * 2
+            end
+            return total
+        end
+    "#;
+
+    // An empty `list` must not be indexed, but `scale * 2` cannot raise
+    let output = compile(source, OptimizationLevel::O2).unwrap();
+    assert!(!output.contains("= list[1]"), "{}", output);
+    assert!(output.contains("local __cse_0 = scale * 2"), "{}", output);
+}
+
+#[test]
+fn test_field_read_in_while_condition_is_hoisted() {
+    let source = r#"
+        function sum(buffer: { size: number }): number
+            local total = 0
+            local i = 1
+            while i <= buffer.size do
+                total = total + i
+                i = i + 1
+            end
+            return total
+        end
+    "#;
+
+    let output = compile(source, OptimizationLevel::O2).unwrap();
+    assert!(output.contains("local __cse_0 = buffer.size"), "{}", output);
+    assert!(output.contains("while i <= __cse_0 do"), "{}", output);
+}
+
+#[test]
+fn test_field_reads_are_not_cached_with_custom_metatables() {
+    let source = r#"
+        const fallback = {}
+        const proxy = setmetatable({}, { __index = fallback })
+
+        function twice(t: { n: number }): number
+            return t.n + t.n
+        end
+    "#;
+
+    let output = compile(source, OptimizationLevel::O2).unwrap();
+    assert!(!output.contains("__cse"), "{}", output);
+}
+
+// ============================================================================
+// Gating
+// ============================================================================
+
+#[test]
+fn test_not_applied_at_o1() {
+    let output = compile(PHYSICS_STEP, OptimizationLevel::O1).unwrap();
+    assert!(!output.contains("__cse"), "{}", output);
+}
+
+#[test]
+fn test_skipped_when_module_declares_operator_overloads() {
+    let source = r#"
+        class Money {
+            cents: number
+
+            constructor(cents: number) {
+                self.cents = cents
+            }
+
+            operator +(other: Money): Money {
+                return new Money(self.cents + other.cents)
+            }
+        }
+
+        function total(a: { price: Money }, b: Money): Money
+            const first = a.price + b
+            const second = a.price + b
+            return first + second
+        end
+    "#;
+
+    let output = compile(source, OptimizationLevel::O2).unwrap();
+    assert!(!output.contains("__cse"), "{}", output);
+}
@@ -0,0 +1,263 @@
+use typedlua_core::config::OptimizationLevel;
+use typedlua_core::di::DiContainer;
+
+fn compile(source: &str, level: OptimizationLevel) -> Result<String, String> {
+    let mut container = DiContainer::test_default();
+    container.compile_with_optimization(source, level)
+}
+
+// ============================================================================
+// Constant propagation (O1)
+// ============================================================================
+
+#[test]
+fn test_const_feeds_constant_folding() {
+    let source = r#"
+        const N = 4
+        local x = N * 2
+        print(x)
+    "#;
+
+    let output = compile(source, OptimizationLevel::O1).unwrap();
+    assert!(output.contains("local x = 8"), "{}", output);
+    assert!(output.contains("print(8)"), "{}", output);
+}
+
+#[test]
+fn test_constant_branch_is_removed() {
+    let source = r#"
+        const DEBUG = false
+        if DEBUG then
+            print("debugging")
+        end
+        print("done")
+    "#;
+
+    let output = compile(source, OptimizationLevel::O1).unwrap();
+    assert!(!output.contains("debugging"), "{}", output);
+    assert!(output.contains("print(\"done\")"), "{}", output);
+}
+
+#[test]
+fn test_constant_branch_takes_else() {
+    let source = r#"
+        const VERBOSE = nil
+        if VERBOSE then
+            print("verbose")
+        elseif true then
+            print("quiet")
+        else
+            print("unreachable")
+        end
+    "#;
+
+    let output = compile(source, OptimizationLevel::O1).unwrap();
+    assert!(!output.contains("verbose"), "{}", output);
+    assert!(!output.contains("unreachable"), "{}", output);
+    assert!(output.contains("print(\"quiet\")"), "{}", output);
+}
+
+#[test]
+fn test_module_const_propagates_into_functions() {
+    let source = r#"
+        const SCALE = 10
+        function scale(x: number): number
+            return x * SCALE
+        end
+    "#;
+
+    let output = compile(source, OptimizationLevel::O1).unwrap();
+    assert!(output.contains("return x * 10"), "{}", output);
+}
+
+#[test]
+fn test_inner_declaration_shadows() {
+    let source = r#"
+        const limit = 1
+        function check(): number
+            const limit = 2
+            return limit
+        end
+        print(limit)
+    "#;
+
+    let output = compile(source, OptimizationLevel::O1).unwrap();
+    assert!(output.contains("return 2"), "{}", output);
+    assert!(output.contains("print(1)"), "{}", output);
+}
+
+#[test]
+fn test_use_before_shadowing_declaration() {
+    let source = r#"
+        const size = 1
+        function show()
+            print(size)
+            const size = 2
+            print(size)
+        end
+    "#;
+
+    let output = compile(source, OptimizationLevel::O1).unwrap();
+    assert!(output.contains("print(1)"), "{}", output);
+    assert!(output.contains("print(2)"), "{}", output);
+}
+
+#[test]
+fn test_reassigned_local_is_not_propagated() {
+    let source = r#"
+        local count = 0
+        function bump()
+            count = count + 1
+        end
+        bump()
+        print(count)
+    "#;
+
+    let output = compile(source, OptimizationLevel::O1).unwrap();
+    assert!(output.contains("print(count)"), "{}", output);
+    assert!(output.contains("count = count + 1"), "{}", output);
+}
+
+#[test]
+fn test_parameter_with_same_name_is_not_propagated() {
+    let source = r#"
+        const width = 3
+        function area(width: number): number
+            return width * width
+        end
+        print(width)
+    "#;
+
+    let output = compile(source, OptimizationLevel::O1).unwrap();
+    assert!(output.contains("return width * width"), "{}", output);
+}
+
+#[test]
+fn test_loop_variable_with_same_name_is_not_propagated() {
+    let source = r#"
+        const i = 100
+        for i = 1, 3 do
+            print(i)
+        end
+    "#;
+
+    let output = compile(source, OptimizationLevel::O1).unwrap();
+    assert!(output.contains("print(i)"), "{}", output);
+}
+
+#[test]
+fn test_captured_constant_is_propagated() {
+    let source = r#"
+        const step = 5
+        const advance = (x: number) => x + step
+        print(advance(1))
+    "#;
+
+    let output = compile(source, OptimizationLevel::O1).unwrap();
+    assert!(output.contains("x + 5"), "{}", output);
+}
+
+#[test]
+fn test_repeat_until_sees_body_locals() {
+    let source = r#"
+        const done = false
+        repeat
+            const done = true
+        until done
+    "#;
+
+    let output = compile(source, OptimizationLevel::O1).unwrap();
+    assert!(output.contains("until true"), "{}", output);
+}
+
+#[test]
+fn test_not_applied_at_o0() {
+    let source = r#"
+        const N = 4
+        print(N)
+    "#;
+
+    let output = compile(source, OptimizationLevel::O0).unwrap();
+    assert!(output.contains("print(N)"), "{}", output);
+}
+
+// ============================================================================
+// Copy propagation and cleanup (O2)
+// ============================================================================
+
+#[test]
+fn test_propagated_const_is_removed_by_dead_store_elimination() {
+    let source = r#"
+        const DEBUG = false
+        if DEBUG then
+            print("debugging")
+        end
+    "#;
+
+    let output = compile(source, OptimizationLevel::O2).unwrap();
+    assert!(!output.contains("DEBUG"), "{}", output);
+    assert!(!output.contains("debugging"), "{}", output);
+}
+
+#[test]
+fn test_copy_propagation() {
+    let source = r#"
+        function first(source: { items: number[] }): number
+            const items = source.items
+            const list = items
+            return list[1]
+        end
+    "#;
+
+    let o1_output = compile(source, OptimizationLevel::O1).unwrap();
+    assert!(o1_output.contains("return list[1]"), "{}", o1_output);
+
+    let o2_output = compile(source, OptimizationLevel::O2).unwrap();
+    assert!(o2_output.contains("return items[1]"), "{}", o2_output);
+    assert!(!o2_output.contains("list"), "{}", o2_output);
+}
+
+#[test]
+fn test_copy_of_reassigned_local_is_not_propagated() {
+    let source = r#"
+        local current = 1
+        const saved = current
+        current = 2
+        print(saved)
+    "#;
+
+    let output = compile(source, OptimizationLevel::O2).unwrap();
+    assert!(output.contains("print(saved)"), "{}", output);
+}
+
+#[test]
+fn test_copy_is_not_propagated_past_shadowing() {
+    let source = r#"
+        function pick(source: { value: number }): number
+            const a = source.value
+            const b = a
+            function inner(): number
+                const a = 7
+                print(a)
+                return b
+            end
+            return inner()
+        end
+    "#;
+
+    let output = compile(source, OptimizationLevel::O2).unwrap();
+    assert!(output.contains("return b"), "{}", output);
+    assert!(output.contains("print(7)"), "{}", output);
+}
+
+#[test]
+fn test_exported_const_is_kept() {
+    let source = r#"
+        export const VERSION = 3
+        print(VERSION)
+    "#;
+
+    let output = compile(source, OptimizationLevel::O2).unwrap();
+    assert!(output.contains("VERSION = 3"), "{}", output);
+    assert!(output.contains("print(3)"), "{}", output);
+}
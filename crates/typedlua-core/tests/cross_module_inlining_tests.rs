@@ -0,0 +1,243 @@
+use bumpalo::Bump;
+use rustc_hash::FxHashMap;
+use std::sync::Arc;
+use typedlua_core::codegen::CodeGenerator;
+use typedlua_core::config::OptimizationLevel;
+use typedlua_core::diagnostics::CollectingDiagnosticHandler;
+use typedlua_core::optimizer::{Optimizer, WholeProgramAnalysis};
+use typedlua_core::MutableProgram;
+use typedlua_parser::ast::Program;
+use typedlua_parser::lexer::Lexer;
+use typedlua_parser::parser::Parser;
+use typedlua_parser::string_interner::{CommonIdentifiers, StringInterner};
+
+const MATH: &str = r#"
+    export const MAX = 10
+
+    export function clamp(x: number, lo: number, hi: number): number
+        return math.min(math.max(x, lo), hi)
+    end
+
+    export function square(x: number): number
+        return x * x
+    end
+
+    export function report(n: number): number
+        print(n)
+        return n
+    end
+"#;
+
+fn parse<'arena>(
+    source: &str,
+    interner: &StringInterner,
+    common: &CommonIdentifiers,
+    arena: &'arena Bump,
+) -> Program<'arena> {
+    let handler = Arc::new(CollectingDiagnosticHandler::new());
+    let mut lexer = Lexer::new(source, handler.clone(), interner);
+    let tokens = lexer.tokenize().expect("Lexing failed");
+    let mut parser = Parser::new(tokens, handler, interner, common, arena);
+    parser.parse().expect("Parsing failed")
+}
+
+/// Compiles `main` with `library` summarized under `library_id`; every module
+/// has its own interner, as in the CLI
+fn compile_with_ids(
+    library: &str,
+    library_id: &str,
+    main: &str,
+    imports: &[(&str, &str)],
+    level: OptimizationLevel,
+) -> String {
+    let arena = Bump::new();
+    let (library_interner, library_common) = StringInterner::new_with_common_identifiers();
+    let library = parse(library, &library_interner, &library_common, &arena);
+    let (interner, common) = StringInterner::new_with_common_identifiers();
+    let interner = Arc::new(interner);
+    let main = parse(main, &interner, &common, &arena);
+
+    let mut analysis = WholeProgramAnalysis::build(&[&library, &main], level);
+    analysis.summarize_exports(library_id, &library, &library_interner);
+
+    let handler = Arc::new(CollectingDiagnosticHandler::new());
+    let mut optimizer = Optimizer::new(level, handler, interner.clone());
+    optimizer.set_whole_program_analysis(analysis);
+    optimizer.set_module_imports(
+        imports
+            .iter()
+            .map(|(source, id)| (source.to_string(), id.to_string()))
+            .collect::<FxHashMap<_, _>>(),
+    );
+
+    let mut program = MutableProgram::from_program(&main);
+    optimizer.optimize(&mut program, &arena).unwrap();
+
+    let mut generator = CodeGenerator::new(interner.clone()).with_optimization_level(level);
+    generator.generate(&program)
+}
+
+fn compile(library: &str, main: &str, level: OptimizationLevel) -> String {
+    compile_with_ids(library, "./math", main, &[], level)
+}
+
+// ============================================================================
+// Inlined exports
+// ============================================================================
+
+#[test]
+fn test_imported_constant_is_inlined_and_import_dropped() {
+    let main = r#"
+        import { MAX } from "./math"
+
+        export function limit(n: number): boolean
+            return n > MAX
+        end
+    "#;
+
+    let output = compile(MATH, main, OptimizationLevel::O3);
+    assert!(output.contains("n > 10"), "{}", output);
+    assert!(!output.contains("require"), "{}", output);
+}
+
+#[test]
+fn test_imported_function_is_inlined() {
+    let main = r#"
+        import { clamp } from "./math"
+
+        export function unit(v: number): number
+            return clamp(v, 0, 1)
+        end
+    "#;
+
+    let output = compile(MATH, main, OptimizationLevel::O3);
+    assert!(output.contains("max(v, 0)"), "{}", output);
+    assert!(output.contains("min("), "{}", output);
+    assert!(!output.contains("clamp("), "{}", output);
+    assert!(!output.contains("require"), "{}", output);
+}
+
+#[test]
+fn test_pure_argument_read_once_is_substituted() {
+    let main = r#"
+        import { square } from "./math"
+
+        export function area(side: number): number
+            return square(side)
+        end
+    "#;
+
+    let output = compile(MATH, main, OptimizationLevel::O3);
+    assert!(output.contains("side * side"), "{}", output);
+}
+
+#[test]
+fn test_source_resolved_to_module_id() {
+    let main = r#"
+        import { MAX } from "../shared/math"
+
+        export function limit(n: number): boolean
+            return n > MAX
+        end
+    "#;
+
+    let output = compile_with_ids(
+        MATH,
+        "/project/shared/math.tl",
+        main,
+        &[("../shared/math", "/project/shared/math.tl")],
+        OptimizationLevel::O3,
+    );
+    assert!(output.contains("n > 10"), "{}", output);
+}
+
+// ============================================================================
+// Kept imports
+// ============================================================================
+
+#[test]
+fn test_import_keeps_exports_still_referenced() {
+    let main = r#"
+        import { clamp, report } from "./math"
+
+        export function unit(v: number): number
+            return report(clamp(v, 0, 1))
+        end
+    "#;
+
+    let output = compile(MATH, main, OptimizationLevel::O3);
+    assert!(output.contains("require(\"./math\")"), "{}", output);
+    assert!(output.contains("_mod.report"), "{}", output);
+    assert!(!output.contains("_mod.clamp"), "{}", output);
+}
+
+#[test]
+fn test_call_argument_read_twice_is_not_inlined() {
+    let main = r#"
+        import { square } from "./math"
+
+        export function area(next: () => number): number
+            return square(next())
+        end
+    "#;
+
+    let output = compile(MATH, main, OptimizationLevel::O3);
+    assert!(output.contains("square(next())"), "{}", output);
+    assert!(output.contains("_mod.square"), "{}", output);
+}
+
+#[test]
+fn test_module_with_side_effects_stays_required() {
+    let library = r#"
+        print("loading")
+
+        export const MAX = 10
+    "#;
+    let main = r#"
+        import { MAX } from "./math"
+
+        export function limit(n: number): boolean
+            return n > MAX
+        end
+    "#;
+
+    let output = compile(library, main, OptimizationLevel::O3);
+    assert!(output.contains("n > 10"), "{}", output);
+    assert!(output.contains("require(\"./math\")"), "{}", output);
+}
+
+#[test]
+fn test_rebound_import_name_is_not_inlined() {
+    let main = r#"
+        import { MAX } from "./math"
+
+        export function limit(n: number): boolean
+            for MAX = 1, 3 do
+                n = n + MAX
+            end
+            return n > 0
+        end
+    "#;
+
+    let output = compile(MATH, main, OptimizationLevel::O3);
+    assert!(output.contains("_mod.MAX"), "{}", output);
+}
+
+// ============================================================================
+// Gating
+// ============================================================================
+
+#[test]
+fn test_not_applied_at_o2() {
+    let main = r#"
+        import { clamp } from "./math"
+
+        export function unit(v: number): number
+            return clamp(v, 0, 1)
+        end
+    "#;
+
+    let output = compile(MATH, main, OptimizationLevel::O2);
+    assert!(output.contains("clamp(v, 0, 1)"), "{}", output);
+    assert!(output.contains("require(\"./math\")"), "{}", output);
+}
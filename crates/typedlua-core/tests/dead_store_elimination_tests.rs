@@ -119,3 +119,18 @@ fn test_dead_store_with_function_call() {
         "Function call with side effect should be preserved"
     );
 }
+
+#[test]
+fn test_dead_store_keeps_nested_call() {
+    let source = r#"
+        local x = { print("kept") }
+        return 42
+    "#;
+
+    let output = compile_with_o2_stdlib(source).unwrap();
+    assert!(
+        output.contains("print"),
+        "A call inside the initializer should be preserved: {}",
+        output
+    );
+}
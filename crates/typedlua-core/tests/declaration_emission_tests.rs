@@ -0,0 +1,228 @@
+use typedlua_core::di::{DiContainer, TypeCheckHelper};
+
+fn emit(source: &str) -> String {
+    let mut container = DiContainer::test_default();
+    container
+        .emit_declarations(source)
+        .unwrap_or_else(|e| panic!("declaration emission failed: {}", e))
+}
+
+/// The emitted `.d.tl` must itself parse and type-check
+fn assert_round_trips(declarations: &str) {
+    let container = DiContainer::test_default();
+    if let Err(e) = container.type_check_source(declarations) {
+        panic!("declarations do not type-check: {}\n{}", e, declarations);
+    }
+}
+
+#[test]
+fn test_exported_function() {
+    let source = r#"
+        export function add(a: number, b: number = 0, ...rest: number[]): number {
+            return a + b
+        }
+        function helper(): void {}
+    "#;
+    let output = emit(source);
+    assert!(
+        output.contains(
+            "export declare function add(a: number, b?: number, ...rest: number[]): number"
+        ),
+        "function: {}",
+        output
+    );
+    assert!(
+        !output.contains("helper"),
+        "private function leaked: {}",
+        output
+    );
+    assert!(
+        !output.contains("return"),
+        "implementation leaked: {}",
+        output
+    );
+    assert_round_trips(&output);
+}
+
+#[test]
+fn test_unannotated_exports_use_checked_types() {
+    let source = r#"
+        export function double(x: number) {
+            return x * 2
+        }
+        export const LIMIT = double(21)
+    "#;
+    let output = emit(source);
+    assert!(
+        output.contains("export declare function double(x: number): number"),
+        "inferred return type: {}",
+        output
+    );
+    assert!(
+        output.contains("export declare const LIMIT: number"),
+        "inferred const type: {}",
+        output
+    );
+    assert!(!output.contains("unknown"), "checked type dropped: {}", output);
+    assert_round_trips(&output);
+}
+
+#[test]
+fn test_exported_class_public_members_only() {
+    let source = r#"
+        export class Counter {
+            count: number = 0
+            private secret: string = ""
+            static instances: number = 0
+
+            increment(by: number): void {
+                self.count = self.count + by
+            }
+
+            protected reset(): void {
+                self.count = 0
+            }
+
+            static create(): Counter {
+                return new Counter()
+            }
+        }
+    "#;
+    let output = emit(source);
+    assert!(
+        output.contains("export declare class Counter {\n    count: number\n    static instances: number\n    increment(by: number): void\n    static create(): Counter\n}"),
+        "class: {}",
+        output
+    );
+    assert!(
+        !output.contains("secret"),
+        "private member leaked: {}",
+        output
+    );
+    assert!(
+        !output.contains("reset"),
+        "protected member leaked: {}",
+        output
+    );
+    assert_round_trips(&output);
+}
+
+#[test]
+fn test_exported_class_constructor() {
+    let source = r#"
+        export class Point {
+            x: number
+            y: number
+
+            constructor(x: number, y: number = 0) {
+                self.x = x
+                self.y = y
+            }
+        }
+
+        export class Point3 extends Point {
+            z: number = 0
+        }
+
+        export class Size(public readonly width: number, private height: number) {}
+    "#;
+    let output = emit(source);
+    assert!(
+        output.contains("export declare class Point {\n    constructor(x: number, y?: number)\n    x: number\n    y: number\n}"),
+        "constructor: {}",
+        output
+    );
+    assert!(
+        output.contains("export declare class Point3 extends Point {\n    z: number\n}"),
+        "subclass: {}",
+        output
+    );
+    assert!(
+        output.contains("export declare class Size {\n    constructor(width: number, height: number)\n    readonly width: number\n}"),
+        "primary constructor: {}",
+        output
+    );
+    assert!(!output.contains("//"), "not a Lua comment: {}", output);
+    assert_round_trips(&output);
+}
+
+#[test]
+fn test_exported_interface_alias_and_enum() {
+    let source = r#"
+        export interface Shape {
+            readonly name: string
+            area(scale: number): number
+        }
+        export type Id = string | number
+        export type Maybe<T> = T | nil
+        export enum Color { Red, Green, Blue }
+    "#;
+    let output = emit(source);
+    assert!(
+        output.contains("export declare interface Shape {\n    readonly name: string\n    area(scale: number): number\n}"),
+        "interface: {}",
+        output
+    );
+    assert!(
+        output.contains("export declare type Id = string | number"),
+        "alias: {}",
+        output
+    );
+    assert!(
+        output.contains("export declare type Maybe<T> = T | nil"),
+        "generic alias: {}",
+        output
+    );
+    assert!(
+        output.contains("export declare type Color = 0 | 1 | 2"),
+        "enum type: {}",
+        output
+    );
+    assert!(
+        output.contains("export declare namespace Color {\n    declare Red: 0\n    declare Green: 1\n    declare Blue: 2\n}"),
+        "enum values: {}",
+        output
+    );
+    assert_round_trips(&output);
+}
+
+#[test]
+fn test_named_export_list() {
+    let source = r#"
+        const VERSION: string = "1.0"
+        function greet(name: string): string {
+            return "hi " .. name
+        }
+        export { VERSION, greet }
+    "#;
+    let output = emit(source);
+    assert!(
+        output.contains("export declare const VERSION: string"),
+        "const: {}",
+        output
+    );
+    assert!(
+        output.contains("export declare function greet(name: string): string"),
+        "function: {}",
+        output
+    );
+    assert_round_trips(&output);
+}
+
+#[test]
+fn test_namespace_wraps_exports() {
+    let source = r#"
+        namespace Math.Vector;
+
+        export function length(x: number, y: number): number {
+            return x + y
+        }
+    "#;
+    let output = emit(source);
+    assert!(
+        output.contains("export declare namespace Math {\n    namespace Vector {\n        function length(x: number, y: number): number\n    }\n}"),
+        "namespace: {}",
+        output
+    );
+    assert_round_trips(&output);
+}
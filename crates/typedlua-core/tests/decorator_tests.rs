@@ -419,3 +419,158 @@ fn test_decorator_type_preservation() {
     let result = compile_and_check(source);
     assert!(result.is_ok(), "Decorator should preserve type information");
 }
+
+#[test]
+fn test_decorators_receive_context() {
+    let source = r#"
+        function logged(method, context)
+            return method
+        end
+
+        class MyClass {
+            @logged
+            private static helper(): void {}
+
+            @logged
+            public run(): void {}
+        }
+    "#;
+
+    let output = compile_and_check(source).unwrap();
+    assert!(
+        output.contains("local function __decorate(class, value, decorators, context)"),
+        "protocol helper embedded: {}",
+        output
+    );
+    assert!(
+        output.contains(
+            "MyClass.helper = __decorate(MyClass, MyClass.helper, { logged }, { kind = \"method\", name = \"helper\", static = true, private = true })"
+        ),
+        "static private method: {}",
+        output
+    );
+    assert!(
+        output.contains(
+            "MyClass.run = __decorate(MyClass, MyClass.run, { logged }, { kind = \"method\", name = \"run\", static = false, private = false })"
+        ),
+        "instance method: {}",
+        output
+    );
+}
+
+#[test]
+fn test_class_decorators_evaluate_in_order_and_apply_last() {
+    let source = r#"
+        function first(target) return target end
+        function tagged(name: string)
+            return function(target) return target end
+        end
+
+        @first
+        @tagged("x")
+        class MyClass {
+        }
+    "#;
+
+    let output = compile_and_check(source).unwrap();
+    assert!(
+        output.contains(
+            "MyClass = __decorate(MyClass, MyClass, { first, tagged(\"x\") }, { kind = \"class\", name = \"MyClass\" })"
+        ),
+        "class decorators: {}",
+        output
+    );
+    assert!(
+        output.contains("for i = #decorators, 1, -1 do"),
+        "applied bottom-up: {}",
+        output
+    );
+    assert!(
+        !output.contains("__initInstance(MyClass, self)"),
+        "no instance initializers without member decorators: {}",
+        output
+    );
+}
+
+#[test]
+fn test_field_and_accessor_decorators_run_instance_initializers() {
+    let source = r#"
+        function column(value, context)
+            return function(initial, instance) return initial end
+        end
+        function cached(getter, context) return getter end
+
+        class Model {
+            @column
+            public email: string
+
+            @cached
+            public get label(): string {
+                return self.email
+            }
+        }
+
+        class Admin extends Model {
+        }
+    "#;
+
+    let output = compile_and_check(source).unwrap();
+    assert!(
+        output.contains(
+            "__decorate(Model, nil, { column }, { kind = \"field\", name = \"email\", static = false, private = false })"
+        ),
+        "field decorator: {}",
+        output
+    );
+    assert!(
+        output.contains(
+            "Model.get_label = __decorate(Model, Model.get_label, { cached }, { kind = \"getter\", name = \"label\", static = false, private = false })"
+        ),
+        "getter decorator: {}",
+        output
+    );
+    assert!(
+        output.contains("__initInstance(Model, self)"),
+        "instance initializers in new: {}",
+        output
+    );
+    assert!(
+        output.contains("__initInstance(Admin, self)"),
+        "subclasses run inherited initializers: {}",
+        output
+    );
+}
+
+#[test]
+fn test_field_initializers_run_after_constructor_body() {
+    let source = r#"
+        function trim(value, context)
+            return function(initial, instance) return initial end
+        end
+
+        class Account {
+            @trim
+            public owner: string
+
+            constructor(owner: string) {
+                self.owner = owner
+            }
+        }
+    "#;
+
+    let output = compile_and_check(source).unwrap();
+    let init_instance = output
+        .find("__initInstance(Account, self)")
+        .expect("addInitializer callbacks in new");
+    let constructor = output
+        .find("Account._init(self, owner)")
+        .expect("constructor call");
+    let init_fields = output
+        .find("__initFields(Account, self)")
+        .expect("field initializers in new");
+    assert!(
+        init_instance < constructor && constructor < init_fields,
+        "addInitializer callbacks before the body, field initializers after: {}",
+        output
+    );
+}
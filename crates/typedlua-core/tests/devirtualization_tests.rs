@@ -1,5 +1,15 @@
+use bumpalo::Bump;
+use std::sync::Arc;
+use typedlua_core::codegen::CodeGenerator;
 use typedlua_core::config::OptimizationLevel;
 use typedlua_core::di::DiContainer;
+use typedlua_core::diagnostics::CollectingDiagnosticHandler;
+use typedlua_core::optimizer::{Optimizer, WholeProgramAnalysis};
+use typedlua_core::MutableProgram;
+use typedlua_parser::ast::Program;
+use typedlua_parser::lexer::Lexer;
+use typedlua_parser::parser::Parser;
+use typedlua_parser::string_interner::{CommonIdentifiers, StringInterner};
 
 fn compile_with_optimization_level(
     source: &str,
@@ -413,3 +423,272 @@ fn test_devirtualization_with_interface() {
     let output = compile_with_o3(source).unwrap();
     println!("Interface O3 output:\n{}", output);
 }
+
+const ANIMALS: &str = r#"
+    class Animal {
+        speak(): string {
+            return "..."
+        }
+
+        name(): string {
+            return "animal"
+        }
+    }
+
+    class Dog extends Animal {
+        speak(): string {
+            return "woof"
+        }
+    }
+
+    class Cat extends Animal {
+        speak(): string {
+            return "meow"
+        }
+    }
+"#;
+
+#[test]
+fn test_exact_receiver_calls_class_directly() {
+    let source = format!(
+        "{}\n{}",
+        ANIMALS,
+        r#"
+        const d = new Dog()
+        const c = new Cat()
+        const s = d:speak()
+        const n = c:name()
+    "#
+    );
+
+    let output = compile_with_o3(&source).unwrap();
+    assert!(output.contains("Dog.speak(d)"), "{}", output);
+    assert!(
+        output.contains("Animal.name(c)"),
+        "inherited method should resolve to its declaring class: {}",
+        output
+    );
+}
+
+#[test]
+fn test_single_instantiated_subclass_devirtualizes_base_receiver() {
+    let source = format!(
+        "{}\n{}",
+        ANIMALS,
+        r#"
+        function greet(a: Animal): string {
+            return a:speak()
+        }
+
+        const s = greet(new Dog())
+    "#
+    );
+
+    let output = compile_with_o3(&source).unwrap();
+    assert!(
+        output.contains("return Dog.speak(a)"),
+        "only Dog is ever created, so Animal receivers run Dog.speak: {}",
+        output
+    );
+}
+
+#[test]
+fn test_polymorphic_receiver_stays_virtual() {
+    let source = format!(
+        "{}\n{}",
+        ANIMALS,
+        r#"
+        function greet(a: Animal): string {
+            return a:speak()
+        }
+
+        const s = greet(new Dog()) .. greet(new Cat())
+    "#
+    );
+
+    let output = compile_with_o3(&source).unwrap();
+    assert!(output.contains("a:speak()"), "{}", output);
+}
+
+#[test]
+fn test_base_instances_block_single_subclass() {
+    let source = format!(
+        "{}\n{}",
+        ANIMALS,
+        r#"
+        function greet(a: Animal): string {
+            return a:speak()
+        }
+
+        const s = greet(new Dog()) .. greet(new Animal())
+    "#
+    );
+
+    let output = compile_with_o3(&source).unwrap();
+    assert!(output.contains("a:speak()"), "{}", output);
+}
+
+#[test]
+fn test_patched_subclass_stays_virtual() {
+    let source = format!(
+        "{}\n{}",
+        ANIMALS,
+        r#"
+        Dog.speak = (): string => "arf"
+
+        function greet(a: Animal): string {
+            return a:speak()
+        }
+
+        const s = greet(new Dog())
+    "#
+    );
+
+    let output = compile_with_o3(&source).unwrap();
+    assert!(output.contains("a:speak()"), "{}", output);
+}
+
+fn parse<'arena>(
+    source: &str,
+    interner: &StringInterner,
+    common: &CommonIdentifiers,
+    arena: &'arena Bump,
+) -> Program<'arena> {
+    let handler = Arc::new(CollectingDiagnosticHandler::new());
+    let mut lexer = Lexer::new(source, handler.clone(), interner);
+    let tokens = lexer.tokenize().expect("Lexing failed");
+    let mut parser = Parser::new(tokens, handler, interner, common, arena);
+    parser.parse().expect("Parsing failed")
+}
+
+#[test]
+fn test_cross_module_hierarchy_from_whole_program_analysis() {
+    let (interner, common) = StringInterner::new_with_common_identifiers();
+    let interner = Arc::new(interner);
+    let arena = Bump::new();
+
+    let animals = parse(
+        r#"
+        export class Animal {
+            speak(): string {
+                return "..."
+            }
+        }
+
+        export class Dog extends Animal {
+            speak(): string {
+                return "woof"
+            }
+        }
+    "#,
+        &interner,
+        &common,
+        &arena,
+    );
+    let main = parse(
+        r#"
+        import { Animal, Dog } from "./animals"
+
+        function greet(a: Animal): string {
+            return a:speak()
+        }
+
+        const s = greet(new Dog())
+    "#,
+        &interner,
+        &common,
+        &arena,
+    );
+
+    let analysis = WholeProgramAnalysis::build(&[&animals, &main], OptimizationLevel::O3);
+    let handler = Arc::new(CollectingDiagnosticHandler::new());
+    let mut optimizer = Optimizer::new(OptimizationLevel::O3, handler, interner.clone());
+    optimizer.set_whole_program_analysis(analysis);
+
+    let mut program = MutableProgram::from_program(&main);
+    optimizer.optimize(&mut program, &arena).unwrap();
+
+    let mut generator =
+        CodeGenerator::new(interner.clone()).with_optimization_level(OptimizationLevel::O3);
+    let output = generator.generate(&program);
+    assert!(
+        output.contains("return Dog.speak(a)"),
+        "the instantiation in main and the hierarchy in animals prove a single target: {}",
+        output
+    );
+}
+
+#[test]
+fn test_single_module_exported_class_stays_virtual() {
+    // Without whole-program analysis another module may extend the export
+    let source = r#"
+        export class Shape {
+            area(): number {
+                return 0
+            }
+        }
+
+        export function measure(s: Shape): number {
+            return s:area()
+        }
+    "#;
+
+    let output = compile_with_o3(source).unwrap();
+    assert!(output.contains("s:area()"), "{}", output);
+}
+
+#[test]
+fn test_calls_in_catch_finally_and_nested_bodies_are_rewritten() {
+    let source = format!(
+        "{}\n{}",
+        ANIMALS,
+        r#"
+        const d = new Dog()
+        try {
+            print(d:speak())
+        } catch (e) {
+            print(d:name())
+        } finally {
+            print(d:speak())
+        end
+        const greet = (n: number) => d:name()
+        for i = 1, 3 do
+            print(d:speak())
+        end
+    "#
+    );
+
+    let output = compile_with_o3(&source).unwrap();
+    assert_eq!(
+        output.matches("Dog.speak(d)").count(),
+        3,
+        "try, finally and loop bodies: {}",
+        output
+    );
+    assert_eq!(
+        output.matches("Animal.name(d)").count(),
+        2,
+        "catch clause and arrow body: {}",
+        output
+    );
+    assert!(!output.contains("d:speak()"), "{}", output);
+}
+
+#[test]
+fn test_catch_variable_shadows_outer_receiver() {
+    let source = format!(
+        "{}\n{}",
+        ANIMALS,
+        r#"
+        const d = new Dog()
+        try {
+            throw "error"
+        } catch (d) {
+            print(d:speak())
+        end
+    "#
+    );
+
+    let output = compile_with_o3(&source).unwrap();
+    assert!(output.contains("d:speak()"), "{}", output);
+}
@@ -0,0 +1,225 @@
+use bumpalo::Bump;
+use std::sync::Arc;
+use typedlua_core::config::OptimizationLevel;
+use typedlua_core::di::DiContainer;
+use typedlua_core::diagnostics::CollectingDiagnosticHandler;
+use typedlua_core::optimizer::Optimizer;
+use typedlua_core::MutableProgram;
+use typedlua_parser::ast::statement::{ForStatement, Statement};
+use typedlua_parser::lexer::Lexer;
+use typedlua_parser::parser::Parser;
+use typedlua_parser::string_interner::StringInterner;
+
+fn compile(source: &str, level: OptimizationLevel) -> Result<String, String> {
+    let mut container = DiContainer::test_default();
+    container.compile_with_stdlib_and_optimization(source, level)
+}
+
+const SUM: &str = r#"
+    function sum(xs: number[]): number
+        local total = 0
+        for i, x in ipairs(xs) do
+            total = total + x * i
+        end
+        return total
+    end
+"#;
+
+// ============================================================================
+// Rewritten loops
+// ============================================================================
+
+#[test]
+fn test_typed_array_parameter_becomes_numeric_loop() {
+    let output = compile(SUM, OptimizationLevel::O2).unwrap();
+    assert!(output.contains("for i = 1, #xs do"), "{}", output);
+    assert!(output.contains("local x = xs[i]"), "{}", output);
+    assert!(!output.contains("ipairs"), "{}", output);
+}
+
+#[test]
+fn test_loop_over_name_stops_at_first_nil() {
+    // `xs` may come from untyped code with holes, where `#xs` can be any
+    // border; `ipairs` stops at the first nil, and so must the rewrite
+    let output = compile(SUM, OptimizationLevel::O2).unwrap();
+    let check = output.find("if x == nil then").expect(&output);
+    assert!(check > output.find("local x = xs[i]").unwrap(), "{}", output);
+    assert!(check < output.find("total = total").unwrap(), "{}", output);
+}
+
+#[test]
+fn test_generic_array_annotation_and_ignored_value() {
+    let source = r#"
+        function count(names: Array<string>): number
+            local n = 0
+            for _ in ipairs(names) do
+                n = n + 1
+            end
+            return n
+        end
+    "#;
+
+    let output = compile(source, OptimizationLevel::O2).unwrap();
+    assert!(output.contains("for _ = 1, #names do"), "{}", output);
+    assert!(output.contains("if names[_] == nil then"), "{}", output);
+    assert!(!output.contains("ipairs"), "{}", output);
+}
+
+#[test]
+fn test_array_literal_is_bound_before_the_loop() {
+    let source = r#"
+        function total(): number
+            local sum = 0
+            for _, n in ipairs([10, 20, 30]) do
+                sum = sum + n
+            end
+            return sum
+        end
+    "#;
+
+    let output = compile(source, OptimizationLevel::O2).unwrap();
+    assert!(
+        output.contains("local __array_0 = {10, 20, 30}"),
+        "{}",
+        output
+    );
+    assert!(output.contains("for _ = 1, #__array_0 do"), "{}", output);
+    assert!(output.contains("local n = __array_0[_]"), "{}", output);
+    assert!(!output.contains("== nil"), "{}", output);
+}
+
+#[test]
+fn test_local_bound_to_array_literal() {
+    let source = r#"
+        const primes = [2, 3, 5, 7]
+
+        function product(): number
+            local result = 1
+            for _, p in ipairs(primes) do
+                result = result * p
+            end
+            return result
+        end
+    "#;
+
+    let output = compile(source, OptimizationLevel::O2).unwrap();
+    assert!(output.contains("for _ = 1, #primes do"), "{}", output);
+}
+
+// ============================================================================
+// Loops left alone
+// ============================================================================
+
+#[test]
+fn test_nullable_elements_are_not_rewritten() {
+    let source = r#"
+        function sum(xs: (number | nil)[]): number
+            local total = 0
+            for _, x in ipairs(xs) do
+                total = total + (x or 0)
+            end
+            return total
+        end
+    "#;
+
+    let output = compile(source, OptimizationLevel::O2).unwrap();
+    assert!(output.contains("ipairs(xs)"), "{}", output);
+}
+
+#[test]
+fn test_body_writing_the_array_is_not_rewritten() {
+    let source = r#"
+        function double(xs: number[]): void
+            for i, x in ipairs(xs) do
+                xs[i] = x * 2
+            end
+        end
+    "#;
+
+    let output = compile(source, OptimizationLevel::O2).unwrap();
+    assert!(output.contains("ipairs(xs)"), "{}", output);
+}
+
+#[test]
+fn test_array_passed_along_in_body_is_not_rewritten() {
+    let source = r#"
+        function visit(xs: number[], f: (xs: number[], x: number) => void): void
+            for _, x in ipairs(xs) do
+                f(xs, x)
+            end
+        end
+    "#;
+
+    let output = compile(source, OptimizationLevel::O2).unwrap();
+    assert!(output.contains("ipairs(xs)"), "{}", output);
+}
+
+#[test]
+fn test_indexed_write_elsewhere_disqualifies_the_name() {
+    let source = r#"
+        function sum(xs: number[]): number
+            xs[10] = 1
+            local total = 0
+            for _, x in ipairs(xs) do
+                total = total + x
+            end
+            return total
+        end
+    "#;
+
+    let output = compile(source, OptimizationLevel::O2).unwrap();
+    assert!(output.contains("ipairs(xs)"), "{}", output);
+}
+
+#[test]
+fn test_appends_keep_the_array_dense() {
+    let source = r#"
+        function fill(xs: number[]): number
+            xs[#xs + 1] = 4
+            local total = 0
+            for _, x in ipairs(xs) do
+                total = total + x
+            end
+            return total
+        end
+    "#;
+
+    let output = compile(source, OptimizationLevel::O2).unwrap();
+    assert!(output.contains("for _ = 1, #xs do"), "{}", output);
+}
+
+// ============================================================================
+// Gating
+// ============================================================================
+
+#[test]
+fn test_not_applied_at_o1() {
+    let output = compile(SUM, OptimizationLevel::O1).unwrap();
+    assert!(output.contains("ipairs(xs)"), "{}", output);
+}
+
+/// Without facts from the type checker, `xs: number[]` is not trusted
+#[test]
+fn test_untype_checked_program_keeps_ipairs_over_names() {
+    let arena = Bump::new();
+    let handler = Arc::new(CollectingDiagnosticHandler::new());
+    let (interner, common_ids) = StringInterner::new_with_common_identifiers();
+    let interner = Arc::new(interner);
+    let mut lexer = Lexer::new(SUM, handler.clone(), &interner);
+    let tokens = lexer.tokenize().unwrap();
+    let mut parser = Parser::new(tokens, handler.clone(), &interner, &common_ids, &arena);
+    let program = parser.parse().unwrap();
+
+    let mut mutable_program = MutableProgram::from_program(&program);
+    let mut optimizer = Optimizer::new(OptimizationLevel::O2, handler, interner.clone());
+    optimizer.optimize(&mut mutable_program, &arena).unwrap();
+
+    let Statement::Function(func) = &mutable_program.statements[0] else {
+        panic!("expected the function declaration");
+    };
+    assert!(func
+        .body
+        .statements
+        .iter()
+        .any(|stmt| matches!(stmt, Statement::For(ForStatement::Generic(_)))));
+}
@@ -0,0 +1,189 @@
+use bumpalo::Bump;
+use std::sync::Arc;
+use typedlua_core::config::OptimizationLevel;
+use typedlua_core::di::DiContainer;
+use typedlua_core::diagnostics::CollectingDiagnosticHandler;
+use typedlua_core::optimizer::{Optimizer, PASS_NAMES};
+use typedlua_core::project_config::{OptimizerOptions, ProjectConfig};
+use typedlua_core::MutableProgram;
+use typedlua_parser::lexer::Lexer;
+use typedlua_parser::parser::Parser;
+use typedlua_parser::string_interner::StringInterner;
+
+fn create_optimizer(level: OptimizationLevel, yaml: &str) -> Optimizer<'static> {
+    let handler = Arc::new(CollectingDiagnosticHandler::new());
+    let interner = Arc::new(StringInterner::new());
+    let options = ProjectConfig::from_yaml(yaml).unwrap().optimizer;
+    Optimizer::new(level, handler, interner).with_options(options)
+}
+
+fn compile_with_o1(source: &str) -> Result<String, String> {
+    let mut container = DiContainer::test_default();
+    container.compile_with_optimization(source, OptimizationLevel::O1)
+}
+
+// ============================================================================
+// optimizer: section
+// ============================================================================
+
+#[test]
+fn test_defaults_match_optimization_level() {
+    let configured = create_optimizer(OptimizationLevel::O3, "optimizer: {}\n");
+    assert_eq!(configured.pass_names().len(), PASS_NAMES.len());
+    for name in PASS_NAMES {
+        assert!(configured.pass_names().contains(name), "missing {}", name);
+    }
+}
+
+#[test]
+fn test_disable_pass_by_name() {
+    let optimizer = create_optimizer(
+        OptimizationLevel::O2,
+        "optimizer:\n  passes:\n    function-inlining: false\n    loop-optimization: false\n",
+    );
+    let names = optimizer.pass_names();
+    assert!(!names.contains(&"function-inlining"));
+    assert!(!names.contains(&"loop-optimization"));
+    assert!(names.contains(&"tail-call-optimization"));
+    assert_eq!(optimizer.pass_count(), names.len());
+}
+
+#[test]
+fn test_enable_pass_above_level() {
+    let optimizer = create_optimizer(
+        OptimizationLevel::O1,
+        "optimizer:\n  passes:\n    devirtualization: true\n",
+    );
+    let names = optimizer.pass_names();
+    assert!(names.contains(&"devirtualization"));
+    assert!(!names.contains(&"generic-specialization"));
+}
+
+#[test]
+fn test_max_iterations() {
+    let (interner, common) = StringInterner::new_with_common_identifiers();
+    let interner = Arc::new(interner);
+    let arena = Bump::new();
+    let handler = Arc::new(CollectingDiagnosticHandler::new());
+
+    let source = "const a = 1 + 2\nconst b = a * 3\n";
+    let mut lexer = Lexer::new(source, handler.clone(), &interner);
+    let tokens = lexer.tokenize().expect("Lexing failed");
+    let mut parser = Parser::new(tokens, handler.clone(), &interner, &common, &arena);
+    let program = parser.parse().expect("Parsing failed");
+
+    let options = OptimizerOptions {
+        max_iterations: 1,
+        ..Default::default()
+    };
+    let mut optimizer =
+        Optimizer::new(OptimizationLevel::O2, handler, interner.clone()).with_options(options);
+    let mut mutable = MutableProgram::from_program(&program);
+    optimizer.optimize(&mut mutable, &arena).unwrap();
+    let report = optimizer.take_report();
+    assert_eq!(report.iterations, 1);
+    assert!(report.timings.iter().all(|timing| timing.iteration == 1));
+}
+
+// ============================================================================
+// Source-level hints
+// ============================================================================
+
+#[test]
+fn test_unused_pure_call_is_removed() {
+    let source = r#"
+        class Units {
+            @pure
+            public static toMeters(feet: number): number
+                return feet * 0.3048
+            end
+
+            public static log(feet: number): number
+                print(feet)
+                return feet
+            end
+        }
+
+        function convert(feet: number): void
+            Units.toMeters(feet)
+            Units.log(feet)
+            print(Units.toMeters(3))
+        end
+    "#;
+
+    let output = compile_with_o1(source).unwrap();
+    assert!(!output.contains("Units.toMeters(feet)"), "{}", output);
+    assert!(output.contains("Units.log(feet)"), "{}", output);
+    assert!(output.contains("Units.toMeters(3)"), "{}", output);
+}
+
+#[test]
+fn test_pure_call_with_effectful_arguments_is_kept() {
+    let source = r#"
+        class Units {
+            @TypedLua.pure
+            public static toMeters(feet: number): number
+                return feet * 0.3048
+            end
+        }
+
+        function read(): number
+            print("reading")
+            return 1
+        end
+
+        Units.toMeters(read())
+    "#;
+
+    let output = compile_with_o1(source).unwrap();
+    assert!(output.contains("Units.toMeters(read())"), "{}", output);
+}
+
+#[test]
+fn test_user_decorator_named_like_a_hint_is_not_a_hint() {
+    let source = r#"
+        function pure(target, context)
+            return target
+        end
+
+        class Units {
+            @pure
+            public static toMeters(feet: number): number
+                return feet * 0.3048
+            end
+        }
+
+        function convert(feet: number): void
+            Units.toMeters(feet)
+        end
+    "#;
+
+    let output = compile_with_o1(source).unwrap();
+    assert!(output.contains("Units.toMeters(feet)"), "{}", output);
+}
+
+#[test]
+fn test_calls_in_cold_methods_are_not_inlined() {
+    let source = r#"
+        function double(x: number): number
+            return x * 2
+        end
+
+        class Report {
+            @cold
+            public static render(n: number): number
+                return double(n)
+            end
+
+            public static total(n: number): number
+                return double(n)
+            end
+        }
+    "#;
+
+    let mut container = DiContainer::test_default();
+    let output = container
+        .compile_with_optimization(source, OptimizationLevel::O2)
+        .unwrap();
+    assert_eq!(output.matches("double(n)").count(), 1, "{}", output);
+}
@@ -0,0 +1,164 @@
+use bumpalo::Bump;
+use std::sync::Arc;
+use typedlua_core::config::OptimizationLevel;
+use typedlua_core::diagnostics::CollectingDiagnosticHandler;
+use typedlua_core::optimizer::{OptimizationReport, Optimizer, RemarkKind};
+use typedlua_core::MutableProgram;
+use typedlua_parser::lexer::Lexer;
+use typedlua_parser::parser::Parser;
+use typedlua_parser::string_interner::StringInterner;
+
+const ANIMALS: &str = r#"
+    class Animal {
+        speak(): string {
+            return "..."
+        }
+    }
+
+    class Dog extends Animal {
+        speak(): string {
+            return "woof"
+        }
+    }
+
+    class Cat extends Animal {
+        speak(): string {
+            return "meow"
+        }
+    }
+
+    function greet(a: Animal): string {
+        return a:speak()
+    }
+
+    const d = new Dog()
+    const s = d:speak() .. greet(new Cat())
+"#;
+
+fn optimize(source: &str, level: OptimizationLevel, remarks: bool) -> OptimizationReport {
+    let (interner, common) = StringInterner::new_with_common_identifiers();
+    let interner = Arc::new(interner);
+    let arena = Bump::new();
+    let handler = Arc::new(CollectingDiagnosticHandler::new());
+
+    let mut lexer = Lexer::new(source, handler.clone(), &interner);
+    let tokens = lexer.tokenize().expect("Lexing failed");
+    let mut parser = Parser::new(tokens, handler.clone(), &interner, &common, &arena);
+    let program = parser.parse().expect("Parsing failed");
+
+    let mut optimizer = Optimizer::new(level, handler, interner.clone());
+    if remarks {
+        optimizer.enable_remarks();
+    }
+    let mut mutable = MutableProgram::from_program(&program);
+    optimizer.optimize(&mut mutable, &arena).unwrap();
+    optimizer.take_report()
+}
+
+#[test]
+fn test_devirtualization_remarks() {
+    let report = optimize(ANIMALS, OptimizationLevel::O3, true);
+    let devirt: Vec<_> = report
+        .remarks
+        .iter()
+        .filter(|remark| remark.pass == "devirtualization")
+        .collect();
+
+    let applied = devirt
+        .iter()
+        .find(|remark| remark.kind == RemarkKind::Applied)
+        .expect("exact receiver should be devirtualized");
+    assert_eq!(applied.message, "devirtualized `:speak()` to `Dog.speak`");
+
+    let missed = devirt
+        .iter()
+        .find(|remark| remark.kind == RemarkKind::Missed)
+        .expect("Animal receiver has two instantiated subclasses");
+    assert_eq!(missed.message, "not devirtualized `:speak()` on `Animal`");
+    assert!(missed.reason.is_some());
+}
+
+#[test]
+fn test_remarks_are_not_repeated_across_iterations() {
+    let report = optimize(ANIMALS, OptimizationLevel::O3, true);
+    let missed = report
+        .remarks
+        .iter()
+        .filter(|remark| remark.kind == RemarkKind::Missed && remark.pass == "devirtualization")
+        .count();
+    assert_eq!(missed, 1, "{:?}", report.remarks);
+}
+
+#[test]
+fn test_remarks_disabled_by_default() {
+    let report = optimize(ANIMALS, OptimizationLevel::O3, false);
+    assert!(report.remarks.is_empty());
+    assert!(report.iterations >= 1);
+    assert!(report
+        .timings
+        .iter()
+        .any(|timing| timing.pass == "devirtualization"));
+}
+
+#[test]
+fn test_report_text_names_module_and_pass() {
+    let report = optimize(ANIMALS, OptimizationLevel::O3, true);
+    let text = report.remarks_to_text("animals.tl");
+    assert!(
+        text.lines().any(|line| line.starts_with("animals.tl:")
+            && line.contains(": applied: devirtualized `:speak()` to `Dog.speak`")
+            && line.ends_with("[devirtualization]")),
+        "{}",
+        text
+    );
+}
+
+#[test]
+fn test_o0_reports_nothing() {
+    let report = optimize(ANIMALS, OptimizationLevel::O0, true);
+    assert!(report.remarks.is_empty());
+    assert!(report.timings.is_empty());
+    assert_eq!(report.iterations, 0);
+}
+
+#[test]
+fn test_cleanup_passes_report_remarks() {
+    let source = r#"
+        interface Vec2 {
+            x: number
+            y: number
+        }
+
+        function len2(v: Vec2): number
+            return v.x * v.x + v.y * v.y
+        end
+
+        const scale = 2
+        print(scale)
+
+        if false then
+            print("never")
+        end
+
+        while false do
+            print("never")
+        end
+    "#;
+    let report = optimize(source, OptimizationLevel::O2, true);
+    for pass in [
+        "dead-code-elimination",
+        "constant-propagation",
+        "common-subexpression-elimination",
+        "loop-optimization",
+    ] {
+        assert!(
+            report
+                .remarks
+                .iter()
+                .any(|remark| remark.pass == pass && remark.kind == RemarkKind::Applied),
+            "no remark from {}: {:?}",
+            pass,
+            report.remarks
+        );
+    }
+}
@@ -0,0 +1,103 @@
+use typedlua_core::config::OptimizationLevel;
+use typedlua_core::di::DiContainer;
+
+fn compile_with_o1(source: &str) -> Result<String, String> {
+    let mut container = DiContainer::test_default();
+    container.compile_with_optimization(source, OptimizationLevel::O1)
+}
+
+// ============================================================================
+// Composite passes reach every nested body
+// ============================================================================
+
+#[test]
+fn test_folds_constants_in_class_members() {
+    let source = r#"
+        class Circle {
+            public radius: number
+
+            constructor(r: number) {
+                self.radius = 2 * 5 * r
+            }
+
+            public get diameter(): number {
+                return 1 + 1 + self.radius
+            }
+
+            public area(): number {
+                const pi = 3 * 1
+                return pi * self.radius * self.radius
+            }
+        }
+    "#;
+
+    let output = compile_with_o1(source).unwrap();
+    assert!(output.contains("10 * r"), "{}", output);
+    assert!(output.contains("2 + self.radius"), "{}", output);
+    assert!(output.contains("local pi = 3"), "{}", output);
+}
+
+#[test]
+fn test_eliminates_dead_code_in_methods() {
+    let source = r#"
+        class Greeter {
+            public greet(): string {
+                return "hello"
+                print("unreachable in method")
+            }
+        }
+    "#;
+
+    let output = compile_with_o1(source).unwrap();
+    assert!(!output.contains("unreachable in method"), "{}", output);
+}
+
+#[test]
+fn test_folds_constants_in_arrow_bodies() {
+    let source = r#"
+        const offset = (x: number) => 2 * 3 + x
+        const handlers = {
+            run: (x: number) => 4 + 4 + x
+        }
+    "#;
+
+    let output = compile_with_o1(source).unwrap();
+    assert!(output.contains("6 + x"), "{}", output);
+    assert!(output.contains("8 + x"), "{}", output);
+}
+
+#[test]
+fn test_folds_constants_in_try_catch_finally() {
+    let source = r#"
+        function risky(): number
+            try {
+                const a = 2 * 3
+                print(a)
+            } catch (e) {
+                const b = 4 * 5
+                print(b)
+            } finally {
+                const c = 6 * 7
+                print(c)
+            end
+            return 0
+        end
+    "#;
+
+    let output = compile_with_o1(source).unwrap();
+    assert!(output.contains("local a = 6"), "{}", output);
+    assert!(output.contains("local b = 20"), "{}", output);
+    assert!(output.contains("local c = 42"), "{}", output);
+}
+
+#[test]
+fn test_folds_constants_in_exported_declarations() {
+    let source = r#"
+        export function area(w: number): number
+            return 10 - 4 + w
+        end
+    "#;
+
+    let output = compile_with_o1(source).unwrap();
+    assert!(output.contains("6 + w"), "{}", output);
+}
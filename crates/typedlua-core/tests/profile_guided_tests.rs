@@ -0,0 +1,353 @@
+use bumpalo::Bump;
+use std::sync::Arc;
+use typedlua_core::codegen::{CodeGenerator, Instrumentation};
+use typedlua_core::config::OptimizationLevel;
+use typedlua_core::diagnostics::CollectingDiagnosticHandler;
+use typedlua_core::optimizer::{counter_key, Counter, CounterKind, Optimizer, Profile};
+use typedlua_core::MutableProgram;
+use typedlua_parser::ast::Program;
+use typedlua_parser::lexer::Lexer;
+use typedlua_parser::parser::Parser;
+use typedlua_parser::string_interner::{CommonIdentifiers, StringInterner};
+
+const MODULE: &str = "src/main.tl";
+
+const DISPATCH: &str = r#"
+    export function handle(kind: string): number
+        if kind == "open" then
+            return 1
+        elseif kind == "data" then
+            return 2
+        elseif kind == "close" then
+            return 3
+        else
+            return 0
+        end
+    end
+"#;
+
+const MIX: &str = r#"
+    function mix(a: number, b: number): number
+        local x = a * 31
+        local y = x + b
+        local z = y % 1000
+        local w = z * z
+        local v = w + a
+        local u = v - b
+        return u
+    end
+
+    export function run(n: number): number
+        local total = 0
+        for i = 1, n do
+            local step = mix(i, total)
+            total = total + step
+        end
+        return total
+    end
+"#;
+
+fn parse<'arena>(
+    source: &str,
+    interner: &StringInterner,
+    common: &CommonIdentifiers,
+    arena: &'arena Bump,
+) -> Program<'arena> {
+    let handler = Arc::new(CollectingDiagnosticHandler::new());
+    let mut lexer = Lexer::new(source, handler.clone(), interner);
+    let tokens = lexer.tokenize().expect("Lexing failed");
+    let mut parser = Parser::new(tokens, handler, interner, common, arena);
+    parser.parse().expect("Parsing failed")
+}
+
+/// The counters an instrumented build of `source` records, in source order
+fn counters(source: &str) -> Vec<Counter> {
+    let arena = Bump::new();
+    let (interner, common) = StringInterner::new_with_common_identifiers();
+    let interner = Arc::new(interner);
+    let program = parse(source, &interner, &common, &arena);
+    let mut generator =
+        CodeGenerator::new(interner.clone()).with_instrumentation(Instrumentation::Pgo, MODULE);
+    let output = generator.generate(&MutableProgram::from_program(&program));
+
+    let entries: String = output
+        .split("__pgo[\"")
+        .skip(1)
+        .filter_map(|rest| rest.split_once("\"]"))
+        .map(|(key, _)| format!("{}\t0\n", key))
+        .collect();
+    Profile::parse(&entries)
+        .unwrap()
+        .module(MODULE)
+        .map(|module| module.counters().to_vec())
+        .unwrap_or_default()
+}
+
+/// A profile of `source` giving the functions, branches and loops these
+/// counts in source order; the rest ran zero times
+fn profile(source: &str, functions: &[u64], branches: &[u64], loops: &[u64]) -> String {
+    let mut next = [0usize; 3];
+    counters(source)
+        .iter()
+        .map(|counter| {
+            let (slot, counts) = match counter.kind {
+                CounterKind::Function => (0, functions),
+                CounterKind::Branch => (1, branches),
+                CounterKind::Loop => (2, loops),
+                CounterKind::Closure => return (counter, 0),
+            };
+            let count = counts.get(next[slot]).copied().unwrap_or(0);
+            next[slot] += 1;
+            (counter, count)
+        })
+        .map(|(counter, count)| {
+            let key = counter_key(
+                MODULE,
+                counter.kind,
+                counter.line,
+                counter.column,
+                &counter.label,
+            );
+            format!("{}\t{}\n", key, count)
+        })
+        .collect()
+}
+
+fn compile(source: &str, profile: Option<&str>, level: OptimizationLevel) -> String {
+    let arena = Bump::new();
+    let (interner, common) = StringInterner::new_with_common_identifiers();
+    let interner = Arc::new(interner);
+    let program = parse(source, &interner, &common, &arena);
+
+    let handler = Arc::new(CollectingDiagnosticHandler::new());
+    let mut optimizer = Optimizer::new(level, handler, interner.clone());
+    if let Some(profile) = profile {
+        let profile = Profile::parse(profile).unwrap();
+        optimizer.set_profile(profile.module(MODULE).expect("no counters for module"));
+    }
+
+    let mut program = MutableProgram::from_program(&program);
+    optimizer.optimize(&mut program, &arena).unwrap();
+
+    let mut generator = CodeGenerator::new(interner.clone()).with_optimization_level(level);
+    generator.generate(&program)
+}
+
+fn position(output: &str, needle: &str) -> usize {
+    output
+        .find(needle)
+        .unwrap_or_else(|| panic!("missing {}: {}", needle, output))
+}
+
+// ============================================================================
+// Instrumentation
+// ============================================================================
+
+#[test]
+fn test_instrumented_build_counts_functions_arms_and_loops() {
+    let kinds: Vec<_> = counters(MIX).iter().map(|counter| counter.kind).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            CounterKind::Function,
+            CounterKind::Function,
+            CounterKind::Loop
+        ]
+    );
+    let branches = counters(DISPATCH)
+        .iter()
+        .filter(|counter| counter.kind == CounterKind::Branch && counter.label == "handle")
+        .count();
+    assert_eq!(branches, 4);
+}
+
+// ============================================================================
+// Branch ordering
+// ============================================================================
+
+#[test]
+fn test_hot_if_arm_moves_first() {
+    let profile = profile(DISPATCH, &[1000], &[10, 200, 790, 0], &[]);
+    let output = compile(DISPATCH, Some(&profile), OptimizationLevel::O1);
+    assert!(
+        position(&output, "kind == \"close\"") < position(&output, "kind == \"data\""),
+        "{}",
+        output
+    );
+    assert!(
+        position(&output, "kind == \"data\"") < position(&output, "kind == \"open\""),
+        "{}",
+        output
+    );
+    assert!(
+        position(&output, "return 1") < position(&output, "return 0"),
+        "{}",
+        output
+    );
+}
+
+#[test]
+fn test_overlapping_conditions_keep_their_order() {
+    let source = r#"
+        export function size(n: number): string
+            if n > 100 then
+                return "large"
+            elseif n > 10 then
+                return "medium"
+            else
+                return "small"
+            end
+        end
+    "#;
+    let profile = profile(source, &[1000], &[1, 999, 0], &[]);
+    let output = compile(source, Some(&profile), OptimizationLevel::O1);
+    assert!(
+        position(&output, "n > 100") < position(&output, "n > 10 "),
+        "{}",
+        output
+    );
+}
+
+#[test]
+fn test_hot_match_arm_moves_first() {
+    let source = r#"
+        export function describe(code: number): string
+            return match code {
+                200 => "ok",
+                404 => "missing",
+                500 => "error",
+                _ => "other",
+            }
+        end
+    "#;
+    let profile = profile(source, &[1000], &[5, 900, 20, 75], &[]);
+    let output = compile(source, Some(&profile), OptimizationLevel::O1);
+    assert!(
+        position(&output, "== 404") < position(&output, "== 500"),
+        "{}",
+        output
+    );
+    assert!(
+        position(&output, "== 500") < position(&output, "== 200"),
+        "{}",
+        output
+    );
+    assert!(
+        position(&output, "\"other\"") > position(&output, "== 200"),
+        "{}",
+        output
+    );
+}
+
+#[test]
+fn test_profile_of_shifted_source_still_applies() {
+    let recorded = format!("\n\n\n{}", DISPATCH);
+    let profile = profile(&recorded, &[1000], &[10, 200, 790, 0], &[]);
+    let output = compile(DISPATCH, Some(&profile), OptimizationLevel::O1);
+    assert!(
+        position(&output, "kind == \"close\"") < position(&output, "kind == \"open\""),
+        "{}",
+        output
+    );
+}
+
+// ============================================================================
+// Hot call inlining
+// ============================================================================
+
+#[test]
+fn test_hot_call_inlined_past_threshold() {
+    let profile = profile(MIX, &[5000, 1], &[], &[5000]);
+    let output = compile(MIX, Some(&profile), OptimizationLevel::O1);
+    assert!(!output.contains("mix(i, total)"), "{}", output);
+    assert!(output.contains("local step = __u_"), "{}", output);
+    assert!(output.contains("* 31"), "{}", output);
+}
+
+#[test]
+fn test_cold_call_not_inlined() {
+    let profile = profile(MIX, &[1, 1], &[], &[1]);
+    let output = compile(MIX, Some(&profile), OptimizationLevel::O1);
+    assert!(output.contains("mix(i, total)"), "{}", output);
+}
+
+#[test]
+fn test_call_reading_rebound_name_not_inlined() {
+    let source = r#"
+        function scale(a: number): number
+            local s = a * factor
+            return s
+        end
+
+        export function run(n: number): number
+            local total = 0
+            for factor = 1, n do
+                total = total + 1
+                local step = scale(total)
+                total = step
+            end
+            return total
+        end
+    "#;
+    let profile = profile(source, &[5000, 1], &[], &[5000]);
+    let output = compile(source, Some(&profile), OptimizationLevel::O1);
+    assert!(output.contains("scale(total)"), "{}", output);
+}
+
+#[test]
+fn test_inliners_skip_call_in_arm_that_never_ran() {
+    let source = r#"
+        function double(x: number): number
+            return x * 2
+        end
+
+        export function run(n: number): number
+            local total = 0
+            if n > 1000 then
+                total = double(n)
+            end
+            return total
+        end
+    "#;
+    // The arm never ran, so the runtime wrote no counter for it
+    let recorded: String = profile(source, &[0, 5000], &[], &[])
+        .lines()
+        .filter(|line| line.contains(":function:run\t"))
+        .map(|line| format!("{}\n", line))
+        .collect();
+    let output = compile(source, Some(&recorded), OptimizationLevel::O3);
+    assert!(output.contains("double(n)"), "{}", output);
+
+    let profile = profile(source, &[5000, 5000], &[5000], &[]);
+    let output = compile(source, Some(&profile), OptimizationLevel::O3);
+    assert!(!output.contains("double(n)"), "{}", output);
+    assert!(output.contains("n * 2"), "{}", output);
+}
+
+// ============================================================================
+// Gating
+// ============================================================================
+
+#[test]
+fn test_nothing_changes_without_profile() {
+    let output = compile(MIX, None, OptimizationLevel::O1);
+    assert!(output.contains("mix(i, total)"), "{}", output);
+
+    let output = compile(DISPATCH, None, OptimizationLevel::O1);
+    assert!(
+        position(&output, "kind == \"open\"") < position(&output, "kind == \"close\""),
+        "{}",
+        output
+    );
+}
+
+#[test]
+fn test_not_applied_at_o0() {
+    let profile = profile(DISPATCH, &[1000], &[10, 200, 790, 0], &[]);
+    let output = compile(DISPATCH, Some(&profile), OptimizationLevel::O0);
+    assert!(
+        position(&output, "kind == \"open\"") < position(&output, "kind == \"close\""),
+        "{}",
+        output
+    );
+}
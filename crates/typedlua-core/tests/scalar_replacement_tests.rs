@@ -0,0 +1,146 @@
+use typedlua_core::config::OptimizationLevel;
+use typedlua_core::di::DiContainer;
+
+fn compile(source: &str, level: OptimizationLevel) -> Result<String, String> {
+    let mut container = DiContainer::test_default();
+    container.compile_with_stdlib_and_optimization(source, level)
+}
+
+const LENGTH_SQUARED: &str = r#"
+    function lengthSquared(a: number, b: number): number
+        local v = { x: a * 2, y: b * 2 }
+        return v.x * v.x + v.y * v.y
+    end
+"#;
+
+// ============================================================================
+// Replaced tables
+// ============================================================================
+
+#[test]
+fn test_local_table_becomes_locals() {
+    let output = compile(LENGTH_SQUARED, OptimizationLevel::O3).unwrap();
+    assert!(output.contains("local v_x = a * 2"), "{}", output);
+    assert!(output.contains("local v_y = b * 2"), "{}", output);
+    assert!(
+        output.contains("return v_x * v_x + v_y * v_y"),
+        "{}",
+        output
+    );
+    assert!(!output.contains("v.x"), "{}", output);
+}
+
+#[test]
+fn test_field_writes_become_local_assignments() {
+    let source = r#"
+        function advance(a: number, b: number): number
+            local p = { x: 0, y: 0 }
+            p.x = a
+            p.y = p.x + b
+            return p.y
+        end
+    "#;
+
+    let output = compile(source, OptimizationLevel::O3).unwrap();
+    assert!(!output.contains("local p ="), "{}", output);
+    assert!(output.contains("p_x = a"), "{}", output);
+}
+
+#[test]
+fn test_nested_block_uses_are_replaced() {
+    let source = r#"
+        function clamp(a: number, b: number): number
+            local range = { low: a, high: b }
+            if range.low > range.high then
+                range.low = range.high
+            end
+            return range.high - range.low
+        end
+    "#;
+
+    let output = compile(source, OptimizationLevel::O3).unwrap();
+    assert!(output.contains("range_low = range_high"), "{}", output);
+    assert!(!output.contains("range."), "{}", output);
+}
+
+// ============================================================================
+// Escaping tables
+// ============================================================================
+
+#[test]
+fn test_returned_table_is_kept() {
+    let source = r#"
+        function make(a: number): { x: number }
+            local p = { x: a }
+            p.x = p.x + 1
+            return p
+        end
+    "#;
+
+    let output = compile(source, OptimizationLevel::O3).unwrap();
+    assert!(output.contains("local p = {"), "{}", output);
+}
+
+#[test]
+fn test_table_passed_to_call_is_kept() {
+    let source = r#"
+        function show(a: number): number
+            local p = { x: a }
+            print(p)
+            return p.x
+        end
+    "#;
+
+    let output = compile(source, OptimizationLevel::O3).unwrap();
+    assert!(output.contains("local p = {"), "{}", output);
+}
+
+#[test]
+fn test_table_stored_in_field_is_kept() {
+    let source = r#"
+        function attach(owner: { pos: { x: number } }, a: number): number
+            local p = { x: a }
+            owner.pos = p
+            return p.x
+        end
+    "#;
+
+    let output = compile(source, OptimizationLevel::O3).unwrap();
+    assert!(output.contains("local p = {"), "{}", output);
+}
+
+#[test]
+fn test_table_captured_by_closure_is_kept() {
+    let source = r#"
+        function reader(a: number): () => number
+            local p = { x: a }
+            return () => p.x
+        end
+    "#;
+
+    let output = compile(source, OptimizationLevel::O3).unwrap();
+    assert!(output.contains("local p = {"), "{}", output);
+}
+
+#[test]
+fn test_existing_name_blocks_replacement() {
+    let source = r#"
+        function sum(a: number, v_x: number): number
+            local v = { x: a }
+            return v.x + v_x
+        end
+    "#;
+
+    let output = compile(source, OptimizationLevel::O3).unwrap();
+    assert!(output.contains("local v = {"), "{}", output);
+}
+
+// ============================================================================
+// Gating
+// ============================================================================
+
+#[test]
+fn test_not_applied_at_o2() {
+    let output = compile(LENGTH_SQUARED, OptimizationLevel::O2).unwrap();
+    assert!(output.contains("local v = {"), "{}", output);
+}
@@ -14,6 +14,7 @@ use std::sync::Arc;
 use typedlua_core::codegen::scope_hoisting::{EscapeAnalysis, HoistingContext};
 use typedlua_core::codegen::CodeGenerator;
 use typedlua_core::codegen::LuaTarget;
+use typedlua_core::config::OutputFormat;
 use typedlua_core::diagnostics::CollectingDiagnosticHandler;
 use typedlua_parser::ast::Program;
 use typedlua_parser::lexer::Lexer;
@@ -76,7 +77,9 @@ fn generate_bundle(sources: &[(&str, &str)], entry: &str, scope_hoisting_enabled
         Some(interner),
         None, // no tree shaking
         scope_hoisting_enabled,
-    );
+        OutputFormat::Readable,
+    )
+    .expect("bundle generation failed");
 
     output
 }
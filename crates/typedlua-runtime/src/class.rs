@@ -52,3 +52,104 @@ pub const BUILD_ALL_METHODS: &str = r#"function {}._buildAllMethods()
     return methods
 end
 "#;
+
+/// Debug-build access checks for `private`/`protected` instance members.
+/// No placeholders. `Class.__access` maps member names to their reflection
+/// flags (bit 1 private, bit 2 protected); functions defined by a class are
+/// registered as its code, and the first registered function on the call
+/// stack decides which class is accessing the member.
+pub const ACCESS_CHECKS: &str = r#"local __accessOwners = setmetatable({}, { __mode = "k" })
+
+local function __accessRegister(class)
+    for _, v in pairs(class) do
+        if type(v) == "function" then
+            __accessOwners[v] = class
+        end
+    end
+end
+
+local function __accessCaller()
+    local level = 4
+    while true do
+        local info = debug.getinfo(level, "f")
+        if info == nil then
+            return nil
+        end
+        local owner = __accessOwners[info.func]
+        if owner ~= nil then
+            return owner
+        end
+        level = level + 1
+    end
+end
+
+local function __accessCheck(class, key)
+    local declaring = class
+    while declaring do
+        local access = rawget(declaring, "__access")
+        local flags = access and access[key]
+        if flags then
+            local private = flags % 4 >= 2
+            if not private and flags % 8 < 4 then
+                return
+            end
+            local caller = __accessCaller()
+            if private then
+                if caller ~= declaring then
+                    error("Member '" .. tostring(key) .. "' is private to class '" .. declaring.__typeName .. "'", 3)
+                end
+                return
+            end
+            while caller do
+                if caller == declaring then
+                    return
+                end
+                caller = rawget(caller, "__parent")
+            end
+            error("Member '" .. tostring(key) .. "' is protected in class '" .. declaring.__typeName .. "'", 3)
+        end
+        declaring = rawget(declaring, "__parent")
+    end
+end
+
+local __accessMetamethods = {
+    "__tostring", "__eq", "__lt", "__le", "__add", "__sub", "__mul", "__div",
+    "__mod", "__pow", "__unm", "__concat", "__len", "__call", "__idiv",
+    "__band", "__bor", "__bxor", "__shl", "__shr", "__bnot", "__close",
+}
+
+local function __guardInstance(class, instance)
+    if debug == nil or debug.getinfo == nil then
+        return instance
+    end
+    local storage = {}
+    local mt = {
+        __index = function(self, key)
+            __accessCheck(class, key)
+            local value = storage[key]
+            if value ~= nil then
+                return value
+            end
+            local index = class.__index
+            if type(index) == "function" then
+                return index(self, key)
+            end
+            return class[key]
+        end,
+        __newindex = function(_, key, value)
+            __accessCheck(class, key)
+            storage[key] = value
+        end,
+        __pairs = function()
+            return next, storage, nil
+        end,
+        __metatable = class,
+    }
+    for _, name in ipairs(__accessMetamethods) do
+        if class[name] ~= nil and mt[name] == nil then
+            mt[name] = class[name]
+        end
+    end
+    return setmetatable(instance, mt)
+end
+"#;
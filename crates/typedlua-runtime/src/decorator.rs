@@ -139,6 +139,217 @@ function TypedLua._markDeprecated(target, customMessage)
     return target
 end
 
+-- ============================================================================
+-- @memoize Decorator
+-- ============================================================================
+
+local __unpack = table.unpack or unpack
+local __NIL_KEY = {}
+local __NAN_KEY = {}
+local __RESULT = {}
+local __weakKeys = { __mode = "k" }
+
+local function __pack(...)
+    return { n = select("#", ...), ... }
+end
+
+-- Node of a memoize cache for the given arguments. Each argument selects a
+-- child in its own weak-keyed table, so distinct values never share an entry
+-- and table arguments can still be collected.
+local function __cacheNode(root, keyFn, ...)
+    local node = root
+    local n = select("#", ...)
+    if keyFn then
+        n = 1
+    end
+    for i = 1, n do
+        local key
+        if keyFn then
+            key = keyFn(...)
+        else
+            key = select(i, ...)
+        end
+        if key == nil then
+            key = __NIL_KEY
+        elseif key ~= key then
+            key = __NAN_KEY
+        end
+        local child = node[key]
+        if child == nil then
+            child = setmetatable({}, __weakKeys)
+            node[key] = child
+        end
+        node = child
+    end
+    return node
+end
+
+-- Caches results by argument key. Usable as `@memoize` or
+-- `@memoize(keyFn)`; instance methods and getters keep one cache per
+-- instance in a weak-keyed table so caches go away with their instances
+function TypedLua.memoize(value, context)
+    if context == nil then
+        local keyFn = value
+        return function(target, ctx)
+            return TypedLua._memoize(target, ctx, keyFn)
+        end
+    end
+    return TypedLua._memoize(value, context, nil)
+end
+
+function TypedLua._memoize(fn, context, keyFn)
+    local perInstance = (context.kind == "method" or context.kind == "getter")
+        and not context.static
+    local caches = setmetatable({}, __weakKeys)
+    local shared = setmetatable({}, __weakKeys)
+
+    if perInstance then
+        return function(self, ...)
+            local cache = caches[self]
+            if cache == nil then
+                cache = setmetatable({}, __weakKeys)
+                caches[self] = cache
+            end
+            local node = __cacheNode(cache, keyFn, ...)
+            local result = node[__RESULT]
+            if result == nil then
+                result = __pack(fn(self, ...))
+                node[__RESULT] = result
+            end
+            return __unpack(result, 1, result.n)
+        end
+    end
+    return function(...)
+        local node = __cacheNode(shared, keyFn, ...)
+        local result = node[__RESULT]
+        if result == nil then
+            result = __pack(fn(...))
+            node[__RESULT] = result
+        end
+        return __unpack(result, 1, result.n)
+    end
+end
+
+-- ============================================================================
+-- @bind Decorator
+-- ============================================================================
+
+-- Gives each instance its own copy of the method with `self` bound, so
+-- `obj.method` can be passed as a callback
+function TypedLua.bind(value, context)
+    if context == nil or context.kind ~= "method" or context.static then
+        return value
+    end
+    local name = context.name
+    context.addInitializer(function(self)
+        local method = self[name]
+        self[name] = function(...)
+            return method(self, ...)
+        end
+    end)
+    return value
+end
+
+-- ============================================================================
+-- @frozen Decorator
+-- ============================================================================
+
+-- Read-only view of a table and everything reachable from it. The view
+-- reports the original metatable, so type checks keep working
+function TypedLua.deepFreeze(value, seen)
+    if type(value) ~= "table" then
+        return value
+    end
+    seen = seen or {}
+    if seen[value] then
+        return seen[value]
+    end
+    local proxy = {}
+    seen[value] = proxy
+
+    local frozen = {}
+    for k, v in pairs(value) do
+        frozen[k] = TypedLua.deepFreeze(v, seen)
+    end
+    local mt = getmetatable(value)
+    return setmetatable(proxy, {
+        __index = function(_, key)
+            local v = frozen[key]
+            if v ~= nil then
+                return v
+            end
+            return value[key]
+        end,
+        __newindex = function(_, key)
+            error("Cannot modify frozen object property '" .. tostring(key) .. "'", 2)
+        end,
+        __pairs = function()
+            return next, frozen, nil
+        end,
+        __len = function()
+            return #frozen
+        end,
+        __tostring = mt and mt.__tostring,
+        __metatable = mt,
+    })
+end
+
+-- Class decorator: instances are deeply frozen once constructed
+function TypedLua.frozen(value, context)
+    if type(value) ~= "table" or type(value.new) ~= "function" then
+        return TypedLua.deepFreeze(value)
+    end
+    local new = value.new
+    value.new = function(...)
+        return TypedLua.deepFreeze(new(...))
+    end
+    return value
+end
+
+-- ============================================================================
+-- @once Decorator
+-- ============================================================================
+
+-- Runs the function a single time and returns that result afterwards;
+-- instance methods run once per instance
+function TypedLua.once(value, context)
+    local perInstance = context ~= nil and context.kind == "method" and not context.static
+    local results = setmetatable({}, { __mode = "k" })
+    local shared = {}
+    return function(...)
+        local owner = perInstance and (...) or shared
+        local result = results[owner]
+        if result == nil then
+            result = __pack(value(...))
+            results[owner] = result
+        end
+        return __unpack(result, 1, result.n)
+    end
+end
+
+-- ============================================================================
+-- @trace Decorator
+-- ============================================================================
+
+-- Logs entry and exit to stderr. The compiler only keeps @trace in debug
+-- (O0) builds
+function TypedLua.trace(value, context)
+    if type(value) ~= "function" then
+        return value
+    end
+    local name = context and context.name or "function"
+    return function(...)
+        local args = {}
+        for i = 1, select("#", ...) do
+            args[i] = tostring((select(i, ...)))
+        end
+        io.stderr:write("-> " .. name .. "(" .. table.concat(args, ", ") .. ")\n")
+        local result = __pack(value(...))
+        io.stderr:write("<- " .. name .. "\n")
+        return __unpack(result, 1, result.n)
+    end
+end
+
 -- Export to global scope if not already defined (allows user overrides)
 if not readonly then
     readonly = TypedLua.readonly
@@ -152,5 +363,131 @@ if not deprecated then
     deprecated = TypedLua.deprecated
 end
 
+if not memoize then
+    memoize = TypedLua.memoize
+end
+
+if not bind then
+    bind = TypedLua.bind
+end
+
+if not frozen then
+    frozen = TypedLua.frozen
+end
+
+if not once then
+    once = TypedLua.once
+end
+
+if not trace then
+    trace = TypedLua.trace
+end
+
 return TypedLua
 "#;
+
+/// Decorator application protocol, embedded in every module that uses
+/// decorators. `__decorate` runs a member's or class's decorators bottom-up,
+/// passing each the current value and a context table
+/// (`kind`, `name`, `static`, `private`, `addInitializer`, `metadata`).
+/// A decorator may return a replacement value; field decorators return an
+/// initializer `function(initialValue, self)` instead. In `new`,
+/// `__initInstance` runs the `addInitializer` callbacks before the constructor
+/// body and `__initFields` runs the field initializers after it, so they see
+/// the value the constructor assigned. Both run parent classes first.
+pub const DECORATOR_PROTOCOL: &str = r#"local function __decoratorMetadata(class)
+    local metadata = rawget(class, "__metadata")
+    if metadata == nil then
+        -- Inherits the parent's metadata through the class's __index chain
+        metadata = setmetatable({}, { __index = class.__metadata })
+        rawset(class, "__metadata", metadata)
+    end
+    return metadata
+end
+
+local function __addInitializers(class, key, initializers)
+    if #initializers == 0 then
+        return
+    end
+    local list = rawget(class, key)
+    if list == nil then
+        list = {}
+        rawset(class, key, list)
+    end
+    for _, fn in ipairs(initializers) do
+        list[#list + 1] = fn
+    end
+end
+
+local function __decorate(class, value, decorators, context)
+    local initializers = {}
+    context.metadata = __decoratorMetadata(class)
+    context.addInitializer = function(fn)
+        initializers[#initializers + 1] = fn
+    end
+
+    local isField = context.kind == "field"
+    local fieldInits = {}
+    for i = #decorators, 1, -1 do
+        local result = decorators[i](value, context)
+        if isField then
+            if type(result) == "function" then
+                fieldInits[#fieldInits + 1] = result
+            end
+        elseif result ~= nil then
+            value = result
+        end
+    end
+    local fieldInit = nil
+    if #fieldInits > 0 then
+        local name = context.name
+        fieldInit = function(target)
+            local v = target[name]
+            for _, init in ipairs(fieldInits) do
+                v = init(v, target)
+            end
+            target[name] = v
+        end
+    end
+
+    if context.kind == "class" then
+        for _, fn in ipairs(initializers) do
+            fn(value)
+        end
+    elseif context.static then
+        if fieldInit then
+            fieldInit(class)
+        end
+        for _, fn in ipairs(initializers) do
+            fn(class)
+        end
+    else
+        __addInitializers(class, "__initializers", initializers)
+        if fieldInit then
+            __addInitializers(class, "__fieldInitializers", { fieldInit })
+        end
+    end
+    return value
+end
+
+local function __runInitializers(class, self, key)
+    local parent = rawget(class, "__parent")
+    if parent then
+        __runInitializers(parent, self, key)
+    end
+    local list = rawget(class, key)
+    if list then
+        for _, fn in ipairs(list) do
+            fn(self)
+        end
+    end
+end
+
+local function __initInstance(class, self)
+    __runInitializers(class, self, "__initializers")
+end
+
+local function __initFields(class, self)
+    __runInitializers(class, self, "__fieldInitializers")
+end
+"#;
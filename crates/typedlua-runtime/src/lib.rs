@@ -6,4 +6,7 @@ pub mod class;
 pub mod decorator;
 pub mod enum_rt;
 pub mod module;
+pub mod profile;
 pub mod reflection;
+pub mod table;
+pub mod validation;
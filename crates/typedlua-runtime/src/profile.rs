@@ -0,0 +1,39 @@
+//! Profile counters for TypedLua builds instrumented with `--instrument pgo`.
+
+/// Counter table shared by every instrumented module of a program, keyed by
+/// the counter keys of `typedlua_core::optimizer::counter_key`. The counts
+/// are written as `key<TAB>count` lines to `$TYPEDLUA_PROFILE` (default
+/// `typedlua.profile`) when the Lua state closes. Programs that end with
+/// `os.exit` without closing the state call `__typedlua_pgo_dump()` first.
+pub const PGO_RUNTIME: &str = r#"local __pgo = rawget(_G, "__typedlua_pgo")
+if __pgo == nil then
+    __pgo = {}
+    rawset(_G, "__typedlua_pgo", __pgo)
+
+    local function dump()
+        local file = io.open(os.getenv("TYPEDLUA_PROFILE") or "typedlua.profile", "w")
+        if file == nil then
+            return
+        end
+        local keys = {}
+        for key in pairs(__pgo) do
+            keys[#keys + 1] = key
+        end
+        table.sort(keys)
+        for _, key in ipairs(keys) do
+            file:write(key, "\t", string.format("%d", __pgo[key]), "\n")
+        end
+        file:close()
+    end
+    rawset(_G, "__typedlua_pgo_dump", dump)
+
+    local sentinel
+    if newproxy then
+        sentinel = newproxy(true)
+        getmetatable(sentinel).__gc = dump
+    else
+        sentinel = setmetatable({}, { __gc = dump })
+    end
+    rawset(_G, "__typedlua_pgo_sentinel", sentinel)
+end
+"#;
@@ -1,13 +1,19 @@
 //! Reflection runtime support for TypedLua.
 
-pub const TYPE_REGISTRY: &str = r#"__TypeRegistry = {}
-__TypeIdToClass = {}
+/// Registry tables are shared by every module in the program, so each module
+/// adds its types to them instead of replacing them.
+pub const TYPE_REGISTRY: &str = r#"__TypeRegistry = __TypeRegistry or {}
+__TypeIdToClass = __TypeIdToClass or {}
 "#;
 
+/// Side table for enum reflection metadata, weak-keyed by the enum table so
+/// `pairs()` over an enum only sees its members
+pub const ENUM_METADATA: &str = r#"__EnumMetadata = __EnumMetadata or setmetatable({}, { __mode = "k" })"#;
+
 pub const REFLECTION_MODULE: &str = r#"-- ============================================================
 -- Reflection Runtime Module
 -- ============================================================
-Reflect = {}
+Reflect = Reflect or {}
 
 -- O(1) instanceof check using ancestors table
 function Reflect.isInstance(obj, typeName)
@@ -15,11 +21,28 @@ function Reflect.isInstance(obj, typeName)
         return false
     end
     local typeId = __TypeRegistry[typeName]
-    if not typeId then return false end
-    return obj.__ancestors[typeId] == true
+    if typeId then
+        return obj.__ancestors[typeId] == true
+    end
+    -- Registry keys are qualified ("module::Class"); match a bare class name
+    -- against the names of the object's ancestors
+    for ancestorId in pairs(obj.__ancestors) do
+        local class = __TypeIdToClass[ancestorId]
+        if class and class.__typeName == typeName then
+            return true
+        end
+    end
+    return false
 end
 
 function Reflect.typeof(obj)
+    local enumMeta = type(obj) == "table" and __EnumMetadata and __EnumMetadata[obj]
+    if enumMeta then
+        return {
+            name = enumMeta.typeName,
+            kind = "enum"
+        }
+    end
     if type(obj) == "table" and obj.__typeName then
         return {
             id = obj.__typeId,
@@ -53,6 +76,17 @@ function Reflect.forName(name)
             return classConstructor
         end
     end
+    -- A bare class name resolves when exactly one registered class has it
+    local found = nil
+    for _, class in pairs(__TypeIdToClass) do
+        if class.__typeName == name then
+            if found then return nil end
+            found = class
+        end
+    end
+    if found then
+        return found
+    end
     -- Fallback to global lookup for dynamically created types
     _G = _G or getfenv(0)
     if _G[name] and _G[name].__typeName == name then
@@ -100,4 +134,73 @@ function Reflect.parseParams(paramStr)
     end
     return result
 end
+-- Interfaces implemented by a class and its ancestors (full reflection only).
+-- Each entry is { name = "Comparable", typeArgs = { "number" } }
+function Reflect.getInterfaces(obj)
+    local result = {}
+    if type(obj) ~= "table" then return result end
+    -- Instances carry the class as their metatable
+    local cls = rawget(obj, "__typeName") and obj or getmetatable(obj)
+    local seen = {}
+    while type(cls) == "table" do
+        for _, iface in ipairs(rawget(cls, "__interfaces") or {}) do
+            if not seen[iface.name] then
+                seen[iface.name] = true
+                result[#result + 1] = iface
+            end
+        end
+        cls = rawget(cls, "__parent")
+    end
+    return result
+end
+
+local function _findMember(list, name)
+    for _, entry in ipairs(list) do
+        if entry.name == name then
+            return entry
+        end
+    end
+    return nil
+end
+
+-- Decorators applied to a class, or to one of its fields or methods
+-- when memberName is given (full reflection only)
+function Reflect.getDecorators(obj, memberName)
+    if type(obj) ~= "table" then return {} end
+    if memberName == nil then
+        return obj.__decorators or {}
+    end
+    local member = _findMember(Reflect.getMethods(obj), memberName)
+        or _findMember(Reflect.getFields(obj), memberName)
+    return member and member.decorators or {}
+end
+
+-- Members of an enum in declaration order as { name = ..., value = ... }
+function Reflect.getEnumValues(enum)
+    local meta = type(enum) == "table" and __EnumMetadata and __EnumMetadata[enum]
+    if meta then
+        return meta.values
+    end
+    return {}
+end
+
+-- Full signature of a method: { typeParams, params = { { name, type } }, returnType }.
+-- Without full reflection only the compact type codes are known, so
+-- parameter names are nil
+function Reflect.getMethodSignature(obj, methodName)
+    local method = _findMember(Reflect.getMethods(obj), methodName)
+    if not method then return nil end
+    if method.signature then
+        return method.signature
+    end
+    local params = {}
+    for i, typeName in ipairs(Reflect.parseParams(method.params)) do
+        params[i] = { type = typeName }
+    end
+    return {
+        typeParams = {},
+        params = params,
+        returnType = _typeCodes[method.ret] or method.ret,
+    }
+end
 "#;
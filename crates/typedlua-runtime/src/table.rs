@@ -0,0 +1,28 @@
+//! Table allocation support for TypedLua.
+//! The optimizer sizes arrays filled by counted loops ahead of time.
+
+/// `__tableCreate(narr)`: an empty table with room for `narr` array
+/// entries. Uses `table.create` on Luau and `table.new` on LuaJIT, and `{}`
+/// on PUC Lua 5.1. The optimizer only calls it on the Lua 5.1 target.
+pub const TABLE_CREATE: &str = r#"local __tableCreate
+do
+    local create = table.create
+    if not create then
+        local ok, new = pcall(require, "table.new")
+        if ok and type(new) == "function" then
+            create = function(narr) return new(narr, 0) end
+        end
+    end
+    if create then
+        local floor = math.floor
+        __tableCreate = function(narr)
+            if narr >= 1 then
+                return create(floor(narr))
+            end
+            return {}
+        end
+    else
+        __tableCreate = function() return {} end
+    end
+end
+"#;
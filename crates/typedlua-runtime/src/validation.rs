@@ -0,0 +1,78 @@
+//! Runtime validation support for TypedLua.
+//! Validators themselves are generated per type; this is the shared glue.
+
+/// Raises for a failed validator result. Fail-fast validators report a
+/// message, collect-mode validators a list of `{ path, expected, got }`
+/// entries (with `constraint` for refinement failures).
+pub const ASSERT_VALID: &str = r#"local function __assertValid(ok, err)
+    if ok then return end
+    if type(err) == "table" then
+        local parts = {}
+        for i, e in ipairs(err) do
+            if e.constraint then
+                parts[i] = e.path .. ": " .. e.constraint .. " " .. tostring(e.expected) .. ", got " .. tostring(e.got)
+            else
+                parts[i] = e.path .. ": expected " .. tostring(e.expected) .. ", got " .. tostring(e.got)
+            end
+        end
+        err = table.concat(parts, "; ")
+    end
+    error("Validation failed: " .. err, 3)
+end
+"#;
+
+/// Class instance check: `val`'s metatable is a class whose ancestors include
+/// the class with `typeId`. Class tables themselves reach `__ancestors` only
+/// through `__index`, so they do not pass.
+pub const IS_INSTANCE: &str = r#"local function __isInstance(val, typeId)
+    local class = type(val) == "table" and getmetatable(val)
+    local ancestors = type(class) == "table" and rawget(class, "__ancestors")
+    return type(ancestors) == "table" and ancestors[typeId] == true
+end
+"#;
+
+/// Helpers for the `parse<T>(v)` and `safeParse<T>(v)` intrinsics, which
+/// pass the generated validator for `T`. Failures become a list of
+/// `{ path, message }` entries (collect-mode entries keep their other
+/// fields); `parse` raises a `__ValidationError` table carrying the first
+/// failing path, its message and the full list.
+pub const PARSE: &str = r#"local __ValidationError = {}
+__ValidationError.__index = __ValidationError
+__ValidationError.__tostring = function(e)
+    return "Validation failed: " .. e.message
+end
+
+local function __validationErrors(err)
+    if type(err) ~= "table" then
+        -- Fail-fast messages read "path: detail"
+        local path, detail = string.match(err, "^(.-): (.*)$")
+        return { { path = path or "", message = detail or err } }
+    end
+    for _, e in ipairs(err) do
+        if e.constraint then
+            e.message = e.constraint .. " " .. tostring(e.expected) .. ", got " .. tostring(e.got)
+        else
+            e.message = "expected " .. tostring(e.expected) .. ", got " .. tostring(e.got)
+        end
+    end
+    return err
+end
+
+local function __parse(validator, value, path)
+    local ok, err = validator(value, path)
+    if ok then return value end
+    local errors = __validationErrors(err)
+    local first = errors[1]
+    error(setmetatable({
+        path = first.path,
+        message = first.path .. ": " .. first.message,
+        errors = errors,
+    }, __ValidationError), 2)
+end
+
+local function __safeParse(validator, value, path)
+    local ok, err = validator(value, path)
+    if ok then return { ok = true, value = value } end
+    return { ok = false, errors = __validationErrors(err) }
+end
+"#;